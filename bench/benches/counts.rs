@@ -0,0 +1,70 @@
+//! Reports the number of kernel calls and allocations per call of the API of `wnf`
+//!
+//! Run with `cargo bench --bench counts` from the `bench` directory. The numbers are deterministic, so unlike the
+//! timings reported by the `throughput` benchmark they can be compared exactly between revisions.
+
+use std::io;
+
+use wnf::{DataAccessor, OwnedState, PerfCounters, SeenChangeStamp};
+use wnf_bench::CountingAllocator;
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator::new();
+
+const ITERATIONS: u64 = 1000;
+
+fn main() -> io::Result<()> {
+    let state = OwnedState::<u32>::create_temporary()?;
+    state.set(&42)?;
+
+    let small_slice_state = OwnedState::<[u8]>::create_temporary()?;
+    small_slice_state.set(&[0; 16])?;
+
+    let large_slice_state = OwnedState::<[u8]>::create_temporary()?;
+    large_slice_state.set(&[0; 4096])?;
+
+    println!("{:<40}{:>16}{:>16}", "operation", "kernel calls", "allocations");
+
+    report("get", || state.get().map(drop))?;
+    report("get_boxed (16 bytes)", || small_slice_state.get_boxed().map(drop))?;
+    report("get_boxed (4096 bytes)", || large_slice_state.get_boxed().map(drop))?;
+    report("query_slice_with_len_hint (4096 bytes)", || {
+        large_slice_state.query_slice_with_len_hint(4096).map(drop)
+    })?;
+    report("set", || state.set(&42))?;
+    report("set (4096 bytes)", || large_slice_state.set(&[0; 4096]))?;
+    report("apply", || state.apply(|value| value + 1).map(drop))?;
+    report("subscribe + unsubscribe", || {
+        state
+            .subscribe(|_: DataAccessor<'_, u32>| {}, SeenChangeStamp::Current)?
+            .unsubscribe()
+    })?;
+
+    Ok(())
+}
+
+/// Runs the given operation repeatedly and prints the average numbers of kernel calls and allocations per run
+fn report<F>(name: &str, mut op: F) -> io::Result<()>
+where
+    F: FnMut() -> io::Result<()>,
+{
+    let allocations_before = ALLOCATOR.allocations();
+
+    let (result, counters) = PerfCounters::measure(|| (0..ITERATIONS).try_for_each(|_| op()));
+    result?;
+
+    let allocations = ALLOCATOR.allocations() - allocations_before;
+
+    println!(
+        "{:<40}{:>16.2}{:>16.2}",
+        name,
+        counters.total() as f64 / ITERATIONS as f64,
+        allocations as f64 / ITERATIONS as f64
+    );
+
+    for (routine, count) in counters.iter().filter(|&(_, count)| count > 0) {
+        println!("  {:<38}{:>16.2}", routine.name(), count as f64 / ITERATIONS as f64);
+    }
+
+    Ok(())
+}
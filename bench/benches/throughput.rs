@@ -0,0 +1,86 @@
+//! Measures the throughput of querying, updating and subscribing to states
+//!
+//! Run with `cargo bench --bench throughput` from the `bench` directory.
+
+use std::hint::black_box;
+use std::sync::mpsc;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use wnf::{DataAccessor, OwnedState, SeenChangeStamp};
+
+const SLICE_SIZES: [usize; 3] = [16, 1024, 4096];
+
+fn get(c: &mut Criterion) {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    state.set(&42).unwrap();
+
+    c.bench_function("get", |b| b.iter(|| black_box(state.get().unwrap())));
+
+    let mut group = c.benchmark_group("get_boxed");
+
+    for size in SLICE_SIZES {
+        let state = OwnedState::<[u8]>::create_temporary().unwrap();
+        state.set(&vec![0; size][..]).unwrap();
+
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &state, |b, state| {
+            b.iter(|| black_box(state.get_boxed().unwrap()))
+        });
+    }
+
+    group.finish();
+}
+
+fn set(c: &mut Criterion) {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+
+    c.bench_function("set", |b| b.iter(|| state.set(black_box(&42)).unwrap()));
+
+    let mut group = c.benchmark_group("set_slice");
+
+    for size in SLICE_SIZES {
+        let state = OwnedState::<[u8]>::create_temporary().unwrap();
+        let data = vec![0; size];
+
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &data, |b, data| {
+            b.iter(|| state.set(black_box(data)).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+fn subscribe(c: &mut Criterion) {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+
+    c.bench_function("subscribe_unsubscribe", |b| {
+        b.iter(|| {
+            state
+                .subscribe(|_: DataAccessor<'_, u32>| {}, SeenChangeStamp::Current)
+                .unwrap()
+                .unsubscribe()
+                .unwrap()
+        })
+    });
+
+    let (tx, rx) = mpsc::channel();
+    let _subscription = state
+        .subscribe(
+            move |accessor: DataAccessor<'_, u32>| {
+                let _ = tx.send(accessor.get());
+            },
+            SeenChangeStamp::Current,
+        )
+        .unwrap();
+
+    c.bench_function("set_and_notify", |b| {
+        b.iter(|| {
+            state.set(&42).unwrap();
+            black_box(rx.recv().unwrap().unwrap())
+        })
+    });
+}
+
+criterion_group!(benches, get, set, subscribe);
+criterion_main!(benches);
@@ -0,0 +1,66 @@
+//! Utilities shared by the benchmarks of `wnf`
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A global allocator counting the allocations made through it
+///
+/// Reallocations are counted as allocations. Note that the count includes allocations made by all threads.
+#[derive(Debug)]
+pub struct CountingAllocator {
+    allocations: AtomicU64,
+}
+
+impl CountingAllocator {
+    /// Creates a new [`CountingAllocator`] with a count of zero
+    pub const fn new() -> Self {
+        Self {
+            allocations: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the number of allocations made so far
+    pub fn allocations(&self) -> u64 {
+        self.allocations.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for CountingAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// SAFETY:
+// All methods delegate to the `System` allocator
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.allocations.fetch_add(1, Ordering::Relaxed);
+
+        // SAFETY:
+        // The safety conditions are the same as those of `GlobalAlloc::alloc`
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        self.allocations.fetch_add(1, Ordering::Relaxed);
+
+        // SAFETY:
+        // The safety conditions are the same as those of `GlobalAlloc::alloc_zeroed`
+        unsafe { System.alloc_zeroed(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // SAFETY:
+        // The safety conditions are the same as those of `GlobalAlloc::dealloc`
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        self.allocations.fetch_add(1, Ordering::Relaxed);
+
+        // SAFETY:
+        // The safety conditions are the same as those of `GlobalAlloc::realloc`
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
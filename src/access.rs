@@ -0,0 +1,138 @@
+//! Probing the access of the current process to states
+
+use std::io;
+
+use windows::Win32::Foundation::STATUS_ACCESS_DENIED;
+
+use crate::data::ChangeStamp;
+#[cfg(feature = "subscribe")]
+use crate::data::OpaqueData;
+use crate::state::{BorrowedState, OwnedState, RawState};
+use crate::state_error::raw_ntstatus;
+#[cfg(feature = "subscribe")]
+use crate::subscribe::{DataAccessor, SeenChangeStamp};
+
+/// The operations the current process is allowed to perform on a state
+///
+/// This is returned by [`OwnedState::capabilities`] and [`BorrowedState::capabilities`]. It makes it possible for an
+/// application to adapt its behavior, e.g. by disabling parts of its user interface, when it is only partially
+/// permitted to access a state, rather than failing when first trying to use the state.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub struct StateCapabilities {
+    /// Whether the state data can be queried
+    pub can_read: bool,
+
+    /// Whether the state data can be updated
+    pub can_write: bool,
+
+    /// Whether listeners can be subscribed to the state
+    #[cfg(feature = "subscribe")]
+    pub can_subscribe: bool,
+}
+
+impl<T> OwnedState<T>
+where
+    T: ?Sized,
+{
+    /// Determines which operations the current process is allowed to perform on this state
+    ///
+    /// This probes every operation in a way that does not change the state data:
+    /// - Reading is probed by querying the change stamp of the state.
+    /// - Writing is probed by updating the state with data of size zero, expecting a change stamp that does not match
+    ///   the current change stamp, so the update is refused after the access check. If the state data cannot be read,
+    ///   the current change stamp is unknown and [`u32::MAX`] is expected, which only matches if the state has been
+    ///   updated exactly that many times.
+    /// - Subscribing (with the `subscribe` feature) is probed by subscribing a listener and immediately unsubscribing
+    ///   it again.
+    ///
+    /// An operation is considered not allowed if it fails because access is denied.
+    ///
+    /// Note that the result is only a snapshot, as the security descriptor of the state may change afterwards. Also
+    /// note that a subscription probe is visible to the publishers of the state through
+    /// [`subscribers_present`](OwnedState::subscribers_present) while it lasts.
+    ///
+    /// # Errors
+    /// Returns an error if probing any of the operations fails for a reason other than denied access, e.g. because the
+    /// state does not exist
+    pub fn capabilities(&self) -> io::Result<StateCapabilities> {
+        self.raw.capabilities()
+    }
+}
+
+impl<T> BorrowedState<'_, T>
+where
+    T: ?Sized,
+{
+    /// Determines which operations the current process is allowed to perform on this state
+    ///
+    /// See [`OwnedState::capabilities`]
+    pub fn capabilities(self) -> io::Result<StateCapabilities> {
+        self.raw.capabilities()
+    }
+}
+
+impl<T> RawState<T>
+where
+    T: ?Sized,
+{
+    /// Determines which operations the current process is allowed to perform on this state
+    fn capabilities(self) -> io::Result<StateCapabilities> {
+        let change_stamp = unless_access_denied(self.change_stamp())?;
+
+        // Change stamps only ever increase, so the change stamp preceding the one just queried cannot match anymore
+        let expected_change_stamp = change_stamp.map_or(ChangeStamp::new(u32::MAX), |change_stamp| change_stamp - 1);
+        let updated = unless_access_denied(self.cast::<[u8]>().update(&[], expected_change_stamp))?;
+
+        Ok(StateCapabilities {
+            can_read: change_stamp.is_some(),
+            can_write: updated.is_some(),
+
+            #[cfg(feature = "subscribe")]
+            can_subscribe: unless_access_denied(self.probe_subscribe())?.is_some(),
+        })
+    }
+
+    /// Subscribes a listener doing nothing to this state and immediately unsubscribes it again
+    #[cfg(feature = "subscribe")]
+    fn probe_subscribe(self) -> io::Result<()> {
+        self.cast::<OpaqueData>()
+            .subscribe(|_: DataAccessor<'_, OpaqueData>| {}, SeenChangeStamp::Current)?
+            .unsubscribe()
+    }
+}
+
+/// Turns the given result into [`None`] if it is an error because access is denied
+pub(crate) fn unless_access_denied<T>(result: io::Result<T>) -> io::Result<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(err) if raw_ntstatus(&err) == Some(STATUS_ACCESS_DENIED.0) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+
+    use super::*;
+
+    #[test]
+    fn unless_access_denied_ok() {
+        assert_eq!(unless_access_denied(Ok(42)).unwrap(), Some(42));
+    }
+
+    #[test]
+    fn unless_access_denied_access_denied() {
+        let result = unless_access_denied::<()>(Err(io::Error::from_raw_os_error(STATUS_ACCESS_DENIED.0)));
+
+        assert_eq!(result.unwrap(), None);
+    }
+
+    #[test]
+    fn unless_access_denied_other_error() {
+        let result = unless_access_denied::<()>(Err(io::Error::new(ErrorKind::Other, "test")));
+
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::Other);
+    }
+}
@@ -1,16 +1,18 @@
 //! Methods for applying a transformation to state data
 //!
-//! This module only adds inherent impls to [`OwnedState<T>`] and [`BorrowedState<'_, T>`](BorrowedState).
+//! Besides the [`SliceIndexError`], [`RetryPolicy`], [`Backoff`] and [`ContentionError`] types, this module adds
+//! inherent impls to [`OwnedState<T>`] and [`BorrowedState<'_, T>`](BorrowedState).
 
 #![deny(unsafe_code)]
 
 use std::borrow::Borrow;
 use std::convert::Infallible;
 use std::error::Error;
-use std::io;
 use std::io::ErrorKind;
+use std::time::Duration;
+use std::{io, thread};
 
-use crate::bytes::NoUninit;
+use crate::bytes::{CheckedBitPattern, NoUninit};
 use crate::read::Read;
 use crate::state::{BorrowedState, OwnedState, RawState};
 
@@ -130,6 +132,52 @@ where
     {
         self.raw.try_apply(transform)
     }
+
+    /// Applies a transformation to the data of this state, retrying according to the given [`RetryPolicy`]
+    ///
+    /// This is the same as [`apply`](OwnedState::apply), except that the loop retrying after concurrent updates is
+    /// controlled by the given [`RetryPolicy`]: It gives up after [`RetryPolicy::max_retries`] retries and waits
+    /// according to [`RetryPolicy::backoff`] before every retry. This keeps heavily contended states from making the
+    /// loop spin.
+    ///
+    /// Besides the value with which the state was ultimately updated, this returns the number of attempts that were
+    /// needed (i.e. the number of calls to the given closure), which is `1` if there was no contention. This makes it
+    /// possible for callers to observe contention.
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use std::time::Duration;
+    ///
+    /// use wnf::{Backoff, OwnedState, RetryPolicy};
+    ///
+    /// let state = OwnedState::create_temporary()?;
+    /// state.set(&42)?;
+    ///
+    /// let policy = RetryPolicy {
+    ///     max_retries: 10,
+    ///     backoff: Backoff::Exponential {
+    ///         initial: Duration::from_micros(100),
+    ///         max: Duration::from_millis(10),
+    ///     },
+    /// };
+    ///
+    /// let (new_data, attempts) = state.apply_with_policy(|value| value + 1, policy)?;
+    /// assert_eq!(new_data, 43);
+    /// assert_eq!(attempts, 1);
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an error if querying or updating fails. Also returns an error of kind [`ErrorKind::Other`] wrapping a
+    /// [`ContentionError`] if the state was updated concurrently on every attempt.
+    pub fn apply_with_policy<D, F>(&self, transform: F, policy: RetryPolicy) -> io::Result<(D, usize)>
+    where
+        D: Borrow<T>,
+        F: FnMut(T) -> D,
+    {
+        self.raw.apply_with_policy(transform, policy)
+    }
 }
 
 impl<T> OwnedState<T>
@@ -254,6 +302,20 @@ where
     {
         self.raw.try_apply_boxed(transform)
     }
+
+    /// Applies a transformation to the data of this state as a box, retrying according to the given [`RetryPolicy`]
+    ///
+    /// See [`apply_with_policy`](OwnedState::apply_with_policy) for details.
+    ///
+    /// # Errors
+    /// See [`apply_with_policy`](OwnedState::apply_with_policy)
+    pub fn apply_boxed_with_policy<D, F>(&self, transform: F, policy: RetryPolicy) -> io::Result<(D, usize)>
+    where
+        D: Borrow<T>,
+        F: FnMut(Box<T>) -> D,
+    {
+        self.raw.apply_boxed_with_policy(transform, policy)
+    }
 }
 
 impl<T> BorrowedState<'_, T>
@@ -282,6 +344,17 @@ where
     {
         self.raw.try_apply(transform)
     }
+
+    /// Applies a transformation to the data of this state, retrying according to the given [`RetryPolicy`]
+    ///
+    /// See [`OwnedState::apply_with_policy`]
+    pub fn apply_with_policy<D, F>(self, transform: F, policy: RetryPolicy) -> io::Result<(D, usize)>
+    where
+        D: Borrow<T>,
+        F: FnMut(T) -> D,
+    {
+        self.raw.apply_with_policy(transform, policy)
+    }
 }
 
 impl<T> BorrowedState<'_, T>
@@ -310,6 +383,64 @@ where
     {
         self.raw.try_apply_boxed(transform)
     }
+
+    /// Applies a transformation to the data of this state as a box, retrying according to the given [`RetryPolicy`]
+    ///
+    /// See [`OwnedState::apply_boxed_with_policy`]
+    pub fn apply_boxed_with_policy<D, F>(self, transform: F, policy: RetryPolicy) -> io::Result<(D, usize)>
+    where
+        D: Borrow<T>,
+        F: FnMut(Box<T>) -> D,
+    {
+        self.raw.apply_boxed_with_policy(transform, policy)
+    }
+}
+
+impl<T> OwnedState<[T]>
+where
+    T: CheckedBitPattern + NoUninit,
+{
+    /// Updates a single element of the slice data of this state
+    ///
+    /// This queries the slice data, replaces the element at the given index with the given value and then updates the
+    /// state data with the patched slice. It does so through the same loop as [`apply_boxed`](OwnedState::apply_boxed),
+    /// so that no concurrent update happens between querying and updating the state data. In particular, the index is
+    /// checked against the length of the slice data on every iteration of the loop, so the update fails rather than
+    /// writing out of bounds if the slice data shrink concurrently.
+    ///
+    /// The return value is the slice with which the state was ultimately updated.
+    ///
+    /// For example, to set the second element of a slice:
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use wnf::OwnedState;
+    ///
+    /// let state = OwnedState::<[u32]>::create_temporary()?;
+    /// state.set(&[1, 2, 3])?;
+    ///
+    /// let new_data = state.update_slice_element(1, &42)?;
+    /// assert_eq!(*new_data, [1, 42, 3]);
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an error if querying or updating fails, or if the index is out of bounds of the slice data. In the
+    /// latter case, [`io::Error::kind`] returns [`ErrorKind::InvalidInput`] and the error wraps a [`SliceIndexError`].
+    pub fn update_slice_element(&self, index: usize, value: &T) -> io::Result<Box<[T]>> {
+        self.raw.update_slice_element(index, value)
+    }
+}
+
+impl<T> BorrowedState<'_, [T]>
+where
+    T: CheckedBitPattern + NoUninit,
+{
+    /// Updates a single element of the slice data of this state
+    ///
+    /// See [`OwnedState::update_slice_element`]
+    pub fn update_slice_element(self, index: usize, value: &T) -> io::Result<Box<[T]>> {
+        self.raw.update_slice_element(index, value)
+    }
 }
 
 impl<T> RawState<T>
@@ -334,6 +465,15 @@ where
     {
         self.try_apply_as(transform)
     }
+
+    /// Applies a transformation to the data of this state, retrying according to the given [`RetryPolicy`]
+    fn apply_with_policy<D, F>(self, mut transform: F, policy: RetryPolicy) -> io::Result<(D, usize)>
+    where
+        D: Borrow<T>,
+        F: FnMut(T) -> D,
+    {
+        self.apply_as_with_policy(|data| Ok(transform(data)), policy)
+    }
 }
 
 impl<T> RawState<T>
@@ -358,6 +498,15 @@ where
     {
         self.try_apply_as(transform)
     }
+
+    /// Applies a transformation to the data of this state as a box, retrying according to the given [`RetryPolicy`]
+    fn apply_boxed_with_policy<D, F>(self, mut transform: F, policy: RetryPolicy) -> io::Result<(D, usize)>
+    where
+        D: Borrow<T>,
+        F: FnMut(Box<T>) -> D,
+    {
+        self.apply_as_with_policy(|data| Ok(transform(data)), policy)
+    }
 }
 
 impl<T> RawState<T>
@@ -388,14 +537,207 @@ where
         E: Into<Box<dyn Error + Send + Sync>>,
         F: FnMut(ReadInto) -> Result<WriteFrom, E>,
     {
-        let result = loop {
+        self.apply_as_with_io_error(|data| transform(data).map_err(|err| io::Error::new(ErrorKind::Other, err)))
+    }
+
+    /// Applies a transformation to the data of this state that can fail with an [`io::Error`], passing a value of type
+    /// `D` to it
+    ///
+    /// If `T: Sized`, then `D` can be either `T` or `Box<T>`.
+    /// If `T: !Sized`, then `D` must be `Box<T>`.
+    pub(crate) fn apply_as_with_io_error<ReadInto, WriteFrom, F>(self, transform: F) -> io::Result<WriteFrom>
+    where
+        WriteFrom: Borrow<T>,
+        T: Read<ReadInto> + NoUninit,
+        F: FnMut(ReadInto) -> io::Result<WriteFrom>,
+    {
+        let (result, _) = self.apply_as_with_policy(transform, RetryPolicy::default())?;
+        Ok(result)
+    }
+
+    /// Applies a transformation to the data of this state that can fail with an [`io::Error`], passing a value of type
+    /// `D` to it and retrying according to the given [`RetryPolicy`]
+    ///
+    /// If `T: Sized`, then `D` can be either `T` or `Box<T>`.
+    /// If `T: !Sized`, then `D` must be `Box<T>`.
+    ///
+    /// Returns the transformed value together with the number of attempts.
+    fn apply_as_with_policy<ReadInto, WriteFrom, F>(
+        self,
+        mut transform: F,
+        policy: RetryPolicy,
+    ) -> io::Result<(WriteFrom, usize)>
+    where
+        WriteFrom: Borrow<T>,
+        T: Read<ReadInto> + NoUninit,
+        F: FnMut(ReadInto) -> io::Result<WriteFrom>,
+    {
+        let mut retries = 0;
+
+        loop {
             let (data, change_stamp) = self.query_as()?.into_data_change_stamp();
-            let result = transform(data).map_err(|err| io::Error::new(ErrorKind::Other, err))?;
+            let result = transform(data)?;
             if self.update(result.borrow(), change_stamp)? {
-                break result;
+                return Ok((result, retries + 1));
+            }
+
+            if retries == policy.max_retries {
+                return Err(io::Error::new(
+                    ErrorKind::Other,
+                    ContentionError { attempts: retries + 1 },
+                ));
             }
+
+            retries += 1;
+            policy.backoff.wait(retries);
+        }
+    }
+}
+
+impl<T> RawState<[T]>
+where
+    T: CheckedBitPattern + NoUninit,
+{
+    /// Updates a single element of the slice data of this state
+    fn update_slice_element(self, index: usize, value: &T) -> io::Result<Box<[T]>> {
+        self.apply_as_with_io_error(|mut slice: Box<[T]>| {
+            let len = slice.len();
+
+            let element = slice
+                .get_mut(index)
+                .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, SliceIndexError { index, len }))?;
+
+            *element = *value;
+            Ok(slice)
+        })
+    }
+}
+
+/// An error updating an element of the slice data of a state at an index that is out of bounds
+///
+/// When updating an element through [`OwnedState::update_slice_element`] or [`BorrowedState::update_slice_element`]
+/// fails because of this, the returned [`io::Error`] has kind [`ErrorKind::InvalidInput`] and wraps a
+/// [`SliceIndexError`], which can be obtained via [`io::Error::get_ref`].
+#[derive(Clone, Copy, Debug, Eq, thiserror::Error, Hash, PartialEq)]
+#[error("failed to update slice element: index {index} is out of bounds for slice of length {len}")]
+pub struct SliceIndexError {
+    /// The index of the element to be updated
+    pub index: usize,
+
+    /// The length of the slice data of the state at the time of the update
+    pub len: usize,
+}
+
+/// A policy for retrying the application of a transformation to state data after concurrent updates
+///
+/// This is used by [`OwnedState::apply_with_policy`] and related methods. The default policy retries indefinitely
+/// without waiting, which is the behavior of [`OwnedState::apply`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct RetryPolicy {
+    /// The maximum number of retries after the first attempt
+    pub max_retries: usize,
+
+    /// How long to wait before every retry
+    pub backoff: Backoff,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: usize::MAX,
+            backoff: Backoff::None,
+        }
+    }
+}
+
+/// How long to wait before retrying the application of a transformation to state data, see [`RetryPolicy`]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Backoff {
+    /// Retry immediately
+    None,
+
+    /// Wait for the given duration before every retry
+    Fixed(Duration),
+
+    /// Wait for the `initial` duration before the first retry and double the duration before every further retry, up
+    /// to the `max` duration
+    Exponential {
+        /// The duration to wait before the first retry
+        initial: Duration,
+
+        /// The maximum duration to wait before a retry
+        max: Duration,
+    },
+}
+
+impl Backoff {
+    /// Returns the duration to wait before the given (one-based) retry
+    fn delay(self, retry: usize) -> Duration {
+        match self {
+            Self::None => Duration::ZERO,
+            Self::Fixed(delay) => delay,
+            Self::Exponential { initial, max } => {
+                let factor = u32::try_from(retry - 1)
+                    .ok()
+                    .and_then(|exponent| 2u32.checked_pow(exponent))
+                    .unwrap_or(u32::MAX);
+
+                initial.saturating_mul(factor).min(max)
+            }
+        }
+    }
+
+    /// Waits before the given (one-based) retry
+    pub(crate) fn wait(self, retry: usize) {
+        let delay = self.delay(retry);
+
+        if !delay.is_zero() {
+            thread::sleep(delay);
+        }
+    }
+}
+
+/// An error applying a transformation to state data because the state was updated concurrently on every attempt
+///
+/// When applying a transformation through [`OwnedState::apply_with_policy`] or related methods fails because of this,
+/// the returned [`io::Error`] has kind [`ErrorKind::Other`] and wraps a [`ContentionError`], which can be obtained via
+/// [`io::Error::get_ref`].
+#[derive(Clone, Copy, Debug, Eq, thiserror::Error, Hash, PartialEq)]
+#[error("failed to apply transformation: state was updated concurrently on all {attempts} attempts")]
+pub struct ContentionError {
+    /// The number of attempts that were made
+    pub attempts: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_none_delay() {
+        assert_eq!(Backoff::None.delay(1), Duration::ZERO);
+        assert_eq!(Backoff::None.delay(100), Duration::ZERO);
+    }
+
+    #[test]
+    fn backoff_fixed_delay() {
+        let backoff = Backoff::Fixed(Duration::from_millis(5));
+
+        assert_eq!(backoff.delay(1), Duration::from_millis(5));
+        assert_eq!(backoff.delay(100), Duration::from_millis(5));
+    }
+
+    #[test]
+    fn backoff_exponential_delay() {
+        let backoff = Backoff::Exponential {
+            initial: Duration::from_millis(1),
+            max: Duration::from_millis(10),
         };
 
-        Ok(result)
+        assert_eq!(backoff.delay(1), Duration::from_millis(1));
+        assert_eq!(backoff.delay(2), Duration::from_millis(2));
+        assert_eq!(backoff.delay(4), Duration::from_millis(8));
+        assert_eq!(backoff.delay(5), Duration::from_millis(10));
+        assert_eq!(backoff.delay(usize::MAX), Duration::from_millis(10));
     }
 }
@@ -0,0 +1,164 @@
+//! A command line tool for inspecting and updating states, built on the public API of the `wnf` crate
+//!
+//! This requires the `cli` feature to be enabled. Run `wnf-cli help` for usage information.
+
+use std::env;
+use std::error::Error;
+use std::fmt::Write as _;
+use std::io::{stdin, Read};
+use std::process::ExitCode;
+
+use wnf::{BorrowedState, DataAccessor, SeenChangeStamp, StateLifetime, StateName};
+
+const USAGE: &str = "\
+Usage: wnf-cli <COMMAND>
+
+Commands:
+  dump [<NAME>...]    Print diagnostic information and data of the given states, or of all registered states
+                      if no names are given
+  query <NAME>        Print the change stamp and data of the given state
+  set <NAME> <HEX>    Update the data of the given state with the given hex-encoded bytes
+  watch <NAME>        Print the data of the given state whenever it is updated, until ENTER is pressed
+  help                Print this help
+
+State names are given as hexadecimal opaque values, e.g. 0x0D83063EA3BE5075.";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    let result = match args.as_slice() {
+        ["dump"] => dump_registered(),
+        ["dump", names @ ..] => dump(names),
+        ["query", name] => query(name),
+        ["set", name, hex] => set(name, hex),
+        ["watch", name] => watch(name),
+        ["help" | "--help" | "-h"] => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        _ => {
+            eprintln!("{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn dump(names: &[&str]) -> Result<(), Box<dyn Error>> {
+    for (index, name) in names.iter().enumerate() {
+        if index > 0 {
+            println!();
+        }
+
+        dump_state(state(name)?)?;
+    }
+
+    Ok(())
+}
+
+fn dump_registered() -> Result<(), Box<dyn Error>> {
+    let mut state_names = Vec::new();
+
+    for lifetime in [
+        StateLifetime::WellKnown,
+        StateLifetime::Permanent,
+        StateLifetime::Persistent,
+    ] {
+        state_names.extend(wnf::registered_state_names(lifetime)?);
+    }
+
+    for (index, state_name) in state_names.into_iter().enumerate() {
+        if index > 0 {
+            println!();
+        }
+
+        // A single state failing to be inspected (e.g. due to missing permissions) must not abort the enumeration
+        if let Err(err) = dump_state(BorrowedState::from_state_name(state_name)) {
+            eprintln!("error: {state_name}: {err}");
+        }
+    }
+
+    Ok(())
+}
+
+fn dump_state(state: BorrowedState<'static, [u8]>) -> Result<(), Box<dyn Error>> {
+    let report = state.describe()?;
+    println!("{report}");
+
+    if report.exists {
+        println!("Data:                {}", encode_hex(&state.query_boxed()?.into_data()));
+    }
+
+    Ok(())
+}
+
+fn query(name: &str) -> Result<(), Box<dyn Error>> {
+    let (data, change_stamp) = state(name)?.query_boxed()?.into_data_change_stamp();
+    println!("{change_stamp}: {}", encode_hex(&data));
+    Ok(())
+}
+
+fn set(name: &str, hex: &str) -> Result<(), Box<dyn Error>> {
+    let data = decode_hex(hex)?;
+    state(name)?.set(&data[..])?;
+    Ok(())
+}
+
+fn watch(name: &str) -> Result<(), Box<dyn Error>> {
+    let state = state(name)?;
+
+    let _subscription = state.subscribe(
+        |accessor: DataAccessor<_>| match accessor.query_boxed() {
+            Ok(stamped_data) => {
+                let (data, change_stamp) = stamped_data.into_data_change_stamp();
+                println!("{change_stamp}: {}", encode_hex(&data));
+            }
+            Err(err) => eprintln!("error: {err}"),
+        },
+        SeenChangeStamp::None,
+    )?;
+
+    eprintln!("Watching {}, press ENTER to exit", state.state_name());
+    stdin().read_exact(&mut [0u8])?;
+
+    Ok(())
+}
+
+fn state(name: &str) -> Result<BorrowedState<'static, [u8]>, Box<dyn Error>> {
+    let state_name: StateName = name
+        .parse()
+        .map_err(|err| format!("invalid state name `{name}`: {err}"))?;
+
+    Ok(BorrowedState::from_state_name(state_name))
+}
+
+fn encode_hex(data: &[u8]) -> String {
+    data.iter()
+        .fold(String::with_capacity(2 * data.len()), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02X}");
+            hex
+        })
+}
+
+fn decode_hex(hex: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    if hex.len() % 2 != 0 || !hex.bytes().all(|digit| digit.is_ascii_hexdigit()) {
+        return Err(format!("invalid hex data `{hex}`").into());
+    }
+
+    Ok(hex
+        .as_bytes()
+        .chunks(2)
+        .map(|digits| {
+            // The digits are ASCII hex digits, so they form a valid UTF-8 string and a valid `u8` in base 16
+            u8::from_str_radix(std::str::from_utf8(digits).unwrap(), 16).unwrap()
+        })
+        .collect())
+}
@@ -0,0 +1,198 @@
+//! Broadcasting state updates to multiple receivers through a single subscription
+
+#![deny(unsafe_code)]
+
+use std::fmt::{self, Debug, Formatter};
+use std::io;
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
+use crate::data::StampedData;
+use crate::read::Read;
+use crate::state::AsState;
+use crate::subscribe::{DataAccessor, SeenChangeStamp, StateListener, Subscription};
+
+/// An adapter broadcasting the updates of a state to any number of receivers through a single subscription
+///
+/// When many components in one process observe the same state, subscribing each of them separately creates as many
+/// subscriptions in the operating system. A [`BroadcastAdapter<'a, D>`](BroadcastAdapter) instead subscribes to the
+/// state once and fans the updates out to receivers created via [`BroadcastAdapter::receiver`].
+///
+/// The receivers are [`tokio::sync::watch::Receiver`](https://docs.rs/tokio/1/tokio/sync/watch/struct.Receiver.html)s
+/// holding the latest data of the state together with its change stamp, or [`None`] if no data have been written to
+/// the state yet. They can be used both in async code, e.g. by awaiting
+/// [`changed`](https://docs.rs/tokio/1/tokio/sync/watch/struct.Receiver.html#method.changed), and in non-async code,
+/// e.g. by calling [`borrow`](https://docs.rs/tokio/1/tokio/sync/watch/struct.Receiver.html#method.borrow). Like all
+/// watch channels, they only keep the latest data, so a receiver that is not checked often enough misses intermediate
+/// updates. This does not require a tokio runtime.
+///
+/// The type parameter `D` is the type the state data are read as, which is either `T` or `Box<T>` for a state with data
+/// type `T`. Updates whose data cannot be read as `D` are skipped, so the receivers keep holding the previous data.
+///
+/// Note that the adapter is automatically unsubscribed from the state when it is dropped. In this case, errors while
+/// unsubscribing are silently ignored. If you want to handle them explicitly, use the
+/// [`BroadcastAdapter::unsubscribe`] method. After unsubscribing, the receivers still hold the latest data, but are
+/// not notified anymore.
+///
+/// # Example
+/// ```
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use wnf::{BroadcastAdapter, OwnedState};
+///
+/// let state = OwnedState::<u32>::create_temporary()?;
+/// state.set(&0)?;
+///
+/// let adapter = BroadcastAdapter::new(&state)?;
+///
+/// let mut receiver1 = adapter.receiver();
+/// let mut receiver2 = adapter.receiver();
+///
+/// state.set(&1)?;
+///
+/// for receiver in [&mut receiver1, &mut receiver2] {
+///     receiver
+///         .wait_for(|data| data.as_ref().is_some_and(|data| *data.data() == 1))
+///         .await?;
+/// }
+/// # Ok(()) }
+/// ```
+pub struct BroadcastAdapter<'a, D> {
+    sender: Arc<watch::Sender<Option<StampedData<D>>>>,
+    subscription: Subscription<'a, BroadcastListener<D>>,
+}
+
+impl<'a, T> BroadcastAdapter<'a, T>
+where
+    T: Read<T> + Send + Sync,
+{
+    /// Creates a new [`BroadcastAdapter<'a, T>`](BroadcastAdapter) subscribed to the given state, reading the state
+    /// data as an owned value
+    ///
+    /// This produces an owned `T` and hence requires `T: Sized`. In order to produce a `Box<T>` for `T: ?Sized`, use
+    /// the [`new_boxed`](BroadcastAdapter::new_boxed) method.
+    ///
+    /// # Errors
+    /// Returns an error if subscribing fails
+    pub fn new<S>(state: &'a S) -> io::Result<Self>
+    where
+        S: AsState<Data = T>,
+    {
+        Self::subscribe(state)
+    }
+}
+
+impl<'a, T> BroadcastAdapter<'a, Box<T>>
+where
+    T: Read<Box<T>> + Send + Sync + ?Sized,
+{
+    /// Creates a new [`BroadcastAdapter<'a, Box<T>>`](BroadcastAdapter) subscribed to the given state, reading the
+    /// state data as a box
+    ///
+    /// This is the same as [`new`](BroadcastAdapter::new), except that it produces a [`Box<T>`] instead of an owned
+    /// `T` (requiring `T: Sized`).
+    ///
+    /// # Errors
+    /// Returns an error if subscribing fails
+    pub fn new_boxed<S>(state: &'a S) -> io::Result<Self>
+    where
+        S: AsState<Data = T>,
+    {
+        Self::subscribe(state)
+    }
+}
+
+impl<'a, D> BroadcastAdapter<'a, D>
+where
+    D: Send + Sync + 'static,
+{
+    /// Creates a new [`BroadcastAdapter<'a, D>`](BroadcastAdapter) subscribed to the given state
+    fn subscribe<S>(state: &'a S) -> io::Result<Self>
+    where
+        S: AsState,
+        S::Data: Read<D>,
+    {
+        let (sender, _) = watch::channel(None);
+        let sender = Arc::new(sender);
+
+        let listener = BroadcastListener {
+            sender: Arc::clone(&sender),
+        };
+
+        // Subscribing with `SeenChangeStamp::None` makes the receivers hold the current data
+        let subscription = state.as_state().subscribe(listener, SeenChangeStamp::None)?;
+
+        Ok(Self { sender, subscription })
+    }
+}
+
+impl<D> BroadcastAdapter<'_, D> {
+    /// Creates a new receiver of the updates of the state
+    ///
+    /// The receiver initially holds the latest data of the state received by this adapter, which are considered seen.
+    pub fn receiver(&self) -> watch::Receiver<Option<StampedData<D>>> {
+        self.sender.subscribe()
+    }
+
+    /// Returns the number of receivers that currently exist
+    pub fn receiver_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+
+    /// Unsubscribes this adapter from the state
+    ///
+    /// This happens automatically when the [`BroadcastAdapter<'_, D>`](BroadcastAdapter) is dropped, so there is
+    /// usually no need to call this method. Its only purpose is to enable you to handle errors while unsubscribing.
+    ///
+    /// # Errors
+    /// Returns an error if unsubscribing fails
+    pub fn unsubscribe(self) -> io::Result<()> {
+        self.subscription.unsubscribe()
+    }
+}
+
+// We cannot derive this because that would impose an unnecessary trait bound `D: Debug`
+impl<D> Debug for BroadcastAdapter<'_, D> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BroadcastAdapter")
+            .field("receiver_count", &self.receiver_count())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The state listener of a [`BroadcastAdapter<'_, D>`](BroadcastAdapter), sending the state data to its receivers
+struct BroadcastListener<D> {
+    sender: Arc<watch::Sender<Option<StampedData<D>>>>,
+}
+
+impl<D, T> StateListener<T> for BroadcastListener<D>
+where
+    T: Read<D> + ?Sized,
+{
+    fn call(&mut self, accessor: DataAccessor<'_, T>) {
+        if let Ok(data) = accessor.query_as() {
+            self.sender.send_replace(Some(data));
+        }
+    }
+}
+
+// We cannot derive this because that would impose an unnecessary trait bound `D: Debug`
+impl<D> Debug for BroadcastListener<D> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BroadcastListener").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use static_assertions::assert_impl_all;
+
+    use super::*;
+
+    #[test]
+    fn broadcast_adapter_is_send_and_sync() {
+        assert_impl_all!(BroadcastAdapter<'_, u32>: Send, Sync);
+        assert_impl_all!(BroadcastAdapter<'_, Box<[u32]>>: Send, Sync);
+    }
+}
@@ -19,6 +19,8 @@ use std::num;
 /// This trait is already implemented by the `wnf` crate for many primitive types and types from the standard
 /// library. There are several ways to implement it for your own types:
 /// - Implement it directly, requiring `unsafe` code
+/// - Derive it together with [`NoUninit`] for a plain struct via the `WnfStateData` derive macro, which requires the
+///   `derive` feature
 /// - Derive the [`AnyBitPattern`](https://docs.rs/bytemuck/1/bytemuck/trait.AnyBitPattern.html) trait of the [`bytemuck`](https://docs.rs/bytemuck/1/bytemuck)
 ///   crate and derive this trait from it via the [`derive_from_bytemuck_v1`](crate::derive_from_bytemuck_v1) macro:
 /// ```
@@ -218,6 +220,8 @@ unsafe impl CheckedBitPattern for bool {
 /// This trait is already implemented by the `wnf` crate for many primitive types and types from the standard
 /// library. There are several ways to implement it for your own types:
 /// - Implement it directly, requiring `unsafe` code
+/// - Derive it together with [`AnyBitPattern`] for a plain struct via the `WnfStateData` derive macro, which requires
+///   the `derive` feature
 /// - Derive the [`NoUninit`](https://docs.rs/bytemuck/1/bytemuck/trait.NoUninit.html) trait of the [`bytemuck`](https://docs.rs/bytemuck/1/bytemuck)
 ///   crate and derive this trait from it via the [`derive_from_bytemuck_v1`](crate::derive_from_bytemuck_v1) macro:
 /// ```
@@ -495,9 +499,10 @@ macro_rules! derive_from_bytemuck_v1 {
 /// This macro provides an alternative to a blanket implementation by requiring you to explicitly opt in to the
 /// implementation.
 ///
-/// If you have a type that implements [`zerocopy::FromBytes`](https://docs.rs/zerocopy/0/zerocopy/trait.FromBytes.html)
-/// or [`zerocopy::IntoBytes`](https://docs.rs/zerocopy/0/zerocopy/trait.IntoBytes.html), you can derive the corresponding
-/// `wnf` traits as follows:
+/// If you have a type that implements
+/// [`zerocopy::FromBytes`](https://docs.rs/zerocopy/0.8/zerocopy/trait.FromBytes.html) or
+/// [`zerocopy::IntoBytes`](https://docs.rs/zerocopy/0.8/zerocopy/trait.IntoBytes.html), you can derive the
+/// corresponding `wnf` traits as follows:
 /// ```
 /// # #[macro_use] extern crate wnf;
 /// #
@@ -0,0 +1,223 @@
+//! Detection of WNF capabilities depending on the version of the running operating system
+
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, ErrorKind};
+
+use thiserror::Error;
+
+use crate::ntapi;
+use crate::state_name::DataScope;
+use crate::type_id::TypeId;
+
+/// A capability of the WNF API that is not available on all versions of Windows
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[non_exhaustive]
+pub enum OsCapability {
+    /// Creating and subscribing to states with a type id
+    TypeIds,
+
+    /// Creating states with the [`DataScope::PhysicalMachine`] data scope
+    PhysicalMachineScope,
+
+    /// Subscribing in meta-notification mode, i.e. to consumers becoming active or inactive or publishers terminating
+    ///
+    /// Note that this crate does not support meta-notifications yet, so this is for informational purposes only.
+    MetaNotifications,
+}
+
+impl OsCapability {
+    /// Returns the minimum Windows build number supporting this capability
+    ///
+    /// These numbers have been collected from various reverse engineering resources. There is no guarantee that they
+    /// are correct.
+    const fn minimum_build_number(self) -> u32 {
+        match self {
+            Self::TypeIds => 9600,               // Windows 8.1
+            Self::PhysicalMachineScope => 10240, // Windows 10 1507
+            Self::MetaNotifications => 9200,     // Windows 8
+        }
+    }
+}
+
+impl Display for OsCapability {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::TypeIds => "type ids",
+            Self::PhysicalMachineScope => "physical machine data scope",
+            Self::MetaNotifications => "meta-notifications",
+        })
+    }
+}
+
+/// The WNF capabilities of the running operating system
+///
+/// This is returned by [`os_capabilities`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct OsCapabilities {
+    major_version: u32,
+    minor_version: u32,
+    build_number: u32,
+}
+
+impl OsCapabilities {
+    /// Returns the major version of the running operating system
+    pub const fn major_version(self) -> u32 {
+        self.major_version
+    }
+
+    /// Returns the minor version of the running operating system
+    pub const fn minor_version(self) -> u32 {
+        self.minor_version
+    }
+
+    /// Returns the build number of the running operating system
+    pub const fn build_number(self) -> u32 {
+        self.build_number
+    }
+
+    /// Returns whether the running operating system supports the given capability
+    pub const fn supports(self, capability: OsCapability) -> bool {
+        self.build_number >= capability.minimum_build_number()
+    }
+
+    /// Returns whether the running operating system supports states with a type id
+    pub const fn supports_type_ids(self) -> bool {
+        self.supports(OsCapability::TypeIds)
+    }
+
+    /// Returns whether the running operating system supports states with the given data scope
+    pub const fn supports_data_scope(self, data_scope: DataScope) -> bool {
+        match data_scope {
+            DataScope::PhysicalMachine => self.supports(OsCapability::PhysicalMachineScope),
+            _ => true,
+        }
+    }
+
+    /// Returns whether the running operating system supports meta-notifications
+    pub const fn supports_meta_notifications(self) -> bool {
+        self.supports(OsCapability::MetaNotifications)
+    }
+
+    /// Returns an error if the running operating system does not support the given capability
+    fn ensure(self, capability: OsCapability) -> io::Result<()> {
+        if self.supports(capability) {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                ErrorKind::Unsupported,
+                CapabilityError::UnsupportedByOs {
+                    capability,
+                    build_number: self.build_number,
+                },
+            ))
+        }
+    }
+
+    /// Returns an error if the running operating system does not support states with the given type id
+    pub(crate) fn ensure_type_id(self, type_id: TypeId) -> io::Result<()> {
+        if type_id.as_ptr().is_null() {
+            Ok(())
+        } else {
+            self.ensure(OsCapability::TypeIds)
+        }
+    }
+
+    /// Returns an error if the running operating system does not support states with the given data scope
+    pub(crate) fn ensure_data_scope(self, data_scope: DataScope) -> io::Result<()> {
+        if self.supports_data_scope(data_scope) {
+            Ok(())
+        } else {
+            self.ensure(OsCapability::PhysicalMachineScope)
+        }
+    }
+}
+
+/// An error indicating that a capability of the WNF API is not available
+///
+/// When an operation fails because of this, the returned [`io::Error`] has kind [`ErrorKind::Unsupported`] and wraps
+/// a [`CapabilityError`], which can be obtained via [`io::Error::get_ref`].
+#[derive(Clone, Copy, Debug, Eq, Error, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum CapabilityError {
+    /// The capability is not supported by the running operating system
+    #[error("capability `{capability}` is not supported by the operating system (build {build_number})")]
+    UnsupportedByOs {
+        /// The unsupported capability
+        capability: OsCapability,
+
+        /// The build number of the running operating system
+        build_number: u32,
+    },
+}
+
+/// Detects the WNF capabilities of the running operating system
+///
+/// The capabilities are derived from the version of the running operating system as reported by `ntdll.dll`, which,
+/// unlike `GetVersionEx`, is not affected by the application manifest.
+pub fn os_capabilities() -> OsCapabilities {
+    let mut major_version = 0;
+    let mut minor_version = 0;
+    let mut build_number = 0;
+
+    // SAFETY:
+    // The pointers in all three arguments are valid for writes of `u32` because they come from live mutable references
+    unsafe { ntapi::RtlGetNtVersionNumbers(&mut major_version, &mut minor_version, &mut build_number) };
+
+    OsCapabilities {
+        major_version,
+        minor_version,
+        // The upper four bits are flags that are not part of the build number
+        build_number: build_number & 0x0FFF_FFFF,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capabilities(build_number: u32) -> OsCapabilities {
+        OsCapabilities {
+            major_version: 10,
+            minor_version: 0,
+            build_number,
+        }
+    }
+
+    #[test]
+    fn os_capabilities_build_number_has_no_flags() {
+        assert_eq!(os_capabilities().build_number() & 0xF000_0000, 0);
+    }
+
+    #[test]
+    fn supports_by_build_number() {
+        assert!(!capabilities(9200).supports_type_ids());
+        assert!(capabilities(9600).supports_type_ids());
+
+        assert!(!capabilities(9600).supports_data_scope(DataScope::PhysicalMachine));
+        assert!(capabilities(9600).supports_data_scope(DataScope::Machine));
+        assert!(capabilities(10240).supports_data_scope(DataScope::PhysicalMachine));
+
+        assert!(capabilities(9200).supports_meta_notifications());
+    }
+
+    #[test]
+    fn ensure_data_scope_unsupported() {
+        let err = capabilities(9600)
+            .ensure_data_scope(DataScope::PhysicalMachine)
+            .unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+        assert_eq!(
+            err.get_ref().unwrap().downcast_ref::<CapabilityError>(),
+            Some(&CapabilityError::UnsupportedByOs {
+                capability: OsCapability::PhysicalMachineScope,
+                build_number: 9600
+            })
+        );
+    }
+
+    #[test]
+    fn ensure_type_id_none_is_always_supported() {
+        assert!(capabilities(9200).ensure_type_id(TypeId::none()).is_ok());
+    }
+}
@@ -0,0 +1,310 @@
+//! A typed publish-subscribe channel on top of a state
+
+#![deny(unsafe_code)]
+
+use std::fmt::{self, Debug, Formatter};
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll, Waker};
+
+use crate::bytes::NoUninit;
+use crate::read::Read;
+use crate::state::{AsState, BorrowedState, CowState, OwnedState};
+use crate::state_name::StateName;
+use crate::subscribe::{DataAccessor, SeenChangeStamp, StateListener};
+use crate::subscribe_arc::{OwningSubscription, SubscribeOwning};
+
+/// Creates a channel for sending values of type `T` through a state
+///
+/// This returns a [`Publisher<T>`] and a [`SubscriberStream<T>`] connected through the state given by `name`, which is
+/// either a new temporary state created for the channel ([`ChannelName::New`]) or an existing state with a given name
+/// ([`ChannelName::Existing`]). In the latter case, publishers and subscribers in different processes can communicate
+/// through the same state, making this a simple cross-process publish-subscribe channel.
+///
+/// Values are sent through [`Publisher::send`], which updates the state, and received through
+/// [`SubscriberStream::recv`], which waits for the next update of the state. A subscriber only receives values sent
+/// after it has been created.
+///
+/// # Lossy "latest value" semantics
+/// Unlike a channel from the standard library, this channel does not queue values: The state only ever contains the
+/// latest value. When multiple values are sent before a subscriber receives them, the subscriber only receives the
+/// latest one and all other values are lost. This makes the channel suitable for broadcasting the current value of
+/// something (e.g. a status or a configuration) to any number of subscribers, but not for reliably transmitting a
+/// sequence of messages.
+///
+/// When the channel is created with [`ChannelName::New`], the state is deleted once the publisher and all subscribers
+/// have been dropped. A subscriber does not notice when all publishers have been dropped, it just keeps waiting for
+/// further values.
+///
+/// # Example
+/// ```
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use wnf::ChannelName;
+///
+/// let (publisher, mut subscriber) = wnf::channel::<u32>(ChannelName::New)?;
+///
+/// tokio::spawn(async move {
+///     let value = subscriber.recv().await?;
+///     println!("Received: {value}");
+///     Ok::<_, std::io::Error>(())
+/// });
+///
+/// publisher.send(&42)?;
+/// # Ok(()) }
+/// ```
+///
+/// # Errors
+/// Returns an error if creating the state (for [`ChannelName::New`]) or subscribing to it fails
+pub fn channel<T>(name: impl Into<ChannelName>) -> io::Result<(Publisher<T>, SubscriberStream<T>)>
+where
+    T: NoUninit + Read<T>,
+{
+    let state = match name.into() {
+        ChannelName::New => CowState::Owned(OwnedState::create_temporary()?),
+        ChannelName::Existing(state_name) => CowState::Borrowed(BorrowedState::from_state_name(state_name)),
+    };
+
+    let publisher = Publisher { state: Arc::new(state) };
+    let subscriber = publisher.subscribe()?;
+
+    Ok((publisher, subscriber))
+}
+
+/// The name of the state through which a channel created by [`channel`] sends values
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum ChannelName {
+    /// A new temporary state created for the channel
+    #[default]
+    New,
+
+    /// An existing state with the given name
+    Existing(StateName),
+}
+
+impl From<StateName> for ChannelName {
+    fn from(state_name: StateName) -> Self {
+        Self::Existing(state_name)
+    }
+}
+
+/// The sending half of a channel created by [`channel`]
+///
+/// Cloning a [`Publisher<T>`] creates another publisher sending values through the same state.
+pub struct Publisher<T> {
+    state: Arc<CowState<'static, T>>,
+}
+
+impl<T> Publisher<T> {
+    /// Returns the name of the state through which this publisher sends values
+    ///
+    /// This can be used to create a channel in another process through [`ChannelName::Existing`].
+    pub fn state_name(&self) -> StateName {
+        self.state.state_name()
+    }
+}
+
+impl<T> Publisher<T>
+where
+    T: NoUninit,
+{
+    /// Sends the given value to all subscribers
+    ///
+    /// This updates the state with the given value, replacing the previous value. Subscribers that have not received
+    /// the previous value yet will not receive it anymore, see [`channel`] for details.
+    ///
+    /// # Errors
+    /// Returns an error if updating the state fails
+    pub fn send(&self, value: &T) -> io::Result<()> {
+        self.state.as_state().set(value)
+    }
+}
+
+impl<T> Publisher<T>
+where
+    T: Read<T>,
+{
+    /// Creates a new subscriber receiving the values sent through this publisher
+    ///
+    /// The subscriber only receives values sent after it has been created.
+    ///
+    /// # Errors
+    /// Returns an error if subscribing to the state fails
+    pub fn subscribe(&self) -> io::Result<SubscriberStream<T>> {
+        let slot = Arc::new(Mutex::new(Slot {
+            value: None,
+            waker: None,
+        }));
+
+        let subscription = Arc::clone(&self.state).subscribe_owning(
+            ChannelListener {
+                slot: Arc::clone(&slot),
+            },
+            SeenChangeStamp::Current,
+        )?;
+
+        Ok(SubscriberStream { slot, subscription })
+    }
+}
+
+// We cannot derive this because that would impose an unnecessary trait bound `T: Clone`
+impl<T> Clone for Publisher<T> {
+    fn clone(&self) -> Self {
+        Self {
+            state: Arc::clone(&self.state),
+        }
+    }
+}
+
+// We cannot derive this because that would impose an unnecessary trait bound `T: Debug`
+impl<T> Debug for Publisher<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Publisher").field("state", &self.state).finish()
+    }
+}
+
+/// The receiving half of a channel created by [`channel`]
+///
+/// Further subscribers to the same channel can be created through [`Publisher::subscribe`]. The subscriber is
+/// unsubscribed from the state when the [`SubscriberStream<T>`](SubscriberStream) is dropped.
+pub struct SubscriberStream<T> {
+    slot: Arc<Mutex<Slot<T>>>,
+    subscription: OwningSubscription<Arc<CowState<'static, T>>, ChannelListener<T>>,
+}
+
+impl<T> SubscriberStream<T> {
+    /// Returns the name of the state through which this subscriber receives values
+    pub fn state_name(&self) -> StateName {
+        self.subscription.state().state_name()
+    }
+
+    /// Receives the latest value that has been sent since the last value was received, if any, without waiting
+    ///
+    /// This returns `None` if no value has been sent since the last value was received.
+    pub fn try_recv(&mut self) -> Option<io::Result<T>> {
+        lock(&self.slot).value.take()
+    }
+
+    /// Waits for the next value sent through the channel and receives it
+    ///
+    /// If a value has been sent since the last value was received, the returned future completes immediately with the
+    /// latest such value. Otherwise, it waits until the next value is sent. Note that this waits indefinitely if no
+    /// more values are sent.
+    ///
+    /// This method does not make any assumptions on what async executor you use. In order to implement a timeout,
+    /// wrap it in the appropriate helper function provided by your executor, e.g.
+    /// [`tokio::time::timeout`](https://docs.rs/tokio/1/tokio/time/fn.timeout.html).
+    ///
+    /// The returned future yields an error if reading the sent value fails.
+    pub fn recv(&mut self) -> Recv<'_, T> {
+        Recv { stream: self }
+    }
+}
+
+// We cannot derive this because that would impose an unnecessary trait bound `T: Debug`
+impl<T> Debug for SubscriberStream<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SubscriberStream")
+            .field("subscription", &self.subscription)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The future returned by [`SubscriberStream::recv`]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Recv<'a, T> {
+    stream: &'a mut SubscriberStream<T>,
+}
+
+impl<T> Future for Recv<'_, T> {
+    type Output = io::Result<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut slot = lock(&self.stream.slot);
+
+        match slot.value.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                match &mut slot.waker {
+                    Some(waker) if waker.will_wake(cx.waker()) => (),
+                    waker => *waker = Some(cx.waker().clone()),
+                }
+
+                Poll::Pending
+            }
+        }
+    }
+}
+
+// We cannot derive this because that would impose an unnecessary trait bound `T: Debug`
+impl<T> Debug for Recv<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Recv").field("stream", &self.stream).finish()
+    }
+}
+
+/// The latest value received by a [`ChannelListener<T>`] that has not been taken by the subscriber yet
+struct Slot<T> {
+    value: Option<io::Result<T>>,
+    waker: Option<Waker>,
+}
+
+/// Locks the given slot
+fn lock<T>(slot: &Mutex<Slot<T>>) -> MutexGuard<'_, Slot<T>> {
+    // We can access the slot even when the mutex is poisoned because its fields are only ever replaced as a whole
+    slot.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The state listener of a [`SubscriberStream<T>`], storing the latest value in its slot
+struct ChannelListener<T> {
+    slot: Arc<Mutex<Slot<T>>>,
+}
+
+impl<T> ChannelListener<T> {
+    /// Stores the given value in the slot, replacing any previous value, and wakes the waiting subscriber, if any
+    fn store(&self, value: io::Result<T>) {
+        let mut slot = lock(&self.slot);
+        slot.value = Some(value);
+
+        if let Some(waker) = slot.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> StateListener<T> for ChannelListener<T>
+where
+    T: Read<T>,
+{
+    fn call(&mut self, accessor: DataAccessor<'_, T>) {
+        self.store(accessor.get());
+    }
+
+    fn on_error(&mut self, err: io::Error, _: DataAccessor<'_, T>) {
+        self.store(Err(err));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use static_assertions::assert_impl_all;
+
+    use super::*;
+
+    #[test]
+    fn channel_is_send_and_sync() {
+        assert_impl_all!(Publisher<u32>: Send, Sync);
+        assert_impl_all!(SubscriberStream<u32>: Send, Sync);
+        assert_impl_all!(Recv<'_, u32>: Send, Sync);
+    }
+
+    #[test]
+    fn channel_name_from_state_name() {
+        let state_name = StateName::from_opaque_value(0x0D83_063E_A3BE_5075);
+
+        assert_eq!(ChannelName::from(state_name), ChannelName::Existing(state_name));
+        assert_eq!(ChannelName::default(), ChannelName::New);
+    }
+}
@@ -0,0 +1,90 @@
+//! Deleting orphaned permanent and persistent states
+
+use std::io;
+
+use crate::data::OpaqueData;
+use crate::describe::StateReport;
+use crate::registry;
+use crate::state::BorrowedState;
+use crate::state_name::{StateLifetime, StateName};
+
+/// The outcome of cleaning up a single state as part of [`cleanup_orphaned_states`]
+#[derive(Debug)]
+pub enum CleanupOutcome {
+    /// The state matched the filter and was deleted
+    Deleted,
+
+    /// Describing the state or deleting it failed with the contained error
+    Failed(io::Error),
+}
+
+impl CleanupOutcome {
+    /// Returns whether the state was deleted
+    pub const fn is_deleted(&self) -> bool {
+        matches!(self, Self::Deleted)
+    }
+}
+
+/// Deletes all permanent and persistent states registered in the Windows registry that match the given filter
+///
+/// Permanent and persistent states outlive the processes that created them, so states created by processes that
+/// crashed or were killed before deleting them accumulate over time, e.g. on long-lived test machines. This enumerates
+/// the registered permanent and persistent state names (see
+/// [`registered_state_names`](crate::registry::registered_state_names)), collects a [`StateReport`] for each of them
+/// and deletes those for which the filter returns `true`.
+///
+/// Since the names of permanent and persistent states carry no information about their owner, the filter usually
+/// decides based on a list of state names recorded by the application that created them, or based on the information
+/// in the [`StateReport`], such as [`StateReport::subscribers_present`]. Note that the registry may also contain names
+/// of states that don't exist (anymore), see [`StateReport::exists`].
+///
+/// Deleting permanent and persistent states usually requires administrative rights.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use wnf::{StateName, StateReport};
+///
+/// // State names recorded by previous runs of an application
+/// let recorded_state_names: Vec<StateName> = Vec::new();
+///
+/// let results = wnf::cleanup_orphaned_states(|report: &StateReport| {
+///     report.exists && report.subscribers_present == Some(false) && recorded_state_names.contains(&report.state_name)
+/// })?;
+///
+/// for (state_name, outcome) in results {
+///     println!("{state_name}: {outcome:?}");
+/// }
+/// # Ok(()) }
+/// ```
+///
+/// # Errors
+/// Returns an error if enumerating the registered state names fails. Errors describing or deleting individual states
+/// are reported through the [`CleanupOutcome`] of the respective state instead.
+pub fn cleanup_orphaned_states<F>(mut filter: F) -> io::Result<Vec<(StateName, CleanupOutcome)>>
+where
+    F: FnMut(&StateReport) -> bool,
+{
+    let mut state_names = registry::registered_state_names(StateLifetime::Permanent)?;
+    state_names.extend(registry::registered_state_names(StateLifetime::Persistent)?);
+
+    let results = state_names
+        .into_iter()
+        .filter_map(|state_name| {
+            let state = BorrowedState::<OpaqueData>::from_state_name(state_name);
+
+            let outcome = match state.describe() {
+                Ok(report) if filter(&report) => match state.delete() {
+                    Ok(()) => CleanupOutcome::Deleted,
+                    Err(err) => CleanupOutcome::Failed(err),
+                },
+                Ok(..) => return None,
+                Err(err) => CleanupOutcome::Failed(err),
+            };
+
+            Some((state_name, outcome))
+        })
+        .collect();
+
+    Ok(results)
+}
@@ -0,0 +1,260 @@
+//! Storing compressed data in states
+
+use std::fmt::{self, Debug, Formatter};
+use std::io::{self, ErrorKind};
+use std::marker::PhantomData;
+use std::{mem, slice};
+
+use crate::bytes::NoUninit;
+use crate::read::Read;
+use crate::state::{AsState, BorrowedState};
+
+/// The magic bytes at the start of the data of a state containing compressed data
+const MAGIC: [u8; 4] = *b"WNFZ";
+
+/// The size of the header preceding the compressed data, consisting of the magic bytes and the uncompressed size
+const HEADER_SIZE: usize = MAGIC.len() + mem::size_of::<u32>();
+
+/// The compression level passed to the deflate compressor, ranging from 0 (no compression) to 10 (best compression)
+const COMPRESSION_LEVEL: u8 = 6;
+
+/// A view of a state whose data are stored in compressed form
+///
+/// The data of a state are limited to 4 KB (see [`MAXIMUM_STATE_SIZE`](crate::manage::MAXIMUM_STATE_SIZE)). Larger
+/// payloads that compress well, such as lists of similar records, can still be stored in a state by compressing them.
+/// A [`CompressedState<'a, T>`](CompressedState) transparently compresses data of type `T` before updating the
+/// underlying state and decompresses them when querying it.
+///
+/// The data of the underlying state consist of a header followed by the data of type `T` compressed with the
+/// [deflate](https://www.rfc-editor.org/rfc/rfc1951) algorithm. The header consists of the magic bytes `WNFZ` followed
+/// by the size of the uncompressed data in bytes as a little-endian `u32`. This makes it possible to read the data by
+/// consumers not using this crate.
+///
+/// The underlying state can have any data type, its data are treated as raw bytes. Note that the type id of the
+/// underlying state (if any) is used when updating it.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use wnf::{CompressedState, OwnedState};
+///
+/// let state = OwnedState::<[u8]>::create_temporary()?;
+/// let compressed_state = CompressedState::<[u32]>::new(&state);
+///
+/// let data: Vec<u32> = (0..2048).collect();
+/// compressed_state.set(&data)?;
+///
+/// assert_eq!(*compressed_state.get_boxed()?, *data);
+/// # Ok(()) }
+/// ```
+pub struct CompressedState<'a, T>
+where
+    T: ?Sized,
+{
+    state: BorrowedState<'a, [u8]>,
+    _marker: PhantomData<fn(T) -> T>,
+}
+
+impl<'a, T> CompressedState<'a, T>
+where
+    T: ?Sized,
+{
+    /// Creates a new [`CompressedState<'a, T>`](CompressedState) storing compressed data in the given state
+    pub fn new<S>(state: &'a S) -> Self
+    where
+        S: AsState,
+    {
+        Self {
+            state: state.as_state().cast(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the underlying state containing the compressed data
+    pub const fn state(&self) -> BorrowedState<'a, [u8]> {
+        self.state
+    }
+
+    /// Queries the data of the underlying state and decompresses them into a byte buffer
+    fn get_decompressed(&self) -> io::Result<Vec<u8>> {
+        decompress(&self.state.get_boxed()?)
+    }
+}
+
+impl<T> CompressedState<'_, T>
+where
+    T: Read<T>,
+{
+    /// Queries the data of the underlying state and decompresses them
+    ///
+    /// # Errors
+    /// Returns an error if querying fails, if the data of the underlying state are not valid compressed data (in which
+    /// case [`io::Error::kind`] returns [`ErrorKind::InvalidData`]) or if the decompressed data are not a valid `T`
+    pub fn get(&self) -> io::Result<T> {
+        let buffer = self.get_decompressed()?;
+
+        // SAFETY:
+        // `buffer` is a `Vec<u8>`, so `buffer.as_ptr()` is valid for reads of size `buffer.len()` and the memory range
+        // is initialized
+        unsafe { T::from_buffer(buffer.as_ptr().cast(), buffer.len()) }
+    }
+}
+
+impl<T> CompressedState<'_, T>
+where
+    T: Read<Box<T>> + ?Sized,
+{
+    /// Queries the data of the underlying state and decompresses them as a box
+    ///
+    /// # Errors
+    /// See [`CompressedState::get`]
+    pub fn get_boxed(&self) -> io::Result<Box<T>> {
+        let buffer = self.get_decompressed()?;
+
+        // SAFETY:
+        // `buffer` is a `Vec<u8>`, so `buffer.as_ptr()` is valid for reads of size `buffer.len()` and the memory range
+        // is initialized
+        unsafe { T::from_buffer(buffer.as_ptr().cast(), buffer.len()) }
+    }
+}
+
+impl<T> CompressedState<'_, T>
+where
+    T: NoUninit + ?Sized,
+{
+    /// Compresses the given data and updates the underlying state with them
+    ///
+    /// # Errors
+    /// Returns an error if the size of the given data exceeds [`u32::MAX`] bytes or if updating fails, e.g. because
+    /// the compressed data exceed the maximum state size
+    pub fn set(&self, data: &T) -> io::Result<()> {
+        // SAFETY:
+        // - `data` is a reference to a `T`, so it is valid for reads of size `mem::size_of_val(data)`
+        // - `T: NoUninit` guarantees that the memory range of size `mem::size_of_val(data)` starting at `data` is
+        //   initialized
+        let bytes = unsafe { slice::from_raw_parts((data as *const T).cast::<u8>(), mem::size_of_val(data)) };
+
+        self.state.set(&compress(bytes)?)
+    }
+}
+
+impl<T> Clone for CompressedState<'_, T>
+where
+    T: ?Sized,
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for CompressedState<'_, T> where T: ?Sized {}
+
+// We cannot derive this because that would impose an unnecessary trait bound `T: Debug`
+impl<T> Debug for CompressedState<'_, T>
+where
+    T: ?Sized,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompressedState").field("state", &self.state).finish()
+    }
+}
+
+/// Compresses the given bytes, prepending the header
+fn compress(bytes: &[u8]) -> io::Result<Vec<u8>> {
+    let uncompressed_size: u32 = bytes.len().try_into().map_err(|_| {
+        io::Error::new(
+            ErrorKind::InvalidInput,
+            "data are too large to be compressed into a state",
+        )
+    })?;
+
+    let compressed = miniz_oxide::deflate::compress_to_vec(bytes, COMPRESSION_LEVEL);
+
+    let mut buffer = Vec::with_capacity(HEADER_SIZE + compressed.len());
+    buffer.extend_from_slice(&MAGIC);
+    buffer.extend_from_slice(&uncompressed_size.to_le_bytes());
+    buffer.extend_from_slice(&compressed);
+
+    Ok(buffer)
+}
+
+/// Decompresses the given bytes, validating the header
+fn decompress(bytes: &[u8]) -> io::Result<Vec<u8>> {
+    let invalid_data = |message| io::Error::new(ErrorKind::InvalidData, message);
+
+    let (header, compressed) = (bytes.len() >= HEADER_SIZE)
+        .then(|| bytes.split_at(HEADER_SIZE))
+        .ok_or_else(|| invalid_data("data of state are too short to contain a compression header"))?;
+
+    let (magic, uncompressed_size) = header.split_at(MAGIC.len());
+
+    if magic != MAGIC {
+        return Err(invalid_data(
+            "data of state do not start with the compression magic bytes",
+        ));
+    }
+
+    let uncompressed_size = u32::from_le_bytes(uncompressed_size.try_into().unwrap()) as usize;
+
+    let decompressed = miniz_oxide::inflate::decompress_to_vec_with_limit(compressed, uncompressed_size)
+        .map_err(|_| invalid_data("data of state are not a valid compressed stream"))?;
+
+    if decompressed.len() != uncompressed_size {
+        return Err(invalid_data(
+            "size of decompressed data does not match compression header",
+        ));
+    }
+
+    Ok(decompressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use static_assertions::assert_impl_all;
+
+    use super::*;
+
+    #[test]
+    fn compressed_state_is_send_and_sync_regardless_of_data_type() {
+        type NeitherSendNorSync = *const ();
+
+        assert_impl_all!(CompressedState<'_, NeitherSendNorSync>: Send, Sync);
+    }
+
+    #[test]
+    fn compress_decompress_round_trip() {
+        let bytes: Vec<u8> = (0..8192).map(|i| (i % 7) as u8).collect();
+
+        let compressed = compress(&bytes).unwrap();
+
+        assert_eq!(compressed[..MAGIC.len()], MAGIC);
+        assert_eq!(decompress(&compressed).unwrap(), bytes);
+    }
+
+    #[test]
+    fn decompress_empty_data() {
+        let err = decompress(&[]).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn decompress_invalid_magic() {
+        let mut compressed = compress(b"data").unwrap();
+        compressed[0] = b'X';
+
+        let err = decompress(&compressed).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn decompress_size_mismatch() {
+        let mut compressed = compress(b"data").unwrap();
+        compressed[MAGIC.len()] = 5;
+
+        let err = decompress(&compressed).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,227 @@
+//! Synchronously waiting for conditions on the data of a state
+
+#![deny(unsafe_code)]
+
+use std::fmt::{self, Debug, Formatter};
+use std::io::{self, ErrorKind};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use crate::data::StampedData;
+use crate::read::Read;
+use crate::state::AsState;
+use crate::subscribe::{DataAccessor, SeenChangeStamp, StateListener, Subscription};
+
+/// A condition variable keyed on the data of a state
+///
+/// This is the WNF counterpart of a [`Condvar`]: Threads can block until the data of a state satisfy a condition,
+/// where the state takes the role of the mutex-protected value and updates of the state take the role of
+/// notifications. It replaces hand-rolled combinations of a subscription, a [`Mutex`] and a [`Condvar`].
+///
+/// A [`StateCondvar<'a, T>`](StateCondvar) subscribes to the state once upon creation and keeps track of the latest
+/// data of the state, so any number of threads can wait on it any number of times without creating additional
+/// subscriptions. In contrast, [`OwnedState::wait_until_blocking`](crate::state::OwnedState::wait_until_blocking)
+/// subscribes to the state anew on every call.
+///
+/// Note that the condition is only checked against the latest data of the state, so data that are overwritten before a
+/// waiting thread is woken up are never checked. This is the same as with a [`Condvar`], where a waiting thread only
+/// observes the value of the mutex-protected data at the time it reacquires the lock.
+///
+/// The listener is automatically unsubscribed from the state when the [`StateCondvar<'a, T>`](StateCondvar) is
+/// dropped. In this case, errors while unsubscribing are silently ignored. If you want to handle them explicitly, use
+/// the [`StateCondvar::unsubscribe`] method.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use std::sync::Arc;
+/// use std::thread;
+/// use std::time::Duration;
+///
+/// use wnf::{OwnedState, StateCondvar};
+///
+/// let state = Arc::new(OwnedState::<u32>::create_temporary()?);
+/// state.set(&0)?;
+///
+/// let condvar = StateCondvar::new(&*state)?;
+///
+/// {
+///     let state = Arc::clone(&state);
+///     thread::spawn(move || {
+///         for value in 1..=10 {
+///             state.set(&value).unwrap();
+///         }
+///     });
+/// }
+///
+/// let value = condvar.wait_while(|value| *value < 10, Duration::from_secs(10))?;
+/// assert_eq!(value, 10);
+/// # Ok(()) }
+/// ```
+pub struct StateCondvar<'a, T> {
+    shared: Arc<Shared<T>>,
+    subscription: Subscription<'a, CondvarListener<T>>,
+}
+
+impl<'a, T> StateCondvar<'a, T>
+where
+    T: Read<T> + Clone,
+{
+    /// Creates a new [`StateCondvar<'a, T>`](StateCondvar) for the given state
+    ///
+    /// This queries the current data of the state and subscribes to all updates after that, so the data the
+    /// [`StateCondvar<'a, T>`](StateCondvar) keeps track of are consistent with the state at any time.
+    ///
+    /// # Errors
+    /// Returns an error if querying or subscribing to the state fails
+    pub fn new<S>(state: &'a S) -> io::Result<Self>
+    where
+        S: AsState<Data = T>,
+    {
+        let snapshot = state.as_state().query()?;
+        let change_stamp = snapshot.change_stamp();
+
+        let shared = Arc::new(Shared {
+            latest: Mutex::new(Latest::Data(snapshot)),
+            condvar: Condvar::new(),
+        });
+
+        let listener = CondvarListener {
+            shared: Arc::clone(&shared),
+        };
+
+        // Subscribing with the change stamp of the snapshot ensures that updates happening between querying and
+        // subscribing are not missed
+        let subscription = state
+            .as_state()
+            .subscribe(listener, SeenChangeStamp::Value(change_stamp))?;
+
+        Ok(Self { shared, subscription })
+    }
+
+    /// Blocks the current thread while the data of the state satisfy the given condition, returning the first data
+    /// that don't
+    ///
+    /// This returns immediately if the latest data of the state already don't satisfy the condition. Otherwise, the
+    /// current thread is blocked until the state is updated with data that don't satisfy the condition. The condition
+    /// is re-checked in a loop on every wakeup, so spurious wakeups of the underlying [`Condvar`] never cause this
+    /// method to return early.
+    ///
+    /// This is a blocking method.
+    ///
+    /// # Errors
+    /// Returns an error if reading the data of an update fails or if the timeout has elapsed. In the latter case,
+    /// [`io::Error::kind`] returns [`ErrorKind::TimedOut`].
+    pub fn wait_while<F>(&self, mut condition: F, timeout: Duration) -> io::Result<T>
+    where
+        F: FnMut(&T) -> bool,
+    {
+        let (latest, timeout_result) = self
+            .shared
+            .condvar
+            .wait_timeout_while(self.shared.lock_latest(), timeout, |latest| match latest {
+                Latest::Data(data) => condition(data.data()),
+                Latest::Error(..) => false,
+            })
+            .unwrap_or_else(PoisonError::into_inner);
+
+        if timeout_result.timed_out() {
+            return Err(io::Error::new(
+                ErrorKind::TimedOut,
+                "waiting for state condition timed out",
+            ));
+        }
+
+        match &*latest {
+            Latest::Data(data) => Ok(data.data().clone()),
+            Latest::Error(kind, message) => Err(io::Error::new(*kind, message.clone())),
+        }
+    }
+
+    /// Returns the latest data of the state together with their change stamp
+    ///
+    /// # Errors
+    /// Returns an error if reading the data of the latest update failed
+    pub fn latest(&self) -> io::Result<StampedData<T>> {
+        match &*self.shared.lock_latest() {
+            Latest::Data(data) => Ok(data.clone()),
+            Latest::Error(kind, message) => Err(io::Error::new(*kind, message.clone())),
+        }
+    }
+}
+
+impl<T> StateCondvar<'_, T> {
+    /// Unsubscribes this [`StateCondvar<'_, T>`](StateCondvar) from the state
+    ///
+    /// This happens automatically when the [`StateCondvar<'_, T>`](StateCondvar) is dropped, so there is usually no
+    /// need to call this method. Its only purpose is to enable you to handle errors while unsubscribing.
+    ///
+    /// # Errors
+    /// Returns an error if unsubscribing fails
+    pub fn unsubscribe(self) -> io::Result<()> {
+        self.subscription.unsubscribe()
+    }
+}
+
+// We cannot derive this because that would impose an unnecessary trait bound `T: Debug`
+impl<T> Debug for StateCondvar<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StateCondvar").finish_non_exhaustive()
+    }
+}
+
+/// The state shared between a [`StateCondvar<'_, T>`](StateCondvar) and its listener
+struct Shared<T> {
+    latest: Mutex<Latest<T>>,
+    condvar: Condvar,
+}
+
+impl<T> Shared<T> {
+    /// Locks the latest data of the state
+    fn lock_latest(&self) -> MutexGuard<'_, Latest<T>> {
+        // We can access the latest data even when the mutex is poisoned because it is only ever replaced as a whole
+        self.latest.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// The latest data of a state, or the error that occurred while reading them
+///
+/// Since [`io::Error`] is not [`Clone`], an error is stored as its kind and message so it can be reported to every
+/// waiting thread.
+enum Latest<T> {
+    Data(StampedData<T>),
+    Error(ErrorKind, String),
+}
+
+/// The state listener of a [`StateCondvar<'_, T>`](StateCondvar), storing the latest data and notifying the waiting
+/// threads
+struct CondvarListener<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> StateListener<T> for CondvarListener<T>
+where
+    T: Read<T>,
+{
+    fn call(&mut self, accessor: DataAccessor<'_, T>) {
+        let latest = match accessor.query() {
+            Ok(data) => Latest::Data(data),
+            Err(err) => Latest::Error(err.kind(), err.to_string()),
+        };
+
+        *self.shared.lock_latest() = latest;
+        self.shared.condvar.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use static_assertions::assert_impl_all;
+
+    use super::*;
+
+    #[test]
+    fn state_condvar_is_send_and_sync() {
+        assert_impl_all!(StateCondvar<'_, u32>: Send, Sync);
+    }
+}
@@ -0,0 +1,118 @@
+//! Methods for querying state data while detecting concurrent updates
+
+use std::io;
+
+use crate::data::StampedData;
+use crate::read::Read;
+use crate::state::{BorrowedState, OwnedState, RawState};
+
+/// Whether queried state data are known not to have been updated concurrently
+///
+/// This is returned by [`OwnedState::query_consistent`] and [`OwnedState::query_boxed_consistent`] (and the
+/// corresponding methods of [`BorrowedState`]).
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Consistency {
+    /// The change stamp of the state was unchanged after the data were read
+    Stable,
+
+    /// The change stamp of the state changed after the data were read on every attempt
+    Unstable,
+}
+
+impl Consistency {
+    /// Returns whether this is [`Consistency::Stable`]
+    pub const fn is_stable(self) -> bool {
+        matches!(self, Self::Stable)
+    }
+}
+
+impl<T> OwnedState<T>
+where
+    T: Read<T>,
+{
+    /// Queries the data of this state together with its change stamp, retrying if the state is updated concurrently
+    ///
+    /// After querying the data, this queries the change stamp of the state again. If it has changed, the state was
+    /// updated while or right after the data were read, so the query is retried, at most `max_retries` times. The
+    /// returned [`Consistency`] tells whether a query with an unchanged change stamp succeeded. If not, the data from
+    /// the last attempt are returned together with [`Consistency::Unstable`].
+    ///
+    /// This produces an owned `T` on the stack and hence requires `T: Sized`. In order to produce a `Box<T>` for
+    /// `T: ?Sized`, use the [`query_boxed_consistent`](OwnedState::query_boxed_consistent) method.
+    ///
+    /// # Errors
+    /// Returns an error if querying fails, including the case that the queried data is not a valid `T`
+    pub fn query_consistent(&self, max_retries: usize) -> io::Result<(StampedData<T>, Consistency)> {
+        self.raw.query_consistent_as(max_retries)
+    }
+}
+
+impl<T> OwnedState<T>
+where
+    T: Read<Box<T>> + ?Sized,
+{
+    /// Queries the data of this state as a box together with its change stamp, retrying if the state is updated
+    /// concurrently
+    ///
+    /// This produces a [`Box<T>`]. In order to produce an owned `T` on the stack (requiring `T: Sized`), use the
+    /// [`query_consistent`](OwnedState::query_consistent) method, which also describes the retry behavior.
+    ///
+    /// # Errors
+    /// Returns an error if querying fails, including the case that the queried data is not a valid `T`
+    pub fn query_boxed_consistent(&self, max_retries: usize) -> io::Result<(StampedData<Box<T>>, Consistency)> {
+        self.raw.query_consistent_as(max_retries)
+    }
+}
+
+impl<T> BorrowedState<'_, T>
+where
+    T: Read<T>,
+{
+    /// Queries the data of this state together with its change stamp, retrying if the state is updated concurrently
+    ///
+    /// See [`OwnedState::query_consistent`]
+    pub fn query_consistent(self, max_retries: usize) -> io::Result<(StampedData<T>, Consistency)> {
+        self.raw.query_consistent_as(max_retries)
+    }
+}
+
+impl<T> BorrowedState<'_, T>
+where
+    T: Read<Box<T>> + ?Sized,
+{
+    /// Queries the data of this state as a box together with its change stamp, retrying if the state is updated
+    /// concurrently
+    ///
+    /// See [`OwnedState::query_boxed_consistent`]
+    pub fn query_boxed_consistent(self, max_retries: usize) -> io::Result<(StampedData<Box<T>>, Consistency)> {
+        self.raw.query_consistent_as(max_retries)
+    }
+}
+
+impl<T> RawState<T>
+where
+    T: ?Sized,
+{
+    /// Queries the data of this state as a value of type `D`, retrying at most `max_retries` times if the change
+    /// stamp changes after reading the data
+    fn query_consistent_as<D>(self, max_retries: usize) -> io::Result<(StampedData<D>, Consistency)>
+    where
+        T: Read<D>,
+    {
+        let mut retries = 0;
+
+        loop {
+            let stamped_data = self.query_as()?;
+
+            if self.change_stamp()? == stamped_data.change_stamp() {
+                return Ok((stamped_data, Consistency::Stable));
+            }
+
+            if retries == max_retries {
+                return Ok((stamped_data, Consistency::Unstable));
+            }
+
+            retries += 1;
+        }
+    }
+}
@@ -2,9 +2,15 @@
 
 #![deny(unsafe_code)]
 
-use std::borrow::{Borrow, BorrowMut};
+use std::borrow::{Borrow, BorrowMut, Cow};
+#[cfg(windows)]
+use std::ffi::OsString;
+use std::ffi::{CStr, CString};
 use std::fmt;
 use std::fmt::{Display, Formatter};
+use std::ops::{Add, AddAssign, Range, Sub, SubAssign};
+#[cfg(windows)]
+use std::os::windows::ffi::OsStringExt;
 
 /// A placeholder for state data whose content is irrelevant
 ///
@@ -61,6 +67,172 @@ impl OpaqueData {
     }
 }
 
+/// State data interpreted as a "wide" (i.e. potentially ill-formed UTF-16-encoded) string
+///
+/// Many well-known states contain strings, which are usually encoded as UTF-16 on Windows. Using this type as the
+/// data type of a state, you can read such strings without dealing with `[u16]` slices manually:
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use wnf::{BorrowedState, WideString};
+///
+/// const WNF_SHEL_DESKTOP_APPLICATION_STARTED: u64 = 0x0D83063EA3BE5075;
+///
+/// let state = BorrowedState::<WideString>::from_state_name(WNF_SHEL_DESKTOP_APPLICATION_STARTED);
+/// let last_application_started = state.get()?.into_os_string();
+///
+/// println!("{}", last_application_started.to_string_lossy());
+/// # Ok(()) }
+/// ```
+///
+/// The string is terminated at the first NUL character, if any. Reading fails if the size of the state data is not a
+/// multiple of `2`.
+///
+/// With the `widestring` feature enabled, this can also be converted into a
+/// [`U16CString`](https://docs.rs/widestring/1/widestring/ucstring/type.U16CString.html). With the `windows` feature
+/// enabled, it can be converted into an [`HSTRING`](https://docs.rs/windows/0/windows/core/struct.HSTRING.html) or a
+/// [`BSTR`](https://docs.rs/windows/0/windows/core/struct.BSTR.html).
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct WideString {
+    data: Box<[u16]>,
+}
+
+impl WideString {
+    /// Creates a new [`WideString`] from the given UTF-16 code units, terminating it at the first NUL character
+    pub(crate) fn from_wide(mut data: Vec<u16>) -> Self {
+        if let Some(len) = data.iter().position(|&code_unit| code_unit == 0) {
+            data.truncate(len);
+        }
+
+        Self {
+            data: data.into_boxed_slice(),
+        }
+    }
+
+    /// Returns the UTF-16 code units of this [`WideString`], not including a terminating NUL character
+    pub fn as_wide(&self) -> &[u16] {
+        &self.data
+    }
+
+    /// Converts this [`WideString`] into an [`OsString`]
+    #[cfg(windows)]
+    pub fn into_os_string(self) -> OsString {
+        OsString::from_wide(&self.data)
+    }
+
+    /// Converts this [`WideString`] into a [`String`], replacing invalid UTF-16 with the replacement character
+    /// (`U+FFFD`)
+    pub fn to_string_lossy(&self) -> String {
+        String::from_utf16_lossy(&self.data)
+    }
+
+    /// Converts this [`WideString`] into a
+    /// [`U16CString`](https://docs.rs/widestring/1/widestring/ucstring/type.U16CString.html)
+    #[cfg(feature = "widestring")]
+    pub fn into_u16_cstring(self) -> widestring::U16CString {
+        // `self.data` does not contain a NUL character, so nothing is truncated
+        widestring::U16CString::from_vec_truncate(self.data)
+    }
+
+    /// Converts this [`WideString`] into an [`HSTRING`](https://docs.rs/windows/0/windows/core/struct.HSTRING.html)
+    #[cfg(all(windows, feature = "windows"))]
+    pub fn to_hstring(&self) -> windows::core::HSTRING {
+        windows::core::HSTRING::from_wide(&self.data)
+    }
+
+    /// Converts this [`WideString`] into a [`BSTR`](https://docs.rs/windows/0/windows/core/struct.BSTR.html)
+    #[cfg(all(windows, feature = "windows"))]
+    pub fn to_bstr(&self) -> windows::core::BSTR {
+        windows::core::BSTR::from_wide(&self.data)
+    }
+}
+
+#[cfg(windows)]
+impl From<WideString> for OsString {
+    fn from(wide_string: WideString) -> Self {
+        wide_string.into_os_string()
+    }
+}
+
+#[cfg(feature = "widestring")]
+impl From<WideString> for widestring::U16CString {
+    fn from(wide_string: WideString) -> Self {
+        wide_string.into_u16_cstring()
+    }
+}
+
+#[cfg(all(windows, feature = "windows"))]
+impl From<WideString> for windows::core::HSTRING {
+    fn from(wide_string: WideString) -> Self {
+        wide_string.to_hstring()
+    }
+}
+
+#[cfg(all(windows, feature = "windows"))]
+impl From<WideString> for windows::core::BSTR {
+    fn from(wide_string: WideString) -> Self {
+        wide_string.to_bstr()
+    }
+}
+
+/// State data interpreted as a NUL-terminated string of bytes in an unspecified encoding
+///
+/// Some well-known states contain strings in an ANSI code page or another byte-oriented encoding rather than UTF-16.
+/// Using this type as the data type of a state, you can read such strings without slicing `[u8]` data manually:
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use wnf::{CStrData, OwnedState};
+///
+/// let state = OwnedState::<[u8]>::create_temporary()?;
+/// state.set(b"Hello\0")?;
+///
+/// let data = state.cast::<CStrData>().get()?;
+///
+/// assert_eq!(data.as_c_str().to_bytes(), b"Hello");
+/// assert_eq!(data.to_string_lossy(), "Hello");
+/// # Ok(()) }
+/// ```
+///
+/// The string is terminated at the first NUL byte. Reading fails if the state data do not contain a NUL byte.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct CStrData {
+    data: CString,
+}
+
+impl CStrData {
+    /// Creates a new [`CStrData`] from the given C string
+    pub(crate) fn from_c_string(data: CString) -> Self {
+        Self { data }
+    }
+
+    /// Returns the string of this [`CStrData`] as a [`CStr`]
+    pub fn as_c_str(&self) -> &CStr {
+        &self.data
+    }
+
+    /// Returns the bytes of this [`CStrData`], not including the terminating NUL byte
+    pub fn as_bytes(&self) -> &[u8] {
+        self.data.as_bytes()
+    }
+
+    /// Converts this [`CStrData`] into a [`CString`]
+    pub fn into_c_string(self) -> CString {
+        self.data
+    }
+
+    /// Converts this [`CStrData`] into a string, replacing invalid UTF-8 with the replacement character (`U+FFFD`)
+    ///
+    /// Note that bytes in an ANSI code page other than ASCII are not valid UTF-8 and will be replaced.
+    pub fn to_string_lossy(&self) -> Cow<'_, str> {
+        self.data.to_string_lossy()
+    }
+}
+
+impl From<CStrData> for CString {
+    fn from(c_str_data: CStrData) -> Self {
+        c_str_data.into_c_string()
+    }
+}
+
 /// The change stamp of a state
 ///
 /// This is `0` when the state is created and is increased by `1` on every update to the state.
@@ -74,6 +246,8 @@ impl OpaqueData {
 /// - [`DataAccessor::query`](crate::subscribe::DataAccessor::query) and
 ///   [`DataAccessor::query_boxed`](crate::subscribe::DataAccessor::query_boxed)
 #[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct ChangeStamp(u32);
 
 impl ChangeStamp {
@@ -98,7 +272,41 @@ impl ChangeStamp {
         self.0
     }
 
+    /// Returns the number of updates between `earlier` and this change stamp, taking wrap-around into account
+    ///
+    /// This is the number of times `earlier` needs to be incremented (wrapping around at [`u32::MAX`]) to reach this
+    /// change stamp:
+    ///
+    /// ```
+    /// # use wnf::ChangeStamp;
+    /// #
+    /// assert_eq!(ChangeStamp::new(5).distance_from(ChangeStamp::new(2)), 3);
+    /// assert_eq!(ChangeStamp::new(1).distance_from(ChangeStamp::new(u32::MAX)), 2);
+    /// ```
+    pub const fn distance_from(self, earlier: ChangeStamp) -> u32 {
+        self.0.wrapping_sub(earlier.0)
+    }
+
+    /// Returns whether this change stamp is newer than the given one, taking wrap-around into account
+    ///
+    /// Since change stamps wrap around at [`u32::MAX`], this uses serial number arithmetic: A change stamp is
+    /// considered newer than another one if it is at most `2^31 - 1` updates ahead of it. Unlike comparing change
+    /// stamps via [`Ord`], this gives the correct result across an overflow:
+    ///
+    /// ```
+    /// # use wnf::ChangeStamp;
+    /// #
+    /// assert!(ChangeStamp::new(2).is_newer_than(ChangeStamp::new(1)));
+    /// assert!(ChangeStamp::new(0).is_newer_than(ChangeStamp::new(u32::MAX)));
+    /// assert!(!ChangeStamp::new(1).is_newer_than(ChangeStamp::new(1)));
+    /// ```
+    pub const fn is_newer_than(self, other: ChangeStamp) -> bool {
+        let distance = self.distance_from(other);
+        distance != 0 && distance <= i32::MAX as u32
+    }
+
     /// Returns a mutable raw pointer to the inner value for use in FFI
+    #[cfg(windows)]
     pub(crate) fn as_mut_ptr(&mut self) -> *mut u32 {
         &mut self.0
     }
@@ -116,6 +324,45 @@ impl From<ChangeStamp> for u32 {
     }
 }
 
+impl Add<u32> for ChangeStamp {
+    type Output = Self;
+
+    /// Advances the change stamp by the given number of updates, wrapping around at [`u32::MAX`]
+    fn add(self, rhs: u32) -> Self {
+        Self(self.0.wrapping_add(rhs))
+    }
+}
+
+impl AddAssign<u32> for ChangeStamp {
+    fn add_assign(&mut self, rhs: u32) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub<u32> for ChangeStamp {
+    type Output = Self;
+
+    /// Moves the change stamp back by the given number of updates, wrapping around at `0`
+    fn sub(self, rhs: u32) -> Self {
+        Self(self.0.wrapping_sub(rhs))
+    }
+}
+
+impl SubAssign<u32> for ChangeStamp {
+    fn sub_assign(&mut self, rhs: u32) {
+        *self = *self - rhs;
+    }
+}
+
+impl Sub for ChangeStamp {
+    type Output = u32;
+
+    /// Returns the number of updates between the given change stamp and this one, see [`ChangeStamp::distance_from`]
+    fn sub(self, rhs: Self) -> u32 {
+        self.distance_from(rhs)
+    }
+}
+
 impl Display for ChangeStamp {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         self.0.fmt(f)
@@ -160,6 +407,7 @@ impl BorrowMut<u32> for ChangeStamp {
 
 /// State data together with a change stamp
 #[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StampedData<T> {
     data: T,
     change_stamp: ChangeStamp,
@@ -206,6 +454,51 @@ impl<T> StampedData<T> {
     }
 }
 
+impl<T> StampedData<Box<[T]>>
+where
+    T: PartialEq,
+{
+    /// Compares the data contained in this [`StampedData`] to the data contained in a previous [`StampedData`],
+    /// returning the index ranges that differ
+    ///
+    /// This is useful for listeners of states of slice types that want to process updates incrementally instead of
+    /// processing the whole slice on every update. The comparison is element-wise by index, i.e. an element inserted
+    /// in the middle of the slice shows up as a change of all subsequent elements, plus an addition at the end.
+    ///
+    /// ```
+    /// # use wnf::StampedData;
+    /// #
+    /// let previous = StampedData::from_data_change_stamp(vec![1, 2, 3, 4].into_boxed_slice(), 1);
+    /// let current = StampedData::from_data_change_stamp(vec![1, 5, 6, 4, 7].into_boxed_slice(), 2);
+    ///
+    /// let diff = current.diff(&previous);
+    ///
+    /// assert_eq!(diff.changed(), [1..3]);
+    /// assert_eq!(diff.added(), Some(4..5));
+    /// assert_eq!(diff.removed(), None);
+    /// ```
+    pub fn diff(&self, previous: &Self) -> SliceDiff {
+        let mut changed: Vec<Range<usize>> = Vec::new();
+
+        for (index, (current, previous)) in self.data.iter().zip(previous.data.iter()).enumerate() {
+            if current != previous {
+                match changed.last_mut() {
+                    Some(range) if range.end == index => range.end += 1,
+                    _ => changed.push(index..index + 1),
+                }
+            }
+        }
+
+        let (current_len, previous_len) = (self.data.len(), previous.data.len());
+
+        SliceDiff {
+            changed,
+            added: (current_len > previous_len).then_some(previous_len..current_len),
+            removed: (previous_len > current_len).then_some(current_len..previous_len),
+        }
+    }
+}
+
 impl<T> From<(T, ChangeStamp)> for StampedData<T> {
     fn from((data, change_stamp): (T, ChangeStamp)) -> Self {
         Self { data, change_stamp }
@@ -230,6 +523,40 @@ impl<T> AsMut<T> for StampedData<T> {
     }
 }
 
+/// The differences between two versions of the data of a state of a slice type
+///
+/// This is returned from [`StampedData::diff`].
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct SliceDiff {
+    changed: Vec<Range<usize>>,
+    added: Option<Range<usize>>,
+    removed: Option<Range<usize>>,
+}
+
+impl SliceDiff {
+    /// Returns the ranges of indices at which elements are present in both versions but differ
+    ///
+    /// The ranges are non-empty, sorted and non-adjacent.
+    pub fn changed(&self) -> &[Range<usize>] {
+        &self.changed
+    }
+
+    /// Returns the range of indices of elements present only in the current version, if any
+    pub fn added(&self) -> Option<Range<usize>> {
+        self.added.clone()
+    }
+
+    /// Returns the range of indices of elements present only in the previous version, if any
+    pub fn removed(&self) -> Option<Range<usize>> {
+        self.removed.clone()
+    }
+
+    /// Returns whether both versions are equal
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.added.is_none() && self.removed.is_none()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -239,6 +566,75 @@ mod tests {
         assert_eq!(ChangeStamp::from(42).to_string(), "42");
     }
 
+    #[test]
+    fn change_stamp_arithmetic_wraps_around() {
+        let mut change_stamp = ChangeStamp::new(u32::MAX - 1);
+
+        change_stamp += 3;
+        assert_eq!(change_stamp, 1);
+        assert_eq!(change_stamp - ChangeStamp::new(u32::MAX - 1), 3);
+
+        change_stamp -= 2;
+        assert_eq!(change_stamp, u32::MAX);
+        assert_eq!(change_stamp + 1, 0);
+        assert_eq!(ChangeStamp::new(0) - 1, u32::MAX);
+    }
+
+    #[test]
+    fn change_stamp_is_newer_than() {
+        assert!(ChangeStamp::new(1).is_newer_than(ChangeStamp::new(0)));
+        assert!(!ChangeStamp::new(0).is_newer_than(ChangeStamp::new(1)));
+        assert!(ChangeStamp::new(5).is_newer_than(ChangeStamp::new(u32::MAX - 5)));
+        assert!(!ChangeStamp::new(u32::MAX - 5).is_newer_than(ChangeStamp::new(5)));
+        assert!(ChangeStamp::new(i32::MAX as u32).is_newer_than(ChangeStamp::new(0)));
+        assert!(!ChangeStamp::new(i32::MAX as u32 + 1).is_newer_than(ChangeStamp::new(0)));
+    }
+
+    #[test]
+    fn wide_string_terminates_at_first_nul() {
+        let wide_string = WideString::from_wide(vec![0x0061, 0x0062, 0x0000, 0x0063]);
+
+        assert_eq!(wide_string.as_wide(), [0x0061, 0x0062]);
+        assert_eq!(wide_string.to_string_lossy(), "ab");
+
+        #[cfg(windows)]
+        assert_eq!(wide_string.into_os_string(), "ab");
+    }
+
+    #[test]
+    fn wide_string_to_string_lossy_replaces_invalid_utf16() {
+        let wide_string = WideString::from_wide(vec![0x0061, 0xD800]);
+
+        assert_eq!(wide_string.to_string_lossy(), "a\u{FFFD}");
+    }
+
+    #[test]
+    fn stamped_data_diff() {
+        let stamped = |data: &[u8]| StampedData::from_data_change_stamp(Box::<[u8]>::from(data), 0);
+
+        let diff = stamped(&[1, 2, 3]).diff(&stamped(&[1, 2, 3]));
+        assert!(diff.is_empty());
+
+        let diff = stamped(&[0, 2, 0, 0, 5]).diff(&stamped(&[1, 2, 3, 4, 5, 6, 7]));
+        assert_eq!(diff.changed(), [0..1, 2..4]);
+        assert_eq!(diff.added(), None);
+        assert_eq!(diff.removed(), Some(5..7));
+        assert!(!diff.is_empty());
+
+        let diff = stamped(&[1, 2]).diff(&stamped(&[]));
+        assert_eq!(diff.changed(), []);
+        assert_eq!(diff.added(), Some(0..2));
+        assert_eq!(diff.removed(), None);
+    }
+
+    #[test]
+    fn c_str_data_to_string_lossy_replaces_invalid_utf8() {
+        let c_str_data = CStrData::from_c_string(CString::new(vec![0x61, 0xE4]).unwrap());
+
+        assert_eq!(c_str_data.as_bytes(), [0x61, 0xE4]);
+        assert_eq!(c_str_data.to_string_lossy(), "a\u{FFFD}");
+    }
+
     #[test]
     fn stamped_data_map() {
         assert_eq!(
@@ -0,0 +1,329 @@
+//! Debouncing updates of a state on the publisher side
+
+use std::fmt::{self, Debug, Formatter};
+use std::marker::PhantomData;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use std::{io, mem, panic, slice};
+
+use crate::bytes::NoUninit;
+use crate::state::{AsState, RawState};
+
+/// A publisher coalescing rapid consecutive updates of a state
+///
+/// Every update of a state notifies all of its subscribers. For states that change in bursts, this can cause a storm
+/// of notifications, most of which are outdated by the time they are processed. A
+/// [`DebouncedPublisher<'a, T>`](DebouncedPublisher) reduces them by updating the state at most once per time window:
+///
+/// - When [`set`](DebouncedPublisher::set) is called while no window is open, the state is updated immediately and a
+///   new window is opened.
+/// - When [`set`](DebouncedPublisher::set) is called while a window is open, the data are only stored as pending,
+///   replacing any previously pending data.
+/// - When a window closes while data are pending, the state is updated with the pending data on a background thread and
+///   a new window is opened.
+///
+/// This way, the final data of a burst are always published, at the latest one window after they were passed to
+/// [`set`](DebouncedPublisher::set). Pending data are also published when the publisher is
+/// [`flush`](DebouncedPublisher::flush)ed, [`stop`](DebouncedPublisher::stop)ped or dropped.
+///
+/// Errors updating the state on the background thread are reported by the next call to
+/// [`set`](DebouncedPublisher::set), [`flush`](DebouncedPublisher::flush) or [`stop`](DebouncedPublisher::stop).
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use std::time::Duration;
+///
+/// use wnf::{DebouncedPublisher, OwnedState};
+///
+/// let state = OwnedState::<u32>::create_temporary()?;
+/// let publisher = DebouncedPublisher::new(&state, Duration::from_secs(1))?;
+///
+/// for value in 0..100 {
+///     publisher.set(&value)?;
+/// }
+///
+/// // Only the first value has been published so far
+/// assert_eq!(state.get()?, 0);
+///
+/// publisher.stop()?;
+///
+/// // Stopping the publisher has flushed the final value
+/// assert_eq!(state.get()?, 99);
+/// # Ok(()) }
+/// ```
+#[must_use = "a `DebouncedPublisher` is stopped immediately if it is not used"]
+pub struct DebouncedPublisher<'a, T>
+where
+    T: ?Sized,
+{
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+    _lifetime: PhantomData<&'a ()>,
+    _data: PhantomData<fn(&T)>,
+}
+
+impl<'a, T> DebouncedPublisher<'a, T>
+where
+    T: NoUninit + ?Sized,
+{
+    /// Creates a new [`DebouncedPublisher<'a, T>`](DebouncedPublisher) updating the given state at most once per the
+    /// given time window
+    ///
+    /// This does not update the state by itself.
+    ///
+    /// # Panics
+    /// Panics if `window` is zero
+    ///
+    /// # Errors
+    /// Returns an error if spawning the background thread fails
+    pub fn new<S>(state: &'a S, window: Duration) -> io::Result<Self>
+    where
+        S: AsState<Data = T>,
+    {
+        assert!(!window.is_zero(), "debounce window must not be zero");
+
+        let shared = Arc::new(Shared {
+            state: state.as_state().raw.cast(),
+            window,
+            inner: Mutex::new(Inner {
+                pending: None,
+                window_end: None,
+                error: None,
+                stopped: false,
+            }),
+            condvar: Condvar::new(),
+        });
+
+        let thread = thread::Builder::new().name("wnf-debounce".into()).spawn({
+            let shared = Arc::clone(&shared);
+            move || publish_on_window_close(&shared)
+        })?;
+
+        Ok(Self {
+            shared,
+            thread: Some(thread),
+            _lifetime: PhantomData,
+            _data: PhantomData,
+        })
+    }
+
+    /// Updates the state with the given data, coalescing the update with other updates in the same time window
+    ///
+    /// If no time window is open, this updates the state immediately. Otherwise, the data are stored as pending and
+    /// published when the window closes, unless they are replaced by other data before that.
+    ///
+    /// # Errors
+    /// Returns an error if updating the state immediately fails or if updating the state on the background thread has
+    /// failed since the last call to [`set`](DebouncedPublisher::set) or [`flush`](DebouncedPublisher::flush)
+    pub fn set(&self, data: &T) -> io::Result<()> {
+        // SAFETY:
+        // - `data` is a reference to a `T`, so it is valid for reads of size `mem::size_of_val(data)`
+        // - `T: NoUninit` guarantees that the memory range of size `mem::size_of_val(data)` starting at `data` is
+        //   initialized
+        let bytes = unsafe { slice::from_raw_parts((data as *const T).cast::<u8>(), mem::size_of_val(data)) };
+
+        let mut inner = self.shared.lock_inner();
+        inner.take_error()?;
+
+        let now = Instant::now();
+
+        if inner.is_window_open(now) {
+            inner.pending = Some(bytes.into());
+            self.shared.condvar.notify_all();
+            Ok(())
+        } else {
+            self.shared.publish(&mut inner, bytes, now)
+        }
+    }
+}
+
+impl<T> DebouncedPublisher<'_, T>
+where
+    T: ?Sized,
+{
+    /// Returns the time window in which the state is updated at most once
+    pub fn window(&self) -> Duration {
+        self.shared.window
+    }
+
+    /// Updates the state with the pending data immediately, if any
+    ///
+    /// # Errors
+    /// Returns an error if updating the state fails or if updating the state on the background thread has failed since
+    /// the last call to [`set`](DebouncedPublisher::set) or [`flush`](DebouncedPublisher::flush)
+    pub fn flush(&self) -> io::Result<()> {
+        let mut inner = self.shared.lock_inner();
+        inner.take_error()?;
+        self.shared.publish_pending(&mut inner, Instant::now())
+    }
+
+    /// Stops the publisher, updating the state with the pending data, if any
+    ///
+    /// This is called automatically when the [`DebouncedPublisher<'_, T>`](DebouncedPublisher) is dropped, but calling
+    /// it explicitly makes it possible to handle errors. This blocks until the background thread has finished.
+    ///
+    /// # Errors
+    /// Returns an error if updating the state with the pending data fails or if updating the state on the background
+    /// thread has failed since the last call to [`set`](DebouncedPublisher::set) or
+    /// [`flush`](DebouncedPublisher::flush)
+    pub fn stop(mut self) -> io::Result<()> {
+        self.stop_internal()
+    }
+
+    /// Stops the publisher without consuming the [`DebouncedPublisher<'_, T>`](DebouncedPublisher)
+    fn stop_internal(&mut self) -> io::Result<()> {
+        self.shared.lock_inner().stopped = true;
+        self.shared.condvar.notify_all();
+
+        if let Some(thread) = self.thread.take() {
+            thread.join().unwrap_or_else(|payload| panic::resume_unwind(payload));
+        }
+
+        // Pending data are published even if updating the state on the background thread has failed before
+        let mut inner = self.shared.lock_inner();
+        let result = inner.take_error();
+        result.and(self.shared.publish_pending(&mut inner, Instant::now()))
+    }
+}
+
+impl<T> Drop for DebouncedPublisher<'_, T>
+where
+    T: ?Sized,
+{
+    fn drop(&mut self) {
+        let _ = self.stop_internal();
+    }
+}
+
+// We cannot derive this because that would impose an unnecessary trait bound `T: Debug`
+impl<T> Debug for DebouncedPublisher<'_, T>
+where
+    T: ?Sized,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DebouncedPublisher")
+            .field("window", &self.shared.window)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The state shared between a [`DebouncedPublisher<'_, T>`](DebouncedPublisher) and its background thread
+#[derive(Debug)]
+struct Shared {
+    state: RawState<[u8]>,
+    window: Duration,
+    inner: Mutex<Inner>,
+    condvar: Condvar,
+}
+
+impl Shared {
+    /// Locks the inner state
+    fn lock_inner(&self) -> MutexGuard<'_, Inner> {
+        // We can access the inner state even when the mutex is poisoned because its fields are only ever replaced as a
+        // whole
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Updates the state with the given bytes, opening a new time window at the given time
+    ///
+    /// The lock on the inner state is held while updating so that updates happen in the order in which the data were
+    /// passed to [`DebouncedPublisher::set`].
+    fn publish(&self, inner: &mut Inner, bytes: &[u8], now: Instant) -> io::Result<()> {
+        inner.window_end = Some(now + self.window);
+        self.state.set(bytes)
+    }
+
+    /// Updates the state with the pending data, if any, opening a new time window at the given time
+    fn publish_pending(&self, inner: &mut Inner, now: Instant) -> io::Result<()> {
+        match inner.pending.take() {
+            Some(bytes) => self.publish(inner, &bytes, now),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The inner state of a [`DebouncedPublisher<'_, T>`](DebouncedPublisher), protected by a mutex
+#[derive(Debug)]
+struct Inner {
+    pending: Option<Box<[u8]>>,
+    window_end: Option<Instant>,
+    error: Option<io::Error>,
+    stopped: bool,
+}
+
+impl Inner {
+    /// Returns whether a time window is open at the given time
+    fn is_window_open(&self, now: Instant) -> bool {
+        self.window_end.is_some_and(|window_end| now < window_end)
+    }
+
+    /// Returns the error that occurred while updating the state on the background thread, if any
+    fn take_error(&mut self) -> io::Result<()> {
+        self.error.take().map_or(Ok(()), Err)
+    }
+}
+
+/// Updates the state with the pending data whenever a time window closes until the publisher is stopped
+///
+/// This runs on the background thread of a [`DebouncedPublisher<'_, T>`](DebouncedPublisher). Pending data left when
+/// the publisher is stopped are published by [`DebouncedPublisher::stop`] so that errors can be reported.
+fn publish_on_window_close(shared: &Shared) {
+    let mut inner = shared.lock_inner();
+
+    loop {
+        if inner.stopped {
+            return;
+        }
+
+        let now = Instant::now();
+
+        inner = match inner.window_end {
+            Some(window_end) if inner.pending.is_some() && now < window_end => {
+                shared
+                    .condvar
+                    .wait_timeout(inner, window_end - now)
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .0
+            }
+
+            _ if inner.pending.is_some() => {
+                if let Err(err) = shared.publish_pending(&mut inner, now) {
+                    inner.error = Some(err);
+                }
+                inner
+            }
+
+            _ => shared.condvar.wait(inner).unwrap_or_else(PoisonError::into_inner),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use static_assertions::assert_impl_all;
+
+    use super::*;
+
+    #[test]
+    fn debounced_publisher_is_send_and_sync() {
+        assert_impl_all!(DebouncedPublisher<'_, u32>: Send, Sync);
+        assert_impl_all!(DebouncedPublisher<'_, [u32]>: Send, Sync);
+    }
+
+    #[test]
+    fn window_is_open_until_window_end() {
+        let now = Instant::now();
+
+        let inner = Inner {
+            pending: None,
+            window_end: Some(now + Duration::from_millis(100)),
+            error: None,
+            stopped: false,
+        };
+
+        assert!(inner.is_window_open(now));
+        assert!(!inner.is_window_open(now + Duration::from_millis(100)));
+    }
+}
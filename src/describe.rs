@@ -0,0 +1,277 @@
+//! Methods for collecting diagnostic information on states
+
+use std::fmt::{self, Display, Formatter};
+use std::io;
+
+use crate::data::{ChangeStamp, OpaqueData};
+use crate::state::{BorrowedState, OwnedState, RawState};
+use crate::state_name::{StateName, StateNameDescriptor};
+
+/// A report containing diagnostic information on a state
+///
+/// This is returned by [`OwnedState::describe`] and [`BorrowedState::describe`]. Its [`Display`] implementation
+/// produces a human-readable multi-line report, which is useful e.g. for bug reports.
+///
+/// Note that the security descriptor of a state cannot be queried through the WNF API, so it is not part of the report.
+///
+/// With the `serde` feature enabled, this implements [`serde::Serialize`](https://docs.rs/serde/1/serde/trait.Serialize.html)
+/// and [`serde::Deserialize`](https://docs.rs/serde/1/serde/trait.Deserialize.html), so it can e.g. be exported as JSON.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct StateReport {
+    /// Name of the state
+    pub state_name: StateName,
+
+    /// Descriptor of the state name, or [`None`] if the state name is invalid
+    pub descriptor: Option<StateNameDescriptor>,
+
+    /// Whether the state exists
+    pub exists: bool,
+
+    /// Whether the state has at least one subscriber, or [`None`] if the state does not exist
+    pub subscribers_present: Option<bool>,
+
+    /// Whether the state is quiescent, or [`None`] if the state does not exist
+    pub is_quiescent: Option<bool>,
+
+    /// Current change stamp of the state, or [`None`] if the state does not exist
+    pub change_stamp: Option<ChangeStamp>,
+
+    /// Current size in bytes of the state data, or [`None`] if the state does not exist
+    pub data_size: Option<usize>,
+}
+
+impl Display for StateReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        /// Helper for displaying an optional value, using `-` for [`None`]
+        struct OptionDisplay<T>(Option<T>);
+
+        impl<T> Display for OptionDisplay<T>
+        where
+            T: Display,
+        {
+            fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+                match &self.0 {
+                    Some(value) => value.fmt(f),
+                    None => f.write_str("-"),
+                }
+            }
+        }
+
+        writeln!(f, "State name:          {}", self.state_name)?;
+
+        match self.descriptor {
+            Some(descriptor) => {
+                writeln!(f, "  Version:           {}", descriptor.version)?;
+                writeln!(f, "  Lifetime:          {:?}", descriptor.lifetime)?;
+                writeln!(f, "  Data scope:        {:?}", descriptor.data_scope)?;
+                writeln!(f, "  Permanent data:    {}", descriptor.is_permanent)?;
+                writeln!(f, "  Unique id:         {:#X}", descriptor.unique_id)?;
+                writeln!(
+                    f,
+                    "  Owner tag:         {:#010X} ({})",
+                    descriptor.owner_tag,
+                    OptionDisplay(descriptor.owner_tag_str())
+                )?;
+            }
+            None => writeln!(f, "  (invalid state name)")?,
+        }
+
+        writeln!(f, "Exists:              {}", self.exists)?;
+        writeln!(f, "Subscribers present: {}", OptionDisplay(self.subscribers_present))?;
+        writeln!(f, "Quiescent:           {}", OptionDisplay(self.is_quiescent))?;
+        writeln!(f, "Change stamp:        {}", OptionDisplay(self.change_stamp))?;
+        write!(f, "Data size:           {}", OptionDisplay(self.data_size))
+    }
+}
+
+/// A snapshot of the raw data of a state together with its name, descriptor and change stamp
+///
+/// This is returned by [`OwnedState::snapshot`] and [`BorrowedState::snapshot`]. Since the data are stored as raw
+/// bytes, snapshots of states of different types can be treated uniformly, e.g. when exporting them.
+///
+/// With the `serde` feature enabled, this implements [`serde::Serialize`](https://docs.rs/serde/1/serde/trait.Serialize.html)
+/// and [`serde::Deserialize`](https://docs.rs/serde/1/serde/trait.Deserialize.html), so it can e.g. be exported as JSON.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct StateSnapshot {
+    /// Name of the state
+    pub state_name: StateName,
+
+    /// Descriptor of the state name, or [`None`] if the state name is invalid
+    pub descriptor: Option<StateNameDescriptor>,
+
+    /// Change stamp of the state at the time the snapshot was taken
+    pub change_stamp: ChangeStamp,
+
+    /// Raw data of the state at the time the snapshot was taken
+    pub data: Box<[u8]>,
+}
+
+impl<T> OwnedState<T>
+where
+    T: ?Sized,
+{
+    /// Collects diagnostic information on this state into a [`StateReport`]
+    ///
+    /// This queries the existence, subscriber presence, quiescence, change stamp and data size of the state. If the
+    /// state does not exist, only its existence is queried.
+    ///
+    /// # Errors
+    /// Returns an error if obtaining any of the information fails
+    pub fn describe(&self) -> io::Result<StateReport> {
+        self.raw.describe()
+    }
+
+    /// Takes a [`StateSnapshot`] of the raw data of this state
+    ///
+    /// The data and the change stamp are queried atomically, regardless of the data type of this state.
+    ///
+    /// # Errors
+    /// Returns an error if querying the state data fails
+    pub fn snapshot(&self) -> io::Result<StateSnapshot> {
+        self.raw.snapshot()
+    }
+}
+
+impl<T> BorrowedState<'_, T>
+where
+    T: ?Sized,
+{
+    /// Collects diagnostic information on this state into a [`StateReport`]
+    ///
+    /// See [`OwnedState::describe`]
+    pub fn describe(self) -> io::Result<StateReport> {
+        self.raw.describe()
+    }
+
+    /// Takes a [`StateSnapshot`] of the raw data of this state
+    ///
+    /// See [`OwnedState::snapshot`]
+    pub fn snapshot(self) -> io::Result<StateSnapshot> {
+        self.raw.snapshot()
+    }
+}
+
+impl<T> RawState<T>
+where
+    T: ?Sized,
+{
+    /// Collects diagnostic information on this state into a [`StateReport`]
+    fn describe(self) -> io::Result<StateReport> {
+        let state_name = self.state_name;
+        let descriptor = state_name.try_into().ok();
+
+        if !self.exists()? {
+            return Ok(StateReport {
+                state_name,
+                descriptor,
+                exists: false,
+                subscribers_present: None,
+                is_quiescent: None,
+                change_stamp: None,
+                data_size: None,
+            });
+        }
+
+        let (data, change_stamp) = self.cast::<OpaqueData>().query_as()?.into_data_change_stamp();
+
+        Ok(StateReport {
+            state_name,
+            descriptor,
+            exists: true,
+            subscribers_present: Some(self.subscribers_present()?),
+            is_quiescent: Some(self.is_quiescent()?),
+            change_stamp: Some(change_stamp),
+            data_size: Some(data.size()),
+        })
+    }
+
+    /// Takes a [`StateSnapshot`] of the raw data of this state
+    fn snapshot(self) -> io::Result<StateSnapshot> {
+        let (data, change_stamp) = self.cast::<[u8]>().query_as::<Box<[u8]>>()?.into_data_change_stamp();
+
+        Ok(StateSnapshot {
+            state_name: self.state_name,
+            descriptor: self.state_name.try_into().ok(),
+            change_stamp,
+            data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state_name::{DataScope, StateLifetime};
+
+    #[test]
+    fn display_existing_state() {
+        let descriptor = StateNameDescriptor {
+            version: 1,
+            lifetime: StateLifetime::WellKnown,
+            data_scope: DataScope::Machine,
+            is_permanent: false,
+            unique_id: 0x0000_0012,
+            owner_tag: 0x4C45_4853,
+        };
+
+        let report = StateReport {
+            state_name: descriptor.try_into().unwrap(),
+            descriptor: Some(descriptor),
+            exists: true,
+            subscribers_present: Some(true),
+            is_quiescent: Some(false),
+            change_stamp: Some(ChangeStamp::new(42)),
+            data_size: Some(4),
+        };
+
+        let display = report.to_string();
+
+        assert!(display.contains("Lifetime:          WellKnown"));
+        assert!(display.contains("Owner tag:         0x4C454853 (SHEL)"));
+        assert!(display.contains("Subscribers present: true"));
+        assert!(display.contains("Change stamp:        42"));
+        assert!(display.ends_with("Data size:           4"));
+    }
+
+    #[test]
+    fn display_non_existing_state() {
+        let report = StateReport {
+            state_name: StateName::from_opaque_value(0),
+            descriptor: None,
+            exists: false,
+            subscribers_present: None,
+            is_quiescent: None,
+            change_stamp: None,
+            data_size: None,
+        };
+
+        let display = report.to_string();
+
+        assert!(display.contains("(invalid state name)"));
+        assert!(display.contains("Exists:              false"));
+        assert!(display.contains("Quiescent:           -"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialize_snapshot() {
+        let snapshot = StateSnapshot {
+            state_name: StateName::from_opaque_value(0x0D83_063E_A3BE_5075),
+            descriptor: None,
+            change_stamp: ChangeStamp::new(42),
+            data: Box::new([1, 2]),
+        };
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+
+        assert_eq!(
+            json,
+            r#"{"state_name":973628810547056757,"descriptor":null,"change_stamp":42,"data":[1,2]}"#
+        );
+        assert_eq!(serde_json::from_str::<StateSnapshot>(&json).unwrap(), snapshot);
+    }
+}
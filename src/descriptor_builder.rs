@@ -0,0 +1,342 @@
+//! Building state name descriptors with valid combinations of properties
+
+use std::marker::PhantomData;
+
+use crate::state_name::{DataScope, StateLifetime, StateName, StateNameDescriptor, StateNameFromDescriptorError};
+
+/// A builder for a [`StateNameDescriptor`] that prevents invalid combinations of properties at compile time
+///
+/// Setting the fields of a [`StateNameDescriptor`] directly allows for combinations of properties that don't occur in
+/// valid state names, such as an owner tag for a state name that is not well-known or a temporary state name with
+/// process scope. Converting such a descriptor into a [`StateName`] either fails or produces a state name that cannot
+/// exist. This builder tracks the lifetime and whether the data scope has been set in its type parameters instead, so
+/// that such combinations are rejected by the compiler:
+/// - The lifetime is chosen upfront through [`DescriptorBuilder::well_known`], [`DescriptorBuilder::permanent`],
+///   [`DescriptorBuilder::persistent`] or [`DescriptorBuilder::temporary`], where only the first one takes an owner
+///   tag.
+/// - The data scope must be set through one of the `*_scope` methods before building. The
+///   [`process_scope`](DescriptorBuilder::process_scope) method is not available for temporary state names.
+/// - The [`persist_data`](DescriptorBuilder::persist_data) flag can only be set for well-known and permanent state
+///   names.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use wnf::{DataScope, DescriptorBuilder, StateLifetime};
+///
+/// let descriptor = DescriptorBuilder::permanent()
+///     .process_scope()
+///     .persist_data(true)
+///     .unique_id(42)
+///     .build();
+///
+/// assert_eq!(descriptor.lifetime, StateLifetime::Permanent);
+/// assert_eq!(descriptor.data_scope, DataScope::Process);
+///
+/// let state_name = DescriptorBuilder::temporary()
+///     .machine_scope()
+///     .unique_id(42)
+///     .build_state_name()?;
+/// # let _ = state_name;
+/// # Ok(()) }
+/// ```
+///
+/// Choosing the process scope for a temporary state name does not compile:
+/// ```compile_fail
+/// use wnf::DescriptorBuilder;
+///
+/// let descriptor = DescriptorBuilder::temporary().process_scope().build();
+/// ```
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct DescriptorBuilder<L, S> {
+    lifetime: PhantomData<L>,
+    data_scope: S,
+    is_permanent: bool,
+    unique_id: u32,
+    owner_tag: u32,
+}
+
+/// A marker type for the [`StateLifetime::WellKnown`] lifetime in a [`DescriptorBuilder`]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum WellKnownLifetime {}
+
+/// A marker type for the [`StateLifetime::Permanent`] lifetime in a [`DescriptorBuilder`]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum PermanentLifetime {}
+
+/// A marker type for the [`StateLifetime::Persistent`] lifetime in a [`DescriptorBuilder`]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum PersistentLifetime {}
+
+/// A marker type for the [`StateLifetime::Temporary`] lifetime in a [`DescriptorBuilder`]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum TemporaryLifetime {}
+
+/// Trait for the marker types of lifetimes in a [`DescriptorBuilder`]
+///
+/// This trait is sealed and cannot be implemented outside of `wnf`.
+pub trait DescriptorLifetime: private::Sealed {
+    /// The lifetime represented by this marker type
+    const LIFETIME: StateLifetime;
+}
+
+/// Trait for the marker types of lifetimes whose state names can have the [`DataScope::Process`] data scope
+///
+/// This trait is sealed and cannot be implemented outside of `wnf`.
+pub trait SupportsProcessScope: DescriptorLifetime {}
+
+/// Trait for the marker types of lifetimes whose state names can have persisted data
+///
+/// This trait is sealed and cannot be implemented outside of `wnf`.
+pub trait SupportsPersistData: DescriptorLifetime {}
+
+impl DescriptorLifetime for WellKnownLifetime {
+    const LIFETIME: StateLifetime = StateLifetime::WellKnown;
+}
+
+impl DescriptorLifetime for PermanentLifetime {
+    const LIFETIME: StateLifetime = StateLifetime::Permanent;
+}
+
+impl DescriptorLifetime for PersistentLifetime {
+    const LIFETIME: StateLifetime = StateLifetime::Persistent;
+}
+
+impl DescriptorLifetime for TemporaryLifetime {
+    const LIFETIME: StateLifetime = StateLifetime::Temporary;
+}
+
+impl SupportsProcessScope for WellKnownLifetime {}
+impl SupportsProcessScope for PermanentLifetime {}
+impl SupportsProcessScope for PersistentLifetime {}
+
+impl SupportsPersistData for WellKnownLifetime {}
+impl SupportsPersistData for PermanentLifetime {}
+
+impl<L> DescriptorBuilder<L, ()> {
+    /// Creates a [`DescriptorBuilder`] with all properties other than the lifetime and the owner tag unset
+    const fn with_owner_tag(owner_tag: u32) -> Self {
+        Self {
+            lifetime: PhantomData,
+            data_scope: (),
+            is_permanent: false,
+            unique_id: 0,
+            owner_tag,
+        }
+    }
+}
+
+impl DescriptorBuilder<WellKnownLifetime, ()> {
+    /// Creates a [`DescriptorBuilder`] for a well-known state name with the given owner tag
+    ///
+    /// See [`StateNameDescriptor::owner_tag`]
+    pub const fn well_known(owner_tag: u32) -> Self {
+        Self::with_owner_tag(owner_tag)
+    }
+}
+
+impl DescriptorBuilder<PermanentLifetime, ()> {
+    /// Creates a [`DescriptorBuilder`] for a permanent state name
+    pub const fn permanent() -> Self {
+        Self::with_owner_tag(0)
+    }
+}
+
+impl DescriptorBuilder<PersistentLifetime, ()> {
+    /// Creates a [`DescriptorBuilder`] for a persistent state name
+    pub const fn persistent() -> Self {
+        Self::with_owner_tag(0)
+    }
+}
+
+impl DescriptorBuilder<TemporaryLifetime, ()> {
+    /// Creates a [`DescriptorBuilder`] for a temporary state name
+    pub const fn temporary() -> Self {
+        Self::with_owner_tag(0)
+    }
+}
+
+impl<L> DescriptorBuilder<L, ()>
+where
+    L: DescriptorLifetime,
+{
+    /// Sets the data scope to [`DataScope::System`]
+    pub const fn system_scope(self) -> DescriptorBuilder<L, DataScope> {
+        self.data_scope(DataScope::System)
+    }
+
+    /// Sets the data scope to [`DataScope::Session`]
+    pub const fn session_scope(self) -> DescriptorBuilder<L, DataScope> {
+        self.data_scope(DataScope::Session)
+    }
+
+    /// Sets the data scope to [`DataScope::User`]
+    pub const fn user_scope(self) -> DescriptorBuilder<L, DataScope> {
+        self.data_scope(DataScope::User)
+    }
+
+    /// Sets the data scope to [`DataScope::Machine`]
+    pub const fn machine_scope(self) -> DescriptorBuilder<L, DataScope> {
+        self.data_scope(DataScope::Machine)
+    }
+
+    /// Sets the data scope to [`DataScope::PhysicalMachine`]
+    pub const fn physical_machine_scope(self) -> DescriptorBuilder<L, DataScope> {
+        self.data_scope(DataScope::PhysicalMachine)
+    }
+
+    /// Sets the data scope to the given one
+    const fn data_scope(self, data_scope: DataScope) -> DescriptorBuilder<L, DataScope> {
+        DescriptorBuilder {
+            lifetime: PhantomData,
+            data_scope,
+            is_permanent: self.is_permanent,
+            unique_id: self.unique_id,
+            owner_tag: self.owner_tag,
+        }
+    }
+}
+
+impl<L> DescriptorBuilder<L, ()>
+where
+    L: SupportsProcessScope,
+{
+    /// Sets the data scope to [`DataScope::Process`]
+    ///
+    /// This method is not available for temporary state names, see [`StateLifetime::supports_data_scope`].
+    pub const fn process_scope(self) -> DescriptorBuilder<L, DataScope> {
+        self.data_scope(DataScope::Process)
+    }
+}
+
+impl<L, S> DescriptorBuilder<L, S> {
+    /// Sets the unique sequence number
+    ///
+    /// This defaults to `0`. Note that it must be less than `2^21`, which is checked by
+    /// [`build_state_name`](DescriptorBuilder::build_state_name).
+    pub const fn unique_id(mut self, unique_id: u32) -> Self {
+        self.unique_id = unique_id;
+        self
+    }
+}
+
+impl<L, S> DescriptorBuilder<L, S>
+where
+    L: SupportsPersistData,
+{
+    /// Sets whether the state data are persisted across system reboots
+    ///
+    /// This defaults to `false`. It is only available for well-known and permanent state names.
+    ///
+    /// See [`StateNameDescriptor::is_permanent`]
+    pub const fn persist_data(mut self, persist_data: bool) -> Self {
+        self.is_permanent = persist_data;
+        self
+    }
+}
+
+impl<L> DescriptorBuilder<L, DataScope>
+where
+    L: DescriptorLifetime,
+{
+    /// Builds the [`StateNameDescriptor`]
+    ///
+    /// The version of the descriptor is always `1`.
+    pub const fn build(self) -> StateNameDescriptor {
+        StateNameDescriptor {
+            version: 1,
+            lifetime: L::LIFETIME,
+            data_scope: self.data_scope,
+            is_permanent: self.is_permanent,
+            unique_id: self.unique_id,
+            owner_tag: self.owner_tag,
+        }
+    }
+
+    /// Builds the [`StateNameDescriptor`] and converts it into a [`StateName`]
+    ///
+    /// # Errors
+    /// Returns an error if the unique id is invalid (must be less than `2^21`)
+    pub fn build_state_name(self) -> Result<StateName, StateNameFromDescriptorError> {
+        self.build().try_into()
+    }
+}
+
+/// Making [`DescriptorLifetime`] a sealed trait
+mod private {
+    use super::*;
+
+    pub trait Sealed {}
+
+    impl Sealed for WellKnownLifetime {}
+    impl Sealed for PermanentLifetime {}
+    impl Sealed for PersistentLifetime {}
+    impl Sealed for TemporaryLifetime {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_well_known() {
+        let descriptor = DescriptorBuilder::well_known(0x4C45_4853)
+            .system_scope()
+            .unique_id(0x4A)
+            .build();
+
+        assert_eq!(
+            descriptor,
+            StateNameDescriptor {
+                version: 1,
+                lifetime: StateLifetime::WellKnown,
+                data_scope: DataScope::System,
+                is_permanent: false,
+                unique_id: 0x4A,
+                owner_tag: 0x4C45_4853,
+            }
+        );
+
+        assert_eq!(
+            DescriptorBuilder::well_known(0x4C45_4853)
+                .system_scope()
+                .unique_id(0x4A)
+                .build_state_name(),
+            Ok(StateName::from_opaque_value(0x0D83_063E_A3BE_5075))
+        );
+    }
+
+    #[test]
+    fn build_permanent_with_persisted_data() {
+        let descriptor = DescriptorBuilder::permanent()
+            .persist_data(true)
+            .process_scope()
+            .build();
+
+        assert_eq!(descriptor.lifetime, StateLifetime::Permanent);
+        assert_eq!(descriptor.data_scope, DataScope::Process);
+        assert!(descriptor.is_permanent);
+        assert_eq!(descriptor.owner_tag, 0);
+    }
+
+    #[test]
+    fn build_temporary() {
+        let descriptor = DescriptorBuilder::temporary().user_scope().build();
+
+        assert_eq!(descriptor.lifetime, StateLifetime::Temporary);
+        assert_eq!(descriptor.data_scope, DataScope::User);
+        assert!(!descriptor.is_permanent);
+        assert_eq!(descriptor.unique_id, 0);
+    }
+
+    #[test]
+    fn build_state_name_invalid_unique_id() {
+        assert_eq!(
+            DescriptorBuilder::persistent()
+                .machine_scope()
+                .unique_id(1 << 21)
+                .build_state_name(),
+            Err(StateNameFromDescriptorError::InvalidUniqueId(1 << 21))
+        );
+    }
+}
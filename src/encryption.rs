@@ -0,0 +1,323 @@
+//! Storing encrypted data in states
+
+use std::fmt::{self, Debug, Formatter};
+use std::io::{self, ErrorKind};
+use std::marker::PhantomData;
+use std::{mem, ptr, slice};
+
+use windows::core::PCWSTR;
+use windows::Win32::Foundation::{LocalFree, HLOCAL};
+use windows::Win32::Security::Cryptography::{
+    CryptProtectData, CryptUnprotectData, CRYPTPROTECT_LOCAL_MACHINE, CRYPTPROTECT_UI_FORBIDDEN, CRYPT_INTEGER_BLOB,
+};
+
+use crate::bytes::NoUninit;
+use crate::read::Read;
+use crate::state::{AsState, BorrowedState};
+use crate::wipe;
+
+/// The scope of the key used to encrypt the data of an [`EncryptedState<'a, T>`](EncryptedState)
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum ProtectionScope {
+    /// The data can only be decrypted by the user who encrypted them
+    ///
+    /// Note that this requires the user to have a loaded user profile, which is not the case for some service
+    /// accounts.
+    #[default]
+    CurrentUser,
+
+    /// The data can be decrypted by any user on the machine on which they were encrypted
+    ///
+    /// This protects the data from being read on other machines (e.g. from a copy of the registry), but not from
+    /// other users on the same machine.
+    LocalMachine,
+}
+
+impl ProtectionScope {
+    /// Returns the flags to be passed to `CryptProtectData` for this scope
+    const fn flags(self) -> u32 {
+        match self {
+            Self::CurrentUser => CRYPTPROTECT_UI_FORBIDDEN,
+            Self::LocalMachine => CRYPTPROTECT_UI_FORBIDDEN | CRYPTPROTECT_LOCAL_MACHINE,
+        }
+    }
+}
+
+/// A view of a state whose data are stored in encrypted form
+///
+/// The data of permanent and persistent states are stored in the registry, where they may be readable by other users
+/// on the machine depending on the security descriptor of the state. An [`EncryptedState<'a, T>`](EncryptedState)
+/// transparently encrypts data of type `T` using the Windows Data Protection API (DPAPI) before updating the underlying
+/// state and decrypts them when querying it.
+///
+/// The data of the underlying state are the output of
+/// [`CryptProtectData`](https://learn.microsoft.com/en-us/windows/win32/api/dpapi/nf-dpapi-cryptprotectdata), which
+/// includes an integrity check. Who can decrypt them depends on the [`ProtectionScope`] used for encryption, which is
+/// recorded in the encrypted data themselves.
+///
+/// The underlying state can have any data type, its data are treated as raw bytes. Note that the type id of the
+/// underlying state (if any) is used when updating it.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use wnf::{EncryptedState, OwnedState};
+///
+/// let state = OwnedState::<[u8]>::create_temporary()?;
+/// let encrypted_state = EncryptedState::<u32>::new(&state);
+///
+/// encrypted_state.set(&42)?;
+///
+/// assert_eq!(encrypted_state.get()?, 42);
+/// # Ok(()) }
+/// ```
+pub struct EncryptedState<'a, T>
+where
+    T: ?Sized,
+{
+    state: BorrowedState<'a, [u8]>,
+    scope: ProtectionScope,
+    _marker: PhantomData<fn(T) -> T>,
+}
+
+impl<'a, T> EncryptedState<'a, T>
+where
+    T: ?Sized,
+{
+    /// Creates a new [`EncryptedState<'a, T>`](EncryptedState) storing data in the given state encrypted with the
+    /// [`ProtectionScope::CurrentUser`] scope
+    pub fn new<S>(state: &'a S) -> Self
+    where
+        S: AsState,
+    {
+        Self::with_scope(state, ProtectionScope::CurrentUser)
+    }
+
+    /// Creates a new [`EncryptedState<'a, T>`](EncryptedState) storing data in the given state encrypted with the
+    /// given scope
+    ///
+    /// The scope is only used for encryption. Decryption works for data encrypted with any scope, as long as the
+    /// current user is allowed to decrypt them.
+    pub fn with_scope<S>(state: &'a S, scope: ProtectionScope) -> Self
+    where
+        S: AsState,
+    {
+        Self {
+            state: state.as_state().cast(),
+            scope,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the underlying state containing the encrypted data
+    pub const fn state(&self) -> BorrowedState<'a, [u8]> {
+        self.state
+    }
+
+    /// Returns the scope used for encrypting data
+    pub const fn scope(&self) -> ProtectionScope {
+        self.scope
+    }
+
+    /// Queries the data of the underlying state, decrypts them and reads them using the given function
+    fn get_decrypted<D>(&self, read: impl FnOnce(&[u8]) -> io::Result<D>) -> io::Result<D> {
+        let mut buffer = unprotect(&self.state.get_boxed()?)?;
+        let result = read(&buffer);
+        wipe::wipe_vec(&mut buffer);
+        result
+    }
+}
+
+impl<T> EncryptedState<'_, T>
+where
+    T: Read<T>,
+{
+    /// Queries the data of the underlying state and decrypts them
+    ///
+    /// # Errors
+    /// Returns an error if querying fails, if decrypting fails (e.g. because the data were encrypted by a different
+    /// user or have been tampered with) or if the decrypted data are not a valid `T`
+    pub fn get(&self) -> io::Result<T> {
+        self.get_decrypted(|buffer| {
+            // SAFETY:
+            // `buffer` is a slice, so `buffer.as_ptr()` is valid for reads of size `buffer.len()` and the memory range
+            // is initialized
+            unsafe { T::from_buffer(buffer.as_ptr().cast(), buffer.len()) }
+        })
+    }
+}
+
+impl<T> EncryptedState<'_, T>
+where
+    T: Read<Box<T>> + ?Sized,
+{
+    /// Queries the data of the underlying state and decrypts them as a box
+    ///
+    /// # Errors
+    /// See [`EncryptedState::get`]
+    pub fn get_boxed(&self) -> io::Result<Box<T>> {
+        self.get_decrypted(|buffer| {
+            // SAFETY:
+            // `buffer` is a slice, so `buffer.as_ptr()` is valid for reads of size `buffer.len()` and the memory range
+            // is initialized
+            unsafe { T::from_buffer(buffer.as_ptr().cast(), buffer.len()) }
+        })
+    }
+}
+
+impl<T> EncryptedState<'_, T>
+where
+    T: NoUninit + ?Sized,
+{
+    /// Encrypts the given data and updates the underlying state with them
+    ///
+    /// # Errors
+    /// Returns an error if encrypting fails or if updating fails, e.g. because the encrypted data exceed the maximum
+    /// state size
+    pub fn set(&self, data: &T) -> io::Result<()> {
+        // SAFETY:
+        // - `data` is a reference to a `T`, so it is valid for reads of size `mem::size_of_val(data)`
+        // - `T: NoUninit` guarantees that the memory range of size `mem::size_of_val(data)` starting at `data` is
+        //   initialized
+        let bytes = unsafe { slice::from_raw_parts((data as *const T).cast::<u8>(), mem::size_of_val(data)) };
+
+        self.state.set(protect(bytes, self.scope)?.as_slice())
+    }
+}
+
+impl<T> Clone for EncryptedState<'_, T>
+where
+    T: ?Sized,
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for EncryptedState<'_, T> where T: ?Sized {}
+
+// We cannot derive this because that would impose an unnecessary trait bound `T: Debug`
+impl<T> Debug for EncryptedState<'_, T>
+where
+    T: ?Sized,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptedState")
+            .field("state", &self.state)
+            .field("scope", &self.scope)
+            .finish()
+    }
+}
+
+/// Encrypts the given bytes using `CryptProtectData` with the given scope
+fn protect(bytes: &[u8], scope: ProtectionScope) -> io::Result<LocalBlob> {
+    let input = input_blob(bytes)?;
+    let mut output = CRYPT_INTEGER_BLOB::default();
+
+    // SAFETY:
+    // - The pointer in the first argument points to a valid `CRYPT_INTEGER_BLOB` because it comes from a live
+    //   reference, and its `pbData` is valid for reads of size `cbData` because it comes from `bytes`
+    // - The pointer in the last argument is valid for writes of `CRYPT_INTEGER_BLOB` because it comes from a live
+    //   mutable reference
+    unsafe { CryptProtectData(&input, PCWSTR::null(), None, None, None, scope.flags(), &mut output) }?;
+
+    Ok(LocalBlob(output))
+}
+
+/// Decrypts the given bytes using `CryptUnprotectData`
+fn unprotect(bytes: &[u8]) -> io::Result<Vec<u8>> {
+    let input = input_blob(bytes)?;
+    let mut output = CRYPT_INTEGER_BLOB::default();
+
+    // SAFETY:
+    // - The pointer in the first argument points to a valid `CRYPT_INTEGER_BLOB` because it comes from a live
+    //   reference, and its `pbData` is valid for reads of size `cbData` because it comes from `bytes`
+    // - The pointer in the last argument is valid for writes of `CRYPT_INTEGER_BLOB` because it comes from a live
+    //   mutable reference
+    unsafe { CryptUnprotectData(&input, None, None, None, None, CRYPTPROTECT_UI_FORBIDDEN, &mut output) }?;
+
+    let mut output = LocalBlob(output);
+    let decrypted = output.as_slice().to_vec();
+    wipe::wipe_slice(output.as_mut_slice());
+
+    Ok(decrypted)
+}
+
+/// Creates a [`CRYPT_INTEGER_BLOB`] referring to the given bytes
+///
+/// The DPAPI functions never write through the `pbData` pointer of their input blob, so it is fine to derive it from a
+/// shared reference.
+fn input_blob(bytes: &[u8]) -> io::Result<CRYPT_INTEGER_BLOB> {
+    Ok(CRYPT_INTEGER_BLOB {
+        cbData: bytes
+            .len()
+            .try_into()
+            .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "data are too large to be encrypted"))?,
+        pbData: bytes.as_ptr().cast_mut(),
+    })
+}
+
+/// A [`CRYPT_INTEGER_BLOB`] allocated by a DPAPI function, which is freed using [`LocalFree`] when dropped
+struct LocalBlob(CRYPT_INTEGER_BLOB);
+
+impl LocalBlob {
+    /// Returns the data of this blob as a slice
+    fn as_slice(&self) -> &[u8] {
+        if self.0.pbData.is_null() {
+            &[]
+        } else {
+            // SAFETY:
+            // `self.0.pbData` is valid for reads of size `self.0.cbData` and the memory range is initialized because
+            // it was returned from a successful call to a DPAPI function
+            unsafe { slice::from_raw_parts(self.0.pbData, self.0.cbData as usize) }
+        }
+    }
+
+    /// Returns the data of this blob as a mutable slice
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        if self.0.pbData.is_null() {
+            &mut []
+        } else {
+            // SAFETY:
+            // `self.0.pbData` is valid for reads and writes of size `self.0.cbData` and the memory range is
+            // initialized because it was allocated by a successful call to a DPAPI function and is exclusively owned
+            // by this `LocalBlob`
+            unsafe { slice::from_raw_parts_mut(self.0.pbData, self.0.cbData as usize) }
+        }
+    }
+}
+
+impl Drop for LocalBlob {
+    fn drop(&mut self) {
+        if !self.0.pbData.is_null() {
+            // SAFETY:
+            // - `self.0.pbData` points to a local memory object because it was returned from a DPAPI function
+            // - `self.0.pbData` has not been freed yet
+            unsafe { LocalFree(Some(HLOCAL(self.0.pbData.cast()))) };
+            self.0.pbData = ptr::null_mut();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use static_assertions::assert_impl_all;
+
+    use super::*;
+
+    #[test]
+    fn encrypted_state_is_send_and_sync_regardless_of_data_type() {
+        type NeitherSendNorSync = *const ();
+
+        assert_impl_all!(EncryptedState<'_, NeitherSendNorSync>: Send, Sync);
+    }
+
+    #[test]
+    fn protection_scope_flags() {
+        assert_eq!(ProtectionScope::CurrentUser.flags(), CRYPTPROTECT_UI_FORBIDDEN);
+        assert_eq!(
+            ProtectionScope::LocalMachine.flags(),
+            CRYPTPROTECT_UI_FORBIDDEN | CRYPTPROTECT_LOCAL_MACHINE
+        );
+    }
+}
@@ -0,0 +1,296 @@
+//! Decoding state data from ETW traces
+//!
+//! Tools analyzing Event Tracing for Windows (ETW) traces offline often need to interpret the state data contained in
+//! WNF-related events. This module makes it possible to reuse the typed decoding of this crate for that purpose, so
+//! that the same data types can be shared between live consumers of a state and analysis tools:
+//! - [`decode_event`] decodes the payload of an event into a [`TraceEvent<'_>`](TraceEvent) consisting of a state name
+//!   and the raw state data.
+//! - [`TraceEvent::decode`] and [`TraceEvent::decode_boxed`] read typed values from the state data, the former through
+//!   the [`DecodableFromTrace`] trait.
+//!
+//! This module doesn't call into the operating system, so it is also available with the `core_only` feature on
+//! targets other than Windows.
+//!
+//! # Payload layout
+//! The payload of an event is expected to consist of the following fields, without any padding:
+//! - the opaque value of the state name as a little-endian `u64`
+//! - the size in bytes of the state data as a little-endian `u32`
+//! - the state data
+//!
+//! # Example
+//! ```
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use wnf::etw;
+//!
+//! let mut payload = Vec::new();
+//! payload.extend_from_slice(&0x0D83_063E_A3BE_5075_u64.to_le_bytes());
+//! payload.extend_from_slice(&4_u32.to_le_bytes());
+//! payload.extend_from_slice(&42_u32.to_le_bytes());
+//!
+//! let event = etw::decode_event(&payload)?;
+//! assert_eq!(event.state_name(), 0x0D83_063E_A3BE_5075);
+//! assert_eq!(event.decode::<u32>()?, 42);
+//! # Ok(()) }
+//! ```
+
+use std::io::{self, ErrorKind};
+
+use thiserror::Error;
+
+use crate::read::{read_boxed_from_bytes, read_from_bytes, Read};
+use crate::state_name::{StateName, StateNameDescriptor, StateNameDescriptorFromStateNameError};
+
+/// The size in bytes of the header of an event payload, consisting of the state name and the size of the state data
+const HEADER_SIZE: usize = 12;
+
+/// Decodes the payload of a WNF-related ETW event
+///
+/// See the [module-level documentation](self) for the expected payload layout. This only decodes the state name and
+/// the raw state data. Typed values can then be read through [`TraceEvent::decode`] and [`TraceEvent::decode_boxed`].
+///
+/// # Errors
+/// Returns an error if the payload is malformed, in which case [`io::Error::kind`] returns [`ErrorKind::InvalidData`]
+/// and the error wraps a [`DecodeEventError`]
+pub fn decode_event(bytes: &[u8]) -> io::Result<TraceEvent<'_>> {
+    if bytes.len() < HEADER_SIZE {
+        return Err(DecodeEventError::MissingHeader { actual: bytes.len() }.into());
+    }
+
+    let (header, data) = bytes.split_at(HEADER_SIZE);
+    let (state_name_bytes, size_bytes) = header.split_at(8);
+
+    // Both conversions succeed because the header has a size of `HEADER_SIZE`
+    let state_name = StateName::from_opaque_value(u64::from_le_bytes(state_name_bytes.try_into().unwrap()));
+    let size = u32::from_le_bytes(size_bytes.try_into().unwrap()) as usize;
+
+    if data.len() != size {
+        return Err(DecodeEventError::WrongDataSize {
+            expected: size,
+            actual: data.len(),
+        }
+        .into());
+    }
+
+    Ok(TraceEvent { state_name, data })
+}
+
+/// A decoded WNF-related ETW event
+///
+/// This is returned from [`decode_event`]. It borrows the state data from the event payload.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct TraceEvent<'a> {
+    state_name: StateName,
+    data: &'a [u8],
+}
+
+impl<'a> TraceEvent<'a> {
+    /// Creates a new [`TraceEvent<'a>`](TraceEvent) from the given state name and raw state data
+    ///
+    /// This is useful if the state name and the state data are available as separate event properties rather than as
+    /// a single payload.
+    pub const fn new(state_name: StateName, data: &'a [u8]) -> Self {
+        Self { state_name, data }
+    }
+
+    /// Returns the state name of this event
+    pub const fn state_name(&self) -> StateName {
+        self.state_name
+    }
+
+    /// Returns the [`StateNameDescriptor`] of the state name of this event
+    ///
+    /// # Errors
+    /// Returns an error if the state name cannot be converted into a [`StateNameDescriptor`]
+    pub fn descriptor(&self) -> Result<StateNameDescriptor, StateNameDescriptorFromStateNameError> {
+        self.state_name.try_into()
+    }
+
+    /// Returns the raw state data of this event
+    pub const fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Decodes a value of type `T` from this event
+    ///
+    /// For types implementing [`AnyBitPattern`](crate::AnyBitPattern) or
+    /// [`CheckedBitPattern`](crate::CheckedBitPattern), this reads the state data in the same way as querying a state
+    /// would. In order to produce a `Box<T>` for `T: ?Sized`, use the [`decode_boxed`](TraceEvent::decode_boxed)
+    /// method.
+    ///
+    /// # Errors
+    /// Returns an error if decoding fails, see [`DecodableFromTrace::decode_from_trace`]
+    pub fn decode<T>(&self) -> io::Result<T>
+    where
+        T: DecodableFromTrace,
+    {
+        T::decode_from_trace(self)
+    }
+
+    /// Decodes a value of type `T` from this event as a box
+    ///
+    /// This is the same as [`decode`](TraceEvent::decode), except that it produces a [`Box<T>`] instead of an owned
+    /// `T` (requiring `T: Sized`).
+    ///
+    /// # Errors
+    /// Returns an error if the state data are not a valid `T`, in which case [`io::Error::kind`] returns
+    /// [`ErrorKind::InvalidData`] and the error wraps a [`ReadError`](crate::ReadError)
+    pub fn decode_boxed<T>(&self) -> io::Result<Box<T>>
+    where
+        T: Read<Box<T>> + ?Sized,
+    {
+        read_boxed_from_bytes(self.data)
+    }
+}
+
+/// Trait for types that can be decoded from a WNF-related ETW event
+///
+/// This is implemented for all types that can be read from the data of a state, i.e. all types `T` with
+/// [`T: Read<T>`](crate::Read), by reading the state data of the event. Analysis tools can implement it for their own
+/// types, e.g. in order to decode the data of different states into different variants of an enum based on
+/// [`TraceEvent::state_name`].
+///
+/// # Example
+/// ```
+/// use std::io;
+///
+/// use wnf::etw::{DecodableFromTrace, TraceEvent};
+/// use wnf::StateName;
+///
+/// const COUNTER: StateName = StateName::from_opaque_value(0x0D83_063E_A3BE_5075);
+///
+/// enum Update {
+///     Counter(u32),
+///     Other(Vec<u8>),
+/// }
+///
+/// impl DecodableFromTrace for Update {
+///     fn decode_from_trace(event: &TraceEvent<'_>) -> io::Result<Self> {
+///         Ok(if event.state_name() == COUNTER {
+///             Update::Counter(event.decode()?)
+///         } else {
+///             Update::Other(event.data().to_vec())
+///         })
+///     }
+/// }
+/// ```
+pub trait DecodableFromTrace: Sized {
+    /// Decodes a value of this type from the given event
+    ///
+    /// # Errors
+    /// Returns an error if the event does not contain a valid value of this type. For the implementation for types
+    /// `T: Read<T>`, [`io::Error::kind`] then returns [`ErrorKind::InvalidData`] and the error wraps a
+    /// [`ReadError`](crate::ReadError).
+    fn decode_from_trace(event: &TraceEvent<'_>) -> io::Result<Self>;
+}
+
+impl<T> DecodableFromTrace for T
+where
+    T: Read<T>,
+{
+    fn decode_from_trace(event: &TraceEvent<'_>) -> io::Result<Self> {
+        read_from_bytes(event.data)
+    }
+}
+
+/// An error decoding the payload of a WNF-related ETW event
+#[derive(Clone, Copy, Debug, Eq, Error, Hash, PartialEq)]
+pub enum DecodeEventError {
+    /// The payload is too short to contain a state name and the size of the state data
+    #[error("failed to decode event: payload is too short (expected at least {HEADER_SIZE}, got {actual})")]
+    MissingHeader {
+        /// The actual size in bytes of the payload
+        actual: usize,
+    },
+
+    /// The size of the state data doesn't match the size given in the payload
+    #[error("failed to decode event: state data has wrong size (expected {expected}, got {actual})")]
+    WrongDataSize {
+        /// The size in bytes of the state data given in the payload
+        expected: usize,
+
+        /// The actual size in bytes of the state data
+        actual: usize,
+    },
+}
+
+impl From<DecodeEventError> for io::Error {
+    fn from(err: DecodeEventError) -> Self {
+        io::Error::new(ErrorKind::InvalidData, err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::read::ReadError;
+
+    const SAMPLE_STATE_NAME: StateName = StateName::from_opaque_value(0x0D83_063E_A3BE_5075);
+
+    fn payload(state_name: StateName, size: u32, data: &[u8]) -> Vec<u8> {
+        let mut payload = Vec::new();
+        payload.extend_from_slice(&state_name.opaque_value().to_le_bytes());
+        payload.extend_from_slice(&size.to_le_bytes());
+        payload.extend_from_slice(data);
+        payload
+    }
+
+    #[test]
+    fn decode_event_success() {
+        let payload = payload(SAMPLE_STATE_NAME, 4, &[0x2A, 0x00, 0x00, 0x00]);
+
+        let event = decode_event(&payload).unwrap();
+
+        assert_eq!(event.state_name(), SAMPLE_STATE_NAME);
+        assert_eq!(event.data(), [0x2A, 0x00, 0x00, 0x00]);
+        assert_eq!(event.descriptor().unwrap().owner_tag_str().as_deref(), Some("SHEL"));
+        assert_eq!(event.decode::<u32>().unwrap(), 42);
+        assert_eq!(*event.decode_boxed::<[u16]>().unwrap(), [42, 0]);
+    }
+
+    #[test]
+    fn decode_event_empty_data() {
+        let payload = payload(SAMPLE_STATE_NAME, 0, &[]);
+
+        let event = decode_event(&payload).unwrap();
+
+        assert_eq!(event, TraceEvent::new(SAMPLE_STATE_NAME, &[]));
+        event.decode::<()>().unwrap();
+    }
+
+    #[test]
+    fn decode_event_missing_header() {
+        let result = decode_event(&[0; HEADER_SIZE - 1]);
+
+        assert_eq!(
+            result.unwrap_err().into_inner().unwrap().downcast_ref(),
+            Some(&DecodeEventError::MissingHeader {
+                actual: HEADER_SIZE - 1
+            })
+        );
+    }
+
+    #[test]
+    fn decode_event_wrong_data_size() {
+        let payload = payload(SAMPLE_STATE_NAME, 4, &[0x2A, 0x00]);
+
+        let result = decode_event(&payload);
+
+        assert_eq!(
+            result.unwrap_err().into_inner().unwrap().downcast_ref(),
+            Some(&DecodeEventError::WrongDataSize { expected: 4, actual: 2 })
+        );
+    }
+
+    #[test]
+    fn decode_wrong_type() {
+        let payload = payload(SAMPLE_STATE_NAME, 2, &[0x2A, 0x00]);
+        let event = decode_event(&payload).unwrap();
+
+        let result = event.decode::<u32>();
+
+        assert_eq!(
+            result.unwrap_err().into_inner().unwrap().downcast_ref(),
+            Some(&ReadError::WrongSize { expected: 4, actual: 2 })
+        );
+    }
+}
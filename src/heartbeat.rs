@@ -0,0 +1,277 @@
+//! Publishing periodic heartbeats to a state as a liveness signal
+
+use std::marker::PhantomData;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::{io, panic};
+
+use crate::bytes::{AnyBitPattern, NoUninit};
+use crate::state::{AsState, RawState};
+
+/// The data of a state a [`HeartbeatPublisher`] publishes to
+///
+/// A heartbeat consists of a sequence number that is incremented with every heartbeat and the time at which the
+/// heartbeat was published. It has a fixed layout of two little-endian (on all platforms supported by WNF) `u64`
+/// values, the sequence number followed by the number of milliseconds since the UNIX epoch, so it can also be read by
+/// consumers not using this crate.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[repr(C)]
+pub struct Heartbeat {
+    sequence_number: u64,
+    unix_time_millis: u64,
+}
+
+// SAFETY:
+// `Heartbeat` is `#[repr(C)]` and consists of two `u64` values, so it has no padding and any bit pattern is valid
+unsafe impl AnyBitPattern for Heartbeat {}
+
+// SAFETY:
+// `Heartbeat` is `#[repr(C)]` and consists of two `u64` values, so it has no padding
+unsafe impl NoUninit for Heartbeat {}
+
+impl Heartbeat {
+    /// Creates a new [`Heartbeat`] with the given sequence number, published at the given time
+    ///
+    /// Times before the UNIX epoch are clamped to the UNIX epoch and times too far in the future are clamped to the
+    /// maximum representable time.
+    pub fn new(sequence_number: u64, published_at: SystemTime) -> Self {
+        let unix_time_millis = published_at
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_millis().try_into().unwrap_or(u64::MAX));
+
+        Self {
+            sequence_number,
+            unix_time_millis,
+        }
+    }
+
+    /// Returns the sequence number of this heartbeat
+    pub const fn sequence_number(self) -> u64 {
+        self.sequence_number
+    }
+
+    /// Returns the time at which this heartbeat was published, with millisecond precision
+    pub fn published_at(self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(self.unix_time_millis)
+    }
+}
+
+/// A publisher of periodic heartbeats to a state
+///
+/// Services can use a state as a liveness signal for a watchdog by regularly updating it with a [`Heartbeat`]. A
+/// [`HeartbeatPublisher`] does this on a background thread, which is stopped when the [`HeartbeatPublisher`] is
+/// dropped.
+///
+/// Heartbeats are scheduled at fixed deadlines rather than by sleeping for the interval after each heartbeat, so
+/// delays in publishing a single heartbeat do not accumulate over time. If the background thread falls behind by more
+/// than a whole interval, e.g. because the system was suspended, the missed heartbeats are skipped instead of being
+/// published in a burst, and the schedule is restarted from the current time.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use std::time::Duration;
+///
+/// use wnf::{Heartbeat, HeartbeatPublisher, OwnedState};
+///
+/// let state = OwnedState::<Heartbeat>::create_temporary()?;
+/// let publisher = HeartbeatPublisher::new(&state, Duration::from_millis(100))?;
+///
+/// let heartbeat = state.get()?;
+/// assert_eq!(heartbeat.sequence_number(), 0);
+///
+/// publisher.stop()?;
+/// # Ok(()) }
+/// ```
+#[must_use = "a `HeartbeatPublisher` is stopped immediately if it is not used"]
+#[derive(Debug)]
+pub struct HeartbeatPublisher<'a> {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<io::Result<()>>>,
+    interval: Duration,
+    _marker: PhantomData<&'a ()>,
+}
+
+impl<'a> HeartbeatPublisher<'a> {
+    /// Starts publishing heartbeats to the given state in the given interval
+    ///
+    /// The first heartbeat (with sequence number zero) is published before this returns, so a failure to update the
+    /// state is reported immediately. Subsequent heartbeats are published on a background thread.
+    ///
+    /// # Panics
+    /// Panics if `interval` is zero
+    ///
+    /// # Errors
+    /// Returns an error if publishing the first heartbeat fails or if spawning the background thread fails
+    pub fn new<S>(state: &'a S, interval: Duration) -> io::Result<Self>
+    where
+        S: AsState<Data = Heartbeat>,
+    {
+        assert!(!interval.is_zero(), "heartbeat interval must not be zero");
+
+        let state = state.as_state().raw;
+        state.set(&Heartbeat::new(0, SystemTime::now()))?;
+
+        let shared = Arc::new(Shared {
+            stopped: Mutex::new(false),
+            condvar: Condvar::new(),
+        });
+
+        let thread = thread::Builder::new().name("wnf-heartbeat".into()).spawn({
+            let shared = Arc::clone(&shared);
+            move || publish(state, &shared, interval)
+        })?;
+
+        Ok(Self {
+            shared,
+            thread: Some(thread),
+            interval,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Returns the interval in which heartbeats are published
+    pub const fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Stops publishing heartbeats
+    ///
+    /// This is called automatically when the [`HeartbeatPublisher`] is dropped, but calling it explicitly makes it
+    /// possible to handle errors. This blocks until the background thread has finished.
+    ///
+    /// # Errors
+    /// Returns the error that made the background thread stop early if publishing a heartbeat failed
+    pub fn stop(mut self) -> io::Result<()> {
+        self.stop_internal()
+    }
+
+    /// Stops publishing heartbeats without consuming the [`HeartbeatPublisher`]
+    fn stop_internal(&mut self) -> io::Result<()> {
+        *self.shared.lock_stopped() = true;
+        self.shared.condvar.notify_all();
+
+        match self.thread.take() {
+            Some(thread) => thread.join().unwrap_or_else(|payload| panic::resume_unwind(payload)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for HeartbeatPublisher<'_> {
+    fn drop(&mut self) {
+        let _ = self.stop_internal();
+    }
+}
+
+/// The state shared between a [`HeartbeatPublisher`] and its background thread
+#[derive(Debug)]
+struct Shared {
+    stopped: Mutex<bool>,
+    condvar: Condvar,
+}
+
+impl Shared {
+    /// Waits until the given deadline, returning early with `true` if the publisher has been stopped
+    fn wait_stopped_until(&self, deadline: Instant) -> bool {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+
+        let (stopped, _) = self
+            .condvar
+            .wait_timeout_while(self.lock_stopped(), timeout, |stopped| !*stopped)
+            .unwrap_or_else(PoisonError::into_inner);
+
+        *stopped
+    }
+
+    /// Locks the flag indicating whether the publisher has been stopped
+    fn lock_stopped(&self) -> MutexGuard<'_, bool> {
+        self.stopped.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Publishes heartbeats to the given state until the publisher is stopped or publishing fails
+///
+/// This runs on the background thread of a [`HeartbeatPublisher`].
+fn publish(state: RawState<Heartbeat>, shared: &Shared, interval: Duration) -> io::Result<()> {
+    let mut sequence_number = 0;
+    let mut deadline = Instant::now();
+
+    loop {
+        deadline = next_deadline(deadline, interval, Instant::now());
+
+        if shared.wait_stopped_until(deadline) {
+            return Ok(());
+        }
+
+        sequence_number += 1;
+        state.set(&Heartbeat::new(sequence_number, SystemTime::now()))?;
+    }
+}
+
+/// Computes the deadline of the next heartbeat given the deadline of the previous heartbeat and the current time
+///
+/// If the next deadline on the regular schedule has already passed by more than a whole interval, the missed
+/// heartbeats are skipped and the schedule is restarted from the current time.
+fn next_deadline(previous_deadline: Instant, interval: Duration, now: Instant) -> Instant {
+    let deadline = previous_deadline + interval;
+
+    if now.saturating_duration_since(deadline) > interval {
+        now + interval
+    } else {
+        deadline
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heartbeat_published_at_round_trip() {
+        let published_at = UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+        let heartbeat = Heartbeat::new(42, published_at);
+
+        assert_eq!(heartbeat.sequence_number(), 42);
+        assert_eq!(heartbeat.published_at(), published_at);
+    }
+
+    #[test]
+    fn heartbeat_published_at_before_unix_epoch_is_clamped() {
+        let heartbeat = Heartbeat::new(0, UNIX_EPOCH - Duration::from_secs(1));
+
+        assert_eq!(heartbeat.published_at(), UNIX_EPOCH);
+    }
+
+    #[test]
+    fn next_deadline_on_schedule() {
+        let start = Instant::now();
+        let interval = Duration::from_millis(100);
+
+        assert_eq!(
+            next_deadline(start, interval, start + Duration::from_millis(30)),
+            start + interval
+        );
+    }
+
+    #[test]
+    fn next_deadline_slightly_late_keeps_schedule() {
+        let start = Instant::now();
+        let interval = Duration::from_millis(100);
+
+        assert_eq!(
+            next_deadline(start, interval, start + Duration::from_millis(150)),
+            start + interval
+        );
+    }
+
+    #[test]
+    fn next_deadline_far_behind_skips_missed_heartbeats() {
+        let start = Instant::now();
+        let interval = Duration::from_millis(100);
+        let now = start + Duration::from_secs(10);
+
+        assert_eq!(next_deadline(start, interval, now), now + interval);
+    }
+}
@@ -0,0 +1,108 @@
+//! Methods for querying and updating states containing `HSTRING`s and `BSTR`s
+//!
+//! This module only adds inherent impls to [`OwnedState<WideString>`](OwnedState) and
+//! [`BorrowedState<'_, WideString>`](BorrowedState).
+
+use std::io;
+
+use windows::core::{BSTR, HSTRING};
+
+use crate::data::WideString;
+use crate::state::{BorrowedState, OwnedState, RawState};
+
+impl OwnedState<WideString> {
+    /// Queries the data of this state as an [`HSTRING`](https://docs.rs/windows/0/windows/core/struct.HSTRING.html)
+    ///
+    /// This is the same as calling [`get`](OwnedState::get) and converting the resulting [`WideString`] through
+    /// [`WideString::to_hstring`], so the string is terminated at the first NUL character, if any.
+    ///
+    /// # Errors
+    /// Returns an error if querying fails, including the case that the size of the queried data is not a multiple of
+    /// `2`
+    pub fn get_hstring(&self) -> io::Result<HSTRING> {
+        self.raw.get_hstring()
+    }
+
+    /// Queries the data of this state as a [`BSTR`](https://docs.rs/windows/0/windows/core/struct.BSTR.html)
+    ///
+    /// This is the same as calling [`get`](OwnedState::get) and converting the resulting [`WideString`] through
+    /// [`WideString::to_bstr`], so the string is terminated at the first NUL character, if any.
+    ///
+    /// # Errors
+    /// Returns an error if querying fails, including the case that the size of the queried data is not a multiple of
+    /// `2`
+    pub fn get_bstr(&self) -> io::Result<BSTR> {
+        self.raw.get_bstr()
+    }
+
+    /// Updates the data of this state with the UTF-16 code units of the given
+    /// [`HSTRING`](https://docs.rs/windows/0/windows/core/struct.HSTRING.html)
+    ///
+    /// The data do not include a terminating NUL character.
+    ///
+    /// # Errors
+    /// Returns an error if updating fails
+    pub fn set_hstring(&self, data: &HSTRING) -> io::Result<()> {
+        self.raw.set_wide(data)
+    }
+
+    /// Updates the data of this state with the UTF-16 code units of the given
+    /// [`BSTR`](https://docs.rs/windows/0/windows/core/struct.BSTR.html)
+    ///
+    /// The data do not include a terminating NUL character.
+    ///
+    /// # Errors
+    /// Returns an error if updating fails
+    pub fn set_bstr(&self, data: &BSTR) -> io::Result<()> {
+        self.raw.set_wide(data)
+    }
+}
+
+impl BorrowedState<'_, WideString> {
+    /// Queries the data of this state as an [`HSTRING`](https://docs.rs/windows/0/windows/core/struct.HSTRING.html)
+    ///
+    /// See [`OwnedState::get_hstring`]
+    pub fn get_hstring(self) -> io::Result<HSTRING> {
+        self.raw.get_hstring()
+    }
+
+    /// Queries the data of this state as a [`BSTR`](https://docs.rs/windows/0/windows/core/struct.BSTR.html)
+    ///
+    /// See [`OwnedState::get_bstr`]
+    pub fn get_bstr(self) -> io::Result<BSTR> {
+        self.raw.get_bstr()
+    }
+
+    /// Updates the data of this state with the UTF-16 code units of the given
+    /// [`HSTRING`](https://docs.rs/windows/0/windows/core/struct.HSTRING.html)
+    ///
+    /// See [`OwnedState::set_hstring`]
+    pub fn set_hstring(self, data: &HSTRING) -> io::Result<()> {
+        self.raw.set_wide(data)
+    }
+
+    /// Updates the data of this state with the UTF-16 code units of the given
+    /// [`BSTR`](https://docs.rs/windows/0/windows/core/struct.BSTR.html)
+    ///
+    /// See [`OwnedState::set_bstr`]
+    pub fn set_bstr(self, data: &BSTR) -> io::Result<()> {
+        self.raw.set_wide(data)
+    }
+}
+
+impl RawState<WideString> {
+    /// Queries the data of this state as an `HSTRING`
+    fn get_hstring(self) -> io::Result<HSTRING> {
+        Ok(self.query_as::<WideString>()?.data().to_hstring())
+    }
+
+    /// Queries the data of this state as a `BSTR`
+    fn get_bstr(self) -> io::Result<BSTR> {
+        Ok(self.query_as::<WideString>()?.data().to_bstr())
+    }
+
+    /// Updates the data of this state with the given UTF-16 code units
+    fn set_wide(self, data: &[u16]) -> io::Result<()> {
+        self.cast::<[u16]>().set(data)
+    }
+}
@@ -1,12 +1,23 @@
 //! Methods for obtaining information on states
 
 use std::ffi::c_void;
+#[cfg(any(feature = "wait_async", feature = "wait_blocking"))]
+use std::time::Duration;
 use std::{io, mem, ptr};
 
-use tracing::debug;
+use windows::Win32::Foundation::STATUS_OBJECT_NAME_NOT_FOUND;
 
+use crate::data::ChangeStamp;
 use crate::ntapi;
 use crate::state::{BorrowedState, OwnedState, RawState};
+use crate::state_error::raw_ntstatus;
+use crate::trace::{ntapi_event, WnfRoutine};
+
+/// The interval at which the existence of a state is polled when waiting for it to be created or deleted
+///
+/// The WNF API does not notify subscribers about a state being created or deleted, so we need to poll.
+#[cfg(any(feature = "wait_async", feature = "wait_blocking"))]
+pub(crate) const EXISTENCE_POLL_INTERVAL: Duration = Duration::from_millis(10);
 
 impl<T> OwnedState<T>
 where
@@ -35,6 +46,18 @@ where
     pub fn is_quiescent(&self) -> io::Result<bool> {
         self.raw.is_quiescent()
     }
+
+    /// Returns whether this state exists together with its current change stamp
+    ///
+    /// This obtains both pieces of information through a single call to the WNF API, so they are consistent with each
+    /// other. In contrast, calling [`exists`](OwnedState::exists) and [`change_stamp`](OwnedState::change_stamp)
+    /// separately is subject to a race condition because the state may be deleted or created between the calls.
+    ///
+    /// # Errors
+    /// Returns an error if obtaining the information fails for a reason other than the state not existing
+    pub fn change_stamp_info(&self) -> io::Result<ChangeStampInfo> {
+        self.raw.change_stamp_info()
+    }
 }
 
 impl<T> BorrowedState<'_, T>
@@ -61,6 +84,13 @@ where
     pub fn is_quiescent(self) -> io::Result<bool> {
         self.raw.is_quiescent()
     }
+
+    /// Returns whether this state exists together with its current change stamp
+    ///
+    /// See [`OwnedState::change_stamp_info`]
+    pub fn change_stamp_info(self) -> io::Result<ChangeStampInfo> {
+        self.raw.change_stamp_info()
+    }
 }
 
 impl<T> RawState<T>
@@ -68,20 +98,35 @@ where
     T: ?Sized,
 {
     /// Returns whether this state exists
-    fn exists(self) -> io::Result<bool> {
+    pub(crate) fn exists(self) -> io::Result<bool> {
         self.info_internal(NameInfoClass::StateNameExist)
     }
 
     /// Returns whether this state has at least one subscriber
-    fn subscribers_present(self) -> io::Result<bool> {
+    pub(crate) fn subscribers_present(self) -> io::Result<bool> {
         self.info_internal(NameInfoClass::SubscribersPresent)
     }
 
     /// Returns whether this state is "quiescent", i.e. none of the listeners subscribed to it are currently running
-    fn is_quiescent(self) -> io::Result<bool> {
+    pub(crate) fn is_quiescent(self) -> io::Result<bool> {
         self.info_internal(NameInfoClass::IsQuiescent)
     }
 
+    /// Returns whether this state exists together with its current change stamp
+    pub(crate) fn change_stamp_info(self) -> io::Result<ChangeStampInfo> {
+        // Querying the data of a state that does not exist fails with `STATUS_OBJECT_NAME_NOT_FOUND`, so a single query
+        // tells us both whether the state exists and, if so, its change stamp
+        match self.change_stamp() {
+            Ok(change_stamp) => Ok(ChangeStampInfo {
+                change_stamp: Some(change_stamp),
+            }),
+            Err(err) if raw_ntstatus(&err) == Some(STATUS_OBJECT_NAME_NOT_FOUND.0) => {
+                Ok(ChangeStampInfo { change_stamp: None })
+            }
+            Err(err) => Err(err),
+        }
+    }
+
     /// Returns the flag containing the information of the given class
     fn info_internal(self, name_info_class: NameInfoClass) -> io::Result<bool> {
         let mut buffer = u32::MAX;
@@ -103,10 +148,11 @@ where
         };
 
         if result.is_ok() {
-            debug!(
-                 target: ntapi::TRACING_TARGET,
-                 ?result,
+            ntapi_event!(
+                 WnfRoutine::QueryStateNameInformation,
+                 false,
                  input.state_name = %self.state_name,
+                 ?result,
                  input.name_info_class = name_info_class,
                  output.buffer = buffer,
                  "NtQueryWnfStateNameInformation",
@@ -118,19 +164,40 @@ where
                 _ => unreachable!("NtQueryWnfStateNameInformation did not produce valid boolean"),
             })
         } else {
-            debug!(
-                 target: ntapi::TRACING_TARGET,
-                 ?result,
+            ntapi_event!(
+                 WnfRoutine::QueryStateNameInformation,
+                 true,
                  input.state_name = %self.state_name,
+                 ?result,
                  input.name_info_class = name_info_class,
                  "NtQueryWnfStateNameInformation",
             );
 
-            Err(io::Error::from_raw_os_error(result.0))
+            Err(self.ntapi_error(WnfRoutine::QueryStateNameInformation, result))
         }
     }
 }
 
+/// Information on whether a state exists together with its current change stamp
+///
+/// This is returned from [`OwnedState::change_stamp_info`] and [`BorrowedState::change_stamp_info`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ChangeStampInfo {
+    change_stamp: Option<ChangeStamp>,
+}
+
+impl ChangeStampInfo {
+    /// Returns whether the state exists
+    pub const fn exists(self) -> bool {
+        self.change_stamp.is_some()
+    }
+
+    /// Returns the change stamp of the state, or [`None`] if the state does not exist
+    pub const fn change_stamp(self) -> Option<ChangeStamp> {
+        self.change_stamp
+    }
+}
+
 /// Different classes of information on a state
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 #[repr(u32)]
@@ -0,0 +1,315 @@
+//! Querying all instances of session-scoped and user-scoped states
+//!
+//! Besides the [`ScopeInstance`] and [`UnscopedStateError`] types, this module adds inherent impls to
+//! [`OwnedState<T>`] and [`BorrowedState<'_, T>`](BorrowedState).
+//!
+//! The methods in this module are only available with the `unstable_ntapi` feature. They are not covered by the
+//! semver guarantees of this crate and may change or be removed in any release.
+
+use std::collections::BTreeMap;
+use std::ffi::c_void;
+use std::ptr::{self, NonNull};
+use std::{io, slice};
+
+use thiserror::Error;
+use windows::Win32::Foundation::{LocalFree, HLOCAL};
+use windows::Win32::Security::Authorization::ConvertStringSidToSidW;
+use windows::Win32::Security::PSID;
+use windows::Win32::System::Registry::HKEY_USERS;
+use windows::Win32::System::RemoteDesktop::{
+    WTSEnumerateSessionsW, WTSFreeMemory, WTS_CURRENT_SERVER_HANDLE, WTS_SESSION_INFOW,
+};
+
+use crate::access::unless_access_denied;
+use crate::data::StampedData;
+use crate::read::Read;
+use crate::registry::RegistryKey;
+use crate::state::{BorrowedState, OwnedState, RawState};
+use crate::state_name::{DataScope, StateNameDescriptor};
+use crate::util::CWideString;
+
+/// An instance of a data scope, i.e. a session or a user
+///
+/// Session-scoped and user-scoped states hold separate data for every session and user, respectively. This identifies
+/// one such instance in the result of [`OwnedState::query_all_instances`].
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum ScopeInstance {
+    /// The session with the given session id
+    Session(u32),
+
+    /// The user with the given security identifier (SID) in string format, e.g. `S-1-5-18`
+    User(String),
+}
+
+/// An error querying all instances of a state that is neither session-scoped nor user-scoped
+///
+/// This is returned, wrapped in an [`io::Error`], by [`OwnedState::query_all_instances`] and related methods.
+#[derive(Clone, Copy, Debug, Error, Eq, Hash, PartialEq)]
+#[error("state is neither session-scoped nor user-scoped, but has data scope {data_scope:?}")]
+pub struct UnscopedStateError {
+    /// The data scope of the state
+    pub data_scope: DataScope,
+}
+
+impl<T> OwnedState<T>
+where
+    T: Read<T>,
+{
+    /// Queries the data of all instances of this session-scoped or user-scoped state
+    ///
+    /// For a session-scoped state, this queries the data for every session on the local machine. For a user-scoped
+    /// state, this queries the data for every user whose profile is currently loaded, i.e. every user with a subkey of
+    /// `HKEY_USERS` in the Windows registry. This is useful e.g. for administrative tools that show the values of a
+    /// state for all sessions side by side.
+    ///
+    /// Instances for which the current process does not have sufficient privileges to query the data are skipped, so
+    /// unless the process is running with elevated privileges, the result usually only contains the instances of the
+    /// current session or user.
+    ///
+    /// This passes a pointer to the session id or the user SID as the (undocumented) `ExplicitScope` argument of the
+    /// `NtQueryWnfStateData` routine, according to reverse engineering resources. See
+    /// [`query_with_explicit_scope`](OwnedState::query_with_explicit_scope) for details.
+    ///
+    /// This method is only available with the `unstable_ntapi` feature and is not covered by semver guarantees.
+    ///
+    /// # Errors
+    /// Returns an error of kind [`ErrorKind::InvalidInput`](io::ErrorKind::InvalidInput) if the state is neither
+    /// session-scoped nor user-scoped, in which case it wraps an [`UnscopedStateError`]. Also returns an error if
+    /// enumerating the instances fails or if querying any of the instances fails for a reason other than insufficient
+    /// privileges, including the case that the queried data is not a valid `T`.
+    pub fn query_all_instances(&self) -> io::Result<BTreeMap<ScopeInstance, StampedData<T>>> {
+        self.raw.query_as_all_instances()
+    }
+}
+
+impl<T> OwnedState<T>
+where
+    T: Read<Box<T>> + ?Sized,
+{
+    /// Queries the data of all instances of this session-scoped or user-scoped state as boxes
+    ///
+    /// See [`query_all_instances`](OwnedState::query_all_instances) for details.
+    ///
+    /// This method is only available with the `unstable_ntapi` feature and is not covered by semver guarantees.
+    ///
+    /// # Errors
+    /// See [`query_all_instances`](OwnedState::query_all_instances)
+    pub fn query_boxed_all_instances(&self) -> io::Result<BTreeMap<ScopeInstance, StampedData<Box<T>>>> {
+        self.raw.query_as_all_instances()
+    }
+}
+
+impl<T> BorrowedState<'_, T>
+where
+    T: Read<T>,
+{
+    /// Queries the data of all instances of this session-scoped or user-scoped state
+    ///
+    /// See [`OwnedState::query_all_instances`]
+    pub fn query_all_instances(self) -> io::Result<BTreeMap<ScopeInstance, StampedData<T>>> {
+        self.raw.query_as_all_instances()
+    }
+}
+
+impl<T> BorrowedState<'_, T>
+where
+    T: Read<Box<T>> + ?Sized,
+{
+    /// Queries the data of all instances of this session-scoped or user-scoped state as boxes
+    ///
+    /// See [`OwnedState::query_boxed_all_instances`]
+    pub fn query_boxed_all_instances(self) -> io::Result<BTreeMap<ScopeInstance, StampedData<Box<T>>>> {
+        self.raw.query_as_all_instances()
+    }
+}
+
+impl<T> RawState<T>
+where
+    T: ?Sized,
+{
+    /// Queries the data of all instances of this session-scoped or user-scoped state as values of type `D`
+    ///
+    /// If `T: Sized`, then `D` can be either `T` or `Box<T>`.
+    /// If `T: !Sized`, then `D` must be `Box<T>`.
+    fn query_as_all_instances<D>(self) -> io::Result<BTreeMap<ScopeInstance, StampedData<D>>>
+    where
+        T: Read<D>,
+    {
+        let descriptor = StateNameDescriptor::try_from(self.state_name)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+        let mut instances = BTreeMap::new();
+
+        match descriptor.data_scope {
+            DataScope::Session => {
+                for session_id in session_ids()? {
+                    // SAFETY:
+                    // According to reverse engineering resources, the explicit scope of a session-scoped state is a
+                    // pointer to a session id, and the pointer is valid for reads of `u32` because it comes from a
+                    // live reference
+                    let result = unsafe { self.query_as_with_explicit_scope((&session_id as *const u32).cast()) };
+
+                    if let Some(data) = unless_access_denied(result)? {
+                        instances.insert(ScopeInstance::Session(session_id), data);
+                    }
+                }
+            }
+
+            DataScope::User => {
+                for string_sid in user_sids()? {
+                    let sid = BoxedSid::from_string_sid(&string_sid)?;
+
+                    // SAFETY:
+                    // According to reverse engineering resources, the explicit scope of a user-scoped state is a
+                    // pointer to a SID, and the pointer points to a valid SID because it comes from a live `BoxedSid`
+                    let result = unsafe { self.query_as_with_explicit_scope(sid.as_ptr()) };
+
+                    if let Some(data) = unless_access_denied(result)? {
+                        instances.insert(ScopeInstance::User(string_sid), data);
+                    }
+                }
+            }
+
+            data_scope => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    UnscopedStateError { data_scope },
+                ))
+            }
+        }
+
+        Ok(instances)
+    }
+}
+
+/// Returns the ids of all sessions on the local machine
+fn session_ids() -> io::Result<Vec<u32>> {
+    let mut session_infos: *mut WTS_SESSION_INFOW = ptr::null_mut();
+    let mut count = 0;
+
+    // SAFETY:
+    // - The first argument is a valid server handle denoting the local machine
+    // - The pointer in the fourth argument is valid for writes of `*mut WTS_SESSION_INFOW` because it comes from a live
+    //   mutable reference
+    // - The pointer in the fifth argument is valid for writes of `u32` because it comes from a live mutable reference
+    unsafe { WTSEnumerateSessionsW(Some(WTS_CURRENT_SERVER_HANDLE), 0, 1, &mut session_infos, &mut count) }?;
+
+    if session_infos.is_null() {
+        return Ok(Vec::new());
+    }
+
+    let session_ids = {
+        // SAFETY:
+        // - `session_infos` is non-null and points to an array of `count` valid `WTS_SESSION_INFOW` values because it
+        //   was returned from a successful call to `WTSEnumerateSessionsW`
+        // - The array is not mutated and is live during the lifetime of the produced slice because it is only freed
+        //   below, after the slice has been dropped
+        let session_infos = unsafe { slice::from_raw_parts(session_infos, count as usize) };
+
+        session_infos
+            .iter()
+            .map(|session_info| session_info.SessionId)
+            .collect()
+    };
+
+    // SAFETY:
+    // - `session_infos` was returned from `WTSEnumerateSessionsW`
+    // - `session_infos` has not been freed yet
+    unsafe { WTSFreeMemory(session_infos.cast()) };
+
+    Ok(session_ids)
+}
+
+/// Returns the SIDs in string format of all users whose profile is currently loaded
+///
+/// These are the names of the subkeys of `HKEY_USERS` that are SIDs, excluding the `*_Classes` subkeys.
+fn user_sids() -> io::Result<Vec<String>> {
+    let Some(key) = RegistryKey::open(HKEY_USERS, "")? else {
+        return Ok(Vec::new());
+    };
+
+    let mut sub_key_names = key.sub_key_names()?;
+    sub_key_names.retain(|sub_key_name| is_user_sid(sub_key_name));
+    Ok(sub_key_names)
+}
+
+/// Returns whether the given name of a subkey of `HKEY_USERS` is the SID of a user
+fn is_user_sid(sub_key_name: &str) -> bool {
+    sub_key_name.starts_with("S-1-") && !sub_key_name.ends_with("_Classes")
+}
+
+/// A SID allocated on the local heap
+#[derive(Debug)]
+struct BoxedSid {
+    ptr: NonNull<c_void>,
+}
+
+impl BoxedSid {
+    /// Converts the given SID in string format into a [`BoxedSid`]
+    fn from_string_sid(string_sid: &str) -> io::Result<Self> {
+        let string_sid = CWideString::new(string_sid);
+        let mut sid = PSID::default();
+
+        // SAFETY:
+        // - The pointer in the first argument points to a valid null-terminated wide string because it comes from a
+        //   live `CWideString`
+        // - The pointer in the second argument is valid for writes of `PSID` because it comes from a live mutable
+        //   reference
+        unsafe { ConvertStringSidToSidW(string_sid.as_pcwstr(), &mut sid) }?;
+
+        Ok(Self {
+            ptr: NonNull::new(sid.0).expect("ConvertStringSidToSidW returned `NULL` SID"),
+        })
+    }
+
+    /// Returns a raw pointer to the SID for use in FFI
+    const fn as_ptr(&self) -> *const c_void {
+        self.ptr.as_ptr()
+    }
+}
+
+impl Drop for BoxedSid {
+    fn drop(&mut self) {
+        // SAFETY:
+        // - `self.ptr` points to a local memory object because it was returned from `ConvertStringSidToSidW`
+        // - `self.ptr` has not been freed yet
+        unsafe { LocalFree(Some(HLOCAL(self.ptr.as_ptr()))) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_user_sid_accepts_sids() {
+        assert!(is_user_sid("S-1-5-18"));
+        assert!(is_user_sid("S-1-5-21-1004336348-1177238915-682003330-1001"));
+    }
+
+    #[test]
+    fn is_user_sid_rejects_other_sub_keys() {
+        assert!(!is_user_sid(".DEFAULT"));
+        assert!(!is_user_sid("S-1-5-21-1004336348-1177238915-682003330-1001_Classes"));
+    }
+
+    #[test]
+    fn scope_instance_ordering() {
+        let mut instances = vec![
+            ScopeInstance::User(String::from("S-1-5-18")),
+            ScopeInstance::Session(1),
+            ScopeInstance::Session(0),
+        ];
+
+        instances.sort();
+
+        assert_eq!(
+            instances,
+            [
+                ScopeInstance::Session(0),
+                ScopeInstance::Session(1),
+                ScopeInstance::User(String::from("S-1-5-18")),
+            ]
+        );
+    }
+}
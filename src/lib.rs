@@ -93,6 +93,9 @@
 //! - Wait for updates of state data (in both blocking and async variants)
 //! - Wait until state data satisfy a certain condition (in both blocking and async variants)
 //!
+//! Whether these functions are available and working on the current system can be checked at runtime using
+//! [`probe_support`].
+//!
 //! The following WNF features are currently not supported:
 //! - Subscriptions in meta-notification mode, i.e. subscribing to consumers becoming active or inactive or publishers
 //!   terminating
@@ -192,7 +195,9 @@
 //!
 //! # Examples
 //!
-//! For more detailed examples, see the `examples` folder in the crate repository. Some common use cases:
+//! For more detailed examples, see the `examples` folder in the crate repository. Instead of importing the required
+//! types one by one as in the examples below, you can also import the most commonly used types and traits at once
+//! through `use wnf::prelude::*;`, see the [`prelude`] module. Some common use cases:
 //!
 //! ## Creating a state and querying/updating its data
 //!
@@ -215,21 +220,15 @@
 //!
 //! ```
 //! # fn main() -> Result<(), Box<dyn std::error::Error>> {
-//! use std::ffi::{OsStr, OsString};
-//! use std::os::windows::ffi::OsStringExt;
-//!
-//! use wnf::BorrowedState;
+//! use wnf::{BorrowedState, WideString};
 //!
 //! const WNF_SHEL_DESKTOP_APPLICATION_STARTED: u64 = 0x0D83063EA3BE5075;
 //!
-//! let state = BorrowedState::<[u16]>::from_state_name(WNF_SHEL_DESKTOP_APPLICATION_STARTED);
+//! let state = BorrowedState::<WideString>::from_state_name(WNF_SHEL_DESKTOP_APPLICATION_STARTED);
 //!
-//! let last_application_started_wide = {
-//!     let data = state.get_boxed()?;
-//!     OsString::from_wide(&data)
-//! };
+//! let last_application_started = state.get()?.into_os_string();
 //!
-//! println!("{}", last_application_started_wide.to_string_lossy());
+//! println!("{}", last_application_started.to_string_lossy());
 //! # Ok(()) }
 //! ```
 //!
@@ -293,7 +292,8 @@
 //!   with the following payload is emitted:
 //!   - The [`target`](https://docs.rs/tracing/latest/tracing/struct.Metadata.html#method.target) is always
 //!     `wnf::ntapi`.
-//!   - The [`level`](https://docs.rs/tracing/latest/tracing/struct.Metadata.html#method.level) is [`DEBUG`](https://docs.rs/tracing/latest/tracing/struct.Level.html#associatedconstant.DEBUG).
+//!   - The [`level`](https://docs.rs/tracing/latest/tracing/struct.Metadata.html#method.level) is [`DEBUG`](https://docs.rs/tracing/latest/tracing/struct.Level.html#associatedconstant.DEBUG)
+//!     by default. It can be configured per routine and for failed invocations through [`set_tracing_config`].
 //!   - The message is the name of the WNF API routine.
 //!   - The [`fields`](https://docs.rs/tracing/latest/tracing/struct.Metadata.html#method.fields) consist of three
 //!     groups:
@@ -308,35 +308,96 @@
 //!   - The [`name`](https://docs.rs/tracing/latest/tracing/struct.Metadata.html#method.name) is `WnfUserCallback`.
 //!   - The [`fields`](https://docs.rs/tracing/latest/tracing/struct.Metadata.html#method.fields) are all named
 //!     `input.*` and contain the inputs of the invocation.
+//! - Within the span of a callback function invoked for a state update, another span wraps the call of the state
+//!   listener:
+//!   - The [`target`](https://docs.rs/tracing/latest/tracing/struct.Metadata.html#method.target) is `wnf::listener`.
+//!   - The [`level`](https://docs.rs/tracing/latest/tracing/struct.Metadata.html#method.level) is [`TRACE`](https://docs.rs/tracing/latest/tracing/struct.Level.html#associatedconstant.TRACE).
+//!   - The [`name`](https://docs.rs/tracing/latest/tracing/struct.Metadata.html#method.name) is `StateListener`.
+//!   - The only field is `listener.type_name`, the type name of the listener.
+//!
+//!   This makes it possible to attribute the time spent in callbacks to individual listeners, e.g. in flame graphs.
+//!   In addition, [`set_slow_listener_threshold`] enables warnings about listener calls exceeding a given duration.
+//!
+//! By default, state names are only contained as opaque values. Calling [`set_trace_state_name_descriptors`] makes
+//! all events and spans additionally contain the decoded properties of state names (lifetime, data scope, unique id and
+//! owner tag) as separate fields, e.g. `input.state_name.lifetime`.
 //!
 //! See the `examples` folder in the crate repository for examples on how to subscribe to these events and spans.
 //!
 //! # Cargo features
 //!
 //! This crate has various [feature flags](https://doc.rust-lang.org/cargo/reference/features.html), none of which are
-//! enabled by default. They fall into two groups:
+//! enabled by default. They fall into three groups:
 //!
 //! - Features enabling compatibility with other crates:
+//!   - `async_std`: Enables the optional [async-std](https://docs.rs/async-std/1/async_std) dependency and provides the
+//!     [`AsyncStdSleep`] timer for async waits with a deadline, implies the `wait_async` feature
+//!   - `broadcast`: Enables the optional [tokio](https://docs.rs/tokio/1/tokio) dependency and provides the
+//!     [`BroadcastAdapter`] type for broadcasting state updates to multiple `tokio::sync::watch` receivers through a
+//!     single subscription, implies the `subscribe` feature
 //!   - `bytemuck_v1`: Enables the optional [bytemuck](https://docs.rs/bytemuck/1/bytemuck) dependency and provides the
 //!     [`derive_from_bytemuck_v1`] macro
+//!   - `compression`: Enables the optional [miniz_oxide](https://docs.rs/miniz_oxide/0/miniz_oxide) dependency and
+//!     provides the [`CompressedState`] type for storing compressed data in states
+//!   - `derive`: Enables the optional `wnf-derive` dependency and provides the [`WnfStateData`](derive@WnfStateData)
+//!     derive macro for implementing [`AnyBitPattern`] and [`NoUninit`] for plain structs
+//!   - `dpapi`: Enables the [Data Protection API](https://learn.microsoft.com/en-us/windows/win32/seccng/cng-dpapi)
+//!     bindings of the `windows` dependency and provides the [`EncryptedState`] type for storing encrypted data in
+//!     states
+//!   - `serde`: Enables the optional [serde](https://docs.rs/serde/1/serde) dependency and provides `Serialize` and
+//!     `Deserialize` implementations for [`StateReport`], [`StateSnapshot`] and the types they consist of
+//!   - `service`: Enables the [Services](https://learn.microsoft.com/en-us/windows/win32/services/services) bindings of
+//!     the `windows` dependency and provides the [`ServiceSubscriptions`] type for tying subscriptions to the control
+//!     handler of a Windows service, implies the `subscribe` feature
+//!   - `test_util`: Enables the optional [proptest](https://docs.rs/proptest/1/proptest) dependency and provides the
+//!     [`testing`] module with utilities for testing code that uses states
+//!   - `tokio`: Enables the optional [tokio](https://docs.rs/tokio/1/tokio) dependency and provides the [`TokioSleep`]
+//!     timer for async waits with a deadline, implies the `wait_async` feature
 //!   - `uuid`: Enables the optional [uuid](https://docs.rs/uuid/1/uuid) dependency and provides conversions between the
 //!     [`uuid::Uuid`](https://docs.rs/uuid/1/uuid/struct.Uuid.html) and [`wnf::GUID`](crate::GUID) types
+//!   - `widestring`: Enables the optional [widestring](https://docs.rs/widestring/1/widestring) dependency and provides
+//!     conversions from [`WideString`] into [`widestring::U16CString`](https://docs.rs/widestring/1/widestring/ucstring/type.U16CString.html)
 //!   - `winapi`: Enables the optional [winapi](https://docs.rs/winapi/latest/winapi) dependency and provides conversions
 //!     between the [`winapi::shared::guiddef::GUID`](https://docs.rs/winapi/latest/winapi/shared/guiddef/struct.GUID.html)
 //!     and [`wnf::GUID`](crate::GUID) types
 //!   - `windows`: Provides conversions between the [`windows::core::GUID`](https://docs.rs/windows/latest/windows/core/struct.GUID.html)
-//!     and [`wnf::GUID`](crate::GUID) types (the `windows` dependency is not optional because it is also used by `wnf`
-//!     internally)
+//!     and [`wnf::GUID`](crate::GUID) types, conversions from [`WideString`] into [`windows::core::HSTRING`](https://docs.rs/windows/latest/windows/core/struct.HSTRING.html)
+//!     and [`windows::core::BSTR`](https://docs.rs/windows/latest/windows/core/struct.BSTR.html) as well as methods for
+//!     querying and updating states with these types (the `windows` dependency is not optional because it is also used
+//!     by `wnf` internally)
 //!   - `windows_permissions`: Enables the optional [windows-permissions](https://docs.rs/windows-permissions/latest/windows_permissions)
 //!     dependency and enables the use of [`windows_permissions::SecurityDescriptor`](https://docs.rs/windows-permissions/latest/windows_permissions/struct.SecurityDescriptor.html)
 //!     when creating a state
-//!   - `zerocopy`: Enables the optional [zerocopy](https://docs.rs/zerocopy/0/zerocopy) dependency and provides the
-//!     [`derive_from_zerocopy`] macro
+//!   - `zerocopy`: Enables the optional [zerocopy](https://docs.rs/zerocopy/0.8/zerocopy) dependency (version `0.8`)
+//!     and provides the [`derive_from_zerocopy`] macro bridging its `FromBytes` and `IntoBytes` traits
+//!   - `zeroize`: Enables the optional [zeroize](https://docs.rs/zeroize/1/zeroize) dependency, causing intermediate
+//!     buffers used for reading state data to be wiped before they are deallocated, and provides the
+//!     [`OwnedState::set_secret`] method
 //!
 //! - Features enabling functionality that uses the higher-level `Rtl*` functions from `ntdll.dll` (see above):
 //!   - `subscribe`: Enables subscribing to state updates
 //!   - `wait_blocking`: Enables blocking waits for state updates, implies the `subscribe` feature
-//!   - `wait_async`: Enables async waits for state updates, implies the `subscribe` feature
+//!   - `wait_async`: Enables async waits for state updates and typed channels through [`channel`], implies the
+//!     `subscribe` feature
+//!   - `async_callbacks`: Enables the optional [tokio](https://docs.rs/tokio/1/tokio) dependency and enables
+//!     subscribing async listeners whose futures are spawned onto a tokio runtime, implies the `subscribe` feature
+//!
+//! - Features enabling unstable functionality that is not covered by semver guarantees:
+//!   - `perf_counters`: Provides [`PerfCounters`] for counting the invocations of WNF API routines per thread, which is
+//!     used by the benchmarks of this crate to make the number of kernel calls per API call visible
+//!   - `unstable_ntapi`: Enables unsafe methods exposing undocumented parameters of the WNF API, such as
+//!     [`OwnedState::query_with_explicit_scope`], as well as methods built on them, such as
+//!     [`OwnedState::query_all_instances`]
+//!
+//! In addition, the `cli` feature enables the `wnf-cli` binary, a command line tool for dumping, querying, updating and
+//! watching states that is built on the public API of this crate. It implies the `subscribe` feature.
+//!
+//! Finally, the `core_only` feature makes it possible to build this crate on targets other than Windows. On such
+//! targets, only the parts that don't call into the operating system are available, i.e. the traits and derive macros
+//! for data types ([`AnyBitPattern`], [`CheckedBitPattern`], [`NoUninit`]), reading data from bytes
+//! ([`read_from_bytes`], [`read_boxed_from_bytes`], [`ReadError`]), the data types defined by this crate and state
+//! names ([`StateName`], [`StateNameDescriptor`]) as well as decoding events from ETW traces ([`etw`]). This is useful
+//! for tools decoding captured state data offline. On Windows, this feature has no effect.
 //!
 //! # Stability
 //!
@@ -376,51 +437,292 @@
 #![deny(rustdoc::missing_crate_level_docs)]
 #![deny(rustdoc::private_intra_doc_links)]
 
-#[cfg(not(windows))]
-compile_error!("the `wnf` crate supports Windows only");
+#[cfg(all(not(windows), not(feature = "core_only")))]
+compile_error!("the `wnf` crate supports Windows only, except for the `core_only` feature");
 
 #[macro_use]
 extern crate num_derive;
 
-mod apply;
 mod bytes;
 mod data;
+mod descriptor_builder;
+pub mod etw;
+pub mod prelude;
+mod read;
+mod state_name;
+mod wipe;
+
+#[cfg(windows)]
+mod access;
+
+#[cfg(windows)]
+mod apply;
+
+#[cfg(windows)]
+mod capabilities;
+
+#[cfg(windows)]
+mod cleanup;
+
+#[cfg(windows)]
+mod consistent;
+
+#[cfg(windows)]
+mod debounce;
+
+#[cfg(windows)]
+mod describe;
+
+#[cfg(windows)]
+mod heartbeat;
+
+#[cfg(windows)]
 mod info;
+
+#[cfg(windows)]
+mod locked;
+
+#[cfg(windows)]
 mod manage;
+
+#[cfg(windows)]
+mod migrate;
+
+#[cfg(windows)]
 mod ntapi;
+
+#[cfg(windows)]
+mod primitives;
+
+#[cfg(windows)]
 mod privilege;
+
+#[cfg(windows)]
+mod publisher;
+
+#[cfg(windows)]
 mod query;
-mod read;
+
+#[cfg(windows)]
+mod registry;
+
+#[cfg(windows)]
 mod replace;
+
+#[cfg(windows)]
 mod security;
+
+#[cfg(windows)]
 mod state;
-mod state_name;
+
+#[cfg(windows)]
+mod state_error;
+
+#[cfg(windows)]
+mod support;
+
+#[cfg(windows)]
+mod trace;
+
+#[cfg(windows)]
 mod type_id;
+
+#[cfg(windows)]
 mod update;
+
+#[cfg(windows)]
+mod update_all;
+
+#[cfg(windows)]
 mod util;
 
-#[cfg(any(feature = "wait_async", feature = "wait_blocking"))]
+#[cfg(windows)]
+mod versioned;
+
+#[cfg(all(windows, feature = "broadcast"))]
+mod broadcast;
+
+#[cfg(all(windows, feature = "wait_async"))]
+mod channel;
+
+#[cfg(all(windows, feature = "compression"))]
+mod compression;
+
+#[cfg(all(windows, feature = "wait_blocking"))]
+mod condvar;
+
+#[cfg(all(windows, feature = "dpapi"))]
+mod encryption;
+
+#[cfg(all(windows, feature = "windows"))]
+mod hstring;
+
+#[cfg(all(windows, feature = "unstable_ntapi"))]
+mod instances;
+
+#[cfg(all(windows, feature = "perf_counters"))]
+mod perf;
+
+#[cfg(all(windows, feature = "subscribe"))]
+mod poll;
+
+#[cfg(all(windows, any(feature = "wait_async", feature = "wait_blocking")))]
 mod predicate;
 
-#[cfg(feature = "subscribe")]
+#[cfg(all(windows, feature = "subscribe"))]
+mod reattach;
+
+#[cfg(all(windows, feature = "subscribe"))]
+mod replay;
+
+#[cfg(all(windows, feature = "subscribe"))]
+mod scope;
+
+#[cfg(all(windows, feature = "service"))]
+mod service;
+
+#[cfg(all(windows, feature = "subscribe"))]
+mod shutdown;
+
+#[cfg(all(windows, feature = "subscribe"))]
+mod staleness;
+
+#[cfg(all(windows, feature = "subscribe"))]
 mod subscribe;
 
-#[cfg(feature = "wait_async")]
+#[cfg(all(windows, feature = "async_callbacks"))]
+mod subscribe_async;
+
+#[cfg(all(windows, feature = "subscribe"))]
+mod subscribe_arc;
+
+#[cfg(all(windows, feature = "subscribe"))]
+mod subscribe_debounced;
+
+#[cfg(all(windows, feature = "subscribe"))]
+mod subscribe_group;
+
+#[cfg(all(windows, feature = "test_util"))]
+pub mod testing;
+
+#[cfg(all(windows, feature = "unstable_ntapi"))]
+mod unstable_ntapi;
+
+#[cfg(all(windows, feature = "wait_async"))]
 mod wait_async;
 
-#[cfg(feature = "wait_blocking")]
+#[cfg(all(windows, feature = "wait_blocking"))]
 mod wait_blocking;
 
+#[cfg(all(windows, feature = "wait_blocking"))]
+mod wait_event;
+
+#[cfg(all(windows, feature = "wait_async"))]
+mod wait_deadline;
+
+#[cfg(all(windows, feature = "subscribe"))]
+mod watch_prefix;
+
+#[cfg(windows)]
+pub use access::*;
+#[cfg(windows)]
+pub use apply::*;
+#[cfg(all(windows, feature = "broadcast"))]
+pub use broadcast::*;
 pub use bytes::*;
+#[cfg(windows)]
+pub use capabilities::*;
+#[cfg(all(windows, feature = "wait_async"))]
+pub use channel::*;
+#[cfg(windows)]
+pub use cleanup::*;
+#[cfg(all(windows, feature = "compression"))]
+pub use compression::*;
+#[cfg(all(windows, feature = "wait_blocking"))]
+pub use condvar::*;
+#[cfg(windows)]
+pub use consistent::*;
 pub use data::*;
+#[cfg(windows)]
+pub use debounce::*;
+#[cfg(windows)]
+pub use describe::*;
+pub use descriptor_builder::*;
+#[cfg(all(windows, feature = "dpapi"))]
+pub use encryption::*;
+#[cfg(windows)]
+pub use heartbeat::*;
+#[cfg(windows)]
+pub use info::*;
+#[cfg(all(windows, feature = "unstable_ntapi"))]
+pub use instances::*;
+#[cfg(windows)]
+pub use locked::*;
+#[cfg(windows)]
 pub use manage::*;
+#[cfg(windows)]
+pub use migrate::*;
+#[cfg(all(windows, feature = "perf_counters"))]
+pub use perf::*;
+#[cfg(all(windows, feature = "subscribe"))]
+pub use poll::*;
+#[cfg(windows)]
+pub use primitives::*;
+#[cfg(windows)]
 pub use privilege::*;
+#[cfg(windows)]
+pub use publisher::*;
 pub use read::*;
+#[cfg(all(windows, feature = "subscribe"))]
+pub use reattach::*;
+#[cfg(windows)]
+pub use registry::*;
+#[cfg(all(windows, feature = "subscribe"))]
+pub use replay::*;
+#[cfg(all(windows, feature = "subscribe"))]
+pub use scope::*;
+#[cfg(windows)]
 pub use security::*;
+#[cfg(all(windows, feature = "service"))]
+pub use service::*;
+#[cfg(all(windows, feature = "subscribe"))]
+pub use shutdown::*;
+#[cfg(all(windows, feature = "subscribe"))]
+pub use staleness::*;
+#[cfg(windows)]
 pub use state::*;
+#[cfg(windows)]
+pub use state_error::*;
 pub use state_name::*;
-#[cfg(feature = "subscribe")]
+#[cfg(all(windows, feature = "subscribe"))]
 pub use subscribe::*;
+#[cfg(all(windows, feature = "subscribe"))]
+pub use subscribe_arc::*;
+#[cfg(all(windows, feature = "async_callbacks"))]
+pub use subscribe_async::*;
+#[cfg(all(windows, feature = "subscribe"))]
+pub use subscribe_debounced::*;
+#[cfg(all(windows, feature = "subscribe"))]
+pub use subscribe_group::*;
+#[cfg(windows)]
+pub use support::*;
+#[cfg(windows)]
+pub use trace::*;
+#[cfg(windows)]
 pub use type_id::*;
-#[cfg(feature = "wait_async")]
+#[cfg(windows)]
+pub use update::*;
+#[cfg(windows)]
+pub use update_all::*;
+#[cfg(windows)]
+pub use versioned::*;
+#[cfg(all(windows, feature = "wait_async"))]
 pub use wait_async::*;
+#[cfg(all(windows, feature = "wait_async"))]
+pub use wait_deadline::*;
+#[cfg(all(windows, feature = "wait_blocking"))]
+pub use wait_event::*;
+#[cfg(all(windows, feature = "subscribe"))]
+pub use watch_prefix::*;
+#[cfg(feature = "derive")]
+pub use wnf_derive::WnfStateData;
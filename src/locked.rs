@@ -0,0 +1,424 @@
+//! Serializing writers of a state through an auxiliary lock state
+
+use std::borrow::Borrow;
+use std::fmt::{self, Debug, Formatter};
+use std::io::{self, ErrorKind};
+use std::mem;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use thiserror::Error;
+
+use crate::apply::Backoff;
+use crate::bytes::NoUninit;
+use crate::publisher::{query_lock, PublisherId};
+use crate::read::Read;
+use crate::state::{AsState, BorrowedState, RawState};
+
+/// The default timeout for acquiring the lock of a [`LockedState`]
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The default lease of the lock of a [`LockedState`]
+const DEFAULT_LEASE: Duration = Duration::from_secs(30);
+
+/// The default backoff between attempts to acquire the lock of a [`LockedState`]
+const DEFAULT_BACKOFF: Backoff = Backoff::Exponential {
+    initial: Duration::from_millis(1),
+    max: Duration::from_millis(50),
+};
+
+/// A state whose writers are serialized through an auxiliary lock state
+///
+/// The [`apply`](crate::state::OwnedState::apply) method and related methods use an optimistic loop: They query the
+/// state data, apply a transformation and update the state data only if it has not been updated concurrently, retrying
+/// otherwise. Under constant pressure from other writers, this loop may never converge. A [`LockedState`] guarantees
+/// progress instead by making writers agree on an auxiliary *lock state*: Before applying a transformation, a writer
+/// acquires the lock by writing a lock record to the lock state, waiting while another writer holds it, and releases
+/// the lock afterwards. As long as all writers go through a [`LockedState`] with the same lock state, the
+/// transformation is applied on the first attempt.
+///
+/// Acquiring the lock gives up after a [timeout](LockedState::timeout), in which case the returned [`io::Error`] has
+/// kind [`ErrorKind::TimedOut`] and wraps a [`LockTimeoutError`]. A lock record contains the time at which the lock
+/// was acquired, and a lock that has been held for longer than the [lease](LockedState::lease) is considered abandoned
+/// and is stolen by the next writer. This avoids waiting forever for a writer that terminated while holding the lock,
+/// but it means that the lease must be long enough for every transformation to complete.
+///
+/// The lock state can be any state with data of any type. Its data are treated as raw bytes, where data of size zero
+/// or data consisting only of zeros mean that the lock is not held. Data that are neither of these nor a valid lock
+/// record make applying a transformation fail with an error of kind [`ErrorKind::InvalidData`]. Note that the lock
+/// records are different from the data written by a [`PublisherGuard`](crate::publisher::PublisherGuard), so the same
+/// lock state cannot be used for both.
+///
+/// Like the [`PublisherGuard`](crate::publisher::PublisherGuard), this is a cooperative mechanism: It does not prevent
+/// anyone from updating the actual state without acquiring the lock. Such updates are still detected through change
+/// stamps, making the transformation be retried.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use std::time::Duration;
+///
+/// use wnf::{LockedState, OwnedState};
+///
+/// let state = OwnedState::<u32>::create_temporary()?;
+/// let lock_state = OwnedState::<[u8]>::create_temporary()?;
+/// state.set(&42)?;
+///
+/// let locked_state = LockedState::new(&state, &lock_state).timeout(Duration::from_secs(1));
+/// let new_data = locked_state.apply(|value| value + 1)?;
+///
+/// assert_eq!(new_data, 43);
+/// # Ok(()) }
+/// ```
+pub struct LockedState<'a, T>
+where
+    T: ?Sized,
+{
+    state: BorrowedState<'a, T>,
+    lock_state: BorrowedState<'a, [u8]>,
+    timeout: Duration,
+    lease: Duration,
+    backoff: Backoff,
+}
+
+impl<'a, T> LockedState<'a, T>
+where
+    T: ?Sized,
+{
+    /// Creates a [`LockedState`] for the given state, serializing writers through the given lock state
+    ///
+    /// The timeout defaults to 10 seconds, the lease defaults to 30 seconds and the backoff between attempts to acquire
+    /// the lock defaults to [`Backoff::Exponential`] from 1 millisecond up to 50 milliseconds.
+    pub fn new<S, L>(state: &'a S, lock_state: &'a L) -> Self
+    where
+        S: AsState<Data = T>,
+        L: AsState,
+    {
+        Self {
+            state: state.as_state(),
+            lock_state: lock_state.as_state().cast(),
+            timeout: DEFAULT_TIMEOUT,
+            lease: DEFAULT_LEASE,
+            backoff: DEFAULT_BACKOFF,
+        }
+    }
+
+    /// Sets how long to wait for the lock before giving up
+    pub const fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets how long the lock can be held before it is considered abandoned and can be stolen
+    pub const fn lease(mut self, lease: Duration) -> Self {
+        self.lease = lease;
+        self
+    }
+
+    /// Sets how long to wait between attempts to acquire the lock
+    pub const fn backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Returns the [`PublisherId`] of the writer currently holding the lock, or [`None`] if the lock is not held
+    ///
+    /// Note that the returned writer may hold the lock for longer than the lease, i.e. the lock may be about to be
+    /// stolen.
+    ///
+    /// # Errors
+    /// Returns an error if querying the lock state fails or its data are invalid
+    pub fn holder(&self) -> io::Result<Option<PublisherId>> {
+        let (data, _) = query_lock(self.lock_state.raw)?;
+        Ok(LockRecord::from_bytes(&data)?.map(|record| record.holder))
+    }
+
+    /// Acquires the lock, waiting for it to be released or its lease to expire up to the timeout
+    fn lock(&self) -> io::Result<LockGuard> {
+        let id = PublisherId::new();
+        let deadline = Instant::now().checked_add(self.timeout);
+        let mut holder = None;
+        let mut retries = 0;
+
+        loop {
+            let (data, change_stamp) = query_lock(self.lock_state.raw)?;
+
+            let available = match LockRecord::from_bytes(&data)? {
+                Some(record) => {
+                    holder = Some(record.holder);
+                    record.is_expired(self.lease)
+                }
+                None => true,
+            };
+
+            if available
+                && self
+                    .lock_state
+                    .raw
+                    .update(&LockRecord::new(id).to_bytes(), change_stamp)?
+            {
+                return Ok(LockGuard {
+                    lock_state: self.lock_state.raw,
+                    id,
+                });
+            }
+
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Err(io::Error::new(
+                    ErrorKind::TimedOut,
+                    LockTimeoutError {
+                        timeout: self.timeout,
+                        holder,
+                    },
+                ));
+            }
+
+            retries += 1;
+            self.backoff.wait(retries);
+        }
+    }
+}
+
+// We cannot derive this because that would impose an unnecessary trait bound `T: Copy`
+impl<T> Copy for LockedState<'_, T> where T: ?Sized {}
+
+// We cannot derive this because that would impose an unnecessary trait bound `T: Clone`
+impl<T> Clone for LockedState<'_, T>
+where
+    T: ?Sized,
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+// We cannot derive this because that would impose an unnecessary trait bound `T: Debug`
+impl<T> Debug for LockedState<'_, T>
+where
+    T: ?Sized,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LockedState")
+            .field("state", &self.state)
+            .field("lock_state", &self.lock_state)
+            .field("timeout", &self.timeout)
+            .field("lease", &self.lease)
+            .field("backoff", &self.backoff)
+            .finish()
+    }
+}
+
+impl<T> LockedState<'_, T>
+where
+    T: Read<T> + NoUninit,
+{
+    /// Applies a transformation to the data of the state while holding the lock
+    ///
+    /// This is the same as [`OwnedState::apply`](crate::state::OwnedState::apply), except that the lock is acquired
+    /// before and released after applying the transformation. Since writers going through a [`LockedState`] with the
+    /// same lock state cannot update the state concurrently, the given closure is usually called only once.
+    ///
+    /// The closure receives an owned `T` on the stack, requiring `T: Sized`. In order to receive a `Box<T>` for
+    /// `T: ?Sized`, use the [`apply_boxed`](LockedState::apply_boxed) method.
+    ///
+    /// # Errors
+    /// Returns an error if acquiring the lock times out, in which case the error wraps a [`LockTimeoutError`], or if
+    /// querying or updating the state or the lock state fails
+    pub fn apply<D, F>(&self, transform: F) -> io::Result<D>
+    where
+        D: Borrow<T>,
+        F: FnMut(T) -> D,
+    {
+        let guard = self.lock()?;
+        let result = self.state.raw.apply_as(transform)?;
+        guard.release()?;
+        Ok(result)
+    }
+}
+
+impl<T> LockedState<'_, T>
+where
+    T: Read<Box<T>> + NoUninit + ?Sized,
+{
+    /// Applies a transformation to the data of the state as a box while holding the lock
+    ///
+    /// This is the same as [`OwnedState::apply_boxed`](crate::state::OwnedState::apply_boxed), except that the lock is
+    /// acquired before and released after applying the transformation. Since writers going through a [`LockedState`]
+    /// with the same lock state cannot update the state concurrently, the given closure is usually called only once.
+    ///
+    /// # Errors
+    /// Returns an error if acquiring the lock times out, in which case the error wraps a [`LockTimeoutError`], or if
+    /// querying or updating the state or the lock state fails
+    pub fn apply_boxed<D, F>(&self, transform: F) -> io::Result<D>
+    where
+        D: Borrow<T>,
+        F: FnMut(Box<T>) -> D,
+    {
+        let guard = self.lock()?;
+        let result = self.state.raw.apply_as(transform)?;
+        guard.release()?;
+        Ok(result)
+    }
+}
+
+/// An error acquiring the lock of a [`LockedState`] because it was held by another writer until the timeout elapsed
+///
+/// When applying a transformation through a [`LockedState`] fails because of this, the returned [`io::Error`] has kind
+/// [`ErrorKind::TimedOut`] and wraps a [`LockTimeoutError`], which can be obtained via [`io::Error::get_ref`].
+#[derive(Clone, Copy, Debug, Eq, Error, Hash, PartialEq)]
+#[error("timed out after {timeout:?} waiting for the lock of a state")]
+pub struct LockTimeoutError {
+    /// The timeout that elapsed
+    pub timeout: Duration,
+
+    /// The [`PublisherId`] of the writer last seen holding the lock, if any
+    pub holder: Option<PublisherId>,
+}
+
+/// The data of a lock state while the lock is held
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct LockRecord {
+    holder: PublisherId,
+    unix_time_millis: u64,
+}
+
+impl LockRecord {
+    /// Creates a [`LockRecord`] for the given holder acquiring the lock now
+    fn new(holder: PublisherId) -> Self {
+        let unix_time_millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_millis().try_into().unwrap_or(u64::MAX));
+
+        Self {
+            holder,
+            unix_time_millis,
+        }
+    }
+
+    /// Returns whether the lock has been held for longer than the given lease
+    ///
+    /// If the system clock has been set back since the lock was acquired, the lock is not considered expired.
+    fn is_expired(self, lease: Duration) -> bool {
+        let acquired_at = UNIX_EPOCH + Duration::from_millis(self.unix_time_millis);
+        SystemTime::now()
+            .duration_since(acquired_at)
+            .is_ok_and(|held_for| held_for > lease)
+    }
+
+    /// Encodes this [`LockRecord`] as the data of a lock state
+    fn to_bytes(self) -> [u8; 16] {
+        let mut bytes = [0; 16];
+        bytes[..8].copy_from_slice(&self.holder.to_bytes());
+        bytes[8..].copy_from_slice(&self.unix_time_millis.to_le_bytes());
+        bytes
+    }
+
+    /// Decodes a [`LockRecord`] from the data of a lock state, returning [`None`] if the lock is not held
+    fn from_bytes(bytes: &[u8]) -> io::Result<Option<Self>> {
+        if bytes.iter().all(|&byte| byte == 0) {
+            return Ok(None);
+        }
+
+        let invalid = || {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                "data of lock state do not contain a lock record",
+            )
+        };
+
+        if bytes.len() != 16 {
+            return Err(invalid());
+        }
+
+        let (holder, unix_time_millis) = bytes.split_at(8);
+
+        Ok(Some(Self {
+            holder: PublisherId::from_bytes(holder)?.ok_or_else(invalid)?,
+            unix_time_millis: u64::from_le_bytes(unix_time_millis.try_into().map_err(|_| invalid())?),
+        }))
+    }
+}
+
+/// A guard holding the lock of a [`LockedState`], releasing it when dropped
+struct LockGuard {
+    lock_state: RawState<[u8]>,
+    id: PublisherId,
+}
+
+impl LockGuard {
+    /// Releases the lock
+    fn release(self) -> io::Result<()> {
+        let result = self.release_internal();
+        mem::forget(self);
+        result
+    }
+
+    /// Releases the lock without consuming the guard
+    ///
+    /// If the lock has been stolen in the meantime, the lock state is left unchanged.
+    fn release_internal(&self) -> io::Result<()> {
+        loop {
+            let (data, change_stamp) = query_lock(self.lock_state)?;
+
+            if LockRecord::from_bytes(&data)?.map(|record| record.holder) != Some(self.id) {
+                return Ok(());
+            }
+
+            if self.lock_state.update(&[], change_stamp)? {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl Drop for LockGuard {
+    fn drop(&mut self) {
+        let _ = self.release_internal();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lock_record_bytes_round_trip() {
+        let record = LockRecord::new(PublisherId::new());
+
+        assert_eq!(LockRecord::from_bytes(&record.to_bytes()).unwrap(), Some(record));
+    }
+
+    #[test]
+    fn lock_record_from_bytes_not_held() {
+        assert_eq!(LockRecord::from_bytes(&[]).unwrap(), None);
+        assert_eq!(LockRecord::from_bytes(&[0; 16]).unwrap(), None);
+    }
+
+    #[test]
+    fn lock_record_from_bytes_invalid() {
+        let err = LockRecord::from_bytes(&[0xFF; 8]).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn lock_record_is_expired() {
+        let record = LockRecord::new(PublisherId::new());
+
+        assert!(!record.is_expired(Duration::from_secs(60)));
+        assert!(LockRecord {
+            unix_time_millis: 0,
+            ..record
+        }
+        .is_expired(Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn lock_timeout_error_display() {
+        let err = LockTimeoutError {
+            timeout: Duration::from_millis(100),
+            holder: None,
+        };
+
+        assert_eq!(err.to_string(), "timed out after 100ms waiting for the lock of a state");
+    }
+}
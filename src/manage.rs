@@ -2,14 +2,21 @@
 
 use std::borrow::Borrow;
 use std::fmt::{self, Debug, Formatter};
-use std::io;
+use std::io::{self, ErrorKind};
 
-use tracing::debug;
+use thiserror::Error;
+use windows::Win32::Foundation::{
+    NTSTATUS, STATUS_ACCESS_DENIED, STATUS_INVALID_PARAMETER, STATUS_OBJECT_NAME_COLLISION, STATUS_PRIVILEGE_NOT_HELD,
+};
 
+use crate::capabilities::os_capabilities;
+use crate::data::{ChangeStamp, OpaqueData, StampedData};
 use crate::ntapi;
+use crate::privilege::{can_create_permanent_shared_objects, Privilege};
 use crate::security::{BoxedSecurityDescriptor, SecurityDescriptor};
 use crate::state::{BorrowedState, OwnedState, RawState};
 use crate::state_name::{DataScope, StateLifetime, StateName};
+use crate::trace::{ntapi_event, WnfRoutine};
 use crate::type_id::{TypeId, GUID};
 
 /// The maximum size of a state in bytes
@@ -107,6 +114,12 @@ pub enum CreatableStateLifetime {
     Persistent,
 
     /// Lifetime of a *temporary* state, see [`StateLifetime::Temporary`]
+    ///
+    /// A temporary state is always bound to the process that creates it. The WNF API offers no way of binding it to a
+    /// different process, e.g. by passing a process handle, so a supervisor process cannot create a temporary state on
+    /// behalf of a child process. Instead, the child process can create the state itself and pass its name to the
+    /// supervisor, or the supervisor can create a persistent state and delete it when the child process exits (see
+    /// also [`cleanup_orphaned_states`](crate::cleanup::cleanup_orphaned_states)).
     Temporary,
 }
 
@@ -127,6 +140,133 @@ impl From<CreatableStateLifetime> for StateLifetime {
     }
 }
 
+/// The policy determining what happens when an [`OwnedState<T>`] is dropped
+///
+/// This can be configured upon creation of a state via [`StateCreation::drop_policy`] or later via
+/// [`OwnedState::set_drop_policy`].
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum DropPolicy {
+    /// Keep the state, i.e. don't delete it
+    ///
+    /// This is similar to calling [`OwnedState::leak`], except that it can be decided at runtime.
+    Keep,
+
+    /// Delete the state
+    #[default]
+    Delete,
+
+    /// Overwrite the state data with zeros, then delete the state
+    ///
+    /// This is useful if the state contains sensitive data that must not survive the state in the memory of the
+    /// kernel. The state data are overwritten in place with the same number of zero bytes before deleting the state.
+    ClearThenDelete,
+}
+
+/// An error creating a state
+///
+/// When creating a state fails for one of these reasons, the returned [`io::Error`] wraps a [`CreateError`], which can
+/// be obtained via [`io::Error::get_ref`]. Other errors, such as a failure to create the security descriptor, are
+/// returned as they are.
+///
+/// # Example
+/// ```
+/// use wnf::{CreatableStateLifetime, CreateError, DataScope, StateCreation};
+///
+/// let result = StateCreation::new()
+///     .lifetime(CreatableStateLifetime::Persistent)
+///     .scope(DataScope::Machine)
+///     .create_owned::<u32>();
+///
+/// if let Err(err) = result {
+///     match err.get_ref().and_then(|err| err.downcast_ref::<CreateError>()) {
+///         Some(CreateError::MissingPrivilege(privilege)) => {
+///             eprintln!("Please run the installer as a user holding the `{privilege}` privilege");
+///         }
+///         _ => eprintln!("Failed to create state: {err}"),
+///     }
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, Eq, Error, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum CreateError {
+    /// The current process does not hold a privilege that is necessary for creating the state
+    ///
+    /// The corresponding [`io::Error`] has kind [`ErrorKind::PermissionDenied`].
+    #[error("creating the state requires the `{0}` privilege")]
+    MissingPrivilege(Privilege),
+
+    /// The configured maximum state size exceeds [`MAXIMUM_STATE_SIZE`]
+    ///
+    /// The corresponding [`io::Error`] has kind [`ErrorKind::InvalidInput`].
+    #[error(
+        "maximum state size {maximum_state_size} exceeds the limit of {} bytes",
+        MAXIMUM_STATE_SIZE
+    )]
+    InvalidMaximumSize {
+        /// The configured maximum state size
+        maximum_state_size: usize,
+    },
+
+    /// The operating system rejected the configured data scope for states with the configured lifetime
+    ///
+    /// Note that if the data scope is not supported by the running operating system at all, creating the state fails
+    /// with a [`CapabilityError`](crate::capabilities::CapabilityError) instead.
+    ///
+    /// The corresponding [`io::Error`] has kind [`ErrorKind::InvalidInput`].
+    #[error("data scope {scope:?} is not supported for states with lifetime {lifetime:?}")]
+    ScopeNotSupported {
+        /// The configured lifetime
+        lifetime: CreatableStateLifetime,
+
+        /// The configured data scope
+        scope: DataScope,
+    },
+
+    /// Access to creating the state was denied, e.g. by a security policy
+    ///
+    /// The corresponding [`io::Error`] has kind [`ErrorKind::PermissionDenied`].
+    #[error("access to creating the state was denied")]
+    AccessDenied,
+
+    /// The operating system generated a state name that collides with an existing state
+    ///
+    /// This is very unlikely to happen, so it is usually safe to just retry.
+    ///
+    /// The corresponding [`io::Error`] has kind [`ErrorKind::AlreadyExists`].
+    #[error("the generated state name collides with an existing state")]
+    NameCollision,
+}
+
+impl CreateError {
+    /// Returns the [`ErrorKind`] of the [`io::Error`] corresponding to this [`CreateError`]
+    const fn kind(self) -> ErrorKind {
+        match self {
+            Self::MissingPrivilege(..) | Self::AccessDenied => ErrorKind::PermissionDenied,
+            Self::InvalidMaximumSize { .. } | Self::ScopeNotSupported { .. } => ErrorKind::InvalidInput,
+            Self::NameCollision => ErrorKind::AlreadyExists,
+        }
+    }
+
+    /// Determines the [`CreateError`] corresponding to the given status returned from creating a state with the given
+    /// lifetime and scope, if any
+    fn from_status(result: NTSTATUS, lifetime: CreatableStateLifetime, scope: DataScope) -> Option<Self> {
+        match result {
+            STATUS_PRIVILEGE_NOT_HELD => Some(Self::MissingPrivilege(Privilege::CreatePermanent)),
+            STATUS_ACCESS_DENIED => Some(Self::AccessDenied),
+            STATUS_OBJECT_NAME_COLLISION => Some(Self::NameCollision),
+            // The maximum state size is validated upfront, so an invalid parameter must be the scope
+            STATUS_INVALID_PARAMETER => Some(Self::ScopeNotSupported { lifetime, scope }),
+            _ => None,
+        }
+    }
+}
+
+impl From<CreateError> for io::Error {
+    fn from(err: CreateError) -> Self {
+        io::Error::new(err.kind(), err)
+    }
+}
+
 /// A trait for types that can be fallibly converted into a security descriptor
 ///
 /// Since [`SecurityDescriptor`] is an opaque type, this does not mean (fallibly) converting into an actual
@@ -183,6 +323,7 @@ impl TryIntoSecurityDescriptor for UnspecifiedSecurityDescriptor {
 ///
 /// - [`lifetime`](StateCreation::lifetime): Mandatory
 /// - [`scope`](StateCreation::scope): Mandatory
+/// - [`drop_policy`](StateCreation::drop_policy): Optional, default: [`DropPolicy::Delete`]
 /// - [`maximum_state_size`](StateCreation::maximum_state_size): Optional, default: `0x1000`
 /// - [`security_descriptor`](StateCreation::security_descriptor): Optional, default: see
 ///   [`BoxedSecurityDescriptor::create_everyone_generic_all`]
@@ -231,6 +372,7 @@ pub struct StateCreation<L, S, SD> {
     scope: S,
 
     // optional fields
+    drop_policy: DropPolicy,
     maximum_state_size: Option<usize>,
     security_descriptor: SD,
     type_id: TypeId,
@@ -249,11 +391,50 @@ impl StateCreation<UnspecifiedLifetime, UnspecifiedScope, UnspecifiedSecurityDes
             lifetime: UnspecifiedLifetime::new(),
             scope: UnspecifiedScope::new(),
 
+            drop_policy: DropPolicy::Delete,
             maximum_state_size: None,
             security_descriptor: UnspecifiedSecurityDescriptor::new(),
             type_id: TypeId::none(),
         }
     }
+
+    /// Creates a new [`StateCreation`] builder for a state in the current session that only the current user can
+    /// access
+    ///
+    /// This configures the given lifetime, the [`DataScope::Session`] scope and a security descriptor granting access
+    /// only to the user the current process runs as (see
+    /// [`BoxedSecurityDescriptor::create_current_user_generic_all`]). Other options can still be configured on the
+    /// returned builder.
+    ///
+    /// Creating a state with the [`CreatableStateLifetime::Permanent`] or [`CreatableStateLifetime::Persistent`]
+    /// lifetime requires the `SeCreatePermanentPrivilege` privilege (see [`can_create_permanent_shared_objects`]).
+    /// This is checked upfront, so a missing privilege is reported here rather than when creating the state.
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use wnf::{CreatableStateLifetime, OwnedState, StateCreation};
+    ///
+    /// let state: OwnedState<u32> =
+    ///     StateCreation::session_scoped_for_current_user(CreatableStateLifetime::Temporary)?.create_owned()?;
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an error if the given lifetime requires a privilege that the current process does not have, in which
+    /// case [`io::Error::kind`] returns [`ErrorKind::PermissionDenied`] and the error wraps a
+    /// [`CreateError::MissingPrivilege`], or if checking the privilege, looking up the current user or creating the
+    /// security descriptor fails
+    pub fn session_scoped_for_current_user(
+        lifetime: CreatableStateLifetime,
+    ) -> io::Result<StateCreation<CreatableStateLifetime, DataScope, BoxedSecurityDescriptor>> {
+        ensure_privilege(required_privilege(lifetime, DataScope::Session))?;
+
+        Ok(Self::new()
+            .lifetime(lifetime)
+            .scope(DataScope::Session)
+            .security_descriptor(BoxedSecurityDescriptor::create_current_user_generic_all()?))
+    }
 }
 
 impl<L, S, SD> StateCreation<L, S, SD> {
@@ -265,6 +446,7 @@ impl<L, S, SD> StateCreation<L, S, SD> {
         StateCreation {
             lifetime,
 
+            drop_policy: self.drop_policy,
             scope: self.scope,
             security_descriptor: self.security_descriptor,
             maximum_state_size: self.maximum_state_size,
@@ -280,6 +462,7 @@ impl<L, S, SD> StateCreation<L, S, SD> {
         StateCreation {
             scope,
 
+            drop_policy: self.drop_policy,
             lifetime: self.lifetime,
             maximum_state_size: self.maximum_state_size,
             security_descriptor: self.security_descriptor,
@@ -287,6 +470,17 @@ impl<L, S, SD> StateCreation<L, S, SD> {
         }
     }
 
+    /// Configures the drop policy of a [`StateCreation`] builder
+    ///
+    /// This determines what happens when an [`OwnedState<T>`] created by [`StateCreation::create_owned`] is dropped,
+    /// see [`DropPolicy`]. It has no effect on states created by [`StateCreation::create_static`].
+    ///
+    /// If this is not configured, it defaults to [`DropPolicy::Delete`].
+    #[must_use]
+    pub fn drop_policy(self, drop_policy: DropPolicy) -> StateCreation<L, S, SD> {
+        StateCreation { drop_policy, ..self }
+    }
+
     /// Configures the maximum state size of a [`StateCreation`] builder
     ///
     /// If this is not configured, it defaults to `0x1000` (4 KB), which is the absolute maximum size of a state.
@@ -309,6 +503,7 @@ impl<L, S, SD> StateCreation<L, S, SD> {
         StateCreation {
             security_descriptor,
 
+            drop_policy: self.drop_policy,
             lifetime: self.lifetime,
             maximum_state_size: self.maximum_state_size,
             scope: self.scope,
@@ -334,18 +529,26 @@ where
 {
     /// Creates an [`OwnedState<T>`] from this [`StateCreation`]
     ///
-    /// Note that the state will be deleted when the returned [`OwnedState<T>`] is dropped. You can avoid this by
-    /// calling [`StateCreation::create_static`] instead, which returns a statically borrowed state.
+    /// Note that the state will be deleted when the returned [`OwnedState<T>`] is dropped, unless configured otherwise
+    /// through [`StateCreation::drop_policy`]. You can avoid this by calling [`StateCreation::create_static`] instead,
+    /// which returns a statically borrowed state.
+    ///
+    /// If creating the state requires a privilege (see [`StateCreation::required_privilege`]), consider using
+    /// [`StateCreation::create_owned_privileged`] instead.
     ///
     /// This method is only available once [`StateCreation::lifetime`] and [`StateCreation::scope`] have been called.
     ///
     /// # Errors
-    /// Returns an error if creating the state fails
+    /// Returns an error if creating the state fails. If the reason for the failure is known, the error wraps a
+    /// [`CreateError`].
     pub fn create_owned<T>(self) -> io::Result<OwnedState<T>>
     where
         T: ?Sized,
     {
-        self.create_raw().map(OwnedState::from_raw)
+        let drop_policy = self.drop_policy;
+        let mut state = self.create_raw().map(OwnedState::from_raw)?;
+        state.set_drop_policy(drop_policy);
+        Ok(state)
     }
 
     /// Creates a state from this [`StateCreation`], returning a [`BorrowedState<'static, T>`](BorrowedState)
@@ -369,7 +572,8 @@ where
     /// This method is only available once [`StateCreation::lifetime`] and [`StateCreation::scope`] have been called.
     ///
     /// # Errors
-    /// Returns an error if creating the state fails
+    /// Returns an error if creating the state fails. If the reason for the failure is known, the error wraps a
+    /// [`CreateError`].
     pub fn create_static<T>(self) -> io::Result<BorrowedState<'static, T>>
     where
         T: ?Sized,
@@ -377,19 +581,114 @@ where
         self.create_raw().map(BorrowedState::from_raw)
     }
 
+    /// Returns the privilege that is necessary for creating a state from this [`StateCreation`], if any
+    ///
+    /// Creating a state with the [`CreatableStateLifetime::Permanent`] or [`CreatableStateLifetime::Persistent`]
+    /// lifetime or with the [`DataScope::Process`] scope requires [`Privilege::CreatePermanent`].
+    ///
+    /// This method is only available once [`StateCreation::lifetime`] and [`StateCreation::scope`] have been called.
+    pub fn required_privilege(&self) -> Option<Privilege> {
+        required_privilege(self.lifetime, self.scope)
+    }
+
+    /// Creates an [`OwnedState<T>`] from this [`StateCreation`], checking upfront that the current process holds the
+    /// required privilege
+    ///
+    /// This is the same as [`StateCreation::create_owned`], except that if creating the state requires a privilege
+    /// (see [`StateCreation::required_privilege`]), the token of the current process is checked for that privilege
+    /// before trying to create the state. Using this method for states with a lifetime or scope requiring a
+    /// privilege makes it explicit in your code that creating the state fails for processes not holding it.
+    ///
+    /// This method is only available once [`StateCreation::lifetime`] and [`StateCreation::scope`] have been called.
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use wnf::{CreatableStateLifetime, DataScope, OwnedState, StateCreation};
+    ///
+    /// let result = StateCreation::new()
+    ///     .lifetime(CreatableStateLifetime::Permanent { persist_data: true })
+    ///     .scope(DataScope::Machine)
+    ///     .create_owned_privileged::<u32>();
+    ///
+    /// match result {
+    ///     Ok(state) => state.set(&42)?,
+    ///     Err(err) => eprintln!("Failed to create state: {err}"),
+    /// }
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an error if the current process does not hold the required privilege, in which case
+    /// [`io::Error::kind`] returns [`ErrorKind::PermissionDenied`] and the error wraps a
+    /// [`CreateError::MissingPrivilege`], or if checking the privilege or creating the state fails
+    pub fn create_owned_privileged<T>(self) -> io::Result<OwnedState<T>>
+    where
+        T: ?Sized,
+    {
+        ensure_privilege(self.required_privilege())?;
+        self.create_owned()
+    }
+
+    /// Creates a state from this [`StateCreation`], returning a [`BorrowedState<'static, T>`](BorrowedState) and
+    /// checking upfront that the current process holds the required privilege
+    ///
+    /// This is the same as [`StateCreation::create_static`], except that the token of the current process is checked
+    /// for the required privilege upfront, see [`StateCreation::create_owned_privileged`].
+    ///
+    /// This method is only available once [`StateCreation::lifetime`] and [`StateCreation::scope`] have been called.
+    ///
+    /// # Errors
+    /// Returns an error if the current process does not hold the required privilege, in which case
+    /// [`io::Error::kind`] returns [`ErrorKind::PermissionDenied`] and the error wraps a
+    /// [`CreateError::MissingPrivilege`], or if checking the privilege or creating the state fails
+    pub fn create_static_privileged<T>(self) -> io::Result<BorrowedState<'static, T>>
+    where
+        T: ?Sized,
+    {
+        ensure_privilege(self.required_privilege())?;
+        self.create_static()
+    }
+
     /// Creates a [`RawState<T>`] from this [`StateCreation`]
     fn create_raw<T>(self) -> io::Result<RawState<T>>
     where
         T: ?Sized,
     {
+        let maximum_state_size = self.maximum_state_size.unwrap_or(MAXIMUM_STATE_SIZE);
+        if maximum_state_size > MAXIMUM_STATE_SIZE {
+            return Err(CreateError::InvalidMaximumSize { maximum_state_size }.into());
+        }
+
+        let security_descriptor = self.security_descriptor.try_into_security_descriptor()?;
+
         RawState::create(
             self.lifetime.into(),
             self.scope,
             self.lifetime.persist_data(),
             self.type_id,
-            self.maximum_state_size.unwrap_or(MAXIMUM_STATE_SIZE),
-            self.security_descriptor.try_into_security_descriptor()?,
+            maximum_state_size,
+            security_descriptor,
         )
+        .map_err(|err| match err.raw_os_error() {
+            Some(code) => CreateError::from_status(NTSTATUS(code), self.lifetime, self.scope).map_or(err, Into::into),
+            None => err,
+        })
+    }
+}
+
+/// Returns the privilege that is necessary for creating a state with the given lifetime and scope, if any
+fn required_privilege(lifetime: CreatableStateLifetime, scope: DataScope) -> Option<Privilege> {
+    (lifetime != CreatableStateLifetime::Temporary || scope == DataScope::Process).then_some(Privilege::CreatePermanent)
+}
+
+/// Returns an error if the given privilege is required but not held by the current process
+fn ensure_privilege(privilege: Option<Privilege>) -> io::Result<()> {
+    match privilege {
+        Some(privilege @ Privilege::CreatePermanent) if !can_create_permanent_shared_objects()? => {
+            Err(CreateError::MissingPrivilege(privilege).into())
+        }
+        _ => Ok(()),
     }
 }
 
@@ -420,10 +719,85 @@ where
     /// Note that an [`OwnedState<T>`] will be deleted automatically when it is dropped, so calling this method is
     /// usually not necessary. It is useful, however, if you want to handle errors.
     ///
+    /// If the drop policy of this state is [`DropPolicy::ClearThenDelete`], the state data are cleared before
+    /// deleting the state. The state is deleted regardless of the drop policy, even if it is [`DropPolicy::Keep`].
+    ///
     /// # Errors
-    /// Returns an error if deleting the state fails
+    /// Returns an error if clearing the state data or deleting the state fails
     pub fn delete(self) -> io::Result<()> {
-        self.into_raw().delete()
+        let drop_policy = self.drop_policy();
+        let raw = self.into_raw();
+
+        if drop_policy == DropPolicy::ClearThenDelete {
+            raw.clear_data()?;
+        }
+
+        raw.delete()
+    }
+}
+
+impl<T> OwnedState<T>
+where
+    T: ?Sized,
+{
+    /// Creates a new state from the given [`StateCreation`] and copies the data of this state into it
+    ///
+    /// This is useful for migration scenarios, e.g. for moving the data of a temporary state into a persistent state.
+    /// The new state gets a new name and its own change stamp history: If this state has been updated at least once,
+    /// the new state is updated once with its data, so its change stamp is `1`. Otherwise, the new state is not updated
+    /// at all, so its change stamp is `0`.
+    ///
+    /// The lifetime, scope and other options of the new state are taken from `creation`. If `creation` does not
+    /// configure a type id, the new state gets the same type id as this state. The security descriptor of a state
+    /// cannot be queried, so the security descriptor of the new state is the one configured in `creation` (or the
+    /// default one).
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use wnf::{CreatableStateLifetime, DataScope, OwnedState, StateCreation};
+    ///
+    /// let state = OwnedState::<u32>::create_temporary()?;
+    /// state.set(&42)?;
+    ///
+    /// let creation = StateCreation::new()
+    ///     .lifetime(CreatableStateLifetime::Temporary)
+    ///     .scope(DataScope::Session);
+    ///
+    /// let duplicate = state.duplicate(&creation)?;
+    /// assert_eq!(duplicate.get()?, 42);
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an error if querying the data of this state, creating the new state or updating it fails. If updating
+    /// the new state fails, it is deleted again.
+    pub fn duplicate<SD>(
+        &self,
+        creation: &StateCreation<CreatableStateLifetime, DataScope, SD>,
+    ) -> io::Result<OwnedState<T>>
+    where
+        SD: TryIntoSecurityDescriptor + Clone,
+    {
+        self.raw.duplicate(creation)
+    }
+}
+
+impl<T> BorrowedState<'_, T>
+where
+    T: ?Sized,
+{
+    /// Creates a new state from the given [`StateCreation`] and copies the data of this state into it
+    ///
+    /// See [`OwnedState::duplicate`]
+    pub fn duplicate<SD>(
+        self,
+        creation: &StateCreation<CreatableStateLifetime, DataScope, SD>,
+    ) -> io::Result<OwnedState<T>>
+    where
+        SD: TryIntoSecurityDescriptor + Clone,
+    {
+        self.raw.duplicate(creation)
     }
 }
 
@@ -467,6 +841,29 @@ impl<T> RawState<T>
 where
     T: ?Sized,
 {
+    /// Creates a new state from the given [`StateCreation`] and copies the data of this state into it
+    fn duplicate<SD>(self, creation: &StateCreation<CreatableStateLifetime, DataScope, SD>) -> io::Result<OwnedState<T>>
+    where
+        SD: TryIntoSecurityDescriptor + Clone,
+    {
+        let mut creation = creation.clone();
+        if creation.type_id.is_none() {
+            creation.type_id = self.type_id;
+        }
+
+        let data: StampedData<Box<[u8]>> = self.cast::<[u8]>().query_as()?;
+        let state = creation.create_owned::<T>()?;
+
+        if data.change_stamp() != ChangeStamp::initial() {
+            if let Err(err) = state.raw.cast::<[u8]>().set(data.data()) {
+                let _ = state.delete();
+                return Err(err);
+            }
+        }
+
+        Ok(state)
+    }
+
     /// Creates a state
     fn create(
         name_lifetime: StateLifetime,
@@ -476,6 +873,10 @@ where
         maximum_state_size: usize,
         security_descriptor: impl Borrow<SecurityDescriptor>,
     ) -> io::Result<Self> {
+        let capabilities = os_capabilities();
+        capabilities.ensure_data_scope(data_scope)?;
+        capabilities.ensure_type_id(type_id)?;
+
         let mut opaque_value = 0;
 
         let name_lifetime = name_lifetime as u32;
@@ -505,22 +906,24 @@ where
         if result.is_ok() {
             let state_name = StateName::from_opaque_value(opaque_value);
 
-            debug!(
-                target: ntapi::TRACING_TARGET,
+            ntapi_event!(
+                WnfRoutine::CreateStateName,
+                false,
+                output.state_name = %state_name,
                 ?result,
                 input.name_lifetime = name_lifetime,
                 input.data_scope = data_scope,
                 input.persist_data = persist_data,
                 input.type_id = %type_id,
                 input.maximum_state_size = maximum_state_size,
-                output.state_name = %state_name,
                 "NtCreateWnfStateName",
             );
 
             Ok(Self::from_state_name_and_type_id(state_name, type_id))
         } else {
-            debug!(
-                target: ntapi::TRACING_TARGET,
+            ntapi_event!(
+                WnfRoutine::CreateStateName,
+                true,
                 ?result,
                 input.name_lifetime = name_lifetime,
                 input.data_scope = data_scope,
@@ -540,16 +943,40 @@ where
         // The pointer points to a valid `u64` because it comes from a live reference
         let result = unsafe { ntapi::NtDeleteWnfStateName(&self.state_name.opaque_value()) };
 
-        debug!(
-            target: ntapi::TRACING_TARGET,
-            ?result,
+        ntapi_event!(
+            WnfRoutine::DeleteStateName,
+            result.is_err(),
             input.state_name = %self.state_name,
+            ?result,
             "NtDeleteWnfStateName",
         );
 
-        result.ok()?;
+        if result.is_err() {
+            return Err(self.ntapi_error(WnfRoutine::DeleteStateName, result));
+        }
+
         Ok(())
     }
+
+    /// Overwrites the data of this state with the same number of zero bytes
+    fn clear_data(self) -> io::Result<()> {
+        let size = self.cast::<OpaqueData>().query_as::<OpaqueData>()?.into_data().size();
+        self.cast::<[u8]>().set(&vec![0; size])
+    }
+
+    /// Applies the given drop policy to this state
+    pub(crate) fn apply_drop_policy(self, drop_policy: DropPolicy) -> io::Result<()> {
+        match drop_policy {
+            DropPolicy::Keep => Ok(()),
+            DropPolicy::Delete => self.delete(),
+            DropPolicy::ClearThenDelete => {
+                // Try to delete the state even if clearing its data fails
+                let clear_result = self.clear_data();
+                self.delete()?;
+                clear_result
+            }
+        }
+    }
 }
 
 /// Making [`TryIntoSecurityDescriptor`] a sealed trait
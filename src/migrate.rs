@@ -0,0 +1,405 @@
+//! Exporting and importing permanent and persistent states in the format of `.reg` files
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io;
+
+use thiserror::Error;
+
+use crate::manage::{CreatableStateLifetime, StateCreation};
+use crate::registry::registry_sub_key;
+use crate::state::BorrowedState;
+use crate::state_name::{StateLifetime, StateName, StateNameDescriptor};
+
+/// The header line of a `.reg` file
+const HEADER: &str = "Windows Registry Editor Version 5.00";
+
+/// The line ending used in `.reg` files
+const LINE_ENDING: &str = "\r\n";
+
+/// The maximum length of a line written to a `.reg` file, excluding the line ending
+const MAX_LINE_LEN: usize = 80;
+
+/// An error exporting or importing states in the format of `.reg` files
+///
+/// This is returned, wrapped in an [`io::Error`], by [`export_registry`] and [`import_registry`].
+#[derive(Clone, Debug, Error, Eq, Hash, PartialEq)]
+pub enum RegFileError {
+    /// The state name to export has a lifetime that is not persisted in the registry
+    #[error("state name {state_name} has lifetime {lifetime:?}, which cannot be exported")]
+    UnsupportedLifetime {
+        /// The state name to export
+        state_name: StateName,
+
+        /// The lifetime of the state name
+        lifetime: StateLifetime,
+    },
+
+    /// The input doesn't start with the `.reg` file header
+    #[error("missing header \"{HEADER}\"")]
+    MissingHeader,
+
+    /// The input contains a registry key that doesn't hold the data of permanent or persistent states
+    #[error("unsupported registry key in line {line}: {key}")]
+    UnsupportedKey {
+        /// The (one-based) number of the line containing the key
+        line: usize,
+
+        /// The registry key
+        key: String,
+    },
+
+    /// The input contains a line that is not a valid state data entry
+    #[error("invalid entry in line {line}")]
+    InvalidEntry {
+        /// The (one-based) number of the line containing the entry
+        line: usize,
+    },
+}
+
+/// Exports the data of the given permanent or persistent states in the format of a `.reg` file
+///
+/// The data of every state is written as a binary value named after the hexadecimal representation of the opaque value
+/// of its state name, below the `Data` subkey of the registry key corresponding to the lifetime of the state (see the
+/// respective [`StateLifetime`] variants). The result can be inspected with any text editor and imported again through
+/// [`import_registry`], e.g. on a different machine, which gives administrators a way of migrating states.
+///
+/// The data are read through the WNF API rather than from the registry, so this also works for states whose data are
+/// not persisted. Note that only the data of the states are exported, not their type ids or security descriptors,
+/// which cannot be queried.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use wnf::StateLifetime;
+///
+/// let state_names = wnf::registered_state_names(StateLifetime::Persistent)?;
+/// match wnf::export_registry(state_names.into_iter().take(1)) {
+///     Ok(reg) => println!("{reg}"),
+///     Err(err) => eprintln!("Failed to export state: {err}"),
+/// }
+/// # Ok(()) }
+/// ```
+///
+/// # Errors
+/// Returns an error of kind [`ErrorKind::InvalidInput`](io::ErrorKind::InvalidInput) wrapping a
+/// [`RegFileError::UnsupportedLifetime`] if any of the given state names is neither permanent nor persistent. Also
+/// returns an error if querying the data of any of the states fails.
+pub fn export_registry<I>(state_names: I) -> io::Result<String>
+where
+    I: IntoIterator<Item = StateName>,
+{
+    let mut entries = Vec::new();
+
+    for state_name in state_names {
+        let descriptor = StateNameDescriptor::try_from(state_name)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+        if data_key(descriptor.lifetime).is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                RegFileError::UnsupportedLifetime {
+                    state_name,
+                    lifetime: descriptor.lifetime,
+                },
+            ));
+        }
+
+        let data = BorrowedState::<[u8]>::from_state_name(state_name).get_boxed()?;
+        entries.push(RegEntry {
+            state_name,
+            lifetime: descriptor.lifetime,
+            data: data.into_vec(),
+        });
+    }
+
+    Ok(write_reg(&entries))
+}
+
+/// Imports states from the format of a `.reg` file, as produced by [`export_registry`]
+///
+/// Since WNF assigns the name of a state upon creation, the imported states cannot keep their exported names. Instead,
+/// a new state is created for every entry, with the lifetime given by the registry key of the entry and the data scope
+/// given by its exported state name, and updated with the exported data. For permanent states, the data are persisted.
+/// The new states are not deleted automatically and get the default security descriptor and no type id.
+///
+/// This returns a map from the exported state names to the names of the newly created states, which can be used to
+/// update references to the states, e.g. in configuration files.
+///
+/// Creating permanent and persistent states requires the `SeCreatePermanentPrivilege` privilege, see
+/// [`StateCreation::required_privilege`].
+///
+/// # Errors
+/// Returns an error of kind [`ErrorKind::InvalidData`](io::ErrorKind::InvalidData) wrapping a [`RegFileError`] if
+/// the input is malformed, in which case no states have been created. Also returns an error if creating or updating
+/// any of the states fails, in which case the states created so far are not deleted, except for a state that could be
+/// created but not updated.
+pub fn import_registry(reg: &str) -> io::Result<HashMap<StateName, StateName>> {
+    let entries = parse_reg(reg).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    let mut state_names = HashMap::new();
+
+    for entry in entries {
+        let descriptor = StateNameDescriptor::try_from(entry.state_name)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        let lifetime = match entry.lifetime {
+            StateLifetime::Permanent => CreatableStateLifetime::Permanent { persist_data: true },
+            _ => CreatableStateLifetime::Persistent,
+        };
+
+        let state = StateCreation::new()
+            .lifetime(lifetime)
+            .scope(descriptor.data_scope)
+            .create_static::<[u8]>()?;
+
+        if let Err(err) = state.set(&entry.data) {
+            let _ = state.delete();
+            return Err(err);
+        }
+
+        state_names.insert(entry.state_name, state.state_name());
+    }
+
+    Ok(state_names)
+}
+
+/// An entry of a `.reg` file holding the data of a state
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct RegEntry {
+    state_name: StateName,
+    lifetime: StateLifetime,
+    data: Vec<u8>,
+}
+
+/// Returns the registry key under which the data of states with the given lifetime are exported, if any
+fn data_key(lifetime: StateLifetime) -> Option<String> {
+    match lifetime {
+        StateLifetime::Permanent | StateLifetime::Persistent => {
+            registry_sub_key(lifetime).map(|sub_key| format!(r"HKEY_LOCAL_MACHINE\{sub_key}\Data"))
+        }
+        StateLifetime::WellKnown | StateLifetime::Temporary => None,
+    }
+}
+
+/// Writes the given entries in the format of a `.reg` file, grouped by lifetime
+fn write_reg(entries: &[RegEntry]) -> String {
+    let mut reg = String::from(HEADER);
+    reg.push_str(LINE_ENDING);
+
+    for lifetime in [StateLifetime::Permanent, StateLifetime::Persistent] {
+        let mut entries = entries.iter().filter(|entry| entry.lifetime == lifetime).peekable();
+
+        if entries.peek().is_none() {
+            continue;
+        }
+
+        let key = data_key(lifetime).expect("lifetime is persisted in the registry");
+        let _ = write!(reg, "{LINE_ENDING}[{key}]{LINE_ENDING}");
+
+        for entry in entries {
+            write_value(&mut reg, entry);
+        }
+    }
+
+    reg
+}
+
+/// Writes the given entry as a binary value, wrapping long lines like the Registry Editor does
+fn write_value(reg: &mut String, entry: &RegEntry) {
+    let mut line = format!("\"{:016X}\"=hex:", entry.state_name.opaque_value());
+
+    for (index, byte) in entry.data.iter().enumerate() {
+        let is_last = index + 1 == entry.data.len();
+
+        // Three characters for the byte and the separator plus one for the line continuation character
+        if line.len() + 4 > MAX_LINE_LEN {
+            reg.push_str(&line);
+            reg.push('\\');
+            reg.push_str(LINE_ENDING);
+            line = String::from("  ");
+        }
+
+        let _ = write!(line, "{byte:02x}{}", if is_last { "" } else { "," });
+    }
+
+    reg.push_str(&line);
+    reg.push_str(LINE_ENDING);
+}
+
+/// Parses entries from the format of a `.reg` file
+fn parse_reg(reg: &str) -> Result<Vec<RegEntry>, RegFileError> {
+    let reg = reg.strip_prefix('\u{feff}').unwrap_or(reg);
+    let mut lines = logical_lines(reg).filter(|(_, line)| !line.is_empty() && !line.starts_with(';'));
+
+    match lines.next() {
+        Some((_, header)) if header == HEADER => {}
+        _ => return Err(RegFileError::MissingHeader),
+    }
+
+    let mut entries = Vec::new();
+    let mut lifetime = None;
+
+    for (line_number, line) in lines {
+        if let Some(key) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            let key_lifetime = [StateLifetime::Permanent, StateLifetime::Persistent]
+                .into_iter()
+                .find(|&lifetime| data_key(lifetime).is_some_and(|data_key| data_key.eq_ignore_ascii_case(key)));
+
+            match key_lifetime {
+                Some(key_lifetime) => lifetime = Some(key_lifetime),
+                None => {
+                    return Err(RegFileError::UnsupportedKey {
+                        line: line_number,
+                        key: key.to_owned(),
+                    })
+                }
+            }
+        } else {
+            let invalid_entry = || RegFileError::InvalidEntry { line: line_number };
+            let lifetime = lifetime.ok_or_else(invalid_entry)?;
+            let entry = parse_value(&line, lifetime).ok_or_else(invalid_entry)?;
+            entries.push(entry);
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Parses a binary value below the key for the given lifetime into an entry, returning [`None`] if it is invalid
+fn parse_value(line: &str, lifetime: StateLifetime) -> Option<RegEntry> {
+    let (name, value) = line.strip_prefix('"')?.split_once("\"=")?;
+    let state_name: StateName = name.parse().ok()?;
+
+    let descriptor = StateNameDescriptor::try_from(state_name).ok()?;
+    if descriptor.lifetime != lifetime {
+        return None;
+    }
+
+    let hex = value.strip_prefix("hex:")?.trim();
+    let data = if hex.is_empty() {
+        Vec::new()
+    } else {
+        hex.split(',')
+            .map(|byte| {
+                let byte = byte.trim();
+                if byte.len() == 2 {
+                    u8::from_str_radix(byte, 16).ok()
+                } else {
+                    None
+                }
+            })
+            .collect::<Option<_>>()?
+    };
+
+    Some(RegEntry {
+        state_name,
+        lifetime,
+        data,
+    })
+}
+
+/// Returns the lines of the given `.reg` file content together with their (one-based) line numbers, with continued
+/// lines joined and surrounding whitespace removed
+fn logical_lines(reg: &str) -> impl Iterator<Item = (usize, String)> + '_ {
+    let mut physical_lines = reg.lines().enumerate();
+
+    std::iter::from_fn(move || {
+        let (index, first_line) = physical_lines.next()?;
+        let mut line = first_line.trim().to_owned();
+
+        while line.ends_with('\\') {
+            line.pop();
+
+            match physical_lines.next() {
+                Some((_, next_line)) => line.push_str(next_line.trim()),
+                None => break,
+            }
+        }
+
+        Some((index + 1, line))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERMANENT_STATE_NAME: StateName = StateName::from_opaque_value(0x41C6_4E6D_A3BC_0165);
+    const PERSISTENT_STATE_NAME: StateName = StateName::from_opaque_value(0x41C6_4E6D_A3BC_0155);
+
+    #[test]
+    fn write_and_parse_round_trip() {
+        let entries = vec![
+            RegEntry {
+                state_name: PERSISTENT_STATE_NAME,
+                lifetime: StateLifetime::Persistent,
+                data: Vec::new(),
+            },
+            RegEntry {
+                state_name: PERMANENT_STATE_NAME,
+                lifetime: StateLifetime::Permanent,
+                data: (0..100).collect(),
+            },
+        ];
+
+        let reg = write_reg(&entries);
+
+        assert!(reg.starts_with(HEADER));
+        assert!(reg
+            .lines()
+            .filter(|line| !line.starts_with('['))
+            .all(|line| line.len() <= MAX_LINE_LEN));
+
+        let mut parsed_entries = parse_reg(&reg).unwrap();
+        parsed_entries.reverse();
+
+        assert_eq!(parsed_entries, entries);
+    }
+
+    #[test]
+    fn write_reg_format() {
+        let entries = [RegEntry {
+            state_name: PERMANENT_STATE_NAME,
+            lifetime: StateLifetime::Permanent,
+            data: vec![0x01, 0xAB],
+        }];
+
+        assert_eq!(
+            write_reg(&entries),
+            "Windows Registry Editor Version 5.00\r\n\
+             \r\n\
+             [HKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Windows NT\\CurrentVersion\\Notifications\\Data]\r\n\
+             \"41C64E6DA3BC0165\"=hex:01,ab\r\n"
+        );
+    }
+
+    #[test]
+    fn parse_reg_missing_header() {
+        assert_eq!(
+            parse_reg("[HKEY_LOCAL_MACHINE\\SOFTWARE]"),
+            Err(RegFileError::MissingHeader)
+        );
+    }
+
+    #[test]
+    fn parse_reg_unsupported_key() {
+        let reg = "Windows Registry Editor Version 5.00\n\n[HKEY_LOCAL_MACHINE\\SOFTWARE]\n";
+
+        assert_eq!(
+            parse_reg(reg),
+            Err(RegFileError::UnsupportedKey {
+                line: 3,
+                key: String::from("HKEY_LOCAL_MACHINE\\SOFTWARE"),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_reg_lifetime_mismatch() {
+        let reg = "Windows Registry Editor Version 5.00\n\
+                   [HKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Windows NT\\CurrentVersion\\VolatileNotifications\\Data]\n\
+                   \"41C64E6DA3BC0165\"=hex:01\n";
+
+        assert_eq!(parse_reg(reg), Err(RegFileError::InvalidEntry { line: 3 }));
+    }
+}
@@ -7,12 +7,20 @@
 //! Functions in the `ntrtl` submodule, whose names start with `Rtl` (standing for *runtime library*), provide
 //! higher-level abstractions while functions in the `ntexapi` submodule, whose names start with `Nt`, are more low
 //! level. We use a combination of both, choosing whichever function is more suitable for the task at hand.
+//!
+//! The rest of the crate calls these bindings directly rather than through an abstract backend that could be replaced
+//! by an in-memory implementation, e.g. for unit testing on non-Windows systems. This is deliberate: The crate only
+//! supports Windows, its public API is built on Windows types (such as security descriptors) and faithfully emulating
+//! the semantics of WNF (change stamps, data scopes, security checks, the threading model of callbacks) would be a
+//! maintenance burden of its own without giving any guarantees about the behavior of the real API. Code using this
+//! crate can be tested against real states instead, see the `testing` module (requires the `test_util` feature).
 
 #![deny(unsafe_code)]
 
 pub(crate) use ntexapi::*;
 #[cfg(feature = "subscribe")]
 pub(crate) use ntrtl::*;
+pub(crate) use ntrtl_version::*;
 
 /// Target used for logging calls to NTAPI functions using the `tracing` crate
 pub(crate) const TRACING_TARGET: &str = "wnf::ntapi";
@@ -88,7 +96,7 @@ mod ntexapi {
         /// # Arguments
         /// - (in) `state_name`: Pointer to the state name
         /// - (in) `type_id`: Pointer to a GUID used as the type ID, can be a null pointer
-        /// - (in) `explicit_scope`: Irrelevant, can be a null pointer
+        /// - (in) `explicit_scope`: Pointer to an (undocumented) explicit scope, can be a null pointer
         /// - (out) `change_stamp`: Pointer to a `u32` buffer the change stamp will be written to
         /// - (out) `buffer`: Pointer to a buffer the data will be written to
         /// - (in, out) `buffer_size`: Pointer to a `u32` buffer containing the size of the buffer pointed to by
@@ -100,6 +108,8 @@ mod ntexapi {
         /// # Safety
         /// - `state_name` must point to a valid `u64`
         /// - `type_id` must either be a null pointer or point to a valid [`GUID`]
+        /// - `explicit_scope` must either be a null pointer or satisfy the (undocumented) requirements for an explicit
+        ///   scope
         /// - `change_stamp` must be valid for writes of `u32`
         /// - `buffer` must be valid for writes of at least size `*buffer_size`
         /// - `buffer_size` must point to a valid `u32`
@@ -210,6 +220,7 @@ mod ntrtl {
     ///
     /// # Assumption
     /// During the runtime of the callback:
+    /// - `type_id` is either a null pointer or points to a valid `GUID`
     /// - `buffer` is valid for reads of size `buffer_size`
     /// - the memory range of size `buffer_size` starting at `buffer` is initialized
     pub(crate) type WnfUserCallback = extern "system" fn(
@@ -270,9 +281,12 @@ mod ntrtl {
         /// # Safety
         /// - `subscription_handle` must have been returned from a successful call to
         ///   `RtlSubscribeWnfStateChangeNotification`
-        /// - `RtlUnsubscribeWnfStateChangeNotification` must not have been called with `subscription_handle` before
+        /// - `RtlUnsubscribeWnfStateChangeNotification` must not have been called successfully with
+        ///   `subscription_handle` before
         ///
         /// # Assumptions
+        /// - If this function fails, the subscription is left intact and the function can be called again with the same
+        ///   `subscription_handle`
         /// - If `subscription_handle` was returned from a successful call of `RtlSubscribeWnfStateChangeNotification(_,
         ///   _, _, callback, callback_context, _, _, _)`, where `callback_context` is unique among all such calls, and
         ///   this function succeeds, then `callback` is not called with `callback_context` anymore.
@@ -280,3 +294,27 @@ mod ntrtl {
         pub(crate) fn RtlUnsubscribeWnfStateChangeNotification(subscription_handle: *mut c_void) -> NTSTATUS;
     }
 }
+
+/// Raw binding to the function of the RTL support library for obtaining the version of the running operating system
+///
+/// This is not part of the WNF API, but is needed to detect which WNF features are supported by the operating system.
+mod ntrtl_version {
+    #[link(name = "ntdll")]
+    extern "system" {
+        /// Obtains the version numbers of the running operating system
+        ///
+        /// Unlike `GetVersionExW`, this is not subject to application manifest based version lies.
+        ///
+        /// # Arguments
+        /// - (out) `major_version`: Pointer to a `u32` buffer the major version will be written to
+        /// - (out) `minor_version`: Pointer to a `u32` buffer the minor version will be written to
+        /// - (out) `build_number`: Pointer to a `u32` buffer the build number will be written to, where the upper four
+        ///   bits are flags (e.g. for checked builds) that are not part of the build number
+        ///
+        /// # Safety
+        /// - `major_version` must be valid for writes of `u32`
+        /// - `minor_version` must be valid for writes of `u32`
+        /// - `build_number` must be valid for writes of `u32`
+        pub(crate) fn RtlGetNtVersionNumbers(major_version: *mut u32, minor_version: *mut u32, build_number: *mut u32);
+    }
+}
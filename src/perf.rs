@@ -0,0 +1,139 @@
+//! Counting invocations of WNF API routines for profiling
+
+#![deny(unsafe_code)]
+
+use std::cell::Cell;
+use std::ops::Sub;
+
+use crate::trace::WnfRoutine;
+
+thread_local! {
+    /// The number of invocations of each WNF API routine on the current thread, indexed by [`WnfRoutine`]
+    static COUNTS: Cell<[u64; WnfRoutine::COUNT]> = const { Cell::new([0; WnfRoutine::COUNT]) };
+}
+
+/// A snapshot of the number of invocations of WNF API routines on the current thread
+///
+/// Every invocation of a WNF API routine (see [`WnfRoutine`]) made by this crate increments a counter for the routine.
+/// The counters are kept per thread, so invocations made by other threads, such as subscription callbacks running on
+/// the thread pool of the operating system, don't show up in the counters of the current thread. This makes the
+/// counters suitable for determining how many kernel calls a single call of the API of this crate makes, even when
+/// other threads are using states concurrently:
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use wnf::{OwnedState, PerfCounters, WnfRoutine};
+///
+/// let state = OwnedState::<u32>::create_temporary()?;
+/// state.set(&42)?;
+///
+/// let (value, counters) = PerfCounters::measure(|| state.get());
+///
+/// assert_eq!(value?, 42);
+/// assert_eq!(counters.count(WnfRoutine::QueryStateData), 1);
+/// assert_eq!(counters.total(), 1);
+/// # Ok(()) }
+/// ```
+///
+/// This requires the `perf_counters` feature, which is intended for profiling and benchmarking. Counting the
+/// invocations incurs a small overhead, so it should not be enabled in production builds.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct PerfCounters {
+    counts: [u64; WnfRoutine::COUNT],
+}
+
+impl PerfCounters {
+    /// Returns the current values of the counters of the current thread
+    pub fn current() -> Self {
+        Self {
+            counts: COUNTS.with(Cell::get),
+        }
+    }
+
+    /// Resets the counters of the current thread to zero
+    pub fn reset() {
+        COUNTS.with(|counts| counts.set([0; WnfRoutine::COUNT]));
+    }
+
+    /// Invokes the given closure, returning its result together with the invocations it made on the current thread
+    pub fn measure<F, R>(op: F) -> (R, Self)
+    where
+        F: FnOnce() -> R,
+    {
+        let before = Self::current();
+        let result = op();
+        (result, Self::current() - before)
+    }
+
+    /// Returns the number of invocations of the given routine
+    pub const fn count(&self, routine: WnfRoutine) -> u64 {
+        self.counts[routine as usize]
+    }
+
+    /// Returns the total number of invocations of all routines
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Returns an iterator over all routines together with their numbers of invocations
+    pub fn iter(&self) -> impl Iterator<Item = (WnfRoutine, u64)> + '_ {
+        WnfRoutine::ALL
+            .into_iter()
+            .map(|routine| (routine, self.count(routine)))
+    }
+}
+
+impl Sub for PerfCounters {
+    type Output = Self;
+
+    /// Returns the number of invocations made between two snapshots, saturating at zero if the counters were reset in
+    /// between
+    fn sub(mut self, rhs: Self) -> Self {
+        for (count, rhs_count) in self.counts.iter_mut().zip(rhs.counts) {
+            *count = count.saturating_sub(rhs_count);
+        }
+
+        self
+    }
+}
+
+/// Records an invocation of the given routine on the current thread
+pub(crate) fn record(routine: WnfRoutine) {
+    COUNTS.with(|counts| {
+        let mut values = counts.get();
+        values[routine as usize] += 1;
+        counts.set(values);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measure_counts_invocations_of_closure() {
+        record(WnfRoutine::UpdateStateData);
+
+        let ((), counters) = PerfCounters::measure(|| {
+            record(WnfRoutine::QueryStateData);
+            record(WnfRoutine::QueryStateData);
+            record(WnfRoutine::UpdateStateData);
+        });
+
+        assert_eq!(counters.count(WnfRoutine::QueryStateData), 2);
+        assert_eq!(counters.count(WnfRoutine::UpdateStateData), 1);
+        assert_eq!(counters.count(WnfRoutine::CreateStateName), 0);
+        assert_eq!(counters.total(), 3);
+        assert_eq!(counters.iter().filter(|&(_, count)| count > 0).count(), 2);
+    }
+
+    #[test]
+    fn reset_clears_counters() {
+        record(WnfRoutine::DeleteStateName);
+        let before = PerfCounters::current();
+
+        PerfCounters::reset();
+
+        assert_eq!(PerfCounters::current(), PerfCounters::default());
+        assert_eq!(PerfCounters::current() - before, PerfCounters::default());
+    }
+}
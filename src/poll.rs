@@ -0,0 +1,422 @@
+//! Methods for subscribing to state changes by polling instead of through the WNF API
+//!
+//! This module adds inherent impls to [`OwnedState<T>`] and [`BorrowedState<'_, T>`](BorrowedState) as well as the
+//! [`PollingSubscription<'_, F>`](PollingSubscription), [`SubscriptionMode`] and
+//! [`ModalSubscription<'_, F>`](ModalSubscription) types.
+
+use std::fmt::{self, Debug, Formatter};
+use std::io;
+use std::marker::PhantomData;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::data::{ChangeStamp, StampedData};
+use crate::state::{BorrowedState, OwnedState, RawState};
+use crate::subscribe::{call_listener, ScopedData, SeenChangeStamp, StateListener, Subscription, UpdateKind};
+
+impl<T> OwnedState<T>
+where
+    T: 'static + ?Sized,
+{
+    /// Subscribes the given state listener to this state by polling the state in the given interval
+    ///
+    /// This is a fallback for environments where subscribing through the WNF API (see
+    /// [`subscribe`](OwnedState::subscribe)) is not possible, e.g. because the `Rtl*` functions are blocked by security
+    /// software. Instead of being notified by the operating system, a background thread queries the state in the given
+    /// interval and calls the listener whenever the change stamp of the state has changed since the previous call.
+    ///
+    /// The listener semantics are the same as for [`subscribe`](OwnedState::subscribe): The listener receives a
+    /// [`DataAccessor<'_, T>`](crate::DataAccessor) for the queried data, and updates that happened between two polls
+    /// are reported as missed through [`DataAccessor::update_kind`](crate::DataAccessor::update_kind). See
+    /// [`subscribe`](OwnedState::subscribe) for the meaning of the `last_seen_change_stamp` argument. Queries that
+    /// fail, e.g. because the state does not exist (anymore), are ignored and retried in the next interval. If the
+    /// listener panics, the panic is reported to the hook set through
+    /// [`set_listener_panic_hook`](crate::set_listener_panic_hook) and the listener is not called anymore.
+    ///
+    /// Note that this is more expensive than a subscription through the WNF API because it queries the state data even
+    /// if they have not changed, and it adds a delay of up to `interval` to the delivery of every update.
+    ///
+    /// The listener is called on the background thread and never concurrently. It is not called anymore after
+    /// [`shutdown`](crate::shutdown) has been called.
+    ///
+    /// # Errors
+    /// Returns an error if querying the current change stamp fails (only for [`SeenChangeStamp::Current`]) or if
+    /// spawning the background thread fails
+    pub fn subscribe_polling<F>(
+        &self,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+        interval: Duration,
+    ) -> io::Result<PollingSubscription<'_, F>>
+    where
+        F: StateListener<T> + Send + 'static,
+    {
+        self.raw.subscribe_polling(listener, last_seen_change_stamp, interval)
+    }
+
+    /// Subscribes the given state listener to this state using the given subscription mode
+    ///
+    /// This makes it possible to select at runtime whether to subscribe through the WNF API (see
+    /// [`subscribe`](OwnedState::subscribe)) or by polling (see [`subscribe_polling`](OwnedState::subscribe_polling)),
+    /// e.g. depending on a configuration setting.
+    ///
+    /// # Errors
+    /// Returns an error if subscribing fails
+    pub fn subscribe_with_mode<F>(
+        &self,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+        mode: SubscriptionMode,
+    ) -> io::Result<ModalSubscription<'_, F>>
+    where
+        F: StateListener<T> + Send + 'static,
+    {
+        self.raw.subscribe_with_mode(listener, last_seen_change_stamp, mode)
+    }
+}
+
+impl<'a, T> BorrowedState<'a, T>
+where
+    T: 'static + ?Sized,
+{
+    /// Subscribes the given state listener to this state by polling the state in the given interval
+    ///
+    /// See [`OwnedState::subscribe_polling`]
+    pub fn subscribe_polling<F>(
+        self,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+        interval: Duration,
+    ) -> io::Result<PollingSubscription<'a, F>>
+    where
+        F: StateListener<T> + Send + 'static,
+    {
+        self.raw.subscribe_polling(listener, last_seen_change_stamp, interval)
+    }
+
+    /// Subscribes the given state listener to this state using the given subscription mode
+    ///
+    /// See [`OwnedState::subscribe_with_mode`]
+    pub fn subscribe_with_mode<F>(
+        self,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+        mode: SubscriptionMode,
+    ) -> io::Result<ModalSubscription<'a, F>>
+    where
+        F: StateListener<T> + Send + 'static,
+    {
+        self.raw.subscribe_with_mode(listener, last_seen_change_stamp, mode)
+    }
+}
+
+impl<T> RawState<T>
+where
+    T: 'static + ?Sized,
+{
+    /// Subscribes the given state listener to this state by polling the state in the given interval
+    fn subscribe_polling<'a, F>(
+        self,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+        interval: Duration,
+    ) -> io::Result<PollingSubscription<'a, F>>
+    where
+        F: StateListener<T> + Send + 'static,
+    {
+        let poller = Poller::new(self.cast(), last_seen_change_stamp)?;
+
+        let shared = Arc::new(Shared {
+            stopped: Mutex::new(false),
+            condvar: Condvar::new(),
+        });
+
+        let thread = thread::Builder::new().name("wnf-poll".into()).spawn({
+            let shared = Arc::clone(&shared);
+            move || poll(poller, listener, shared, interval)
+        })?;
+
+        Ok(PollingSubscription {
+            shared,
+            thread: Some(thread),
+            _marker: PhantomData,
+        })
+    }
+
+    /// Subscribes the given state listener to this state using the given subscription mode
+    fn subscribe_with_mode<'a, F>(
+        self,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+        mode: SubscriptionMode,
+    ) -> io::Result<ModalSubscription<'a, F>>
+    where
+        F: StateListener<T> + Send + 'static,
+    {
+        Ok(match mode {
+            SubscriptionMode::Notification => {
+                ModalSubscription::Notification(self.subscribe(listener, last_seen_change_stamp)?)
+            }
+            SubscriptionMode::Polling(interval) => {
+                ModalSubscription::Polling(self.subscribe_polling(listener, last_seen_change_stamp, interval)?)
+            }
+        })
+    }
+}
+
+/// Polls the given state, calling the given listener whenever the state has changed
+///
+/// This runs on the background thread of a [`PollingSubscription<'_, F>`](PollingSubscription) until it is stopped.
+fn poll<F, T>(mut poller: Poller, mut listener: F, shared: Arc<Shared>, interval: Duration)
+where
+    F: StateListener<T>,
+    T: ?Sized,
+{
+    loop {
+        let state_name = poller.state.state_name;
+
+        // Asserting unwind safety is fine because after a panic, the listener is not called anymore, just like a
+        // listener behind a poisoned mutex in a subscription through the WNF API
+        let panicked = call_listener(state_name, AssertUnwindSafe(|| poller.poll(&mut listener)));
+
+        if panicked || shared.wait_stopped(interval) {
+            return;
+        }
+    }
+}
+
+/// The mode in which a state listener is subscribed to a state
+///
+/// This is passed to [`OwnedState::subscribe_with_mode`] and [`BorrowedState::subscribe_with_mode`].
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum SubscriptionMode {
+    /// Subscribes through the WNF API, see [`OwnedState::subscribe`]
+    #[default]
+    Notification,
+
+    /// Subscribes by polling the state in the given interval, see [`OwnedState::subscribe_polling`]
+    Polling(Duration),
+}
+
+/// A subscription of a listener to updates of a state by polling the state
+///
+/// This is returned from [`OwnedState::subscribe_polling`] and [`BorrowedState::subscribe_polling`].
+///
+/// Dropping this stops the background thread. This blocks until the background thread has finished, which takes at
+/// most the time of a single poll including the listener call.
+#[must_use = "a `PollingSubscription` is unsubscribed immediately if it is not used"]
+pub struct PollingSubscription<'a, F> {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+    _marker: PhantomData<fn() -> &'a F>,
+}
+
+impl<F> Drop for PollingSubscription<'_, F> {
+    fn drop(&mut self) {
+        *self.shared.lock_stopped() = true;
+        self.shared.condvar.notify_all();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+// We cannot derive this because that would impose an unnecessary trait bound `F: Debug`
+impl<F> Debug for PollingSubscription<'_, F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PollingSubscription")
+            .field("thread", &self.thread)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A subscription of a listener to updates of a state in a mode selected at runtime
+///
+/// This is returned from [`OwnedState::subscribe_with_mode`] and [`BorrowedState::subscribe_with_mode`].
+#[must_use = "a `ModalSubscription` is unsubscribed immediately if it is not used"]
+pub enum ModalSubscription<'a, F> {
+    /// A subscription through the WNF API
+    Notification(Subscription<'a, F>),
+
+    /// A subscription by polling the state
+    Polling(PollingSubscription<'a, F>),
+}
+
+impl<F> ModalSubscription<'_, F> {
+    /// Returns whether this subscription polls the state
+    pub const fn is_polling(&self) -> bool {
+        matches!(self, Self::Polling(..))
+    }
+
+    /// Unsubscribes the listener for this [`ModalSubscription<'_, F>`](ModalSubscription)
+    ///
+    /// This happens automatically when the [`ModalSubscription<'_, F>`](ModalSubscription) is dropped, so there is
+    /// usually no need to call this method. Its only purpose is to enable you to handle errors while unsubscribing,
+    /// see [`Subscription::unsubscribe`].
+    ///
+    /// # Errors
+    /// Returns an error if unsubscribing fails, which can only happen for a subscription through the WNF API
+    pub fn unsubscribe(self) -> io::Result<()> {
+        match self {
+            Self::Notification(subscription) => subscription.unsubscribe(),
+            Self::Polling(..) => Ok(()),
+        }
+    }
+}
+
+// We cannot derive this because that would impose an unnecessary trait bound `F: Debug`
+impl<F> Debug for ModalSubscription<'_, F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Notification(subscription) => f.debug_tuple("Notification").field(subscription).finish(),
+            Self::Polling(subscription) => f.debug_tuple("Polling").field(subscription).finish(),
+        }
+    }
+}
+
+/// The state shared between a [`PollingSubscription<'_, F>`](PollingSubscription) and its background thread
+#[derive(Debug)]
+struct Shared {
+    stopped: Mutex<bool>,
+    condvar: Condvar,
+}
+
+impl Shared {
+    /// Waits for the given duration, returning early with `true` if the subscription has been stopped
+    fn wait_stopped(&self, duration: Duration) -> bool {
+        let (stopped, _) = self
+            .condvar
+            .wait_timeout_while(self.lock_stopped(), duration, |stopped| !*stopped)
+            .unwrap_or_else(PoisonError::into_inner);
+
+        *stopped
+    }
+
+    /// Locks the flag indicating whether the subscription has been stopped
+    fn lock_stopped(&self) -> MutexGuard<'_, bool> {
+        self.stopped.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Queries a state and deduplicates the results by their change stamps
+#[derive(Debug)]
+struct Poller {
+    state: RawState<[u8]>,
+    last_seen_change_stamp: Option<ChangeStamp>,
+    replay: bool,
+}
+
+impl Poller {
+    /// Creates a new [`Poller`] for the given state that assumes the given change stamp to have been seen
+    fn new(state: RawState<[u8]>, last_seen_change_stamp: SeenChangeStamp) -> io::Result<Self> {
+        let (last_seen_change_stamp, replay) = match last_seen_change_stamp {
+            SeenChangeStamp::None => (None, false),
+            SeenChangeStamp::Replay => (None, true),
+            SeenChangeStamp::Current => (Some(state.change_stamp()?), false),
+            SeenChangeStamp::Value(value) => (Some(value), false),
+        };
+
+        Ok(Self {
+            state,
+            last_seen_change_stamp,
+            replay,
+        })
+    }
+
+    /// Queries the state, calling the given listener if it has changed since the last call
+    fn poll<F, T>(&mut self, listener: &mut F)
+    where
+        F: StateListener<T>,
+        T: ?Sized,
+    {
+        if let Ok(data) = self.state.query_as() {
+            self.dispatch(data, listener);
+        }
+    }
+
+    /// Calls the given listener with the given data if they are newer than the ones it has last seen
+    fn dispatch<F, T>(&mut self, data: StampedData<Box<[u8]>>, listener: &mut F)
+    where
+        F: StateListener<T>,
+        T: ?Sized,
+    {
+        let (data, change_stamp) = data.into_data_change_stamp();
+
+        let update_kind = match self.last_seen_change_stamp {
+            Some(last_seen) if change_stamp.is_newer_than(last_seen) => {
+                UpdateKind::from_missed(change_stamp.distance_from(last_seen) - 1)
+            }
+            Some(..) => return,
+            None if change_stamp != ChangeStamp::initial() || self.replay => UpdateKind::Sequential,
+            None => return,
+        };
+
+        self.last_seen_change_stamp = Some(change_stamp);
+
+        // SAFETY:
+        // `data` is a boxed slice that is live and initialized for as long as `scoped_data` is live because it is
+        // declared before it
+        let scoped_data = unsafe { ScopedData::new(data.as_ptr().cast(), data.len(), change_stamp) };
+
+        listener.call(scoped_data.accessor_with_update_kind(update_kind));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use static_assertions::assert_impl_all;
+
+    use super::*;
+    use crate::state_name::StateName;
+    use crate::subscribe::DataAccessor;
+    use crate::type_id::TypeId;
+
+    fn poller(last_seen_change_stamp: Option<ChangeStamp>, replay: bool) -> Poller {
+        Poller {
+            state: RawState::from_state_name_and_type_id(
+                StateName::from_opaque_value(0x0D83_063E_A3BE_5075),
+                TypeId::none(),
+            ),
+            last_seen_change_stamp,
+            replay,
+        }
+    }
+
+    fn dispatch(poller: &mut Poller, change_stamp: u32) -> Option<UpdateKind> {
+        let mut update_kind = None;
+        let data = StampedData::from_data_change_stamp(Box::<[u8]>::from([0; 4]), change_stamp);
+
+        poller.dispatch(data, &mut |accessor: DataAccessor<'_, u32>| {
+            update_kind = Some(accessor.update_kind());
+        });
+
+        update_kind
+    }
+
+    #[test]
+    fn polling_subscription_is_send_and_sync_if_listener_is_send() {
+        type Listener = Box<dyn FnMut(DataAccessor<'_, u32>) + Send>;
+
+        assert_impl_all!(PollingSubscription<'_, Listener>: Send, Sync);
+    }
+
+    #[test]
+    fn poller_deduplicates_by_change_stamp() {
+        let mut poller = poller(Some(ChangeStamp::new(1)), false);
+
+        assert_eq!(dispatch(&mut poller, 1), None);
+        assert_eq!(dispatch(&mut poller, 2), Some(UpdateKind::Sequential));
+        assert_eq!(dispatch(&mut poller, 2), None);
+        assert_eq!(dispatch(&mut poller, 5), Some(UpdateKind::Coalesced { missed: 2 }));
+    }
+
+    #[test]
+    fn poller_without_last_seen_change_stamp_skips_initial_data_unless_replaying() {
+        assert_eq!(dispatch(&mut poller(None, false), 0), None);
+        assert_eq!(dispatch(&mut poller(None, false), 3), Some(UpdateKind::Sequential));
+        assert_eq!(dispatch(&mut poller(None, true), 0), Some(UpdateKind::Sequential));
+    }
+}
@@ -0,0 +1,44 @@
+//! Re-exports of the most commonly used types and traits
+//!
+//! Code using states usually needs a number of types for creating and accessing states as well as traits that have to
+//! be in scope for their methods to be available, such as [`AsState`]. Importing this module with a glob import brings
+//! all of these into scope at once:
+//! ```
+//! # fn main() -> std::io::Result<()> {
+//! use std::io;
+//!
+//! use wnf::prelude::*;
+//!
+//! fn increment<S>(state: S) -> io::Result<u32>
+//! where
+//!     S: AsState<Data = u32>,
+//! {
+//!     state.as_state().apply(|value| value + 1)
+//! }
+//!
+//! let state: OwnedState<u32> = StateCreation::new()
+//!     .lifetime(CreatableStateLifetime::Temporary)
+//!     .scope(DataScope::Machine)
+//!     .create_owned()?;
+//!
+//! state.set(&41)?;
+//!
+//! assert_eq!(increment(&state)?, 42);
+//! # Ok(()) }
+//! ```
+//!
+//! Types and traits that are only available with certain features are only re-exported if the respective feature is
+//! enabled, e.g. [`SeenChangeStamp`] and [`StateListener`] require the `subscribe` feature.
+
+pub use crate::bytes::{AnyBitPattern, CheckedBitPattern, NoUninit};
+pub use crate::data::{ChangeStamp, OpaqueData, StampedData};
+#[cfg(windows)]
+pub use crate::manage::{CreatableStateLifetime, StateCreation};
+pub use crate::read::Read;
+#[cfg(windows)]
+pub use crate::state::{AsState, BorrowedState, OwnedState};
+pub use crate::state_name::{DataScope, StateName};
+#[cfg(all(windows, feature = "subscribe"))]
+pub use crate::subscribe::{DataAccessor, SeenChangeStamp, StateListener, Subscription};
+#[cfg(windows)]
+pub use crate::type_id::GUID;
@@ -0,0 +1,410 @@
+//! Cross-process synchronization primitives built on states
+//!
+//! This module provides the [`WnfFlag<'a>`](WnfFlag), [`WnfCounter<'a>`](WnfCounter) and
+//! [`WnfEventPulse<'a>`](WnfEventPulse) types, which are views of states implementing simple building blocks for
+//! coordinating processes.
+
+use std::io::{self, ErrorKind};
+use std::mem;
+#[cfg(feature = "wait_blocking")]
+use std::time::Duration;
+
+use crate::data::{ChangeStamp, OpaqueData};
+use crate::read::ReadError;
+use crate::state::{AsState, BorrowedState};
+#[cfg(feature = "wait_async")]
+use crate::wait_async::Wait;
+
+/// A view of a state as a flag that can be set and cleared
+///
+/// The data of the underlying state consist of a single `bool`. A newly created state, whose data have size zero, is
+/// treated as a cleared flag, so the state does not need to be initialized before use.
+///
+/// With the `wait_blocking` resp. `wait_async` features, it is possible to wait until the flag is set, e.g. in order to
+/// signal to other processes that some initialization has completed.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use wnf::{OwnedState, WnfFlag};
+///
+/// let state = OwnedState::<[bool]>::create_temporary()?;
+/// let flag = WnfFlag::new(&state);
+///
+/// assert!(!flag.is_set()?);
+///
+/// flag.set()?;
+/// assert!(flag.is_set()?);
+///
+/// flag.clear()?;
+/// assert!(!flag.is_set()?);
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct WnfFlag<'a> {
+    state: BorrowedState<'a, [bool]>,
+}
+
+impl<'a> WnfFlag<'a> {
+    /// Creates a new [`WnfFlag<'a>`](WnfFlag) viewing the given state as a flag
+    ///
+    /// The underlying state can have any data type, its data are treated as a `bool`.
+    pub fn new<S>(state: &'a S) -> Self
+    where
+        S: AsState,
+    {
+        Self {
+            state: state.as_state().cast(),
+        }
+    }
+
+    /// Returns the underlying state of this flag
+    pub const fn state(&self) -> BorrowedState<'a, [bool]> {
+        self.state
+    }
+
+    /// Queries whether this flag is set
+    ///
+    /// # Errors
+    /// Returns an error if querying fails or if the data of the underlying state are not a valid flag
+    pub fn is_set(&self) -> io::Result<bool> {
+        single_or_default(&self.state.get_boxed()?)
+    }
+
+    /// Sets this flag
+    ///
+    /// # Errors
+    /// Returns an error if updating fails
+    pub fn set(&self) -> io::Result<()> {
+        self.state.set(&[true])
+    }
+
+    /// Clears this flag
+    ///
+    /// # Errors
+    /// Returns an error if updating fails
+    pub fn clear(&self) -> io::Result<()> {
+        self.state.set(&[false])
+    }
+
+    /// Waits until this flag is set
+    ///
+    /// This returns immediately if the flag is already set.
+    ///
+    /// This is a blocking method. If you are in an async context, use [`wait_set_async`](WnfFlag::wait_set_async).
+    ///
+    /// # Errors
+    /// Returns an error if querying, subscribing to or unsubscribing from the underlying state fails, if its data are
+    /// not a valid flag or if the timeout has elapsed. In the latter case, [`io::Error::kind`] returns
+    /// [`ErrorKind::TimedOut`].
+    #[cfg(feature = "wait_blocking")]
+    pub fn wait_set_blocking(&self, timeout: Duration) -> io::Result<()> {
+        let data = self.state.wait_until_boxed_blocking(is_set, timeout)?;
+        single_or_default(&data).map(|_| ())
+    }
+
+    /// Waits until this flag is set
+    ///
+    /// This returns immediately if the flag is already set.
+    ///
+    /// This is an async method. If you are in a sync context, use [`wait_set_blocking`](WnfFlag::wait_set_blocking).
+    ///
+    /// This method does not make any assumptions on what async executor you use. In order to implement a timeout,
+    /// wrap it in the appropriate helper function provided by your executor.
+    ///
+    /// # Errors
+    /// Returns an error if querying, subscribing to or unsubscribing from the underlying state fails or if its data
+    /// are not a valid flag
+    #[cfg(feature = "wait_async")]
+    pub async fn wait_set_async(&self) -> io::Result<()> {
+        let data = self.state.wait_until_boxed_async(is_set).await?;
+        single_or_default(&data).map(|_| ())
+    }
+}
+
+/// A view of a state as a counter that can be incremented
+///
+/// The data of the underlying state consist of a single `u64`. A newly created state, whose data have size zero, is
+/// treated as a counter with value zero, so the state does not need to be initialized before use.
+///
+/// Incrementing the counter is done through the same loop as [`apply`](crate::OwnedState::apply), so concurrent
+/// increments from different processes are not lost, unless the internal capacity of the underlying state is exceeded
+/// as explained there. Increments wrap around on overflow.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use wnf::{OwnedState, WnfCounter};
+///
+/// let state = OwnedState::<[u64]>::create_temporary()?;
+/// let counter = WnfCounter::new(&state);
+///
+/// assert_eq!(counter.get()?, 0);
+/// assert_eq!(counter.increment()?, 1);
+/// assert_eq!(counter.add(41)?, 42);
+///
+/// counter.reset()?;
+/// assert_eq!(counter.get()?, 0);
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct WnfCounter<'a> {
+    state: BorrowedState<'a, [u64]>,
+}
+
+impl<'a> WnfCounter<'a> {
+    /// Creates a new [`WnfCounter<'a>`](WnfCounter) viewing the given state as a counter
+    ///
+    /// The underlying state can have any data type, its data are treated as a `u64`.
+    pub fn new<S>(state: &'a S) -> Self
+    where
+        S: AsState,
+    {
+        Self {
+            state: state.as_state().cast(),
+        }
+    }
+
+    /// Returns the underlying state of this counter
+    pub const fn state(&self) -> BorrowedState<'a, [u64]> {
+        self.state
+    }
+
+    /// Queries the value of this counter
+    ///
+    /// # Errors
+    /// Returns an error if querying fails or if the data of the underlying state are not a valid counter
+    pub fn get(&self) -> io::Result<u64> {
+        single_or_default(&self.state.get_boxed()?)
+    }
+
+    /// Increments this counter by one, returning the new value
+    ///
+    /// # Errors
+    /// Returns an error if querying or updating fails or if the data of the underlying state are not a valid counter
+    pub fn increment(&self) -> io::Result<u64> {
+        self.add(1)
+    }
+
+    /// Increments this counter by the given amount, returning the new value
+    ///
+    /// # Errors
+    /// Returns an error if querying or updating fails or if the data of the underlying state are not a valid counter
+    pub fn add(&self, amount: u64) -> io::Result<u64> {
+        let [value] = self
+            .state
+            .raw
+            .apply_as_with_io_error(|data: Box<[u64]>| Ok([single_or_default(&data)?.wrapping_add(amount)]))?;
+
+        Ok(value)
+    }
+
+    /// Resets this counter to zero
+    ///
+    /// # Errors
+    /// Returns an error if updating fails
+    pub fn reset(&self) -> io::Result<()> {
+        self.state.set(&[0])
+    }
+
+    /// Waits until the value of this counter is at least the given value, returning the value
+    ///
+    /// This returns immediately if the value is already at least the given value.
+    ///
+    /// This is a blocking method. If you are in an async context, use
+    /// [`wait_until_at_least_async`](WnfCounter::wait_until_at_least_async).
+    ///
+    /// # Errors
+    /// Returns an error if querying, subscribing to or unsubscribing from the underlying state fails, if its data are
+    /// not a valid counter or if the timeout has elapsed. In the latter case, [`io::Error::kind`] returns
+    /// [`ErrorKind::TimedOut`].
+    #[cfg(feature = "wait_blocking")]
+    pub fn wait_until_at_least_blocking(&self, min_value: u64, timeout: Duration) -> io::Result<u64> {
+        let data = self
+            .state
+            .wait_until_boxed_blocking(|data| is_at_least(data, min_value), timeout)?;
+
+        single_or_default(&data)
+    }
+
+    /// Waits until the value of this counter is at least the given value, returning the value
+    ///
+    /// This returns immediately if the value is already at least the given value.
+    ///
+    /// This is an async method. If you are in a sync context, use
+    /// [`wait_until_at_least_blocking`](WnfCounter::wait_until_at_least_blocking).
+    ///
+    /// This method does not make any assumptions on what async executor you use. In order to implement a timeout,
+    /// wrap it in the appropriate helper function provided by your executor.
+    ///
+    /// # Errors
+    /// Returns an error if querying, subscribing to or unsubscribing from the underlying state fails or if its data
+    /// are not a valid counter
+    #[cfg(feature = "wait_async")]
+    pub async fn wait_until_at_least_async(&self, min_value: u64) -> io::Result<u64> {
+        let data = self
+            .state
+            .wait_until_boxed_async(|data: &[u64]| is_at_least(data, min_value))
+            .await?;
+
+        single_or_default(&data)
+    }
+}
+
+/// A view of a state as an event that can be pulsed
+///
+/// Pulsing the event updates the underlying state with data of size zero. This carries no information other than the
+/// update itself, which increments the change stamp of the state and notifies all of its subscribers. The change stamp
+/// can hence be used to detect how many times the event has been pulsed.
+///
+/// With the `wait_blocking` resp. `wait_async` features, it is possible to wait until the event is pulsed the next
+/// time.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use wnf::{OwnedState, WnfEventPulse};
+///
+/// let state = OwnedState::<()>::create_temporary()?;
+/// let event = WnfEventPulse::new(&state);
+///
+/// let change_stamp = event.change_stamp()?;
+/// event.pulse()?;
+///
+/// assert_eq!(event.change_stamp()?.distance_from(change_stamp), 1);
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct WnfEventPulse<'a> {
+    state: BorrowedState<'a, OpaqueData>,
+}
+
+impl<'a> WnfEventPulse<'a> {
+    /// Creates a new [`WnfEventPulse<'a>`](WnfEventPulse) viewing the given state as an event
+    ///
+    /// The underlying state can have any data type, its data are overwritten with data of size zero when the event is
+    /// pulsed.
+    pub fn new<S>(state: &'a S) -> Self
+    where
+        S: AsState,
+    {
+        Self {
+            state: state.as_state().cast(),
+        }
+    }
+
+    /// Returns the underlying state of this event
+    pub const fn state(&self) -> BorrowedState<'a, OpaqueData> {
+        self.state
+    }
+
+    /// Pulses this event, notifying all subscribers of the underlying state
+    ///
+    /// # Errors
+    /// Returns an error if updating fails
+    pub fn pulse(&self) -> io::Result<()> {
+        self.state.cast::<()>().set(&())
+    }
+
+    /// Queries the change stamp of the underlying state, which is incremented every time this event is pulsed
+    ///
+    /// # Errors
+    /// Returns an error if querying fails
+    pub fn change_stamp(&self) -> io::Result<ChangeStamp> {
+        self.state.change_stamp()
+    }
+
+    /// Waits until this event is pulsed
+    ///
+    /// This waits for the next pulse, i.e. pulses that have happened before calling this method are not taken into
+    /// account. Note that this also returns if the underlying state is updated in any other way.
+    ///
+    /// This is a blocking method. If you are in an async context, use [`wait_async`](WnfEventPulse::wait_async).
+    ///
+    /// # Errors
+    /// Returns an error if querying, subscribing to or unsubscribing from the underlying state fails or if the timeout
+    /// has elapsed. In the latter case, [`io::Error::kind`] returns [`ErrorKind::TimedOut`].
+    #[cfg(feature = "wait_blocking")]
+    pub fn wait_blocking(&self, timeout: Duration) -> io::Result<()> {
+        self.state.wait_blocking(timeout)
+    }
+
+    /// Waits until this event is pulsed
+    ///
+    /// This waits for the next pulse, i.e. pulses that have happened before calling this method are not taken into
+    /// account. Note that this also returns if the underlying state is updated in any other way.
+    ///
+    /// This is an async method. If you are in a sync context, use [`wait_blocking`](WnfEventPulse::wait_blocking).
+    ///
+    /// This method does not make any assumptions on what async executor you use. In order to implement a timeout,
+    /// wrap it in the appropriate helper function provided by your executor.
+    ///
+    /// # Errors
+    /// Returns an error if querying, subscribing to or unsubscribing from the underlying state fails
+    #[cfg(feature = "wait_async")]
+    pub fn wait_async(&self) -> Wait<'a> {
+        self.state.wait_async()
+    }
+}
+
+/// Returns whether the given flag data represent a set flag
+#[cfg(any(feature = "wait_async", feature = "wait_blocking"))]
+fn is_set(data: &[bool]) -> bool {
+    data == [true]
+}
+
+/// Returns whether the given counter data represent a value of at least the given value
+#[cfg(any(feature = "wait_async", feature = "wait_blocking"))]
+fn is_at_least(data: &[u64], min_value: u64) -> bool {
+    single_or_default(data).is_ok_and(|value| value >= min_value)
+}
+
+/// Returns the single element of the given slice, or the default value if the slice is empty
+///
+/// This is used for reading the data of states that may have not been initialized yet, i.e. have data of size zero.
+fn single_or_default<T>(data: &[T]) -> io::Result<T>
+where
+    T: Copy + Default,
+{
+    match data {
+        [] => Ok(T::default()),
+        [value] => Ok(*value),
+        _ => Err(io::Error::new(
+            ErrorKind::InvalidData,
+            ReadError::WrongSize {
+                expected: mem::size_of::<T>(),
+                actual: mem::size_of_val(data),
+            },
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_or_default_empty() {
+        assert_eq!(single_or_default::<u64>(&[]).unwrap(), 0);
+        assert!(!single_or_default::<bool>(&[]).unwrap());
+    }
+
+    #[test]
+    fn single_or_default_single() {
+        assert_eq!(single_or_default(&[42_u64]).unwrap(), 42);
+        assert!(single_or_default(&[true]).unwrap());
+    }
+
+    #[test]
+    fn single_or_default_wrong_size() {
+        let result = single_or_default(&[1_u64, 2]);
+
+        assert_eq!(
+            result.unwrap_err().into_inner().unwrap().downcast_ref(),
+            Some(&ReadError::WrongSize {
+                expected: 8,
+                actual: 16
+            })
+        );
+    }
+}
@@ -1,5 +1,6 @@
 //! Utility functions dealing with privileges
 
+use std::fmt::{self, Display, Formatter};
 use std::io;
 
 use windows::Win32::Foundation::{BOOL, HANDLE, LUID};
@@ -10,6 +11,31 @@ use windows::Win32::Security::{
 use windows::Win32::System::SystemServices::PRIVILEGE_SET_ALL_NECESSARY;
 use windows::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
 
+/// A privilege that is necessary for certain operations on states
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[non_exhaustive]
+pub enum Privilege {
+    /// The `SeCreatePermanentPrivilege` privilege
+    ///
+    /// See [`can_create_permanent_shared_objects`] for the operations requiring this privilege.
+    CreatePermanent,
+}
+
+impl Privilege {
+    /// Returns the name of this privilege, such as `SeCreatePermanentPrivilege`
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::CreatePermanent => "SeCreatePermanentPrivilege",
+        }
+    }
+}
+
+impl Display for Privilege {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
 /// Returns whether the current process has the `SeCreatePermanentPrivilege` privilege
 ///
 /// This privilege is necessary for creating states with the
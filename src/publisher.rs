@@ -0,0 +1,274 @@
+//! Enforcing a single publisher for a state through an auxiliary lock state
+
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, ErrorKind};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::{mem, process};
+
+use thiserror::Error;
+
+use crate::data::ChangeStamp;
+use crate::state::{AsState, BorrowedState, RawState};
+
+/// The identity of a publisher holding publication rights through a [`PublisherGuard`]
+///
+/// A publisher id consists of the id of the process the publisher lives in and a sequence number that is unique
+/// within that process.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct PublisherId {
+    process_id: u32,
+    sequence_number: u32,
+}
+
+impl PublisherId {
+    /// Creates a new [`PublisherId`] that is unique within the current process
+    pub fn new() -> Self {
+        static NEXT_SEQUENCE_NUMBER: AtomicU32 = AtomicU32::new(1);
+
+        Self {
+            process_id: process::id(),
+            sequence_number: NEXT_SEQUENCE_NUMBER.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the id of the process this publisher lives in
+    pub const fn process_id(self) -> u32 {
+        self.process_id
+    }
+
+    /// Returns the sequence number of this publisher within its process
+    pub const fn sequence_number(self) -> u32 {
+        self.sequence_number
+    }
+
+    /// Encodes this [`PublisherId`] as the data of a lock state
+    pub(crate) fn to_bytes(self) -> [u8; 8] {
+        ((u64::from(self.process_id) << 32) | u64::from(self.sequence_number)).to_le_bytes()
+    }
+
+    /// Decodes a [`PublisherId`] from the data of a lock state, returning [`None`] if no publisher holds the rights
+    pub(crate) fn from_bytes(bytes: &[u8]) -> io::Result<Option<Self>> {
+        if bytes.iter().all(|&byte| byte == 0) {
+            return Ok(None);
+        }
+
+        let value = u64::from_le_bytes(bytes.try_into().map_err(|_| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                "data of lock state do not contain a publisher id",
+            )
+        })?);
+
+        Ok(Some(Self {
+            process_id: (value >> 32) as u32,
+            sequence_number: value as u32,
+        }))
+    }
+}
+
+impl Default for PublisherId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Display for PublisherId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.process_id, self.sequence_number)
+    }
+}
+
+/// An error acquiring publication rights through a [`PublisherGuard`]
+///
+/// When acquiring fails because of this, the returned [`io::Error`] has kind [`ErrorKind::WouldBlock`] and wraps a
+/// [`PublisherError`], which can be obtained via [`io::Error::get_ref`].
+#[derive(Clone, Copy, Debug, Eq, Error, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum PublisherError {
+    /// Publication rights are currently held by another publisher
+    #[error("publication rights are held by publisher {holder}")]
+    AlreadyHeld {
+        /// The publisher currently holding publication rights
+        holder: PublisherId,
+    },
+}
+
+/// A guard holding exclusive publication rights for a state
+///
+/// WNF itself does not restrict how many processes or components update a state. In order to detect multiple
+/// components accidentally publishing to the same state, publishers can agree on an auxiliary *lock state* whose data
+/// contain the [`PublisherId`] of the publisher currently holding publication rights. A [`PublisherGuard`] represents
+/// these rights: Acquiring it fails if another publisher holds the rights, and dropping it releases them.
+///
+/// The lock state can be any state with data of any type. Its data are treated as raw bytes, where data of size zero
+/// or data consisting only of zeros mean that no publisher holds the rights. Data that are neither of these nor a valid
+/// [`PublisherId`] make acquiring fail with an error of kind [`ErrorKind::InvalidData`]. Acquiring and releasing use
+/// [`update`](crate::state::OwnedState::update) with the change stamp of the lock state, so concurrent attempts to
+/// acquire the rights cannot both succeed.
+///
+/// Note that this is a cooperative mechanism: It does not prevent anyone from updating the actual state, it only
+/// detects publishers that also use a [`PublisherGuard`] with the same lock state. Also, if a publisher terminates
+/// without releasing the rights, they stay held until the lock state is cleared or deleted. Using a temporary lock
+/// state created by the publisher avoids this, because it is deleted when the publisher's process exits.
+#[derive(Debug)]
+pub struct PublisherGuard<'a> {
+    lock_state: BorrowedState<'a, [u8]>,
+    id: PublisherId,
+}
+
+impl<'a> PublisherGuard<'a> {
+    /// Acquires publication rights through the given lock state using a new [`PublisherId`]
+    ///
+    /// # Errors
+    /// Returns an error if another publisher holds publication rights, in which case the error wraps a
+    /// [`PublisherError::AlreadyHeld`] containing the [`PublisherId`] of that publisher, or if querying or updating
+    /// the lock state fails
+    pub fn acquire<S>(lock_state: &'a S) -> io::Result<Self>
+    where
+        S: AsState,
+    {
+        Self::acquire_as(lock_state, PublisherId::new())
+    }
+
+    /// Acquires publication rights through the given lock state using the given [`PublisherId`]
+    ///
+    /// If the rights are already held by a publisher with the given id, this succeeds, which makes it possible to
+    /// reacquire the rights after a [`PublisherGuard`] was forgotten.
+    ///
+    /// # Errors
+    /// See [`PublisherGuard::acquire`]
+    pub fn acquire_as<S>(lock_state: &'a S, id: PublisherId) -> io::Result<Self>
+    where
+        S: AsState,
+    {
+        let lock_state = lock_state.as_state().cast();
+
+        loop {
+            let (data, change_stamp) = query_lock(lock_state.raw)?;
+
+            match PublisherId::from_bytes(&data)? {
+                Some(holder) if holder == id => break,
+                Some(holder) => {
+                    return Err(io::Error::new(
+                        ErrorKind::WouldBlock,
+                        PublisherError::AlreadyHeld { holder },
+                    ))
+                }
+                None => {
+                    if lock_state.raw.update(&id.to_bytes(), change_stamp)? {
+                        break;
+                    }
+                }
+            }
+        }
+
+        Ok(Self { lock_state, id })
+    }
+
+    /// Returns the [`PublisherId`] of the publisher currently holding publication rights through the given lock state,
+    /// or [`None`] if no publisher holds them
+    ///
+    /// # Errors
+    /// Returns an error if querying the lock state fails
+    pub fn holder<S>(lock_state: &S) -> io::Result<Option<PublisherId>>
+    where
+        S: AsState,
+    {
+        let (data, _) = query_lock(lock_state.as_state().raw.cast())?;
+        PublisherId::from_bytes(&data)
+    }
+
+    /// Returns the [`PublisherId`] of this guard
+    pub const fn id(&self) -> PublisherId {
+        self.id
+    }
+
+    /// Releases publication rights
+    ///
+    /// This is called automatically when the guard is dropped, but calling it explicitly makes it possible to handle
+    /// errors. If the lock state has been changed to another holder in the meantime, it is left unchanged.
+    ///
+    /// # Errors
+    /// Returns an error if querying or updating the lock state fails
+    pub fn release(self) -> io::Result<()> {
+        let result = self.release_internal();
+        mem::forget(self);
+        result
+    }
+
+    /// Releases publication rights without consuming the guard
+    fn release_internal(&self) -> io::Result<()> {
+        loop {
+            let (data, change_stamp) = query_lock(self.lock_state.raw)?;
+
+            if PublisherId::from_bytes(&data)? != Some(self.id) {
+                return Ok(());
+            }
+
+            if self.lock_state.raw.update(&[], change_stamp)? {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl Drop for PublisherGuard<'_> {
+    fn drop(&mut self) {
+        let _ = self.release_internal();
+    }
+}
+
+/// Queries the raw data and change stamp of the given lock state
+pub(crate) fn query_lock(lock_state: RawState<[u8]>) -> io::Result<(Box<[u8]>, ChangeStamp)> {
+    Ok(lock_state.query_as::<Box<[u8]>>()?.into_data_change_stamp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn publisher_id_is_unique() {
+        let id1 = PublisherId::new();
+        let id2 = PublisherId::new();
+
+        assert_eq!(id1.process_id(), process::id());
+        assert_ne!(id1, id2);
+    }
+
+    #[test]
+    fn publisher_id_bytes_round_trip() {
+        let id = PublisherId {
+            process_id: 0x1234_5678,
+            sequence_number: 42,
+        };
+
+        assert_eq!(PublisherId::from_bytes(&id.to_bytes()).unwrap(), Some(id));
+    }
+
+    #[test]
+    fn publisher_id_from_bytes_not_held() {
+        assert_eq!(PublisherId::from_bytes(&[]).unwrap(), None);
+        assert_eq!(PublisherId::from_bytes(&[0; 8]).unwrap(), None);
+    }
+
+    #[test]
+    fn publisher_id_from_bytes_invalid() {
+        let err = PublisherId::from_bytes(&[0xFF; 4]).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn publisher_error_display() {
+        let holder = PublisherId {
+            process_id: 1234,
+            sequence_number: 1,
+        };
+
+        assert_eq!(
+            PublisherError::AlreadyHeld { holder }.to_string(),
+            "publication rights are held by publisher 1234:1"
+        );
+    }
+}
@@ -2,15 +2,19 @@
 //!
 //! This module only adds inherent impls to [`OwnedState<T>`] and [`BorrowedState<'_, T>`](BorrowedState).
 
-use std::{io, ptr};
+use std::ffi::c_void;
+use std::io::ErrorKind;
+use std::{io, mem, ptr};
 
-use tracing::debug;
 use windows::Win32::Foundation::STATUS_BUFFER_TOO_SMALL;
 
+use crate::bytes::CheckedBitPattern;
 use crate::data::{ChangeStamp, OpaqueData, StampedData};
 use crate::ntapi;
-use crate::read::Read;
+use crate::read::{self, QueryOptions, Read, ReadError, ReadInto};
 use crate::state::{BorrowedState, OwnedState, RawState};
+use crate::trace::{ntapi_event, WnfRoutine};
+use crate::type_id::{TypeId, GUID};
 
 impl<T> OwnedState<T>
 where
@@ -43,6 +47,19 @@ where
     pub fn query(&self) -> io::Result<StampedData<T>> {
         self.raw.query()
     }
+
+    /// Queries the data of this state together with its change stamp, using the given type id instead of the type id
+    /// of this state
+    ///
+    /// This is the same as [`query`](OwnedState::query), except that the given type id is passed to the WNF API for
+    /// this call only. This is useful for states whose writers use a type id that is only known at runtime.
+    ///
+    /// # Errors
+    /// Returns an error if querying fails, including the case that the given type id does not match the type id of
+    /// the state and the case that the queried data is not a valid `T`
+    pub fn query_with_type_id(&self, type_id: impl Into<GUID>) -> io::Result<StampedData<T>> {
+        self.raw.with_type_id(TypeId::from_guid(type_id.into())).query()
+    }
 }
 
 impl<T> OwnedState<T>
@@ -76,6 +93,78 @@ where
     pub fn query_boxed(&self) -> io::Result<StampedData<Box<T>>> {
         self.raw.query_boxed()
     }
+
+    /// Queries the data of this state as a box together with its change stamp, using the given options for
+    /// allocating the buffer
+    ///
+    /// This is the same as [`query_boxed`](OwnedState::query_boxed), except that for a slice type `T`, the given
+    /// [`QueryOptions`] control the initial capacity and growth of the buffer used for querying the data. For other
+    /// types, the options are ignored.
+    ///
+    /// # Errors
+    /// Returns an error if querying fails, including the case that the queried data is not a valid `T`
+    pub fn query_boxed_with_options(&self, options: QueryOptions) -> io::Result<StampedData<Box<T>>> {
+        self.raw.query_as_with_options(options)
+    }
+
+    /// Queries the data of this state as a box together with its change stamp, using the given type id instead of the
+    /// type id of this state
+    ///
+    /// This is the same as [`query_with_type_id`](OwnedState::query_with_type_id), except that it produces a
+    /// [`Box<T>`] instead of an owned `T` on the stack (requiring `T: Sized`).
+    ///
+    /// # Errors
+    /// Returns an error if querying fails, including the case that the given type id does not match the type id of
+    /// the state and the case that the queried data is not a valid `T`
+    pub fn query_boxed_with_type_id(&self, type_id: impl Into<GUID>) -> io::Result<StampedData<Box<T>>> {
+        self.raw.with_type_id(TypeId::from_guid(type_id.into())).query_boxed()
+    }
+}
+
+impl<T> OwnedState<[T]>
+where
+    [T]: Read<Box<[T]>>,
+{
+    /// Queries the data of this state as a boxed slice together with its change stamp, using the given expected number
+    /// of elements for allocating the buffer
+    ///
+    /// This is the same as [`query_boxed`](OwnedState::query_boxed), except that the buffer used for querying the data
+    /// is allocated upfront with room for `len` elements. If the state data fit into this buffer, they are queried with
+    /// a single call to the WNF API, whereas [`query_boxed`](OwnedState::query_boxed) needs an additional call for
+    /// determining the size of the data. If the state data turn out to be larger, the buffer is grown as usual, so the
+    /// hint only affects performance but never the result.
+    ///
+    /// This is a shorthand for [`query_boxed_with_options`](OwnedState::query_boxed_with_options) with an initial
+    /// capacity of `len` elements.
+    ///
+    /// # Errors
+    /// Returns an error if querying fails, including the case that the queried data is not a valid `[T]`
+    pub fn query_slice_with_len_hint(&self, len: usize) -> io::Result<StampedData<Box<[T]>>> {
+        self.raw.query_slice_with_len_hint(len)
+    }
+}
+
+impl<T> OwnedState<[T]>
+where
+    T: CheckedBitPattern,
+{
+    /// Queries the data of this state into the given container, returning the change stamp
+    ///
+    /// This is the same as [`query_boxed`](OwnedState::query_boxed), except that the data are written directly into
+    /// the spare capacity of the given container instead of a newly allocated box (see [`ReadInto`]), replacing the
+    /// previous elements of the container. When reusing the same container for multiple queries, this avoids
+    /// allocating a new buffer for every query, and if the capacity of the container suffices, the data are queried
+    /// with a single call to the WNF API.
+    ///
+    /// # Errors
+    /// Returns an error if querying fails, including the case that the queried data is not a valid `[T]`. In this
+    /// case, the container is left empty.
+    pub fn query_into<C>(&self, container: &mut C) -> io::Result<ChangeStamp>
+    where
+        C: ReadInto<T> + ?Sized,
+    {
+        self.raw.query_into(container)
+    }
 }
 
 impl<T> OwnedState<T>
@@ -108,6 +197,14 @@ where
     pub fn query(self) -> io::Result<StampedData<T>> {
         self.raw.query()
     }
+
+    /// Queries the data of this state together with its change stamp, using the given type id instead of the type id
+    /// of this state
+    ///
+    /// See [`OwnedState::query_with_type_id`]
+    pub fn query_with_type_id(self, type_id: impl Into<GUID>) -> io::Result<StampedData<T>> {
+        self.raw.with_type_id(TypeId::from_guid(type_id.into())).query()
+    }
 }
 
 impl<T> BorrowedState<'_, T>
@@ -127,6 +224,50 @@ where
     pub fn query_boxed(self) -> io::Result<StampedData<Box<T>>> {
         self.raw.query_boxed()
     }
+
+    /// Queries the data of this state as a box together with its change stamp, using the given options for
+    /// allocating the buffer
+    ///
+    /// See [`OwnedState::query_boxed_with_options`]
+    pub fn query_boxed_with_options(self, options: QueryOptions) -> io::Result<StampedData<Box<T>>> {
+        self.raw.query_as_with_options(options)
+    }
+
+    /// Queries the data of this state as a box together with its change stamp, using the given type id instead of the
+    /// type id of this state
+    ///
+    /// See [`OwnedState::query_boxed_with_type_id`]
+    pub fn query_boxed_with_type_id(self, type_id: impl Into<GUID>) -> io::Result<StampedData<Box<T>>> {
+        self.raw.with_type_id(TypeId::from_guid(type_id.into())).query_boxed()
+    }
+}
+
+impl<T> BorrowedState<'_, [T]>
+where
+    [T]: Read<Box<[T]>>,
+{
+    /// Queries the data of this state as a boxed slice together with its change stamp, using the given expected number
+    /// of elements for allocating the buffer
+    ///
+    /// See [`OwnedState::query_slice_with_len_hint`]
+    pub fn query_slice_with_len_hint(self, len: usize) -> io::Result<StampedData<Box<[T]>>> {
+        self.raw.query_slice_with_len_hint(len)
+    }
+}
+
+impl<T> BorrowedState<'_, [T]>
+where
+    T: CheckedBitPattern,
+{
+    /// Queries the data of this state into the given container, returning the change stamp
+    ///
+    /// See [`OwnedState::query_into`]
+    pub fn query_into<C>(self, container: &mut C) -> io::Result<ChangeStamp>
+    where
+        C: ReadInto<T> + ?Sized,
+    {
+        self.raw.query_into(container)
+    }
 }
 
 impl<T> BorrowedState<'_, T>
@@ -171,6 +312,36 @@ where
     }
 }
 
+impl<T> RawState<[T]>
+where
+    [T]: Read<Box<[T]>>,
+{
+    /// Queries the data of this state as a boxed slice together with its change stamp, allocating the buffer with room
+    /// for `len` elements
+    fn query_slice_with_len_hint(self, len: usize) -> io::Result<StampedData<Box<[T]>>> {
+        self.query_as_with_options(QueryOptions::new().initial_capacity(len.saturating_mul(mem::size_of::<T>())))
+    }
+}
+
+impl<T> RawState<[T]>
+where
+    T: CheckedBitPattern,
+{
+    /// Queries the data of this state into the given container, returning the change stamp
+    fn query_into<C>(self, container: &mut C) -> io::Result<ChangeStamp>
+    where
+        C: ReadInto<T> + ?Sized,
+    {
+        // SAFETY:
+        // The explicit scope is a null pointer
+        let reader = unsafe { self.reader(ptr::null()) };
+
+        // SAFETY:
+        // By the guarantees of `reader`, the safety condition of `read::read_into` is satisfied
+        unsafe { read::read_into(container, reader, QueryOptions::default()) }
+    }
+}
+
 impl<T> RawState<T>
 where
     T: ?Sized,
@@ -188,7 +359,93 @@ where
     where
         T: Read<D>,
     {
-        let reader = |ptr, size| {
+        self.query_as_with_options(QueryOptions::default())
+    }
+
+    /// Queries the data of this state as a value of type `D`, using the given options for allocating the buffer
+    ///
+    /// If `T: Sized`, then `D` can be either `T` or `Box<T>`.
+    /// If `T: !Sized`, then `D` must be `Box<T>`.
+    pub(crate) fn query_as_with_options<D>(self, options: QueryOptions) -> io::Result<StampedData<D>>
+    where
+        T: Read<D>,
+    {
+        // SAFETY:
+        // The explicit scope is a null pointer
+        unsafe { self.query_as_with_explicit_scope_and_options(ptr::null(), options) }
+    }
+
+    /// Queries the data of this state as a value of type `D` using the given explicit scope
+    ///
+    /// If `T: Sized`, then `D` can be either `T` or `Box<T>`.
+    /// If `T: !Sized`, then `D` must be `Box<T>`.
+    ///
+    /// # Safety
+    /// `explicit_scope` must either be a null pointer or satisfy the (undocumented) requirements of
+    /// `NtQueryWnfStateData` for its `explicit_scope` argument
+    #[cfg(feature = "unstable_ntapi")]
+    pub(crate) unsafe fn query_as_with_explicit_scope<D>(
+        self,
+        explicit_scope: *const c_void,
+    ) -> io::Result<StampedData<D>>
+    where
+        T: Read<D>,
+    {
+        // SAFETY:
+        // The safety conditions of this method are the same as those of `query_as_with_explicit_scope_and_options`
+        unsafe { self.query_as_with_explicit_scope_and_options(explicit_scope, QueryOptions::default()) }
+    }
+
+    /// Queries the data of this state as a value of type `D` using the given explicit scope and the given options for
+    /// allocating the buffer
+    ///
+    /// If `T: Sized`, then `D` can be either `T` or `Box<T>`.
+    /// If `T: !Sized`, then `D` must be `Box<T>`.
+    ///
+    /// # Safety
+    /// `explicit_scope` must either be a null pointer or satisfy the (undocumented) requirements of
+    /// `NtQueryWnfStateData` for its `explicit_scope` argument
+    unsafe fn query_as_with_explicit_scope_and_options<D>(
+        self,
+        explicit_scope: *const c_void,
+        options: QueryOptions,
+    ) -> io::Result<StampedData<D>>
+    where
+        T: Read<D>,
+    {
+        // SAFETY:
+        // The safety condition of `reader` is the same as that of this method
+        let reader = unsafe { self.reader(explicit_scope) };
+
+        // SAFETY:
+        // By the guarantees of `reader`, the safety condition of `T::from_reader_with_options` is satisfied
+        let result = unsafe { T::from_reader_with_options(reader, options) };
+
+        Ok(result?.into())
+    }
+
+    /// Returns a reader closure querying the data of this state using the given explicit scope
+    ///
+    /// The closure satisfies the requirements of the reader closure passed to [`Read::from_reader`], i.e. when
+    /// `reader(ptr, size)` returns `Ok((read_size, _))` with `read_size <= size`, then the memory range of size
+    /// `read_size` starting at `ptr` is initialized. It returns the change stamp of the state as metadata.
+    ///
+    /// # Safety
+    /// `explicit_scope` must either be a null pointer or satisfy the (undocumented) requirements of
+    /// `NtQueryWnfStateData` for its `explicit_scope` argument
+    unsafe fn reader(
+        self,
+        explicit_scope: *const c_void,
+    ) -> impl FnMut(*mut c_void, usize) -> io::Result<(usize, ChangeStamp)> {
+        let max_read_size = self.max_read_size.or_else(read::default_max_read_size);
+
+        // When `reader(ptr, size)` returns `Ok((read_size, _))` with `read_size <= size`,
+        // - then condition a) (see below) holds,
+        // - hence the call to `NtQueryWnfStateData` succeeded,
+        // - hence by the assumption on `NtQueryWnfStateData`, the memory range of size `read_size` starting at `ptr` is
+        //   initialized,
+        // so the closure satisfies the guarantees documented above
+        move |ptr, size| {
             let mut change_stamp = ChangeStamp::default();
             let mut read_size = size as u32;
 
@@ -196,6 +453,7 @@ where
             // - The pointer in the first argument points to a valid `u64` because it comes from a live reference
             // - The pointer in the second argument is either a null pointer or points to a valid `GUID` by the
             //   guarantees of `TypeId::as_ptr`
+            // - The pointer in the third argument is either a null pointer or valid by the safety condition
             // - The pointer in the fourth argument is valid for writes of `u32` because it comes from a live mutable
             //   reference
             // - The pointer in the fifth argument is valid for writes of `read_size` by the precondition of `reader`
@@ -207,7 +465,7 @@ where
                 ntapi::NtQueryWnfStateData(
                     &self.state_name.opaque_value(),
                     self.type_id.as_ptr(),
-                    ptr::null(),
+                    explicit_scope,
                     change_stamp.as_mut_ptr(),
                     ptr,
                     &mut read_size,
@@ -215,42 +473,42 @@ where
             };
 
             if result.is_err() && (result != STATUS_BUFFER_TOO_SMALL || read_size as usize <= size) {
-                debug!(
-                     target: ntapi::TRACING_TARGET,
-                     ?result,
+                ntapi_event!(
+                     WnfRoutine::QueryStateData,
+                     true,
                      input.state_name = %self.state_name,
+                     ?result,
                      input.type_id = %self.type_id,
                      "NtQueryWnfStateData",
                 );
 
-                Err(io::Error::from_raw_os_error(result.0))
+                Err(self.ntapi_error(WnfRoutine::QueryStateData, result))
             } else {
                 // Here we know that either of the following conditions holds:
                 // a) `result.is_ok()`
                 // b) `result == STATUS_BUFFER_TOO_SMALL && read_size as usize > size`
-                debug!(
-                    target: ntapi::TRACING_TARGET,
-                    ?result,
+                ntapi_event!(
+                    WnfRoutine::QueryStateData,
+                    false,
                     input.state_name = %self.state_name,
+                    ?result,
                     input.type_id = %self.type_id,
                     output.change_stamp = %change_stamp,
                     output.buffer_size = read_size,
                     "NtQueryWnfStateData",
                 );
 
-                Ok((read_size as usize, change_stamp))
+                match max_read_size {
+                    Some(max_read_size) if read_size as usize > max_read_size => Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        ReadError::ExceedsMaxReadSize {
+                            max_read_size,
+                            actual: read_size as usize,
+                        },
+                    )),
+                    _ => Ok((read_size as usize, change_stamp)),
+                }
             }
-        };
-
-        // SAFETY:
-        // When `reader(ptr, size)` returns `Ok((read_size, _))` with `read_size <= size`,
-        // - then condition a) (see above) holds,
-        // - hence the call to `NtQueryWnfStateData` succeeded,
-        // - hence by the assumption on `NtQueryWnfStateData`, the memory range of size `read_size` starting at `ptr` is
-        //   initialized,
-        // so the safety condition of `T::from_reader` is satisfied
-        let result = unsafe { T::from_reader(reader) };
-
-        Ok(result?.into())
+        }
     }
 }
@@ -1,16 +1,18 @@
 //! Reading types from state data
 
 use std::alloc::Layout;
-use std::ffi::c_void;
+use std::ffi::{c_void, CStr};
 use std::io::ErrorKind;
 use std::mem::MaybeUninit;
 use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::{alloc, io, mem, ptr};
 
 use thiserror::Error;
 
 use crate::bytes::CheckedBitPattern;
-use crate::data::OpaqueData;
+use crate::data::{CStrData, OpaqueData, WideString};
+use crate::wipe;
 
 /// A trait for types that can be read from state data
 ///
@@ -54,6 +56,361 @@ pub trait Read<D>: private::Sealed + Send + 'static {
     unsafe fn from_reader<F, Meta>(reader: F) -> io::Result<(D, Meta)>
     where
         F: FnMut(*mut c_void, usize) -> io::Result<(usize, Meta)>;
+
+    /// Tries to read a `D` by invoking a reader closure, allocating buffers according to the given options
+    ///
+    /// This is the same as [`Read::from_reader`], except that types whose size is not known in advance (i.e. slices)
+    /// use the given [`QueryOptions`] to determine the capacity of the buffer passed to `reader`. Other types ignore
+    /// the options.
+    ///
+    /// # Safety
+    /// See [`Read::from_reader`]
+    ///
+    /// # Errors
+    /// See [`Read::from_reader`]
+    #[doc(hidden)]
+    unsafe fn from_reader_with_options<F, Meta>(reader: F, options: QueryOptions) -> io::Result<(D, Meta)>
+    where
+        F: FnMut(*mut c_void, usize) -> io::Result<(usize, Meta)>,
+    {
+        let _ = options;
+
+        // SAFETY:
+        // The safety conditions of `from_reader_with_options` are the same as those of `from_reader`
+        unsafe { Self::from_reader(reader) }
+    }
+}
+
+/// Reads data of type `T` from the given bytes
+///
+/// This validates the bytes in the same way as querying the data of a state with data type `T`, i.e. it checks that
+/// they have the right size and (for types implementing [`CheckedBitPattern`]) a valid bit pattern. It is useful for
+/// decoding state data captured elsewhere, e.g. in a trace, without querying a state.
+///
+/// This produces an owned `T` on the stack and hence requires `T: Sized`. In order to produce a `Box<T>` for
+/// `T: ?Sized`, use the [`read_boxed_from_bytes`] function.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let value: u32 = wnf::read_from_bytes(&42u32.to_le_bytes())?;
+/// assert_eq!(value, 42);
+///
+/// assert!(wnf::read_from_bytes::<u32>(&[0; 3]).is_err());
+/// # Ok(()) }
+/// ```
+///
+/// # Errors
+/// Returns an error if the bytes are not a valid `T`, in which case [`io::Error::kind`] returns
+/// [`ErrorKind::InvalidData`] and the error wraps a [`ReadError`]
+pub fn read_from_bytes<T>(bytes: &[u8]) -> io::Result<T>
+where
+    T: Read<T>,
+{
+    // SAFETY:
+    // `bytes` is a slice of bytes, so `bytes.as_ptr()` is valid for reads of size `bytes.len()` and the memory range is
+    // initialized
+    unsafe { T::from_buffer(bytes.as_ptr().cast(), bytes.len()) }
+}
+
+/// Reads data of type `T` from the given bytes as a box
+///
+/// This is the same as [`read_from_bytes`], except that it produces a [`Box<T>`] instead of an owned `T` (requiring
+/// `T: Sized`).
+///
+/// # Errors
+/// Returns an error if the bytes are not a valid `T`, in which case [`io::Error::kind`] returns
+/// [`ErrorKind::InvalidData`] and the error wraps a [`ReadError`]
+pub fn read_boxed_from_bytes<T>(bytes: &[u8]) -> io::Result<Box<T>>
+where
+    T: Read<Box<T>> + ?Sized,
+{
+    // SAFETY:
+    // `bytes` is a slice of bytes, so `bytes.as_ptr()` is valid for reads of size `bytes.len()` and the memory range is
+    // initialized
+    unsafe { T::from_buffer(bytes.as_ptr().cast(), bytes.len()) }
+}
+
+/// The global default maximum size in bytes of data that can be read from a state, where [`usize::MAX`] means no limit
+static DEFAULT_MAX_READ_SIZE: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// Sets the maximum size in bytes of data that can be read from states that don't have their own maximum read size
+///
+/// When querying the data of a state and the data turn out to be larger than this, querying fails with a
+/// [`ReadError::ExceedsMaxReadSize`] error before a buffer for the data is allocated. Passing [`None`] (the default)
+/// removes the limit.
+///
+/// This can be overridden for individual states via
+/// [`OwnedState::set_max_read_size`](crate::state::OwnedState::set_max_read_size) and
+/// [`BorrowedState::with_max_read_size`](crate::state::BorrowedState::with_max_read_size). Note that it only applies
+/// to querying state data, not to the data passed to state listeners, which have already been allocated by the system.
+pub fn set_default_max_read_size(max_read_size: Option<usize>) {
+    DEFAULT_MAX_READ_SIZE.store(max_read_size.unwrap_or(usize::MAX), Ordering::Relaxed);
+}
+
+/// Returns the maximum size in bytes of data that can be read from states that don't have their own maximum read size
+///
+/// See [`set_default_max_read_size`]
+pub fn default_max_read_size() -> Option<usize> {
+    match DEFAULT_MAX_READ_SIZE.load(Ordering::Relaxed) {
+        usize::MAX => None,
+        max_read_size => Some(max_read_size),
+    }
+}
+
+/// The strategy for growing the buffer used for querying state data whose size is not known in advance
+///
+/// This is part of [`QueryOptions`].
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum BufferGrowth {
+    /// Grow the buffer to at least twice its previous capacity
+    ///
+    /// This amortizes the cost of reallocations in case the state data keep growing while being queried, but may
+    /// allocate up to twice the memory that is actually needed.
+    #[default]
+    Doubling,
+
+    /// Grow the buffer to exactly the size of the state data
+    ///
+    /// This never allocates more memory than needed, but may cause more reallocations in case the state data keep
+    /// growing while being queried.
+    Exact,
+}
+
+/// Options for querying state data whose size is not known in advance
+///
+/// When querying state data as a slice, a buffer is allocated to hold the data. If the buffer turns out to be too
+/// small, it is grown and the query is repeated. These options control the initial capacity of the buffer and the
+/// strategy for growing it, see e.g.
+/// [`OwnedState::query_boxed_with_options`](crate::state::OwnedState::query_boxed_with_options).
+///
+/// The default options use an initial capacity of zero and [`BufferGrowth::Doubling`], which is what
+/// [`OwnedState::query_boxed`](crate::state::OwnedState::query_boxed) uses.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct QueryOptions {
+    initial_capacity: usize,
+    growth: BufferGrowth,
+}
+
+impl QueryOptions {
+    /// Creates new [`QueryOptions`] with default values
+    pub const fn new() -> Self {
+        Self {
+            initial_capacity: 0,
+            growth: BufferGrowth::Doubling,
+        }
+    }
+
+    /// Configures the initial capacity of the buffer in bytes
+    ///
+    /// If the state data are known to have a certain size, setting this to that size avoids an additional query for
+    /// determining the size. Note that the capacity is rounded up to a multiple of the size of the slice elements.
+    #[must_use]
+    pub const fn initial_capacity(self, initial_capacity: usize) -> Self {
+        Self {
+            initial_capacity,
+            ..self
+        }
+    }
+
+    /// Configures the strategy for growing the buffer
+    #[must_use]
+    pub const fn growth(self, growth: BufferGrowth) -> Self {
+        Self { growth, ..self }
+    }
+}
+
+/// A trait for containers that the data of a state of type `[T]` can be read into directly
+///
+/// Querying the data of a state as a [`Box<[T]>`](Box) allocates a new buffer for every query. Implementing this trait
+/// for a container makes it possible to query the data directly into the spare capacity of the container instead, e.g.
+/// through [`OwnedState::query_into`](crate::state::OwnedState::query_into), avoiding both the allocation (if the
+/// container is reused) and an extra copy. It is implemented for [`Vec<T>`]. Since this trait is defined in `wnf`, you
+/// need to implement it for a newtype wrapper in order to use a container type defined in another crate, such as
+/// `SmallVec` or `BytesMut`.
+///
+/// The data are read as follows:
+/// 1. The container is cleared through [`ReadInto::clear`].
+/// 2. The data are written to the beginning of the slice returned from [`ReadInto::spare_capacity_mut`]. If the slice
+///    is too small, capacity is reserved through [`ReadInto::reserve`] and this step is repeated.
+/// 3. The data are validated. If they are valid, [`ReadInto::set_len`] is called with the number of elements written,
+///    otherwise the container stays empty.
+///
+/// # Example
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use wnf::OwnedState;
+///
+/// let state = OwnedState::<[u32]>::create_temporary()?;
+/// state.set(&[1, 2, 3])?;
+///
+/// let mut buffer = Vec::with_capacity(16);
+/// state.query_into(&mut buffer)?;
+/// assert_eq!(buffer, [1, 2, 3]);
+/// # Ok(()) }
+/// ```
+pub trait ReadInto<T> {
+    /// Removes all elements from the container
+    fn clear(&mut self);
+
+    /// Reserves capacity for at least `additional` more elements
+    ///
+    /// Afterwards, [`ReadInto::spare_capacity_mut`] must return a slice of at least `additional` elements, otherwise
+    /// reading fails.
+    fn reserve(&mut self, additional: usize);
+
+    /// Returns the spare capacity of the container, i.e. the uninitialized memory following its elements
+    fn spare_capacity_mut(&mut self) -> &mut [MaybeUninit<T>];
+
+    /// Sets the number of elements of the container, making the initialized beginning of the spare capacity part of
+    /// its elements
+    ///
+    /// # Safety
+    /// The container must be empty and the first `len` elements of the slice returned from the last call to
+    /// [`ReadInto::spare_capacity_mut`] must be initialized
+    unsafe fn set_len(&mut self, len: usize);
+}
+
+impl<T> ReadInto<T> for Vec<T> {
+    fn clear(&mut self) {
+        Vec::clear(self);
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        Vec::reserve(self, additional);
+    }
+
+    fn spare_capacity_mut(&mut self) -> &mut [MaybeUninit<T>] {
+        Vec::spare_capacity_mut(self)
+    }
+
+    unsafe fn set_len(&mut self, len: usize) {
+        // SAFETY:
+        // - `len <= self.capacity()` because the slice returned from `Vec::spare_capacity_mut` has a length of
+        //   `self.capacity() - self.len()` and `self.len() == 0`
+        // - The elements at `0..len` are initialized by the safety condition
+        unsafe { Vec::set_len(self, len) }
+    }
+}
+
+/// Reads the elements of a `[T]` into the given container by invoking a reader closure
+///
+/// This returns the metadata returned from the reader closure.
+///
+/// # Safety
+/// See [`Read::from_reader`]
+///
+/// # Errors
+/// Returns an error if `reader` fails or the read data is not a valid `[T]`
+#[cfg(windows)]
+pub(crate) unsafe fn read_into<T, C, F, Meta>(
+    container: &mut C,
+    mut reader: F,
+    options: QueryOptions,
+) -> io::Result<Meta>
+where
+    T: CheckedBitPattern,
+    C: ReadInto<T> + ?Sized,
+    F: FnMut(*mut c_void, usize) -> io::Result<(usize, Meta)>,
+{
+    container.clear();
+
+    if let Some(initial_len) = options.initial_capacity.checked_div(mem::size_of::<T>()) {
+        container.reserve(initial_len);
+    }
+
+    // We need to loop for the same reason as in `<[T] as Read<Box<[T]>>>::from_reader_with_options`
+    loop {
+        let spare = container.spare_capacity_mut();
+        let spare_len = spare.len();
+
+        // The precondition of `reader` is satisfied because `spare.as_mut_ptr()` is valid for accesses of
+        // `mem::size_of_val(spare)` bytes since it comes from a live mutable reference
+        let (size, meta) = reader(spare.as_mut_ptr().cast(), mem::size_of_val(spare))?;
+
+        let len = if size == 0 {
+            0
+        } else {
+            if mem::size_of::<T>() == 0 {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    ReadError::WrongSize {
+                        expected: 0,
+                        actual: size,
+                    },
+                ));
+            }
+
+            if size % mem::size_of::<T>() != 0 {
+                wipe_spare_capacity(container);
+
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    ReadError::WrongSizeMultiple {
+                        expected_modulus: mem::size_of::<T>(),
+                        actual: size,
+                    },
+                ));
+            }
+
+            size / mem::size_of::<T>()
+        };
+
+        if len > spare_len {
+            wipe_spare_capacity(container);
+            container.reserve(len);
+
+            if container.spare_capacity_mut().len() < len {
+                return Err(io::Error::new(
+                    ErrorKind::OutOfMemory,
+                    "container did not reserve the requested capacity",
+                ));
+            }
+
+            continue;
+        }
+
+        // We validate the slice the reader has written to rather than calling `spare_capacity_mut` again, because
+        // `ReadInto` is a safe trait, so we cannot rely on it returning the same memory again
+        let spare = &spare[..len];
+
+        // SAFETY:
+        // - `MaybeUninit<T>` has the same memory layout as `T`, which has the same memory layout as `T::Bits` by the
+        //   safety conditions of `CheckedBitPattern`
+        // - The elements at `0..len` are initialized because `size == len * mem::size_of::<T>()` (by the safety
+        //   condition) and they are valid `T::Bits` because `T::Bits: AnyBitPattern`
+        let bits = unsafe { &*(spare as *const [MaybeUninit<T>] as *const [T::Bits]) };
+
+        if bits.iter().all(T::is_valid_bit_pattern) {
+            // SAFETY:
+            // - The container is empty because it was cleared and `set_len` has not been called since
+            // - The elements at `0..len` of the slice returned from the last call to `spare_capacity_mut` are
+            //   initialized (see above)
+            unsafe {
+                container.set_len(len);
+            }
+
+            return Ok(meta);
+        }
+
+        wipe_spare_capacity(container);
+        return Err(io::Error::new(ErrorKind::InvalidData, ReadError::InvalidBitPattern));
+    }
+}
+
+/// Overwrites the spare capacity of the given container with zeros
+#[cfg(windows)]
+fn wipe_spare_capacity<T, C>(container: &mut C)
+where
+    T: CheckedBitPattern,
+    C: ReadInto<T> + ?Sized,
+{
+    for elem in container.spare_capacity_mut() {
+        // SAFETY:
+        // `MaybeUninit<T>` has the same memory layout as `MaybeUninit<T::Bits>` by the safety conditions of
+        // `CheckedBitPattern`
+        wipe::wipe_uninit(unsafe { &mut *(elem as *mut MaybeUninit<T>).cast::<MaybeUninit<T::Bits>>() });
+    }
 }
 
 impl Read<OpaqueData> for OpaqueData {
@@ -71,6 +428,72 @@ impl Read<OpaqueData> for OpaqueData {
     }
 }
 
+impl Read<CStrData> for CStrData {
+    unsafe fn from_buffer(ptr: *const c_void, size: usize) -> io::Result<CStrData> {
+        // SAFETY:
+        // The safety conditions of `from_buffer` are the same as those of `<[u8] as Read<Box<[u8]>>>::from_buffer`
+        let data = unsafe { <[u8] as Read<Box<[u8]>>>::from_buffer(ptr, size) }?;
+        c_str_data_from_bytes(&data)
+    }
+
+    unsafe fn from_reader<F, Meta>(reader: F) -> io::Result<(CStrData, Meta)>
+    where
+        F: FnMut(*mut c_void, usize) -> io::Result<(usize, Meta)>,
+    {
+        // SAFETY:
+        // The safety conditions of `from_reader` are the same as those of `from_reader_with_options`
+        unsafe { Self::from_reader_with_options(reader, QueryOptions::default()) }
+    }
+
+    unsafe fn from_reader_with_options<F, Meta>(reader: F, options: QueryOptions) -> io::Result<(CStrData, Meta)>
+    where
+        F: FnMut(*mut c_void, usize) -> io::Result<(usize, Meta)>,
+    {
+        // SAFETY:
+        // The safety conditions of `from_reader_with_options` are the same as those of
+        // `<[u8] as Read<Box<[u8]>>>::from_reader_with_options`
+        let (data, meta) = unsafe { <[u8] as Read<Box<[u8]>>>::from_reader_with_options(reader, options) }?;
+        Ok((c_str_data_from_bytes(&data)?, meta))
+    }
+}
+
+/// Reads a [`CStrData`] from the given bytes, which must contain a NUL byte
+fn c_str_data_from_bytes(data: &[u8]) -> io::Result<CStrData> {
+    let c_str = CStr::from_bytes_until_nul(data)
+        .map_err(|_| io::Error::new(ErrorKind::InvalidData, ReadError::MissingNulTerminator))?;
+
+    Ok(CStrData::from_c_string(c_str.to_owned()))
+}
+
+impl Read<WideString> for WideString {
+    unsafe fn from_buffer(ptr: *const c_void, size: usize) -> io::Result<WideString> {
+        // SAFETY:
+        // The safety conditions of `from_buffer` are the same as those of `<[u16] as Read<Box<[u16]>>>::from_buffer`
+        let data = unsafe { <[u16] as Read<Box<[u16]>>>::from_buffer(ptr, size) }?;
+        Ok(WideString::from_wide(data.into_vec()))
+    }
+
+    unsafe fn from_reader<F, Meta>(reader: F) -> io::Result<(WideString, Meta)>
+    where
+        F: FnMut(*mut c_void, usize) -> io::Result<(usize, Meta)>,
+    {
+        // SAFETY:
+        // The safety conditions of `from_reader` are the same as those of `from_reader_with_options`
+        unsafe { Self::from_reader_with_options(reader, QueryOptions::default()) }
+    }
+
+    unsafe fn from_reader_with_options<F, Meta>(reader: F, options: QueryOptions) -> io::Result<(WideString, Meta)>
+    where
+        F: FnMut(*mut c_void, usize) -> io::Result<(usize, Meta)>,
+    {
+        // SAFETY:
+        // The safety conditions of `from_reader_with_options` are the same as those of
+        // `<[u16] as Read<Box<[u16]>>>::from_reader_with_options`
+        let (data, meta) = unsafe { <[u16] as Read<Box<[u16]>>>::from_reader_with_options(reader, options) }?;
+        Ok((WideString::from_wide(data.into_vec()), meta))
+    }
+}
+
 impl<T> Read<T> for T
 where
     T: CheckedBitPattern,
@@ -90,16 +513,19 @@ where
         // - `ptr` is valid for reads of `T::Bits` by the safety condition and `size == mem::size_of::<T::Bits>()`
         // - `ptr` points to a valid `T::Bits` because the memory range is initialized (by the safety condition) and
         //   `T::Bits: AnyBitPattern`
-        let bits: T::Bits = unsafe { ptr::read_unaligned(ptr.cast()) };
+        let mut bits: T::Bits = unsafe { ptr::read_unaligned(ptr.cast()) };
 
-        if T::is_valid_bit_pattern(&bits) {
+        let result = if T::is_valid_bit_pattern(&bits) {
             // SAFETY: By the safety conditions of `CheckedBitPattern`,
             // - `T` has the same memory layout as `T::Bits`
             // - `bits` can be reinterpreted as a `T` because `T::is_valid_bit_pattern(&bits)` is `true`
             Ok(unsafe { *(&bits as *const T::Bits as *const T) })
         } else {
             Err(io::Error::new(ErrorKind::InvalidData, ReadError::InvalidBitPattern))
-        }
+        };
+
+        wipe::wipe(&mut bits);
+        result
     }
 
     unsafe fn from_reader<F, Meta>(mut reader: F) -> io::Result<(T, Meta)>
@@ -112,6 +538,8 @@ where
         let (size, meta) = reader(bits.as_mut_ptr().cast(), mem::size_of::<T::Bits>())?;
 
         if size != mem::size_of::<T::Bits>() {
+            wipe::wipe_uninit(&mut bits);
+
             return Err(io::Error::new(
                 ErrorKind::InvalidData,
                 ReadError::WrongSize {
@@ -124,9 +552,9 @@ where
         // SAFETY:
         // `bits.as_mut_ptr()` points to a valid `T::Bits` because the memory range is initialized (by the safety
         // condition and `size == mem::size_of::<T::Bits>()`) and `T::Bits: AnyBitPattern`
-        let bits = unsafe { bits.assume_init() };
+        let mut bits = unsafe { bits.assume_init() };
 
-        if T::is_valid_bit_pattern(&bits) {
+        let result = if T::is_valid_bit_pattern(&bits) {
             // SAFETY: By the safety conditions of `CheckedBitPattern`,
             // - `T` has the same memory layout as `T::Bits`
             // - `bits` can be reinterpreted as a `T` because `T::is_valid_bit_pattern(&bits)` is `true`
@@ -134,7 +562,10 @@ where
             Ok((data, meta))
         } else {
             Err(io::Error::new(ErrorKind::InvalidData, ReadError::InvalidBitPattern))
-        }
+        };
+
+        wipe::wipe(&mut bits);
+        result
     }
 }
 
@@ -154,7 +585,7 @@ where
         }
 
         // When MSRV 1.82 is acceptable, we can use `Box::new_uninit` instead
-        let bits = if mem::size_of::<T::Bits>() == 0 {
+        let mut bits = if mem::size_of::<T::Bits>() == 0 {
             // SAFETY:
             // The all-zero byte pattern is a valid `T::Bits` because `T::Bits` is zero-sized
             // (or, alternatively, because `T::Bits: AnyBitPattern`)
@@ -195,6 +626,7 @@ where
             // - `bits` can be reinterpreted as a `T` because `T::is_valid_bit_pattern(&bits)` is `true`
             Ok(unsafe { Box::from_raw(Box::into_raw(bits) as *mut T) })
         } else {
+            wipe::wipe(&mut *bits);
             Err(io::Error::new(ErrorKind::InvalidData, ReadError::InvalidBitPattern))
         }
     }
@@ -225,6 +657,8 @@ where
         let (size, meta) = reader(bits.as_mut_ptr().cast(), mem::size_of::<T::Bits>())?;
 
         if size != mem::size_of::<T::Bits>() {
+            wipe::wipe_uninit(&mut *bits);
+
             return Err(io::Error::new(
                 ErrorKind::InvalidData,
                 ReadError::WrongSize {
@@ -239,7 +673,7 @@ where
         // - `T::Bits` has the same memory layout as `MaybeUninit<T::Bits>`
         // - The box contains a valid `T::Bits` because the memory range is initialized (by the safety condition and
         //   `size == mem::size_of::<T::Bits>()`) and `T::Bits: AnyBitPattern`
-        let bits = unsafe { Box::from_raw(Box::into_raw(bits) as *mut T::Bits) };
+        let mut bits = unsafe { Box::from_raw(Box::into_raw(bits) as *mut T::Bits) };
 
         if T::is_valid_bit_pattern(&bits) {
             // SAFETY:
@@ -251,6 +685,7 @@ where
             let data = unsafe { Box::from_raw(Box::into_raw(bits) as *mut T) };
             Ok((data, meta))
         } else {
+            wipe::wipe(&mut *bits);
             Err(io::Error::new(ErrorKind::InvalidData, ReadError::InvalidBitPattern))
         }
     }
@@ -261,74 +696,37 @@ where
     T: CheckedBitPattern,
 {
     unsafe fn from_buffer(ptr: *const c_void, size: usize) -> io::Result<Box<[T]>> {
-        if mem::size_of::<T::Bits>() == 0 {
-            return if size == 0 {
-                Ok(Vec::new().into_boxed_slice())
-            } else {
-                Err(io::Error::new(
-                    ErrorKind::InvalidData,
-                    ReadError::WrongSize {
-                        expected: 0,
-                        actual: size,
-                    },
-                ))
-            };
-        }
-
-        if size % mem::size_of::<T::Bits>() != 0 {
-            return Err(io::Error::new(
-                ErrorKind::InvalidData,
-                ReadError::WrongSizeMultiple {
-                    expected_modulus: mem::size_of::<T::Bits>(),
-                    actual: size,
-                },
-            ));
-        }
-
-        let len = size / mem::size_of::<T::Bits>();
-        let mut buffer = Vec::with_capacity(len);
-
         // SAFETY:
-        // - `ptr` is valid for reads of size `size` by the safety condition
-        // - `buffer.as_mut_ptr()` is valid for writes of size `size` because `buffer.capacity() *
-        //   mem::size_of::<T::Bits>() == size`
-        // - Both `ptr` and `buffer.as_mut_ptr()` are trivially properly aligned as `mem::align_of::<u8>() == 1`
-        // - The source and destination regions don't overlap because the source region is within the bounds of a single
-        //   allocated object (because `ptr` is valid for reads) while the destination region is a freshly allocated
-        //   object
-        unsafe {
-            ptr::copy_nonoverlapping(ptr as *const u8, buffer.as_mut_ptr() as *mut u8, size);
-        }
-
-        // SAFETY:
-        // - `len <= buffer.capacity()`
-        // - The elements at `0..len` are valid `T::Bits` because the memory range is initialized (by the safety
-        //   condition) and `T::Bits: AnyBitPattern`
-        unsafe {
-            buffer.set_len(len);
-        }
+        // The safety conditions of `from_buffer` are the same as those of `slice_bits_from_buffer`
+        let mut buffer = unsafe { slice_bits_from_buffer::<T>(ptr, size) }?;
 
         if buffer.iter().all(T::is_valid_bit_pattern) {
-            let data = buffer.into_boxed_slice();
-
             // SAFETY:
-            // - The raw pointer is obtained via `Box::into_raw` from a `Box<[T::Bits]>`
-            //
-            // By the safety conditions of `CheckedBitPattern`,
-            // - `T` has the same memory layout as `T::Bits`
-            // - all elements of `data` can be reinterpreted as `T` because `T::is_valid_bit_pattern` is `true` for each
-            //   element
-            Ok(unsafe { Box::from_raw(Box::into_raw(data) as *mut [T]) })
+            // `T::is_valid_bit_pattern` is `true` for each element of `buffer`
+            Ok(unsafe { slice_from_valid_bits(buffer) })
         } else {
+            wipe::wipe_vec(&mut buffer);
             Err(io::Error::new(ErrorKind::InvalidData, ReadError::InvalidBitPattern))
         }
     }
 
-    unsafe fn from_reader<F, Meta>(mut reader: F) -> io::Result<(Box<[T]>, Meta)>
+    unsafe fn from_reader<F, Meta>(reader: F) -> io::Result<(Box<[T]>, Meta)>
+    where
+        F: FnMut(*mut c_void, usize) -> io::Result<(usize, Meta)>,
+    {
+        // SAFETY:
+        // The safety conditions of `from_reader` are the same as those of `from_reader_with_options`
+        unsafe { Self::from_reader_with_options(reader, QueryOptions::default()) }
+    }
+
+    unsafe fn from_reader_with_options<F, Meta>(mut reader: F, options: QueryOptions) -> io::Result<(Box<[T]>, Meta)>
     where
         F: FnMut(*mut c_void, usize) -> io::Result<(usize, Meta)>,
     {
-        let mut buffer: Vec<T::Bits> = Vec::new();
+        let mut buffer: Vec<T::Bits> = match mem::size_of::<T::Bits>() {
+            0 => Vec::new(),
+            elem_size => Vec::with_capacity(options.initial_capacity.div_ceil(elem_size)),
+        };
 
         // We need to loop to deal with race conditions caused by the state data growing larger after we determine
         // its size but before we perform the actual read. This is guaranteed to terminate because we only reiterate
@@ -356,6 +754,8 @@ where
             }
 
             if size % mem::size_of::<T::Bits>() != 0 {
+                wipe::wipe_vec(&mut buffer);
+
                 return Err(io::Error::new(
                     ErrorKind::InvalidData,
                     ReadError::WrongSizeMultiple {
@@ -368,7 +768,13 @@ where
             let len = size / mem::size_of::<T::Bits>();
 
             if len > buffer.capacity() {
-                buffer.reserve(len);
+                // Reserving may move the buffer to a new allocation, so we wipe the old one first
+                wipe::wipe_vec(&mut buffer);
+
+                match options.growth {
+                    BufferGrowth::Doubling => buffer.reserve(len),
+                    BufferGrowth::Exact => buffer.reserve_exact(len),
+                }
                 // At this point we have `buffer.capacity() >= len`
             } else {
                 break (len, meta);
@@ -386,7 +792,7 @@ where
         }
 
         if buffer.iter().all(T::is_valid_bit_pattern) {
-            let data = buffer.into_boxed_slice();
+            let data = wipe::into_boxed_slice(buffer);
 
             // SAFETY:
             // - The raw pointer is obtained via `Box::into_raw` from a `Box<[T::Bits]>`
@@ -399,11 +805,128 @@ where
 
             Ok((data, meta))
         } else {
+            wipe::wipe_vec(&mut buffer);
             Err(io::Error::new(ErrorKind::InvalidData, ReadError::InvalidBitPattern))
         }
     }
 }
 
+/// Reads the longest prefix of valid elements of a `[T]` from a preallocated buffer
+///
+/// The buffer starts at `ptr` and is `size` bytes long.
+///
+/// This returns the valid prefix together with the index of the first invalid element, or [`None`] if all elements
+/// are valid.
+///
+/// # Errors
+/// Returns an error if `size` is not a multiple of the size of `T` (or nonzero if `T` is zero-sized)
+///
+/// # Safety
+/// - `ptr` must be valid for reads of size `size`
+/// - The memory range of size `size` starting at `ptr` must be initialized
+#[cfg(all(windows, feature = "subscribe"))]
+pub(crate) unsafe fn slice_prefix_from_buffer<T>(
+    ptr: *const c_void,
+    size: usize,
+) -> io::Result<(Box<[T]>, Option<usize>)>
+where
+    T: CheckedBitPattern,
+{
+    // SAFETY:
+    // The safety conditions of `slice_prefix_from_buffer` are the same as those of `slice_bits_from_buffer`
+    let mut buffer = unsafe { slice_bits_from_buffer::<T>(ptr, size) }?;
+
+    let first_invalid_index = buffer.iter().position(|bits| !T::is_valid_bit_pattern(bits));
+
+    if let Some(first_invalid_index) = first_invalid_index {
+        buffer.truncate(first_invalid_index);
+    }
+
+    // SAFETY:
+    // After truncating, `T::is_valid_bit_pattern` is `true` for each element of `buffer`
+    let data = unsafe { slice_from_valid_bits(buffer) };
+
+    Ok((data, first_invalid_index))
+}
+
+/// Reads the elements of a `[T]` from a preallocated buffer as `T::Bits` without checking them for validity
+///
+/// # Safety
+/// - `ptr` must be valid for reads of size `size`
+/// - The memory range of size `size` starting at `ptr` must be initialized
+unsafe fn slice_bits_from_buffer<T>(ptr: *const c_void, size: usize) -> io::Result<Vec<T::Bits>>
+where
+    T: CheckedBitPattern,
+{
+    if mem::size_of::<T::Bits>() == 0 {
+        return if size == 0 {
+            Ok(Vec::new())
+        } else {
+            Err(io::Error::new(
+                ErrorKind::InvalidData,
+                ReadError::WrongSize {
+                    expected: 0,
+                    actual: size,
+                },
+            ))
+        };
+    }
+
+    if size % mem::size_of::<T::Bits>() != 0 {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            ReadError::WrongSizeMultiple {
+                expected_modulus: mem::size_of::<T::Bits>(),
+                actual: size,
+            },
+        ));
+    }
+
+    let len = size / mem::size_of::<T::Bits>();
+    let mut buffer = Vec::with_capacity(len);
+
+    // SAFETY:
+    // - `ptr` is valid for reads of size `size` by the safety condition
+    // - `buffer.as_mut_ptr()` is valid for writes of size `size` because `buffer.capacity() * mem::size_of::<T::Bits>()
+    //   == size`
+    // - Both `ptr` and `buffer.as_mut_ptr()` are trivially properly aligned as `mem::align_of::<u8>() == 1`
+    // - The source and destination regions don't overlap because the source region is within the bounds of a single
+    //   allocated object (because `ptr` is valid for reads) while the destination region is a freshly allocated object
+    unsafe {
+        ptr::copy_nonoverlapping(ptr as *const u8, buffer.as_mut_ptr() as *mut u8, size);
+    }
+
+    // SAFETY:
+    // - `len <= buffer.capacity()`
+    // - The elements at `0..len` are valid `T::Bits` because the memory range is initialized (by the safety condition)
+    //   and `T::Bits: AnyBitPattern`
+    unsafe {
+        buffer.set_len(len);
+    }
+
+    Ok(buffer)
+}
+
+/// Reinterprets a vector of `T::Bits` as a boxed slice of `T`
+///
+/// # Safety
+/// `T::is_valid_bit_pattern` must be `true` for each element of `buffer`
+unsafe fn slice_from_valid_bits<T>(buffer: Vec<T::Bits>) -> Box<[T]>
+where
+    T: CheckedBitPattern,
+{
+    let data = wipe::into_boxed_slice(buffer);
+
+    // SAFETY:
+    // - The raw pointer is obtained via `Box::into_raw` from a `Box<[T::Bits]>`
+    //
+    // By the safety conditions of `CheckedBitPattern`,
+    // - `T` has the same memory layout as `T::Bits`
+    // - all elements of `data` can be reinterpreted as `T` because `T::is_valid_bit_pattern` is `true` for each element
+    //   by the safety condition
+    unsafe { Box::from_raw(Box::into_raw(data) as *mut [T]) }
+}
+
 /// An error reading state data
 #[derive(Clone, Copy, Debug, Eq, Error, Hash, PartialEq)]
 pub enum ReadError {
@@ -429,6 +952,37 @@ pub enum ReadError {
         actual: usize,
     },
 
+    /// The state data doesn't contain a NUL byte (for the data type [`CStrData`])
+    #[error("failed to read state data: data is not NUL-terminated")]
+    MissingNulTerminator,
+
+    /// The size of the data exceeds the maximum read size of the state
+    #[error("failed to read state data: data is too large (expected at most {max_read_size}, got {actual})")]
+    ExceedsMaxReadSize {
+        /// The maximum read size in bytes of the state
+        max_read_size: usize,
+
+        /// The actual size in bytes of the state data
+        actual: usize,
+    },
+
+    /// The state data is too short to contain a version number (for a [`VersionedState`](crate::VersionedState))
+    #[error("failed to read state data: data is too short to contain a version number (got {actual})")]
+    MissingVersion {
+        /// The actual size in bytes of the state data
+        actual: usize,
+    },
+
+    /// The state data has an unexpected version number (for a [`VersionedState`](crate::VersionedState))
+    #[error("failed to read state data: data has unexpected version (expected {expected}, got {actual})")]
+    UnexpectedVersion {
+        /// The expected version number
+        expected: u32,
+
+        /// The actual version number of the state data
+        actual: u32,
+    },
+
     /// The state data has an invalid bit pattern for the data type `T`
     #[error("failed to read state data: data has invalid bit pattern")]
     InvalidBitPattern,
@@ -441,6 +995,8 @@ mod private {
     pub trait Sealed {}
 
     impl Sealed for OpaqueData {}
+    impl Sealed for CStrData {}
+    impl Sealed for WideString {}
     impl<T> Sealed for T where T: CheckedBitPattern {}
     impl<T> Sealed for [T] where T: CheckedBitPattern {}
 }
@@ -474,6 +1030,51 @@ mod tests {
         assert!(matches!(result, Ok((data, "Meta")) if data.size() == 2));
     }
 
+    #[test]
+    fn c_str_data_from_reader_success() {
+        // SAFETY: See `reader`
+        let result = unsafe { CStrData::from_reader(reader(b"ab\0c\0", "Meta")) };
+
+        assert!(matches!(result, Ok((data, "Meta")) if data.as_bytes() == b"ab"));
+    }
+
+    #[test]
+    fn c_str_data_from_reader_missing_nul_terminator() {
+        // SAFETY: See `reader`
+        let result = unsafe { CStrData::from_reader(reader(b"abc", "Meta")) };
+
+        assert!(result.is_err());
+        let err = result.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(
+            *err.into_inner().unwrap().downcast::<ReadError>().unwrap(),
+            ReadError::MissingNulTerminator
+        );
+    }
+
+    #[test]
+    fn wide_string_from_reader_success() {
+        let raw_data: Vec<_> = [0x0061_u16, 0x0062, 0x0000, 0x0063]
+            .iter()
+            .flat_map(|&value| value.to_le_bytes().into_iter())
+            .collect();
+
+        // SAFETY: See `reader`
+        let result = unsafe { WideString::from_reader(reader(&raw_data, "Meta")) };
+
+        assert!(matches!(result, Ok((data, "Meta")) if data.as_wide() == [0x0061, 0x0062]));
+    }
+
+    #[test]
+    fn wide_string_from_reader_wrong_size() {
+        // SAFETY: See `reader`
+        let result = unsafe { WideString::from_reader(reader(&[0xFF; 3], "Meta")) };
+
+        assert!(result.is_err());
+        let err = result.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
     #[test]
     fn zero_sized_from_buffer_success() {
         // SAFETY:
@@ -793,6 +1394,46 @@ mod tests {
         );
     }
 
+    #[test]
+    #[cfg(all(windows, feature = "subscribe"))]
+    fn slice_prefix_from_buffer_all_valid() {
+        let data = MisalignedU16Slice::default();
+        let (ptr, size) = data.as_buffer();
+
+        // SAFETY:
+        // - `ptr` and `size` come from a preallocated buffer
+        let result: io::Result<(Box<[u16]>, _)> = unsafe { slice_prefix_from_buffer(ptr, size) };
+
+        assert!(matches!(result, Ok((read_data, None)) if *read_data == *data.as_u16_slice()));
+    }
+
+    #[test]
+    #[cfg(all(windows, feature = "subscribe"))]
+    fn slice_prefix_from_buffer_partially_valid() {
+        let data: [u8; 4] = [1, 0, 2, 1];
+
+        // SAFETY:
+        // - `data.as_ptr()` and `data.len()` come from a live array
+        let result: io::Result<(Box<[bool]>, _)> =
+            unsafe { slice_prefix_from_buffer(data.as_ptr().cast(), data.len()) };
+
+        assert!(matches!(result, Ok((read_data, Some(2))) if *read_data == [true, false]));
+    }
+
+    #[test]
+    #[cfg(all(windows, feature = "subscribe"))]
+    fn slice_prefix_from_buffer_wrong_size_multiple() {
+        let data = MisalignedU16Slice::default();
+        let (ptr, size) = data.as_buffer();
+
+        // SAFETY:
+        // - `ptr` and `size` come from a preallocated buffer
+        let result: io::Result<(Box<[u64]>, _)> = unsafe { slice_prefix_from_buffer(ptr, size) };
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
     #[test]
     fn nonzero_sized_slice_from_reader_success() {
         let data: [u16; 2] = [0x1234, 0x5678];
@@ -820,6 +1461,52 @@ mod tests {
         assert!(matches!(result, Ok((read_data, "Meta 2")) if *read_data == data));
     }
 
+    #[test]
+    fn nonzero_sized_slice_from_reader_with_initial_capacity() {
+        let data: [u16; 2] = [0x1234, 0x5678];
+        let raw_data: Vec<_> = data.iter().flat_map(|&value| value.to_le_bytes().into_iter()).collect();
+        let mut buffer_sizes = Vec::new();
+        let mut reader = reader(&raw_data, "Meta");
+
+        // SAFETY: See `reader`
+        let result: io::Result<(Box<[u16]>, &str)> = unsafe {
+            <[u16]>::from_reader_with_options(
+                |ptr, size| {
+                    buffer_sizes.push(size);
+                    reader(ptr, size)
+                },
+                QueryOptions::new().initial_capacity(3),
+            )
+        };
+
+        assert!(matches!(result, Ok((read_data, "Meta")) if *read_data == data));
+        // The initial capacity of three bytes is rounded up to two elements, so no additional query is needed
+        assert_eq!(buffer_sizes, [4]);
+    }
+
+    #[test]
+    fn nonzero_sized_slice_from_reader_growing_exact() {
+        let data: [u16; 5] = [0x1122, 0x3344, 0x5566, 0x7788, 0x99AA];
+        let raw_data_1 = data[0].to_le_bytes();
+        let raw_data_2: Vec<_> = data.iter().flat_map(|&value| value.to_le_bytes().into_iter()).collect();
+        let mut buffer_sizes = Vec::new();
+        let mut reader = multireader(vec![(&raw_data_1, "Meta 1"), (&raw_data_2, "Meta 2")]);
+
+        // SAFETY: See `multireader`
+        let result: io::Result<(Box<[u16]>, &str)> = unsafe {
+            <[u16]>::from_reader_with_options(
+                |ptr, size| {
+                    buffer_sizes.push(size);
+                    reader(ptr, size)
+                },
+                QueryOptions::new().growth(BufferGrowth::Exact),
+            )
+        };
+
+        assert!(matches!(result, Ok((read_data, "Meta 2")) if *read_data == data));
+        assert_eq!(buffer_sizes, [0, 2, 10]);
+    }
+
     #[test]
     fn nonzero_sized_slice_from_reader_wrong_size_multiple() {
         // SAFETY: See `reader`
@@ -849,6 +1536,180 @@ mod tests {
         );
     }
 
+    #[cfg(windows)]
+    #[test]
+    fn read_into_vec_growing() {
+        let data: [u16; 5] = [0x1122, 0x3344, 0x5566, 0x7788, 0x99AA];
+        let raw_data_1 = data[0].to_le_bytes();
+        let raw_data_2: Vec<_> = data.iter().flat_map(|&value| value.to_le_bytes().into_iter()).collect();
+        let mut container: Vec<u16> = Vec::new();
+
+        // SAFETY: See `multireader`
+        let result = unsafe {
+            read_into(
+                &mut container,
+                multireader(vec![(&raw_data_1, "Meta 1"), (&raw_data_2, "Meta 2")]),
+                QueryOptions::default(),
+            )
+        };
+
+        assert!(matches!(result, Ok("Meta 2")));
+        assert_eq!(container, data);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn read_into_vec_with_sufficient_capacity() {
+        let data: [u16; 2] = [0x1234, 0x5678];
+        let raw_data: Vec<_> = data.iter().flat_map(|&value| value.to_le_bytes().into_iter()).collect();
+        let mut buffer_sizes = Vec::new();
+        let mut reader = reader(&raw_data, "Meta");
+        let mut container: Vec<u16> = Vec::with_capacity(4);
+        container.push(0xFFFF);
+
+        // SAFETY: See `reader`
+        let result = unsafe {
+            read_into(
+                &mut container,
+                |ptr, size| {
+                    buffer_sizes.push(size);
+                    reader(ptr, size)
+                },
+                QueryOptions::default(),
+            )
+        };
+
+        assert!(matches!(result, Ok("Meta")));
+        assert_eq!(container, data);
+        // The previous element is removed, so the whole capacity is available
+        assert_eq!(buffer_sizes, [container.capacity() * 2]);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn read_into_vec_invalid_bit_pattern() {
+        let mut container = vec![AlwaysInvalid(0u16)];
+
+        // SAFETY: See `reader`
+        let result = unsafe { read_into(&mut container, reader(&[0xFF; 4], "Meta"), QueryOptions::default()) };
+
+        let err = result.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(
+            err.get_ref().unwrap().downcast_ref::<ReadError>(),
+            Some(&ReadError::InvalidBitPattern)
+        );
+        assert!(container.is_empty());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn read_into_vec_wrong_size_multiple() {
+        let mut container: Vec<u64> = Vec::new();
+
+        // SAFETY: See `reader`
+        let result = unsafe { read_into(&mut container, reader(&[0xFF; 4], "Meta"), QueryOptions::default()) };
+
+        assert_eq!(
+            result.unwrap_err().get_ref().unwrap().downcast_ref::<ReadError>(),
+            Some(&ReadError::WrongSizeMultiple {
+                expected_modulus: 8,
+                actual: 4
+            })
+        );
+        assert!(container.is_empty());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn read_into_container_returning_different_spare_capacity() {
+        /// A container returning its buffer only on the first call of `spare_capacity_mut` after clearing
+        struct OneShotBuffer {
+            data: [MaybeUninit<u16>; 4],
+            len: usize,
+            is_spare_taken: bool,
+        }
+
+        impl ReadInto<u16> for OneShotBuffer {
+            fn clear(&mut self) {
+                self.len = 0;
+                self.is_spare_taken = false;
+            }
+
+            fn reserve(&mut self, _: usize) {}
+
+            fn spare_capacity_mut(&mut self) -> &mut [MaybeUninit<u16>] {
+                if mem::replace(&mut self.is_spare_taken, true) {
+                    &mut []
+                } else {
+                    &mut self.data[self.len..]
+                }
+            }
+
+            unsafe fn set_len(&mut self, len: usize) {
+                self.len = len;
+            }
+        }
+
+        let data: [u16; 2] = [0x1234, 0x5678];
+        let raw_data: Vec<_> = data.iter().flat_map(|&value| value.to_le_bytes().into_iter()).collect();
+        let mut container = OneShotBuffer {
+            data: [MaybeUninit::uninit(); 4],
+            len: 0,
+            is_spare_taken: false,
+        };
+
+        // SAFETY: See `reader`
+        let result = unsafe { read_into(&mut container, reader(&raw_data, "Meta"), QueryOptions::default()) };
+
+        assert!(matches!(result, Ok("Meta")));
+        assert_eq!(container.len, 2);
+
+        // SAFETY:
+        // The first two elements have been written by the reader
+        let read_data = unsafe { [container.data[0].assume_init(), container.data[1].assume_init()] };
+        assert_eq!(read_data, data);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn read_into_vec_zero_sized() {
+        let mut container = vec![(); 3];
+
+        // SAFETY: See `reader`
+        let result = unsafe { read_into(&mut container, reader(&[], "Meta"), QueryOptions::default()) };
+
+        assert!(matches!(result, Ok("Meta")));
+        assert!(container.is_empty());
+    }
+
+    #[test]
+    fn read_from_bytes_success() {
+        let value: u32 = read_from_bytes(&0x1234_5678u32.to_ne_bytes()).unwrap();
+
+        assert_eq!(value, 0x1234_5678);
+    }
+
+    #[test]
+    fn read_from_bytes_wrong_size() {
+        let err = read_from_bytes::<u32>(&[0; 3]).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(
+            err.get_ref().unwrap().downcast_ref::<ReadError>(),
+            Some(&ReadError::WrongSize { expected: 4, actual: 3 })
+        );
+    }
+
+    #[test]
+    fn read_boxed_from_bytes_slice() {
+        let bytes: Vec<u8> = [1u16, 2, 3].iter().flat_map(|value| value.to_ne_bytes()).collect();
+
+        let slice: Box<[u16]> = read_boxed_from_bytes(&bytes).unwrap();
+
+        assert_eq!(*slice, [1, 2, 3]);
+    }
+
     #[derive(Clone, Copy, Debug)]
     #[repr(C)]
     struct ZeroSized;
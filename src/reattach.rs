@@ -0,0 +1,351 @@
+//! Methods for subscribing to state changes with automatic reattaching after the state is deleted
+//!
+//! This module adds inherent impls to [`OwnedState<T>`] and [`BorrowedState<'_, T>`](BorrowedState) as well as the
+//! [`ReattachPolicy`], [`ReattachEvent<'_, T>`](ReattachEvent) and
+//! [`ReattachingSubscription<'_, F>`](ReattachingSubscription) types.
+
+#![deny(unsafe_code)]
+
+use std::fmt::{self, Debug, Formatter};
+use std::io;
+use std::marker::PhantomData;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::state::{BorrowedState, OwnedState, RawState};
+use crate::subscribe::{DataAccessor, SeenChangeStamp, StateListener, Subscription};
+
+/// A policy controlling how a [`ReattachingSubscription<'_, F>`](ReattachingSubscription) detects that its state has
+/// been deleted and how it tries to subscribe again
+///
+/// Since the WNF API does not notify subscribers about a state being deleted or created, both are detected by
+/// polling whether the state exists.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ReattachPolicy {
+    check_interval: Duration,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl ReattachPolicy {
+    /// Creates a new [`ReattachPolicy`] with default values
+    ///
+    /// By default, the existence of the state is checked every second while subscribed. After the state has been
+    /// deleted, the delay between attempts to subscribe again starts at 100 milliseconds and doubles after every failed
+    /// attempt up to a maximum of 30 seconds.
+    pub const fn new() -> Self {
+        Self {
+            check_interval: Duration::from_secs(1),
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+
+    /// Configures the interval in which the existence of the state is checked while subscribed
+    #[must_use]
+    pub const fn check_interval(self, check_interval: Duration) -> Self {
+        Self { check_interval, ..self }
+    }
+
+    /// Configures the delay before the first attempt to subscribe again after the state has been deleted
+    #[must_use]
+    pub const fn initial_backoff(self, initial_backoff: Duration) -> Self {
+        Self {
+            initial_backoff,
+            ..self
+        }
+    }
+
+    /// Configures the maximum delay between attempts to subscribe again after the state has been deleted
+    #[must_use]
+    pub const fn max_backoff(self, max_backoff: Duration) -> Self {
+        Self { max_backoff, ..self }
+    }
+}
+
+impl Default for ReattachPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An event passed to the listener of a [`ReattachingSubscription<'_, F>`](ReattachingSubscription)
+#[derive(Debug)]
+pub enum ReattachEvent<'a, T>
+where
+    T: ?Sized,
+{
+    /// The state was updated
+    ///
+    /// This is the equivalent of a call to a listener subscribed through [`OwnedState::subscribe`].
+    Updated(DataAccessor<'a, T>),
+
+    /// The state was deleted, so no updates are delivered until it exists again
+    Detached,
+
+    /// The state exists again after it was deleted and the listener has been subscribed to it again
+    ///
+    /// Updates that happened while the listener was detached are not delivered.
+    Reattached,
+}
+
+impl<T> OwnedState<T>
+where
+    T: 'static + ?Sized,
+{
+    /// Subscribes the given listener to this state, subscribing it again if the state is deleted and then exists again
+    ///
+    /// A plain subscription (see [`subscribe`](OwnedState::subscribe)) silently stops receiving updates when the state
+    /// is deleted. This method instead spawns a background thread that regularly checks whether the state still
+    /// exists. When the state is deleted, the listener is called with [`ReattachEvent::Detached`] and the background
+    /// thread tries to subscribe again with a backoff according to the given [`ReattachPolicy`]. Once this succeeds,
+    /// the listener is called with [`ReattachEvent::Reattached`] and then receives updates again.
+    ///
+    /// Note that a state that is created through the WNF API always gets a new state name, so this is only useful for
+    /// states whose names can exist again after being deleted, e.g. states that are provisioned externally.
+    ///
+    /// The listener is never called concurrently. See [`OwnedState::subscribe`] for the meaning of the
+    /// `last_seen_change_stamp` argument, which only applies to the initial subscription.
+    ///
+    /// # Errors
+    /// Returns an error if the initial subscription fails or if spawning the background thread fails
+    pub fn subscribe_reattaching<F>(
+        &self,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+        policy: ReattachPolicy,
+    ) -> io::Result<ReattachingSubscription<'_, F>>
+    where
+        F: FnMut(ReattachEvent<'_, T>) + Send + 'static,
+    {
+        self.raw.subscribe_reattaching(listener, last_seen_change_stamp, policy)
+    }
+}
+
+impl<'a, T> BorrowedState<'a, T>
+where
+    T: 'static + ?Sized,
+{
+    /// Subscribes the given listener to this state, subscribing it again if the state is deleted and then exists again
+    ///
+    /// See [`OwnedState::subscribe_reattaching`]
+    pub fn subscribe_reattaching<F>(
+        self,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+        policy: ReattachPolicy,
+    ) -> io::Result<ReattachingSubscription<'a, F>>
+    where
+        F: FnMut(ReattachEvent<'_, T>) + Send + 'static,
+    {
+        self.raw.subscribe_reattaching(listener, last_seen_change_stamp, policy)
+    }
+}
+
+impl<T> RawState<T>
+where
+    T: 'static + ?Sized,
+{
+    /// Subscribes the given listener to this state, subscribing it again if the state is deleted and then exists again
+    fn subscribe_reattaching<'a, F>(
+        self,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+        policy: ReattachPolicy,
+    ) -> io::Result<ReattachingSubscription<'a, F>>
+    where
+        F: FnMut(ReattachEvent<'_, T>) + Send + 'static,
+    {
+        let shared = Arc::new(Shared {
+            listener: Mutex::new(listener),
+            stopped: Mutex::new(false),
+            condvar: Condvar::new(),
+        });
+
+        let subscription = self.subscribe(
+            ReattachListener {
+                shared: Arc::clone(&shared),
+                _marker: PhantomData,
+            },
+            last_seen_change_stamp,
+        )?;
+
+        let thread = thread::Builder::new().name("wnf-reattach".into()).spawn({
+            let shared = Arc::clone(&shared);
+            move || monitor(self, shared, subscription, policy)
+        })?;
+
+        Ok(ReattachingSubscription {
+            shared,
+            thread: Some(thread),
+            _marker: PhantomData,
+        })
+    }
+}
+
+/// Monitors the existence of the given state, reattaching the listener after the state has been deleted
+///
+/// This runs on the background thread of a [`ReattachingSubscription<'_, F>`](ReattachingSubscription) until it is
+/// stopped.
+fn monitor<F, T>(
+    state: RawState<T>,
+    shared: Arc<Shared<F>>,
+    subscription: Subscription<'static, ReattachListener<F, T>>,
+    policy: ReattachPolicy,
+) where
+    F: FnMut(ReattachEvent<'_, T>) + Send + 'static,
+    T: 'static + ?Sized,
+{
+    let mut subscription = Some(subscription);
+    let mut backoff = policy.initial_backoff;
+
+    loop {
+        let delay = if subscription.is_some() {
+            policy.check_interval
+        } else {
+            backoff
+        };
+
+        if shared.wait_stopped(delay) {
+            return;
+        }
+
+        match (subscription.is_some(), state.exists()) {
+            (true, Ok(false)) => {
+                subscription = None;
+                backoff = policy.initial_backoff;
+                shared.notify(ReattachEvent::Detached);
+            }
+            (false, Ok(true)) => {
+                let listener = ReattachListener {
+                    shared: Arc::clone(&shared),
+                    _marker: PhantomData,
+                };
+
+                match state.subscribe(listener, SeenChangeStamp::Current) {
+                    Ok(new_subscription) => {
+                        subscription = Some(new_subscription);
+                        shared.notify(ReattachEvent::Reattached);
+                    }
+                    Err(..) => backoff = (backoff * 2).min(policy.max_backoff),
+                }
+            }
+            (false, _) => backoff = (backoff * 2).min(policy.max_backoff),
+            (true, _) => {}
+        }
+    }
+}
+
+/// A subscription of a listener to updates of a state that is subscribed again after the state has been deleted
+///
+/// This is returned from [`OwnedState::subscribe_reattaching`] and [`BorrowedState::subscribe_reattaching`].
+///
+/// Dropping this stops the background thread and unsubscribes the listener. This blocks until the background thread
+/// has finished, which takes at most the time of a single existence check.
+#[must_use = "a `ReattachingSubscription` is unsubscribed immediately if it is not used"]
+pub struct ReattachingSubscription<'a, F> {
+    shared: Arc<Shared<F>>,
+    thread: Option<JoinHandle<()>>,
+    _marker: PhantomData<&'a ()>,
+}
+
+impl<F> Drop for ReattachingSubscription<'_, F> {
+    fn drop(&mut self) {
+        *self.shared.lock_stopped() = true;
+        self.shared.condvar.notify_all();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+// We cannot derive this because that would impose an unnecessary trait bound `F: Debug`
+impl<F> Debug for ReattachingSubscription<'_, F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReattachingSubscription")
+            .field("thread", &self.thread)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The state shared between a [`ReattachingSubscription<'_, F>`](ReattachingSubscription), its background thread and
+/// its underlying subscriptions
+struct Shared<F> {
+    listener: Mutex<F>,
+    stopped: Mutex<bool>,
+    condvar: Condvar,
+}
+
+impl<F> Shared<F> {
+    /// Calls the listener with the given event
+    fn notify<T>(&self, event: ReattachEvent<'_, T>)
+    where
+        F: FnMut(ReattachEvent<'_, T>),
+        T: ?Sized,
+    {
+        // A panic in the listener poisons the mutex, but the listener is still usable afterwards
+        let mut listener = self.listener.lock().unwrap_or_else(PoisonError::into_inner);
+        listener(event);
+    }
+
+    /// Waits for the given duration, returning early with `true` if the subscription has been stopped
+    fn wait_stopped(&self, duration: Duration) -> bool {
+        let (stopped, _) = self
+            .condvar
+            .wait_timeout_while(self.lock_stopped(), duration, |stopped| !*stopped)
+            .unwrap_or_else(PoisonError::into_inner);
+
+        *stopped
+    }
+
+    /// Locks the flag indicating whether the subscription has been stopped
+    fn lock_stopped(&self) -> MutexGuard<'_, bool> {
+        self.stopped.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// A state listener forwarding updates to the listener of a [`ReattachingSubscription<'_, F>`](ReattachingSubscription)
+struct ReattachListener<F, T>
+where
+    T: ?Sized,
+{
+    shared: Arc<Shared<F>>,
+    _marker: PhantomData<fn(T)>,
+}
+
+impl<F, T> StateListener<T> for ReattachListener<F, T>
+where
+    F: FnMut(ReattachEvent<'_, T>),
+    T: ?Sized,
+{
+    fn call(&mut self, accessor: DataAccessor<'_, T>) {
+        self.shared.notify(ReattachEvent::Updated(accessor));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use static_assertions::assert_impl_all;
+
+    use super::*;
+
+    #[test]
+    fn reattaching_subscription_is_send_and_sync_if_listener_is_send() {
+        type Listener = Box<dyn FnMut(ReattachEvent<'_, u32>) + Send>;
+
+        assert_impl_all!(ReattachingSubscription<'_, Listener>: Send, Sync);
+    }
+
+    #[test]
+    fn wait_stopped_returns_early_when_stopped() {
+        let shared = Shared {
+            listener: Mutex::new(()),
+            stopped: Mutex::new(true),
+            condvar: Condvar::new(),
+        };
+
+        assert!(shared.wait_stopped(Duration::from_secs(60)));
+    }
+}
@@ -0,0 +1,248 @@
+//! Enumerating and searching state names registered in the Windows registry
+
+use std::io;
+
+use windows::core::PWSTR;
+use windows::Win32::Foundation::{ERROR_FILE_NOT_FOUND, ERROR_MORE_DATA, ERROR_NO_MORE_ITEMS};
+#[cfg(feature = "unstable_ntapi")]
+use windows::Win32::System::Registry::RegEnumKeyExW;
+use windows::Win32::System::Registry::{RegCloseKey, RegEnumValueW, RegOpenKeyExW, HKEY, HKEY_LOCAL_MACHINE, KEY_READ};
+
+use crate::data::OpaqueData;
+use crate::state::BorrowedState;
+use crate::state_name::{StateLifetime, StateName};
+use crate::type_id::GUID;
+use crate::util::CWideString;
+
+/// Returns the names of all states with the given lifetime that are registered in the Windows registry
+///
+/// State names with the lifetimes [`StateLifetime::WellKnown`], [`StateLifetime::Permanent`] and
+/// [`StateLifetime::Persistent`] are persisted in the Windows registry under the keys documented at the respective
+/// [`StateLifetime`] variants. This enumerates the state names found under the key corresponding to the given
+/// lifetime.
+///
+/// Temporary state names are not persisted in the registry, so for [`StateLifetime::Temporary`] this always returns
+/// an empty list.
+///
+/// Note that a registered state name doesn't necessarily correspond to an existing state, e.g. because the state was
+/// registered by a component that is not running. Use [`BorrowedState::exists`](crate::state::BorrowedState::exists)
+/// to check for that.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use wnf::{StateLifetime, StateNameDescriptor};
+///
+/// for state_name in wnf::registered_state_names(StateLifetime::WellKnown)? {
+///     let descriptor = StateNameDescriptor::try_from(state_name)?;
+///
+///     if descriptor.matches_prefix("SHEL") {
+///         println!("{state_name}");
+///     }
+/// }
+/// # Ok(()) }
+/// ```
+///
+/// # Errors
+/// Returns an error if opening or enumerating the registry key fails. It is not an error if the registry key doesn't
+/// exist, in which case an empty list is returned.
+pub fn registered_state_names(lifetime: StateLifetime) -> io::Result<Vec<StateName>> {
+    let Some(sub_key) = registry_sub_key(lifetime) else {
+        return Ok(Vec::new());
+    };
+
+    match RegistryKey::open(HKEY_LOCAL_MACHINE, sub_key)? {
+        Some(key) => key.state_names(),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Returns the names of all permanent and persistent states registered in the Windows registry that have the given
+/// type id
+///
+/// This is useful for finding the states an application created in previous runs when it only knows the type id it
+/// uses for its states. The state names are enumerated through [`registered_state_names`] (temporary states cannot be
+/// enumerated and don't outlive the process that created them anyway).
+///
+/// WNF provides no way to read the type id of a state directly, so it is probed instead: A state is considered to have
+/// the given type id if querying it with the given type id succeeds, but querying it with a different type id fails.
+/// As a consequence, states that cannot be queried by the current user (and states that don't exist anymore) are never
+/// returned, even if they have the given type id.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use wnf::{BorrowedState, GUID};
+///
+/// let type_id = GUID::try_from("b75fa6ba-77fd-4790-b825-1715ffefbac8")?;
+///
+/// for state_name in wnf::find_states_by_type_id(type_id)? {
+///     let state = BorrowedState::<u32>::from_state_name_and_type_id(state_name, type_id);
+///     println!("{state_name}: {}", state.get()?);
+/// }
+/// # Ok(()) }
+/// ```
+///
+/// # Errors
+/// Returns an error if enumerating the registered state names fails
+pub fn find_states_by_type_id(type_id: impl Into<GUID>) -> io::Result<Vec<StateName>> {
+    let type_id = type_id.into();
+    let mut state_names = registered_state_names(StateLifetime::Permanent)?;
+    state_names.extend(registered_state_names(StateLifetime::Persistent)?);
+    state_names.retain(|&state_name| has_type_id(state_name, type_id));
+    Ok(state_names)
+}
+
+/// Returns whether the state with the given name has the given type id, as far as can be determined by probing
+fn has_type_id(state_name: StateName, type_id: GUID) -> bool {
+    let can_query_with_type_id = |type_id| {
+        BorrowedState::<OpaqueData>::from_state_name_and_type_id(state_name, type_id)
+            .query()
+            .is_ok()
+    };
+
+    // A GUID that is guaranteed to be different from `type_id`
+    let other_type_id = GUID::from_u128(type_id.to_u128() ^ 1);
+
+    can_query_with_type_id(type_id) && !can_query_with_type_id(other_type_id)
+}
+
+/// Returns the subkey of `HKEY_LOCAL_MACHINE` under which state names with the given lifetime are persisted, if any
+pub(crate) const fn registry_sub_key(lifetime: StateLifetime) -> Option<&'static str> {
+    match lifetime {
+        StateLifetime::WellKnown => Some(r"SYSTEM\CurrentControlSet\Control\Notifications"),
+        StateLifetime::Permanent => Some(r"SOFTWARE\Microsoft\Windows NT\CurrentVersion\Notifications"),
+        StateLifetime::Persistent => Some(r"SOFTWARE\Microsoft\Windows NT\CurrentVersion\VolatileNotifications"),
+        StateLifetime::Temporary => None,
+    }
+}
+
+/// An open key in the Windows registry, which is closed on drop
+#[derive(Debug)]
+pub(crate) struct RegistryKey(HKEY);
+
+impl RegistryKey {
+    /// Opens the given key below the given predefined key (e.g. `HKEY_LOCAL_MACHINE`) for reading, returning [`None`]
+    /// if it doesn't exist
+    pub(crate) fn open(root: HKEY, sub_key: &str) -> io::Result<Option<Self>> {
+        let sub_key = CWideString::new(sub_key);
+        let mut key = HKEY::default();
+
+        // SAFETY:
+        // - The first argument is a valid predefined key because all callers pass one
+        // - The pointer in the second argument points to a valid null-terminated wide string because it comes from a
+        //   live `CWideString`
+        // - The pointer in the fifth argument is valid for writes of `HKEY` because it comes from a live mutable
+        //   reference
+        let result = unsafe { RegOpenKeyExW(root, sub_key.as_pcwstr(), None, KEY_READ, &mut key) };
+
+        if result == ERROR_FILE_NOT_FOUND {
+            return Ok(None);
+        }
+
+        result.ok()?;
+        Ok(Some(Self(key)))
+    }
+
+    /// Returns the state names found among the value names of this key
+    ///
+    /// Value names that are not the hexadecimal representation of the opaque value of a state name are skipped.
+    fn state_names(&self) -> io::Result<Vec<StateName>> {
+        // The hexadecimal representation of a `u64` has at most 16 digits, plus one for the terminating NUL character
+        const BUFFER_LEN: usize = 17;
+
+        let mut state_names = Vec::new();
+        let mut buffer = [0u16; BUFFER_LEN];
+
+        for index in 0.. {
+            let mut len = BUFFER_LEN as u32;
+
+            // SAFETY:
+            // - The first argument is a valid open key because it comes from a live `RegistryKey`
+            // - The pointer in the third argument is valid for writes of `len` `u16` values because it comes from a
+            //   live mutable reference to an array of that length
+            // - The pointer in the fourth argument is valid for reads and writes of `u32` because it comes from a live
+            //   mutable reference
+            let result = unsafe {
+                RegEnumValueW(
+                    self.0,
+                    index,
+                    Some(PWSTR::from_raw(buffer.as_mut_ptr())),
+                    &mut len,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+            };
+
+            if result == ERROR_NO_MORE_ITEMS {
+                break;
+            }
+
+            // A value name that doesn't fit into the buffer is not a state name
+            if result == ERROR_MORE_DATA {
+                continue;
+            }
+
+            result.ok()?;
+
+            let value_name = String::from_utf16_lossy(&buffer[..len as usize]);
+            if let Ok(state_name) = value_name.parse() {
+                state_names.push(state_name);
+            }
+        }
+
+        Ok(state_names)
+    }
+
+    /// Returns the names of all subkeys of this key
+    #[cfg(feature = "unstable_ntapi")]
+    pub(crate) fn sub_key_names(&self) -> io::Result<Vec<String>> {
+        // The name of a key has at most 255 characters, plus one for the terminating NUL character
+        const BUFFER_LEN: usize = 256;
+
+        let mut sub_key_names = Vec::new();
+        let mut buffer = [0u16; BUFFER_LEN];
+
+        for index in 0.. {
+            let mut len = BUFFER_LEN as u32;
+
+            // SAFETY:
+            // - The first argument is a valid open key because it comes from a live `RegistryKey`
+            // - The pointer in the third argument is valid for writes of `len` `u16` values because it comes from a
+            //   live mutable reference to an array of that length
+            // - The pointer in the fourth argument is valid for reads and writes of `u32` because it comes from a live
+            //   mutable reference
+            let result = unsafe {
+                RegEnumKeyExW(
+                    self.0,
+                    index,
+                    Some(PWSTR::from_raw(buffer.as_mut_ptr())),
+                    &mut len,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+            };
+
+            if result == ERROR_NO_MORE_ITEMS {
+                break;
+            }
+
+            result.ok()?;
+            sub_key_names.push(String::from_utf16_lossy(&buffer[..len as usize]));
+        }
+
+        Ok(sub_key_names)
+    }
+}
+
+impl Drop for RegistryKey {
+    fn drop(&mut self) {
+        // SAFETY:
+        // The argument is a valid open key because it comes from a live `RegistryKey`, which is not used afterwards
+        let _ = unsafe { RegCloseKey(self.0) };
+    }
+}
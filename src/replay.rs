@@ -0,0 +1,554 @@
+//! Recording state updates and replaying them into state listeners
+
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Formatter};
+use std::io::{self, ErrorKind, Read, Write};
+use std::num::NonZeroU32;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::data::ChangeStamp;
+use crate::state_name::StateName;
+use crate::subscribe::{DataAccessor, ScopedData, StateListener, UpdateKind};
+
+/// The magic bytes at the start of a recording
+const MAGIC: [u8; 4] = *b"WNFR";
+
+/// The version of the recording format
+const VERSION: u8 = 1;
+
+/// The maximum time elapsed between the start of a recording and an update, in microseconds
+///
+/// This corresponds to 100 years. Larger values can only stem from corrupt recordings, and replaying them at the
+/// original speed would block practically forever.
+const MAX_ELAPSED_MICROS: u64 = 100 * 365 * 24 * 60 * 60 * 1_000_000;
+
+/// A state update that has been recorded by a [`Recorder<W>`](Recorder)
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct RecordedUpdate {
+    state_name: StateName,
+    change_stamp: ChangeStamp,
+    elapsed: Duration,
+    data: Box<[u8]>,
+}
+
+impl RecordedUpdate {
+    /// Creates a new [`RecordedUpdate`] from its parts
+    ///
+    /// This is useful for constructing or modifying recordings by hand, e.g. to reorder updates.
+    pub fn new(
+        state_name: StateName,
+        change_stamp: ChangeStamp,
+        elapsed: Duration,
+        data: impl Into<Box<[u8]>>,
+    ) -> Self {
+        Self {
+            state_name,
+            change_stamp,
+            elapsed,
+            data: data.into(),
+        }
+    }
+
+    /// Returns the name of the state that was updated
+    pub const fn state_name(&self) -> StateName {
+        self.state_name
+    }
+
+    /// Returns the change stamp of the update
+    pub const fn change_stamp(&self) -> ChangeStamp {
+        self.change_stamp
+    }
+
+    /// Returns the time elapsed between the start of the recording and the update
+    pub const fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Returns the raw data of the state after the update
+    pub const fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Writes this update to the given writer in the recording format
+    fn write_to<W>(&self, writer: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let size: u32 = self
+            .data
+            .len()
+            .try_into()
+            .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "recorded data are too large"))?;
+
+        let elapsed_micros: u64 = self.elapsed.as_micros().try_into().unwrap_or(u64::MAX);
+
+        writer.write_all(&self.state_name.opaque_value().to_le_bytes())?;
+        writer.write_all(&self.change_stamp.value().to_le_bytes())?;
+        writer.write_all(&elapsed_micros.to_le_bytes())?;
+        writer.write_all(&size.to_le_bytes())?;
+        writer.write_all(&self.data)
+    }
+
+    /// Reads an update in the recording format from the given reader, returning [`None`] at the end of the recording
+    fn read_from<R>(reader: &mut R) -> io::Result<Option<Self>>
+    where
+        R: Read,
+    {
+        let mut state_name = [0; 8];
+
+        // Only the end of the reader before the first byte of an update marks the end of the recording
+        match reader.read_exact(&mut state_name[..1]) {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err),
+        }
+
+        reader.read_exact(&mut state_name[1..])?;
+
+        let mut change_stamp = [0; 4];
+        let mut elapsed_micros = [0; 8];
+        let mut size = [0; 4];
+        reader.read_exact(&mut change_stamp)?;
+        reader.read_exact(&mut elapsed_micros)?;
+        reader.read_exact(&mut size)?;
+
+        let elapsed_micros = u64::from_le_bytes(elapsed_micros);
+
+        if elapsed_micros > MAX_ELAPSED_MICROS {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "recorded update has an implausible elapsed time",
+            ));
+        }
+
+        // We don't allocate a buffer of the recorded size upfront because the size may be corrupt
+        let size = u32::from_le_bytes(size);
+        let mut data = Vec::new();
+        reader.by_ref().take(size.into()).read_to_end(&mut data)?;
+
+        if data.len() != size as usize {
+            return Err(io::Error::new(ErrorKind::UnexpectedEof, "recorded update is truncated"));
+        }
+
+        Ok(Some(Self {
+            state_name: StateName::from_opaque_value(u64::from_le_bytes(state_name)),
+            change_stamp: ChangeStamp::new(u32::from_le_bytes(change_stamp)),
+            elapsed: Duration::from_micros(elapsed_micros),
+            data: data.into_boxed_slice(),
+        }))
+    }
+}
+
+/// A state listener recording the updates it is notified about into a writer
+///
+/// In order to record the updates of a state, subscribe a [`Recorder<W>`](Recorder) to it. Each update is written to
+/// the writer together with the name of the state, the change stamp and the time elapsed since the recorder was
+/// created. In order to record the updates of multiple states into the same writer (preserving their relative order),
+/// create additional recorders through [`Recorder::for_state`].
+///
+/// The recording can be read through [`read_recording`] and replayed into a state listener through [`replay`], making
+/// it possible to reproduce issues that depend on a specific sequence of updates.
+///
+/// Since the recorder is moved into the subscription, it is cheaply clonable: Keep a clone in order to call
+/// [`Recorder::finish`] after unsubscribing. If writing an update fails, recording stops and the error is returned from
+/// [`Recorder::finish`].
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use wnf::{read_recording, OwnedState, Recorder, SeenChangeStamp};
+///
+/// let state = OwnedState::<u32>::create_temporary()?;
+/// let recorder = Recorder::new(state.state_name(), Vec::new())?;
+///
+/// let subscription = state.subscribe(recorder.clone(), SeenChangeStamp::Current)?;
+/// state.set(&42)?;
+/// # std::thread::sleep(std::time::Duration::from_millis(100));
+/// subscription.unsubscribe()?;
+///
+/// let recording = recorder.finish()?;
+/// let updates = read_recording(recording.as_slice())?;
+/// assert_eq!(updates[0].data(), 42u32.to_ne_bytes());
+/// # Ok(()) }
+/// ```
+pub struct Recorder<W> {
+    shared: Arc<Mutex<RecorderState<W>>>,
+    state_name: StateName,
+    started_at: Instant,
+}
+
+/// The state shared between clones of a [`Recorder<W>`](Recorder)
+struct RecorderState<W> {
+    writer: Option<W>,
+    error: Option<io::Error>,
+}
+
+impl<W> Recorder<W>
+where
+    W: Write,
+{
+    /// Creates a new [`Recorder<W>`](Recorder) recording updates of the state with the given name into the given writer
+    ///
+    /// # Errors
+    /// Returns an error if writing the header of the recording fails
+    pub fn new(state_name: StateName, mut writer: W) -> io::Result<Self> {
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&[VERSION])?;
+
+        Ok(Self {
+            shared: Arc::new(Mutex::new(RecorderState {
+                writer: Some(writer),
+                error: None,
+            })),
+            state_name,
+            started_at: Instant::now(),
+        })
+    }
+
+    /// Returns a [`Recorder<W>`](Recorder) recording updates of the state with the given name into the same writer as
+    /// this recorder
+    pub fn for_state(&self, state_name: StateName) -> Self {
+        Self {
+            shared: Arc::clone(&self.shared),
+            state_name,
+            started_at: self.started_at,
+        }
+    }
+
+    /// Finishes the recording, returning the writer
+    ///
+    /// Updates that this recorder or any of its clones are notified about afterwards are not recorded.
+    ///
+    /// # Errors
+    /// Returns an error if writing an update or flushing the writer has failed or if the recording has already been
+    /// finished
+    pub fn finish(&self) -> io::Result<W> {
+        let mut shared = self.shared.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if let Some(err) = shared.error.take() {
+            shared.writer = None;
+            return Err(err);
+        }
+
+        let mut writer = shared
+            .writer
+            .take()
+            .ok_or_else(|| io::Error::new(ErrorKind::Other, "recording has already been finished"))?;
+
+        writer.flush()?;
+        Ok(writer)
+    }
+
+    /// Records the given update
+    fn record(&self, change_stamp: ChangeStamp, data: io::Result<Box<[u8]>>) {
+        let elapsed = self.started_at.elapsed();
+        let mut shared = self.shared.lock().unwrap_or_else(PoisonError::into_inner);
+        let RecorderState { writer, error } = &mut *shared;
+
+        if let Some(writer) = writer.as_mut().filter(|_| error.is_none()) {
+            let result = data.and_then(|data| {
+                RecordedUpdate {
+                    state_name: self.state_name,
+                    change_stamp,
+                    elapsed,
+                    data,
+                }
+                .write_to(writer)
+            });
+
+            if let Err(err) = result {
+                *error = Some(err);
+            }
+        }
+    }
+}
+
+impl<W, T> StateListener<T> for Recorder<W>
+where
+    W: Write,
+    T: ?Sized,
+{
+    fn call(&mut self, accessor: DataAccessor<'_, T>) {
+        self.record(accessor.change_stamp(), accessor.cast::<[u8]>().get_boxed());
+    }
+}
+
+// We cannot derive this because that would impose an unnecessary trait bound `W: Clone`
+impl<W> Clone for Recorder<W> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: Arc::clone(&self.shared),
+            state_name: self.state_name,
+            started_at: self.started_at,
+        }
+    }
+}
+
+// We cannot derive this because that would impose an unnecessary trait bound `W: Debug`
+impl<W> Debug for Recorder<W> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Recorder")
+            .field("state_name", &self.state_name)
+            .field("started_at", &self.started_at)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Reads a recording written by a [`Recorder<W>`](Recorder) from the given reader
+///
+/// # Errors
+/// Returns an error if reading fails or if the data read are not a valid recording, in which case
+/// [`io::Error::kind`] returns [`ErrorKind::InvalidData`] or [`ErrorKind::UnexpectedEof`]. In particular, a recording
+/// is invalid if the elapsed times of its updates are implausibly large or not in ascending order.
+pub fn read_recording<R>(mut reader: R) -> io::Result<Vec<RecordedUpdate>>
+where
+    R: Read,
+{
+    let mut header = [0; MAGIC.len() + 1];
+    reader.read_exact(&mut header)?;
+
+    if header[..MAGIC.len()] != MAGIC || header[MAGIC.len()] != VERSION {
+        return Err(io::Error::new(ErrorKind::InvalidData, "data are not a valid recording"));
+    }
+
+    let mut updates: Vec<RecordedUpdate> = Vec::new();
+
+    while let Some(update) = RecordedUpdate::read_from(&mut reader)? {
+        if updates.last().is_some_and(|last| update.elapsed < last.elapsed) {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "recorded updates are not in ascending order of elapsed time",
+            ));
+        }
+
+        updates.push(update);
+    }
+
+    Ok(updates)
+}
+
+/// The speed at which recorded updates are replayed
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ReplaySpeed {
+    /// Replay updates with the same delays between them as when they were recorded
+    Original,
+
+    /// Replay updates with the delays between them divided by the given factor
+    Accelerated(NonZeroU32),
+
+    /// Replay updates without any delays between them
+    Immediate,
+}
+
+impl ReplaySpeed {
+    /// Returns the delay to wait before replaying an update that was recorded after the given delay
+    fn scale(self, delay: Duration) -> Duration {
+        match self {
+            Self::Original => delay,
+            Self::Accelerated(factor) => delay / factor.get(),
+            Self::Immediate => Duration::ZERO,
+        }
+    }
+}
+
+/// Replays the given recorded updates into the given state listener
+///
+/// The listener is called on the current thread once for each update, in the given order and at the given speed.
+/// It receives a [`DataAccessor<'_, T>`](DataAccessor) for the recorded data, whose
+/// [`update_kind`](DataAccessor::update_kind) is derived from the change stamps of the previous updates of the same
+/// state, so the listener behaves as if it were notified about the updates by the WNF API. Use
+/// [`Iterator::filter`] on the updates in order to replay only the updates of specific states.
+///
+/// This returns when all updates have been replayed.
+pub fn replay<'a, F, T>(updates: impl IntoIterator<Item = &'a RecordedUpdate>, speed: ReplaySpeed, listener: &mut F)
+where
+    F: StateListener<T> + ?Sized,
+    T: ?Sized,
+{
+    let started_at = Instant::now();
+    let mut last_seen_change_stamps = HashMap::new();
+
+    for update in updates {
+        // The elapsed time of an update constructed by hand may be too large to be added to an instant
+        if let Some(deadline) = started_at.checked_add(speed.scale(update.elapsed)) {
+            thread::sleep(deadline.saturating_duration_since(Instant::now()));
+        }
+
+        let update_kind = match last_seen_change_stamps.insert(update.state_name, update.change_stamp) {
+            Some(last_seen) if update.change_stamp.is_newer_than(last_seen) => {
+                UpdateKind::from_missed(update.change_stamp.distance_from(last_seen) - 1)
+            }
+            _ => UpdateKind::Sequential,
+        };
+
+        // SAFETY:
+        // `update.data` is a boxed slice that is live and initialized for as long as `data` is live because `update`
+        // is borrowed for the whole iteration
+        let data = unsafe { ScopedData::new(update.data.as_ptr().cast(), update.data.len(), update.change_stamp) };
+
+        listener.call(data.accessor_with_update_kind(update_kind));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recorded_update_round_trip() {
+        let update = RecordedUpdate::new(
+            StateName::from_opaque_value(0x0D83_063E_A3BE_5075),
+            ChangeStamp::new(42),
+            Duration::from_millis(1500),
+            vec![1, 2, 3],
+        );
+
+        let mut buffer = Vec::new();
+        update.write_to(&mut buffer).unwrap();
+
+        let mut reader = buffer.as_slice();
+        assert_eq!(RecordedUpdate::read_from(&mut reader).unwrap(), Some(update));
+        assert_eq!(RecordedUpdate::read_from(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn read_recording_truncated_update() {
+        let mut buffer = Vec::from(MAGIC);
+        buffer.push(VERSION);
+        buffer.extend_from_slice(&[0; 3]);
+
+        let err = read_recording(buffer.as_slice()).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn read_recording_corrupt_size() {
+        let update = RecordedUpdate::new(
+            StateName::from_opaque_value(0x0D83_063E_A3BE_5075),
+            ChangeStamp::new(1),
+            Duration::ZERO,
+            vec![1, 2, 3],
+        );
+
+        let mut buffer = Vec::from(MAGIC);
+        buffer.push(VERSION);
+        update.write_to(&mut buffer).unwrap();
+
+        // Overwrite the size with `u32::MAX`, which must not cause an allocation of that size
+        let size_offset = buffer.len() - 3 - 4;
+        buffer[size_offset..size_offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        let err = read_recording(buffer.as_slice()).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn read_recording_implausible_elapsed() {
+        let update = RecordedUpdate::new(
+            StateName::from_opaque_value(0x0D83_063E_A3BE_5075),
+            ChangeStamp::new(1),
+            Duration::MAX,
+            vec![],
+        );
+
+        let mut buffer = Vec::from(MAGIC);
+        buffer.push(VERSION);
+        update.write_to(&mut buffer).unwrap();
+
+        let err = read_recording(buffer.as_slice()).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn read_recording_non_monotonic_elapsed() {
+        let state_name = StateName::from_opaque_value(0x0D83_063E_A3BE_5075);
+
+        let mut buffer = Vec::from(MAGIC);
+        buffer.push(VERSION);
+
+        for elapsed in [2, 1] {
+            RecordedUpdate::new(
+                state_name,
+                ChangeStamp::new(elapsed),
+                Duration::from_secs(elapsed.into()),
+                vec![],
+            )
+            .write_to(&mut buffer)
+            .unwrap();
+        }
+
+        let err = read_recording(buffer.as_slice()).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn replay_with_huge_elapsed_does_not_panic() {
+        let updates = [RecordedUpdate::new(
+            StateName::from_opaque_value(0x0D83_063E_A3BE_5075),
+            ChangeStamp::new(1),
+            Duration::MAX,
+            vec![42],
+        )];
+
+        let mut seen = Vec::new();
+        replay(&updates, ReplaySpeed::Original, &mut |accessor: DataAccessor<
+            '_,
+            u8,
+        >| {
+            seen.push(accessor.get().unwrap())
+        });
+
+        assert_eq!(seen, [42]);
+    }
+
+    #[test]
+    fn read_recording_invalid_header() {
+        let err = read_recording(&b"WNFZ\x01"[..]).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn replay_derives_update_kind() {
+        let state_name = StateName::from_opaque_value(0x0D83_063E_A3BE_5075);
+        let updates: Vec<_> = [1, 2, 5]
+            .into_iter()
+            .map(|value| RecordedUpdate::new(state_name, ChangeStamp::new(value), Duration::ZERO, vec![value as u8]))
+            .collect();
+
+        let mut seen = Vec::new();
+        replay(&updates, ReplaySpeed::Immediate, &mut |accessor: DataAccessor<
+            '_,
+            u8,
+        >| {
+            seen.push((accessor.get().unwrap(), accessor.update_kind()))
+        });
+
+        assert_eq!(
+            seen,
+            [
+                (1, UpdateKind::Sequential),
+                (2, UpdateKind::Sequential),
+                (5, UpdateKind::Coalesced { missed: 2 })
+            ]
+        );
+    }
+
+    #[test]
+    fn replay_speed_scale() {
+        let delay = Duration::from_secs(10);
+
+        assert_eq!(ReplaySpeed::Original.scale(delay), delay);
+        assert_eq!(
+            ReplaySpeed::Accelerated(NonZeroU32::new(4).unwrap()).scale(delay),
+            Duration::from_millis(2500)
+        );
+        assert_eq!(ReplaySpeed::Immediate.scale(delay), Duration::ZERO);
+    }
+}
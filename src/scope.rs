@@ -0,0 +1,335 @@
+//! Scoped subscriptions whose listeners can borrow data
+//!
+//! Besides the [`scope`] function and the [`Scope<'scope, 'env>`](Scope) type, this module adds inherent impls to
+//! [`OwnedState<T>`] and [`BorrowedState<'_, T>`](BorrowedState).
+
+use std::error::Error;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::io;
+use std::marker::PhantomData;
+use std::mem::{self, ManuallyDrop};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::state::{AsState, BorrowedState, OwnedState};
+use crate::subscribe::{SeenChangeStamp, StateListener, Subscription};
+
+/// Creates a scope for subscribing listeners that can borrow data from outside of the scope
+///
+/// This is the subscription counterpart of [`std::thread::scope`]: The given closure is passed a
+/// [`Scope<'scope, 'env>`](Scope) through which you can subscribe listeners to states. Unlike listeners subscribed
+/// through [`OwnedState::subscribe`](crate::state::OwnedState::subscribe), these listeners don't need to be `'static`
+/// but can borrow any data that outlives the scope, because all subscriptions created in the scope are guaranteed to be
+/// unsubscribed before this function returns. This is the case even when the closure panics.
+///
+/// Subscribing within the scope is done through [`Scope::subscribe`] or, equivalently, through the
+/// [`OwnedState::subscribe_scoped`] and [`BorrowedState::subscribe_scoped`] methods.
+///
+/// After the closure has returned, all subscriptions are unsubscribed. The listeners are not called anymore afterwards,
+/// even if unsubscribing fails for some of them.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use std::sync::atomic::{AtomicU32, Ordering};
+///
+/// use wnf::{DataAccessor, OwnedState, SeenChangeStamp};
+///
+/// let state = OwnedState::<u32>::create_temporary()?;
+/// state.set(&0)?;
+///
+/// let updates = AtomicU32::new(0);
+///
+/// wnf::scope(|s| {
+///     s.subscribe(
+///         &state,
+///         |_: DataAccessor<_>| {
+///             updates.fetch_add(1, Ordering::Relaxed);
+///         },
+///         SeenChangeStamp::Current,
+///     )?;
+///
+///     state.set(&1)
+/// })??;
+///
+/// // All subscriptions have been unsubscribed, so `updates` is not borrowed anymore
+/// println!("Number of updates: {}", updates.into_inner());
+/// # Ok(()) }
+/// ```
+///
+/// # Errors
+/// Returns an error if unsubscribing fails for any of the subscriptions created in the scope. The error contains the
+/// individual errors for all subscriptions that could not be unsubscribed.
+///
+/// # Panics
+/// Propagates a panic of the given closure after all subscriptions created in the scope have been unsubscribed
+pub fn scope<'env, F, R>(f: F) -> Result<R, ScopeError>
+where
+    F: for<'scope> FnOnce(&'scope Scope<'scope, 'env>) -> R,
+{
+    let scope = Scope {
+        subscriptions: ManuallyDrop::new(Mutex::new(Vec::new())),
+        _scope: PhantomData,
+        _env: PhantomData,
+    };
+
+    // The closure is only called once and the scope is only used for unsubscribing afterwards, so observing a broken
+    // invariant after a panic is not an issue
+    let result = panic::catch_unwind(AssertUnwindSafe(|| f(&scope)));
+    let unsubscribe_result = scope.unsubscribe_all();
+
+    match result {
+        Ok(value) => unsubscribe_result.map(|()| value),
+        Err(payload) => panic::resume_unwind(payload),
+    }
+}
+
+/// A scope for subscribing listeners that can borrow data from outside of the scope
+///
+/// This is created through the [`scope`] function. See there for details.
+pub struct Scope<'scope, 'env: 'scope> {
+    // This is wrapped in `ManuallyDrop` so that dropping a `Scope<'scope, 'env>` does not require `'scope` to be live,
+    // which would make it impossible to borrow the scope for `'scope` in the `scope` function. Nothing is leaked
+    // because the `scope` function always empties the vector, and a `Mutex` does not allocate on our MSRV.
+    subscriptions: ManuallyDrop<Mutex<Vec<Box<dyn ScopedSubscription + 'scope>>>>,
+    _scope: PhantomData<&'scope mut &'scope ()>,
+    _env: PhantomData<&'env mut &'env ()>,
+}
+
+impl<'scope> Scope<'scope, '_> {
+    /// Subscribes the given state listener to the given state for the duration of this scope
+    ///
+    /// This is the same as [`OwnedState::subscribe`](crate::state::OwnedState::subscribe), except that the listener
+    /// only needs to live for the duration of the scope and is unsubscribed automatically at the end of the scope.
+    ///
+    /// # Errors
+    /// Returns an error if subscribing fails
+    pub fn subscribe<S, F>(
+        &'scope self,
+        state: &'scope S,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+    ) -> io::Result<()>
+    where
+        S: AsState,
+        F: StateListener<S::Data> + Send + 'scope,
+    {
+        self.subscribe_borrowed(state.as_state(), listener, last_seen_change_stamp)
+    }
+
+    /// Subscribes the given state listener to the given borrowed state for the duration of this scope
+    fn subscribe_borrowed<T, F>(
+        &'scope self,
+        state: BorrowedState<'scope, T>,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+    ) -> io::Result<()>
+    where
+        T: ?Sized,
+        F: StateListener<T> + Send + 'scope,
+    {
+        // SAFETY:
+        // The subscription is unsubscribed at the end of the scope by the `scope` function, which outlives `'scope` and
+        // hence any lifetime contained in `F`. It cannot be forgotten because it is owned by `self`.
+        let subscription = unsafe { state.raw.subscribe_non_static(listener, last_seen_change_stamp)? };
+
+        self.lock().push(Box::new(subscription));
+        Ok(())
+    }
+
+    /// Unsubscribes all subscriptions created in this scope, collecting the errors
+    fn unsubscribe_all(&self) -> Result<(), ScopeError> {
+        let mut errors = Vec::new();
+
+        // We don't hold the lock while unsubscribing because unsubscribing waits for running listeners, which may
+        // subscribe further listeners in this scope. Those are unsubscribed in the next iteration.
+        loop {
+            let subscriptions = mem::take(&mut *self.lock());
+
+            if subscriptions.is_empty() {
+                break;
+            }
+
+            errors.extend(
+                subscriptions
+                    .into_iter()
+                    .filter_map(|subscription| subscription.unsubscribe().err()),
+            );
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ScopeError { errors })
+        }
+    }
+
+    /// Locks the subscriptions created in this scope
+    fn lock(&self) -> MutexGuard<'_, Vec<Box<dyn ScopedSubscription + 'scope>>> {
+        // We can access the subscriptions even when the mutex is poisoned as we're only pushing and draining them
+        self.subscriptions.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T> OwnedState<T>
+where
+    T: ?Sized,
+{
+    /// Subscribes the given state listener to this state for the duration of the given scope
+    ///
+    /// This is the same as [`subscribe`](OwnedState::subscribe), except that the listener only needs to live for the
+    /// duration of the given scope rather than being `'static`. This means it can borrow data from outside of the
+    /// scope, such as local variables, instead of having to own everything it uses, e.g. through an
+    /// [`Arc<Mutex<_>>`](std::sync::Arc). The listener is unsubscribed automatically at the end of the scope, see
+    /// [`scope`] for details.
+    ///
+    /// This is equivalent to calling [`Scope::subscribe`] with this state.
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use std::sync::Mutex;
+    ///
+    /// use wnf::{DataAccessor, OwnedState, SeenChangeStamp};
+    ///
+    /// let state = OwnedState::<u32>::create_temporary()?;
+    /// state.set(&0)?;
+    ///
+    /// let mut values = Vec::new();
+    ///
+    /// {
+    ///     let values = Mutex::new(&mut values);
+    ///
+    ///     wnf::scope(|s| {
+    ///         state.subscribe_scoped(
+    ///             s,
+    ///             |accessor: DataAccessor<_>| {
+    ///                 if let Ok(value) = accessor.get() {
+    ///                     values.lock().unwrap().push(value);
+    ///                 }
+    ///             },
+    ///             SeenChangeStamp::Current,
+    ///         )?;
+    ///
+    ///         state.set(&1)
+    ///     })??;
+    /// }
+    ///
+    /// println!("Values: {values:?}");
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an error if subscribing fails
+    pub fn subscribe_scoped<'scope, F>(
+        &'scope self,
+        scope: &'scope Scope<'scope, '_>,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+    ) -> io::Result<()>
+    where
+        F: StateListener<T> + Send + 'scope,
+    {
+        scope.subscribe_borrowed(self.as_state(), listener, last_seen_change_stamp)
+    }
+}
+
+impl<'a, T> BorrowedState<'a, T>
+where
+    T: ?Sized,
+{
+    /// Subscribes the given state listener to this state for the duration of the given scope
+    ///
+    /// See [`OwnedState::subscribe_scoped`]
+    pub fn subscribe_scoped<'scope, F>(
+        self,
+        scope: &'scope Scope<'scope, '_>,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+    ) -> io::Result<()>
+    where
+        'a: 'scope,
+        F: StateListener<T> + Send + 'scope,
+    {
+        scope.subscribe_borrowed(self, listener, last_seen_change_stamp)
+    }
+}
+
+impl Debug for Scope<'_, '_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scope")
+            .field("num_subscriptions", &self.lock().len())
+            .finish()
+    }
+}
+
+/// An error unsubscribing the subscriptions created in a [`Scope<'_, '_>`](Scope)
+///
+/// This contains one error for every subscription that could not be unsubscribed, in the order in which the
+/// subscriptions were created. It is never empty.
+#[derive(Debug)]
+pub struct ScopeError {
+    errors: Vec<io::Error>,
+}
+
+impl ScopeError {
+    /// Returns the errors that occurred while unsubscribing
+    pub fn errors(&self) -> &[io::Error] {
+        &self.errors
+    }
+
+    /// Consumes this error, returning the errors that occurred while unsubscribing
+    pub fn into_errors(self) -> Vec<io::Error> {
+        self.errors
+    }
+}
+
+impl Display for ScopeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "failed to unsubscribe {} subscription(s) created in a scope: {}",
+            self.errors.len(),
+            self.errors[0]
+        )
+    }
+}
+
+impl Error for ScopeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.errors[0])
+    }
+}
+
+impl From<ScopeError> for io::Error {
+    fn from(err: ScopeError) -> Self {
+        io::Error::new(err.errors[0].kind(), err)
+    }
+}
+
+/// A type-erased subscription created in a [`Scope<'_, '_>`](Scope)
+trait ScopedSubscription: Send {
+    /// Unsubscribes this subscription
+    fn unsubscribe(self: Box<Self>) -> io::Result<()>;
+}
+
+impl<F> ScopedSubscription for Subscription<'_, F>
+where
+    F: Send,
+{
+    fn unsubscribe(self: Box<Self>) -> io::Result<()> {
+        Subscription::unsubscribe(*self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use static_assertions::assert_impl_all;
+
+    use super::*;
+
+    #[test]
+    fn scope_is_send_and_sync() {
+        assert_impl_all!(Scope<'_, '_>: Send, Sync);
+    }
+}
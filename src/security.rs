@@ -3,14 +3,18 @@
 use std::borrow::Borrow;
 use std::ffi::c_void;
 use std::fmt::{self, Debug, Formatter};
-use std::io;
 use std::ops::Deref;
 use std::ptr::NonNull;
 use std::str::FromStr;
+use std::{io, mem};
 
-use windows::Win32::Foundation::{LocalFree, HLOCAL};
-use windows::Win32::Security::Authorization::{ConvertStringSecurityDescriptorToSecurityDescriptorW, SDDL_REVISION};
-use windows::Win32::Security::PSECURITY_DESCRIPTOR;
+use windows::core::PWSTR;
+use windows::Win32::Foundation::{CloseHandle, LocalFree, HANDLE, HLOCAL};
+use windows::Win32::Security::Authorization::{
+    ConvertSidToStringSidW, ConvertStringSecurityDescriptorToSecurityDescriptorW, SDDL_REVISION,
+};
+use windows::Win32::Security::{GetTokenInformation, TokenUser, PSECURITY_DESCRIPTOR, TOKEN_QUERY, TOKEN_USER};
+use windows::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
 
 use crate::util::CWideString;
 
@@ -57,8 +61,9 @@ impl Debug for SecurityDescriptor {
 /// Unlike [`Box<SecurityDescriptor>`], this allocates memory on the
 /// [local heap](https://learn.microsoft.com/en-us/windows/win32/memory/global-and-local-functions).
 ///
-/// There are two ways to create a [`BoxedSecurityDescriptor`]:
+/// There are three ways to create a [`BoxedSecurityDescriptor`]:
 /// - via the [`BoxedSecurityDescriptor::create_everyone_generic_all`] method
+/// - via the [`BoxedSecurityDescriptor::create_current_user_generic_all`] method
 /// - via the [`FromStr`] implementation of [`BoxedSecurityDescriptor`]
 #[derive(Debug)]
 pub struct BoxedSecurityDescriptor {
@@ -91,6 +96,22 @@ impl BoxedSecurityDescriptor {
     pub fn create_everyone_generic_all() -> io::Result<Self> {
         "D:(A;;GA;;;WD)".parse()
     }
+
+    /// Creates a security descriptor granting `GENERIC_ALL` access to the user of the current process only
+    ///
+    /// The created security descriptor corresponds to the Security Descriptor String `D:(A;;GA;;;<SID>)`, where
+    /// `<SID>` is the security identifier (SID) of the user the current process runs as, meaning it has:
+    /// - no owner
+    /// - no group
+    /// - no System Access Control List (SACL)
+    /// - a Discretionary Access Control List (`D` = DACL) with a single Access Control Entry (ACE) granting (`A`) the
+    ///   `GENERIC_ALL` access right (`GA`) to the current user
+    ///
+    /// # Errors
+    /// Returns an error if looking up the SID of the current user or creating the security descriptor fails
+    pub fn create_current_user_generic_all() -> io::Result<Self> {
+        format!("D:(A;;GA;;;{})", current_user_sid()?).parse()
+    }
 }
 
 impl FromStr for BoxedSecurityDescriptor {
@@ -127,6 +148,85 @@ impl FromStr for BoxedSecurityDescriptor {
     }
 }
 
+/// Returns the string representation of the security identifier (SID) of the user the current process runs as
+fn current_user_sid() -> io::Result<String> {
+    // SAFETY:
+    // Calling this function is always safe
+    let process_handle = unsafe { GetCurrentProcess() };
+
+    let mut token_handle = HANDLE::default();
+
+    // SAFETY:
+    // The pointer in the third argument is valid for writes of `HANDLE` because it comes from a live mutable reference
+    unsafe { OpenProcessToken(process_handle, TOKEN_QUERY, &mut token_handle) }?;
+
+    let result = token_user_sid(token_handle);
+
+    // SAFETY:
+    // `token_handle` is a valid handle because it was returned from a successful call to `OpenProcessToken` and has
+    // not been closed yet
+    let _ = unsafe { CloseHandle(token_handle) };
+
+    result
+}
+
+/// Returns the string representation of the security identifier (SID) of the user of the given access token
+fn token_user_sid(token_handle: HANDLE) -> io::Result<String> {
+    let mut size = 0;
+
+    // This is expected to fail with `ERROR_INSUFFICIENT_BUFFER` while reporting the required buffer size
+
+    // SAFETY:
+    // - `token_handle` is a valid access token handle with `TOKEN_QUERY` access
+    // - The third argument can be `None` if the fourth argument is zero according to documentation
+    // - The pointer in the fifth argument is valid for writes of `u32` because it comes from a live mutable reference
+    let _ = unsafe { GetTokenInformation(token_handle, TokenUser, None, 0, &mut size) };
+
+    // We use a buffer of `usize` to make sure it is properly aligned for a `TOKEN_USER`
+    let mut buffer = vec![0usize; (size as usize).div_ceil(mem::size_of::<usize>()).max(1)];
+
+    // SAFETY:
+    // - `token_handle` is a valid access token handle with `TOKEN_QUERY` access
+    // - The pointer in the third argument is valid for writes of `size` bytes because it comes from a live `Vec` of at
+    //   least that size
+    // - The pointer in the fifth argument is valid for writes of `u32` because it comes from a live mutable reference
+    unsafe {
+        GetTokenInformation(
+            token_handle,
+            TokenUser,
+            Some(buffer.as_mut_ptr().cast()),
+            size,
+            &mut size,
+        )
+    }?;
+
+    // SAFETY:
+    // - The buffer is properly aligned for a `TOKEN_USER` because it consists of `usize` values
+    // - The buffer contains a valid `TOKEN_USER` because it was filled by a successful call to `GetTokenInformation`
+    //   with the `TokenUser` information class
+    let token_user = unsafe { &*buffer.as_ptr().cast::<TOKEN_USER>() };
+
+    let mut string_sid = PWSTR::null();
+
+    // SAFETY:
+    // - `token_user.User.Sid` points to a valid SID within `buffer`, which is still live
+    // - The pointer in the second argument is valid for writes of `PWSTR` because it comes from a live mutable
+    //   reference
+    unsafe { ConvertSidToStringSidW(token_user.User.Sid, &mut string_sid) }?;
+
+    // SAFETY:
+    // `string_sid` points to a valid null-terminated wide string because it was returned from a successful call to
+    // `ConvertSidToStringSidW`
+    let result = unsafe { string_sid.to_string() };
+
+    // SAFETY:
+    // - `string_sid` points to a local memory object because it was returned from `ConvertSidToStringSidW`
+    // - `string_sid` has not been freed yet
+    unsafe { LocalFree(Some(HLOCAL(string_sid.as_ptr().cast()))) };
+
+    result.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
 impl Drop for BoxedSecurityDescriptor {
     fn drop(&mut self) {
         // Note: This can fail, but we have to silently ignore the error because `drop` must not fail
@@ -0,0 +1,353 @@
+//! Tying subscriptions to the lifecycle of a Windows service
+
+use std::fmt::{self, Debug, Formatter};
+use std::io::{self, ErrorKind};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use thiserror::Error;
+use windows::Win32::System::Services::{
+    SERVICE_CONTROL_CONTINUE, SERVICE_CONTROL_PAUSE, SERVICE_CONTROL_PRESHUTDOWN, SERVICE_CONTROL_SHUTDOWN,
+    SERVICE_CONTROL_STOP,
+};
+
+use crate::state::BorrowedState;
+use crate::subscribe::{DataAccessor, SeenChangeStamp, StateListener, Subscription};
+
+/// A set of subscriptions that follow the lifecycle of a Windows service
+///
+/// When a Windows service stops, its control handler usually signals the worker threads to finish and returns. If the
+/// service has subscribed to state updates, their listeners may still be called on threads of the system thread pool
+/// while the service is shutting down, racing with the cleanup of the resources they use. A
+/// [`ServiceSubscriptions`] avoids this by managing the subscriptions of a service and reacting to the controls it
+/// receives:
+/// - On `SERVICE_CONTROL_PAUSE`, its listeners are paused, i.e. state updates are ignored until
+///   `SERVICE_CONTROL_CONTINUE` is received.
+/// - On `SERVICE_CONTROL_STOP`, `SERVICE_CONTROL_SHUTDOWN` and `SERVICE_CONTROL_PRESHUTDOWN`, its listeners are paused
+///   and then all of its subscriptions are unsubscribed before [`handle_control`](Self::handle_control) returns. Since
+///   unsubscribing waits for a listener call that is in progress, no listener is called anymore once the control
+///   handler returns.
+///
+/// Pass the controls received by the control handler of your service to [`handle_control`](Self::handle_control).
+/// This works with any way of registering a control handler, e.g. through the `windows-service` crate (using
+/// `ServiceControl::raw_value`) or through `RegisterServiceCtrlHandlerExW` directly.
+///
+/// # Example
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use std::sync::Arc;
+///
+/// use windows::Win32::System::Services::SERVICE_CONTROL_STOP;
+/// use wnf::{BorrowedState, DataAccessor, SeenChangeStamp, ServiceSubscriptions, StateName};
+///
+/// let subscriptions = Arc::new(ServiceSubscriptions::new());
+///
+/// let state = BorrowedState::<u32>::from_state_name(StateName::from_opaque_value(0x0D83_063E_A3BE_5075));
+/// # let state = wnf::OwnedState::<u32>::create_temporary()?.leak();
+/// subscriptions.subscribe(
+///     state,
+///     |accessor: DataAccessor<u32>| println!("{:?}", accessor.get()),
+///     SeenChangeStamp::Current,
+/// )?;
+///
+/// // Within the control handler of the service:
+/// let handled = subscriptions.handle_control(SERVICE_CONTROL_STOP)?;
+///
+/// assert!(handled);
+/// assert!(subscriptions.is_stopped());
+/// # Ok(()) }
+/// ```
+///
+/// This requires the `service` feature.
+pub struct ServiceSubscriptions {
+    paused: Arc<AtomicBool>,
+    inner: Mutex<ServiceSubscriptionsInner>,
+}
+
+/// The mutable part of a [`ServiceSubscriptions`]
+#[derive(Default)]
+struct ServiceSubscriptionsInner {
+    subscriptions: Vec<Box<dyn Unsubscribe>>,
+    is_stopped: bool,
+}
+
+impl ServiceSubscriptions {
+    /// Creates a new empty [`ServiceSubscriptions`]
+    pub fn new() -> Self {
+        Self {
+            paused: Arc::new(AtomicBool::new(false)),
+            inner: Mutex::new(ServiceSubscriptionsInner::default()),
+        }
+    }
+
+    /// Subscribes the given state listener to the given state, tying the subscription to the lifecycle of the service
+    ///
+    /// The listener is not called while the service is paused and is unsubscribed when the service stops, see
+    /// [`handle_control`](Self::handle_control).
+    ///
+    /// See [`OwnedState::subscribe`](crate::state::OwnedState::subscribe) for the meaning of the
+    /// `last_seen_change_stamp` argument.
+    ///
+    /// # Errors
+    /// Returns an error if subscribing fails. Also returns an error of kind [`ErrorKind::Other`] wrapping a
+    /// [`ServiceStoppedError`] if the service has already been stopped.
+    pub fn subscribe<F, T>(
+        &self,
+        state: BorrowedState<'static, T>,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+    ) -> io::Result<()>
+    where
+        F: StateListener<T> + Send + 'static,
+        T: ?Sized + 'static,
+    {
+        let mut inner = self.lock_inner();
+
+        if inner.is_stopped {
+            return Err(io::Error::new(ErrorKind::Other, ServiceStoppedError));
+        }
+
+        let listener = ServiceListener {
+            listener,
+            paused: Arc::clone(&self.paused),
+        };
+
+        let subscription = state.subscribe(listener, last_seen_change_stamp)?;
+        inner.subscriptions.push(Box::new(subscription));
+
+        Ok(())
+    }
+
+    /// Adds the given subscription, tying it to the lifecycle of the service
+    ///
+    /// The subscription is unsubscribed when the service stops, see [`handle_control`](Self::handle_control). Unlike
+    /// for subscriptions created through [`subscribe`](Self::subscribe), its listener is not paused while the service
+    /// is paused.
+    ///
+    /// # Errors
+    /// Returns an error of kind [`ErrorKind::Other`] wrapping a [`ServiceStoppedError`] if the service has already been
+    /// stopped. In this case, the subscription is unsubscribed immediately.
+    pub fn add<F>(&self, subscription: Subscription<'static, F>) -> io::Result<()>
+    where
+        F: Send + 'static,
+    {
+        let mut inner = self.lock_inner();
+
+        if inner.is_stopped {
+            drop(inner);
+            let _ = subscription.unsubscribe();
+            return Err(io::Error::new(ErrorKind::Other, ServiceStoppedError));
+        }
+
+        inner.subscriptions.push(Box::new(subscription));
+
+        Ok(())
+    }
+
+    /// Handles the given service control
+    ///
+    /// This pauses, resumes or unsubscribes the subscriptions depending on the control, see [`ServiceSubscriptions`].
+    /// It returns whether the control was one of the controls handled by this method. Other controls are ignored.
+    ///
+    /// # Errors
+    /// Returns an error if unsubscribing fails for any of the subscriptions. In this case, the other subscriptions are
+    /// unsubscribed anyway and the listener of the failed subscription is not called anymore.
+    pub fn handle_control(&self, control: u32) -> io::Result<bool> {
+        match control {
+            SERVICE_CONTROL_PAUSE => self.pause(),
+            SERVICE_CONTROL_CONTINUE => self.resume(),
+            SERVICE_CONTROL_STOP | SERVICE_CONTROL_SHUTDOWN | SERVICE_CONTROL_PRESHUTDOWN => self.stop()?,
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+
+    /// Pauses the listeners of the subscriptions created through [`subscribe`](Self::subscribe)
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::Release);
+    }
+
+    /// Resumes the listeners of the subscriptions created through [`subscribe`](Self::subscribe)
+    ///
+    /// This has no effect after the service has been stopped.
+    pub fn resume(&self) {
+        if !self.is_stopped() {
+            self.paused.store(false, Ordering::Release);
+        }
+    }
+
+    /// Pauses the listeners and unsubscribes all subscriptions
+    ///
+    /// After this, new subscriptions cannot be added anymore.
+    ///
+    /// # Errors
+    /// Returns an error if unsubscribing fails for any of the subscriptions, see
+    /// [`handle_control`](Self::handle_control)
+    pub fn stop(&self) -> io::Result<()> {
+        self.pause();
+
+        let subscriptions = {
+            let mut inner = self.lock_inner();
+            inner.is_stopped = true;
+            std::mem::take(&mut inner.subscriptions)
+        };
+
+        subscriptions
+            .into_iter()
+            .map(Unsubscribe::unsubscribe)
+            .fold(Ok(()), Result::and)
+    }
+
+    /// Returns whether the listeners are currently paused
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Acquire)
+    }
+
+    /// Returns whether the service has been stopped
+    pub fn is_stopped(&self) -> bool {
+        self.lock_inner().is_stopped
+    }
+
+    /// Returns the number of subscriptions that are currently managed
+    pub fn len(&self) -> usize {
+        self.lock_inner().subscriptions.len()
+    }
+
+    /// Returns whether there are no subscriptions that are currently managed
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock_inner(&self) -> MutexGuard<'_, ServiceSubscriptionsInner> {
+        // The inner state is always left consistent, so we can access it even when the mutex is poisoned
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Default for ServiceSubscriptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debug for ServiceSubscriptions {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServiceSubscriptions")
+            .field("len", &self.len())
+            .field("is_paused", &self.is_paused())
+            .field("is_stopped", &self.is_stopped())
+            .finish()
+    }
+}
+
+/// Error returned when adding a subscription to a [`ServiceSubscriptions`] after the service has been stopped
+#[derive(Clone, Copy, Debug, Eq, Error, Hash, PartialEq)]
+#[error("the service has already been stopped")]
+pub struct ServiceStoppedError;
+
+/// A state listener that is not called while the service is paused
+struct ServiceListener<F> {
+    listener: F,
+    paused: Arc<AtomicBool>,
+}
+
+impl<F, T> StateListener<T> for ServiceListener<F>
+where
+    F: StateListener<T>,
+    T: ?Sized,
+{
+    fn call(&mut self, accessor: DataAccessor<'_, T>) {
+        if !self.paused.load(Ordering::Acquire) {
+            self.listener.call(accessor);
+        }
+    }
+
+    fn on_error(&mut self, err: io::Error, accessor: DataAccessor<'_, T>) {
+        if !self.paused.load(Ordering::Acquire) {
+            self.listener.on_error(err, accessor);
+        }
+    }
+}
+
+/// A type-erased subscription that can be unsubscribed
+trait Unsubscribe: Send {
+    /// Unsubscribes the listener of this subscription
+    fn unsubscribe(self: Box<Self>) -> io::Result<()>;
+}
+
+impl<F> Unsubscribe for Subscription<'static, F>
+where
+    F: Send,
+{
+    fn unsubscribe(self: Box<Self>) -> io::Result<()> {
+        Subscription::unsubscribe(*self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use static_assertions::assert_impl_all;
+
+    use super::*;
+    use crate::data::ChangeStamp;
+    use crate::subscribe::{ScopedData, UpdateKind};
+
+    #[test]
+    fn service_subscriptions_is_send_and_sync() {
+        assert_impl_all!(ServiceSubscriptions: Send, Sync);
+    }
+
+    #[test]
+    fn handle_control_pauses_and_resumes() {
+        let subscriptions = ServiceSubscriptions::new();
+
+        assert!(subscriptions.handle_control(SERVICE_CONTROL_PAUSE).unwrap());
+        assert!(subscriptions.is_paused());
+
+        assert!(subscriptions.handle_control(SERVICE_CONTROL_CONTINUE).unwrap());
+        assert!(!subscriptions.is_paused());
+
+        assert!(!subscriptions.handle_control(4).unwrap());
+    }
+
+    #[test]
+    fn handle_control_stops() {
+        let subscriptions = ServiceSubscriptions::new();
+
+        assert!(subscriptions.handle_control(SERVICE_CONTROL_STOP).unwrap());
+        assert!(subscriptions.is_stopped());
+        assert!(subscriptions.is_paused());
+
+        subscriptions.resume();
+        assert!(subscriptions.is_paused());
+    }
+
+    #[test]
+    fn service_listener_is_not_called_while_paused() {
+        let paused = Arc::new(AtomicBool::new(false));
+        let mut calls = Vec::new();
+        let mut listener = ServiceListener {
+            listener: |accessor: DataAccessor<'_, u32>| calls.push(accessor.change_stamp()),
+            paused: Arc::clone(&paused),
+        };
+
+        let buffer = [0u8; 4];
+
+        // SAFETY:
+        // `buffer` is live and initialized for as long as the `ScopedData` instances are live because it is declared
+        // before them
+        let data: Vec<_> = (1..=3)
+            .map(|change_stamp| unsafe { ScopedData::new(buffer.as_ptr().cast(), buffer.len(), change_stamp) })
+            .collect();
+
+        listener.call(data[0].accessor_with_update_kind(UpdateKind::Sequential));
+        paused.store(true, Ordering::Release);
+        listener.call(data[1].accessor_with_update_kind(UpdateKind::Sequential));
+        paused.store(false, Ordering::Release);
+        listener.call(data[2].accessor_with_update_kind(UpdateKind::Sequential));
+
+        drop(listener);
+        assert_eq!(calls, [ChangeStamp::new(1), ChangeStamp::new(3)]);
+    }
+}
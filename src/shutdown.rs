@@ -0,0 +1,175 @@
+//! Shutting down the delivery of state updates to listeners
+
+#![deny(unsafe_code)]
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::subscribe::{active_subscriptions, is_in_listener};
+
+/// The number of listener calls in progress
+static LISTENER_CALLS_IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether [`shutdown`] has been called
+static IS_SHUT_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// Mutex for [`shutdown`] to wait on [`LISTENER_CALL_FINISHED`] with
+///
+/// This is only locked after [`shutdown`] has been called, so listener calls don't contend on it otherwise.
+static SHUTDOWN_LOCK: Mutex<()> = Mutex::new(());
+
+/// Notified whenever a listener call finishes after [`shutdown`] has been called
+static LISTENER_CALL_FINISHED: Condvar = Condvar::new();
+
+/// Locks [`SHUTDOWN_LOCK`]
+fn lock_shutdown() -> MutexGuard<'static, ()> {
+    // We can lock the mutex even when it is poisoned because it doesn't protect any data
+    match SHUTDOWN_LOCK.lock() {
+        Ok(guard) => guard,
+        Err(err) => err.into_inner(),
+    }
+}
+
+/// Shuts down the delivery of state updates to listeners in the current process
+///
+/// After calling this function, no listener subscribed through this crate is called anymore, regardless of whether it
+/// was subscribed before or after the call. Listener calls that are already in progress are not interrupted. Instead,
+/// this function waits for them to finish, but for no longer than the given `timeout`.
+///
+/// This is useful for making the exit of a process deterministic: Listeners are called on threads of the thread pool
+/// of the operating system, so without shutting down, a listener may still be running while the main thread is
+/// already tearing down resources used by the listener, or while the process is exiting.
+///
+/// Note that this does not unsubscribe any listeners. Subscriptions that are still active when this function is called
+/// are abandoned, i.e. their listeners are not called anymore even though they are still subscribed. They are reported
+/// in the returned [`ShutdownReport`] together with the number of listener calls that did not finish before the
+/// timeout elapsed.
+///
+/// When this function is called from within a listener, it does not wait for that listener call to finish.
+///
+/// Calling this function again waits for the listener calls that are still in progress, if any.
+pub fn shutdown(timeout: Duration) -> ShutdownReport {
+    let deadline = Instant::now().checked_add(timeout);
+
+    // A call of this function from within a listener must not wait for that listener call to finish
+    let own_calls = usize::from(is_in_listener());
+
+    IS_SHUT_DOWN.store(true, Ordering::SeqCst);
+
+    let mut guard = lock_shutdown();
+
+    while LISTENER_CALLS_IN_FLIGHT.load(Ordering::SeqCst) > own_calls {
+        let timeout = match deadline {
+            Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                Some(timeout) if !timeout.is_zero() => timeout,
+                _ => break,
+            },
+            None => Duration::MAX,
+        };
+
+        guard = match LISTENER_CALL_FINISHED.wait_timeout(guard, timeout) {
+            Ok((guard, _)) => guard,
+            Err(err) => err.into_inner().0,
+        };
+    }
+
+    let pending_listener_calls = LISTENER_CALLS_IN_FLIGHT
+        .load(Ordering::SeqCst)
+        .saturating_sub(own_calls);
+    drop(guard);
+
+    ShutdownReport {
+        abandoned_subscriptions: active_subscriptions().len(),
+        pending_listener_calls,
+    }
+}
+
+/// Returns whether [`shutdown`] has been called in the current process
+pub fn is_shut_down() -> bool {
+    IS_SHUT_DOWN.load(Ordering::SeqCst)
+}
+
+/// The result of shutting down the delivery of state updates to listeners
+///
+/// This is returned by [`shutdown`].
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct ShutdownReport {
+    abandoned_subscriptions: usize,
+    pending_listener_calls: usize,
+}
+
+impl ShutdownReport {
+    /// Returns the number of subscriptions that were still active and hence have been abandoned
+    ///
+    /// The listeners of these subscriptions are not called anymore even though they have not been unsubscribed.
+    pub const fn abandoned_subscriptions(&self) -> usize {
+        self.abandoned_subscriptions
+    }
+
+    /// Returns the number of listener calls that were still in progress when the timeout elapsed
+    pub const fn pending_listener_calls(&self) -> usize {
+        self.pending_listener_calls
+    }
+
+    /// Returns whether all listener calls finished before the timeout elapsed
+    pub const fn is_drained(&self) -> bool {
+        self.pending_listener_calls == 0
+    }
+}
+
+/// Guard tracking a listener call that is in progress until it is dropped
+#[derive(Debug)]
+pub(crate) struct ListenerCallGuard;
+
+impl ListenerCallGuard {
+    /// Starts tracking a listener call, returning [`None`] if listeners must not be called anymore
+    pub(crate) fn enter() -> Option<Self> {
+        // Incrementing the counter before checking the flag makes sure that `shutdown` either waits for this call or
+        // this call sees the flag, since both use sequentially consistent orderings
+        LISTENER_CALLS_IN_FLIGHT.fetch_add(1, Ordering::SeqCst);
+        let guard = Self;
+
+        if IS_SHUT_DOWN.load(Ordering::SeqCst) {
+            // Dropping the guard stops tracking the call and notifies `shutdown`
+            return None;
+        }
+
+        Some(guard)
+    }
+}
+
+impl Drop for ListenerCallGuard {
+    fn drop(&mut self) {
+        LISTENER_CALLS_IN_FLIGHT.fetch_sub(1, Ordering::SeqCst);
+
+        if IS_SHUT_DOWN.load(Ordering::SeqCst) {
+            // Taking the lock makes sure that `shutdown` is either waiting and hence notified or has not yet loaded
+            // the counter and hence sees the decremented value
+            let _guard = lock_shutdown();
+            LISTENER_CALL_FINISHED.notify_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shutdown_report_is_drained_without_pending_listener_calls() {
+        let report = ShutdownReport {
+            abandoned_subscriptions: 2,
+            pending_listener_calls: 0,
+        };
+
+        assert!(report.is_drained());
+
+        let report = ShutdownReport {
+            pending_listener_calls: 1,
+            ..report
+        };
+
+        assert!(!report.is_drained());
+    }
+}
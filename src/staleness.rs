@@ -0,0 +1,263 @@
+//! Detecting states that have not been updated for too long
+
+#![deny(unsafe_code)]
+
+use std::fmt::{self, Debug, Formatter};
+use std::io;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::data::{ChangeStamp, OpaqueData};
+use crate::state::AsState;
+use crate::subscribe::{DataAccessor, SeenChangeStamp, StateListener, Subscription};
+
+/// A monitor detecting that a state has not been updated for longer than a given maximum age
+///
+/// This is the consumer side of a liveness signal such as the one published by a
+/// [`HeartbeatPublisher`](crate::heartbeat::HeartbeatPublisher): A watchdog can use a [`StalenessMonitor`] to detect
+/// that the publisher has stopped updating the state.
+///
+/// The monitor subscribes to the state and records the time of every update locally, so it works with states of any
+/// data type and does not rely on the publisher's clock. Before the first update is observed, the age of the data is
+/// measured from the time the monitor was created.
+///
+/// If a callback is given (see [`StalenessMonitor::with_callback`]), a background thread calls it whenever the data
+/// become stale, i.e. once per period without updates exceeding the maximum age.
+///
+/// Dropping a [`StalenessMonitor`] unsubscribes from the state and stops the background thread, if any.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use std::time::Duration;
+///
+/// use wnf::{OwnedState, StalenessMonitor};
+///
+/// let state = OwnedState::<u32>::create_temporary()?;
+/// let monitor = StalenessMonitor::new(&state, Duration::from_secs(60))?;
+///
+/// state.set(&42)?;
+/// assert!(!monitor.is_stale());
+/// # Ok(()) }
+/// ```
+#[must_use = "a `StalenessMonitor` stops monitoring immediately if it is not used"]
+pub struct StalenessMonitor<'a> {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+    subscription: Subscription<'a, StalenessListener>,
+}
+
+impl<'a> StalenessMonitor<'a> {
+    /// Starts monitoring the given state for updates older than `max_age`
+    ///
+    /// # Errors
+    /// Returns an error if subscribing to the state fails
+    pub fn new<S>(state: &'a S, max_age: Duration) -> io::Result<Self>
+    where
+        S: AsState,
+    {
+        Self::start(state, max_age, None)
+    }
+
+    /// Starts monitoring the given state for updates older than `max_age`, calling the given callback whenever the
+    /// data become stale
+    ///
+    /// The callback is called on a background thread with the age of the data at the time of the call, which is
+    /// slightly greater than `max_age`. It is not called again until the state has been updated and the data have
+    /// become stale again.
+    ///
+    /// # Errors
+    /// Returns an error if subscribing to the state fails or if spawning the background thread fails
+    pub fn with_callback<S, F>(state: &'a S, max_age: Duration, callback: F) -> io::Result<Self>
+    where
+        S: AsState,
+        F: FnMut(Duration) + Send + 'static,
+    {
+        Self::start(state, max_age, Some(Box::new(callback)))
+    }
+
+    /// Starts monitoring the given state, spawning a background thread if a callback is given
+    fn start<S>(state: &'a S, max_age: Duration, callback: Option<Box<dyn FnMut(Duration) + Send>>) -> io::Result<Self>
+    where
+        S: AsState,
+    {
+        let shared = Arc::new(Shared {
+            inner: Mutex::new(Inner {
+                started_at: Instant::now(),
+                last_update: None,
+                stopped: false,
+            }),
+            condvar: Condvar::new(),
+            max_age,
+        });
+
+        let subscription = state.as_state().raw.cast::<OpaqueData>().subscribe(
+            StalenessListener {
+                shared: Arc::clone(&shared),
+            },
+            SeenChangeStamp::Current,
+        )?;
+
+        let thread = callback
+            .map(|callback| {
+                thread::Builder::new().name("wnf-staleness".into()).spawn({
+                    let shared = Arc::clone(&shared);
+                    move || shared.watch(callback)
+                })
+            })
+            .transpose()?;
+
+        Ok(Self {
+            shared,
+            thread,
+            subscription,
+        })
+    }
+
+    /// Returns the maximum age of the data before they are considered stale
+    pub fn max_age(&self) -> Duration {
+        self.shared.max_age
+    }
+
+    /// Returns the time and change stamp of the last update of the state observed by this monitor, or [`None`] if no
+    /// update has been observed yet
+    pub fn last_update(&self) -> Option<(Instant, ChangeStamp)> {
+        self.shared.lock().last_update
+    }
+
+    /// Returns the age of the data, i.e. the time elapsed since the last observed update
+    ///
+    /// If no update has been observed yet, this is the time elapsed since the monitor was created.
+    pub fn age(&self) -> Duration {
+        self.shared.lock().reference().elapsed()
+    }
+
+    /// Returns whether the data are stale, i.e. their [`age`](StalenessMonitor::age) exceeds the maximum age
+    pub fn is_stale(&self) -> bool {
+        self.age() > self.shared.max_age
+    }
+}
+
+impl Drop for StalenessMonitor<'_> {
+    fn drop(&mut self) {
+        self.shared.lock().stopped = true;
+        self.shared.condvar.notify_all();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+// We cannot derive this because `Box<dyn FnMut(Duration) + Send>` does not implement `Debug`
+impl Debug for StalenessMonitor<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StalenessMonitor")
+            .field("max_age", &self.shared.max_age)
+            .field("last_update", &self.last_update())
+            .field("subscription", &self.subscription)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The state shared between a [`StalenessMonitor`], its background thread and its subscription
+#[derive(Debug)]
+struct Shared {
+    inner: Mutex<Inner>,
+    condvar: Condvar,
+    max_age: Duration,
+}
+
+/// The mutable part of the state shared between a [`StalenessMonitor`], its background thread and its subscription
+#[derive(Debug)]
+struct Inner {
+    started_at: Instant,
+    last_update: Option<(Instant, ChangeStamp)>,
+    stopped: bool,
+}
+
+impl Inner {
+    /// Returns the time from which the age of the data is measured
+    fn reference(&self) -> Instant {
+        self.last_update.map_or(self.started_at, |(instant, _)| instant)
+    }
+}
+
+impl Shared {
+    /// Locks the mutable part of the shared state
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Calls the given callback whenever the data become stale, until the monitor is stopped
+    ///
+    /// This runs on the background thread of a [`StalenessMonitor`].
+    fn watch(&self, mut callback: Box<dyn FnMut(Duration) + Send>) {
+        let mut inner = self.lock();
+        let mut notified_reference = None;
+
+        while !inner.stopped {
+            let reference = inner.reference();
+            let age = reference.elapsed();
+
+            inner = if notified_reference == Some(reference) {
+                self.condvar.wait(inner).unwrap_or_else(PoisonError::into_inner)
+            } else if age <= self.max_age {
+                self.condvar
+                    .wait_timeout(inner, self.max_age - age)
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .0
+            } else {
+                notified_reference = Some(reference);
+
+                // The lock must not be held while calling the callback, otherwise updates would be blocked
+                drop(inner);
+                callback(age);
+                self.lock()
+            };
+        }
+    }
+}
+
+/// A state listener recording the time of each update for a [`StalenessMonitor`]
+#[derive(Debug)]
+struct StalenessListener {
+    shared: Arc<Shared>,
+}
+
+impl StateListener<OpaqueData> for StalenessListener {
+    fn call(&mut self, accessor: DataAccessor<'_, OpaqueData>) {
+        self.shared.lock().last_update = Some((Instant::now(), accessor.change_stamp()));
+        self.shared.condvar.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use static_assertions::assert_impl_all;
+
+    use super::*;
+
+    #[test]
+    fn staleness_monitor_is_send_and_sync() {
+        assert_impl_all!(StalenessMonitor<'_>: Send, Sync);
+    }
+
+    #[test]
+    fn reference_is_start_before_first_update() {
+        let started_at = Instant::now();
+        let mut inner = Inner {
+            started_at,
+            last_update: None,
+            stopped: false,
+        };
+
+        assert_eq!(inner.reference(), started_at);
+
+        let updated_at = started_at + Duration::from_secs(1);
+        inner.last_update = Some((updated_at, ChangeStamp::initial()));
+
+        assert_eq!(inner.reference(), updated_at);
+    }
+}
@@ -4,20 +4,22 @@
 
 #![deny(unsafe_code)]
 
-use std::fmt;
 use std::fmt::{Debug, Formatter};
 use std::hash::{Hash, Hasher};
 use std::marker::PhantomData;
 use std::mem::ManuallyDrop;
 use std::ops::Deref;
+use std::{fmt, io};
 
-use crate::state_name::StateName;
+use crate::manage::DropPolicy;
+use crate::state_name::{DataScope, StateName};
 use crate::type_id::{TypeId, GUID};
 
 /// An owned state
 ///
 /// This deletes the represented state on drop. You can prevent this behavior by calling the
-/// [`leak`](OwnedState::leak) method.
+/// [`leak`](OwnedState::leak) method or customize it by setting a [`DropPolicy`] via the
+/// [`set_drop_policy`](OwnedState::set_drop_policy) method.
 ///
 /// While ownership in Rust usually refers to the ownership of memory, this applies the idea of ownership to an
 /// external entity, namely a state. It's similar to [`OwnedHandle`](std::os::windows::io::OwnedHandle) in that
@@ -32,6 +34,7 @@ where
     T: ?Sized,
 {
     pub(crate) raw: RawState<T>,
+    drop_policy: DropPolicy,
 }
 
 impl<T> OwnedState<T>
@@ -62,12 +65,56 @@ where
     where
         U: ?Sized,
     {
-        OwnedState::from_raw(self.into_raw().cast())
+        let drop_policy = self.drop_policy;
+
+        OwnedState {
+            raw: self.into_raw().cast(),
+            drop_policy,
+        }
+    }
+
+    /// Returns the drop policy of this state
+    ///
+    /// See [`DropPolicy`]
+    pub const fn drop_policy(&self) -> DropPolicy {
+        self.drop_policy
+    }
+
+    /// Sets the drop policy of this state, determining what happens when this [`OwnedState<T>`] is dropped
+    ///
+    /// See [`DropPolicy`]
+    pub fn set_drop_policy(&mut self, drop_policy: DropPolicy) {
+        self.drop_policy = drop_policy;
+    }
+
+    /// Returns the maximum size in bytes of data that can be read from this state, if any
+    ///
+    /// See [`set_max_read_size`](OwnedState::set_max_read_size)
+    pub const fn max_read_size(&self) -> Option<usize> {
+        self.raw.max_read_size
+    }
+
+    /// Sets the maximum size in bytes of data that can be read from this state
+    ///
+    /// When querying the data of this state (or of a [`BorrowedState<'_, T>`](BorrowedState) borrowed from it
+    /// afterwards) and the data turn out to be larger than this, querying fails with a
+    /// [`ReadError::ExceedsMaxReadSize`](crate::read::ReadError::ExceedsMaxReadSize) error before a buffer for the data
+    /// is allocated. This protects against misbehaving publishers in environments with tight memory budgets.
+    ///
+    /// Passing [`None`] makes this state use the global default, see
+    /// [`set_default_max_read_size`](crate::read::set_default_max_read_size).
+    pub fn set_max_read_size(&mut self, max_read_size: Option<usize>) {
+        self.raw = self.raw.with_max_read_size(max_read_size);
     }
 
     /// Creates a new [`OwnedState`] wrapping a given [`RawState`]
+    ///
+    /// The drop policy of the created [`OwnedState`] is [`DropPolicy::Delete`].
     pub(crate) const fn from_raw(raw: RawState<T>) -> Self {
-        Self { raw }
+        Self {
+            raw,
+            drop_policy: DropPolicy::Delete,
+        }
     }
 
     /// Consumes this [`OwnedState`] without dropping it, returning the inner [`RawState`]
@@ -108,7 +155,10 @@ where
     T: ?Sized,
 {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        f.debug_struct("OwnedState").field("raw", &self.raw).finish()
+        f.debug_struct("OwnedState")
+            .field("raw", &self.raw)
+            .field("drop_policy", &self.drop_policy)
+            .finish()
     }
 }
 
@@ -117,7 +167,7 @@ where
     T: ?Sized,
 {
     fn drop(&mut self) {
-        let _ = self.raw.delete();
+        let _ = self.raw.apply_drop_policy(self.drop_policy);
     }
 }
 
@@ -175,6 +225,22 @@ where
         BorrowedState::from_raw(self.raw.cast())
     }
 
+    /// Returns the maximum size in bytes of data that can be read from this state, if any
+    ///
+    /// See [`OwnedState::set_max_read_size`]
+    pub const fn max_read_size(self) -> Option<usize> {
+        self.raw.max_read_size
+    }
+
+    /// Returns a [`BorrowedState<'a, T>`](BorrowedState) representing the same underlying state, but using the given
+    /// maximum size in bytes of data that can be read from it
+    ///
+    /// See [`OwnedState::set_max_read_size`]
+    #[must_use]
+    pub const fn with_max_read_size(self, max_read_size: Option<usize>) -> Self {
+        BorrowedState::from_raw(self.raw.with_max_read_size(max_read_size))
+    }
+
     /// Creates a new [`BorrowedState<'_, T>`](BorrowedState) wrapping a given [`RawState<T>`]
     ///
     /// The lifetime `'a` of the returned [`BorrowedState<'a, T>`](BorrowedState) is inferred at the call site.
@@ -210,6 +276,30 @@ where
             TypeId::from_guid(type_id.into()),
         ))
     }
+
+    /// Statically borrows the well-known state with the given owner tag, unique id and data scope
+    ///
+    /// This builds the name of the state through [`StateName::well_known`], see there for details.
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> std::io::Result<()> {
+    /// use wnf::{BorrowedState, DataScope, OpaqueData};
+    ///
+    /// let state = BorrowedState::<OpaqueData>::well_known("SHEL", 0x4A, DataScope::System)?;
+    /// assert_eq!(state.state_name(), 0x0D83_063E_A3BE_5075);
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an error of kind [`ErrorKind::InvalidInput`](io::ErrorKind::InvalidInput) if the owner tag or the unique
+    /// id is invalid
+    pub fn well_known(owner_tag: &str, unique_id: u32, data_scope: DataScope) -> io::Result<Self> {
+        let state_name = StateName::well_known(owner_tag, unique_id, data_scope)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+        Ok(Self::from_state_name(state_name))
+    }
 }
 
 // We cannot derive this because that would impose an unnecessary trait bound `T: Copy`
@@ -352,6 +442,149 @@ where
     }
 }
 
+/// A state that is either owned or borrowed
+///
+/// This is similar to [`Cow<'a, B>`](std::borrow::Cow), except that there is no cloning involved: It contains either an
+/// [`OwnedState<T>`] or a [`BorrowedState<'a, T>`](BorrowedState), deleting the represented state on drop only in the
+/// former case (according to its [`DropPolicy`]). This is useful for library APIs that want to accept either an owned
+/// or a borrowed state and store it, leaving the decision whether the state should be deleted on drop to the caller:
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use wnf::{AsState, BorrowedState, CowState, OwnedState};
+///
+/// struct Counter<'a> {
+///     state: CowState<'a, u32>,
+/// }
+///
+/// impl<'a> Counter<'a> {
+///     fn new(state: impl Into<CowState<'a, u32>>) -> Self {
+///         Self { state: state.into() }
+///     }
+///
+///     fn increment(&self) -> std::io::Result<()> {
+///         self.state.as_state().apply(|value| value + 1)?;
+///         Ok(())
+///     }
+/// }
+///
+/// // The counter owns the state, deleting it when dropped
+/// let counter = Counter::new(OwnedState::<u32>::create_temporary()?);
+/// counter.increment()?;
+///
+/// // The counter borrows the state, which outlives it
+/// let state = OwnedState::<u32>::create_temporary()?;
+/// let counter = Counter::new(state.as_state());
+/// counter.increment()?;
+/// drop(counter);
+///
+/// assert_eq!(state.get()?, 1);
+/// # Ok(()) }
+/// ```
+///
+/// The methods for querying, updating and subscribing to the represented state are available on the
+/// [`BorrowedState<'_, T>`](BorrowedState) returned from the [`AsState::as_state`] method.
+pub enum CowState<'a, T>
+where
+    T: ?Sized,
+{
+    /// An owned state
+    Owned(OwnedState<T>),
+
+    /// A borrowed state
+    Borrowed(BorrowedState<'a, T>),
+}
+
+impl<'a, T> CowState<'a, T>
+where
+    T: ?Sized,
+{
+    /// Returns the name of this state
+    pub fn state_name(&self) -> StateName {
+        self.as_state().state_name()
+    }
+
+    /// Returns whether this [`CowState<'_, T>`](CowState) contains an [`OwnedState<T>`]
+    pub const fn is_owned(&self) -> bool {
+        matches!(self, Self::Owned(..))
+    }
+
+    /// Returns whether this [`CowState<'_, T>`](CowState) contains a [`BorrowedState<'_, T>`](BorrowedState)
+    pub const fn is_borrowed(&self) -> bool {
+        matches!(self, Self::Borrowed(..))
+    }
+
+    /// Casts the data type of this state to a different type `U`
+    ///
+    /// The returned [`CowState<'a, U>`](CowState) represents the same underlying state, but treats it as containing
+    /// data of a different type `U`. It is owned if and only if this [`CowState<'a, T>`](CowState) is owned.
+    pub fn cast<U>(self) -> CowState<'a, U>
+    where
+        U: ?Sized,
+    {
+        match self {
+            Self::Owned(state) => CowState::Owned(state.cast()),
+            Self::Borrowed(state) => CowState::Borrowed(state.cast()),
+        }
+    }
+
+    /// Turns this [`CowState<'a, T>`](CowState) into a [`BorrowedState<'a, T>`](BorrowedState) representing the same
+    /// underlying state
+    ///
+    /// If this [`CowState<'a, T>`](CowState) is owned, the contained [`OwnedState<T>`] is leaked (see
+    /// [`OwnedState::leak`]), so the underlying state will not be deleted.
+    pub fn leak(self) -> BorrowedState<'a, T> {
+        match self {
+            Self::Owned(state) => state.leak(),
+            Self::Borrowed(state) => state,
+        }
+    }
+}
+
+impl<T> From<OwnedState<T>> for CowState<'_, T>
+where
+    T: ?Sized,
+{
+    fn from(state: OwnedState<T>) -> Self {
+        Self::Owned(state)
+    }
+}
+
+impl<'a, T> From<BorrowedState<'a, T>> for CowState<'a, T>
+where
+    T: ?Sized,
+{
+    fn from(state: BorrowedState<'a, T>) -> Self {
+        Self::Borrowed(state)
+    }
+}
+
+impl<T> AsState for CowState<'_, T>
+where
+    T: ?Sized,
+{
+    type Data = T;
+
+    fn as_state(&self) -> BorrowedState<'_, T> {
+        match self {
+            Self::Owned(state) => state.as_state(),
+            Self::Borrowed(state) => *state,
+        }
+    }
+}
+
+// We cannot derive this because that would impose an unnecessary trait bound `T: Debug`
+impl<T> Debug for CowState<'_, T>
+where
+    T: ?Sized,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Owned(state) => f.debug_tuple("Owned").field(state).finish(),
+            Self::Borrowed(state) => f.debug_tuple("Borrowed").field(state).finish(),
+        }
+    }
+}
+
 /// A raw state
 ///
 /// This neither deletes the underlying state on drop, nor does it have a lifetime.
@@ -361,6 +594,7 @@ where
 {
     pub(crate) state_name: StateName,
     pub(crate) type_id: TypeId,
+    pub(crate) max_read_size: Option<usize>,
     // `RawState<T>` is neither covariant nor contravariant in `T` and doesn't own a `T`
     _marker: PhantomData<fn(T) -> T>,
 }
@@ -374,6 +608,7 @@ where
         Self {
             state_name,
             type_id,
+            max_read_size: None,
             _marker: PhantomData,
         }
     }
@@ -383,6 +618,16 @@ where
         self.state_name
     }
 
+    /// Returns a [`RawState<T>`] representing the same underlying state, but using the given type id
+    pub(crate) const fn with_type_id(self, type_id: TypeId) -> Self {
+        Self {
+            state_name: self.state_name,
+            type_id,
+            max_read_size: self.max_read_size,
+            _marker: PhantomData,
+        }
+    }
+
     /// Casts the data type of this state to a different type `U`
     ///
     /// The returned [`RawState<U>`] represents the same underlying state, but treats it as containing data of
@@ -391,7 +636,17 @@ where
     where
         U: ?Sized,
     {
-        RawState::from_state_name_and_type_id(self.state_name, self.type_id)
+        RawState {
+            state_name: self.state_name,
+            type_id: self.type_id,
+            max_read_size: self.max_read_size,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns a [`RawState<T>`] representing the same underlying state, but using the given maximum read size
+    pub(crate) const fn with_max_read_size(self, max_read_size: Option<usize>) -> Self {
+        Self { max_read_size, ..self }
     }
 }
 
@@ -444,6 +699,7 @@ where
         f.debug_struct("RawState")
             .field("state_name", &self.state_name)
             .field("type_id", &self.type_id)
+            .field("max_read_size", &self.max_read_size)
             .finish()
     }
 }
@@ -452,12 +708,13 @@ where
 mod private {
     use std::ops::Deref;
 
-    use super::{BorrowedState, OwnedState};
+    use super::{BorrowedState, CowState, OwnedState};
 
     pub trait Sealed {}
 
     impl<T> Sealed for OwnedState<T> where T: ?Sized {}
     impl<T> Sealed for BorrowedState<'_, T> where T: ?Sized {}
+    impl<T> Sealed for CowState<'_, T> where T: ?Sized {}
     impl<S> Sealed for S
     where
         S: Deref,
@@ -0,0 +1,171 @@
+//! Attaching the context of a state to errors returned from WNF API routines
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+
+use windows::Win32::Foundation::NTSTATUS;
+
+use crate::state::RawState;
+use crate::state_name::StateName;
+use crate::trace::WnfRoutine;
+use crate::type_id::{TypeId, GUID};
+
+/// An error returned from a WNF API routine invoked on a state, together with the context of the invocation
+///
+/// Whenever a WNF API routine invoked on a specific state fails, the returned [`io::Error`] wraps a [`StateError`]
+/// recording the name and type id of the state as well as the routine that failed. Its [`Display`] implementation
+/// includes this context, so logs identify the failing state even if the error is propagated through deep call stacks.
+/// The underlying error, containing the `NTSTATUS` code returned from the routine, is available through
+/// [`Error::source`] and [`StateError::raw_os_error`]. It is not included in the [`Display`] output, so that error
+/// reporters printing the whole chain of sources don't print it twice.
+///
+/// The [`io::ErrorKind`] of the returned [`io::Error`] is the same as that of the underlying error. Note however that
+/// [`io::Error::raw_os_error`] returns [`None`] for the returned [`io::Error`], so use [`StateError::raw_os_error`]
+/// instead:
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use wnf::{BorrowedState, OwnedState, StateError};
+///
+/// let state = OwnedState::<u32>::create_temporary()?;
+/// let state_name = state.state_name();
+/// state.delete()?;
+///
+/// let err = BorrowedState::<u32>::from_state_name(state_name).get().unwrap_err();
+/// let state_err = err.get_ref().and_then(|err| err.downcast_ref::<StateError>()).unwrap();
+///
+/// assert_eq!(state_err.state_name(), state_name);
+/// println!("{err} (NTSTATUS {:?})", state_err.raw_os_error());
+/// # Ok(()) }
+/// ```
+///
+/// Only errors of failed WNF API routines wrap a [`StateError`]. In particular, errors of routines that are not invoked
+/// on a specific state, i.e. creating a state (see [`CreateError`](crate::manage::CreateError)) and unsubscribing a
+/// listener, don't wrap a [`StateError`]. Neither do errors that occur while reading or validating the data of a state
+/// after the routine has succeeded, e.g. because the data have the wrong size or an invalid bit pattern or exceed the
+/// maximum read size. These wrap a [`ReadError`](crate::ReadError) instead.
+#[derive(Debug)]
+pub struct StateError {
+    state_name: StateName,
+    type_id: TypeId,
+    routine: WnfRoutine,
+    source: io::Error,
+}
+
+impl StateError {
+    /// Returns the name of the state on which the routine was invoked
+    pub const fn state_name(&self) -> StateName {
+        self.state_name
+    }
+
+    /// Returns the type id passed to the routine, if any
+    pub const fn type_id(&self) -> Option<GUID> {
+        self.type_id.guid()
+    }
+
+    /// Returns the routine that failed
+    pub const fn routine(&self) -> WnfRoutine {
+        self.routine
+    }
+
+    /// Returns the `NTSTATUS` code returned from the routine, if known
+    pub fn raw_os_error(&self) -> Option<i32> {
+        self.source.raw_os_error()
+    }
+}
+
+impl Display for StateError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} failed for state {}", self.routine.name(), self.state_name)?;
+
+        if let Some(type_id) = self.type_id() {
+            write!(f, " with type id {type_id:?}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for StateError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl From<StateError> for io::Error {
+    fn from(err: StateError) -> Self {
+        io::Error::new(err.source.kind(), err)
+    }
+}
+
+impl<T> RawState<T>
+where
+    T: ?Sized,
+{
+    /// Returns an error for the given result of a failed invocation of the given routine on this state
+    pub(crate) fn ntapi_error(self, routine: WnfRoutine, result: NTSTATUS) -> io::Error {
+        StateError {
+            state_name: self.state_name,
+            type_id: self.type_id,
+            routine,
+            source: io::Error::from_raw_os_error(result.0),
+        }
+        .into()
+    }
+}
+
+/// Returns the `NTSTATUS` code of the given error, looking through a wrapped [`StateError`]
+pub(crate) fn raw_ntstatus(err: &io::Error) -> Option<i32> {
+    err.raw_os_error().or_else(|| {
+        err.get_ref()
+            .and_then(|err| err.downcast_ref::<StateError>())
+            .and_then(StateError::raw_os_error)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use windows::Win32::Foundation::STATUS_ACCESS_DENIED;
+
+    use super::*;
+
+    const STATE_NAME: StateName = StateName::from_opaque_value(0x0D83_063E_A3BE_5075);
+
+    #[test]
+    fn state_error_display() {
+        let err = RawState::<u32>::from_state_name_and_type_id(STATE_NAME, TypeId::none())
+            .ntapi_error(WnfRoutine::QueryStateData, STATUS_ACCESS_DENIED);
+
+        assert_eq!(
+            err.to_string(),
+            "NtQueryWnfStateData failed for state 0x0D83063EA3BE5075"
+        );
+    }
+
+    #[test]
+    fn state_error_source() {
+        let err = RawState::<u32>::from_state_name_and_type_id(STATE_NAME, TypeId::none())
+            .ntapi_error(WnfRoutine::UpdateStateData, STATUS_ACCESS_DENIED);
+
+        let state_err = err.get_ref().unwrap().downcast_ref::<StateError>().unwrap();
+
+        assert_eq!(state_err.state_name(), STATE_NAME);
+        assert_eq!(state_err.type_id(), None);
+        assert_eq!(state_err.routine(), WnfRoutine::UpdateStateData);
+        assert_eq!(state_err.raw_os_error(), Some(STATUS_ACCESS_DENIED.0));
+        assert!(state_err.source().is_some());
+    }
+
+    #[test]
+    fn raw_ntstatus_looks_through_state_error() {
+        let err = RawState::<u32>::from_state_name_and_type_id(STATE_NAME, TypeId::none())
+            .ntapi_error(WnfRoutine::DeleteStateName, STATUS_ACCESS_DENIED);
+
+        assert_eq!(err.raw_os_error(), None);
+        assert_eq!(raw_ntstatus(&err), Some(STATUS_ACCESS_DENIED.0));
+        assert_eq!(
+            raw_ntstatus(&io::Error::from_raw_os_error(STATUS_ACCESS_DENIED.0)),
+            Some(STATUS_ACCESS_DENIED.0)
+        );
+    }
+}
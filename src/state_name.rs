@@ -3,6 +3,7 @@
 #![deny(unsafe_code)]
 
 use std::fmt::{self, Binary, Display, Formatter, LowerHex, Octal, UpperHex};
+use std::str::FromStr;
 
 use num_traits::FromPrimitive;
 use thiserror::Error;
@@ -17,6 +18,7 @@ const STATE_NAME_XOR_KEY: u64 = 0x41C6_4E6D_A3BC_0074;
 /// This property of a state controls at what point in time it is automatically deleted as well as if and how it is
 /// persisted.
 #[derive(Clone, Copy, Debug, Eq, FromPrimitive, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum StateLifetime {
     /// Lifetime of a *well-known* state
@@ -63,11 +65,22 @@ pub enum StateLifetime {
     Temporary = 3,
 }
 
+impl StateLifetime {
+    /// Returns whether state names with this lifetime can have the given data scope
+    ///
+    /// The only combination known to be rejected by the operating system is a [`StateLifetime::Temporary`] lifetime
+    /// with a [`DataScope::Process`] data scope.
+    pub const fn supports_data_scope(self, data_scope: DataScope) -> bool {
+        !matches!((self, data_scope), (Self::Temporary, DataScope::Process))
+    }
+}
+
 /// The data scope of a state
 ///
 /// This property of a state controls whether it maintains multiple instances of its data that are scoped in different
 /// ways.
 #[derive(Clone, Copy, Debug, Eq, FromPrimitive, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum DataScope {
     /// *System* data scope
@@ -99,6 +112,7 @@ pub enum DataScope {
 ///
 /// This contains the properties of a [`StateName`] that are encoded in the bits of its transparent value.
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StateNameDescriptor {
     /// WNF version number, currently always `1`
     pub version: u8,
@@ -125,6 +139,41 @@ pub struct StateNameDescriptor {
     pub owner_tag: u32,
 }
 
+impl StateNameDescriptor {
+    /// Returns the owner tag of the state name as an ASCII string
+    ///
+    /// The owner tag is made up of the four bytes of [`owner_tag`](StateNameDescriptor::owner_tag) in little-endian
+    /// order, e.g. `"SHEL"` for `0x4C45_4853`. Trailing NUL and space characters are stripped, so shorter tags such as
+    /// `"PO"` are returned without padding.
+    ///
+    /// Returns [`None`] if the owner tag is empty (which is always the case for state names with a lifetime other
+    /// than [`StateLifetime::WellKnown`]) or contains bytes that are not printable ASCII characters.
+    pub fn owner_tag_str(&self) -> Option<String> {
+        let bytes = self.owner_tag.to_le_bytes();
+        let len = bytes
+            .iter()
+            .rposition(|&byte| byte != 0 && byte != b' ')
+            .map_or(0, |idx| idx + 1);
+        let tag = &bytes[..len];
+
+        if tag.is_empty() || !tag.iter().all(u8::is_ascii_graphic) {
+            return None;
+        }
+
+        Some(tag.iter().copied().map(char::from).collect())
+    }
+
+    /// Returns whether the owner tag of the state name starts with the given prefix
+    ///
+    /// This is useful for filtering state names by owner, e.g. `descriptor.matches_prefix("SHEL")`.
+    ///
+    /// Returns `false` if the state name has no valid owner tag, see
+    /// [`owner_tag_str`](StateNameDescriptor::owner_tag_str).
+    pub fn matches_prefix(&self, prefix: &str) -> bool {
+        self.owner_tag_str().is_some_and(|tag| tag.starts_with(prefix))
+    }
+}
+
 /// A state name
 ///
 /// A state name is usually represented by its "opaque value", which is a 64-bit integer. This opaque value can be
@@ -132,7 +181,12 @@ pub struct StateNameDescriptor {
 /// encodes certain properties of the state name in its bits. The set of these properties is represented by the
 /// [`StateNameDescriptor`] type. Use the provided [`TryFrom`]/[`TryInto`] implementations to convert between a
 /// [`StateName`] (represented by its opaque value) and the corresponding [`StateNameDescriptor`].
+///
+/// A [`StateName`] can be parsed from the hexadecimal representation of its opaque value through its [`FromStr`]
+/// implementation, e.g. when taking state names from configuration files or command line arguments.
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct StateName {
     opaque_value: u64,
 }
@@ -147,6 +201,64 @@ impl StateName {
     pub const fn opaque_value(self) -> u64 {
         self.opaque_value
     }
+
+    /// Creates a [`StateName`] from the given transparent value
+    ///
+    /// The transparent value is the opaque value XOR'ed with the magic number `0x41C64E6DA3BC0074`.
+    pub const fn from_transparent_value(transparent_value: u64) -> Self {
+        Self::from_opaque_value(transparent_value ^ STATE_NAME_XOR_KEY)
+    }
+
+    /// Returns the transparent value of this [`StateName`]
+    ///
+    /// The transparent value is the opaque value XOR'ed with the magic number `0x41C64E6DA3BC0074`.
+    pub const fn transparent_value(self) -> u64 {
+        self.opaque_value ^ STATE_NAME_XOR_KEY
+    }
+
+    /// Creates the [`StateName`] of a well-known state from the given owner tag, unique id and data scope
+    ///
+    /// This is useful for constructing the names of well-known states from their component fields as published e.g. in
+    /// tables obtained through reverse engineering, instead of hardcoding their opaque values. The owner tag is given
+    /// as a string of up to four printable ASCII characters, which is padded with NUL characters, see
+    /// [`StateNameDescriptor::owner_tag_str`]. The resulting state name has version `1` and does not have permanent
+    /// data. For full control over all properties, convert a [`StateNameDescriptor`] instead.
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use wnf::{DataScope, StateName};
+    ///
+    /// let state_name = StateName::well_known("SHEL", 0x4A, DataScope::System)?;
+    /// assert_eq!(state_name, 0x0D83_063E_A3BE_5075);
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an error if the owner tag is empty, longer than four characters or contains characters that are not
+    /// printable ASCII characters, or if the unique id is invalid (must be less than `2^21`)
+    pub fn well_known(owner_tag: &str, unique_id: u32, data_scope: DataScope) -> Result<Self, WellKnownStateNameError> {
+        let descriptor = StateNameDescriptor {
+            version: 1,
+            lifetime: StateLifetime::WellKnown,
+            data_scope,
+            is_permanent: false,
+            unique_id,
+            owner_tag: parse_owner_tag(owner_tag).ok_or(WellKnownStateNameError::InvalidOwnerTag)?,
+        };
+
+        descriptor.try_into().map_err(|err| match err {
+            StateNameFromDescriptorError::InvalidUniqueId(unique_id) => {
+                WellKnownStateNameError::InvalidUniqueId(unique_id)
+            }
+
+            // The version is always `1` and well-known state names support all data scopes
+            StateNameFromDescriptorError::InvalidVersion(..)
+            | StateNameFromDescriptorError::UnsupportedDataScope { .. } => {
+                unreachable!()
+            }
+        })
+    }
 }
 
 impl From<u64> for StateName {
@@ -161,6 +273,30 @@ impl From<StateName> for u64 {
     }
 }
 
+impl FromStr for StateName {
+    type Err = ParseStateNameError;
+
+    /// Parses a [`StateName`] from the hexadecimal representation of its opaque value
+    ///
+    /// The representation may optionally be prefixed with `0x` or `0X`, so this accepts the output of both the
+    /// [`Display`] and [`UpperHex`]/[`LowerHex`] implementations of [`StateName`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")).unwrap_or(s);
+
+        if digits.is_empty() {
+            return Err(ParseStateNameError::Empty);
+        }
+
+        if !digits.bytes().all(|digit| digit.is_ascii_hexdigit()) {
+            return Err(ParseStateNameError::InvalidDigit);
+        }
+
+        u64::from_str_radix(digits, 16)
+            .map(Self::from_opaque_value)
+            .map_err(|_| ParseStateNameError::Overflow)
+    }
+}
+
 impl PartialEq<u64> for StateName {
     fn eq(&self, other: &u64) -> bool {
         self.opaque_value == *other
@@ -215,6 +351,13 @@ impl TryFrom<StateNameDescriptor> for StateName {
             return Err(StateNameFromDescriptorError::InvalidUniqueId(descriptor.unique_id));
         }
 
+        if !descriptor.lifetime.supports_data_scope(descriptor.data_scope) {
+            return Err(StateNameFromDescriptorError::UnsupportedDataScope {
+                lifetime: descriptor.lifetime,
+                data_scope: descriptor.data_scope,
+            });
+        }
+
         let transparent_value = u64::from(descriptor.version)
             + ((descriptor.lifetime as u64) << 4)
             + ((descriptor.data_scope as u64) << 6)
@@ -222,9 +365,7 @@ impl TryFrom<StateNameDescriptor> for StateName {
             + ((u64::from(descriptor.unique_id)) << 11)
             + ((u64::from(descriptor.owner_tag)) << 32);
 
-        let opaque_value = transparent_value ^ STATE_NAME_XOR_KEY;
-
-        Ok(Self { opaque_value })
+        Ok(Self::from_transparent_value(transparent_value))
     }
 }
 
@@ -232,7 +373,7 @@ impl TryFrom<StateName> for StateNameDescriptor {
     type Error = StateNameDescriptorFromStateNameError;
 
     fn try_from(state_name: StateName) -> Result<Self, Self::Error> {
-        let transparent_value = state_name.opaque_value ^ STATE_NAME_XOR_KEY;
+        let transparent_value = state_name.transparent_value();
 
         let lifetime_value = ((transparent_value >> 4) & 0b11) as u8;
         let data_scope_value = ((transparent_value >> 6) & 0b1111) as u8;
@@ -251,6 +392,33 @@ impl TryFrom<StateName> for StateNameDescriptor {
     }
 }
 
+/// Parses an owner tag from a string of up to four printable ASCII characters, padding it with NUL characters
+///
+/// This is the inverse of [`StateNameDescriptor::owner_tag_str`].
+fn parse_owner_tag(owner_tag: &str) -> Option<u32> {
+    let tag = owner_tag.as_bytes();
+
+    if tag.is_empty() || tag.len() > 4 || !tag.iter().all(u8::is_ascii_graphic) {
+        return None;
+    }
+
+    let mut bytes = [0; 4];
+    bytes[..tag.len()].copy_from_slice(tag);
+    Some(u32::from_le_bytes(bytes))
+}
+
+/// An error creating the [`StateName`] of a well-known state through [`StateName::well_known`]
+#[derive(Clone, Copy, Debug, Error, Eq, Hash, PartialEq)]
+pub enum WellKnownStateNameError {
+    /// The owner tag is invalid (must consist of one to four printable ASCII characters)
+    #[error("invalid owner tag")]
+    InvalidOwnerTag,
+
+    /// The unique id is invalid (must be less than `2^21`)
+    #[error("invalid unique id: {0}")]
+    InvalidUniqueId(u32),
+}
+
 /// An error converting a [`StateNameDescriptor`] into a [`StateName`]
 #[derive(Clone, Copy, Debug, Error, Eq, Hash, PartialEq)]
 pub enum StateNameFromDescriptorError {
@@ -261,6 +429,17 @@ pub enum StateNameFromDescriptorError {
     /// The [`StateNameDescriptor::unique_id`] is invalid (must be less than `2^21`)
     #[error("invalid unique id: {0}")]
     InvalidUniqueId(u32),
+
+    /// The [`StateNameDescriptor::data_scope`] is not supported for the [`StateNameDescriptor::lifetime`], see
+    /// [`StateLifetime::supports_data_scope`]
+    #[error("data scope {data_scope:?} is not supported for state names with lifetime {lifetime:?}")]
+    UnsupportedDataScope {
+        /// The lifetime of the state name
+        lifetime: StateLifetime,
+
+        /// The data scope of the state name
+        data_scope: DataScope,
+    },
 }
 
 /// An error converting a [`StateName`] into a [`StateNameDescriptor`]
@@ -271,6 +450,22 @@ pub enum StateNameDescriptorFromStateNameError {
     InvalidDataScope(u8),
 }
 
+/// An error parsing a [`StateName`] from a string
+#[derive(Clone, Copy, Debug, Error, Eq, Hash, PartialEq)]
+pub enum ParseStateNameError {
+    /// The string contains no digits
+    #[error("state name is empty")]
+    Empty,
+
+    /// The string contains a character that is not a hexadecimal digit
+    #[error("state name contains an invalid hexadecimal digit")]
+    InvalidDigit,
+
+    /// The value represented by the string does not fit into 64 bits
+    #[error("state name is too large to fit into 64 bits")]
+    Overflow,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -285,6 +480,35 @@ mod tests {
         owner_tag: 0x4C45_4853,
     };
 
+    #[test]
+    fn state_name_transparent_value() {
+        assert_eq!(SAMPLE_STATE_NAME.transparent_value(), 0x4C45_4853_0002_5001);
+        assert_eq!(
+            StateName::from_transparent_value(0x4C45_4853_0002_5001),
+            SAMPLE_STATE_NAME
+        );
+    }
+
+    #[test]
+    fn state_name_from_str_success() {
+        assert_eq!("0x0D83063EA3BE5075".parse(), Ok(SAMPLE_STATE_NAME));
+        assert_eq!("0X0d83063ea3be5075".parse(), Ok(SAMPLE_STATE_NAME));
+        assert_eq!("D83063EA3BE5075".parse(), Ok(SAMPLE_STATE_NAME));
+        assert_eq!(SAMPLE_STATE_NAME.to_string().parse(), Ok(SAMPLE_STATE_NAME));
+    }
+
+    #[test]
+    fn state_name_from_str_error() {
+        assert_eq!("".parse::<StateName>(), Err(ParseStateNameError::Empty));
+        assert_eq!("0x".parse::<StateName>(), Err(ParseStateNameError::Empty));
+        assert_eq!("+1234".parse::<StateName>(), Err(ParseStateNameError::InvalidDigit));
+        assert_eq!("0x12G4".parse::<StateName>(), Err(ParseStateNameError::InvalidDigit));
+        assert_eq!(
+            "0x10000000000000000".parse::<StateName>(),
+            Err(ParseStateNameError::Overflow)
+        );
+    }
+
     #[test]
     fn state_name_into_descriptor_success() {
         let result: Result<StateNameDescriptor, _> = SAMPLE_STATE_NAME.try_into();
@@ -335,6 +559,112 @@ mod tests {
         assert_eq!(result, Err(StateNameFromDescriptorError::InvalidUniqueId(1 << 21)));
     }
 
+    #[test]
+    fn descriptor_into_state_name_unsupported_data_scope() {
+        let descriptor = StateNameDescriptor {
+            lifetime: StateLifetime::Temporary,
+            data_scope: DataScope::Process,
+            owner_tag: 0,
+            ..SAMPLE_DESCRIPTOR
+        };
+
+        let result: Result<StateName, _> = descriptor.try_into();
+
+        assert_eq!(
+            result,
+            Err(StateNameFromDescriptorError::UnsupportedDataScope {
+                lifetime: StateLifetime::Temporary,
+                data_scope: DataScope::Process,
+            })
+        );
+    }
+
+    #[test]
+    fn lifetime_supports_data_scope() {
+        assert!(StateLifetime::Temporary.supports_data_scope(DataScope::Machine));
+        assert!(StateLifetime::Persistent.supports_data_scope(DataScope::Process));
+        assert!(!StateLifetime::Temporary.supports_data_scope(DataScope::Process));
+    }
+
+    #[test]
+    fn descriptor_owner_tag_str() {
+        assert_eq!(SAMPLE_DESCRIPTOR.owner_tag_str().as_deref(), Some("SHEL"));
+    }
+
+    #[test]
+    fn well_known() {
+        assert_eq!(
+            StateName::well_known("SHEL", 0x4A, DataScope::System),
+            Ok(SAMPLE_STATE_NAME)
+        );
+    }
+
+    #[test]
+    fn well_known_short_owner_tag() {
+        let state_name = StateName::well_known("PO", 0x4A, DataScope::System).unwrap();
+        let descriptor = StateNameDescriptor::try_from(state_name).unwrap();
+
+        assert_eq!(descriptor.owner_tag, 0x0000_4F50);
+        assert_eq!(descriptor.owner_tag_str().as_deref(), Some("PO"));
+    }
+
+    #[test]
+    fn well_known_invalid_owner_tag() {
+        for owner_tag in ["", "SHELL", "SH L", "SH\u{e4}"] {
+            assert_eq!(
+                StateName::well_known(owner_tag, 0x4A, DataScope::System),
+                Err(WellKnownStateNameError::InvalidOwnerTag)
+            );
+        }
+    }
+
+    #[test]
+    fn well_known_invalid_unique_id() {
+        assert_eq!(
+            StateName::well_known("SHEL", 1 << 21, DataScope::System),
+            Err(WellKnownStateNameError::InvalidUniqueId(1 << 21))
+        );
+    }
+
+    #[test]
+    fn descriptor_owner_tag_str_strips_padding() {
+        let descriptor = StateNameDescriptor {
+            owner_tag: 0x0000_4F50,
+            ..SAMPLE_DESCRIPTOR
+        };
+
+        assert_eq!(descriptor.owner_tag_str().as_deref(), Some("PO"));
+    }
+
+    #[test]
+    fn descriptor_owner_tag_str_empty() {
+        let descriptor = StateNameDescriptor {
+            owner_tag: 0,
+            ..SAMPLE_DESCRIPTOR
+        };
+
+        assert_eq!(descriptor.owner_tag_str(), None);
+    }
+
+    #[test]
+    fn descriptor_owner_tag_str_non_ascii() {
+        let descriptor = StateNameDescriptor {
+            owner_tag: 0x4C45_48FF,
+            ..SAMPLE_DESCRIPTOR
+        };
+
+        assert_eq!(descriptor.owner_tag_str(), None);
+    }
+
+    #[test]
+    fn descriptor_matches_prefix() {
+        assert!(SAMPLE_DESCRIPTOR.matches_prefix("SHEL"));
+        assert!(SAMPLE_DESCRIPTOR.matches_prefix("SH"));
+        assert!(SAMPLE_DESCRIPTOR.matches_prefix(""));
+        assert!(!SAMPLE_DESCRIPTOR.matches_prefix("PO"));
+        assert!(!SAMPLE_DESCRIPTOR.matches_prefix("SHELL"));
+    }
+
     #[test]
     fn state_name_display() {
         assert_eq!(SAMPLE_STATE_NAME.to_string(), "0x0D83063EA3BE5075");
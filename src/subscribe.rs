@@ -1,22 +1,32 @@
 //! Methods for subscribing to state changes
 
+use std::cell::Cell;
+use std::collections::BTreeMap;
 use std::ffi::c_void;
 use std::fmt::{Debug, Display, Formatter};
 use std::hash::{Hash, Hasher};
 use std::marker::PhantomData;
 use std::mem::ManuallyDrop;
-use std::sync::Mutex;
-use std::{fmt, io, mem, panic, ptr};
-
-use tracing::{debug, trace_span};
-use windows::core::GUID;
+#[allow(deprecated)] // `PanicInfo` is deprecated in favor of `PanicHookInfo` in Rust 1.82, but our MSRV is lower
+use std::panic::{AssertUnwindSafe, PanicInfo, UnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard, Once, PoisonError, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+use std::{any, fmt, io, mem, panic, ptr, slice};
+
+use tracing::{field, trace_span, warn};
 use windows::Win32::Foundation::{NTSTATUS, STATUS_SUCCESS};
 
+use crate::bytes::CheckedBitPattern;
+use crate::capabilities::os_capabilities;
 use crate::data::{ChangeStamp, StampedData};
 use crate::ntapi;
-use crate::read::Read;
+use crate::read::{self, Read};
+use crate::shutdown::ListenerCallGuard;
 use crate::state::{BorrowedState, OwnedState, RawState};
 use crate::state_name::StateName;
+use crate::trace::{ntapi_event, TracedStateName, WnfRoutine};
+use crate::type_id::GUID;
 
 /// A trait for types that are capable of listening to state updates
 ///
@@ -33,6 +43,21 @@ where
     /// The provided [`DataAccessor<'_, T>`](DataAccessor) can be used to obtain the state data at the time the update
     /// took place.
     fn call(&mut self, accessor: DataAccessor<'_, T>);
+
+    /// Notifies this state listener about an error that occurred while processing a state update
+    ///
+    /// This is called instead of [`call`](StateListener::call) if `wnf` fails to prepare the data for the listener,
+    /// e.g. if querying the latest state data for [`DeliveryMode::CoalesceToLatest`] fails. The provided
+    /// [`DataAccessor<'_, T>`](DataAccessor) can be used to obtain the state data as delivered with the update
+    /// notification.
+    ///
+    /// Implementing this method makes it possible to handle such errors centrally instead of having to detect them in
+    /// every call. It is also a natural place to handle errors from [`DataAccessor::get`] and similar methods, which
+    /// implementations of [`call`](StateListener::call) can forward to it.
+    ///
+    /// The default implementation ignores the error.
+    #[allow(unused_variables)]
+    fn on_error(&mut self, err: io::Error, accessor: DataAccessor<'_, T>) {}
 }
 
 impl<F, T> StateListener<T> for F
@@ -45,6 +70,193 @@ where
     }
 }
 
+/// A state listener that can be called through a shared reference
+///
+/// This is the counterpart of [`StateListener<T>`](StateListener) for listeners subscribed through
+/// [`OwnedState::subscribe_lock_free`] or [`BorrowedState::subscribe_lock_free`], which are called without locking.
+/// Its methods take `&self` instead of `&mut self`, so a listener that needs to mutate its own state has to use
+/// interior mutability, e.g. through atomics.
+///
+/// This is implemented for all types implementing [`Fn(DataAccessor<'_, T>)`](Fn).
+pub trait SyncStateListener<T>
+where
+    T: ?Sized,
+{
+    /// Calls this state listener
+    ///
+    /// See [`StateListener::call`]
+    fn call(&self, accessor: DataAccessor<'_, T>);
+
+    /// Notifies this state listener about an error that occurred while processing a state update
+    ///
+    /// See [`StateListener::on_error`]
+    ///
+    /// The default implementation ignores the error.
+    #[allow(unused_variables)]
+    fn on_error(&self, err: io::Error, accessor: DataAccessor<'_, T>) {}
+}
+
+impl<F, T> SyncStateListener<T> for F
+where
+    F: Fn(DataAccessor<'_, T>),
+    T: ?Sized,
+{
+    fn call(&self, accessor: DataAccessor<'_, T>) {
+        self(accessor);
+    }
+}
+
+/// A hook that is called when a state listener panics
+#[allow(deprecated)] // `PanicInfo` is deprecated in favor of `PanicHookInfo` in Rust 1.82, but our MSRV is lower
+type ListenerPanicHook = Box<dyn Fn(&PanicInfo<'_>, StateName) + Send + Sync>;
+
+/// The hook registered through [`set_listener_panic_hook`], if any
+static LISTENER_PANIC_HOOK: RwLock<Option<ListenerPanicHook>> = RwLock::new(None);
+
+/// Makes sure the panic hook dispatching to the [`LISTENER_PANIC_HOOK`] is only installed once
+static INSTALL_LISTENER_PANIC_HOOK: Once = Once::new();
+
+thread_local! {
+    /// The name of the state whose listener is currently running on this thread, if any
+    static LISTENER_STATE_NAME: Cell<Option<StateName>> = const { Cell::new(None) };
+}
+
+/// Registers a process-wide hook that is called whenever a state listener panics
+///
+/// Panics in state listeners are caught by `wnf` because they must not unwind into the WNF API. By default, such a
+/// panic is reported by the standard panic hook (usually printing a message to stderr) and otherwise swallowed. This
+/// function lets you handle it yourself, e.g. by logging it or aborting the process. The hook is passed the
+/// [`PanicInfo`] of the panic and the name of the state whose listener panicked. Note that a panic inside of the hook
+/// itself aborts the process.
+///
+/// Calling this function again replaces the previously registered hook.
+///
+/// This installs a panic hook via [`std::panic::set_hook`] that calls the given hook for panics in state listeners
+/// and the previously installed panic hook for all other panics. In order for this to work, you should not replace
+/// the panic hook afterwards.
+#[allow(deprecated)] // `PanicInfo` is deprecated in favor of `PanicHookInfo` in Rust 1.82, but our MSRV is lower
+pub fn set_listener_panic_hook<F>(hook: F)
+where
+    F: Fn(&PanicInfo<'_>, StateName) + Send + Sync + 'static,
+{
+    *LISTENER_PANIC_HOOK.write().unwrap_or_else(|err| err.into_inner()) = Some(Box::new(hook));
+
+    INSTALL_LISTENER_PANIC_HOOK.call_once(|| {
+        let previous_hook = panic::take_hook();
+
+        panic::set_hook(Box::new(move |panic_info| {
+            if let Some(state_name) = LISTENER_STATE_NAME.with(Cell::get) {
+                if let Some(hook) = LISTENER_PANIC_HOOK
+                    .read()
+                    .unwrap_or_else(|err| err.into_inner())
+                    .as_ref()
+                {
+                    hook(panic_info, state_name);
+                    return;
+                }
+            }
+
+            previous_hook(panic_info);
+        }));
+    });
+}
+
+/// The tracing target of the spans around listener calls
+const LISTENER_TRACING_TARGET: &str = "wnf::listener";
+
+/// The tracing target of the events about slow listener calls
+const SLOW_CALLBACK_TRACING_TARGET: &str = "wnf::slow_callback";
+
+/// The threshold set through [`set_slow_listener_threshold`] in nanoseconds, or [`NO_SLOW_LISTENER_THRESHOLD`]
+static SLOW_LISTENER_THRESHOLD_NANOS: AtomicU64 = AtomicU64::new(NO_SLOW_LISTENER_THRESHOLD);
+
+/// The value of [`SLOW_LISTENER_THRESHOLD_NANOS`] indicating that no threshold is set
+const NO_SLOW_LISTENER_THRESHOLD: u64 = u64::MAX;
+
+/// Sets a process-wide threshold for the duration of state listener calls, above which a warning is emitted
+///
+/// The WNF API runs all listeners within a process sequentially on a single thread, so a single slow listener silently
+/// delays the notifications of all other listeners in the process. When a threshold is set, every listener call taking
+/// longer than the threshold emits a [`tracing`](https://docs.rs/tracing/latest/tracing) event with the target
+/// `wnf::slow_callback` and the level `WARN`, containing the state name, the type name of the listener, the elapsed
+/// time and the threshold as fields.
+///
+/// Passing [`None`] removes the threshold, which is the default.
+pub fn set_slow_listener_threshold(threshold: Option<Duration>) {
+    let nanos = match threshold {
+        Some(threshold) => u64::try_from(threshold.as_nanos()).map_or(NO_SLOW_LISTENER_THRESHOLD - 1, |nanos| {
+            nanos.min(NO_SLOW_LISTENER_THRESHOLD - 1)
+        }),
+        None => NO_SLOW_LISTENER_THRESHOLD,
+    };
+
+    SLOW_LISTENER_THRESHOLD_NANOS.store(nanos, Ordering::Relaxed);
+}
+
+/// Returns the threshold set through [`set_slow_listener_threshold`], if any
+pub fn slow_listener_threshold() -> Option<Duration> {
+    match SLOW_LISTENER_THRESHOLD_NANOS.load(Ordering::Relaxed) {
+        NO_SLOW_LISTENER_THRESHOLD => None,
+        nanos => Some(Duration::from_nanos(nanos)),
+    }
+}
+
+/// Emits a warning if a call of a listener of type `F` to the state with the given name took longer than the
+/// threshold set through [`set_slow_listener_threshold`]
+fn report_slow_listener<F>(state_name: StateName, elapsed: Duration) {
+    if let Some(threshold) = slow_listener_threshold() {
+        if elapsed > threshold {
+            warn!(
+                target: SLOW_CALLBACK_TRACING_TARGET,
+                %state_name,
+                listener.type_name = any::type_name::<F>(),
+                ?elapsed,
+                ?threshold,
+                "State listener exceeded slow listener threshold",
+            );
+        }
+    }
+}
+
+/// Returns whether the current thread is running a listener
+pub(crate) fn is_in_listener() -> bool {
+    LISTENER_STATE_NAME.with(Cell::get).is_some()
+}
+
+/// Guard marking the current thread as running a listener of a given state until it is dropped
+#[derive(Debug)]
+struct ListenerScope;
+
+impl ListenerScope {
+    /// Marks the current thread as running a listener of the given state
+    fn enter(state_name: StateName) -> Self {
+        LISTENER_STATE_NAME.with(|cell| cell.set(Some(state_name)));
+        Self
+    }
+}
+
+impl Drop for ListenerScope {
+    fn drop(&mut self) {
+        LISTENER_STATE_NAME.with(|cell| cell.set(None));
+    }
+}
+
+/// Runs the given closure as a call of a listener of the state with the given name
+///
+/// The call is skipped after [`shutdown`](crate::shutdown) has been called and otherwise tracked until it finishes.
+/// Panics in the closure are reported to the hook set through [`set_listener_panic_hook`] and do not unwind into the
+/// caller.
+///
+/// This returns whether the closure panicked.
+pub(crate) fn call_listener(state_name: StateName, f: impl FnOnce() + UnwindSafe) -> bool {
+    let Some(_call) = ListenerCallGuard::enter() else {
+        return false;
+    };
+
+    let _scope = ListenerScope::enter(state_name);
+    panic::catch_unwind(f).is_err()
+}
+
 /// The change stamp that a state listener has last seen
 ///
 /// The [`OwnedState::subscribe`] and [`BorrowedState::subscribe`] methods expect an argument of this type to
@@ -79,7 +291,79 @@ pub enum SeenChangeStamp {
     /// updates regardless of the passed value.
     ///
     /// This is most useful if you have queried the state data before and are already holding a change stamp.
+    ///
+    /// Passing [`ChangeStamp::initial`] explicitly makes the listener be notified about the current data of the state
+    /// (again only if any data has been written to the state yet), just like [`SeenChangeStamp::None`]. The difference
+    /// is that the listener is assumed to have seen the change stamp `0`, so the [`UpdateKind`] of the first
+    /// notification reports all updates before the current one as missed.
     Value(ChangeStamp),
+
+    /// Indicates that a listener has not seen any change stamp and wants to be notified even if no data has been
+    /// written to the state yet
+    ///
+    /// When subscribing with this value, the listener will be notified once about the current data of the state and
+    /// then again once about each future state update, just like with [`SeenChangeStamp::None`]. However, if no data
+    /// has been written to the state yet, the listener is notified once about empty data with the change stamp `0`
+    /// instead. Note that reading empty data fails for most data types, so the listener should check
+    /// [`DataAccessor::change_stamp`] or read the data as [`OpaqueData`](crate::data::OpaqueData) first.
+    ///
+    /// This is most useful for listeners implementing state machines, because it ensures that they are always called
+    /// at least once and can initialize themselves on the first call.
+    Replay,
+}
+
+/// The mode in which state updates are delivered to a state listener
+///
+/// The WNF API does not queue state updates for a listener. If several updates happen in quick succession, the listener
+/// may only be notified about the latest of them, in which case the change stamps it sees are not consecutive. The
+/// number of updates missed in this way is reported through [`DataAccessor::update_kind`] in both modes.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum DeliveryMode {
+    /// Delivers every notification received from the WNF API about an update the listener has not seen yet
+    ///
+    /// The listener is called once for every such notification, with the data passed along with the notification.
+    /// Notifications whose change stamp is not newer than the one the listener has last seen are skipped, so the
+    /// listener is never called twice for the same update, e.g. when an update replayed on subscribing (see
+    /// [`SeenChangeStamp::Replay`]) is also reported by the WNF API. This is the mode used by
+    /// [`OwnedState::subscribe`] and [`BorrowedState::subscribe`].
+    #[default]
+    EveryChange,
+
+    /// Coalesces notifications to the latest state data
+    ///
+    /// Whenever a gap in the change stamps is detected, the current data of the state are queried and passed to the
+    /// listener instead of the (possibly outdated) data passed along with the notification. Notifications about
+    /// updates the listener has already seen in this way are skipped.
+    CoalesceToLatest,
+}
+
+/// The kind of a state update that a state listener is notified about
+///
+/// This can be obtained through [`DataAccessor::update_kind`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum UpdateKind {
+    /// The update directly follows the one the listener has last seen
+    ///
+    /// This is also the kind of the first update a listener subscribed with [`SeenChangeStamp::None`] is notified
+    /// about.
+    Sequential,
+
+    /// The update was preceded by updates the listener has not been notified about
+    Coalesced {
+        /// The number of updates the listener has not been notified about
+        missed: u32,
+    },
+}
+
+impl UpdateKind {
+    /// Creates an [`UpdateKind`] from the given number of missed updates
+    pub(crate) const fn from_missed(missed: u32) -> Self {
+        if missed == 0 {
+            Self::Sequential
+        } else {
+            Self::Coalesced { missed }
+        }
+    }
 }
 
 impl<T> OwnedState<T>
@@ -135,6 +419,269 @@ where
     {
         self.raw.subscribe(listener, last_seen_change_stamp)
     }
+
+    /// Subscribes the given state listener to this state forever, leaking the state
+    ///
+    /// This is the same as calling [`subscribe`](OwnedState::subscribe), [`Subscription::forget`] on the returned
+    /// subscription and then [`leak`](OwnedState::leak) on this state, but in a single call. Forgetting a
+    /// subscription to an owned state without leaking the state is usually a mistake because dropping the
+    /// [`OwnedState<T>`] deletes the state, so the forgotten listener would never be called again. Consuming the state
+    /// makes this mistake impossible.
+    ///
+    /// It returns a [`BorrowedState<'static, T>`](BorrowedState) representing the leaked state.
+    ///
+    /// See [`subscribe`](OwnedState::subscribe) for the meaning of the `last_seen_change_stamp` argument.
+    ///
+    /// # Errors
+    /// Returns an error if subscribing fails. In this case, this [`OwnedState<T>`] is dropped as usual, i.e. the state
+    /// is deleted according to its [`DropPolicy`](crate::DropPolicy).
+    pub fn subscribe_forever<F>(
+        self,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+    ) -> io::Result<BorrowedState<'static, T>>
+    where
+        F: StateListener<T> + Send + 'static,
+    {
+        self.raw.subscribe(listener, last_seen_change_stamp)?.forget();
+        Ok(self.leak())
+    }
+
+    /// Subscribes the given state listener to this state using the given delivery mode
+    ///
+    /// This is the same as [`subscribe`](OwnedState::subscribe), except that it lets you choose how notifications are
+    /// delivered to the listener in case it misses state updates. See [`DeliveryMode`] for the available options.
+    ///
+    /// # Errors
+    /// Returns an error if subscribing fails
+    pub fn subscribe_with_delivery_mode<F>(
+        &self,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+        delivery_mode: DeliveryMode,
+    ) -> io::Result<Subscription<'_, F>>
+    where
+        F: StateListener<T> + Send + 'static,
+    {
+        self.raw
+            .subscribe_with_delivery_mode(listener, last_seen_change_stamp, delivery_mode)
+    }
+
+    /// Subscribes the given state listener to this state, calling it without locking
+    ///
+    /// This is the same as [`subscribe`](OwnedState::subscribe), except that the listener is called through a shared
+    /// reference instead of behind a [`Mutex`]. This avoids acquiring a lock on every state update, which can reduce
+    /// the latency of notifications for frequently updated states. In return, the listener must implement
+    /// [`SyncStateListener<T>`](SyncStateListener) and be [`Sync`].
+    ///
+    /// There are the following trade-offs compared to [`subscribe`](OwnedState::subscribe):
+    /// - If unsubscribing fails, a call of the listener that is already in progress is not waited for, so the listener
+    ///   may still be running (or about to be called one last time) when [`Subscription::unsubscribe`] returns or the
+    ///   [`Subscription<'_, F>`](Subscription) has been dropped. In this case, the listener itself (rather than a value
+    ///   the size of a [`Mutex<Option<F>>`]) is leaked on the heap until unsubscribing is retried successfully (see
+    ///   [`gc_failed_unsubscriptions`]), because it cannot be determined when such a call has finished.
+    /// - If the listener panics, it keeps being called for later updates, whereas a listener subscribed through
+    ///   [`subscribe`](OwnedState::subscribe) is not called anymore because its mutex is poisoned.
+    /// - Calls of the listener are not serialized, so if the WNF API ran them in parallel, the listener could be called
+    ///   concurrently. In practice, the WNF API runs all listeners within a process sequentially on a single thread.
+    ///
+    /// If unsubscribing succeeds, the listener is not called anymore afterwards, just as with
+    /// [`subscribe`](OwnedState::subscribe).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use std::sync::atomic::{AtomicU32, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// use wnf::{DataAccessor, OwnedState, SeenChangeStamp};
+    ///
+    /// let state = OwnedState::<u32>::create_temporary()?;
+    /// state.set(&0)?;
+    ///
+    /// let latest = Arc::new(AtomicU32::new(0));
+    ///
+    /// let _subscription = state.subscribe_lock_free(
+    ///     {
+    ///         let latest = Arc::clone(&latest);
+    ///         move |accessor: DataAccessor<_>| {
+    ///             if let Ok(value) = accessor.get() {
+    ///                 latest.store(value, Ordering::Release);
+    ///             }
+    ///         }
+    ///     },
+    ///     SeenChangeStamp::Current,
+    /// )?;
+    ///
+    /// state.set(&1)?;
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an error if subscribing fails
+    pub fn subscribe_lock_free<F>(
+        &self,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+    ) -> io::Result<Subscription<'_, F>>
+    where
+        F: SyncStateListener<T> + Send + Sync + 'static,
+    {
+        self.raw.subscribe_lock_free(listener, last_seen_change_stamp)
+    }
+
+    /// Subscribes the given closure to pulses of this state
+    ///
+    /// The closure is only called for updates with empty data (see [`DataAccessor::is_pulse`]), such as those
+    /// performed through [`pulse`](OwnedState::pulse), and receives the change stamp of the update. Updates with
+    /// non-empty data are ignored. This makes it possible to use a state as an event trigger without dealing with its
+    /// data.
+    ///
+    /// See [`subscribe`](OwnedState::subscribe) for the meaning of the `last_seen_change_stamp` argument and for how
+    /// unsubscribing works.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use std::sync::mpsc;
+    ///
+    /// use wnf::{OwnedState, SeenChangeStamp};
+    ///
+    /// let state = OwnedState::<u32>::create_temporary()?;
+    ///
+    /// let (tx, rx) = mpsc::channel();
+    /// let _subscription = state.subscribe_pulses(
+    ///     move |change_stamp| tx.send(change_stamp).unwrap(),
+    ///     SeenChangeStamp::Current,
+    /// )?;
+    ///
+    /// state.set(&42)?;
+    /// state.pulse()?;
+    /// assert_eq!(rx.recv()?, 2);
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an error if subscribing fails
+    pub fn subscribe_pulses<F>(
+        &self,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+    ) -> io::Result<Subscription<'_, PulseListener<F>>>
+    where
+        F: FnMut(ChangeStamp) + Send + 'static,
+    {
+        self.raw.subscribe(PulseListener::new(listener), last_seen_change_stamp)
+    }
+
+    /// Subscribes the given closure to the change stamps of this state
+    ///
+    /// The closure is called for every state update and receives only the change stamp of the update, regardless of
+    /// whether the update has empty data. This is meant for listeners that only need to know that the state has changed
+    /// and query the data lazily later, if at all.
+    ///
+    /// The WNF API always delivers the state data together with an update notification, so this does not save the
+    /// kernel from copying them into the buffer passed to the callback. However, the data are never read from that
+    /// buffer, so no copies of the data are made in user mode, and no error can occur from reading them.
+    ///
+    /// See [`subscribe`](OwnedState::subscribe) for the meaning of the `last_seen_change_stamp` argument and for how
+    /// unsubscribing works.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use std::sync::mpsc;
+    ///
+    /// use wnf::{OwnedState, SeenChangeStamp};
+    ///
+    /// let state = OwnedState::<[u8]>::create_temporary()?;
+    ///
+    /// let (tx, rx) = mpsc::channel();
+    /// let _subscription = state.subscribe_stamps(
+    ///     move |change_stamp| tx.send(change_stamp).unwrap(),
+    ///     SeenChangeStamp::Current,
+    /// )?;
+    ///
+    /// state.set(&[0; 4096])?;
+    /// assert_eq!(rx.recv()?, 1);
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an error if subscribing fails
+    pub fn subscribe_stamps<F>(
+        &self,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+    ) -> io::Result<Subscription<'_, StampListener<F>>>
+    where
+        F: FnMut(ChangeStamp) + Send + 'static,
+    {
+        self.raw.subscribe(StampListener::new(listener), last_seen_change_stamp)
+    }
+
+    /// Subscribes the given one-shot closure to this state
+    ///
+    /// This is the same as [`subscribe`](OwnedState::subscribe), except that the listener is a closure implementing
+    /// [`FnOnce`] rather than [`FnMut`], so it can consume the resources it captures, e.g. a one-shot channel. It is
+    /// called for the first state update only.
+    ///
+    /// When the listener is called, the subscription is unsubscribed automatically: It is removed from
+    /// [`active_subscriptions`] right away, and it is removed from the WNF API outside of the listener, since this may
+    /// fail from within a listener. This happens the next time a subscription is created or unsubscribed through this
+    /// crate, when [`Subscription::is_done`] or [`gc_failed_unsubscriptions`] is called, or at the latest when the
+    /// returned [`Subscription<'_, F>`](Subscription) is dropped. Until then, further updates are ignored. Dropping or
+    /// unsubscribing the [`Subscription<'_, F>`](Subscription) after that is not necessary, but it is harmless.
+    ///
+    /// Errors that occur while processing a state update (see [`StateListener::on_error`]) are ignored and don't count
+    /// as the first delivery.
+    ///
+    /// See [`subscribe`](OwnedState::subscribe) for the meaning of the `last_seen_change_stamp` argument.
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use std::sync::mpsc;
+    ///
+    /// use wnf::{DataAccessor, OwnedState, SeenChangeStamp};
+    ///
+    /// let state = OwnedState::<u32>::create_temporary()?;
+    ///
+    /// let (tx, rx) = mpsc::channel();
+    /// let subscription = state.subscribe_once(
+    ///     move |accessor: DataAccessor<_>| {
+    ///         tx.send(accessor.get().unwrap()).unwrap();
+    ///         drop(tx);
+    ///     },
+    ///     SeenChangeStamp::Current,
+    /// )?;
+    ///
+    /// state.set(&42)?;
+    /// assert_eq!(rx.recv()?, 42);
+    ///
+    /// // The sender has been dropped together with the listener, so the channel is closed
+    /// assert!(rx.recv().is_err());
+    ///
+    /// // The subscription has been unsubscribed automatically
+    /// assert!(subscription.is_done());
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an error if subscribing fails
+    pub fn subscribe_once<F>(
+        &self,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+    ) -> io::Result<Subscription<'_, OnceListener<F>>>
+    where
+        F: FnOnce(DataAccessor<'_, T>) + Send + 'static,
+    {
+        self.raw.subscribe_once(listener, last_seen_change_stamp)
+    }
 }
 
 impl<'a, T> BorrowedState<'a, T>
@@ -150,47 +697,454 @@ where
     {
         self.raw.subscribe(listener, last_seen_change_stamp)
     }
-}
 
-impl<T> RawState<T>
-where
-    T: ?Sized,
-{
-    /// Subscribes the given state listener to this state
-    pub(crate) fn subscribe<'a, F>(
-        &self,
+    /// Subscribes the given state listener to this state using the given delivery mode
+    ///
+    /// See [`OwnedState::subscribe_with_delivery_mode`]
+    pub fn subscribe_with_delivery_mode<F>(
+        self,
         listener: F,
         last_seen_change_stamp: SeenChangeStamp,
+        delivery_mode: DeliveryMode,
     ) -> io::Result<Subscription<'a, F>>
     where
         F: StateListener<T> + Send + 'static,
     {
-        extern "system" fn callback<F, T>(
-            state_name: u64,
-            change_stamp: u32,
-            _type_id: *const GUID,
-            context: *mut c_void,
-            buffer: *const c_void,
-            buffer_size: u32,
-        ) -> NTSTATUS
-        where
-            F: StateListener<T> + Send + 'static,
-            T: ?Sized,
-        {
-            let _ = panic::catch_unwind(|| {
-                let span = trace_span!(
-                    target: ntapi::TRACING_TARGET,
-                    "WnfUserCallback",
-                    input.state_name = %StateName::from_opaque_value(state_name),
-                    input.change_stamp = change_stamp,
-                    input.buffer_size = buffer_size
-                );
-                let _enter = span.enter();
+        self.raw
+            .subscribe_with_delivery_mode(listener, last_seen_change_stamp, delivery_mode)
+    }
 
-                // SAFETY:
-                // (1) By the assumption on `RtlSubscribeWnfStateChangeNotification`, `context` is the pointer passed in
-                // the fifth argument of some successful call to that function. Let `subscription_handle` be the
-                // subscription handle returned from that call. Then `context` points to a `SubscriptionContext<F>`
+    /// Subscribes the given state listener to this state, calling it without locking
+    ///
+    /// See [`OwnedState::subscribe_lock_free`]
+    pub fn subscribe_lock_free<F>(
+        self,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+    ) -> io::Result<Subscription<'a, F>>
+    where
+        F: SyncStateListener<T> + Send + Sync + 'static,
+    {
+        self.raw.subscribe_lock_free(listener, last_seen_change_stamp)
+    }
+
+    /// Subscribes the given closure to pulses of this state
+    ///
+    /// See [`OwnedState::subscribe_pulses`]
+    pub fn subscribe_pulses<F>(
+        self,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+    ) -> io::Result<Subscription<'a, PulseListener<F>>>
+    where
+        F: FnMut(ChangeStamp) + Send + 'static,
+    {
+        self.raw.subscribe(PulseListener::new(listener), last_seen_change_stamp)
+    }
+
+    /// Subscribes the given closure to the change stamps of this state
+    ///
+    /// See [`OwnedState::subscribe_stamps`]
+    pub fn subscribe_stamps<F>(
+        self,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+    ) -> io::Result<Subscription<'a, StampListener<F>>>
+    where
+        F: FnMut(ChangeStamp) + Send + 'static,
+    {
+        self.raw.subscribe(StampListener::new(listener), last_seen_change_stamp)
+    }
+
+    /// Subscribes the given one-shot closure to this state
+    ///
+    /// See [`OwnedState::subscribe_once`]
+    pub fn subscribe_once<F>(
+        self,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+    ) -> io::Result<Subscription<'a, OnceListener<F>>>
+    where
+        F: FnOnce(DataAccessor<'_, T>) + Send + 'static,
+    {
+        self.raw.subscribe_once(listener, last_seen_change_stamp)
+    }
+}
+
+impl<T> OwnedState<T>
+where
+    T: Read<T>,
+{
+    /// Subscribes the given listener to this state, passing it the state data as an owned value
+    ///
+    /// In contrast to [`subscribe`](OwnedState::subscribe), this reads the state data *before* calling the listener and
+    /// passes it a [`StampedData<T>`] (or the error that occurred while reading) instead of a
+    /// [`DataAccessor<'_, T>`](DataAccessor). As the data have no lifetime, the listener can forward them, e.g. to a
+    /// channel, without having to deal with the lifetime of a [`DataAccessor<'_, T>`](DataAccessor).
+    ///
+    /// This produces an owned `T` on the stack and hence requires `T: Sized`. In order to produce a `Box<T>` for
+    /// `T: ?Sized`, use the [`subscribe_owned_boxed`](OwnedState::subscribe_owned_boxed) method.
+    ///
+    /// See [`subscribe`](OwnedState::subscribe) for the meaning of the `last_seen_change_stamp` argument and for how
+    /// unsubscribing works.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use std::sync::mpsc;
+    ///
+    /// use wnf::{OwnedState, SeenChangeStamp};
+    ///
+    /// let state = OwnedState::<u32>::create_temporary()?;
+    ///
+    /// let (tx, rx) = mpsc::channel();
+    /// let _subscription = state.subscribe_owned(move |result| tx.send(result).unwrap(), SeenChangeStamp::Current)?;
+    ///
+    /// state.set(&1)?;
+    /// assert_eq!(rx.recv()??.into_data(), 1);
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an error if subscribing fails
+    pub fn subscribe_owned<F>(
+        &self,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+    ) -> io::Result<Subscription<'_, OwnedListener<F, T>>>
+    where
+        F: FnMut(io::Result<StampedData<T>>) + Send + 'static,
+    {
+        self.raw.subscribe(OwnedListener::new(listener), last_seen_change_stamp)
+    }
+
+    /// Queries the data of this state and subscribes the given state listener to all updates after that
+    ///
+    /// This returns the current state data together with a subscription of the listener. The listener is notified
+    /// about exactly those state updates whose change stamp is larger than the change stamp of the returned data, even
+    /// if they happen while subscribing. This means that the returned data and the data passed to the listener are
+    /// always consistent: no update is missed and no update is reported twice.
+    ///
+    /// This is useful if you need the current state data synchronously, e.g. for initializing a UI, and then want to be
+    /// notified about changes. Subscribing with [`SeenChangeStamp::None`] instead would pass the current data to the
+    /// listener asynchronously.
+    ///
+    /// This produces an owned `T` on the stack and hence requires `T: Sized`. In order to produce a `Box<T>` for
+    /// `T: ?Sized`, use the [`subscribe_boxed_with_snapshot`](OwnedState::subscribe_boxed_with_snapshot) method.
+    ///
+    /// See [`subscribe`](OwnedState::subscribe) for how unsubscribing works.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use wnf::{DataAccessor, OwnedState};
+    ///
+    /// let state = OwnedState::<u32>::create_temporary()?;
+    /// state.set(&0)?;
+    ///
+    /// let (snapshot, _subscription) = state.subscribe_with_snapshot(|accessor: DataAccessor<_>| {
+    ///     let value = accessor.get().unwrap();
+    ///     println!("State data updated: {value}");
+    /// })?;
+    ///
+    /// println!("Initial state data: {}", snapshot.data());
+    ///
+    /// state.set(&1)?;
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// This prints:
+    /// ```no_compile
+    /// Initial state data: 0
+    /// State data updated: 1
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an error if querying the state data or subscribing fails
+    pub fn subscribe_with_snapshot<F>(&self, listener: F) -> io::Result<(StampedData<T>, Subscription<'_, F>)>
+    where
+        F: StateListener<T> + Send + 'static,
+    {
+        self.raw.subscribe_with_snapshot(listener)
+    }
+}
+
+impl<T> OwnedState<T>
+where
+    T: Read<Box<T>> + ?Sized,
+{
+    /// Subscribes the given listener to this state, passing it the state data as a box
+    ///
+    /// This is the same as [`subscribe_owned`](OwnedState::subscribe_owned), except that it produces a [`Box<T>`]
+    /// instead of an owned `T` on the stack (requiring `T: Sized`).
+    ///
+    /// # Errors
+    /// Returns an error if subscribing fails
+    pub fn subscribe_owned_boxed<F>(
+        &self,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+    ) -> io::Result<Subscription<'_, OwnedListener<F, Box<T>>>>
+    where
+        F: FnMut(io::Result<StampedData<Box<T>>>) + Send + 'static,
+    {
+        self.raw.subscribe(OwnedListener::new(listener), last_seen_change_stamp)
+    }
+
+    /// Queries the data of this state as a box and subscribes the given state listener to all updates after that
+    ///
+    /// This is the same as [`subscribe_with_snapshot`](OwnedState::subscribe_with_snapshot), except that it produces
+    /// a [`Box<T>`] instead of an owned `T` on the stack (requiring `T: Sized`).
+    ///
+    /// # Errors
+    /// Returns an error if querying the state data or subscribing fails
+    pub fn subscribe_boxed_with_snapshot<F>(
+        &self,
+        listener: F,
+    ) -> io::Result<(StampedData<Box<T>>, Subscription<'_, F>)>
+    where
+        F: StateListener<T> + Send + 'static,
+    {
+        self.raw.subscribe_with_snapshot(listener)
+    }
+}
+
+impl<'a, T> BorrowedState<'a, T>
+where
+    T: Read<T>,
+{
+    /// Subscribes the given listener to this state, passing it the state data as an owned value
+    ///
+    /// See [`OwnedState::subscribe_owned`]
+    pub fn subscribe_owned<F>(
+        self,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+    ) -> io::Result<Subscription<'a, OwnedListener<F, T>>>
+    where
+        F: FnMut(io::Result<StampedData<T>>) + Send + 'static,
+    {
+        self.raw.subscribe(OwnedListener::new(listener), last_seen_change_stamp)
+    }
+
+    /// Queries the data of this state and subscribes the given state listener to all updates after that
+    ///
+    /// See [`OwnedState::subscribe_with_snapshot`]
+    pub fn subscribe_with_snapshot<F>(self, listener: F) -> io::Result<(StampedData<T>, Subscription<'a, F>)>
+    where
+        F: StateListener<T> + Send + 'static,
+    {
+        self.raw.subscribe_with_snapshot(listener)
+    }
+}
+
+impl<'a, T> BorrowedState<'a, T>
+where
+    T: Read<Box<T>> + ?Sized,
+{
+    /// Subscribes the given listener to this state, passing it the state data as a box
+    ///
+    /// See [`OwnedState::subscribe_owned_boxed`]
+    pub fn subscribe_owned_boxed<F>(
+        self,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+    ) -> io::Result<Subscription<'a, OwnedListener<F, Box<T>>>>
+    where
+        F: FnMut(io::Result<StampedData<Box<T>>>) + Send + 'static,
+    {
+        self.raw.subscribe(OwnedListener::new(listener), last_seen_change_stamp)
+    }
+
+    /// Queries the data of this state as a box and subscribes the given state listener to all updates after that
+    ///
+    /// See [`OwnedState::subscribe_boxed_with_snapshot`]
+    pub fn subscribe_boxed_with_snapshot<F>(self, listener: F) -> io::Result<(StampedData<Box<T>>, Subscription<'a, F>)>
+    where
+        F: StateListener<T> + Send + 'static,
+    {
+        self.raw.subscribe_with_snapshot(listener)
+    }
+}
+
+impl<T> RawState<T>
+where
+    T: ?Sized,
+{
+    /// Subscribes the given state listener to this state
+    pub(crate) fn subscribe<'a, F>(
+        &self,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+    ) -> io::Result<Subscription<'a, F>>
+    where
+        F: StateListener<T> + Send + 'static,
+    {
+        self.subscribe_with_delivery_mode(listener, last_seen_change_stamp, DeliveryMode::EveryChange)
+    }
+
+    /// Queries the data of this state as a value of type `D` and subscribes the given state listener to all updates
+    /// after that
+    pub(crate) fn subscribe_with_snapshot<'a, D, F>(
+        &self,
+        listener: F,
+    ) -> io::Result<(StampedData<D>, Subscription<'a, F>)>
+    where
+        T: Read<D>,
+        F: StateListener<T> + Send + 'static,
+    {
+        let snapshot = self.query_as()?;
+
+        // Subscribing with the change stamp of the snapshot ensures that updates happening between querying and
+        // subscribing are not missed
+        let subscription = self.subscribe(listener, SeenChangeStamp::Value(snapshot.change_stamp()))?;
+
+        Ok((snapshot, subscription))
+    }
+
+    /// Subscribes the given state listener to this state using the given delivery mode
+    pub(crate) fn subscribe_with_delivery_mode<'a, F>(
+        &self,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+        delivery_mode: DeliveryMode,
+    ) -> io::Result<Subscription<'a, F>>
+    where
+        F: StateListener<T> + Send + 'static,
+    {
+        // SAFETY:
+        // `F: 'static`
+        unsafe {
+            self.subscribe_context(
+                |tracker| SubscriptionContext::new(listener, tracker),
+                last_seen_change_stamp,
+                delivery_mode,
+            )
+        }
+    }
+
+    /// Subscribes the given state listener to this state, allowing it to borrow data
+    ///
+    /// # Safety
+    /// The returned [`Subscription<'a, F>`](Subscription) must be dropped or unsubscribed before the end of any
+    /// lifetime contained in `F`
+    pub(crate) unsafe fn subscribe_non_static<'a, F>(
+        &self,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+    ) -> io::Result<Subscription<'a, F>>
+    where
+        F: StateListener<T> + Send,
+    {
+        // SAFETY:
+        // - The context is created through `SubscriptionContext::new`
+        // - The condition on the returned subscription is guaranteed by the safety conditions of this function
+        unsafe {
+            self.subscribe_context(
+                |tracker| SubscriptionContext::new(listener, tracker),
+                last_seen_change_stamp,
+                DeliveryMode::EveryChange,
+            )
+        }
+    }
+
+    /// Subscribes the given state listener to this state, calling it without locking
+    pub(crate) fn subscribe_lock_free<'a, F>(
+        &self,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+    ) -> io::Result<Subscription<'a, F>>
+    where
+        F: SyncStateListener<T> + Send + Sync + 'static,
+    {
+        // SAFETY:
+        // `F: 'static`
+        unsafe {
+            self.subscribe_context(
+                |tracker| SubscriptionContext::new_lock_free(listener, tracker),
+                last_seen_change_stamp,
+                DeliveryMode::EveryChange,
+            )
+        }
+    }
+
+    /// Subscribes the given one-shot closure to this state, retiring the subscription once it is called
+    pub(crate) fn subscribe_once<'a, F>(
+        &self,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+    ) -> io::Result<Subscription<'a, OnceListener<F>>>
+    where
+        F: FnOnce(DataAccessor<'_, T>) + Send + 'static,
+    {
+        // SAFETY:
+        // `F: 'static`
+        unsafe {
+            self.subscribe_context(
+                |tracker| SubscriptionContext::new_once(OnceListener::new(listener), tracker),
+                last_seen_change_stamp,
+                DeliveryMode::EveryChange,
+            )
+        }
+    }
+
+    /// Subscribes to this state with the subscription context produced by the given closure
+    ///
+    /// # Safety
+    /// Unless `F: 'static`:
+    /// - The context must be created through [`SubscriptionContext::new`]
+    /// - The returned [`Subscription<'a, F>`](Subscription) must be dropped or unsubscribed before the end of any
+    ///   lifetime contained in `F`
+    unsafe fn subscribe_context<'a, F>(
+        &self,
+        new_context: impl FnOnce(ChangeTracker) -> SubscriptionContext<F>,
+        last_seen_change_stamp: SeenChangeStamp,
+        delivery_mode: DeliveryMode,
+    ) -> io::Result<Subscription<'a, F>>
+    where
+        F: Send,
+    {
+        extern "system" fn callback<F>(
+            state_name: u64,
+            change_stamp: u32,
+            type_id: *const windows::core::GUID,
+            context: *mut c_void,
+            buffer: *const c_void,
+            buffer_size: u32,
+        ) -> NTSTATUS
+        where
+            F: Send,
+        {
+            call_listener(StateName::from_opaque_value(state_name), || {
+                let span = trace_span!(
+                    target: ntapi::TRACING_TARGET,
+                    "WnfUserCallback",
+                    input.state_name = %StateName::from_opaque_value(state_name),
+                    input.state_name.lifetime = field::Empty,
+                    input.state_name.data_scope = field::Empty,
+                    input.state_name.unique_id = field::Empty,
+                    input.state_name.owner_tag = field::Empty,
+                    input.change_stamp = change_stamp,
+                    input.buffer_size = buffer_size
+                );
+
+                // Only decode the state name if the span is enabled
+                if !span.is_disabled() {
+                    let traced_state_name = TracedStateName::new(StateName::from_opaque_value(state_name));
+                    span.record("input.state_name.lifetime", traced_state_name.lifetime);
+                    span.record("input.state_name.data_scope", traced_state_name.data_scope);
+                    span.record("input.state_name.unique_id", traced_state_name.unique_id);
+                    span.record("input.state_name.owner_tag", traced_state_name.owner_tag);
+                }
+
+                let _enter = span.enter();
+
+                // SAFETY:
+                // (1) By the assumption on `RtlSubscribeWnfStateChangeNotification`, `context` is the pointer passed in
+                // the fifth argument of some successful call to that function. Let `subscription_handle` be the
+                // subscription handle returned from that call. Then `context` points to a `SubscriptionContext<F>`
                 // holding this `subscription_handle`.
                 //
                 // (2) By
@@ -211,14 +1165,25 @@ where
                 // `SubscriptionContext<F>` hasn't been dropped either.
                 //
                 // In case (b) the `Subscription<'a, F>` has been dropped but the `SubscriptionContext<F>` it
-                // contains has been leaked (see comment below).
+                // contains has been handed over to the list of failed unsubscriptions, which only drops it after a
+                // call to `RtlUnsubscribeWnfStateChangeNotification` with `subscription_handle` has succeeded.
+                //
+                // If the subscription has been retired, the `Subscription<'a, F>` may have been dropped even in case
+                // (a), but then it has left the `SubscriptionContext<F>` to the list of retired subscriptions, which
+                // likewise only drops it after a call to `RtlUnsubscribeWnfStateChangeNotification` with
+                // `subscription_handle` has succeeded.
                 //
                 // In any case, `context` points to a valid `SubscriptionContext<F>`.
                 //
                 // (3) We may be on a different thread than the one that created the `SubscriptionContext<F>`, but
-                // `F: Send` implies `SubscriptionContext<F>: Send`.
+                // `F: Send` implies `SubscriptionContext<F>: Sync`.
                 //
-                // (4) `F` outlives the lifetime of the produced reference because `F: 'static`.
+                // (4) If `F: 'static` (which is the case for retired subscriptions), then `F` outlives the lifetime of
+                // the produced reference. Otherwise, by the safety conditions of `subscribe_context`,
+                // in case (a) the lifetimes contained in `F` have not ended yet because the
+                // `Subscription<'a, F>` has not been dropped. In case (b) the context was created
+                // through `SubscriptionContext::new`, so clearing it has removed the listener, and hence no value of
+                // type `F` is accessed through the produced reference.
                 let context: &SubscriptionContext<F> = unsafe { &*context.cast() };
 
                 // SAFETY:
@@ -226,24 +1191,60 @@ where
                 //   is satisfied
                 // - As `data` is dropped before `callback` returns, the assumption on `WnfUserCallback` then implies
                 //   the safety conditions of `ScopedData::new`
-                let data = unsafe { ScopedData::new(buffer, buffer_size as usize, change_stamp) };
+                // - `context.stats` is live for as long as `data` is live because `context` is valid until `callback`
+                //   returns (see above)
+                let data =
+                    unsafe { ScopedData::new(buffer, buffer_size as usize, change_stamp).with_stats(&context.stats) };
 
-                context.with_listener(|listener| {
-                    listener.call(data.accessor());
-                });
+                // SAFETY:
+                // By the assumption on `RtlSubscribeWnfStateChangeNotification`, the assumption on `WnfUserCallback` is
+                // satisfied, so `type_id` is either a null pointer or points to a valid `GUID`
+                let type_id = unsafe { type_id.as_ref() }.copied().map(GUID::from_raw);
+                let data = data.with_type_id(type_id);
+
+                let listener_span = trace_span!(
+                    target: LISTENER_TRACING_TARGET,
+                    "StateListener",
+                    listener.type_name = any::type_name::<F>()
+                );
+                let _enter_listener = listener_span.enter();
+
+                let start = Instant::now();
+                context.dispatch(data);
+                report_slow_listener::<F>(StateName::from_opaque_value(state_name), start.elapsed());
             });
 
             STATUS_SUCCESS
         }
 
+        os_capabilities().ensure_type_id(self.type_id)?;
+
+        let replay = last_seen_change_stamp == SeenChangeStamp::Replay;
+
         let change_stamp = match last_seen_change_stamp {
-            SeenChangeStamp::None => ChangeStamp::initial(),
+            SeenChangeStamp::None | SeenChangeStamp::Replay => ChangeStamp::initial(),
             SeenChangeStamp::Current => self.change_stamp()?,
             SeenChangeStamp::Value(value) => value,
         };
 
+        let last_seen_change_stamp = match last_seen_change_stamp {
+            SeenChangeStamp::None | SeenChangeStamp::Replay => None,
+            SeenChangeStamp::Current | SeenChangeStamp::Value(..) => Some(change_stamp),
+        };
+
         let mut subscription_handle = SubscriptionHandle::null();
-        let context = Box::new(SubscriptionContext::new(listener));
+        let context = Box::new(new_context(ChangeTracker::new(
+            self.cast(),
+            delivery_mode,
+            last_seen_change_stamp,
+        )));
+
+        // The replay happens before subscribing so that it cannot run concurrently with a notification by the WNF API
+        // and a panicking listener cannot cause the context to be dropped while it is still in use by the WNF API. An
+        // update happening in between is still reported because we subscribe with the change stamp `0`.
+        if replay {
+            replay_empty(self.cast(), &context);
+        }
 
         // SAFETY:
         // - The pointer in the first argument is valid for writes of `*mut c_void` because it comes from a live mutable
@@ -257,7 +1258,7 @@ where
                 &mut subscription_handle as *mut SubscriptionHandle as *mut *mut c_void,
                 self.state_name.opaque_value(),
                 change_stamp.into(),
-                callback::<F, T>,
+                callback::<F>,
                 &*context as *const SubscriptionContext<F> as *mut c_void,
                 self.type_id.as_ptr(),
                 0,
@@ -266,12 +1267,18 @@ where
         };
 
         if result.is_ok() {
+            ActiveSubscriptions::register::<F>(subscription_handle, self.state_name);
+
+            // The listener may already have been called, so this may retire the subscription right away
+            context.set_subscription_handle(subscription_handle);
             let subscription = Subscription::new(context, subscription_handle);
+            unsubscribe_retired_subscriptions();
 
-            debug!(
-                target: ntapi::TRACING_TARGET,
-                ?result,
+            ntapi_event!(
+                WnfRoutine::SubscribeStateChangeNotification,
+                false,
                 input.state_name = %self.state_name,
+                ?result,
                 input.change_stamp = %change_stamp,
                 input.type_id = %self.type_id,
                 output.subscription_handle = %subscription_handle,
@@ -280,514 +1287,2349 @@ where
 
             Ok(subscription)
         } else {
-            debug!(
-                target: ntapi::TRACING_TARGET,
-                ?result,
+            ntapi_event!(
+                WnfRoutine::SubscribeStateChangeNotification,
+                true,
                 input.state_name = %self.state_name,
+                ?result,
                 input.change_stamp = %change_stamp,
                 input.type_id = %self.type_id,
                 "RtlSubscribeWnfStateChangeNotification",
             );
 
-            Err(io::Error::from_raw_os_error(result.0))
+            Err(self.ntapi_error(WnfRoutine::SubscribeStateChangeNotification, result))
         }
     }
 }
 
+/// Notifies the listener in the given context about empty data with the change stamp `0` if no data has been written
+/// to the given state yet
+///
+/// This implements [`SeenChangeStamp::Replay`]. It must be called before subscribing with the given context, so the
+/// notification is delivered before any notification by the WNF API.
+fn replay_empty<F>(state: RawState<[u8]>, context: &SubscriptionContext<F>) {
+    if !matches!(state.change_stamp(), Ok(change_stamp) if change_stamp == ChangeStamp::initial()) {
+        return;
+    }
+
+    // Asserting unwind safety is fine because after a panic, a listener behind a mutex is not called anymore as its
+    // mutex is poisoned, and a lock-free listener is documented to keep being called
+    call_listener(
+        state.state_name,
+        AssertUnwindSafe(|| {
+            let empty: [u8; 0] = [];
+
+            // SAFETY:
+            // - `empty.as_ptr()` is valid for reads of size `0` and the (empty) memory range is trivially initialized
+            // - `context.stats` is live for as long as `data` is live because `context` is borrowed for the whole
+            //   function
+            let data =
+                unsafe { ScopedData::new(empty.as_ptr().cast(), 0, ChangeStamp::initial()).with_stats(&context.stats) };
+
+            context.dispatch(data);
+        }),
+    );
+}
+
 /// State data that is only valid within a certain scope
 ///
 /// This is used to tie the lifetime `'a` of a [`DataAccessor<'a, T>`](DataAccessor) to the scope of a call to the
 /// listener. This is not to be confused with [`DataScope`](crate::DataScope).
 #[derive(Clone, Copy, Debug)]
-struct ScopedData {
+pub(crate) struct ScopedData {
     buffer: *const c_void,
     buffer_size: usize,
     change_stamp: ChangeStamp,
+    type_id: Option<GUID>,
+    stats: *const SubscriptionStatsCell,
+}
+
+// SAFETY:
+// The `buffer` pointer is only used for reading data and the `stats` pointer is only used for obtaining a shared
+// reference to a `SubscriptionStatsCell`, which is `Sync`
+unsafe impl Send for ScopedData {}
+
+// SAFETY:
+// The `buffer` pointer is only used for reading data and the `stats` pointer is only used for obtaining a shared
+// reference to a `SubscriptionStatsCell`, which is `Sync`
+unsafe impl Sync for ScopedData {}
+
+impl ScopedData {
+    /// Creates a new [`ScopedData`] with the given `buffer`, `buffer_size` and `change_stamp`
+    ///
+    /// # Safety
+    /// As long as the instance of [`ScopedData`] is live:
+    /// - `buffer` must be valid for reads of size `buffer_size`
+    /// - the memory range of size `buffer_size` starting at `buffer` must be initialized
+    pub(crate) unsafe fn new(buffer: *const c_void, buffer_size: usize, change_stamp: impl Into<ChangeStamp>) -> Self {
+        Self {
+            buffer,
+            buffer_size,
+            change_stamp: change_stamp.into(),
+            type_id: None,
+            stats: ptr::null(),
+        }
+    }
+
+    /// Attaches the given type id, with which the data of this [`ScopedData`] were written, to it
+    const fn with_type_id(self, type_id: Option<GUID>) -> Self {
+        Self { type_id, ..self }
+    }
+
+    /// Makes this [`ScopedData`] record failures to read its data in the given subscription statistics
+    ///
+    /// # Safety
+    /// `stats` must be live for as long as the returned instance of [`ScopedData`] or any copy of it is live
+    unsafe fn with_stats(self, stats: &SubscriptionStatsCell) -> Self {
+        Self { stats, ..self }
+    }
+
+    /// Records a failure to read the data of this [`ScopedData`] in its subscription statistics, if any
+    fn record_read_failure(&self) {
+        // SAFETY:
+        // `self.stats` is either a null pointer or was set through `ScopedData::with_stats`, whose safety conditions
+        // imply that it points to a live `SubscriptionStatsCell`
+        if let Some(stats) = unsafe { self.stats.as_ref() } {
+            stats.decode_failures.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Obtains a [`DataAccessor<'a, T>`] for this [`ScopedData`] with the given [`UpdateKind`]
+    ///
+    /// The lifetime parameter `'a` of the returned [`DataAccessor<'a, T>`] is the lifetime of the reference to this
+    /// [`ScopedData`], making sure the [`DataAccessor<'a, T>`] can only be used as long as this [`ScopedData`] is live.
+    pub(crate) const fn accessor_with_update_kind<T>(&self, update_kind: UpdateKind) -> DataAccessor<'_, T>
+    where
+        T: ?Sized,
+    {
+        DataAccessor {
+            data: *self,
+            update_kind,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// A handle to state data passed to state listeners
+///
+/// Listeners receive a [`DataAccessor<'a, T>`](DataAccessor) in their [`StateListener::call`] method. It can be used to
+/// obtain the state data at the time the update took place.
+///
+/// The lifetime parameter `'a` ties a [`DataAccessor<'a, T>`](DataAccessor) to the lifetime of the state data, which is
+/// only valid within the scope of the call to the listener.
+pub struct DataAccessor<'a, T>
+where
+    T: ?Sized,
+{
+    data: ScopedData,
+    update_kind: UpdateKind,
+    _marker: PhantomData<&'a fn() -> T>,
+}
+
+impl<'a, T> DataAccessor<'a, T>
+where
+    T: ?Sized,
+{
+    /// Casts the data type of this [`DataAccessor<'a, T>`](DataAccessor) to a different type `U`
+    ///
+    /// The returned [`DataAccessor<'a, U>`](DataAccessor) represents the same underlying data, but treats them as being
+    /// of a different type `U`.
+    pub const fn cast<U>(self) -> DataAccessor<'a, U>
+    where
+        U: ?Sized,
+    {
+        DataAccessor {
+            data: self.data,
+            update_kind: self.update_kind,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Queries the change stamp of this [`DataAccessor<'_, T>`](DataAccessor)
+    ///
+    /// The change stamp returned by this method is the change stamp of the underlying state for the update that
+    /// caused the listener call to which this [`DataAccessor<'_, T>`](DataAccessor) was passed. Note that in contrast
+    /// to [`OwnedState::change_stamp`] or [`BorrowedState::change_stamp`], this does not involve an OS call.
+    pub const fn change_stamp(self) -> ChangeStamp {
+        self.data.change_stamp
+    }
+
+    /// Returns the type id with which the data of this [`DataAccessor<'_, T>`](DataAccessor) were written, if known
+    ///
+    /// The WNF API passes the type id of the update along with the notification. This returns [`None`] if the data
+    /// were written without a type id, but also if the type id is unknown because the data were not passed along with a
+    /// notification, e.g. when they were queried in [`DeliveryMode::CoalesceToLatest`] or are replayed.
+    ///
+    /// This makes it possible to discriminate updates written with unexpected type ids. For example, to ignore such
+    /// updates:
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use wnf::{DataAccessor, OwnedState, SeenChangeStamp, GUID};
+    ///
+    /// const EXPECTED_TYPE_ID: GUID = GUID::from_u128(0xb8f7f8d3_a5e3_4b2c_9f5e_1c4d6a7b8c9d);
+    ///
+    /// let state = OwnedState::<u32>::create_temporary()?;
+    ///
+    /// let subscription = state.subscribe(
+    ///     |accessor: DataAccessor<_>| {
+    ///         if accessor.type_id() == Some(EXPECTED_TYPE_ID) {
+    ///             println!("{:?}", accessor.get());
+    ///         }
+    ///     },
+    ///     SeenChangeStamp::Current,
+    /// )?;
+    ///
+    /// subscription.unsubscribe()?;
+    /// # Ok(()) }
+    /// ```
+    pub const fn type_id(self) -> Option<GUID> {
+        self.data.type_id
+    }
+
+    /// Returns the kind of the update that caused the listener call to which this
+    /// [`DataAccessor<'_, T>`](DataAccessor) was passed
+    ///
+    /// This tells you whether the listener has missed any state updates since the one it has last seen.
+    pub const fn update_kind(self) -> UpdateKind {
+        self.update_kind
+    }
+
+    /// Returns whether the update that caused the listener call to which this [`DataAccessor<'_, T>`](DataAccessor)
+    /// was passed is a pulse, i.e. an update with empty data
+    ///
+    /// Pulses are usually performed through [`OwnedState::pulse`] or [`BorrowedState::pulse`] in order to signal an
+    /// event, but any update with empty data counts as a pulse. The empty data delivered for a state without data when
+    /// subscribing with [`SeenChangeStamp::Replay`] do not count as a pulse.
+    pub const fn is_pulse(self) -> bool {
+        self.data.buffer_size == 0 && self.data.change_stamp.value() != ChangeStamp::initial().value()
+    }
 }
 
-// SAFETY:
-// The `buffer` pointer is only used for reading data
-unsafe impl Send for ScopedData {}
+impl<T> DataAccessor<'_, T>
+where
+    T: Read<T>,
+{
+    /// Queries the data of this [`DataAccessor<'_, T>`](DataAccessor)
+    ///
+    /// This produces an owned `T` on the stack and hence requires `T: Sized`. In order to produce a `Box<T>` for
+    /// `T: ?Sized`, use the [`get_boxed`](DataAccessor::get_boxed) method.
+    ///
+    /// This returns the data of the accessor without a change stamp. In order to query both the data and the change
+    /// stamp, use the [`query`](DataAccessor::query) method.
+    ///
+    /// The data returned by this method are the data of the underlying state for the update that caused the listener
+    /// call to which this [`DataAccessor<'_, T>`](DataAccessor) was passed. Note that in contrast to
+    /// [`OwnedState::get`] or [`BorrowedState::get`], this does not involve an OS call.
+    ///
+    /// # Errors
+    /// Returns an error if the queried data is not a valid `T`
+    pub fn get(self) -> io::Result<T> {
+        self.get_as()
+    }
+
+    /// Queries the data of this [`DataAccessor<'_, T>`](DataAccessor) together with their change stamp
+    ///
+    /// This produces an owned `T` on the stack and hence requires `T: Sized`. In order to produce a `Box<T>` for
+    /// `T: ?Sized`, use the [`query_boxed`](DataAccessor::query_boxed) method.
+    ///
+    /// This returns the data of the accessor together with their change stamp as a [`StampedData<T>`]. In order to
+    /// only query the data, use the [`get`](DataAccessor::get) method.
+    ///
+    /// The data returned by this method are the data of the underlying state for the update that caused the listener
+    /// call to which this [`DataAccessor<'_, T>`](DataAccessor) was passed. Note that in contrast to
+    /// [`OwnedState::query`] or [`BorrowedState::query`], this does not involve an OS call.
+    ///
+    /// # Errors
+    /// Returns an error if the queried data is not a valid `T`
+    pub fn query(self) -> io::Result<StampedData<T>> {
+        self.query_as()
+    }
+}
+
+impl<T> DataAccessor<'_, T>
+where
+    T: Read<Box<T>> + ?Sized,
+{
+    /// Queries the data of this [`DataAccessor<'_, T>`](DataAccessor) as a box
+    ///
+    /// This produces a [`Box<T>`]. In order to produce an owned `T` on the stack (requiring `T: Sized`), use the
+    /// [`get`](DataAccessor::get) method.
+    ///
+    /// This returns the data of the accessor without a change stamp. In order to query both the data and the change
+    /// stamp, use the [`query_boxed`](DataAccessor::query_boxed) method.
+    ///
+    /// The data returned by this method are the data of the underlying state for the update that caused the listener
+    /// call to which this [`DataAccessor<'_, T>`](DataAccessor) was passed. Note that in contrast to
+    /// [`OwnedState::get_boxed`] or [`BorrowedState::get_boxed`], this does not involve an OS call.
+    ///
+    /// # Errors
+    /// Returns an error if the queried data is not a valid `T`
+    pub fn get_boxed(self) -> io::Result<Box<T>> {
+        self.get_as()
+    }
+
+    /// Queries the data of this [`DataAccessor<'_, T>`](DataAccessor) as a box together with their change stamp
+    ///
+    /// This produces a [`Box<T>`]. In order to produce an owned `T` on the stack (requiring `T: Sized`), use the
+    /// [`query`](DataAccessor::query) method.
+    ///
+    /// This returns the data of the accessor together with their change stamp as a [`StampedData<Box<T>>`]. In order
+    /// to only query the data, use the [`get_boxed`](OwnedState::get_boxed) method.
+    ///
+    /// The data returned by this method are the data of the underlying state for the update that caused the listener
+    /// call to which this [`DataAccessor<'_, T>`](DataAccessor) was passed. Note that in contrast to
+    /// [`OwnedState::query_boxed`] or [`BorrowedState::query_boxed`], this does not involve an OS call.
+    ///
+    /// # Errors
+    /// Returns an error if the queried data is not a valid `T`
+    pub fn query_boxed(self) -> io::Result<StampedData<Box<T>>> {
+        self.query_as()
+    }
+}
+
+impl<T> DataAccessor<'_, T>
+where
+    T: ?Sized,
+{
+    /// Queries the data of this [`DataAccessor<'_, T>`](DataAccessor) as a value of type `D` without a change stamp
+    ///
+    /// If `T: Sized`, then `D` can be either `T` or `Box<T>`.
+    /// If `T: !Sized`, then `D` must be `Box<T>`.
+    pub(crate) fn get_as<D>(self) -> io::Result<D>
+    where
+        T: Read<D>,
+    {
+        // SAFETY:
+        // - `self` was obtained from a `ScopedData` through `ScopedData::accessor_with_update_kind`, which ties the
+        //   lifetime parameter `'a` of `DataAccessor<'a, T>` to the lifetime of the `ScopedData`, so the `ScopedData`
+        //   is still live
+        // - `self.data` is a copy of this `ScopedData`, which was created through `ScopedData::new`
+        // - The safety conditions of `ScopedData::new` then imply those of `T::from_buffer`
+        let result = unsafe { T::from_buffer(self.data.buffer, self.data.buffer_size) };
+
+        if result.is_err() {
+            self.data.record_read_failure();
+        }
+
+        result
+    }
+
+    /// Queries the data of this [`DataAccessor<'_, T>`](DataAccessor) as a value of type `D` together with its change
+    /// stamp
+    ///
+    /// If `T: Sized`, then `D` can be either `T` or `Box<T>`.
+    /// If `T: !Sized`, then `D` must be `Box<T>`.
+    pub(crate) fn query_as<D>(self) -> io::Result<StampedData<D>>
+    where
+        T: Read<D>,
+    {
+        Ok(StampedData::from_data_change_stamp(
+            self.get_as()?,
+            self.data.change_stamp,
+        ))
+    }
+}
+
+impl<T> DataAccessor<'_, [T]>
+where
+    T: CheckedBitPattern,
+{
+    /// Queries the valid prefix of the data of this [`DataAccessor<'_, [T]>`](DataAccessor) as a box
+    ///
+    /// In contrast to [`get_boxed`](DataAccessor::get_boxed), which fails if any element of the slice is not a valid
+    /// `T`, this returns the longest prefix of the slice consisting of valid elements together with the index of the
+    /// first invalid element, or [`None`] if all elements are valid. This is useful for consuming best-effort data,
+    /// e.g. event logs, where a single invalid element should not render the whole data unusable.
+    ///
+    /// The data returned by this method are the data of the underlying state for the update that caused the listener
+    /// call to which this [`DataAccessor<'_, [T]>`](DataAccessor) was passed. Note that in contrast to
+    /// [`OwnedState::get_boxed`] or [`BorrowedState::get_boxed`], this does not involve an OS call.
+    ///
+    /// # Errors
+    /// Returns an error if the size of the queried data is not a multiple of the size of `T`
+    pub fn try_get_slice(self) -> io::Result<(Box<[T]>, Option<usize>)> {
+        // SAFETY:
+        // - `self` was obtained from a `ScopedData` through `ScopedData::accessor_with_update_kind`, which ties the
+        //   lifetime parameter `'a` of `DataAccessor<'a, T>` to the lifetime of the `ScopedData`, so the `ScopedData`
+        //   is still live
+        // - `self.data` is a copy of this `ScopedData`, which was created through `ScopedData::new`
+        // - The safety conditions of `ScopedData::new` then imply those of `read::slice_prefix_from_buffer`
+        let result = unsafe { read::slice_prefix_from_buffer(self.data.buffer, self.data.buffer_size) };
+
+        if result.is_err() {
+            self.data.record_read_failure();
+        }
+
+        result
+    }
+}
+
+impl<T> DataAccessor<'_, T>
+where
+    T: ?Sized,
+{
+    /// Copies the data of this [`DataAccessor<'_, T>`](DataAccessor) into a [`DataSnapshot<T>`]
+    ///
+    /// Unlike a [`DataAccessor<'_, T>`](DataAccessor), a [`DataSnapshot<T>`] owns the data and has no lifetime
+    /// parameter, so it can be used after the listener call has returned, e.g. by sending it to a different thread
+    /// through a channel. The data are copied as raw bytes and only interpreted as a `T` when reading from the
+    /// snapshot.
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use std::sync::mpsc;
+    ///
+    /// use wnf::{DataAccessor, OwnedState, SeenChangeStamp};
+    ///
+    /// let state = OwnedState::<u32>::create_temporary()?;
+    /// let (tx, rx) = mpsc::channel();
+    ///
+    /// let subscription = state.subscribe(
+    ///     move |accessor: DataAccessor<'_, u32>| {
+    ///         let _ = tx.send(accessor.to_owned_snapshot());
+    ///     },
+    ///     SeenChangeStamp::Current,
+    /// )?;
+    ///
+    /// state.set(&42)?;
+    ///
+    /// let snapshot = rx.recv()?;
+    /// assert_eq!(snapshot.get()?, 42);
+    ///
+    /// subscription.unsubscribe()?;
+    /// # Ok(()) }
+    /// ```
+    pub fn to_owned_snapshot(self) -> DataSnapshot<T> {
+        let data = if self.data.buffer_size == 0 {
+            Box::default()
+        } else {
+            // SAFETY:
+            // - `self` was obtained from a `ScopedData` through `ScopedData::accessor_with_update_kind`, which ties the
+            //   lifetime parameter `'a` of `DataAccessor<'a, T>` to the lifetime of the `ScopedData`, so the
+            //   `ScopedData` is still live
+            // - `self.data` is a copy of this `ScopedData`, which was created through `ScopedData::new`
+            // - The safety conditions of `ScopedData::new` then imply that `self.data.buffer` is valid for reads of
+            //   size `self.data.buffer_size` and the memory range is initialized, which implies the safety conditions
+            //   of `slice::from_raw_parts` because `u8` has an alignment of one and the size is not zero
+            unsafe { slice::from_raw_parts(self.data.buffer.cast::<u8>(), self.data.buffer_size) }.into()
+        };
+
+        DataSnapshot {
+            data,
+            change_stamp: self.data.change_stamp,
+            type_id: self.data.type_id,
+            update_kind: self.update_kind,
+            _marker: PhantomData,
+        }
+    }
+}
+
+// We cannot derive this because that would impose an unnecessary trait bound `T: Copy`
+impl<T> Copy for DataAccessor<'_, T> where T: ?Sized {}
+
+// We cannot derive this because that would impose an unnecessary trait bound `T: Clone`
+impl<T> Clone for DataAccessor<'_, T>
+where
+    T: ?Sized,
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+// We cannot derive this because that would impose an unnecessary trait bound `T: Debug`
+impl<T> Debug for DataAccessor<'_, T>
+where
+    T: ?Sized,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DataAccessor")
+            .field("data", &self.data)
+            .field("update_kind", &self.update_kind)
+            .finish()
+    }
+}
+
+/// An owned copy of the state data passed to a state listener
+///
+/// This is returned from [`DataAccessor::to_owned_snapshot`]. It offers the same methods for reading the data as a
+/// [`DataAccessor<'_, T>`](DataAccessor), but owns a copy of the data and has no lifetime parameter.
+pub struct DataSnapshot<T>
+where
+    T: ?Sized,
+{
+    data: Box<[u8]>,
+    change_stamp: ChangeStamp,
+    type_id: Option<GUID>,
+    update_kind: UpdateKind,
+    // `DataSnapshot<T>` doesn't own a `T`, it only contains bytes to be read as a `T`
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> DataSnapshot<T>
+where
+    T: ?Sized,
+{
+    /// Casts the data type of this [`DataSnapshot<T>`] to a different type `U`
+    ///
+    /// See [`DataAccessor::cast`]
+    pub fn cast<U>(self) -> DataSnapshot<U>
+    where
+        U: ?Sized,
+    {
+        DataSnapshot {
+            data: self.data,
+            change_stamp: self.change_stamp,
+            type_id: self.type_id,
+            update_kind: self.update_kind,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the change stamp of this [`DataSnapshot<T>`]
+    ///
+    /// See [`DataAccessor::change_stamp`]
+    pub const fn change_stamp(&self) -> ChangeStamp {
+        self.change_stamp
+    }
+
+    /// Returns the type id with which the data of this [`DataSnapshot<T>`] were written, if known
+    ///
+    /// See [`DataAccessor::type_id`]
+    pub const fn type_id(&self) -> Option<GUID> {
+        self.type_id
+    }
+
+    /// Returns the kind of the update this [`DataSnapshot<T>`] was taken from
+    ///
+    /// See [`DataAccessor::update_kind`]
+    pub const fn update_kind(&self) -> UpdateKind {
+        self.update_kind
+    }
+
+    /// Returns the raw bytes of the data of this [`DataSnapshot<T>`]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns whether the update this [`DataSnapshot<T>`] was taken from is a pulse, i.e. an update with empty data
+    ///
+    /// See [`DataAccessor::is_pulse`]
+    pub fn is_pulse(&self) -> bool {
+        self.accessor().is_pulse()
+    }
+
+    /// Returns a [`DataAccessor<'_, T>`](DataAccessor) for the data of this [`DataSnapshot<T>`]
+    fn accessor(&self) -> DataAccessor<'_, T> {
+        // SAFETY:
+        // The returned `DataAccessor<'_, T>` borrows `self`, so `self.data` is a live and initialized boxed slice for
+        // as long as it is used
+        let data = unsafe { ScopedData::new(self.data.as_ptr().cast(), self.data.len(), self.change_stamp) };
+
+        DataAccessor {
+            data: data.with_type_id(self.type_id),
+            update_kind: self.update_kind,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> DataSnapshot<T>
+where
+    T: Read<T>,
+{
+    /// Reads the data of this [`DataSnapshot<T>`]
+    ///
+    /// See [`DataAccessor::get`]
+    ///
+    /// # Errors
+    /// Returns an error if the data is not a valid `T`
+    pub fn get(&self) -> io::Result<T> {
+        self.accessor().get()
+    }
+
+    /// Reads the data of this [`DataSnapshot<T>`] together with their change stamp
+    ///
+    /// See [`DataAccessor::query`]
+    ///
+    /// # Errors
+    /// Returns an error if the data is not a valid `T`
+    pub fn query(&self) -> io::Result<StampedData<T>> {
+        self.accessor().query()
+    }
+}
+
+impl<T> DataSnapshot<T>
+where
+    T: Read<Box<T>> + ?Sized,
+{
+    /// Reads the data of this [`DataSnapshot<T>`] as a box
+    ///
+    /// See [`DataAccessor::get_boxed`]
+    ///
+    /// # Errors
+    /// Returns an error if the data is not a valid `T`
+    pub fn get_boxed(&self) -> io::Result<Box<T>> {
+        self.accessor().get_boxed()
+    }
+
+    /// Reads the data of this [`DataSnapshot<T>`] as a box together with their change stamp
+    ///
+    /// See [`DataAccessor::query_boxed`]
+    ///
+    /// # Errors
+    /// Returns an error if the data is not a valid `T`
+    pub fn query_boxed(&self) -> io::Result<StampedData<Box<T>>> {
+        self.accessor().query_boxed()
+    }
+}
+
+impl<T> DataSnapshot<[T]>
+where
+    T: CheckedBitPattern,
+{
+    /// Reads the valid prefix of the data of this [`DataSnapshot<[T]>`](DataSnapshot) as a box
+    ///
+    /// See [`DataAccessor::try_get_slice`]
+    ///
+    /// # Errors
+    /// Returns an error if the size of the data is not a multiple of the size of `T`
+    pub fn try_get_slice(&self) -> io::Result<(Box<[T]>, Option<usize>)> {
+        self.accessor().try_get_slice()
+    }
+}
+
+// We cannot derive this because that would impose an unnecessary trait bound `T: Clone`
+impl<T> Clone for DataSnapshot<T>
+where
+    T: ?Sized,
+{
+    fn clone(&self) -> Self {
+        Self {
+            data: self.data.clone(),
+            change_stamp: self.change_stamp,
+            type_id: self.type_id,
+            update_kind: self.update_kind,
+            _marker: PhantomData,
+        }
+    }
+}
+
+// We cannot derive this because that would impose an unnecessary trait bound `T: Debug`
+impl<T> Debug for DataSnapshot<T>
+where
+    T: ?Sized,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DataSnapshot")
+            .field("data", &self.data)
+            .field("change_stamp", &self.change_stamp)
+            .field("type_id", &self.type_id)
+            .field("update_kind", &self.update_kind)
+            .finish()
+    }
+}
+
+/// A state listener that reads the state data and passes them to a closure as an owned value
+///
+/// This is the listener type of the [`Subscription<'_, F>`](Subscription) returned from the
+/// [`subscribe_owned`](OwnedState::subscribe_owned) and [`subscribe_owned_boxed`](OwnedState::subscribe_owned_boxed)
+/// methods. The type parameter `F` is the type of the closure, while `D` is the type the state data are read as, which
+/// is either `T` or `Box<T>`.
+pub struct OwnedListener<F, D> {
+    listener: F,
+    _marker: PhantomData<fn() -> D>,
+}
+
+impl<F, D> OwnedListener<F, D> {
+    /// Creates a new [`OwnedListener<F, D>`] passing the state data to `listener`
+    const fn new(listener: F) -> Self {
+        Self {
+            listener,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<D, F, T> StateListener<T> for OwnedListener<F, D>
+where
+    F: FnMut(io::Result<StampedData<D>>),
+    T: Read<D> + ?Sized,
+{
+    fn call(&mut self, accessor: DataAccessor<'_, T>) {
+        (self.listener)(accessor.query_as());
+    }
+
+    fn on_error(&mut self, err: io::Error, _: DataAccessor<'_, T>) {
+        (self.listener)(Err(err));
+    }
+}
+
+// We cannot derive this because that would impose unnecessary trait bounds `F: Debug` and `D: Debug`
+impl<F, D> Debug for OwnedListener<F, D> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OwnedListener").finish_non_exhaustive()
+    }
+}
+
+/// A state listener that passes the change stamps of pulses to a closure
+///
+/// This is the listener type of the [`Subscription<'_, F>`](Subscription) returned from the
+/// [`subscribe_pulses`](OwnedState::subscribe_pulses) methods. The type parameter `F` is the type of the closure.
+pub struct PulseListener<F> {
+    listener: F,
+}
+
+impl<F> PulseListener<F> {
+    /// Creates a new [`PulseListener<F>`] passing the change stamps of pulses to `listener`
+    const fn new(listener: F) -> Self {
+        Self { listener }
+    }
+}
+
+impl<F, T> StateListener<T> for PulseListener<F>
+where
+    F: FnMut(ChangeStamp),
+    T: ?Sized,
+{
+    fn call(&mut self, accessor: DataAccessor<'_, T>) {
+        if accessor.is_pulse() {
+            (self.listener)(accessor.change_stamp());
+        }
+    }
+}
+
+// We cannot derive this because that would impose an unnecessary trait bound `F: Debug`
+impl<F> Debug for PulseListener<F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PulseListener").finish_non_exhaustive()
+    }
+}
+
+/// A state listener that passes the change stamps of all state updates to a closure
+///
+/// This is the listener type of the [`Subscription<'_, F>`](Subscription) returned from the
+/// [`subscribe_stamps`](OwnedState::subscribe_stamps) methods. The type parameter `F` is the type of the closure.
+pub struct StampListener<F> {
+    listener: F,
+}
+
+impl<F> StampListener<F> {
+    /// Creates a new [`StampListener<F>`] passing the change stamps of state updates to `listener`
+    const fn new(listener: F) -> Self {
+        Self { listener }
+    }
+}
+
+impl<F, T> StateListener<T> for StampListener<F>
+where
+    F: FnMut(ChangeStamp),
+    T: ?Sized,
+{
+    fn call(&mut self, accessor: DataAccessor<'_, T>) {
+        (self.listener)(accessor.change_stamp());
+    }
+
+    fn on_error(&mut self, _: io::Error, accessor: DataAccessor<'_, T>) {
+        // The state has changed even though its data could not be prepared, which the listener does not care about
+        (self.listener)(accessor.change_stamp());
+    }
+}
+
+// We cannot derive this because that would impose an unnecessary trait bound `F: Debug`
+impl<F> Debug for StampListener<F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StampListener").finish_non_exhaustive()
+    }
+}
+
+/// A state listener that passes the first state update to a one-shot closure
+///
+/// This is the listener type of the [`Subscription<'_, F>`](Subscription) returned from the
+/// [`subscribe_once`](OwnedState::subscribe_once) methods. The type parameter `F` is the type of the closure.
+pub struct OnceListener<F> {
+    listener: Option<F>,
+}
+
+impl<F> OnceListener<F> {
+    /// Creates a new [`OnceListener<F>`] passing the first state update to `listener`
+    const fn new(listener: F) -> Self {
+        Self {
+            listener: Some(listener),
+        }
+    }
+
+    /// Returns whether the closure has been called
+    const fn is_done(&self) -> bool {
+        self.listener.is_none()
+    }
+}
+
+impl<F, T> StateListener<T> for OnceListener<F>
+where
+    F: FnOnce(DataAccessor<'_, T>),
+    T: ?Sized,
+{
+    fn call(&mut self, accessor: DataAccessor<'_, T>) {
+        if let Some(listener) = self.listener.take() {
+            listener(accessor);
+        }
+    }
+}
+
+// We cannot derive this because that would impose an unnecessary trait bound `F: Debug`
+impl<F> Debug for OnceListener<F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OnceListener")
+            .field("is_done", &self.is_done())
+            .finish_non_exhaustive()
+    }
+}
+
+/// A subscription of a listener to updates of a state
+///
+/// This is returned from [`OwnedState::subscribe`] and [`BorrowedState::subscribe`].
+///
+/// Note that the listener is automatically unsubscribed when the [`Subscription<'_, F>`](Subscription) is dropped. In
+/// this case, errors while unsubscribing are silently ignored. If you want to handle them explicitly, use the
+/// [`Subscription::unsubscribe`] method, which returns an [`io::Result<()>`](io::Result). Note that the listener will
+/// not be called anymore after unsubscribing, even when there is an error.
+///
+/// If you want to keep the subscription for as long as the process is running and the state exists, use the
+/// [`Subscription::forget`] method.
+#[must_use = "a `Subscription` is unsubscribed immediately if it is not used"]
+pub struct Subscription<'a, F> {
+    inner: Option<SubscriptionInner<F>>,
+    _marker: PhantomData<&'a ()>,
+}
+
+impl<F> Subscription<'_, F> {
+    /// Forgets this [`Subscription<'_, F>`](Subscription), effectively keeping it forever
+    ///
+    /// When a [`Subscription<'_, F>`](Subscription) is dropped, the listener is unsubscribed. You can avoid this
+    /// behavior by calling this method. It consumes the [`Subscription<'_, F>`](Subscription) without dropping it,
+    /// effectively keeping the subscription for as long as the process is running and the state exists.
+    ///
+    /// If the subscription is tied to an [`OwnedState<T>`], dropping the state deletes it, so the listener is never
+    /// called again. Use [`OwnedState::subscribe_forever`] to forget the subscription and leak the state in one call.
+    pub const fn forget(self) {
+        mem::forget(self);
+    }
+
+    /// Unsubscribes the listener for this [`Subscription<'_, F>`](Subscription)
+    ///
+    /// This happens automatically when the [`Subscription<'_, F>`](Subscription) is dropped (unless you call
+    /// [`Subscription::forget`]), so there is usually no need to call this method. Its only purpose is to enable you
+    /// to handle errors while unsubscribing. Note that the listener will not be called anymore after unsubscribing,
+    /// even when there is an error (except for a call that is already in progress if the listener was subscribed
+    /// through [`OwnedState::subscribe_lock_free`] or [`BorrowedState::subscribe_lock_free`]).
+    ///
+    /// # Errors
+    /// Returns an error if unsubscribing fails
+    pub fn unsubscribe(mut self) -> io::Result<()> {
+        self.try_unsubscribe()
+    }
+
+    /// Returns statistics on the state updates delivered to the listener of this
+    /// [`Subscription<'_, F>`](Subscription)
+    ///
+    /// This makes it possible to monitor whether a subscription is still alive, e.g. by checking the time of the last
+    /// delivery, without having to wrap every listener.
+    pub fn stats(&self) -> SubscriptionStats {
+        self.inner
+            .as_ref()
+            .map(|inner| inner.context.stats())
+            .unwrap_or_default()
+    }
+
+    /// Converts this [`Subscription<'_, F>`](Subscription) into a raw handle without unsubscribing the listener
+    ///
+    /// This is useful when embedding this crate behind a C API: The returned handle is an opaque pointer that can be
+    /// passed across the FFI boundary without exposing the type of the listener. It can later be converted back into
+    /// a [`Subscription<'_, F>`](Subscription) through [`Subscription::from_raw_handle`], e.g. in order to unsubscribe
+    /// the listener.
+    ///
+    /// The listener stays subscribed for as long as the handle is not converted back. If the handle is never converted
+    /// back, this has the same effect as [`Subscription::forget`], except that the memory used internally by the
+    /// subscription is leaked.
+    pub fn into_raw_handle(mut self) -> *mut c_void {
+        self.inner
+            .take()
+            .map_or(ptr::null_mut(), |inner| Box::into_raw(Box::new(inner)).cast())
+    }
+
+    /// Creates a new [`Subscription<'a, F>`](Subscription) from the given context and subscription handle
+    ///
+    /// Note that the lifetime `'a` is inferred at the call site.
+    const fn new(context: Box<SubscriptionContext<F>>, subscription_handle: SubscriptionHandle) -> Self {
+        Self {
+            inner: Some(SubscriptionInner {
+                context: ManuallyDrop::new(context),
+                subscription_handle,
+            }),
+            _marker: PhantomData,
+        }
+    }
+
+    fn try_unsubscribe(&mut self) -> io::Result<()> {
+        if let Some(inner) = self.inner.take() {
+            if !inner.context.claim_registration() {
+                // The subscription has been retired, so it is unsubscribed through the list of retired subscriptions,
+                // which may already have happened
+                unsubscribe_retired_subscriptions();
+
+                if inner.context.release_subscription() {
+                    ManuallyDrop::into_inner(inner.context);
+                }
+
+                return Ok(());
+            }
+
+            // We unregister before unsubscribing because after a successful unsubscription, a new subscription may be
+            // created with the same handle
+            ActiveSubscriptions::unregister(inner.subscription_handle);
+
+            // SAFETY:
+            // - `inner.subscription_handle` was returned from a successful call to
+            //   `RtlSubscribeWnfStateChangeNotification`
+            // - `RtlUnsubscribeWnfStateChangeNotification` has not been called successfully with
+            //   `inner.subscription_handle` before because it is only held in `inner` and `inner` is dropped or handed
+            //   over to the list of failed unsubscriptions afterwards
+            let result = unsafe { ntapi::RtlUnsubscribeWnfStateChangeNotification(inner.subscription_handle.as_ptr()) };
+
+            ntapi_event!(
+                WnfRoutine::UnsubscribeStateChangeNotification,
+                result.is_err(),
+                ?result,
+                input.subscription_handle = %inner.subscription_handle,
+                "RtlUnsubscribeWnfStateChangeNotification",
+            );
+
+            if result.is_ok() {
+                ManuallyDrop::into_inner(inner.context);
+                retry_failed_unsubscriptions();
+            } else {
+                // In case of an error, we cannot drop the `Box<SubscriptionContext<F>>` because the callback may
+                // still be called with it. Instead, we hand it over to the list of failed unsubscriptions, which only
+                // drops it after a later call to `RtlUnsubscribeWnfStateChangeNotification` has succeeded
+                inner.context.clear();
+                PendingUnsubscription::new(ManuallyDrop::into_inner(inner.context), inner.subscription_handle)
+                    .push_failed();
+            }
+
+            result.ok()?;
+        };
+
+        Ok(())
+    }
+}
+
+impl<F> Subscription<'_, OnceListener<F>> {
+    /// Returns whether the one-shot listener of this [`Subscription<'_, F>`](Subscription) has been called, and hence
+    /// the subscription has been unsubscribed automatically
+    ///
+    /// See [`OwnedState::subscribe_once`]
+    pub fn is_done(&self) -> bool {
+        unsubscribe_retired_subscriptions();
+        self.inner.as_ref().map_or(true, |inner| inner.context.is_retired())
+    }
+}
+
+impl<'a, F> Subscription<'a, F> {
+    /// Converts a raw handle back into a [`Subscription<'a, F>`](Subscription)
+    ///
+    /// This is the inverse of [`Subscription::into_raw_handle`]. The returned [`Subscription<'a, F>`](Subscription)
+    /// owns the subscription again, so dropping it unsubscribes the listener.
+    ///
+    /// Note that the lifetime `'a` is inferred at the call site.
+    ///
+    /// # Safety
+    /// - `handle` must have been returned from a call to [`Subscription::into_raw_handle`] on a [`Subscription<'b,
+    ///   F>`](Subscription) with the same listener type `F`
+    /// - `handle` must not have been converted back through this method before
+    /// - The lifetime `'a` must not be longer than the lifetime `'b`
+    pub unsafe fn from_raw_handle(handle: *mut c_void) -> Self {
+        let inner = if handle.is_null() {
+            None
+        } else {
+            // SAFETY:
+            // By the safety conditions of this method, `handle` was returned from `Box::into_raw` for a
+            // `Box<SubscriptionInner<F>>` in `Subscription::into_raw_handle` and has not been converted back before
+            Some(*unsafe { Box::from_raw(handle.cast::<SubscriptionInner<F>>()) })
+        };
+
+        Self {
+            inner,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<F> Drop for Subscription<'_, F> {
+    fn drop(&mut self) {
+        let _ = self.try_unsubscribe();
+    }
+}
+
+// We cannot derive this because that would impose an unnecessary trait bound `F: Debug`
+impl<F> Debug for Subscription<'_, F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Subscription").field("inner", &self.inner).finish()
+    }
+}
+
+// We cannot derive this because that would impose an unnecessary trait bound `F: PartialEq<F>`
+impl<F> PartialEq for Subscription<'_, F> {
+    fn eq(&self, other: &Self) -> bool {
+        self.inner == other.inner
+    }
+}
+
+// We cannot derive this because that would impose an unnecessary trait bound `F: Eq`
+impl<F> Eq for Subscription<'_, F> {}
+
+// We cannot derive this because that would impose an unnecessary trait bound `F: Hash`
+impl<F> Hash for Subscription<'_, F> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.inner.hash(state);
+    }
+}
+
+/// Statistics on the state updates delivered to the listener of a subscription
+///
+/// This is returned by [`Subscription::stats`].
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct SubscriptionStats {
+    delivered: u64,
+    errors: u64,
+    decode_failures: u64,
+    last_seen_change_stamp: Option<ChangeStamp>,
+    last_delivery_time: Option<SystemTime>,
+}
+
+impl SubscriptionStats {
+    /// Returns the number of state updates delivered to the listener
+    ///
+    /// This counts calls to both [`StateListener::call`] and [`StateListener::on_error`].
+    pub const fn delivered(self) -> u64 {
+        self.delivered
+    }
+
+    /// Returns the number of state updates delivered to the listener through [`StateListener::on_error`]
+    pub const fn errors(self) -> u64 {
+        self.errors
+    }
+
+    /// Returns the number of times reading the data of a state update through a [`DataAccessor<'_, T>`](DataAccessor)
+    /// has failed, e.g. because the data were not a valid `T`
+    pub const fn decode_failures(self) -> u64 {
+        self.decode_failures
+    }
+
+    /// Returns the change stamp of the state update the listener has last seen, if any
+    ///
+    /// Before the first delivery, this is determined by the [`SeenChangeStamp`] passed when subscribing.
+    pub const fn last_seen_change_stamp(self) -> Option<ChangeStamp> {
+        self.last_seen_change_stamp
+    }
+
+    /// Returns the time of the last delivery of a state update to the listener, if any
+    pub const fn last_delivery_time(self) -> Option<SystemTime> {
+        self.last_delivery_time
+    }
+}
+
+/// Statistics on subscriptions whose listeners could not be unsubscribed
+///
+/// This is returned by [`failed_unsubscription_stats`] and [`gc_failed_unsubscriptions`].
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct FailedUnsubscriptionStats {
+    pending: usize,
+    failed: u64,
+    reclaimed: u64,
+}
+
+impl FailedUnsubscriptionStats {
+    /// Returns the number of subscriptions that could not be unsubscribed yet
+    ///
+    /// The memory used by these subscriptions is kept alive until they are unsubscribed successfully.
+    pub const fn pending(self) -> usize {
+        self.pending
+    }
+
+    /// Returns the total number of subscriptions that could not be unsubscribed on the first attempt
+    pub const fn failed(self) -> u64 {
+        self.failed
+    }
+
+    /// Returns the total number of subscriptions that were unsubscribed successfully on a later attempt
+    pub const fn reclaimed(self) -> u64 {
+        self.reclaimed
+    }
+}
+
+/// Retries unsubscribing all subscriptions whose listeners could not be unsubscribed before
+///
+/// When unsubscribing a listener fails (see [`Subscription::unsubscribe`]), the listener itself is dropped and will not
+/// be called anymore, but the memory used internally by the subscription must be kept alive until it has been
+/// unsubscribed successfully. Such subscriptions are retried automatically whenever another subscription is
+/// unsubscribed successfully. This function lets you retry them explicitly, e.g. periodically in long-running services.
+///
+/// This also unsubscribes the subscriptions of one-shot listeners that have been called (see
+/// [`OwnedState::subscribe_once`]).
+///
+/// Returns the statistics after retrying.
+pub fn gc_failed_unsubscriptions() -> FailedUnsubscriptionStats {
+    unsubscribe_retired_subscriptions();
+
+    let mut failed_unsubscriptions = FailedUnsubscriptions::lock();
+    failed_unsubscriptions.retry();
+    failed_unsubscriptions.stats()
+}
+
+/// Returns statistics on subscriptions whose listeners could not be unsubscribed
+///
+/// See [`gc_failed_unsubscriptions`]
+pub fn failed_unsubscription_stats() -> FailedUnsubscriptionStats {
+    FailedUnsubscriptions::lock().stats()
+}
+
+/// Retries unsubscribing all subscriptions whose listeners could not be unsubscribed before, unless this would block
+///
+/// This is skipped when called from within a listener because unsubscribing from within a listener may fail.
+fn retry_failed_unsubscriptions() {
+    if is_in_listener() {
+        return;
+    }
+
+    unsubscribe_retired_subscriptions();
+
+    if let Ok(mut failed_unsubscriptions) = FAILED_UNSUBSCRIPTIONS.try_lock() {
+        failed_unsubscriptions.retry();
+    }
+}
+
+/// The list of subscriptions whose listeners could not be unsubscribed, see [`gc_failed_unsubscriptions`]
+static FAILED_UNSUBSCRIPTIONS: Mutex<FailedUnsubscriptions> = Mutex::new(FailedUnsubscriptions {
+    entries: Vec::new(),
+    failed: 0,
+    reclaimed: 0,
+});
+
+/// Subscriptions whose listeners could not be unsubscribed together with statistics on them
+#[derive(Debug)]
+struct FailedUnsubscriptions {
+    entries: Vec<PendingUnsubscription>,
+    failed: u64,
+    reclaimed: u64,
+}
+
+impl FailedUnsubscriptions {
+    /// Locks the global list of failed unsubscriptions
+    fn lock() -> MutexGuard<'static, Self> {
+        // We can access the list even when the mutex is poisoned because every entry is valid on its own
+        match FAILED_UNSUBSCRIPTIONS.lock() {
+            Ok(guard) => guard,
+            Err(err) => err.into_inner(),
+        }
+    }
+
+    /// Retries unsubscribing all entries, dropping the ones that are unsubscribed successfully
+    fn retry(&mut self) {
+        let len_before = self.entries.len();
+        self.entries.retain(|entry| !entry.try_unsubscribe());
+        self.reclaimed += (len_before - self.entries.len()) as u64;
+    }
+
+    /// Returns the current statistics
+    fn stats(&self) -> FailedUnsubscriptionStats {
+        FailedUnsubscriptionStats {
+            pending: self.entries.len(),
+            failed: self.failed,
+            reclaimed: self.reclaimed,
+        }
+    }
+}
+
+/// A subscription that still needs to be unsubscribed
+///
+/// This owns the type-erased `Box<SubscriptionContext<F>>` of either a subscription whose listener could not be
+/// unsubscribed, in which case the context has been cleared, or a retired subscription (see [`Retirement`]).
+#[derive(Debug)]
+struct PendingUnsubscription {
+    context: *mut c_void,
+    release_context: unsafe fn(*mut c_void),
+    subscription_handle: SubscriptionHandle,
+}
+
+// SAFETY:
+// The context has either been cleared, so it does not contain a value of type `F` anymore or it contains a deactivated
+// `LockFreeListener<F>`, which is only created by `RawState::subscribe_lock_free` requiring `F: Send`, or it belongs to
+// a retired subscription, which is only created by `RawState::subscribe_once` requiring `F: Send`. The remaining parts
+// of a `SubscriptionContext<F>` are `Send` regardless of `F`, so releasing it on a different thread is sound.
+unsafe impl Send for PendingUnsubscription {}
+
+impl PendingUnsubscription {
+    /// Creates a new [`PendingUnsubscription`] from the given cleared context and subscription handle
+    fn new<F>(context: Box<SubscriptionContext<F>>, subscription_handle: SubscriptionHandle) -> Self {
+        /// Drops a type-erased `Box<SubscriptionContext<F>>`
+        ///
+        /// # Safety
+        /// `context` must have been produced by `Box::into_raw` from a `Box<SubscriptionContext<F>>` and must not be
+        /// used afterwards
+        unsafe fn drop_context<F>(context: *mut c_void) {
+            // SAFETY:
+            // Guaranteed by the safety conditions of this function
+            drop(unsafe { Box::from_raw(context.cast::<SubscriptionContext<F>>()) });
+        }
+
+        Self {
+            context: Box::into_raw(context).cast(),
+            release_context: drop_context::<F>,
+            subscription_handle,
+        }
+    }
+
+    /// Creates a new [`PendingUnsubscription`] from the given context of a retired subscription and its subscription
+    /// handle
+    ///
+    /// # Safety
+    /// `context` must point to the context of a subscription with the given handle, owned by a
+    /// [`Subscription<'_, F>`](Subscription), whose registration has just been retired
+    unsafe fn retired<F>(context: &SubscriptionContext<F>, subscription_handle: SubscriptionHandle) -> Self {
+        /// Releases a type-erased context of a retired subscription, see [`SubscriptionContext::release_retired`]
+        ///
+        /// # Safety
+        /// `context` must point to the context of a retired subscription with type `F` whose registration has been
+        /// unsubscribed successfully and must not be used afterwards
+        unsafe fn release_retired<F>(context: *mut c_void) {
+            let context = context.cast::<SubscriptionContext<F>>();
+
+            // SAFETY:
+            // The context is still valid because it is only dropped after its registration has been unsubscribed and
+            // the registration is only marked as unsubscribed here
+            let is_subscription_dropped = unsafe { &*context }.release_registration();
+
+            if is_subscription_dropped {
+                // SAFETY:
+                // The owning `Subscription<'_, F>` has been dropped without dropping its `Box<SubscriptionContext<F>>`
+                // because the registration had not been unsubscribed yet, so ownership has passed to this function
+                drop(unsafe { Box::from_raw(context) });
+            }
+        }
+
+        Self {
+            context: context as *const SubscriptionContext<F> as *mut c_void,
+            release_context: release_retired::<F>,
+            subscription_handle,
+        }
+    }
+
+    /// Adds this entry to the global list of failed unsubscriptions
+    fn push_failed(self) {
+        let mut failed_unsubscriptions = FailedUnsubscriptions::lock();
+        failed_unsubscriptions.entries.push(self);
+        failed_unsubscriptions.failed += 1;
+    }
+
+    /// Tries to unsubscribe this entry, returning whether this succeeded
+    ///
+    /// In case of success, the context is released, so the entry must be discarded afterwards.
+    fn try_unsubscribe(&self) -> bool {
+        // SAFETY:
+        // - `self.subscription_handle` was returned from a successful call to `RtlSubscribeWnfStateChangeNotification`
+        // - `RtlUnsubscribeWnfStateChangeNotification` has not been called successfully with `self.subscription_handle`
+        //   before because in that case the entry would have been discarded
+        let result = unsafe { ntapi::RtlUnsubscribeWnfStateChangeNotification(self.subscription_handle.as_ptr()) };
+
+        ntapi_event!(
+            WnfRoutine::UnsubscribeStateChangeNotification,
+            result.is_err(),
+            ?result,
+            input.subscription_handle = %self.subscription_handle,
+            "RtlUnsubscribeWnfStateChangeNotification",
+        );
+
+        if result.is_err() {
+            return false;
+        }
+
+        // SAFETY:
+        // - `self.context` and `self.release_context` were created together by `PendingUnsubscription::new` or
+        //   `PendingUnsubscription::retired` for the same type `F`
+        // - By the assumptions on `RtlUnsubscribeWnfStateChangeNotification`, the callback is not called with
+        //   `self.context` anymore, and the entry is discarded by the caller, so `self.context` is not used afterwards
+        unsafe { (self.release_context)(self.context) };
+
+        true
+    }
+}
+
+/// The list of retired subscriptions that still need to be unsubscribed, see [`Retirement`]
+static RETIRED_SUBSCRIPTIONS: Mutex<Vec<PendingUnsubscription>> = Mutex::new(Vec::new());
+
+/// Locks the global list of retired subscriptions
+fn lock_retired_subscriptions() -> MutexGuard<'static, Vec<PendingUnsubscription>> {
+    // We can access the list even when the mutex is poisoned because every entry is valid on its own
+    RETIRED_SUBSCRIPTIONS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Unsubscribes all retired subscriptions, keeping the ones that could not be unsubscribed for a later retry
+///
+/// This is skipped when called from within a listener because unsubscribing from within a listener may fail. The list
+/// is not locked while unsubscribing, so that a listener retiring its subscription at the same time does not block.
+fn unsubscribe_retired_subscriptions() {
+    if is_in_listener() {
+        return;
+    }
+
+    let retired = mem::take(&mut *lock_retired_subscriptions());
+
+    if retired.is_empty() {
+        return;
+    }
+
+    let pending: Vec<_> = retired.into_iter().filter(|entry| !entry.try_unsubscribe()).collect();
+    lock_retired_subscriptions().extend(pending);
+}
+
+/// Information on a subscription that is currently active
+///
+/// This is returned by [`active_subscriptions`].
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ActiveSubscription {
+    id: u64,
+    state_name: StateName,
+    listener_type_name: &'static str,
+    created_at: SystemTime,
+}
+
+impl ActiveSubscription {
+    /// Returns an opaque id of this subscription
+    ///
+    /// The id is unique among all subscriptions created in the current process. Ids increase in the order in which the
+    /// subscriptions were created.
+    pub const fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Returns the name of the state the listener is subscribed to
+    pub const fn state_name(&self) -> StateName {
+        self.state_name
+    }
+
+    /// Returns the type name of the listener as produced by [`std::any::type_name`]
+    ///
+    /// This is meant for diagnostic purposes only, its exact contents are not guaranteed to be stable.
+    pub const fn listener_type_name(&self) -> &'static str {
+        self.listener_type_name
+    }
+
+    /// Returns the time at which the subscription was created
+    pub const fn created_at(&self) -> SystemTime {
+        self.created_at
+    }
+}
+
+/// Returns information on all subscriptions created through this crate in the current process that are still active
+///
+/// A subscription is active from the time it is created until its listener is unsubscribed, either explicitly via
+/// [`Subscription::unsubscribe`] or by dropping the [`Subscription<'_, F>`](Subscription). This includes subscriptions
+/// that have been forgotten via [`Subscription::forget`]. It does not include subscriptions whose listeners could not
+/// be unsubscribed (see [`failed_unsubscription_stats`]) because their listeners are not called anymore, nor
+/// subscriptions of one-shot listeners that have been called (see [`OwnedState::subscribe_once`]).
+///
+/// This is useful for debugging, e.g. for finding out which listeners are still subscribed in a large application.
+/// The returned subscriptions are ordered by their [`id`](ActiveSubscription::id), i.e. by the time they were created.
+pub fn active_subscriptions() -> Vec<ActiveSubscription> {
+    let mut active_subscriptions: Vec<_> = ActiveSubscriptions::lock().entries.values().cloned().collect();
+    active_subscriptions.sort_unstable_by_key(ActiveSubscription::id);
+    active_subscriptions
+}
+
+/// The registry of active subscriptions, see [`active_subscriptions`]
+static ACTIVE_SUBSCRIPTIONS: Mutex<ActiveSubscriptions> = Mutex::new(ActiveSubscriptions {
+    entries: BTreeMap::new(),
+    next_id: 1,
+});
+
+/// Active subscriptions keyed by the addresses of their subscription handles
+#[derive(Debug)]
+struct ActiveSubscriptions {
+    entries: BTreeMap<usize, ActiveSubscription>,
+    next_id: u64,
+}
+
+impl ActiveSubscriptions {
+    /// Locks the global registry of active subscriptions
+    fn lock() -> MutexGuard<'static, Self> {
+        // We can access the registry even when the mutex is poisoned because every entry is valid on its own
+        match ACTIVE_SUBSCRIPTIONS.lock() {
+            Ok(guard) => guard,
+            Err(err) => err.into_inner(),
+        }
+    }
+
+    /// Adds a subscription with the given handle to the global registry of active subscriptions
+    fn register<F>(subscription_handle: SubscriptionHandle, state_name: StateName) {
+        let mut active_subscriptions = Self::lock();
+        let id = active_subscriptions.next_id;
+        active_subscriptions.next_id += 1;
+
+        active_subscriptions.entries.insert(
+            subscription_handle.as_ptr() as usize,
+            ActiveSubscription {
+                id,
+                state_name,
+                listener_type_name: any::type_name::<F>(),
+                created_at: SystemTime::now(),
+            },
+        );
+    }
+
+    /// Removes the subscription with the given handle from the global registry of active subscriptions
+    fn unregister(subscription_handle: SubscriptionHandle) {
+        Self::lock().entries.remove(&(subscription_handle.as_ptr() as usize));
+    }
+}
+
+/// The inner value of a [`Subscription<'_, F>`](Subscription)
+///
+/// Unlike [`Subscription<'_, F>`](Subscription), this does not have a lifetime and is not optional.
+struct SubscriptionInner<F> {
+    context: ManuallyDrop<Box<SubscriptionContext<F>>>,
+    subscription_handle: SubscriptionHandle,
+}
+
+// We cannot derive this because that would impose an unnecessary trait bound `F: Debug`
+impl<F> Debug for SubscriptionInner<F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SubscriptionInner")
+            .field("context", &self.context)
+            .field("subscription_handle", &self.subscription_handle)
+            .finish()
+    }
+}
+
+// We cannot derive this because that would impose an unnecessary trait bound `F: PartialEq<F>`
+impl<F> PartialEq for SubscriptionInner<F> {
+    fn eq(&self, other: &Self) -> bool {
+        self.subscription_handle == other.subscription_handle
+    }
+}
+
+// We cannot derive this because that would impose an unnecessary trait bound `F: Eq`
+impl<F> Eq for SubscriptionInner<F> {}
+
+impl<F> Hash for SubscriptionInner<F> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.subscription_handle.hash(state);
+    }
+}
+
+/// The handle to a subscription as returned from the WNF API
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[repr(transparent)]
+struct SubscriptionHandle(*mut c_void);
+
+// SAFETY:
+// By the assumptions on `RtlUnsubscribeWnfStateChangeNotification`, it is safe to call it with a subscription handle
+// originating from a different thread
+unsafe impl Send for SubscriptionHandle {}
+
+// SAFETY:
+// By the assumptions on `RtlUnsubscribeWnfStateChangeNotification`, it is safe to call it with a subscription handle
+// originating from a different thread
+unsafe impl Sync for SubscriptionHandle {}
+
+impl SubscriptionHandle {
+    /// Creates a `NULL` [`SubscriptionHandle`]
+    const fn null() -> Self {
+        Self(ptr::null_mut())
+    }
+
+    /// Returns a mutable raw pointer to the inner value for use in FFI
+    const fn as_ptr(&self) -> *mut c_void {
+        self.0
+    }
+}
+
+impl Display for SubscriptionHandle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#018x}", self.0 as u64)
+    }
+}
+
+/// The context of a subscription
+///
+/// In case unsubscribing fails, this is kept alive in the list of failed unsubscriptions until a retry succeeds (see
+/// [`gc_failed_unsubscriptions`]). Similarly, the context of a retired subscription is kept alive until it has been
+/// unsubscribed (see [`Retirement`]).
+///
+/// Unless the listener was subscribed through [`RawState::subscribe_lock_free`], we put it behind a mutex for two
+/// reasons:
+/// 1) to avoid race conditions between the subscription callback calling the listener and dropping the listener after
+///    (successfully or unsuccessfully) trying to unsubscribe
+/// 2) to avoid race conditions between parallel runs of the subscription callback calling the listener
+///
+/// Note that case 2) does not actually happen in practice because the WNF API runs all listeners within a process
+/// sequentially on a single thread. However, we don't have to assume this because we need the mutex for case 1) anyway.
+///
+/// A listener subscribed through [`RawState::subscribe_lock_free`] is instead called through a shared reference, which
+/// avoids case 2) by requiring it to be [`Sync`]. Case 1) is avoided by not dropping the listener after unsuccessfully
+/// trying to unsubscribe but only deactivating it, see [`LockFreeListener<F>`](LockFreeListener).
+///
+/// Subscribing allocates exactly one context on the heap, as the mutex is stored inline (`std::sync::Mutex` does not
+/// allocate on our MSRV). We deliberately do not pool contexts: The cost of the allocation is negligible compared to
+/// the calls to `RtlSubscribeWnfStateChangeNotification` and `RtlUnsubscribeWnfStateChangeNotification`, and reusing
+/// a context would require proving that the WNF API does not call the callback with it anymore, which we can only
+/// assume after unsubscribing has succeeded.
+struct SubscriptionContext<F> {
+    listener: ListenerCell<F>,
+    tracker: ChangeTracker,
+    stats: SubscriptionStatsCell,
+    dispatch: fn(&Self, ScopedData),
+    retire_on_call: bool,
+    retirement: Mutex<Retirement>,
+}
+
+impl<F> SubscriptionContext<F> {
+    /// Creates a new context from the given listener and change tracker, calling the listener behind a mutex
+    fn new<T>(listener: F, tracker: ChangeTracker) -> Self
+    where
+        F: StateListener<T>,
+        T: ?Sized,
+    {
+        Self {
+            listener: ListenerCell::Locked(Mutex::new(Some(listener))),
+            tracker,
+            stats: SubscriptionStatsCell::default(),
+            dispatch: Self::dispatch_locked::<T>,
+            retire_on_call: false,
+            retirement: Mutex::default(),
+        }
+    }
+
+    /// Creates a new context from the given listener and change tracker, calling the listener behind a mutex and
+    /// retiring the subscription when the listener is called for the first time
+    fn new_once<T>(listener: F, tracker: ChangeTracker) -> Self
+    where
+        F: StateListener<T>,
+        T: ?Sized,
+    {
+        Self {
+            retire_on_call: true,
+            ..Self::new(listener, tracker)
+        }
+    }
+
+    /// Creates a new context from the given listener and change tracker, calling the listener without locking
+    fn new_lock_free<T>(listener: F, tracker: ChangeTracker) -> Self
+    where
+        F: SyncStateListener<T> + Sync,
+        T: ?Sized,
+    {
+        Self {
+            listener: ListenerCell::LockFree(LockFreeListener::new(listener)),
+            tracker,
+            stats: SubscriptionStatsCell::default(),
+            dispatch: Self::dispatch_lock_free::<T>,
+            retire_on_call: false,
+            retirement: Mutex::default(),
+        }
+    }
+
+    /// Clears the context
+    ///
+    /// This makes sure the listener is not called anymore. If the listener is behind a mutex, it is removed from the
+    /// context, causing it to be dropped. This is useful when unsubscribing fails and we need to keep the context alive
+    /// but still want to drop the listener itself.
+    fn clear(&self) {
+        match &self.listener {
+            ListenerCell::Locked(listener) => {
+                // We can access the `Option<F>` even when the mutex is poisoned as we're only overwriting it with
+                // `None` and hence have no invariant to maintain
+                let mut listener = match listener.lock() {
+                    Ok(context) => context,
+                    Err(err) => err.into_inner(),
+                };
+
+                *listener = None;
+            }
+
+            ListenerCell::LockFree(listener) => listener.deactivate(),
+        }
+    }
+
+    /// Dispatches the given state update to the listener contained in this context, if any
+    fn dispatch(&self, data: ScopedData) {
+        (self.dispatch)(self, data);
+    }
+
+    /// Returns the statistics of the subscription
+    fn stats(&self) -> SubscriptionStats {
+        SubscriptionStats {
+            delivered: self.stats.delivered.load(Ordering::Relaxed),
+            errors: self.stats.errors.load(Ordering::Relaxed),
+            decode_failures: self.stats.decode_failures.load(Ordering::Relaxed),
+            last_seen_change_stamp: self.tracker.last_seen_change_stamp(),
+            last_delivery_time: *self.stats.lock_last_delivery_time(),
+        }
+    }
+
+    /// Locks the retirement state of the subscription
+    fn lock_retirement(&self) -> MutexGuard<'_, Retirement> {
+        // We can access the retirement state even when the mutex is poisoned because it is only updated while no code
+        // that could panic is running
+        self.retirement.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Sets the handle of the subscription this context belongs to once subscribing has succeeded
+    ///
+    /// If the listener has already been called, this retires the subscription.
+    fn set_subscription_handle(&self, subscription_handle: SubscriptionHandle) {
+        let mut retirement = self.lock_retirement();
+        retirement.subscription_handle = Some(subscription_handle);
+        self.retire_if_called(&mut retirement);
+    }
+
+    /// Records that the listener is called and retires the subscription if its handle is known already
+    fn record_call(&self) {
+        let mut retirement = self.lock_retirement();
+        retirement.is_called = true;
+        self.retire_if_called(&mut retirement);
+    }
+
+    /// Retires the subscription if the listener has been called and the subscription is still held by its
+    /// [`Subscription<'_, F>`](Subscription)
+    fn retire_if_called(&self, retirement: &mut Retirement) {
+        if !retirement.is_called || retirement.registration != Registration::Held {
+            return;
+        }
 
-// SAFETY:
-// The `buffer` pointer is only used for reading data
-unsafe impl Sync for ScopedData {}
+        if let Some(subscription_handle) = retirement.subscription_handle {
+            retirement.registration = Registration::Retired;
+            ActiveSubscriptions::unregister(subscription_handle);
 
-impl ScopedData {
-    /// Creates a new [`ScopedData`] with the given `buffer`, `buffer_size` and `change_stamp`
+            // SAFETY:
+            // This context belongs to the subscription with `subscription_handle`, whose registration has just been
+            // retired
+            let pending_unsubscription = unsafe { PendingUnsubscription::retired(self, subscription_handle) };
+            lock_retired_subscriptions().push(pending_unsubscription);
+        }
+    }
+
+    /// Claims the registration of the subscription for unsubscribing it when the owning
+    /// [`Subscription<'_, F>`](Subscription) is dropped or unsubscribed
     ///
-    /// # Safety
-    /// As long as the instance of [`ScopedData`] is live:
-    /// - `buffer` must be valid for reads of size `buffer_size`
-    /// - the memory range of size `buffer_size` starting at `buffer` must be initialized
-    unsafe fn new(buffer: *const c_void, buffer_size: usize, change_stamp: impl Into<ChangeStamp>) -> Self {
-        Self {
-            buffer,
-            buffer_size,
-            change_stamp: change_stamp.into(),
+    /// This returns `false` if the subscription has been retired, in which case it is unsubscribed through the list of
+    /// retired subscriptions instead.
+    fn claim_registration(&self) -> bool {
+        let mut retirement = self.lock_retirement();
+
+        if retirement.registration == Registration::Held {
+            retirement.registration = Registration::Claimed;
+            true
+        } else {
+            false
         }
     }
 
-    /// Obtains a [`DataAccessor<'a, T>`] for this [`ScopedData`]
+    /// Records that the owning [`Subscription<'_, F>`](Subscription) of a retired subscription has been dropped
     ///
-    /// The lifetime parameter `'a` of the returned [`DataAccessor<'a, T>`] is the lifetime of the reference to this
-    /// [`ScopedData`], making sure the [`DataAccessor<'a, T>`] can only be used as long as this [`ScopedData`] is live.
-    const fn accessor<T>(&self) -> DataAccessor<'_, T>
+    /// This returns whether the registration has been unsubscribed already, in which case the caller must drop the
+    /// context. Otherwise, it is dropped after unsubscribing, see [`PendingUnsubscription::retired`].
+    fn release_subscription(&self) -> bool {
+        let mut retirement = self.lock_retirement();
+        retirement.is_subscription_dropped = true;
+        retirement.registration == Registration::Unsubscribed
+    }
+
+    /// Records that the registration of a retired subscription has been unsubscribed
+    ///
+    /// This returns whether the owning [`Subscription<'_, F>`](Subscription) has been dropped already, in which case
+    /// the caller must drop the context. Otherwise, the [`Subscription<'_, F>`](Subscription) drops it.
+    fn release_registration(&self) -> bool {
+        let mut retirement = self.lock_retirement();
+        retirement.registration = Registration::Unsubscribed;
+        retirement.is_subscription_dropped
+    }
+
+    /// Returns whether the subscription has been retired
+    fn is_retired(&self) -> bool {
+        matches!(
+            self.lock_retirement().registration,
+            Registration::Retired | Registration::Unsubscribed
+        )
+    }
+
+    /// Dispatches the given state update to a listener behind a mutex
+    fn dispatch_locked<T>(&self, data: ScopedData)
     where
+        F: StateListener<T>,
         T: ?Sized,
     {
-        DataAccessor {
-            data: *self,
-            _marker: PhantomData,
+        if let ListenerCell::Locked(listener) = &self.listener {
+            if let Ok(mut listener) = listener.lock() {
+                if let Some(listener) = listener.as_mut() {
+                    self.tracker.deliver(data, |accessor, err| {
+                        self.stats.record_delivery(err.is_some());
+
+                        match err {
+                            Some(err) => listener.on_error(err, accessor),
+                            None => {
+                                if self.retire_on_call {
+                                    self.record_call();
+                                }
+
+                                listener.call(accessor);
+                            }
+                        }
+                    });
+                }
+            }
         }
     }
-}
-
-/// A handle to state data passed to state listeners
-///
-/// Listeners receive a [`DataAccessor<'a, T>`](DataAccessor) in their [`StateListener::call`] method. It can be used to
-/// obtain the state data at the time the update took place.
-///
-/// The lifetime parameter `'a` ties a [`DataAccessor<'a, T>`](DataAccessor) to the lifetime of the state data, which is
-/// only valid within the scope of the call to the listener.
-pub struct DataAccessor<'a, T>
-where
-    T: ?Sized,
-{
-    data: ScopedData,
-    _marker: PhantomData<&'a fn() -> T>,
-}
 
-impl<'a, T> DataAccessor<'a, T>
-where
-    T: ?Sized,
-{
-    /// Casts the data type of this [`DataAccessor<'a, T>`](DataAccessor) to a different type `U`
-    ///
-    /// The returned [`DataAccessor<'a, U>`](DataAccessor) represents the same underlying data, but treats them as being
-    /// of a different type `U`.
-    pub const fn cast<U>(self) -> DataAccessor<'a, U>
+    /// Dispatches the given state update to a listener that is called without locking
+    fn dispatch_lock_free<T>(&self, data: ScopedData)
     where
-        U: ?Sized,
+        F: SyncStateListener<T> + Sync,
+        T: ?Sized,
     {
-        DataAccessor {
-            data: self.data,
-            _marker: PhantomData,
+        if let ListenerCell::LockFree(listener) = &self.listener {
+            if let Some(listener) = listener.get() {
+                self.tracker.deliver(data, |accessor, err| {
+                    self.stats.record_delivery(err.is_some());
+
+                    match err {
+                        Some(err) => listener.on_error(err, accessor),
+                        None => listener.call(accessor),
+                    }
+                });
+            }
         }
     }
+}
 
-    /// Queries the change stamp of this [`DataAccessor<'_, T>`](DataAccessor)
-    ///
-    /// The change stamp returned by this method is the change stamp of the underlying state for the update that
-    /// caused the listener call to which this [`DataAccessor<'_, T>`](DataAccessor) was passed. Note that in contrast
-    /// to [`OwnedState::change_stamp`] or [`BorrowedState::change_stamp`], this does not involve an OS call.
-    pub const fn change_stamp(self) -> ChangeStamp {
-        self.data.change_stamp
+// We cannot derive this because that would impose an unnecessary trait bound `F: Debug`
+impl<F> Debug for SubscriptionContext<F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        struct Placeholder;
+
+        impl Debug for Placeholder {
+            fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+                f.write_str("<context>")
+            }
+        }
+
+        f.debug_struct("SubscriptionContext")
+            .field("listener", &Placeholder)
+            .field("tracker", &self.tracker)
+            .field("stats", &self.stats)
+            .finish()
     }
 }
 
-impl<T> DataAccessor<'_, T>
-where
-    T: Read<T>,
-{
-    /// Queries the data of this [`DataAccessor<'_, T>`](DataAccessor)
-    ///
-    /// This produces an owned `T` on the stack and hence requires `T: Sized`. In order to produce a `Box<T>` for
-    /// `T: ?Sized`, use the [`get_boxed`](DataAccessor::get_boxed) method.
-    ///
-    /// This returns the data of the accessor without a change stamp. In order to query both the data and the change
-    /// stamp, use the [`query`](DataAccessor::query) method.
-    ///
-    /// The data returned by this method are the data of the underlying state for the update that caused the listener
-    /// call to which this [`DataAccessor<'_, T>`](DataAccessor) was passed. Note that in contrast to
-    /// [`OwnedState::get`] or [`BorrowedState::get`], this does not involve an OS call.
-    ///
-    /// # Errors
-    /// Returns an error if the queried data is not a valid `T`
-    pub fn get(self) -> io::Result<T> {
-        self.get_as()
+/// The statistics of a subscription, which are updated while delivering state updates to the listener
+#[derive(Debug, Default)]
+struct SubscriptionStatsCell {
+    delivered: AtomicU64,
+    errors: AtomicU64,
+    decode_failures: AtomicU64,
+    last_delivery_time: Mutex<Option<SystemTime>>,
+}
+
+impl SubscriptionStatsCell {
+    /// Records that a state update has been delivered to the listener, either as a call or as an error
+    fn record_delivery(&self, is_error: bool) {
+        self.delivered.fetch_add(1, Ordering::Relaxed);
+
+        if is_error {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+
+        *self.lock_last_delivery_time() = Some(SystemTime::now());
     }
 
-    /// Queries the data of this [`DataAccessor<'_, T>`](DataAccessor) together with their change stamp
-    ///
-    /// This produces an owned `T` on the stack and hence requires `T: Sized`. In order to produce a `Box<T>` for
-    /// `T: ?Sized`, use the [`query_boxed`](DataAccessor::query_boxed) method.
-    ///
-    /// This returns the data of the accessor together with their change stamp as a [`StampedData<T>`]. In order to
-    /// only query the data, use the [`get`](DataAccessor::get) method.
-    ///
-    /// The data returned by this method are the data of the underlying state for the update that caused the listener
-    /// call to which this [`DataAccessor<'_, T>`](DataAccessor) was passed. Note that in contrast to
-    /// [`OwnedState::query`] or [`BorrowedState::query`], this does not involve an OS call.
-    ///
-    /// # Errors
-    /// Returns an error if the queried data is not a valid `T`
-    pub fn query(self) -> io::Result<StampedData<T>> {
-        self.query_as()
+    /// Locks the time of the last delivery
+    fn lock_last_delivery_time(&self) -> MutexGuard<'_, Option<SystemTime>> {
+        // We can access the time even when the mutex is poisoned because it is only ever replaced as a whole
+        self.last_delivery_time.lock().unwrap_or_else(PoisonError::into_inner)
     }
 }
 
-impl<T> DataAccessor<'_, T>
-where
-    T: Read<Box<T>> + ?Sized,
-{
-    /// Queries the data of this [`DataAccessor<'_, T>`](DataAccessor) as a box
-    ///
-    /// This produces a [`Box<T>`]. In order to produce an owned `T` on the stack (requiring `T: Sized`), use the
-    /// [`get`](DataAccessor::get) method.
-    ///
-    /// This returns the data of the accessor without a change stamp. In order to query both the data and the change
-    /// stamp, use the [`query_boxed`](DataAccessor::query_boxed) method.
-    ///
-    /// The data returned by this method are the data of the underlying state for the update that caused the listener
-    /// call to which this [`DataAccessor<'_, T>`](DataAccessor) was passed. Note that in contrast to
-    /// [`OwnedState::get_boxed`] or [`BorrowedState::get_boxed`], this does not involve an OS call.
-    ///
-    /// # Errors
-    /// Returns an error if the queried data is not a valid `T`
-    pub fn get_boxed(self) -> io::Result<Box<T>> {
-        self.get_as()
-    }
+/// The storage of the listener within a [`SubscriptionContext<F>`](SubscriptionContext)
+enum ListenerCell<F> {
+    /// A listener that is called behind a mutex
+    Locked(Mutex<Option<F>>),
 
-    /// Queries the data of this [`DataAccessor<'_, T>`](DataAccessor) as a box together with their change stamp
-    ///
-    /// This produces a [`Box<T>`]. In order to produce an owned `T` on the stack (requiring `T: Sized`), use the
-    /// [`query`](DataAccessor::query) method.
-    ///
-    /// This returns the data of the accessor together with their change stamp as a [`StampedData<Box<T>>`]. In order
-    /// to only query the data, use the [`get_boxed`](OwnedState::get_boxed) method.
-    ///
-    /// The data returned by this method are the data of the underlying state for the update that caused the listener
-    /// call to which this [`DataAccessor<'_, T>`](DataAccessor) was passed. Note that in contrast to
-    /// [`OwnedState::query_boxed`] or [`BorrowedState::query_boxed`], this does not involve an OS call.
-    ///
-    /// # Errors
-    /// Returns an error if the queried data is not a valid `T`
-    pub fn query_boxed(self) -> io::Result<StampedData<Box<T>>> {
-        self.query_as()
-    }
+    /// A listener that is called without locking
+    LockFree(LockFreeListener<F>),
 }
 
-impl<T> DataAccessor<'_, T>
-where
-    T: ?Sized,
-{
-    /// Queries the data of this [`DataAccessor<'_, T>`](DataAccessor) as a value of type `D` without a change stamp
-    ///
-    /// If `T: Sized`, then `D` can be either `T` or `Box<T>`.
-    /// If `T: !Sized`, then `D` must be `Box<T>`.
-    pub(crate) fn get_as<D>(self) -> io::Result<D>
-    where
-        T: Read<D>,
-    {
-        // SAFETY:
-        // - `self` was obtained from a `ScopedData` through `ScopedData::accessor`, which ties the lifetime parameter
-        //   `'a` of `DataAccessor<'a, T>` to the lifetime of the `ScopedData`, so the `ScopedData` is still live
-        // - `self.data` is a copy of this `ScopedData`, which was created through `ScopedData::new`
-        // - The safety conditions of `ScopedData::new` then imply those of `T::from_buffer`
-        unsafe { T::from_buffer(self.data.buffer, self.data.buffer_size) }
+/// A listener that is called through a shared reference without locking
+///
+/// Instead of being dropped after unsuccessfully trying to unsubscribe, the listener is deactivated, which prevents new
+/// calls but does not wait for a call that is already in progress. The listener is only dropped together with the
+/// [`SubscriptionContext<F>`](SubscriptionContext), i.e. after unsubscribing has succeeded.
+struct LockFreeListener<F> {
+    listener: F,
+    active: AtomicBool,
+}
+
+// SAFETY:
+// A shared reference to the contained `F` is only handed out by `LockFreeListener::get`, which requires `F: Sync`
+unsafe impl<F> Sync for LockFreeListener<F> {}
+
+impl<F> LockFreeListener<F> {
+    /// Creates a new active [`LockFreeListener<F>`](LockFreeListener) containing the given listener
+    const fn new(listener: F) -> Self {
+        Self {
+            listener,
+            active: AtomicBool::new(true),
+        }
     }
 
-    /// Queries the data of this [`DataAccessor<'_, T>`](DataAccessor) as a value of type `D` together with its change
-    /// stamp
-    ///
-    /// If `T: Sized`, then `D` can be either `T` or `Box<T>`.
-    /// If `T: !Sized`, then `D` must be `Box<T>`.
-    pub(crate) fn query_as<D>(self) -> io::Result<StampedData<D>>
+    /// Returns a reference to the contained listener unless it has been deactivated
+    fn get(&self) -> Option<&F>
     where
-        T: Read<D>,
+        F: Sync,
     {
-        Ok(StampedData::from_data_change_stamp(
-            self.get_as()?,
-            self.data.change_stamp,
-        ))
+        self.active.load(Ordering::Acquire).then_some(&self.listener)
     }
-}
-
-// We cannot derive this because that would impose an unnecessary trait bound `T: Copy`
-impl<T> Copy for DataAccessor<'_, T> where T: ?Sized {}
 
-// We cannot derive this because that would impose an unnecessary trait bound `T: Clone`
-impl<T> Clone for DataAccessor<'_, T>
-where
-    T: ?Sized,
-{
-    fn clone(&self) -> Self {
-        *self
+    /// Deactivates the contained listener, preventing it from being returned by [`LockFreeListener::get`]
+    fn deactivate(&self) {
+        self.active.store(false, Ordering::Release);
     }
 }
 
-// We cannot derive this because that would impose an unnecessary trait bound `T: Debug`
-impl<T> Debug for DataAccessor<'_, T>
-where
-    T: ?Sized,
-{
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        f.debug_struct("DataAccessor").field("data", &self.data).finish()
-    }
+/// The state of the automatic removal of a subscription whose listener is only called once
+///
+/// A subscription created through [`RawState::subscribe_once`] is retired when its listener is called: It is removed
+/// from the registry of active subscriptions and its registration with the WNF API is handed over from the owning
+/// [`Subscription<'_, F>`](Subscription) to a global list of retired subscriptions. As unsubscribing may fail from
+/// within a listener, that list is processed outside of listeners, e.g. whenever a subscription is created or
+/// unsubscribed (see [`unsubscribe_retired_subscriptions`]).
+///
+/// The context is then shared between the [`Subscription<'_, F>`](Subscription) and the list of retired subscriptions,
+/// and it is dropped by whichever of them releases it last.
+#[derive(Debug, Default)]
+struct Retirement {
+    subscription_handle: Option<SubscriptionHandle>,
+    is_called: bool,
+    registration: Registration,
+    is_subscription_dropped: bool,
 }
 
-/// A subscription of a listener to updates of a state
-///
-/// This is returned from [`OwnedState::subscribe`] and [`BorrowedState::subscribe`].
+/// The owner of the registration of a subscription with the WNF API
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+enum Registration {
+    /// The registration is held by the [`Subscription<'_, F>`](Subscription) and may still be retired
+    #[default]
+    Held,
+
+    /// The registration is being unsubscribed by the [`Subscription<'_, F>`](Subscription)
+    Claimed,
+
+    /// The registration has been handed over to the list of retired subscriptions
+    Retired,
+
+    /// The registration has been unsubscribed from the list of retired subscriptions
+    Unsubscribed,
+}
+
+/// Tracker for the change stamps a state listener has seen
 ///
-/// Note that the listener is automatically unsubscribed when the [`Subscription<'_, F>`](Subscription) is dropped. In
-/// this case, errors while unsubscribing are silently ignored. If you want to handle them explicitly, use the
-/// [`Subscription::unsubscribe`] method, which returns an [`io::Result<()>`](io::Result). Note that the listener will
-/// not be called anymore after unsubscribing, even when there is an error.
+/// This is used to detect state updates the listener has missed and to implement [`DeliveryMode::CoalesceToLatest`].
 ///
-/// If you want to keep the subscription for as long as the process is running and the state exists, use the
-/// [`Subscription::forget`] method.
-#[must_use = "a `Subscription` is unsubscribed immediately if it is not used"]
-pub struct Subscription<'a, F> {
-    inner: Option<SubscriptionInner<F>>,
-    _marker: PhantomData<&'a ()>,
+/// The last seen change stamp is stored in an atomic rather than behind a mutex so that listeners subscribed through
+/// [`RawState::subscribe_lock_free`] are called without any locking. It holds [`NO_CHANGE_STAMP`] if no change stamp
+/// has been seen yet.
+#[derive(Debug)]
+struct ChangeTracker {
+    state: RawState<[u8]>,
+    delivery_mode: DeliveryMode,
+    last_seen_change_stamp: AtomicU64,
 }
 
-impl<F> Subscription<'_, F> {
-    /// Forgets this [`Subscription<'_, F>`](Subscription), effectively keeping it forever
+/// The value of [`ChangeTracker::last_seen_change_stamp`] indicating that no change stamp has been seen yet
+///
+/// This is outside of the range of `u32`, so it cannot be confused with an actual change stamp.
+const NO_CHANGE_STAMP: u64 = u64::MAX;
+
+impl ChangeTracker {
+    /// Creates a new tracker for the given state, delivery mode and last seen change stamp
+    const fn new(
+        state: RawState<[u8]>,
+        delivery_mode: DeliveryMode,
+        last_seen_change_stamp: Option<ChangeStamp>,
+    ) -> Self {
+        let last_seen_change_stamp = match last_seen_change_stamp {
+            Some(change_stamp) => change_stamp.value() as u64,
+            None => NO_CHANGE_STAMP,
+        };
+
+        Self {
+            state,
+            delivery_mode,
+            last_seen_change_stamp: AtomicU64::new(last_seen_change_stamp),
+        }
+    }
+
+    /// Delivers the given state update by calling the given closure
     ///
-    /// When a [`Subscription<'_, F>`](Subscription) is dropped, the listener is unsubscribed. You can avoid this
-    /// behavior by calling this method. It consumes the [`Subscription<'_, F>`](Subscription) without dropping it,
-    /// effectively keeping the subscription for as long as the process is running and the state exists.
-    pub const fn forget(self) {
-        mem::forget(self);
+    /// In [`DeliveryMode::CoalesceToLatest`], this delivers the latest data of the state instead of the given data if
+    /// a gap is detected. The closure is passed the accessor for the delivered data together with the error that
+    /// occurred while preparing them, if any. It is not called at all if the listener has already seen the update.
+    fn deliver<T>(&self, data: ScopedData, op: impl FnOnce(DataAccessor<'_, T>, Option<io::Error>))
+    where
+        T: ?Sized,
+    {
+        let caught_up_data = match self.catch_up(data.change_stamp) {
+            Ok(caught_up_data) => caught_up_data,
+            Err(err) => {
+                if let Some(update_kind) = self.record(data.change_stamp) {
+                    op(data.accessor_with_update_kind(update_kind), Some(err));
+                }
+
+                return;
+            }
+        };
+
+        let data = match caught_up_data.as_ref() {
+            Some(caught_up_data) => {
+                // SAFETY:
+                // `caught_up_data` is dropped after `data` because it is declared before it, so the boxed slice is live
+                // and initialized for as long as `data` is live
+                let caught_up = unsafe {
+                    ScopedData::new(
+                        caught_up_data.data().as_ptr().cast(),
+                        caught_up_data.data().len(),
+                        caught_up_data.change_stamp(),
+                    )
+                };
+
+                // The new `data` does not outlive the given `data`, so it can record read failures in the same
+                // subscription statistics
+                ScopedData {
+                    stats: data.stats,
+                    ..caught_up
+                }
+            }
+            None => data,
+        };
+
+        if let Some(update_kind) = self.record(data.change_stamp) {
+            op(data.accessor_with_update_kind(update_kind), None);
+        }
     }
 
-    /// Unsubscribes the listener for this [`Subscription<'_, F>`](Subscription)
+    /// Queries the latest data of the state if a gap is detected before the given change stamp
     ///
-    /// This happens automatically when the [`Subscription<'_, F>`](Subscription) is dropped (unless you call
-    /// [`Subscription::forget`]), so there is usually no need to call this method. Its only purpose is to enable you
-    /// to handle errors while unsubscribing. Note that the listener will not be called anymore after unsubscribing,
-    /// even when there is an error.
+    /// This only queries the state in [`DeliveryMode::CoalesceToLatest`]. It returns [`None`] if there is no gap or if
+    /// the queried data are not newer than the given change stamp.
     ///
     /// # Errors
-    /// Returns an error if unsubscribing fails
-    pub fn unsubscribe(mut self) -> io::Result<()> {
-        self.try_unsubscribe()
+    /// Returns an error if querying fails
+    fn catch_up(&self, change_stamp: ChangeStamp) -> io::Result<Option<StampedData<Box<[u8]>>>> {
+        if self.delivery_mode != DeliveryMode::CoalesceToLatest || self.missed_before(change_stamp) == Some(0) {
+            return Ok(None);
+        }
+
+        let data: StampedData<Box<[u8]>> = self.state.query_as()?;
+        Ok(Some(data).filter(|data| data.change_stamp().is_newer_than(change_stamp)))
     }
 
-    /// Creates a new [`Subscription<'a, F>`](Subscription) from the given context and subscription handle
+    /// Records that the listener is notified about the update with the given change stamp
     ///
-    /// Note that the lifetime `'a` is inferred at the call site.
-    const fn new(context: Box<SubscriptionContext<F>>, subscription_handle: SubscriptionHandle) -> Self {
-        Self {
-            inner: Some(SubscriptionInner {
-                context: ManuallyDrop::new(context),
-                subscription_handle,
-            }),
-            _marker: PhantomData,
-        }
+    /// This returns the [`UpdateKind`] of the update or [`None`] if the listener has already seen it and the
+    /// notification should be skipped.
+    fn record(&self, change_stamp: ChangeStamp) -> Option<UpdateKind> {
+        let mut missed = 0;
+
+        self.last_seen_change_stamp
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |last_seen| {
+                missed = match Self::decode(last_seen) {
+                    Some(last_seen) if !change_stamp.is_newer_than(last_seen) => return None,
+                    Some(last_seen) => change_stamp.distance_from(last_seen) - 1,
+                    None => 0,
+                };
+
+                Some(change_stamp.value() as u64)
+            })
+            .ok()?;
+
+        Some(UpdateKind::from_missed(missed))
     }
 
-    fn try_unsubscribe(&mut self) -> io::Result<()> {
-        if let Some(inner) = self.inner.take() {
-            // SAFETY:
-            // - `inner.subscription_handle` was returned from a successful call to
-            //   `RtlSubscribeWnfStateChangeNotification`
-            // - `RtlUnsubscribeWnfStateChangeNotification` has not been called with `inner.subscription_handle` before
-            //   because it is only held in `inner` and `inner` is dropped afterwards
-            let result = unsafe { ntapi::RtlUnsubscribeWnfStateChangeNotification(inner.subscription_handle.as_ptr()) };
-
-            debug!(
-                target: ntapi::TRACING_TARGET,
-                ?result,
-                input.subscription_handle = %inner.subscription_handle,
-                "RtlUnsubscribeWnfStateChangeNotification",
-            );
-
-            if result.is_ok() {
-                ManuallyDrop::into_inner(inner.context);
+    /// Returns the number of updates missed before the given change stamp, if any change stamp has been seen yet
+    fn missed_before(&self, change_stamp: ChangeStamp) -> Option<u32> {
+        self.last_seen_change_stamp().map(|last_seen| {
+            if change_stamp.is_newer_than(last_seen) {
+                change_stamp.distance_from(last_seen) - 1
             } else {
-                // In case of an error, we do not call `ManuallyDrop::into_inner`, leaking the
-                // `Box<SubscriptionContext<F>>`
-                inner.context.clear();
+                0
             }
+        })
+    }
 
-            result.ok()?;
-        };
-
-        Ok(())
+    /// Returns the change stamp of the update the listener has last seen, if any
+    fn last_seen_change_stamp(&self) -> Option<ChangeStamp> {
+        Self::decode(self.last_seen_change_stamp.load(Ordering::Acquire))
     }
-}
 
-impl<F> Drop for Subscription<'_, F> {
-    fn drop(&mut self) {
-        let _ = self.try_unsubscribe();
+    /// Decodes a value of [`ChangeTracker::last_seen_change_stamp`] into a change stamp
+    fn decode(value: u64) -> Option<ChangeStamp> {
+        u32::try_from(value).ok().map(ChangeStamp::new)
     }
 }
 
-// We cannot derive this because that would impose an unnecessary trait bound `F: Debug`
-impl<F> Debug for Subscription<'_, F> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        f.debug_struct("Subscription").field("inner", &self.inner).finish()
+#[cfg(test)]
+mod tests {
+    #![allow(dead_code)]
+
+    use static_assertions::{assert_impl_all, assert_not_impl_any};
+
+    use super::*;
+    use crate::type_id::TypeId;
+
+    #[test]
+    fn subscription_handle_display() {
+        assert_eq!(SubscriptionHandle::null().to_string(), "0x0000000000000000");
     }
-}
 
-// We cannot derive this because that would impose an unnecessary trait bound `F: PartialEq<F>`
-impl<F> PartialEq for Subscription<'_, F> {
-    fn eq(&self, other: &Self) -> bool {
-        self.inner == other.inner
+    #[test]
+    fn update_kind_from_missed() {
+        assert_eq!(UpdateKind::from_missed(0), UpdateKind::Sequential);
+        assert_eq!(UpdateKind::from_missed(2), UpdateKind::Coalesced { missed: 2 });
     }
-}
 
-// We cannot derive this because that would impose an unnecessary trait bound `F: Eq`
-impl<F> Eq for Subscription<'_, F> {}
+    #[test]
+    fn change_tracker_records_sequential_updates() {
+        let tracker = ChangeTracker::new(sample_state(), DeliveryMode::EveryChange, Some(ChangeStamp::new(1)));
 
-// We cannot derive this because that would impose an unnecessary trait bound `F: Hash`
-impl<F> Hash for Subscription<'_, F> {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.inner.hash(state);
+        assert_eq!(tracker.record(ChangeStamp::new(2)), Some(UpdateKind::Sequential));
+        assert_eq!(tracker.record(ChangeStamp::new(3)), Some(UpdateKind::Sequential));
     }
-}
-
-/// The inner value of a [`Subscription<'_, F>`](Subscription)
-///
-/// Unlike [`Subscription<'_, F>`](Subscription), this does not have a lifetime and is not optional.
-struct SubscriptionInner<F> {
-    context: ManuallyDrop<Box<SubscriptionContext<F>>>,
-    subscription_handle: SubscriptionHandle,
-}
 
-// We cannot derive this because that would impose an unnecessary trait bound `F: Debug`
-impl<F> Debug for SubscriptionInner<F> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        f.debug_struct("SubscriptionInner")
-            .field("context", &self.context)
-            .field("subscription_handle", &self.subscription_handle)
-            .finish()
+    #[test]
+    fn change_tracker_records_missed_updates() {
+        let tracker = ChangeTracker::new(sample_state(), DeliveryMode::EveryChange, Some(ChangeStamp::new(1)));
+
+        assert_eq!(
+            tracker.record(ChangeStamp::new(5)),
+            Some(UpdateKind::Coalesced { missed: 3 })
+        );
+        assert_eq!(tracker.record(ChangeStamp::new(6)), Some(UpdateKind::Sequential));
     }
-}
 
-// We cannot derive this because that would impose an unnecessary trait bound `F: PartialEq<F>`
-impl<F> PartialEq for SubscriptionInner<F> {
-    fn eq(&self, other: &Self) -> bool {
-        self.subscription_handle == other.subscription_handle
+    #[test]
+    fn change_tracker_records_missed_updates_across_wrap_around() {
+        let tracker = ChangeTracker::new(
+            sample_state(),
+            DeliveryMode::EveryChange,
+            Some(ChangeStamp::new(u32::MAX - 1)),
+        );
+
+        assert_eq!(
+            tracker.record(ChangeStamp::new(1)),
+            Some(UpdateKind::Coalesced { missed: 2 })
+        );
+        assert_eq!(tracker.record(ChangeStamp::new(u32::MAX)), None);
     }
-}
 
-// We cannot derive this because that would impose an unnecessary trait bound `F: Eq`
-impl<F> Eq for SubscriptionInner<F> {}
+    #[test]
+    fn change_tracker_treats_first_update_as_sequential_if_nothing_seen() {
+        let tracker = ChangeTracker::new(sample_state(), DeliveryMode::EveryChange, None);
 
-impl<F> Hash for SubscriptionInner<F> {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.subscription_handle.hash(state);
+        assert_eq!(tracker.record(ChangeStamp::new(5)), Some(UpdateKind::Sequential));
     }
-}
 
-/// The handle to a subscription as returned from the WNF API
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
-#[repr(transparent)]
-struct SubscriptionHandle(*mut c_void);
+    #[test]
+    fn change_tracker_skips_seen_updates() {
+        let tracker = ChangeTracker::new(
+            sample_state(),
+            DeliveryMode::CoalesceToLatest,
+            Some(ChangeStamp::new(5)),
+        );
+
+        assert_eq!(tracker.record(ChangeStamp::new(4)), None);
+        assert_eq!(tracker.record(ChangeStamp::new(5)), None);
+    }
 
-// SAFETY:
-// By the assumptions on `RtlUnsubscribeWnfStateChangeNotification`, it is safe to call it with a subscription handle
-// originating from a different thread
-unsafe impl Send for SubscriptionHandle {}
+    #[test]
+    fn change_tracker_does_not_deliver_seen_updates_twice() {
+        let tracker = ChangeTracker::new(sample_state(), DeliveryMode::EveryChange, None);
+        let mut delivered = Vec::new();
 
-// SAFETY:
-// By the assumptions on `RtlUnsubscribeWnfStateChangeNotification`, it is safe to call it with a subscription handle
-// originating from a different thread
-unsafe impl Sync for SubscriptionHandle {}
+        let buffer = [0u8; 4];
 
-impl SubscriptionHandle {
-    /// Creates a `NULL` [`SubscriptionHandle`]
-    const fn null() -> Self {
-        Self(ptr::null_mut())
+        for change_stamp in [1, 2, 2, 1, 3] {
+            // SAFETY:
+            // `buffer` is live and initialized for as long as `data` is live because it is declared before it
+            let data = unsafe { ScopedData::new(buffer.as_ptr().cast(), buffer.len(), ChangeStamp::new(change_stamp)) };
+
+            tracker.deliver::<u32>(data, |accessor, err| {
+                assert!(err.is_none());
+                delivered.push(accessor.change_stamp());
+            });
+        }
+
+        assert_eq!(
+            delivered,
+            [ChangeStamp::new(1), ChangeStamp::new(2), ChangeStamp::new(3)]
+        );
     }
 
-    /// Returns a mutable raw pointer to the inner value for use in FFI
-    const fn as_ptr(&self) -> *mut c_void {
-        self.0
+    #[test]
+    fn change_tracker_does_not_catch_up_in_every_change_mode() {
+        let tracker = ChangeTracker::new(sample_state(), DeliveryMode::EveryChange, Some(ChangeStamp::new(1)));
+
+        assert!(tracker.catch_up(ChangeStamp::new(5)).unwrap().is_none());
     }
-}
 
-impl Display for SubscriptionHandle {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "{:#018x}", self.0 as u64)
+    #[test]
+    fn change_tracker_records_first_update_without_last_seen_change_stamp() {
+        let tracker = ChangeTracker::new(sample_state(), DeliveryMode::EveryChange, None);
+
+        assert_eq!(tracker.missed_before(ChangeStamp::new(5)), None);
+        assert_eq!(tracker.record(ChangeStamp::new(5)), Some(UpdateKind::Sequential));
+        assert_eq!(tracker.missed_before(ChangeStamp::new(7)), Some(1));
     }
-}
 
-/// The context of a subscription
-///
-/// This will be leaked on the heap in case unsubscribing fails.
-///
-/// We put the listener behind a mutex for two reasons:
-/// 1) to avoid race conditions between the subscription callback calling the listener and dropping the listener after
-///    (successfully or unsuccessfully) trying to unsubscribe
-/// 2) to avoid race conditions between parallel runs of the subscription callback calling the listener
-///
-/// Note that case 2) does not actually happen in practice because the WNF API runs all listeners within a process
-/// sequentially on a single thread. However, we don't have to assume this because we need the mutex for case 1) anyway.
-struct SubscriptionContext<F>(Mutex<Option<F>>);
+    #[test]
+    fn lock_free_listener_deactivate() {
+        let listener = LockFreeListener::new(42);
+        assert_eq!(listener.get(), Some(&42));
 
-impl<F> SubscriptionContext<F> {
-    /// Creates a new context from the given listener
-    fn new(listener: F) -> Self {
-        Self(Mutex::new(Some(listener)))
+        listener.deactivate();
+        assert_eq!(listener.get(), None);
     }
 
-    /// Clears the context
-    ///
-    /// This removes the listener from the context, causing it to be dropped and not be called anymore. This is useful
-    /// when unsubscribing fails and we need to leak the context but still want to drop the listener itself.
-    fn clear(&self) {
-        // We can access the `Option<F>` even when the mutex is poisoned as we're only overwriting it with `None` and
-        // hence have no invariant to maintain
-        let mut listener = match self.0.lock() {
-            Ok(context) => context,
-            Err(err) => err.into_inner(),
+    #[test]
+    fn active_subscriptions_register_and_unregister() {
+        let subscription_handle = SubscriptionHandle(ptr::NonNull::<u8>::dangling().as_ptr().cast());
+        let state_name = StateName::from_opaque_value(0x1234);
+        let is_registered = || {
+            active_subscriptions()
+                .iter()
+                .any(|entry| entry.state_name() == state_name)
         };
 
-        *listener = None;
+        ActiveSubscriptions::register::<u32>(subscription_handle, state_name);
+        assert!(is_registered());
+
+        ActiveSubscriptions::unregister(subscription_handle);
+        assert!(!is_registered());
     }
 
-    /// Calls the given closure on the listener contained in this context, if any
-    fn with_listener(&self, op: impl FnOnce(&mut F)) {
-        if let Ok(mut listener) = self.0.lock() {
-            if let Some(listener) = listener.as_mut() {
-                op(listener);
-            }
+    #[test]
+    fn listener_scope_sets_and_resets_state_name() {
+        let state_name = StateName::from_opaque_value(0x0D83_063E_A3BE_5075);
+
+        {
+            let _scope = ListenerScope::enter(state_name);
+            assert_eq!(LISTENER_STATE_NAME.with(Cell::get), Some(state_name));
         }
+
+        assert_eq!(LISTENER_STATE_NAME.with(Cell::get), None);
     }
-}
 
-// We cannot derive this because that would impose an unnecessary trait bound `F: Debug`
-impl<F> Debug for SubscriptionContext<F> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        struct Placeholder;
+    #[test]
+    fn data_accessor_is_send_and_sync_regardless_of_data_type() {
+        type NeitherSendNorSync = *const ();
+        assert_not_impl_any!(NeitherSendNorSync: Send, Sync);
 
-        impl Debug for Placeholder {
-            fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-                f.write_str("<context>")
-            }
-        }
+        assert_impl_all!(DataAccessor<'_, NeitherSendNorSync>: Send, Sync);
+    }
+
+    #[test]
+    fn data_snapshot_is_send_and_sync_regardless_of_data_type() {
+        type NeitherSendNorSync = *const ();
+        assert_not_impl_any!(NeitherSendNorSync: Send, Sync);
 
-        f.debug_tuple("SubscriptionContext").field(&Placeholder).finish()
+        assert_impl_all!(DataSnapshot<NeitherSendNorSync>: Send, Sync);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    #![allow(dead_code)]
+    #[test]
+    fn data_snapshot_outlives_data() {
+        let snapshot = {
+            let buffer = vec![1u16, 2, 3];
 
-    use std::cell::Cell;
+            // SAFETY:
+            // `buffer` is live and initialized for as long as `data` is used
+            let data =
+                unsafe { ScopedData::new(buffer.as_ptr().cast(), mem::size_of_val(&*buffer), ChangeStamp::new(4)) };
 
-    use static_assertions::{assert_impl_all, assert_not_impl_any};
+            data.accessor_with_update_kind::<[u16]>(UpdateKind::Coalesced { missed: 1 })
+                .to_owned_snapshot()
+        };
 
-    use super::*;
+        assert_eq!(snapshot.change_stamp(), ChangeStamp::new(4));
+        assert_eq!(snapshot.update_kind(), UpdateKind::Coalesced { missed: 1 });
+        assert_eq!(*snapshot.get_boxed().unwrap(), [1, 2, 3]);
+        assert_eq!(
+            *snapshot.clone().cast::<[u8]>().get_boxed().unwrap(),
+            *snapshot.as_bytes()
+        );
+    }
 
     #[test]
-    fn subscription_handle_display() {
-        assert_eq!(SubscriptionHandle::null().to_string(), "0x0000000000000000");
+    fn slow_listener_threshold_roundtrip() {
+        assert_eq!(slow_listener_threshold(), None);
+
+        set_slow_listener_threshold(Some(Duration::from_millis(100)));
+        assert_eq!(slow_listener_threshold(), Some(Duration::from_millis(100)));
+
+        set_slow_listener_threshold(Some(Duration::MAX));
+        assert_eq!(slow_listener_threshold(), Some(Duration::from_nanos(u64::MAX - 1)));
+
+        set_slow_listener_threshold(None);
+        assert_eq!(slow_listener_threshold(), None);
     }
 
     #[test]
-    fn data_accessor_is_send_and_sync_regardless_of_data_type() {
+    fn data_accessor_records_read_failures() {
+        let stats = SubscriptionStatsCell::default();
+        let buffer = [0u8; 3];
+
+        // SAFETY:
+        // - `buffer` is live and initialized for as long as `data` is live because it is declared before it
+        // - `stats` is live for as long as `data` is live because it is declared before it
+        let data =
+            unsafe { ScopedData::new(buffer.as_ptr().cast(), buffer.len(), ChangeStamp::initial()).with_stats(&stats) };
+        let accessor = data.accessor_with_update_kind::<u32>(UpdateKind::Sequential);
+
+        assert!(accessor.get().is_err());
+        assert!(accessor.cast::<[u8]>().get_boxed().is_ok());
+        assert!(accessor.cast::<[u16]>().try_get_slice().is_err());
+
+        assert_eq!(stats.decode_failures.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn subscription_stats_cell_records_deliveries() {
+        let stats = SubscriptionStatsCell::default();
+
+        stats.record_delivery(false);
+        stats.record_delivery(true);
+
+        assert_eq!(stats.delivered.load(Ordering::Relaxed), 2);
+        assert_eq!(stats.errors.load(Ordering::Relaxed), 1);
+        assert!(stats.lock_last_delivery_time().is_some());
+    }
+
+    fn sample_state() -> RawState<[u8]> {
+        RawState::from_state_name_and_type_id(StateName::from_opaque_value(0), TypeId::none())
+    }
+
+    #[test]
+    fn owned_listener_is_send_and_sync_regardless_of_data_type() {
         type NeitherSendNorSync = *const ();
         assert_not_impl_any!(NeitherSendNorSync: Send, Sync);
 
-        assert_impl_all!(DataAccessor<'_, NeitherSendNorSync>: Send, Sync);
+        assert_impl_all!(OwnedListener<(), NeitherSendNorSync>: Send, Sync);
+    }
+
+    #[test]
+    fn owned_listener_forwards_errors() {
+        let mut results = Vec::new();
+        let mut listener = OwnedListener::new(|result: io::Result<StampedData<u32>>| results.push(result));
+
+        let buffer = [0u8; 4];
+
+        // SAFETY:
+        // `buffer` is live and initialized for as long as `data` is live because it is declared before it
+        let data = unsafe { ScopedData::new(buffer.as_ptr().cast(), buffer.len(), ChangeStamp::initial()) };
+        StateListener::<u32>::on_error(
+            &mut listener,
+            io::Error::new(io::ErrorKind::Other, "test error"),
+            data.accessor_with_update_kind(UpdateKind::Sequential),
+        );
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].as_ref().unwrap_err().to_string(), "test error");
+    }
+
+    #[test]
+    fn pulse_listener_ignores_non_empty_data() {
+        let mut change_stamps = Vec::new();
+        let mut listener = PulseListener::new(|change_stamp| change_stamps.push(change_stamp));
+
+        let buffer = [0u8; 4];
+
+        // SAFETY:
+        // `buffer` is live and initialized for as long as the `ScopedData` instances are live because it is declared
+        // before them
+        let (data, pulse, replayed) = unsafe {
+            (
+                ScopedData::new(buffer.as_ptr().cast(), buffer.len(), ChangeStamp::new(1)),
+                ScopedData::new(buffer.as_ptr().cast(), 0, ChangeStamp::new(2)),
+                ScopedData::new(buffer.as_ptr().cast(), 0, ChangeStamp::initial()),
+            )
+        };
+
+        for data in [data, pulse, replayed] {
+            StateListener::<u32>::call(&mut listener, data.accessor_with_update_kind(UpdateKind::Sequential));
+        }
+
+        assert_eq!(change_stamps, [ChangeStamp::new(2)]);
+    }
+
+    #[test]
+    fn stamp_listener_is_called_for_updates_and_errors() {
+        let mut calls = Vec::new();
+        let mut listener = StampListener::new(|change_stamp| calls.push(change_stamp));
+
+        let buffer = [0u8; 4];
+
+        // SAFETY:
+        // `buffer` is live and initialized for as long as the `ScopedData` instances are live because it is declared
+        // before them
+        let (first, second) = unsafe {
+            (
+                ScopedData::new(buffer.as_ptr().cast(), buffer.len(), ChangeStamp::new(1)),
+                ScopedData::new(buffer.as_ptr().cast(), 0, ChangeStamp::new(2)),
+            )
+        };
+
+        StateListener::<u32>::call(&mut listener, first.accessor_with_update_kind(UpdateKind::Sequential));
+        StateListener::<u32>::on_error(
+            &mut listener,
+            io::Error::new(io::ErrorKind::Other, "test"),
+            second.accessor_with_update_kind(UpdateKind::Sequential),
+        );
+
+        assert_eq!(calls, [ChangeStamp::new(1), ChangeStamp::new(2)]);
+    }
+
+    #[test]
+    fn once_listener_is_called_once() {
+        let mut calls = Vec::new();
+        let mut listener = OnceListener::new(|accessor: DataAccessor<'_, u32>| calls.push(accessor.change_stamp()));
+        assert!(!listener.is_done());
+
+        let buffer = [0u8; 4];
+
+        // SAFETY:
+        // `buffer` is live and initialized for as long as the `ScopedData` instances are live because it is declared
+        // before them
+        let (first, second) = unsafe {
+            (
+                ScopedData::new(buffer.as_ptr().cast(), buffer.len(), ChangeStamp::new(1)),
+                ScopedData::new(buffer.as_ptr().cast(), buffer.len(), ChangeStamp::new(2)),
+            )
+        };
+
+        for data in [first, second] {
+            listener.call(data.accessor_with_update_kind(UpdateKind::Sequential));
+        }
+
+        assert!(listener.is_done());
+        assert_eq!(calls, [ChangeStamp::new(1)]);
     }
 
     #[test]
@@ -0,0 +1,195 @@
+//! Subscribing to state changes through a value that is kept alive by the subscription
+
+#![deny(unsafe_code)]
+
+use std::fmt::{self, Debug, Formatter};
+use std::io;
+use std::sync::Arc;
+
+use crate::state::{AsState, OwnedState};
+use crate::subscribe::{SeenChangeStamp, StateListener, Subscription, SubscriptionStats};
+
+impl<T> OwnedState<T>
+where
+    T: ?Sized,
+{
+    /// Subscribes the given state listener to this state, keeping the state alive for as long as the subscription
+    ///
+    /// This is the same as [`subscribe`](OwnedState::subscribe), except that it takes the state as an
+    /// [`Arc<OwnedState<T>>`](Arc) and returns an [`ArcSubscription<T, F>`](ArcSubscription), which holds a reference
+    /// to the state and hence has no lifetime parameter. This makes it possible to store a state and a subscription to
+    /// it together in a single struct without resorting to `unsafe` code or leaking the state.
+    ///
+    /// The state is not deleted before the listener has been unsubscribed, even if all other references to it are
+    /// dropped first.
+    ///
+    /// This is a shorthand for [`SubscribeOwning::subscribe_owning`], which works for other smart pointers as well.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use std::sync::Arc;
+    ///
+    /// use wnf::{ArcSubscription, DataAccessor, OwnedState, SeenChangeStamp};
+    ///
+    /// struct Service {
+    ///     subscription: ArcSubscription<u32, fn(DataAccessor<'_, u32>)>,
+    /// }
+    ///
+    /// let state = Arc::new(OwnedState::<u32>::create_temporary()?);
+    /// let listener: fn(DataAccessor<'_, u32>) = |accessor| println!("{:?}", accessor.get());
+    ///
+    /// let service = Service {
+    ///     subscription: state.subscribe_arc(listener, SeenChangeStamp::Current)?,
+    /// };
+    ///
+    /// service.subscription.state().set(&42)?;
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an error if subscribing fails
+    pub fn subscribe_arc<F>(
+        self: Arc<Self>,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+    ) -> io::Result<ArcSubscription<T, F>>
+    where
+        F: StateListener<T> + Send + 'static,
+    {
+        self.subscribe_owning(listener, last_seen_change_stamp)
+    }
+}
+
+/// A trait for subscribing to a state through a value that is then kept alive by the subscription
+///
+/// This is implemented for all types implementing [`AsState`], in particular smart pointers to states such as
+/// [`Arc<OwnedState<T>>`](Arc) or [`Box<OwnedState<T>>`](Box). Unlike [`OwnedState::subscribe`], which borrows the
+/// state, [`subscribe_owning`](SubscribeOwning::subscribe_owning) moves the value into the returned
+/// [`OwningSubscription<S, F>`](OwningSubscription). If `S` does not borrow anything (e.g. for an
+/// [`Arc<OwnedState<T>>`](Arc)), the subscription is `'static` and can be moved into async tasks or stored alongside
+/// the state without lifetime issues.
+pub trait SubscribeOwning: AsState + Sized {
+    /// Subscribes the given state listener to this state, keeping this value alive for as long as the subscription
+    ///
+    /// See [`OwnedState::subscribe`] for the meaning of the arguments.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use wnf::{DataAccessor, OwnedState, SeenChangeStamp, SubscribeOwning};
+    ///
+    /// let state = Box::new(OwnedState::<u32>::create_temporary()?);
+    ///
+    /// let subscription = state.subscribe_owning(
+    ///     |accessor: DataAccessor<'_, u32>| println!("{:?}", accessor.get()),
+    ///     SeenChangeStamp::Current,
+    /// )?;
+    ///
+    /// std::thread::spawn(move || subscription.state().set(&42))
+    ///     .join()
+    ///     .unwrap()?;
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an error if subscribing fails
+    fn subscribe_owning<F>(
+        self,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+    ) -> io::Result<OwningSubscription<Self, F>>
+    where
+        F: StateListener<Self::Data> + Send + 'static,
+    {
+        let subscription = self.as_state().raw.subscribe(listener, last_seen_change_stamp)?;
+
+        Ok(OwningSubscription {
+            subscription,
+            state: self,
+        })
+    }
+}
+
+impl<S> SubscribeOwning for S where S: AsState {}
+
+/// A subscription of a listener to updates of a state that keeps the state alive
+///
+/// This is returned from [`SubscribeOwning::subscribe_owning`]. It behaves like a
+/// [`Subscription<'_, F>`](Subscription), but holds the value `S` through which the listener was subscribed instead of
+/// borrowing the state.
+///
+/// Note that the listener is automatically unsubscribed when the [`OwningSubscription<S, F>`](OwningSubscription) is
+/// dropped. In this case, errors while unsubscribing are silently ignored. If you want to handle them explicitly, use
+/// the [`OwningSubscription::unsubscribe`] method.
+#[must_use = "an `OwningSubscription` is unsubscribed immediately if it is not used"]
+pub struct OwningSubscription<S, F> {
+    // This must be declared before `state` so the listener is unsubscribed before the state is dropped
+    subscription: Subscription<'static, F>,
+    state: S,
+}
+
+/// A subscription of a listener to updates of a state that is kept alive through an [`Arc<OwnedState<T>>`](Arc)
+///
+/// This is returned from [`OwnedState::subscribe_arc`].
+pub type ArcSubscription<T, F> = OwningSubscription<Arc<OwnedState<T>>, F>;
+
+impl<S, F> OwningSubscription<S, F> {
+    /// Returns the value this [`OwningSubscription<S, F>`](OwningSubscription) keeps alive
+    pub const fn state(&self) -> &S {
+        &self.state
+    }
+
+    /// Returns statistics on the state updates delivered to the listener of this
+    /// [`OwningSubscription<S, F>`](OwningSubscription)
+    ///
+    /// See [`Subscription::stats`]
+    pub fn stats(&self) -> SubscriptionStats {
+        self.subscription.stats()
+    }
+
+    /// Unsubscribes the listener for this [`OwningSubscription<S, F>`](OwningSubscription), returning the value it
+    /// kept alive
+    ///
+    /// See [`Subscription::unsubscribe`]
+    ///
+    /// # Errors
+    /// Returns an error if unsubscribing fails. In this case, the value is dropped.
+    pub fn unsubscribe(self) -> io::Result<S> {
+        self.subscription.unsubscribe()?;
+        Ok(self.state)
+    }
+}
+
+// We cannot derive this because that would impose an unnecessary trait bound `F: Debug`
+impl<S, F> Debug for OwningSubscription<S, F>
+where
+    S: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OwningSubscription")
+            .field("subscription", &self.subscription)
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use static_assertions::{assert_impl_all, assert_not_impl_any};
+
+    use super::*;
+
+    #[test]
+    fn arc_subscription_is_send_and_sync_if_listener_is_send() {
+        type SendNotSync = Cell<()>;
+        assert_impl_all!(SendNotSync: Send);
+        assert_not_impl_any!(SendNotSync: Sync);
+
+        assert_impl_all!(ArcSubscription<u32, SendNotSync>: Send, Sync);
+    }
+}
@@ -0,0 +1,259 @@
+//! Methods for subscribing async listeners to state changes
+//!
+//! This module only adds inherent impls to [`OwnedState<T>`] and [`BorrowedState<'_, T>`](BorrowedState) as well as
+//! the [`AsyncListener<F, D>`] type.
+
+#![deny(unsafe_code)]
+
+use std::fmt::{self, Debug, Formatter};
+use std::future::Future;
+use std::io;
+use std::marker::PhantomData;
+
+use tokio::runtime::Handle;
+
+use crate::data::StampedData;
+use crate::read::Read;
+use crate::state::{BorrowedState, OwnedState, RawState};
+use crate::subscribe::{DataAccessor, SeenChangeStamp, StateListener, Subscription};
+
+impl<T> OwnedState<T>
+where
+    T: Read<T>,
+{
+    /// Subscribes the given async state listener to this state
+    ///
+    /// On every state update, the state data are read and the future returned by the listener is spawned onto the
+    /// runtime represented by the given [`Handle`]. Since the data are only valid within the scope of the (synchronous)
+    /// callback invoked by the WNF API, they are read *before* the future is spawned, so the listener receives owned
+    /// data as a [`StampedData<T>`] rather than a [`DataAccessor<'_, T>`](DataAccessor).
+    ///
+    /// This produces an owned `T` on the stack and hence requires `T: Sized`. In order to produce a `Box<T>` for
+    /// `T: ?Sized`, use the [`subscribe_boxed_async`](OwnedState::subscribe_boxed_async) method.
+    ///
+    /// Note that the spawned futures are not awaited by `wnf`, so they may run concurrently and complete in a
+    /// different order than the state updates occurred. See [`OwnedState::subscribe`] for the meaning of the
+    /// `last_seen_change_stamp` argument and for how unsubscribing works.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use tokio::runtime::Handle;
+    /// use wnf::{OwnedState, SeenChangeStamp};
+    ///
+    /// let state = OwnedState::<u32>::create_temporary()?;
+    ///
+    /// let _subscription = state.subscribe_async(
+    ///     Handle::current(),
+    ///     |result| async move {
+    ///         let value = result.unwrap().into_data();
+    ///         println!("State data updated: {value}");
+    ///     },
+    ///     SeenChangeStamp::Current,
+    /// )?;
+    ///
+    /// state.set(&1)?;
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an error if subscribing fails
+    pub fn subscribe_async<F, Fut>(
+        &self,
+        handle: Handle,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+    ) -> io::Result<Subscription<'_, AsyncListener<F, T>>>
+    where
+        F: FnMut(io::Result<StampedData<T>>) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.raw.subscribe_async(handle, listener, last_seen_change_stamp)
+    }
+}
+
+impl<T> OwnedState<T>
+where
+    T: Read<Box<T>> + ?Sized,
+{
+    /// Subscribes the given async state listener to this state, passing the data as a box
+    ///
+    /// This is the same as [`subscribe_async`](OwnedState::subscribe_async), except that it produces a [`Box<T>`]
+    /// instead of an owned `T` on the stack (requiring `T: Sized`).
+    ///
+    /// # Errors
+    /// Returns an error if subscribing fails
+    pub fn subscribe_boxed_async<F, Fut>(
+        &self,
+        handle: Handle,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+    ) -> io::Result<Subscription<'_, AsyncListener<F, Box<T>>>>
+    where
+        F: FnMut(io::Result<StampedData<Box<T>>>) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.raw.subscribe_boxed_async(handle, listener, last_seen_change_stamp)
+    }
+}
+
+impl<'a, T> BorrowedState<'a, T>
+where
+    T: Read<T>,
+{
+    /// Subscribes the given async state listener to this state
+    ///
+    /// See [`OwnedState::subscribe_async`]
+    pub fn subscribe_async<F, Fut>(
+        self,
+        handle: Handle,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+    ) -> io::Result<Subscription<'a, AsyncListener<F, T>>>
+    where
+        F: FnMut(io::Result<StampedData<T>>) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.raw.subscribe_async(handle, listener, last_seen_change_stamp)
+    }
+}
+
+impl<'a, T> BorrowedState<'a, T>
+where
+    T: Read<Box<T>> + ?Sized,
+{
+    /// Subscribes the given async state listener to this state, passing the data as a box
+    ///
+    /// See [`OwnedState::subscribe_boxed_async`]
+    pub fn subscribe_boxed_async<F, Fut>(
+        self,
+        handle: Handle,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+    ) -> io::Result<Subscription<'a, AsyncListener<F, Box<T>>>>
+    where
+        F: FnMut(io::Result<StampedData<Box<T>>>) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.raw.subscribe_boxed_async(handle, listener, last_seen_change_stamp)
+    }
+}
+
+impl<T> RawState<T>
+where
+    T: Read<T>,
+{
+    /// Subscribes the given async state listener to this state
+    fn subscribe_async<'a, F, Fut>(
+        self,
+        handle: Handle,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+    ) -> io::Result<Subscription<'a, AsyncListener<F, T>>>
+    where
+        F: FnMut(io::Result<StampedData<T>>) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.subscribe(AsyncListener::new(handle, listener), last_seen_change_stamp)
+    }
+}
+
+impl<T> RawState<T>
+where
+    T: Read<Box<T>> + ?Sized,
+{
+    /// Subscribes the given async state listener to this state, passing the data as a box
+    fn subscribe_boxed_async<'a, F, Fut>(
+        self,
+        handle: Handle,
+        listener: F,
+        last_seen_change_stamp: SeenChangeStamp,
+    ) -> io::Result<Subscription<'a, AsyncListener<F, Box<T>>>>
+    where
+        F: FnMut(io::Result<StampedData<Box<T>>>) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.subscribe(AsyncListener::new(handle, listener), last_seen_change_stamp)
+    }
+}
+
+/// A state listener that reads the state data and spawns a future onto a runtime
+///
+/// This is the listener type of the [`Subscription<'_, F>`](Subscription) returned from the
+/// [`subscribe_async`](OwnedState::subscribe_async) and [`subscribe_boxed_async`](OwnedState::subscribe_boxed_async)
+/// methods. The type parameter `F` is the type of the async listener, while `D` is the type the state data are read as,
+/// which is either `T` or `Box<T>`.
+pub struct AsyncListener<F, D> {
+    handle: Handle,
+    listener: F,
+    _marker: PhantomData<fn() -> D>,
+}
+
+impl<F, D> AsyncListener<F, D> {
+    /// Creates a new [`AsyncListener<F, D>`] spawning the futures returned by `listener` onto the runtime represented
+    /// by `handle`
+    const fn new(handle: Handle, listener: F) -> Self {
+        Self {
+            handle,
+            listener,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<D, F, Fut, T> StateListener<T> for AsyncListener<F, D>
+where
+    D: Send + 'static,
+    F: FnMut(io::Result<StampedData<D>>) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+    T: Read<D> + ?Sized,
+{
+    fn call(&mut self, accessor: DataAccessor<'_, T>) {
+        // The data must be read here because `accessor` is only valid within the scope of this call
+        let result = accessor.query_as();
+        self.handle.spawn((self.listener)(result));
+    }
+
+    fn on_error(&mut self, err: io::Error, _: DataAccessor<'_, T>) {
+        self.handle.spawn((self.listener)(Err(err)));
+    }
+}
+
+// We cannot derive this because that would impose unnecessary trait bounds `F: Debug` and `D: Debug`
+impl<F, D> Debug for AsyncListener<F, D> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncListener")
+            .field("handle", &self.handle)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(dead_code)]
+
+    use std::cell::Cell;
+
+    use static_assertions::{assert_impl_all, assert_not_impl_any};
+
+    use super::*;
+
+    #[test]
+    fn async_listener_is_send_and_sync_regardless_of_data_type() {
+        type NeitherSendNorSync = *const ();
+        assert_not_impl_any!(NeitherSendNorSync: Send, Sync);
+
+        assert_impl_all!(AsyncListener<(), NeitherSendNorSync>: Send, Sync);
+    }
+
+    #[test]
+    fn async_listener_is_send_if_listener_is_send() {
+        type SendNotSync = Cell<()>;
+        assert_impl_all!(SendNotSync: Send);
+        assert_not_impl_any!(SendNotSync: Sync);
+
+        assert_impl_all!(AsyncListener<SendNotSync, ()>: Send);
+    }
+}
@@ -0,0 +1,359 @@
+//! Debouncing state updates on the subscriber side
+
+#![deny(unsafe_code)]
+
+use std::fmt::{self, Debug, Formatter};
+use std::io;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::data::{ChangeStamp, OpaqueData, StampedData};
+use crate::read::Read;
+use crate::state::{BorrowedState, OwnedState, RawState};
+use crate::subscribe::{DataAccessor, SeenChangeStamp, StateListener, Subscription};
+
+impl<T> OwnedState<T>
+where
+    T: Read<T>,
+{
+    /// Subscribes the given listener to this state, passing it only the latest state data per time window
+    ///
+    /// For states that change in bursts, a listener may not be interested in every single update but only in the
+    /// latest data. A listener subscribed through this method is called at most once per time window:
+    ///
+    /// - When the state is updated while no window is open, the listener is called immediately and a new window is
+    ///   opened.
+    /// - When the state is updated while a window is open, the update is only recorded.
+    /// - When a window closes after updates have been recorded, the listener is called with the latest data and a new
+    ///   window is opened.
+    ///
+    /// This way, the latest data of a burst are always delivered, at the latest one window after the update. Rather
+    /// than reading the data passed along with each notification, the state data are queried right before calling the
+    /// listener. Updates are recorded through their change stamps, so updates whose data have already been delivered
+    /// this way are skipped.
+    ///
+    /// As with [`subscribe_owned`](OwnedState::subscribe_owned), the listener is passed a [`StampedData<T>`] (or the
+    /// error that occurred while querying) rather than a [`DataAccessor<'_, T>`](DataAccessor). It is called on a
+    /// background thread rather than on the thread the WNF API uses for notifications. Only updates after subscribing
+    /// are delivered, as with [`SeenChangeStamp::Current`].
+    ///
+    /// This produces an owned `T` on the stack and hence requires `T: Sized`. In order to produce a `Box<T>` for
+    /// `T: ?Sized`, use the [`subscribe_debounced_boxed`](OwnedState::subscribe_debounced_boxed) method.
+    ///
+    /// The listener is unsubscribed and the background thread is stopped when the returned
+    /// [`DebouncedSubscription<'_>`](DebouncedSubscription) is dropped. Updates that have been recorded but not yet
+    /// delivered at that time are discarded.
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use std::sync::mpsc;
+    /// use std::time::Duration;
+    ///
+    /// use wnf::OwnedState;
+    ///
+    /// let state = OwnedState::<u32>::create_temporary()?;
+    ///
+    /// let (tx, rx) = mpsc::channel();
+    /// let _subscription =
+    ///     state.subscribe_debounced(move |result| tx.send(result).unwrap(), Duration::from_millis(100))?;
+    ///
+    /// for value in 1..=100 {
+    ///     state.set(&value)?;
+    /// }
+    ///
+    /// // The latest data are delivered at the latest one window after the last update
+    /// while rx.recv()??.into_data() != 100 {}
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if `window` is zero
+    ///
+    /// # Errors
+    /// Returns an error if subscribing fails or if spawning the background thread fails
+    pub fn subscribe_debounced<F>(&self, listener: F, window: Duration) -> io::Result<DebouncedSubscription<'_>>
+    where
+        F: FnMut(io::Result<StampedData<T>>) + Send + 'static,
+    {
+        self.raw.subscribe_debounced(listener, window)
+    }
+}
+
+impl<T> OwnedState<T>
+where
+    T: Read<Box<T>> + ?Sized,
+{
+    /// Subscribes the given listener to this state, passing it only the latest state data per time window as a box
+    ///
+    /// This is the same as [`subscribe_debounced`](OwnedState::subscribe_debounced), except that it produces a
+    /// [`Box<T>`] instead of an owned `T` (requiring `T: Sized`).
+    ///
+    /// # Panics
+    /// Panics if `window` is zero
+    ///
+    /// # Errors
+    /// Returns an error if subscribing fails or if spawning the background thread fails
+    pub fn subscribe_debounced_boxed<F>(&self, listener: F, window: Duration) -> io::Result<DebouncedSubscription<'_>>
+    where
+        F: FnMut(io::Result<StampedData<Box<T>>>) + Send + 'static,
+    {
+        self.raw.subscribe_debounced(listener, window)
+    }
+}
+
+impl<'a, T> BorrowedState<'a, T>
+where
+    T: Read<T>,
+{
+    /// Subscribes the given listener to this state, passing it only the latest state data per time window
+    ///
+    /// See [`OwnedState::subscribe_debounced`]
+    pub fn subscribe_debounced<F>(self, listener: F, window: Duration) -> io::Result<DebouncedSubscription<'a>>
+    where
+        F: FnMut(io::Result<StampedData<T>>) + Send + 'static,
+    {
+        self.raw.subscribe_debounced(listener, window)
+    }
+}
+
+impl<'a, T> BorrowedState<'a, T>
+where
+    T: Read<Box<T>> + ?Sized,
+{
+    /// Subscribes the given listener to this state, passing it only the latest state data per time window as a box
+    ///
+    /// See [`OwnedState::subscribe_debounced_boxed`]
+    pub fn subscribe_debounced_boxed<F>(self, listener: F, window: Duration) -> io::Result<DebouncedSubscription<'a>>
+    where
+        F: FnMut(io::Result<StampedData<Box<T>>>) + Send + 'static,
+    {
+        self.raw.subscribe_debounced(listener, window)
+    }
+}
+
+impl<T> RawState<T>
+where
+    T: ?Sized,
+{
+    /// Subscribes the given listener to this state, passing it only the latest state data per time window as a `D`
+    fn subscribe_debounced<'a, D, F>(self, listener: F, window: Duration) -> io::Result<DebouncedSubscription<'a>>
+    where
+        T: Read<D>,
+        F: FnMut(io::Result<StampedData<D>>) + Send + 'static,
+    {
+        assert!(!window.is_zero(), "debounce window must not be zero");
+
+        let shared = Arc::new(Shared {
+            inner: Mutex::new(Inner {
+                latest: None,
+                delivered: None,
+                window_end: None,
+                stopped: false,
+            }),
+            condvar: Condvar::new(),
+            window,
+        });
+
+        let subscription = self.cast::<OpaqueData>().subscribe(
+            DebounceListener {
+                shared: Arc::clone(&shared),
+            },
+            SeenChangeStamp::Current,
+        )?;
+
+        let thread = thread::Builder::new().name("wnf-debounce".into()).spawn({
+            let shared = Arc::clone(&shared);
+            move || shared.deliver(self, listener)
+        })?;
+
+        Ok(DebouncedSubscription {
+            shared,
+            thread: Some(thread),
+            subscription,
+        })
+    }
+}
+
+/// A subscription of a listener that is passed only the latest state data per time window
+///
+/// This is returned from the [`subscribe_debounced`](OwnedState::subscribe_debounced) and
+/// [`subscribe_debounced_boxed`](OwnedState::subscribe_debounced_boxed) methods. See there for details.
+///
+/// Dropping a [`DebouncedSubscription<'a>`](DebouncedSubscription) unsubscribes the listener and stops the background
+/// thread calling it.
+#[must_use = "a `DebouncedSubscription` is unsubscribed immediately if it is not used"]
+pub struct DebouncedSubscription<'a> {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+    subscription: Subscription<'a, DebounceListener>,
+}
+
+impl DebouncedSubscription<'_> {
+    /// Returns the time window in which the listener is called at most once
+    pub fn window(&self) -> Duration {
+        self.shared.window
+    }
+}
+
+impl Drop for DebouncedSubscription<'_> {
+    fn drop(&mut self) {
+        self.shared.lock().stopped = true;
+        self.shared.condvar.notify_all();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Debug for DebouncedSubscription<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DebouncedSubscription")
+            .field("window", &self.shared.window)
+            .field("subscription", &self.subscription)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The state shared between a [`DebouncedSubscription<'_>`](DebouncedSubscription), its background thread and its
+/// listener
+#[derive(Debug)]
+struct Shared {
+    inner: Mutex<Inner>,
+    condvar: Condvar,
+    window: Duration,
+}
+
+/// The mutable part of the state shared between a [`DebouncedSubscription<'_>`](DebouncedSubscription), its
+/// background thread and its listener
+#[derive(Debug)]
+struct Inner {
+    latest: Option<ChangeStamp>,
+    delivered: Option<ChangeStamp>,
+    window_end: Option<Instant>,
+    stopped: bool,
+}
+
+impl Inner {
+    /// Returns whether an update has been recorded whose data have not been delivered yet
+    fn is_pending(&self) -> bool {
+        match (self.latest, self.delivered) {
+            (Some(latest), Some(delivered)) => latest.is_newer_than(delivered),
+            (latest, _) => latest.is_some(),
+        }
+    }
+
+    /// Records an update with the given change stamp
+    fn record(&mut self, change_stamp: ChangeStamp) {
+        if self.latest.map_or(true, |latest| change_stamp.is_newer_than(latest)) {
+            self.latest = Some(change_stamp);
+        }
+    }
+}
+
+impl Shared {
+    /// Locks the mutable part of the shared state
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Calls the given listener with the latest data of the given state whenever a time window closes after an update
+    /// has been recorded, until the subscription is dropped
+    ///
+    /// This runs on the background thread of a [`DebouncedSubscription<'_>`](DebouncedSubscription).
+    fn deliver<D, F, T>(&self, state: RawState<T>, mut listener: F)
+    where
+        F: FnMut(io::Result<StampedData<D>>),
+        T: Read<D> + ?Sized,
+    {
+        let mut inner = self.lock();
+
+        while !inner.stopped {
+            let now = Instant::now();
+
+            inner = match inner.window_end {
+                _ if !inner.is_pending() => self.condvar.wait(inner).unwrap_or_else(PoisonError::into_inner),
+
+                Some(window_end) if now < window_end => {
+                    self.condvar
+                        .wait_timeout(inner, window_end - now)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0
+                }
+
+                _ => {
+                    // The lock must not be held while querying and calling the listener, otherwise recording updates
+                    // would be blocked
+                    drop(inner);
+                    let result = state.query_as();
+
+                    let mut inner = self.lock();
+                    inner.window_end = Some(now + self.window);
+                    inner.delivered = match &result {
+                        Ok(data) => Some(data.change_stamp()),
+                        Err(..) => inner.latest,
+                    };
+                    drop(inner);
+
+                    listener(result);
+                    self.lock()
+                }
+            };
+        }
+    }
+}
+
+/// A state listener recording the change stamp of each update for a
+/// [`DebouncedSubscription<'_>`](DebouncedSubscription)
+#[derive(Debug)]
+struct DebounceListener {
+    shared: Arc<Shared>,
+}
+
+impl StateListener<OpaqueData> for DebounceListener {
+    fn call(&mut self, accessor: DataAccessor<'_, OpaqueData>) {
+        self.shared.lock().record(accessor.change_stamp());
+        self.shared.condvar.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use static_assertions::assert_impl_all;
+
+    use super::*;
+
+    #[test]
+    fn debounced_subscription_is_send_and_sync() {
+        assert_impl_all!(DebouncedSubscription<'_>: Send, Sync);
+    }
+
+    #[test]
+    fn pending_until_latest_change_stamp_is_delivered() {
+        let mut inner = Inner {
+            latest: None,
+            delivered: None,
+            window_end: None,
+            stopped: false,
+        };
+
+        assert!(!inner.is_pending());
+
+        inner.record(ChangeStamp::new(2));
+        assert!(inner.is_pending());
+
+        inner.record(ChangeStamp::new(1));
+        assert_eq!(inner.latest, Some(ChangeStamp::new(2)));
+
+        inner.delivered = Some(ChangeStamp::new(3));
+        assert!(!inner.is_pending());
+
+        inner.record(ChangeStamp::new(3));
+        assert!(!inner.is_pending());
+
+        inner.record(ChangeStamp::new(4));
+        assert!(inner.is_pending());
+    }
+}
@@ -0,0 +1,189 @@
+//! Subscribing a single listener to updates of multiple states
+
+#![deny(unsafe_code)]
+
+use std::fmt::{self, Debug, Formatter};
+use std::io;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::data::OpaqueData;
+use crate::state::BorrowedState;
+use crate::state_name::StateName;
+use crate::subscribe::{DataAccessor, SeenChangeStamp, StateListener, Subscription};
+
+/// Subscribes the given listener to updates of all of the given states
+///
+/// The states are given as [`BorrowedState<'_, T>`](BorrowedState) values, e.g. obtained through
+/// [`AsState::as_state`](crate::state::AsState::as_state). If the states have different data types, cast them to a
+/// common type such as [`OpaqueData`] first.
+///
+/// On every update of any of the states, the listener is called with the name of the updated state and a
+/// [`DataAccessor<'_, OpaqueData>`](DataAccessor), which can be cast to the actual data type of the state using
+/// [`DataAccessor::cast`].
+///
+/// Internally, this subscribes to each state separately, but the listener is shared between the subscriptions and
+/// protected by a mutex, so it is never called concurrently and the calls are totally ordered. All subscriptions are
+/// managed by the returned [`GroupSubscription<'_, F>`](GroupSubscription), which unsubscribes from all states when it
+/// is dropped.
+///
+/// See [`OwnedState::subscribe`](crate::state::OwnedState::subscribe) for the meaning of the `last_seen_change_stamp`
+/// argument, which applies to each state separately.
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use wnf::{AsState, DataAccessor, OpaqueData, OwnedState, SeenChangeStamp, StateName};
+///
+/// let state1 = OwnedState::<u32>::create_temporary()?;
+/// let state2 = OwnedState::<u32>::create_temporary()?;
+///
+/// let _subscription = wnf::subscribe_group(
+///     [state1.as_state(), state2.as_state()],
+///     |state_name: StateName, accessor: DataAccessor<OpaqueData>| {
+///         let value = accessor.cast::<u32>().get().unwrap();
+///         println!("State {state_name} updated: {value}");
+///     },
+///     SeenChangeStamp::Current,
+/// )?;
+///
+/// state1.set(&1)?;
+/// state2.set(&2)?;
+/// # Ok(()) }
+/// ```
+///
+/// # Errors
+/// Returns an error if subscribing to any of the states fails. In this case, the listener is unsubscribed from all
+/// states it has already been subscribed to.
+pub fn subscribe_group<'a, I, T, F>(
+    states: I,
+    listener: F,
+    last_seen_change_stamp: SeenChangeStamp,
+) -> io::Result<GroupSubscription<'a, F>>
+where
+    I: IntoIterator<Item = BorrowedState<'a, T>>,
+    T: ?Sized,
+    F: FnMut(StateName, DataAccessor<'_, OpaqueData>) + Send + 'static,
+{
+    let listener = Arc::new(Mutex::new(listener));
+
+    let subscriptions = states
+        .into_iter()
+        .map(|state| {
+            let raw = state.raw.cast::<OpaqueData>();
+
+            raw.subscribe(
+                GroupMemberListener {
+                    state_name: raw.state_name,
+                    listener: Arc::clone(&listener),
+                },
+                last_seen_change_stamp,
+            )
+        })
+        .collect::<io::Result<_>>()?;
+
+    Ok(GroupSubscription { subscriptions })
+}
+
+/// A subscription of a single listener to updates of multiple states
+///
+/// This is returned from [`subscribe_group`].
+///
+/// Note that the listener is automatically unsubscribed from all states when the
+/// [`GroupSubscription<'_, F>`](GroupSubscription) is dropped. In this case, errors while unsubscribing are silently
+/// ignored. If you want to handle them explicitly, use the [`GroupSubscription::unsubscribe`] method.
+#[must_use = "a `GroupSubscription` is unsubscribed immediately if it is not used"]
+pub struct GroupSubscription<'a, F> {
+    subscriptions: Vec<Subscription<'a, GroupMemberListener<F>>>,
+}
+
+impl<F> GroupSubscription<'_, F> {
+    /// Forgets this [`GroupSubscription<'_, F>`](GroupSubscription), effectively keeping it forever
+    ///
+    /// See [`Subscription::forget`]
+    pub fn forget(self) {
+        for subscription in self.subscriptions {
+            subscription.forget();
+        }
+    }
+
+    /// Unsubscribes the listener from all states of this [`GroupSubscription<'_, F>`](GroupSubscription)
+    ///
+    /// This happens automatically when the [`GroupSubscription<'_, F>`](GroupSubscription) is dropped (unless you call
+    /// [`GroupSubscription::forget`]), so there is usually no need to call this method. Its only purpose is to enable
+    /// you to handle errors while unsubscribing. Note that the listener will not be called anymore after
+    /// unsubscribing, even when there is an error.
+    ///
+    /// # Errors
+    /// Returns the first error that occurred while unsubscribing from any of the states. Unsubscribing from the other
+    /// states is attempted regardless.
+    pub fn unsubscribe(self) -> io::Result<()> {
+        self.subscriptions
+            .into_iter()
+            .map(Subscription::unsubscribe)
+            .fold(Ok(()), Result::and)
+    }
+
+    /// Returns the number of states this [`GroupSubscription<'_, F>`](GroupSubscription) is subscribed to
+    pub fn len(&self) -> usize {
+        self.subscriptions.len()
+    }
+
+    /// Returns whether this [`GroupSubscription<'_, F>`](GroupSubscription) is subscribed to no states at all
+    pub fn is_empty(&self) -> bool {
+        self.subscriptions.is_empty()
+    }
+}
+
+// We cannot derive this because that would impose an unnecessary trait bound `F: Debug`
+impl<F> Debug for GroupSubscription<'_, F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GroupSubscription")
+            .field("subscriptions", &self.subscriptions)
+            .finish()
+    }
+}
+
+/// A state listener forwarding updates of a single state to a listener shared by a [`GroupSubscription<'_, F>`]
+pub(crate) struct GroupMemberListener<F> {
+    pub(crate) state_name: StateName,
+    pub(crate) listener: Arc<Mutex<F>>,
+}
+
+impl<F> StateListener<OpaqueData> for GroupMemberListener<F>
+where
+    F: FnMut(StateName, DataAccessor<'_, OpaqueData>),
+{
+    fn call(&mut self, accessor: DataAccessor<'_, OpaqueData>) {
+        // A panic in the listener poisons the mutex, but the listener is still usable afterwards
+        let mut listener = self.listener.lock().unwrap_or_else(PoisonError::into_inner);
+        listener(self.state_name, accessor);
+    }
+}
+
+// We cannot derive this because that would impose an unnecessary trait bound `F: Debug`
+impl<F> Debug for GroupMemberListener<F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GroupMemberListener")
+            .field("state_name", &self.state_name)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use static_assertions::{assert_impl_all, assert_not_impl_any};
+
+    use super::*;
+
+    #[test]
+    fn group_subscription_is_send_and_sync_if_listener_is_send() {
+        type SendNotSync = Cell<()>;
+        assert_impl_all!(SendNotSync: Send);
+        assert_not_impl_any!(SendNotSync: Sync);
+
+        assert_impl_all!(GroupSubscription<'_, SendNotSync>: Send, Sync);
+    }
+}
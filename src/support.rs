@@ -0,0 +1,172 @@
+//! Runtime detection of support for the WNF API
+
+use std::ffi::CString;
+use std::io;
+
+use windows::core::PCSTR;
+use windows::Win32::System::LibraryLoader::{GetModuleHandleW, GetProcAddress};
+
+use crate::state::OwnedState;
+use crate::util::CWideString;
+
+/// Names of the `Nt*` functions from `ntdll.dll` that are required for managing, querying and updating states
+const NT_EXPORTS: [&str; 5] = [
+    "NtCreateWnfStateName",
+    "NtDeleteWnfStateName",
+    "NtQueryWnfStateData",
+    "NtQueryWnfStateNameInformation",
+    "NtUpdateWnfStateData",
+];
+
+/// Names of the `Rtl*` functions from `ntdll.dll` that are required for subscribing to state updates
+const RTL_EXPORTS: [&str; 2] = [
+    "RtlSubscribeWnfStateChangeNotification",
+    "RtlUnsubscribeWnfStateChangeNotification",
+];
+
+/// Information on the support for the WNF API on the current system
+///
+/// This is returned by [`probe_support`].
+#[derive(Debug)]
+pub struct SupportInfo {
+    missing_exports: Vec<&'static str>,
+    state_error: Option<io::Error>,
+}
+
+impl SupportInfo {
+    /// Returns the names of the functions from `ntdll.dll` used by this crate that are not exported on this system
+    pub fn missing_exports(&self) -> &[&'static str] {
+        &self.missing_exports
+    }
+
+    /// Returns the error that occurred when creating, updating, querying and deleting a temporary state, if any
+    ///
+    /// This is [`None`] if the round trip succeeded. It is also [`None`] if the round trip was not attempted because
+    /// some of the required functions are not exported.
+    pub fn state_error(&self) -> Option<&io::Error> {
+        self.state_error.as_ref()
+    }
+
+    /// Returns whether managing, querying and updating states is supported
+    ///
+    /// This is the functionality that uses the lower-level `Nt*` functions from `ntdll.dll`, which is always
+    /// available regardless of Cargo features.
+    pub fn supports_states(&self) -> bool {
+        !self.missing_exports.iter().any(|name| NT_EXPORTS.contains(name)) && self.state_error.is_none()
+    }
+
+    /// Returns whether subscribing to state updates is supported
+    ///
+    /// This is the functionality that uses the higher-level `Rtl*` functions from `ntdll.dll`, which is enabled by the
+    /// `subscribe`, `wait_blocking`, `wait_async` and `async_callbacks` features.
+    pub fn supports_subscriptions(&self) -> bool {
+        self.supports_states() && !self.missing_exports.iter().any(|name| RTL_EXPORTS.contains(name))
+    }
+}
+
+/// Probes the support for the WNF API on the current system
+///
+/// This checks whether the functions from `ntdll.dll` used by this crate are exported and, if so, whether a temporary
+/// state can be created, updated, queried and deleted. It can be used to detect environments without (full) support
+/// for the WNF API, such as older Windows versions or compatibility layers like Wine, up front rather than through
+/// unexpected errors at first use.
+///
+/// Note that this function never fails. Any errors are reported through the returned [`SupportInfo`].
+pub fn probe_support() -> SupportInfo {
+    let missing_exports: Vec<_> = NT_EXPORTS
+        .into_iter()
+        .chain(RTL_EXPORTS)
+        .filter(|name| !ntdll_exports(name))
+        .collect();
+
+    let state_error = if missing_exports.iter().any(|name| NT_EXPORTS.contains(name)) {
+        None
+    } else {
+        state_round_trip().err()
+    };
+
+    SupportInfo {
+        missing_exports,
+        state_error,
+    }
+}
+
+/// Returns whether `ntdll.dll` exports a function with the given name
+fn ntdll_exports(name: &str) -> bool {
+    let module_name = CWideString::new("ntdll.dll");
+
+    // SAFETY:
+    // The pointer in the first argument points to a valid null-terminated wide string because it comes from a live
+    // `CWideString`
+    let Ok(module) = (unsafe { GetModuleHandleW(module_name.as_pcwstr()) }) else {
+        return false;
+    };
+
+    let Ok(proc_name) = CString::new(name) else {
+        return false;
+    };
+
+    // SAFETY:
+    // - The first argument is a valid module handle because it was returned by `GetModuleHandleW` and `ntdll.dll` is
+    //   never unloaded
+    // - The pointer in the second argument points to a valid null-terminated string because it comes from a live
+    //   `CString`
+    let proc = unsafe { GetProcAddress(module, PCSTR::from_raw(proc_name.as_ptr().cast())) };
+
+    proc.is_some()
+}
+
+/// Creates, updates, queries and deletes a temporary state
+fn state_round_trip() -> io::Result<()> {
+    const DATA: u32 = 0x5750_4E46;
+
+    let state = OwnedState::<u32>::create_temporary()?;
+    state.set(&DATA)?;
+
+    if state.get()? != DATA || !state.exists()? {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            "state data read back does not match state data written",
+        ));
+    }
+
+    state.delete()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn supports_states_is_false_if_nt_export_is_missing() {
+        let support_info = SupportInfo {
+            missing_exports: vec!["NtQueryWnfStateData"],
+            state_error: None,
+        };
+
+        assert!(!support_info.supports_states());
+        assert!(!support_info.supports_subscriptions());
+    }
+
+    #[test]
+    fn supports_subscriptions_is_false_if_rtl_export_is_missing() {
+        let support_info = SupportInfo {
+            missing_exports: vec!["RtlSubscribeWnfStateChangeNotification"],
+            state_error: None,
+        };
+
+        assert!(support_info.supports_states());
+        assert!(!support_info.supports_subscriptions());
+    }
+
+    #[test]
+    fn supports_nothing_if_state_round_trip_fails() {
+        let support_info = SupportInfo {
+            missing_exports: Vec::new(),
+            state_error: Some(io::Error::new(io::ErrorKind::Other, "test")),
+        };
+
+        assert!(!support_info.supports_states());
+        assert!(!support_info.supports_subscriptions());
+    }
+}
@@ -0,0 +1,119 @@
+//! Utilities for testing code that uses states
+//!
+//! This module is available when the `test_util` feature is enabled. It is meant to be used in tests of crates
+//! depending on `wnf`, making it possible to write integration tests against real WNF states:
+//! - [`temporary_state`] creates a new state that is deleted when the test finishes, even if it panics.
+//! - [`any_data`] and [`any_slice_data`] are [`proptest`](https://docs.rs/proptest/1/proptest) strategies generating
+//!   arbitrary state data of types implementing [`AnyBitPattern`].
+//! - [`check_round_trip`] and [`check_round_trip_boxed`] check that data read from a state are the same as the data
+//!   written to it before, reporting a failure to `proptest`.
+//!
+//! # Example
+//! ```
+//! use proptest::test_runner::TestRunner;
+//! use wnf::testing::{any_slice_data, check_round_trip_boxed, temporary_state};
+//!
+//! let state = temporary_state::<[u32]>();
+//!
+//! TestRunner::default()
+//!     .run(&any_slice_data::<u32>(0..16), |data| {
+//!         check_round_trip_boxed(&state, &data)
+//!     })
+//!     .unwrap();
+//! ```
+
+use std::fmt::Debug;
+use std::{mem, ptr};
+
+use proptest::arbitrary::any;
+use proptest::collection::{self, SizeRange};
+use proptest::prop_assert_eq;
+use proptest::strategy::Strategy;
+use proptest::test_runner::TestCaseError;
+
+use crate::bytes::{AnyBitPattern, NoUninit};
+use crate::manage::DropPolicy;
+use crate::read::Read;
+use crate::state::OwnedState;
+
+/// Creates a new temporary state for use in a test
+///
+/// The name of the state is assigned by the system and is unique, so tests creating states this way can safely run in
+/// parallel. The state is deleted when the returned [`OwnedState<T>`] is dropped, which also happens when the test
+/// panics (unless panics abort the process, in which case the state is deleted when the process exits because it is
+/// temporary).
+///
+/// # Panics
+/// Panics if creating the state fails
+pub fn temporary_state<T>() -> OwnedState<T>
+where
+    T: ?Sized,
+{
+    let mut state = OwnedState::create_temporary().expect("failed to create temporary state for testing");
+    state.set_drop_policy(DropPolicy::Delete);
+    state
+}
+
+/// Returns a [`Strategy`] generating arbitrary values of type `T`
+///
+/// Since `T` implements [`AnyBitPattern`], the values are generated from arbitrary bytes, so they cover all valid
+/// values of `T`.
+pub fn any_data<T>() -> impl Strategy<Value = T>
+where
+    T: AnyBitPattern + Debug,
+{
+    collection::vec(any::<u8>(), mem::size_of::<T>()).prop_map(|bytes| {
+        // SAFETY:
+        // - `bytes.as_ptr()` is valid for reads of size `mem::size_of::<T>()` because that is the length of `bytes`
+        // - Any bit pattern is a valid `T` because `T: AnyBitPattern`
+        unsafe { ptr::read_unaligned(bytes.as_ptr().cast::<T>()) }
+    })
+}
+
+/// Returns a [`Strategy`] generating vectors of arbitrary values of type `T` with a length in the given range
+///
+/// The generated vectors can be used as data of states of type `[T]`.
+pub fn any_slice_data<T>(size: impl Into<SizeRange>) -> impl Strategy<Value = Vec<T>>
+where
+    T: AnyBitPattern + Debug,
+{
+    collection::vec(any_data(), size)
+}
+
+/// Updates the given state with the given data, then queries it and checks that the result equals the given data
+///
+/// This is meant to be called from a [`proptest`](https://docs.rs/proptest/1/proptest) test. See
+/// [`check_round_trip_boxed`] for states of slice types.
+///
+/// # Errors
+/// Returns a [`TestCaseError`] if updating or querying the state fails or if the queried data differ from the given
+/// data
+pub fn check_round_trip<T>(state: &OwnedState<T>, data: &T) -> Result<(), TestCaseError>
+where
+    T: Read<T> + NoUninit + Debug + PartialEq,
+{
+    state.set(data).map_err(fail)?;
+    prop_assert_eq!(&state.get().map_err(fail)?, data);
+    Ok(())
+}
+
+/// Updates the given state with the given data, then queries it as a box and checks that the result equals the given
+/// data
+///
+/// This is the same as [`check_round_trip`], except that it works for states of slice types.
+///
+/// # Errors
+/// See [`check_round_trip`]
+pub fn check_round_trip_boxed<T>(state: &OwnedState<T>, data: &T) -> Result<(), TestCaseError>
+where
+    T: Read<Box<T>> + NoUninit + Debug + PartialEq + ?Sized,
+{
+    state.set(data).map_err(fail)?;
+    prop_assert_eq!(&*state.get_boxed().map_err(fail)?, data);
+    Ok(())
+}
+
+/// Converts the given error into a failed test case
+fn fail(err: impl Debug) -> TestCaseError {
+    TestCaseError::fail(format!("{err:?}"))
+}
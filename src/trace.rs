@@ -0,0 +1,328 @@
+//! Configuring the diagnostic information emitted through the `tracing` crate
+
+#![deny(unsafe_code)]
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{PoisonError, RwLock};
+
+use tracing::field::{debug, display, DebugValue, DisplayValue};
+use tracing::Level;
+
+use crate::state_name::{DataScope, StateLifetime, StateName, StateNameDescriptor};
+
+/// Whether tracing events and spans include the decoded properties of state names
+static TRACE_STATE_NAME_DESCRIPTORS: AtomicBool = AtomicBool::new(false);
+
+/// Configures whether tracing events and spans include the decoded properties of state names
+///
+/// By default, tracing events and spans emitted by this crate (see the crate-level documentation) only contain state
+/// names as opaque values, e.g. in an `input.state_name` field. When this is enabled, they additionally contain the
+/// properties of the [`StateNameDescriptor`] decoded from each state name as separate fields, e.g. for a field
+/// `input.state_name`:
+/// - `input.state_name.lifetime`: The [`StateLifetime`] of the state name
+/// - `input.state_name.data_scope`: The [`DataScope`] of the state name
+/// - `input.state_name.unique_id`: The unique id of the state name
+/// - `input.state_name.owner_tag`: The owner tag of the state name as a string if it consists of ASCII characters (see
+///   [`StateNameDescriptor::owner_tag_str`]), otherwise as a hexadecimal number
+///
+/// This makes it possible to aggregate events by these properties without decoding state names in log processors.
+/// Since decoding incurs a small overhead for every emitted event, it is disabled by default. State names that cannot
+/// be decoded are emitted without these fields.
+pub fn set_trace_state_name_descriptors(enabled: bool) {
+    TRACE_STATE_NAME_DESCRIPTORS.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns whether tracing events and spans include the decoded properties of state names
+///
+/// See [`set_trace_state_name_descriptors`]
+pub fn trace_state_name_descriptors() -> bool {
+    TRACE_STATE_NAME_DESCRIPTORS.load(Ordering::Relaxed)
+}
+
+/// The current configuration of the tracing events emitted for invocations of WNF API routines
+static TRACING_CONFIG: RwLock<TracingConfig> = RwLock::new(TracingConfig::new());
+
+/// A WNF API routine whose invocations are traced
+///
+/// This is used to configure the level of the emitted tracing events per routine through
+/// [`TracingConfig::routine_level`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum WnfRoutine {
+    /// The `NtCreateWnfStateName` routine, used for creating states
+    CreateStateName,
+
+    /// The `NtDeleteWnfStateName` routine, used for deleting states
+    DeleteStateName,
+
+    /// The `NtQueryWnfStateData` routine, used for querying state data
+    QueryStateData,
+
+    /// The `NtUpdateWnfStateData` routine, used for updating state data
+    UpdateStateData,
+
+    /// The `NtQueryWnfStateNameInformation` routine, used for querying information about states
+    QueryStateNameInformation,
+
+    /// The `RtlSubscribeWnfStateChangeNotification` routine, used for subscribing to state updates
+    SubscribeStateChangeNotification,
+
+    /// The `RtlUnsubscribeWnfStateChangeNotification` routine, used for unsubscribing from state updates
+    UnsubscribeStateChangeNotification,
+}
+
+impl WnfRoutine {
+    /// The number of [`WnfRoutine`] variants
+    pub(crate) const COUNT: usize = 7;
+
+    /// All [`WnfRoutine`] variants in declaration order
+    #[cfg(feature = "perf_counters")]
+    pub(crate) const ALL: [Self; Self::COUNT] = [
+        Self::CreateStateName,
+        Self::DeleteStateName,
+        Self::QueryStateData,
+        Self::UpdateStateData,
+        Self::QueryStateNameInformation,
+        Self::SubscribeStateChangeNotification,
+        Self::UnsubscribeStateChangeNotification,
+    ];
+
+    /// Returns the name of this routine, e.g. `NtQueryWnfStateData`
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::CreateStateName => "NtCreateWnfStateName",
+            Self::DeleteStateName => "NtDeleteWnfStateName",
+            Self::QueryStateData => "NtQueryWnfStateData",
+            Self::UpdateStateData => "NtUpdateWnfStateData",
+            Self::QueryStateNameInformation => "NtQueryWnfStateNameInformation",
+            Self::SubscribeStateChangeNotification => "RtlSubscribeWnfStateChangeNotification",
+            Self::UnsubscribeStateChangeNotification => "RtlUnsubscribeWnfStateChangeNotification",
+        }
+    }
+}
+
+/// Configuration of the tracing events emitted for invocations of WNF API routines
+///
+/// By default, every invocation of a WNF API routine emits an event at the [`DEBUG`](Level::DEBUG) level (see the
+/// crate-level documentation). Since this can be too noisy for some applications, the level can be changed per
+/// [`WnfRoutine`] through [`TracingConfig::routine_level`], including disabling the events for a routine entirely.
+/// In addition, [`TracingConfig::error_level`] sets a separate level for events of failed invocations, so errors stay
+/// visible even when the events of successful invocations are filtered out.
+///
+/// The configuration is applied globally through [`set_tracing_config`].
+///
+/// Note that the target of the events is always `wnf::ntapi` and cannot be configured because the `tracing` crate
+/// requires targets to be known at compile time. Use the filtering capabilities of your subscriber to route the events
+/// instead.
+///
+/// # Example
+/// ```
+/// use tracing::Level;
+/// use wnf::{TracingConfig, WnfRoutine};
+///
+/// wnf::set_tracing_config(
+///     TracingConfig::new()
+///         .routine_level(WnfRoutine::QueryStateData, Some(Level::TRACE))
+///         .routine_level(WnfRoutine::UpdateStateData, Some(Level::TRACE))
+///         .error_level(Some(Level::WARN)),
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct TracingConfig {
+    routine_levels: [Option<Level>; WnfRoutine::COUNT],
+    error_level: Option<Level>,
+}
+
+impl TracingConfig {
+    /// Creates a [`TracingConfig`] emitting all events at the [`DEBUG`](Level::DEBUG) level
+    pub const fn new() -> Self {
+        Self {
+            routine_levels: [Some(Level::DEBUG); WnfRoutine::COUNT],
+            error_level: None,
+        }
+    }
+
+    /// Sets the level of the events emitted for invocations of the given routine
+    ///
+    /// Passing [`None`] disables the events for the routine, unless overridden for failed invocations through
+    /// [`TracingConfig::error_level`].
+    pub const fn routine_level(mut self, routine: WnfRoutine, level: Option<Level>) -> Self {
+        self.routine_levels[routine as usize] = level;
+        self
+    }
+
+    /// Sets the level of the events emitted for failed invocations of any routine
+    ///
+    /// Passing [`None`] (the default) makes events of failed invocations use the level configured for the respective
+    /// routine.
+    pub const fn error_level(mut self, level: Option<Level>) -> Self {
+        self.error_level = level;
+        self
+    }
+
+    /// Returns the level of the event emitted for an invocation of the given routine, depending on whether it failed,
+    /// or [`None`] if no event is emitted
+    pub const fn event_level(&self, routine: WnfRoutine, failed: bool) -> Option<Level> {
+        match self.error_level {
+            Some(error_level) if failed => Some(error_level),
+            _ => self.routine_levels[routine as usize],
+        }
+    }
+}
+
+impl Default for TracingConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Configures the tracing events emitted for invocations of WNF API routines
+///
+/// See [`TracingConfig`]
+pub fn set_tracing_config(config: TracingConfig) {
+    *TRACING_CONFIG.write().unwrap_or_else(PoisonError::into_inner) = config;
+}
+
+/// Returns the current configuration of the tracing events emitted for invocations of WNF API routines
+///
+/// See [`set_tracing_config`]
+pub fn tracing_config() -> TracingConfig {
+    *TRACING_CONFIG.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Emits a tracing event for an invocation of a WNF API routine at the level configured through
+/// [`set_tracing_config`]
+///
+/// The first two arguments are the [`WnfRoutine`] and whether the invocation failed, the remaining arguments are
+/// passed on to the event macros of the `tracing` crate. With the `perf_counters` feature, this also records the
+/// invocation in the [`PerfCounters`](crate::perf::PerfCounters).
+///
+/// If the first field is a state name of the form `input.state_name = %state_name` (or `output.state_name`), the
+/// event additionally contains the fields of the corresponding [`TracedStateName`]. These are only computed if the
+/// event is enabled.
+macro_rules! ntapi_event {
+    (@emit $level:expr, $io:ident.state_name = %$state_name:expr, $($arg:tt)+) => {
+        if ::tracing::event_enabled!(target: $crate::ntapi::TRACING_TARGET, $level) {
+            let state_name: $crate::state_name::StateName = $state_name;
+            let traced_state_name = $crate::trace::TracedStateName::new(state_name);
+            ::tracing::event!(
+                target: $crate::ntapi::TRACING_TARGET,
+                $level,
+                $io.state_name = %state_name,
+                $io.state_name.lifetime = traced_state_name.lifetime,
+                $io.state_name.data_scope = traced_state_name.data_scope,
+                $io.state_name.unique_id = traced_state_name.unique_id,
+                $io.state_name.owner_tag = traced_state_name.owner_tag,
+                $($arg)+
+            );
+        }
+    };
+    (@emit $level:expr, $($arg:tt)+) => {
+        ::tracing::event!(target: $crate::ntapi::TRACING_TARGET, $level, $($arg)+)
+    };
+    ($routine:expr, $failed:expr, $($arg:tt)+) => {{
+        #[cfg(feature = "perf_counters")]
+        $crate::perf::record($routine);
+
+        match $crate::trace::tracing_config().event_level($routine, $failed) {
+            Some(::tracing::Level::ERROR) => $crate::trace::ntapi_event!(@emit ::tracing::Level::ERROR, $($arg)+),
+            Some(::tracing::Level::WARN) => $crate::trace::ntapi_event!(@emit ::tracing::Level::WARN, $($arg)+),
+            Some(::tracing::Level::INFO) => $crate::trace::ntapi_event!(@emit ::tracing::Level::INFO, $($arg)+),
+            Some(::tracing::Level::DEBUG) => $crate::trace::ntapi_event!(@emit ::tracing::Level::DEBUG, $($arg)+),
+            Some(_) => $crate::trace::ntapi_event!(@emit ::tracing::Level::TRACE, $($arg)+),
+            None => {}
+        }
+    }};
+}
+
+pub(crate) use ntapi_event;
+
+/// The decoded properties of a state name to be included as fields in tracing events and spans
+///
+/// All fields are [`None`] unless enabled through [`set_trace_state_name_descriptors`], in which case they are not
+/// recorded at all.
+#[derive(Debug)]
+pub(crate) struct TracedStateName {
+    pub(crate) lifetime: Option<DebugValue<StateLifetime>>,
+    pub(crate) data_scope: Option<DebugValue<DataScope>>,
+    pub(crate) unique_id: Option<u32>,
+    pub(crate) owner_tag: Option<DisplayValue<String>>,
+}
+
+impl TracedStateName {
+    /// Decodes the given state name if enabled through [`set_trace_state_name_descriptors`]
+    pub(crate) fn new(state_name: StateName) -> Self {
+        let descriptor = trace_state_name_descriptors()
+            .then(|| StateNameDescriptor::try_from(state_name).ok())
+            .flatten();
+
+        Self {
+            lifetime: descriptor.map(|descriptor| debug(descriptor.lifetime)),
+            data_scope: descriptor.map(|descriptor| debug(descriptor.data_scope)),
+            unique_id: descriptor.map(|descriptor| descriptor.unique_id),
+            owner_tag: descriptor.map(|descriptor| {
+                display(
+                    descriptor
+                        .owner_tag_str()
+                        .map_or_else(|| format!("{:#010X}", descriptor.owner_tag), Into::into),
+                )
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracing_config_default() {
+        let config = TracingConfig::default();
+
+        assert_eq!(
+            config.event_level(WnfRoutine::QueryStateData, false),
+            Some(Level::DEBUG)
+        );
+        assert_eq!(config.event_level(WnfRoutine::QueryStateData, true), Some(Level::DEBUG));
+    }
+
+    #[test]
+    fn tracing_config_routine_and_error_levels() {
+        let config = TracingConfig::new()
+            .routine_level(WnfRoutine::UpdateStateData, Some(Level::TRACE))
+            .routine_level(WnfRoutine::QueryStateData, None)
+            .error_level(Some(Level::WARN));
+
+        assert_eq!(
+            config.event_level(WnfRoutine::UpdateStateData, false),
+            Some(Level::TRACE)
+        );
+        assert_eq!(config.event_level(WnfRoutine::QueryStateData, false), None);
+        assert_eq!(config.event_level(WnfRoutine::QueryStateData, true), Some(Level::WARN));
+        assert_eq!(
+            config.event_level(WnfRoutine::DeleteStateName, false),
+            Some(Level::DEBUG)
+        );
+    }
+
+    #[test]
+    fn traced_state_name() {
+        let state_name = StateName::from_opaque_value(0x0D83_063E_A3BE_5075);
+
+        set_trace_state_name_descriptors(false);
+        let traced = TracedStateName::new(state_name);
+
+        assert!(traced.lifetime.is_none());
+        assert!(traced.data_scope.is_none());
+        assert!(traced.unique_id.is_none());
+        assert!(traced.owner_tag.is_none());
+
+        set_trace_state_name_descriptors(true);
+        let traced = TracedStateName::new(state_name);
+        set_trace_state_name_descriptors(false);
+
+        assert_eq!(format!("{:?}", traced.lifetime.unwrap()), "WellKnown");
+        assert_eq!(format!("{:?}", traced.data_scope.unwrap()), "System");
+        assert_eq!(traced.unique_id, Some(0x4A));
+        assert_eq!(traced.owner_tag.unwrap().to_string(), "SHEL");
+    }
+}
@@ -44,6 +44,12 @@ impl GUID {
     pub const fn to_u128(&self) -> u128 {
         self.0.to_u128()
     }
+
+    /// Creates a GUID from a [`windows::core::GUID`] regardless of whether the `windows` feature is enabled
+    #[cfg(feature = "subscribe")]
+    pub(crate) const fn from_raw(guid: windows::core::GUID) -> Self {
+        Self(guid)
+    }
 }
 
 impl TryFrom<&str> for GUID {
@@ -141,6 +147,19 @@ impl TypeId {
         Self(Some(guid.0))
     }
 
+    /// Returns whether this [`TypeId`] contains no [`GUID`]
+    pub(crate) const fn is_none(&self) -> bool {
+        self.0.is_none()
+    }
+
+    /// Returns the underlying [`GUID`], if any
+    pub(crate) const fn guid(&self) -> Option<GUID> {
+        match self.0 {
+            Some(guid) => Some(GUID(guid)),
+            None => None,
+        }
+    }
+
     /// Returns a raw pointer to the underlying [`GUID`], or a null pointer if there is none
     ///
     /// It is guaranteed that the returned pointer is either a null pointer or points to a valid [`GUID`] as long the
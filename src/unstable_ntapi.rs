@@ -0,0 +1,104 @@
+//! Unstable methods exposing undocumented parameters of the WNF API
+//!
+//! This module only adds inherent impls to [`OwnedState<T>`] and [`BorrowedState<'_, T>`](BorrowedState).
+//!
+//! The methods in this module are only available with the `unstable_ntapi` feature. They are not covered by the
+//! semver guarantees of this crate and may change or be removed in any release.
+
+use std::ffi::c_void;
+use std::io;
+
+use crate::data::StampedData;
+use crate::read::Read;
+use crate::state::{BorrowedState, OwnedState};
+
+impl<T> OwnedState<T>
+where
+    T: Read<T>,
+{
+    /// Queries the data of this state using the given explicit scope
+    ///
+    /// This is the same as [`query`](OwnedState::query), except that it passes the given pointer as the (undocumented)
+    /// `ExplicitScope` argument of the `NtQueryWnfStateData` routine, which [`query`](OwnedState::query) always sets
+    /// to a null pointer. According to reverse engineering resources, this can be used to query a state instance in a
+    /// data scope other than the one of the calling process, e.g. a process-scoped state of another process.
+    ///
+    /// This method is only available with the `unstable_ntapi` feature and is not covered by semver guarantees.
+    ///
+    /// # Errors
+    /// Returns an error if querying fails, including the case that the queried data is not a valid `T`
+    ///
+    /// # Safety
+    /// `explicit_scope` must either be a null pointer or satisfy the requirements of `NtQueryWnfStateData` for its
+    /// `ExplicitScope` argument. Since these requirements are undocumented, you are responsible for making sure that
+    /// they are met on the Windows versions you are targeting.
+    pub unsafe fn query_with_explicit_scope(&self, explicit_scope: *const c_void) -> io::Result<StampedData<T>> {
+        // SAFETY:
+        // The safety conditions of this method are the same as those of `RawState::query_as_with_explicit_scope`
+        unsafe { self.raw.query_as_with_explicit_scope(explicit_scope) }
+    }
+}
+
+impl<T> OwnedState<T>
+where
+    T: Read<Box<T>> + ?Sized,
+{
+    /// Queries the data of this state as a box using the given explicit scope
+    ///
+    /// This is the same as [`query_boxed`](OwnedState::query_boxed), except that it passes the given pointer as the
+    /// (undocumented) `ExplicitScope` argument of the `NtQueryWnfStateData` routine. See
+    /// [`query_with_explicit_scope`](OwnedState::query_with_explicit_scope) for details.
+    ///
+    /// This method is only available with the `unstable_ntapi` feature and is not covered by semver guarantees.
+    ///
+    /// # Errors
+    /// Returns an error if querying fails, including the case that the queried data is not a valid `T`
+    ///
+    /// # Safety
+    /// See [`query_with_explicit_scope`](OwnedState::query_with_explicit_scope)
+    pub unsafe fn query_boxed_with_explicit_scope(
+        &self,
+        explicit_scope: *const c_void,
+    ) -> io::Result<StampedData<Box<T>>> {
+        // SAFETY:
+        // The safety conditions of this method are the same as those of `RawState::query_as_with_explicit_scope`
+        unsafe { self.raw.query_as_with_explicit_scope(explicit_scope) }
+    }
+}
+
+impl<T> BorrowedState<'_, T>
+where
+    T: Read<T>,
+{
+    /// Queries the data of this state using the given explicit scope
+    ///
+    /// See [`OwnedState::query_with_explicit_scope`]
+    ///
+    /// # Safety
+    /// See [`OwnedState::query_with_explicit_scope`]
+    pub unsafe fn query_with_explicit_scope(self, explicit_scope: *const c_void) -> io::Result<StampedData<T>> {
+        // SAFETY:
+        // The safety conditions of this method are the same as those of `RawState::query_as_with_explicit_scope`
+        unsafe { self.raw.query_as_with_explicit_scope(explicit_scope) }
+    }
+}
+
+impl<T> BorrowedState<'_, T>
+where
+    T: Read<Box<T>> + ?Sized,
+{
+    /// Queries the data of this state as a box using the given explicit scope
+    ///
+    /// See [`OwnedState::query_boxed_with_explicit_scope`]
+    ///
+    /// # Safety
+    /// See [`OwnedState::query_with_explicit_scope`]
+    pub unsafe fn query_boxed_with_explicit_scope(
+        self,
+        explicit_scope: *const c_void,
+    ) -> io::Result<StampedData<Box<T>>> {
+        // SAFETY:
+        // The safety conditions of this method are the same as those of `RawState::query_as_with_explicit_scope`
+        unsafe { self.raw.query_as_with_explicit_scope(explicit_scope) }
+    }
+}
@@ -1,17 +1,24 @@
 //! Methods for updating state data
 //!
-//! This module only adds inherent impls to [`OwnedState<T>`] and [`BorrowedState<'_, T>`](BorrowedState).
+//! Besides the [`SizeMismatchError`] type, this module adds inherent impls to [`OwnedState<T>`] and
+//! [`BorrowedState<'_, T>`](BorrowedState).
 
 use std::ffi::c_void;
+use std::io::ErrorKind;
 use std::{io, mem, ptr};
 
-use tracing::debug;
+use thiserror::Error;
 use windows::Win32::Foundation::{NTSTATUS, STATUS_UNSUCCESSFUL};
+#[cfg(feature = "zeroize")]
+use zeroize::Zeroize;
 
 use crate::bytes::NoUninit;
-use crate::data::ChangeStamp;
-use crate::ntapi;
+use crate::data::{ChangeStamp, OpaqueData};
+use crate::manage::MAXIMUM_STATE_SIZE;
 use crate::state::{BorrowedState, OwnedState, RawState};
+use crate::trace::{ntapi_event, WnfRoutine};
+use crate::type_id::{TypeId, GUID};
+use crate::{ntapi, wipe};
 
 impl<T> OwnedState<T>
 where
@@ -45,6 +52,56 @@ where
     pub fn update(&self, data: &T, expected_change_stamp: impl Into<ChangeStamp>) -> io::Result<bool> {
         self.raw.update(data, expected_change_stamp.into())
     }
+
+    /// Updates the data of this state with the given value, using the given type id instead of the type id of this
+    /// state
+    ///
+    /// This is the same as [`set`](OwnedState::set), except that the given type id is passed to the WNF API for this
+    /// call only. This is useful for states whose writers use a type id that is only known at runtime.
+    ///
+    /// # Errors
+    /// Returns an error if updating fails, including the case that the given type id does not match the type id of the
+    /// state
+    pub fn set_with_type_id(&self, data: &T, type_id: impl Into<GUID>) -> io::Result<()> {
+        self.raw.with_type_id(TypeId::from_guid(type_id.into())).set(data)
+    }
+
+    /// Updates the data of this state with the given value, provided that the current state data have the given size
+    ///
+    /// This is useful for guarding against breaking consumers that expect data of a certain size. For instance, when a
+    /// newer release of an application introduces a larger version of a data type, it can make sure not to overwrite
+    /// data of the older version that may still be read by consumers of the older release.
+    ///
+    /// The size of the current state data is checked in a loop using change stamps, in the same way as in
+    /// [`apply`](OwnedState::apply), so that no concurrent update happens between checking the size and updating the
+    /// state data.
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use std::mem;
+    ///
+    /// use wnf::OwnedState;
+    ///
+    /// let state = OwnedState::<[u8]>::create_temporary()?;
+    /// state.set(&[1, 2])?;
+    ///
+    /// // Succeeds because the current data have size 2
+    /// state.set_checked(&[3, 4], 2)?;
+    ///
+    /// // Fails because the current data don't have size 3
+    /// assert!(state.set_checked(&[5, 6, 7], 3).is_err());
+    /// assert_eq!(*state.get_boxed()?, [3, 4]);
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an error if querying or updating fails or if the size of the current state data is not
+    /// `expected_current_size`. In the latter case, [`io::Error::kind`] returns [`ErrorKind::InvalidData`] and the
+    /// error wraps a [`SizeMismatchError`].
+    pub fn set_checked(&self, data: &T, expected_current_size: usize) -> io::Result<()> {
+        self.raw.set_checked(data, expected_current_size)
+    }
 }
 
 impl<T> BorrowedState<'_, T>
@@ -64,6 +121,162 @@ where
     pub fn update(self, data: &T, expected_change_stamp: impl Into<ChangeStamp>) -> io::Result<bool> {
         self.raw.update(data, expected_change_stamp.into())
     }
+
+    /// Updates the data of this state with the given value, using the given type id instead of the type id of this
+    /// state
+    ///
+    /// See [`OwnedState::set_with_type_id`]
+    pub fn set_with_type_id(self, data: &T, type_id: impl Into<GUID>) -> io::Result<()> {
+        self.raw.with_type_id(TypeId::from_guid(type_id.into())).set(data)
+    }
+
+    /// Updates the data of this state with the given value, provided that the current state data have the given size
+    ///
+    /// See [`OwnedState::set_checked`]
+    pub fn set_checked(self, data: &T, expected_current_size: usize) -> io::Result<()> {
+        self.raw.set_checked(data, expected_current_size)
+    }
+}
+
+impl<T> OwnedState<T>
+where
+    T: ?Sized,
+{
+    /// Updates the data of this state with bytes written by the given closure
+    ///
+    /// The closure is passed a scratch buffer of [`MAXIMUM_STATE_SIZE`] bytes on the stack, into which it can
+    /// serialize the data directly. It returns the number of bytes written, and the state is updated with that many
+    /// bytes from the start of the buffer. This avoids allocating an intermediate buffer for every update, which is
+    /// useful for high-frequency publishers.
+    ///
+    /// Note that the data are written as raw bytes, so it is up to the closure to produce valid data of type `T`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use std::io::Write;
+    ///
+    /// use wnf::OwnedState;
+    ///
+    /// let state = OwnedState::<[u8]>::create_temporary()?;
+    ///
+    /// state.set_with(|mut buffer| {
+    ///     let len = buffer.len();
+    ///     write!(buffer, "temperature={}", 21).unwrap();
+    ///     len - buffer.len()
+    /// })?;
+    ///
+    /// assert_eq!(*state.get_boxed()?, *b"temperature=21");
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an error if the closure returns a size larger than [`MAXIMUM_STATE_SIZE`] or if updating fails
+    pub fn set_with<F>(&self, writer: F) -> io::Result<()>
+    where
+        F: FnOnce(&mut [u8]) -> usize,
+    {
+        self.raw.set_with(writer)
+    }
+
+    /// Updates this state with empty data in order to signal an event
+    ///
+    /// This increments the change stamp of the state and notifies its listeners without passing any payload, which is
+    /// a common idiom for using a state as an event trigger. It works regardless of the data type `T`, so there is no
+    /// need to cast the state to a state of type `[u8]` in order to write empty data.
+    ///
+    /// Listeners can recognize such updates through
+    /// [`DataAccessor::is_pulse`](crate::subscribe::DataAccessor::is_pulse) or be subscribed through
+    /// [`subscribe_pulses`](OwnedState::subscribe_pulses) in order to be notified only about them. Note that reading
+    /// the empty data as a `T` fails unless `T` is zero-sized or a slice type.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use wnf::OwnedState;
+    ///
+    /// let state = OwnedState::<u32>::create_temporary()?;
+    /// state.pulse()?;
+    ///
+    /// assert_eq!(state.change_stamp()?, 1);
+    /// assert!(state.get().is_err());
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an error if updating fails
+    pub fn pulse(&self) -> io::Result<()> {
+        self.raw.pulse()
+    }
+}
+
+impl<T> BorrowedState<'_, T>
+where
+    T: ?Sized,
+{
+    /// Updates the data of this state with bytes written by the given closure
+    ///
+    /// See [`OwnedState::set_with`]
+    pub fn set_with<F>(self, writer: F) -> io::Result<()>
+    where
+        F: FnOnce(&mut [u8]) -> usize,
+    {
+        self.raw.set_with(writer)
+    }
+
+    /// Updates this state with empty data in order to signal an event
+    ///
+    /// See [`OwnedState::pulse`]
+    pub fn pulse(self) -> io::Result<()> {
+        self.raw.pulse()
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<T> OwnedState<T>
+where
+    T: NoUninit + Zeroize + ?Sized,
+{
+    /// Updates the data of this state with the given value, wiping the value afterwards
+    ///
+    /// This is like [`set`](OwnedState::set), but overwrites `data` with zeros (via [`Zeroize`]) after the update has
+    /// succeeded. This is useful for passing sensitive data such as credentials through a state without leaving a copy
+    /// in the memory of the current process. If the update fails, `data` is left untouched so the update can be
+    /// retried.
+    ///
+    /// # Errors
+    /// Returns an error if updating fails
+    pub fn set_secret(&self, data: &mut T) -> io::Result<()> {
+        self.raw.set_secret(data)
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<T> BorrowedState<'_, T>
+where
+    T: NoUninit + Zeroize + ?Sized,
+{
+    /// Updates the data of this state with the given value, wiping the value afterwards
+    ///
+    /// See [`OwnedState::set_secret`]
+    pub fn set_secret(self, data: &mut T) -> io::Result<()> {
+        self.raw.set_secret(data)
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<T> RawState<T>
+where
+    T: NoUninit + Zeroize + ?Sized,
+{
+    /// Updates the data of this state with the given value, wiping the value afterwards
+    fn set_secret(self, data: &mut T) -> io::Result<()> {
+        self.set(data)?;
+        data.zeroize();
+        Ok(())
+    }
 }
 
 impl<T> RawState<T>
@@ -73,8 +286,13 @@ where
     /// Updates the data of this state with the given value
     ///
     /// The update is performed regardless of the current change stamp of the state.
-    fn set(self, data: &T) -> io::Result<()> {
-        self.update_internal(data, None).ok()?;
+    pub(crate) fn set(self, data: &T) -> io::Result<()> {
+        let result = self.update_internal(data, None);
+
+        if result.is_err() {
+            return Err(self.ntapi_error(WnfRoutine::UpdateStateData, result));
+        }
+
         Ok(())
     }
 
@@ -86,12 +304,35 @@ where
     pub(crate) fn update(self, data: &T, expected_change_stamp: ChangeStamp) -> io::Result<bool> {
         let result = self.update_internal(data, Some(expected_change_stamp));
 
-        Ok(if result == STATUS_UNSUCCESSFUL {
-            false
+        if result == STATUS_UNSUCCESSFUL {
+            Ok(false)
+        } else if result.is_err() {
+            Err(self.ntapi_error(WnfRoutine::UpdateStateData, result))
         } else {
-            result.ok()?;
-            true
-        })
+            Ok(true)
+        }
+    }
+
+    /// Updates the data of this state with the given value, provided that the current state data have the given size
+    fn set_checked(self, data: &T, expected_current_size: usize) -> io::Result<()> {
+        loop {
+            let (current_data, change_stamp) = self.cast::<OpaqueData>().query_as()?.into_data_change_stamp();
+            let actual_size = current_data.size();
+
+            if actual_size != expected_current_size {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    SizeMismatchError {
+                        expected: expected_current_size,
+                        actual: actual_size,
+                    },
+                ));
+            }
+
+            if self.update(data, change_stamp)? {
+                return Ok(());
+            }
+        }
     }
 
     fn update_internal(self, data: &T, expected_change_stamp: Option<ChangeStamp>) -> NTSTATUS {
@@ -118,10 +359,11 @@ where
             )
         };
 
-        debug!(
-            target: ntapi::TRACING_TARGET,
-            ?result,
+        ntapi_event!(
+            WnfRoutine::UpdateStateData,
+            result.is_err(),
             input.state_name = %self.state_name,
+            ?result,
             input.buffer_size = buffer_size,
             input.type_id = %self.type_id,
             input.matching_change_stamp = matching_change_stamp,
@@ -132,3 +374,46 @@ where
         result
     }
 }
+
+impl<T> RawState<T>
+where
+    T: ?Sized,
+{
+    /// Updates the data of this state with bytes written by the given closure into a scratch buffer
+    fn set_with<F>(self, writer: F) -> io::Result<()>
+    where
+        F: FnOnce(&mut [u8]) -> usize,
+    {
+        let mut buffer = [0; MAXIMUM_STATE_SIZE];
+        let size = writer(&mut buffer);
+
+        let result = match buffer.get(..size) {
+            Some(data) => self.cast::<[u8]>().set(data),
+            None => Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("written size {size} exceeds maximum state size {MAXIMUM_STATE_SIZE}"),
+            )),
+        };
+
+        wipe::wipe_slice(&mut buffer);
+        result
+    }
+
+    /// Updates this state with empty data
+    fn pulse(self) -> io::Result<()> {
+        self.cast::<[u8]>().set(&[])
+    }
+}
+
+/// An error updating state data because the current state data have an unexpected size
+///
+/// This is wrapped by the errors returned from [`OwnedState::set_checked`] and [`BorrowedState::set_checked`].
+#[derive(Clone, Copy, Debug, Eq, Error, Hash, PartialEq)]
+#[error("failed to update state data: current data have unexpected size (expected {expected}, got {actual})")]
+pub struct SizeMismatchError {
+    /// The expected size in bytes of the current state data
+    pub expected: usize,
+
+    /// The actual size in bytes of the current state data
+    pub actual: usize,
+}
@@ -0,0 +1,211 @@
+//! Updating the data of multiple states with best-effort rollback
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+
+use crate::state::{AsState, RawState};
+use crate::wipe;
+
+/// The outcome of updating a single state as part of [`update_all_or_nothing`]
+#[derive(Debug)]
+pub enum UpdateOutcome {
+    /// The state was updated and the update was kept
+    Updated,
+
+    /// The state was updated, but its previous data were restored because updating another state failed
+    RolledBack,
+
+    /// The state was updated, but restoring its previous data failed with the contained error
+    RollbackFailed(io::Error),
+
+    /// Querying the previous data of the state or updating the state failed with the contained error
+    Failed(io::Error),
+
+    /// No update of the state was attempted because querying or updating another state failed
+    NotAttempted,
+}
+
+impl UpdateOutcome {
+    /// Returns whether the state data are left unchanged, i.e. the state was not updated or its update was rolled back
+    pub const fn is_unchanged(&self) -> bool {
+        matches!(self, Self::RolledBack | Self::Failed(..) | Self::NotAttempted)
+    }
+}
+
+/// An error updating the data of multiple states using [`update_all_or_nothing`]
+///
+/// This contains the outcome for every state, in the order in which the states were passed.
+#[derive(Debug)]
+pub struct UpdateAllError {
+    failed_index: usize,
+    outcomes: Vec<UpdateOutcome>,
+}
+
+impl UpdateAllError {
+    /// Returns the index of the state for which querying or updating failed
+    pub const fn failed_index(&self) -> usize {
+        self.failed_index
+    }
+
+    /// Returns the outcomes for all states, in the order in which the states were passed
+    pub fn outcomes(&self) -> &[UpdateOutcome] {
+        &self.outcomes
+    }
+
+    /// Consumes this error, returning the outcomes for all states
+    pub fn into_outcomes(self) -> Vec<UpdateOutcome> {
+        self.outcomes
+    }
+
+    /// Returns whether all states that were updated have been rolled back successfully
+    pub fn is_rolled_back(&self) -> bool {
+        self.outcomes.iter().all(UpdateOutcome::is_unchanged)
+    }
+
+    /// Returns the error that caused updating the states to fail
+    fn cause(&self) -> &io::Error {
+        match &self.outcomes[self.failed_index] {
+            UpdateOutcome::Failed(err) => err,
+            _ => unreachable!("outcome at failed index is not a failure"),
+        }
+    }
+}
+
+impl Display for UpdateAllError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "failed to update state {} of {}: {}",
+            self.failed_index,
+            self.outcomes.len(),
+            self.cause()
+        )?;
+
+        if !self.is_rolled_back() {
+            f.write_str(" (rollback failed)")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for UpdateAllError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.cause())
+    }
+}
+
+impl From<UpdateAllError> for io::Error {
+    fn from(err: UpdateAllError) -> Self {
+        io::Error::new(err.cause().kind(), err)
+    }
+}
+
+/// Updates the data of multiple states, restoring their previous data if any update fails
+///
+/// Each state is given together with the bytes it should be updated with. Since the states may have different data
+/// types, the states are treated as byte slices, i.e. as if they were cast to `[u8]`. If the states are of different
+/// types, you can pass them as [`BorrowedState<'_, OpaqueData>`](crate::state::BorrowedState) using
+/// `state.as_state().cast()`.
+///
+/// This first queries the current data of all states and then updates the states in the given order. If querying the
+/// data of a state fails, no state is updated. If updating a state fails, the states that have already been updated
+/// are restored to their previous data in reverse order.
+///
+/// Note that this is a best-effort mechanism rather than a real transaction: Other processes may observe the
+/// intermediate states, and updates performed concurrently by other processes may be overwritten by the rollback.
+///
+/// # Errors
+/// Returns an error if querying or updating any of the states fails. The error contains the outcome for every state.
+pub fn update_all_or_nothing<S>(updates: &[(S, &[u8])]) -> Result<(), UpdateAllError>
+where
+    S: AsState,
+{
+    let states: Vec<RawState<[u8]>> = updates.iter().map(|(state, _)| state.as_state().raw.cast()).collect();
+
+    let mut previous_data = Vec::with_capacity(states.len());
+    for (index, state) in states.iter().enumerate() {
+        match state.query_as::<Box<[u8]>>() {
+            Ok(data) => previous_data.push(data.into_data()),
+            Err(err) => return Err(failure(states.len(), index, err)),
+        }
+    }
+
+    let result = apply_updates(&states, updates, &previous_data);
+
+    for data in &mut previous_data {
+        wipe::wipe_slice(data);
+    }
+
+    result
+}
+
+/// Updates the given states with the given data, restoring the given previous data if any update fails
+fn apply_updates<S>(
+    states: &[RawState<[u8]>],
+    updates: &[(S, &[u8])],
+    previous_data: &[Box<[u8]>],
+) -> Result<(), UpdateAllError> {
+    for (index, (state, (_, data))) in states.iter().zip(updates).enumerate() {
+        if let Err(err) = state.set(data) {
+            let mut err = failure(states.len(), index, err);
+
+            for (outcome, (state, data)) in err.outcomes[..index]
+                .iter_mut()
+                .zip(states.iter().zip(previous_data))
+                .rev()
+            {
+                *outcome = match state.set(data) {
+                    Ok(()) => UpdateOutcome::RolledBack,
+                    Err(err) => UpdateOutcome::RollbackFailed(err),
+                };
+            }
+
+            return Err(err);
+        }
+    }
+
+    Ok(())
+}
+
+/// Creates an [`UpdateAllError`] for a failure at the given index, with all other states marked as not attempted
+fn failure(len: usize, failed_index: usize, err: io::Error) -> UpdateAllError {
+    let mut outcomes: Vec<_> = (0..len).map(|_| UpdateOutcome::NotAttempted).collect();
+    outcomes[failed_index] = UpdateOutcome::Failed(err);
+
+    UpdateAllError { failed_index, outcomes }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update_all_error_display() {
+        let mut err = failure(3, 1, io::Error::new(io::ErrorKind::Other, "test"));
+        err.outcomes[0] = UpdateOutcome::RolledBack;
+
+        assert_eq!(err.to_string(), "failed to update state 1 of 3: test");
+        assert!(err.is_rolled_back());
+    }
+
+    #[test]
+    fn update_all_error_display_rollback_failed() {
+        let mut err = failure(2, 1, io::Error::new(io::ErrorKind::Other, "test"));
+        err.outcomes[0] = UpdateOutcome::RollbackFailed(io::Error::new(io::ErrorKind::Other, "rollback"));
+
+        assert_eq!(err.to_string(), "failed to update state 1 of 2: test (rollback failed)");
+        assert!(!err.is_rolled_back());
+    }
+
+    #[test]
+    fn update_all_error_into_io_error_preserves_kind() {
+        let err = failure(1, 0, io::Error::new(io::ErrorKind::PermissionDenied, "test"));
+
+        let io_err: io::Error = err.into();
+
+        assert_eq!(io_err.kind(), io::ErrorKind::PermissionDenied);
+        assert!(io_err.get_ref().unwrap().is::<UpdateAllError>());
+    }
+}
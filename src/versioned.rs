@@ -0,0 +1,294 @@
+//! Storing versioned data in states
+
+use std::fmt::{self, Debug, Formatter};
+use std::io::{self, ErrorKind};
+use std::marker::PhantomData;
+use std::{mem, slice};
+
+use crate::bytes::NoUninit;
+use crate::read::{Read, ReadError};
+use crate::state::{AsState, BorrowedState};
+
+/// The size of the header preceding the payload, consisting of the version number
+const HEADER_SIZE: usize = mem::size_of::<u32>();
+
+/// A view of a state whose data are prefixed with a version number
+///
+/// When the layout of the data of a state changes between releases of an application, consumers built against an older
+/// release would misinterpret the data written by a newer release or vice versa. A
+/// [`VersionedState<'a, T, VERSION>`](VersionedState) prevents this by prefixing the data of type `T` with the version
+/// number `VERSION` when updating the underlying state and refusing to read data with a different version number when
+/// querying it.
+///
+/// Data with a different version number can be upgraded to the current version through a migration function passed
+/// to [`VersionedState::get_or_migrate`] resp. [`VersionedState::get_boxed_or_migrate`].
+///
+/// The data of the underlying state consist of the version number as a little-endian `u32` followed by the data of type
+/// `T`. This makes it possible to read the data by consumers not using this crate.
+///
+/// The underlying state can have any data type, its data are treated as raw bytes. Note that the type id of the
+/// underlying state (if any) is used when updating it.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use wnf::{OwnedState, VersionedState};
+///
+/// let state = OwnedState::<[u8]>::create_temporary()?;
+///
+/// // An older release of the application stored a `u16`
+/// VersionedState::<u16, 1>::new(&state).set(&42)?;
+///
+/// // The current release stores a `u32` and migrates data from the older release
+/// let versioned_state = VersionedState::<u32, 2>::new(&state);
+///
+/// let data = versioned_state.get_or_migrate(|bytes, version| match version {
+///     1 => Some(u16::from_le_bytes(bytes.try_into().ok()?).into()),
+///     _ => None,
+/// })?;
+///
+/// assert_eq!(data, 42);
+/// # Ok(()) }
+/// ```
+pub struct VersionedState<'a, T, const VERSION: u32>
+where
+    T: ?Sized,
+{
+    state: BorrowedState<'a, [u8]>,
+    _marker: PhantomData<fn(T) -> T>,
+}
+
+impl<'a, T, const VERSION: u32> VersionedState<'a, T, VERSION>
+where
+    T: ?Sized,
+{
+    /// Creates a new [`VersionedState<'a, T, VERSION>`](VersionedState) storing versioned data in the given state
+    pub fn new<S>(state: &'a S) -> Self
+    where
+        S: AsState,
+    {
+        Self {
+            state: state.as_state().cast(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the underlying state containing the versioned data
+    pub const fn state(&self) -> BorrowedState<'a, [u8]> {
+        self.state
+    }
+
+    /// Returns the version number of the data written by this [`VersionedState<'a, T, VERSION>`](VersionedState),
+    /// which is `VERSION`
+    pub const fn version(&self) -> u32 {
+        VERSION
+    }
+
+    /// Queries the version number of the data of the underlying state
+    ///
+    /// # Errors
+    /// Returns an error if querying fails or if the data of the underlying state are too short to contain a version
+    /// number, in which case [`io::Error::kind`] returns [`ErrorKind::InvalidData`] and the error wraps a
+    /// [`ReadError::MissingVersion`]
+    pub fn stored_version(&self) -> io::Result<u32> {
+        let bytes = self.state.get_boxed()?;
+        let (version, _) = split_version(&bytes)?;
+        Ok(version)
+    }
+
+    /// Queries the data of the underlying state, returning the version number and the payload as raw bytes
+    fn get_versioned(&self) -> io::Result<(u32, Box<[u8]>)> {
+        let bytes = self.state.get_boxed()?;
+        let (version, payload) = split_version(&bytes)?;
+        Ok((version, payload.into()))
+    }
+}
+
+impl<T, const VERSION: u32> VersionedState<'_, T, VERSION>
+where
+    T: Read<T>,
+{
+    /// Queries the data of the underlying state
+    ///
+    /// # Errors
+    /// Returns an error if querying fails, if the data of the underlying state are too short to contain a version
+    /// number, if the version number is not `VERSION` (in which case [`io::Error::kind`] returns
+    /// [`ErrorKind::InvalidData`] and the error wraps a [`ReadError::UnexpectedVersion`]) or if the payload is not a
+    /// valid `T`
+    pub fn get(&self) -> io::Result<T> {
+        self.get_or_migrate(|_, _| None)
+    }
+
+    /// Queries the data of the underlying state, migrating them with the given function if they have a different
+    /// version number
+    ///
+    /// If the version number of the data is not `VERSION`, the given function is called with the payload (without the
+    /// version number) and the version number of the data. It can return the migrated data or [`None`] if it cannot
+    /// migrate data with that version number.
+    ///
+    /// Note that the migrated data are not written back to the underlying state. In order to persist them, call
+    /// [`VersionedState::set`] with them.
+    ///
+    /// # Errors
+    /// Returns an error if querying fails, if the data of the underlying state are too short to contain a version
+    /// number, if the version number is not `VERSION` and the given function returns [`None`] (in which case
+    /// [`io::Error::kind`] returns [`ErrorKind::InvalidData`] and the error wraps a [`ReadError::UnexpectedVersion`])
+    /// or if the payload is not a valid `T`
+    pub fn get_or_migrate<F>(&self, migrate: F) -> io::Result<T>
+    where
+        F: FnOnce(&[u8], u32) -> Option<T>,
+    {
+        let (version, payload) = self.get_versioned()?;
+
+        if version == VERSION {
+            // SAFETY:
+            // `payload` is a `Box<[u8]>`, so `payload.as_ptr()` is valid for reads of size `payload.len()` and the
+            // memory range is initialized
+            unsafe { T::from_buffer(payload.as_ptr().cast(), payload.len()) }
+        } else {
+            migrate(&payload, version).ok_or_else(|| unexpected_version(VERSION, version))
+        }
+    }
+}
+
+impl<T, const VERSION: u32> VersionedState<'_, T, VERSION>
+where
+    T: Read<Box<T>> + ?Sized,
+{
+    /// Queries the data of the underlying state as a box
+    ///
+    /// # Errors
+    /// See [`VersionedState::get`]
+    pub fn get_boxed(&self) -> io::Result<Box<T>> {
+        self.get_boxed_or_migrate(|_, _| None)
+    }
+
+    /// Queries the data of the underlying state as a box, migrating them with the given function if they have a
+    /// different version number
+    ///
+    /// See [`VersionedState::get_or_migrate`]
+    ///
+    /// # Errors
+    /// See [`VersionedState::get_or_migrate`]
+    pub fn get_boxed_or_migrate<F>(&self, migrate: F) -> io::Result<Box<T>>
+    where
+        F: FnOnce(&[u8], u32) -> Option<Box<T>>,
+    {
+        let (version, payload) = self.get_versioned()?;
+
+        if version == VERSION {
+            // SAFETY:
+            // `payload` is a `Box<[u8]>`, so `payload.as_ptr()` is valid for reads of size `payload.len()` and the
+            // memory range is initialized
+            unsafe { T::from_buffer(payload.as_ptr().cast(), payload.len()) }
+        } else {
+            migrate(&payload, version).ok_or_else(|| unexpected_version(VERSION, version))
+        }
+    }
+}
+
+impl<T, const VERSION: u32> VersionedState<'_, T, VERSION>
+where
+    T: NoUninit + ?Sized,
+{
+    /// Updates the underlying state with the given data, prefixed with the version number `VERSION`
+    ///
+    /// # Errors
+    /// Returns an error if updating fails, e.g. because the data including the version number exceed the maximum state
+    /// size
+    pub fn set(&self, data: &T) -> io::Result<()> {
+        // SAFETY:
+        // - `data` is a reference to a `T`, so it is valid for reads of size `mem::size_of_val(data)`
+        // - `T: NoUninit` guarantees that the memory range of size `mem::size_of_val(data)` starting at `data` is
+        //   initialized
+        let bytes = unsafe { slice::from_raw_parts((data as *const T).cast::<u8>(), mem::size_of_val(data)) };
+
+        self.state.set(&prepend_version(VERSION, bytes))
+    }
+}
+
+impl<T, const VERSION: u32> Clone for VersionedState<'_, T, VERSION>
+where
+    T: ?Sized,
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T, const VERSION: u32> Copy for VersionedState<'_, T, VERSION> where T: ?Sized {}
+
+// We cannot derive this because that would impose an unnecessary trait bound `T: Debug`
+impl<T, const VERSION: u32> Debug for VersionedState<'_, T, VERSION>
+where
+    T: ?Sized,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VersionedState")
+            .field("state", &self.state)
+            .field("version", &VERSION)
+            .finish()
+    }
+}
+
+/// Prepends the given version number to the given bytes
+fn prepend_version(version: u32, bytes: &[u8]) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(HEADER_SIZE + bytes.len());
+    buffer.extend_from_slice(&version.to_le_bytes());
+    buffer.extend_from_slice(bytes);
+    buffer
+}
+
+/// Splits the given bytes into the version number and the payload
+fn split_version(bytes: &[u8]) -> io::Result<(u32, &[u8])> {
+    if bytes.len() < HEADER_SIZE {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            ReadError::MissingVersion { actual: bytes.len() },
+        ));
+    }
+
+    let (header, payload) = bytes.split_at(HEADER_SIZE);
+    Ok((u32::from_le_bytes(header.try_into().unwrap()), payload))
+}
+
+/// Returns an error indicating that data with the given actual version number were found
+fn unexpected_version(expected: u32, actual: u32) -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidData,
+        ReadError::UnexpectedVersion { expected, actual },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use static_assertions::assert_impl_all;
+
+    use super::*;
+
+    #[test]
+    fn versioned_state_is_send_and_sync_regardless_of_data_type() {
+        type NeitherSendNorSync = *const ();
+
+        assert_impl_all!(VersionedState<'_, NeitherSendNorSync, 1>: Send, Sync);
+    }
+
+    #[test]
+    fn prepend_split_version_round_trip() {
+        let bytes = prepend_version(0x0102_0304, b"data");
+
+        assert_eq!(bytes[..HEADER_SIZE], [0x04, 0x03, 0x02, 0x01]);
+        assert_eq!(split_version(&bytes).unwrap(), (0x0102_0304, &b"data"[..]));
+    }
+
+    #[test]
+    fn split_version_too_short() {
+        let err = split_version(&[1, 2, 3]).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(
+            err.get_ref().unwrap().downcast_ref::<ReadError>(),
+            Some(&ReadError::MissingVersion { actual: 3 })
+        );
+    }
+}
@@ -3,13 +3,16 @@
 #![deny(unsafe_code)]
 
 use std::borrow::Borrow;
+use std::collections::VecDeque;
 use std::future::Future;
-use std::io;
+use std::marker::PhantomData;
 use std::pin::Pin;
 use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll, Waker};
+use std::{io, thread};
 
 use crate::data::OpaqueData;
+use crate::info::EXISTENCE_POLL_INTERVAL;
 use crate::predicate::{ChangedPredicate, Predicate, PredicateStage};
 use crate::read::Read;
 use crate::state::{BorrowedState, OwnedState, RawState};
@@ -62,6 +65,48 @@ where
     pub fn wait_async(&self) -> Wait<'_> {
         self.raw.wait_async()
     }
+
+    /// Waits until this state exists
+    ///
+    /// This returns immediately if the state already exists. Otherwise, it waits until the state is created, e.g. by
+    /// another process. This is useful for coordinating producers and consumers of a state regardless of the order in
+    /// which they are started.
+    ///
+    /// Since the WNF API does not notify about states being created, this regularly polls
+    /// [`exists`](OwnedState::exists) on a background thread while the returned future is pending.
+    ///
+    /// This is an async method. If you are in a sync context, use
+    /// [`wait_until_exists_blocking`](OwnedState::wait_until_exists_blocking).
+    ///
+    /// This method does not make any assumptions on what async executor you use. In order to implement a timeout, wrap
+    /// it in the appropriate helper function provided by your executor, see [`wait_async`](OwnedState::wait_async).
+    ///
+    /// The returned future is [`Send`] and thus can be used with multi-threaded executors.
+    ///
+    /// # Errors
+    /// Returns an error if obtaining information on the state fails
+    pub fn wait_until_exists_async(&self) -> WaitExistence<'_> {
+        self.raw.wait_until_existence_async(true)
+    }
+
+    /// Waits until this state is deleted
+    ///
+    /// This returns immediately if the state does not exist. Otherwise, it waits until the state is deleted, e.g. by
+    /// another process.
+    ///
+    /// Since the WNF API does not notify about states being deleted, this regularly polls
+    /// [`exists`](OwnedState::exists) on a background thread while the returned future is pending.
+    ///
+    /// This is an async method. If you are in a sync context, use
+    /// [`wait_until_deleted_blocking`](OwnedState::wait_until_deleted_blocking).
+    ///
+    /// The returned future is [`Send`] and thus can be used with multi-threaded executors.
+    ///
+    /// # Errors
+    /// Returns an error if obtaining information on the state fails
+    pub fn wait_until_deleted_async(&self) -> WaitExistence<'_> {
+        self.raw.wait_until_existence_async(false)
+    }
 }
 
 impl<T> OwnedState<T>
@@ -146,6 +191,11 @@ where
     /// # }
     /// ```
     ///
+    /// The predicate is evaluated on the polling thread rather than on the thread delivering state updates. If the
+    /// state is updated multiple times before the returned future is polled again, the updates are coalesced and the
+    /// predicate is only evaluated on the latest data, so it is evaluated at most once per poll. In order to evaluate
+    /// it on the data of every update instead, use [`WaitUntil::check_every_update`].
+    ///
     /// If the predicate type `F` is [`Send`], the returned future is [`Send`] and thus can be used with multi-threaded
     /// executors. Otherwise you may be able to use constructs such as tokio's
     /// [`LocalSet`](https://docs.rs/tokio/1/tokio/task/struct.LocalSet.html).
@@ -254,6 +304,11 @@ where
     /// # Ok(()) }
     /// ```
     ///
+    /// The predicate is evaluated on the polling thread rather than on the thread delivering state updates. If the
+    /// state is updated multiple times before the returned future is polled again, the updates are coalesced and the
+    /// predicate is only evaluated on the latest data, so it is evaluated at most once per poll. In order to evaluate
+    /// it on the data of every update instead, use [`WaitUntilBoxed::check_every_update`].
+    ///
     /// If the predicate type `F` is [`Send`], the returned future is [`Send`] and thus can be used with multi-threaded
     /// executors. Otherwise you may be able to use constructs such as tokio's
     /// [`LocalSet`](https://docs.rs/tokio/1/tokio/task/struct.LocalSet.html).
@@ -278,6 +333,20 @@ where
     pub fn wait_async(self) -> Wait<'a> {
         self.raw.wait_async()
     }
+
+    /// Waits until this state exists
+    ///
+    /// See [`OwnedState::wait_until_exists_async`]
+    pub fn wait_until_exists_async(self) -> WaitExistence<'a> {
+        self.raw.wait_until_existence_async(true)
+    }
+
+    /// Waits until this state is deleted
+    ///
+    /// See [`OwnedState::wait_until_deleted_async`]
+    pub fn wait_until_deleted_async(self) -> WaitExistence<'a> {
+        self.raw.wait_until_existence_async(false)
+    }
 }
 
 impl<'a, T> BorrowedState<'a, T>
@@ -318,6 +387,11 @@ where
     fn wait_async<'a>(self) -> Wait<'a> {
         Wait::new(self)
     }
+
+    /// Waits until the existence of this state matches the given value
+    fn wait_until_existence_async<'a>(self, exists: bool) -> WaitExistence<'a> {
+        WaitExistence::new(self, exists)
+    }
 }
 
 impl<T> RawState<T>
@@ -388,6 +462,21 @@ impl<F, T> WaitUntil<'_, T, F> {
             inner: WaitUntilInternal::new(state, predicate),
         }
     }
+
+    /// Makes this future evaluate the predicate on the data of every state update
+    ///
+    /// By default, state updates happening between two polls of this future are coalesced and the predicate is only
+    /// evaluated on the latest data. After calling this method, the data of every state update are queued until the
+    /// next poll, which then evaluates the predicate on them in order and completes with the first data satisfying it.
+    /// This is useful if the predicate must not miss intermediate values, but note that the queue grows without bound
+    /// if the state is updated faster than the future is polled.
+    ///
+    /// Note that this only affects updates delivered to the future. The WNF API itself may skip updates happening in
+    /// quick succession, see [`DataAccessor::update_kind`](crate::subscribe::DataAccessor::update_kind).
+    pub fn check_every_update(mut self) -> Self {
+        self.inner.every_update = true;
+        self
+    }
 }
 
 impl<F, T> Future for WaitUntil<'_, T, F>
@@ -423,6 +512,14 @@ where
             inner: WaitUntilInternal::new(state, predicate),
         }
     }
+
+    /// Makes this future evaluate the predicate on the data of every state update
+    ///
+    /// See [`WaitUntil::check_every_update`]
+    pub fn check_every_update(mut self) -> Self {
+        self.inner.every_update = true;
+        self
+    }
 }
 
 impl<F, T> Future for WaitUntilBoxed<'_, T, F>
@@ -438,6 +535,113 @@ where
     }
 }
 
+/// The future returned by [`wait_until_exists_async`](`OwnedState::wait_until_exists_async`) and
+/// [`wait_until_deleted_async`](`OwnedState::wait_until_deleted_async`) methods
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct WaitExistence<'a> {
+    state: RawState<OpaqueData>,
+    exists: bool,
+    shared_state: Option<Arc<Mutex<ExistenceSharedState>>>,
+    _marker: PhantomData<&'a ()>,
+}
+
+/// Shared state between the polling thread and the thread checking for the existence of a state
+#[derive(Debug)]
+struct ExistenceSharedState {
+    result: Option<io::Result<()>>,
+    waker: Waker,
+    cancelled: bool,
+}
+
+impl WaitExistence<'_> {
+    /// Creates a new [`WaitExistence<'_>`] future for the given raw state and expected existence
+    const fn new<T>(state: RawState<T>, exists: bool) -> Self
+    where
+        T: ?Sized,
+    {
+        Self {
+            state: state.cast(),
+            exists,
+            shared_state: None,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl Future for WaitExistence<'_> {
+    type Output = io::Result<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        if let Some(shared_state) = this.shared_state.as_ref() {
+            let ExistenceSharedState { result, waker, .. } = &mut *shared_state.lock().unwrap();
+
+            return match result.take() {
+                Some(result) => Poll::Ready(result),
+                None => {
+                    if !waker.will_wake(cx.waker()) {
+                        waker.clone_from(cx.waker());
+                    }
+
+                    Poll::Pending
+                }
+            };
+        }
+
+        if this.state.exists()? == this.exists {
+            return Poll::Ready(Ok(()));
+        }
+
+        let shared_state = Arc::new(Mutex::new(ExistenceSharedState {
+            result: None,
+            waker: cx.waker().clone(),
+            cancelled: false,
+        }));
+
+        {
+            let (state, exists, shared_state) = (this.state, this.exists, Arc::clone(&shared_state));
+
+            thread::spawn(move || loop {
+                thread::sleep(EXISTENCE_POLL_INTERVAL);
+
+                let result = match state.exists() {
+                    Ok(actual) if actual == exists => Some(Ok(())),
+                    Ok(..) => None,
+                    Err(err) => Some(Err(err)),
+                };
+
+                let mut guard = shared_state.lock().unwrap();
+
+                if guard.cancelled {
+                    break;
+                }
+
+                if let Some(result) = result {
+                    guard.result = Some(result);
+                    guard.waker.wake_by_ref();
+                    break;
+                }
+            });
+        }
+
+        this.shared_state = Some(shared_state);
+
+        Poll::Pending
+    }
+}
+
+impl Drop for WaitExistence<'_> {
+    fn drop(&mut self) {
+        if let Some(shared_state) = self.shared_state.as_ref() {
+            if let Ok(mut guard) = shared_state.lock() {
+                guard.cancelled = true;
+            }
+        }
+    }
+}
+
 /// Future generalizing the behavior of [`Wait<'_>`](Wait), [`WaitUntil<'_, T, F>`](WaitUntil) and [`WaitUntilBoxed<'_,
 /// T, F>`](WaitUntilBoxed)
 #[derive(Debug)]
@@ -447,6 +651,7 @@ where
     T: ?Sized,
 {
     future_state: Option<FutureState<'a, T, D, F>>,
+    every_update: bool,
 }
 
 // This is not auto-implemented because `F` might be `!Unpin`
@@ -472,16 +677,23 @@ where
 }
 
 /// Shared state between the polling thread and the waking thread
+///
+/// Unless `every_update` is `true`, `results` contains at most one element, i.e. the result for the latest update.
 #[derive(Debug)]
 struct SharedState<D> {
-    result: Option<io::Result<D>>,
+    results: VecDeque<io::Result<D>>,
     waker: Waker,
+    every_update: bool,
 }
 
 impl<D> SharedState<D> {
     /// Creates a new [`SharedState<D>`] from the given waker
-    const fn from_waker(waker: Waker) -> Self {
-        Self { result: None, waker }
+    const fn from_waker(waker: Waker, every_update: bool) -> Self {
+        Self {
+            results: VecDeque::new(),
+            waker,
+            every_update,
+        }
     }
 }
 
@@ -493,6 +705,7 @@ where
     const fn new(state: RawState<T>, predicate: F) -> Self {
         Self {
             future_state: Some(FutureState::Initial { state, predicate }),
+            every_update: false,
         }
     }
 }
@@ -506,6 +719,8 @@ where
     type Output = io::Result<D>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let every_update = self.every_update;
+
         self.future_state = Some(
             match self.future_state.take().expect("future polled after it has completed") {
                 FutureState::Initial { state, mut predicate } => {
@@ -515,7 +730,7 @@ where
                         return Poll::Ready(Ok(data));
                     }
 
-                    let shared_state = Arc::new(Mutex::new(SharedState::from_waker(cx.waker().clone())));
+                    let shared_state = Arc::new(Mutex::new(SharedState::from_waker(cx.waker().clone(), every_update)));
                     let subscription = state.subscribe(
                         WaitListener::new(Arc::clone(&shared_state)),
                         SeenChangeStamp::Value(change_stamp),
@@ -534,13 +749,12 @@ where
                     subscription,
                 } => {
                     let mut guard = shared_state.lock().unwrap();
-                    let SharedState { result, waker } = &mut *guard;
+                    let SharedState { results, waker, .. } = &mut *guard;
 
-                    let ready_result = match result.take() {
-                        Some(Ok(data)) if !predicate.check(data.borrow(), PredicateStage::Changed) => None,
-                        None => None,
-                        result => result,
-                    };
+                    let ready_result = results.drain(..).find(|result| match result {
+                        Ok(data) => predicate.check(data.borrow(), PredicateStage::Changed),
+                        Err(..) => true,
+                    });
 
                     match ready_result {
                         Some(result) => {
@@ -592,9 +806,24 @@ where
     T: Read<D> + ?Sized,
 {
     fn call(&mut self, accessor: DataAccessor<'_, T>) {
-        let SharedState { result, ref waker } = &mut *self.shared_state.lock().unwrap();
-        *result = Some(accessor.get_as());
-        waker.wake_by_ref();
+        let SharedState {
+            results,
+            ref waker,
+            every_update,
+        } = &mut *self.shared_state.lock().unwrap();
+
+        // If there are pending results, the waker has already been woken and the future has not been polled since
+        let pending = !results.is_empty();
+
+        if !*every_update {
+            results.clear();
+        }
+
+        results.push_back(accessor.get_as());
+
+        if !pending {
+            waker.wake_by_ref();
+        }
     }
 }
 
@@ -614,6 +843,11 @@ mod tests {
         assert_impl_all!(Wait<'_>: Send, Sync);
     }
 
+    #[test]
+    fn wait_existence_future_is_send_and_sync() {
+        assert_impl_all!(WaitExistence<'_>: Send, Sync);
+    }
+
     #[test]
     fn wait_until_future_is_send_if_predicate_and_data_type_are_send() {
         type SendNotSync = Cell<()>;
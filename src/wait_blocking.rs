@@ -7,9 +7,11 @@
 use std::borrow::Borrow;
 use std::io::{self, ErrorKind};
 use std::sync::{Arc, Condvar, Mutex};
-use std::time::Duration;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use crate::data::OpaqueData;
+use crate::info::EXISTENCE_POLL_INTERVAL;
 use crate::predicate::{ChangedPredicate, Predicate, PredicateStage};
 use crate::read::Read;
 use crate::state::{BorrowedState, OwnedState, RawState};
@@ -36,6 +38,43 @@ where
     pub fn wait_blocking(&self, timeout: Duration) -> io::Result<()> {
         self.raw.wait_blocking(timeout)
     }
+
+    /// Waits until this state exists
+    ///
+    /// This returns immediately if the state already exists. Otherwise, it waits until the state is created, e.g. by
+    /// another process. This is useful for coordinating producers and consumers of a state regardless of the order in
+    /// which they are started.
+    ///
+    /// Since the WNF API does not notify about states being created, this regularly polls
+    /// [`exists`](OwnedState::exists).
+    ///
+    /// This is a blocking method. If you are in an async context, use
+    /// [`wait_until_exists_async`](OwnedState::wait_until_exists_async).
+    ///
+    /// # Errors
+    /// Returns an error if obtaining information on the state fails or if the timeout has elapsed. In the latter case,
+    /// [`io::Error::kind`] returns [`ErrorKind::TimedOut`].
+    pub fn wait_until_exists_blocking(&self, timeout: Duration) -> io::Result<()> {
+        self.raw.wait_until_existence_blocking(true, timeout)
+    }
+
+    /// Waits until this state is deleted
+    ///
+    /// This returns immediately if the state does not exist. Otherwise, it waits until the state is deleted, e.g. by
+    /// another process.
+    ///
+    /// Since the WNF API does not notify about states being deleted, this regularly polls
+    /// [`exists`](OwnedState::exists).
+    ///
+    /// This is a blocking method. If you are in an async context, use
+    /// [`wait_until_deleted_async`](OwnedState::wait_until_deleted_async).
+    ///
+    /// # Errors
+    /// Returns an error if obtaining information on the state fails or if the timeout has elapsed. In the latter case,
+    /// [`io::Error::kind`] returns [`ErrorKind::TimedOut`].
+    pub fn wait_until_deleted_blocking(&self, timeout: Duration) -> io::Result<()> {
+        self.raw.wait_until_existence_blocking(false, timeout)
+    }
 }
 
 impl<T> OwnedState<T>
@@ -180,6 +219,20 @@ where
     pub fn wait_blocking(self, timeout: Duration) -> io::Result<()> {
         self.raw.wait_blocking(timeout)
     }
+
+    /// Waits until this state exists
+    ///
+    /// See [`OwnedState::wait_until_exists_blocking`]
+    pub fn wait_until_exists_blocking(self, timeout: Duration) -> io::Result<()> {
+        self.raw.wait_until_existence_blocking(true, timeout)
+    }
+
+    /// Waits until this state is deleted
+    ///
+    /// See [`OwnedState::wait_until_deleted_blocking`]
+    pub fn wait_until_deleted_blocking(self, timeout: Duration) -> io::Result<()> {
+        self.raw.wait_until_existence_blocking(false, timeout)
+    }
 }
 
 impl<T> BorrowedState<'_, T>
@@ -221,6 +274,29 @@ where
         let _: OpaqueData = self.cast().wait_until_blocking_internal(ChangedPredicate, timeout)?;
         Ok(())
     }
+
+    /// Waits until the existence of this state matches the given value
+    fn wait_until_existence_blocking(self, exists: bool, timeout: Duration) -> io::Result<()> {
+        let deadline = Instant::now().checked_add(timeout);
+
+        while self.exists()? != exists {
+            let remaining = match deadline {
+                Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+                None => Duration::MAX,
+            };
+
+            if remaining.is_zero() {
+                return Err(io::Error::new(
+                    ErrorKind::TimedOut,
+                    "waiting for state existence timed out",
+                ));
+            }
+
+            thread::sleep(remaining.min(EXISTENCE_POLL_INTERVAL));
+        }
+
+        Ok(())
+    }
 }
 
 impl<T> RawState<T>
@@ -0,0 +1,206 @@
+//! Methods for asynchronously waiting for state updates with a deadline
+
+#![deny(unsafe_code)]
+
+use std::fmt::{self, Debug, Formatter};
+use std::future::Future;
+use std::io::{self, ErrorKind};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use crate::state::{BorrowedState, OwnedState};
+use crate::wait_async::Wait;
+
+/// A trait for timers that can be used to put a deadline on async waits
+///
+/// Since this crate does not make any assumptions on what async executor you use, it cannot provide a timer by itself.
+/// Implement this trait for a type that creates a future completing at a given deadline using the timer of your
+/// executor. This crate provides implementations for [`tokio`](https://docs.rs/tokio/1/tokio) (see [`TokioSleep`],
+/// requires the `tokio` feature) and [`async-std`](https://docs.rs/async-std/1/async_std) (see [`AsyncStdSleep`],
+/// requires the `async_std` feature).
+pub trait Sleep {
+    /// The type of the future completing at the deadline
+    ///
+    /// The output of this future is ignored.
+    type Future: Future;
+
+    /// Creates a future completing at the given deadline
+    fn sleep_until(&self, deadline: Instant) -> Self::Future;
+}
+
+/// A [`Sleep`] implementation using the timer of the [`tokio`](https://docs.rs/tokio/1/tokio) runtime
+///
+/// Note that the sleep future must be polled within a tokio runtime that has the time driver enabled.
+#[cfg(feature = "tokio")]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct TokioSleep;
+
+#[cfg(feature = "tokio")]
+impl Sleep for TokioSleep {
+    type Future = tokio::time::Sleep;
+
+    fn sleep_until(&self, deadline: Instant) -> Self::Future {
+        tokio::time::sleep_until(deadline.into())
+    }
+}
+
+/// A [`Sleep`] implementation using the timer of [`async-std`](https://docs.rs/async-std/1/async_std)
+#[cfg(feature = "async_std")]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct AsyncStdSleep;
+
+#[cfg(feature = "async_std")]
+impl Sleep for AsyncStdSleep {
+    type Future = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+    fn sleep_until(&self, deadline: Instant) -> Self::Future {
+        Box::pin(async_std::task::sleep(
+            deadline.saturating_duration_since(Instant::now()),
+        ))
+    }
+}
+
+impl<T> OwnedState<T>
+where
+    T: ?Sized,
+{
+    /// Waits until this state is updated or the given deadline is reached, whichever happens first
+    ///
+    /// This is the same as [`wait_async`](OwnedState::wait_async), except that it fails with an error of kind
+    /// [`ErrorKind::TimedOut`] when the deadline is reached. The deadline is implemented using the given [`Sleep`]
+    /// implementation, e.g. [`TokioSleep`] when using [`tokio`](https://docs.rs/tokio/1/tokio).
+    ///
+    /// When the deadline is reached, the listener is unsubscribed from the state before the returned future
+    /// completes, regardless of when the future itself is dropped.
+    ///
+    /// # Example
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use std::io::ErrorKind;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// use wnf::{OwnedState, TokioSleep};
+    ///
+    /// let state = OwnedState::<u32>::create_temporary()?;
+    /// let deadline = Instant::now() + Duration::from_millis(100);
+    ///
+    /// let result = state.wait_async_with_deadline(deadline, TokioSleep).await;
+    /// assert_eq!(result.unwrap_err().kind(), ErrorKind::TimedOut);
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// If the sleep future is [`Send`], the returned future is [`Send`] and thus can be used with multi-threaded
+    /// executors.
+    ///
+    /// # Errors
+    /// Returns an error if querying, subscribing to or unsubscribing from the state fails or if the deadline has been
+    /// reached. In the latter case, [`io::Error::kind`] returns [`ErrorKind::TimedOut`].
+    pub fn wait_async_with_deadline<S>(&self, deadline: Instant, sleep: S) -> WaitWithDeadline<Wait<'_>, S::Future>
+    where
+        S: Sleep,
+    {
+        WaitWithDeadline::new(self.wait_async(), sleep.sleep_until(deadline))
+    }
+}
+
+impl<'a, T> BorrowedState<'a, T>
+where
+    T: ?Sized,
+{
+    /// Waits until this state is updated or the given deadline is reached, whichever happens first
+    ///
+    /// See [`OwnedState::wait_async_with_deadline`]
+    pub fn wait_async_with_deadline<S>(self, deadline: Instant, sleep: S) -> WaitWithDeadline<Wait<'a>, S::Future>
+    where
+        S: Sleep,
+    {
+        WaitWithDeadline::new(self.wait_async(), sleep.sleep_until(deadline))
+    }
+}
+
+/// The future returned by [`wait_async_with_deadline`](OwnedState::wait_async_with_deadline) methods
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct WaitWithDeadline<F, S> {
+    inner: Option<F>,
+    sleep: Pin<Box<S>>,
+}
+
+impl<F, S> WaitWithDeadline<F, S> {
+    /// Creates a new [`WaitWithDeadline<F, S>`](WaitWithDeadline) future from the given inner and sleep futures
+    fn new(inner: F, sleep: S) -> Self {
+        Self {
+            inner: Some(inner),
+            sleep: Box::pin(sleep),
+        }
+    }
+}
+
+// We cannot derive this because that would impose an unnecessary trait bound `S: Debug`
+impl<F, S> Debug for WaitWithDeadline<F, S>
+where
+    F: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WaitWithDeadline")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F, S, D> Future for WaitWithDeadline<F, S>
+where
+    F: Future<Output = io::Result<D>> + Unpin,
+    S: Future,
+{
+    type Output = io::Result<D>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        let Some(inner) = this.inner.as_mut() else {
+            panic!("`WaitWithDeadline` polled after completion");
+        };
+
+        if let Poll::Ready(result) = Pin::new(inner).poll(cx) {
+            this.inner = None;
+            return Poll::Ready(result);
+        }
+
+        if this.sleep.as_mut().poll(cx).is_ready() {
+            // Dropping the inner future unsubscribes from the state right away
+            this.inner = None;
+            return Poll::Ready(Err(io::Error::new(ErrorKind::TimedOut, "deadline has elapsed")));
+        }
+
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn inner_ready_takes_precedence() {
+        let mut future = WaitWithDeadline::new(future::ready(io::Result::Ok(42)), future::ready(()));
+
+        let result = (&mut future).await;
+
+        assert_eq!(result.unwrap(), 42);
+        assert!(future.inner.is_none());
+    }
+
+    #[tokio::test]
+    async fn deadline_reached() {
+        let mut future = WaitWithDeadline::new(future::pending::<io::Result<()>>(), future::ready(()));
+
+        let result = (&mut future).await;
+
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::TimedOut);
+        assert!(future.inner.is_none());
+    }
+}
@@ -0,0 +1,442 @@
+//! Methods for synchronously waiting for state updates without running code on the WNF callback thread
+//!
+//! Besides the [`WaitNotification`] type, this module adds inherent impls to [`OwnedState<T>`] and
+//! [`BorrowedState<'_, T>`](BorrowedState).
+
+use std::borrow::Borrow;
+use std::io::{self, ErrorKind};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use windows::core::PCWSTR;
+use windows::Win32::Foundation::{CloseHandle, HANDLE, WAIT_FAILED};
+use windows::Win32::System::Threading::{CreateEventW, SetEvent, WaitForSingleObject, INFINITE};
+
+use crate::data::{ChangeStamp, OpaqueData};
+use crate::predicate::{ChangedPredicate, Predicate, PredicateStage};
+use crate::read::Read;
+use crate::state::{BorrowedState, OwnedState, RawState};
+use crate::subscribe::{DataAccessor, SeenChangeStamp, StateListener, Subscription};
+
+/// The way a waiting thread is notified about state updates when waiting through one of the `*_event_blocking`
+/// methods
+///
+/// In both cases, the state data are queried and the predicate is evaluated on the waiting thread, so no code
+/// provided by you runs on the thread the WNF API uses for notifications.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum WaitNotification {
+    /// Waits on an event object that is signaled by a minimal internal subscription on every state update
+    ///
+    /// The only code running on the WNF callback thread is signaling the event object.
+    #[default]
+    Event,
+
+    /// Polls the state in the given interval without subscribing to it at all
+    ///
+    /// This avoids callbacks entirely at the cost of noticing updates only up to one interval late. Updates happening
+    /// between two polls are not observed individually.
+    Poll(Duration),
+}
+
+impl<T> OwnedState<T>
+where
+    T: ?Sized,
+{
+    /// Waits until this state is updated, without running code on the WNF callback thread
+    ///
+    /// This is the same as [`wait_blocking`](OwnedState::wait_blocking), except that the waiting thread is notified as
+    /// specified by the given [`WaitNotification`] rather than through a regular subscription. This is useful in
+    /// environments where running arbitrary code on the WNF callback thread is undesirable.
+    ///
+    /// This is a blocking method.
+    ///
+    /// # Errors
+    /// Returns an error if querying, subscribing to or unsubscribing from the state or waiting on the event object
+    /// fails or if the timeout has elapsed. In the latter case, [`io::Error::kind`] returns [`ErrorKind::TimedOut`].
+    pub fn wait_event_blocking(&self, timeout: Duration, notification: WaitNotification) -> io::Result<()> {
+        self.raw.wait_event_blocking(timeout, notification)
+    }
+}
+
+impl<T> OwnedState<T>
+where
+    T: Read<T>,
+{
+    /// Waits until the data of this state satisfy a given predicate, returning the data, without running code on the
+    /// WNF callback thread
+    ///
+    /// This is the same as [`wait_until_blocking`](OwnedState::wait_until_blocking), except that the waiting thread is
+    /// notified as specified by the given [`WaitNotification`] rather than through a regular subscription, and that
+    /// the state data are queried and the predicate is evaluated on the waiting thread. This is useful in environments
+    /// where running arbitrary code on the WNF callback thread is undesirable.
+    ///
+    /// Since the state data are queried after being notified rather than passed along with the notification, data of
+    /// updates that are overwritten before the waiting thread queries them are never checked against the predicate.
+    ///
+    /// This produces an owned `T` on the stack and hence requires `T: Sized`. In order to produce a `Box<T>` for
+    /// `T: ?Sized`, use the [`wait_until_boxed_event_blocking`](OwnedState::wait_until_boxed_event_blocking) method.
+    ///
+    /// For example, to wait until the value of a state reaches a given minimum by polling it:
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use std::sync::Arc;
+    /// use std::thread;
+    /// use std::time::Duration;
+    ///
+    /// use wnf::{OwnedState, WaitNotification};
+    ///
+    /// let state = Arc::new(OwnedState::create_temporary()?);
+    /// state.set(&0)?;
+    ///
+    /// {
+    ///     let state = Arc::clone(&state);
+    ///     thread::spawn(move || loop {
+    ///         state.apply(|value| value + 1).unwrap();
+    ///         thread::sleep(Duration::from_millis(10));
+    ///     });
+    /// }
+    ///
+    /// let value = state.wait_until_event_blocking(
+    ///     |value| *value >= 10,
+    ///     Duration::MAX,
+    ///     WaitNotification::Poll(Duration::from_millis(50)),
+    /// )?;
+    /// assert!(value >= 10);
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// This is a blocking method.
+    ///
+    /// # Errors
+    /// Returns an error if querying, subscribing to or unsubscribing from the state or waiting on the event object
+    /// fails or if the timeout has elapsed. In the latter case, [`io::Error::kind`] returns [`ErrorKind::TimedOut`].
+    pub fn wait_until_event_blocking<F>(
+        &self,
+        predicate: F,
+        timeout: Duration,
+        notification: WaitNotification,
+    ) -> io::Result<T>
+    where
+        F: FnMut(&T) -> bool,
+    {
+        self.raw
+            .wait_until_event_blocking_internal(predicate, timeout, notification)
+    }
+}
+
+impl<T> OwnedState<T>
+where
+    T: Read<Box<T>> + ?Sized,
+{
+    /// Waits until the data of this state satisfy a given predicate, returning the data as a box, without running code
+    /// on the WNF callback thread
+    ///
+    /// This is the same as [`wait_until_event_blocking`](OwnedState::wait_until_event_blocking), except that it
+    /// produces a [`Box<T>`] instead of an owned `T` (requiring `T: Sized`).
+    ///
+    /// This is a blocking method.
+    ///
+    /// # Errors
+    /// Returns an error if querying, subscribing to or unsubscribing from the state or waiting on the event object
+    /// fails or if the timeout has elapsed. In the latter case, [`io::Error::kind`] returns [`ErrorKind::TimedOut`].
+    pub fn wait_until_boxed_event_blocking<F>(
+        &self,
+        predicate: F,
+        timeout: Duration,
+        notification: WaitNotification,
+    ) -> io::Result<Box<T>>
+    where
+        F: FnMut(&T) -> bool,
+    {
+        self.raw
+            .wait_until_event_blocking_internal(predicate, timeout, notification)
+    }
+}
+
+impl<T> BorrowedState<'_, T>
+where
+    T: ?Sized,
+{
+    /// Waits until this state is updated, without running code on the WNF callback thread
+    ///
+    /// See [`OwnedState::wait_event_blocking`]
+    pub fn wait_event_blocking(self, timeout: Duration, notification: WaitNotification) -> io::Result<()> {
+        self.raw.wait_event_blocking(timeout, notification)
+    }
+}
+
+impl<T> BorrowedState<'_, T>
+where
+    T: Read<T>,
+{
+    /// Waits until the data of this state satisfy a given predicate, returning the data, without running code on the
+    /// WNF callback thread
+    ///
+    /// See [`OwnedState::wait_until_event_blocking`]
+    pub fn wait_until_event_blocking<F>(
+        self,
+        predicate: F,
+        timeout: Duration,
+        notification: WaitNotification,
+    ) -> io::Result<T>
+    where
+        F: FnMut(&T) -> bool,
+    {
+        self.raw
+            .wait_until_event_blocking_internal(predicate, timeout, notification)
+    }
+}
+
+impl<T> BorrowedState<'_, T>
+where
+    T: Read<Box<T>> + ?Sized,
+{
+    /// Waits until the data of this state satisfy a given predicate, returning the data as a box, without running code
+    /// on the WNF callback thread
+    ///
+    /// See [`OwnedState::wait_until_boxed_event_blocking`]
+    pub fn wait_until_boxed_event_blocking<F>(
+        self,
+        predicate: F,
+        timeout: Duration,
+        notification: WaitNotification,
+    ) -> io::Result<Box<T>>
+    where
+        F: FnMut(&T) -> bool,
+    {
+        self.raw
+            .wait_until_event_blocking_internal(predicate, timeout, notification)
+    }
+}
+
+impl<T> RawState<T>
+where
+    T: ?Sized,
+{
+    /// Waits until this state is updated, without running code on the WNF callback thread
+    fn wait_event_blocking(self, timeout: Duration, notification: WaitNotification) -> io::Result<()> {
+        let _: OpaqueData = self
+            .cast()
+            .wait_until_event_blocking_internal(ChangedPredicate, timeout, notification)?;
+
+        Ok(())
+    }
+
+    /// Waits until the data of this state satisfy a given predicate, returning the data as a value of type `D`, without
+    /// running code on the WNF callback thread
+    ///
+    /// The predicate is called once with [`PredicateStage::Initial`], then again with [`PredicateStage::Changed`]
+    /// whenever the change stamp of the queried data has changed.
+    ///
+    /// If `T: Sized`, then `D` can be either `T` or `Box<T>`.
+    /// If `T: !Sized`, then `D` must be `Box<T>`.
+    fn wait_until_event_blocking_internal<D, F>(
+        self,
+        mut predicate: F,
+        timeout: Duration,
+        notification: WaitNotification,
+    ) -> io::Result<D>
+    where
+        D: Borrow<T>,
+        F: Predicate<T>,
+        T: Read<D>,
+    {
+        let deadline = Instant::now().checked_add(timeout);
+        let (data, mut change_stamp) = self.query_as()?.into_data_change_stamp();
+
+        if predicate.check(data.borrow(), PredicateStage::Initial) {
+            return Ok(data);
+        }
+
+        let waiter = Waiter::new(self, notification, change_stamp)?;
+
+        let result = loop {
+            let remaining = match deadline {
+                Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+                None => Duration::MAX,
+            };
+
+            if remaining.is_zero() {
+                break Err(io::Error::new(
+                    ErrorKind::TimedOut,
+                    "waiting for state update timed out",
+                ));
+            }
+
+            if let Err(err) = waiter.wait(remaining) {
+                break Err(err);
+            }
+
+            let (data, new_change_stamp) = match self.query_as() {
+                Ok(data) => data.into_data_change_stamp(),
+                Err(err) => break Err(err),
+            };
+
+            if new_change_stamp != change_stamp {
+                change_stamp = new_change_stamp;
+
+                if predicate.check(data.borrow(), PredicateStage::Changed) {
+                    break Ok(data);
+                }
+            }
+        };
+
+        waiter.close()?;
+        result
+    }
+}
+
+/// A means of waiting for the next state update, as specified by a [`WaitNotification`]
+enum Waiter<'a> {
+    Event {
+        event: Arc<Event>,
+        subscription: Subscription<'a, EventListener>,
+    },
+    Poll(Duration),
+}
+
+impl<'a> Waiter<'a> {
+    /// Creates a new [`Waiter<'a>`](Waiter) for updates of the given state after the given change stamp
+    fn new<T>(state: RawState<T>, notification: WaitNotification, change_stamp: ChangeStamp) -> io::Result<Self>
+    where
+        T: ?Sized,
+    {
+        Ok(match notification {
+            WaitNotification::Event => {
+                let event = Arc::new(Event::new()?);
+
+                let subscription = state.cast::<OpaqueData>().subscribe(
+                    EventListener {
+                        event: Arc::clone(&event),
+                    },
+                    SeenChangeStamp::Value(change_stamp),
+                )?;
+
+                Self::Event { event, subscription }
+            }
+
+            WaitNotification::Poll(interval) => Self::Poll(interval),
+        })
+    }
+
+    /// Waits until the next state update may have happened, but at most for the given duration
+    ///
+    /// This may return early even if the state has not been updated.
+    fn wait(&self, timeout: Duration) -> io::Result<()> {
+        match self {
+            Self::Event { event, .. } => event.wait(timeout),
+
+            Self::Poll(interval) => {
+                thread::sleep(timeout.min(*interval));
+                Ok(())
+            }
+        }
+    }
+
+    /// Closes this [`Waiter<'a>`](Waiter), unsubscribing from the state if necessary
+    fn close(self) -> io::Result<()> {
+        match self {
+            Self::Event { subscription, .. } => subscription.unsubscribe(),
+            Self::Poll(..) => Ok(()),
+        }
+    }
+}
+
+/// The internal state listener of a [`Waiter<'_>`](Waiter), signaling an event object on every state update
+struct EventListener {
+    event: Arc<Event>,
+}
+
+impl StateListener<OpaqueData> for EventListener {
+    fn call(&mut self, _: DataAccessor<'_, OpaqueData>) {
+        // There is nothing we can do about a failure here, the waiting thread will time out eventually
+        let _ = self.event.set();
+    }
+}
+
+/// An owned handle to an auto-reset event object
+#[derive(Debug)]
+struct Event {
+    handle: HANDLE,
+}
+
+// SAFETY:
+// An event object can be signaled and waited on from any thread and the handle is only closed when the `Event` is
+// dropped, which requires exclusive ownership
+unsafe impl Send for Event {}
+
+// SAFETY:
+// Signaling and waiting on an event object only require a shared reference and can happen concurrently from any thread
+unsafe impl Sync for Event {}
+
+impl Event {
+    /// Creates a new auto-reset event object that is initially not signaled
+    fn new() -> io::Result<Self> {
+        // SAFETY:
+        // - The first argument is `None`, so the event object gets a default security descriptor
+        // - The fourth argument is a null pointer, so the event object is created without a name
+        let handle = unsafe { CreateEventW(None, false, false, PCWSTR::null()) }?;
+        Ok(Self { handle })
+    }
+
+    /// Signals this event object, releasing one waiting thread
+    fn set(&self) -> io::Result<()> {
+        // SAFETY:
+        // `self.handle` is a valid handle to an event object because it is only closed when `self` is dropped
+        unsafe { SetEvent(self.handle) }?;
+        Ok(())
+    }
+
+    /// Waits until this event object is signaled, but at most for the given duration
+    fn wait(&self, timeout: Duration) -> io::Result<()> {
+        // SAFETY:
+        // `self.handle` is a valid handle to an event object because it is only closed when `self` is dropped
+        let result = unsafe { WaitForSingleObject(self.handle, timeout_millis(timeout)) };
+
+        if result == WAIT_FAILED {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Drop for Event {
+    fn drop(&mut self) {
+        // SAFETY:
+        // `self.handle` is a valid handle to an event object that is not used after this
+        let _ = unsafe { CloseHandle(self.handle) };
+    }
+}
+
+/// Converts the given timeout into milliseconds for passing to [`WaitForSingleObject`]
+///
+/// This rounds up so that waiting for a timeout of less than a millisecond does not return immediately. Timeouts not
+/// representable as a `u32` other than [`INFINITE`] are clamped.
+fn timeout_millis(timeout: Duration) -> u32 {
+    if timeout == Duration::MAX {
+        return INFINITE;
+    }
+
+    let millis = timeout.as_nanos().div_ceil(1_000_000);
+    millis.try_into().unwrap_or(INFINITE - 1).min(INFINITE - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeout_millis_rounds_up() {
+        assert_eq!(timeout_millis(Duration::ZERO), 0);
+        assert_eq!(timeout_millis(Duration::from_nanos(1)), 1);
+        assert_eq!(timeout_millis(Duration::from_millis(42)), 42);
+    }
+
+    #[test]
+    fn timeout_millis_clamps_large_timeouts() {
+        assert_eq!(timeout_millis(Duration::from_secs(u64::from(u32::MAX))), INFINITE - 1);
+        assert_eq!(timeout_millis(Duration::MAX), INFINITE);
+    }
+}
@@ -0,0 +1,288 @@
+//! Watching all well-known states with a given owner tag
+
+#![deny(unsafe_code)]
+
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Formatter};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use std::{io, mem, panic};
+
+use crate::data::OpaqueData;
+use crate::registry;
+use crate::state::RawState;
+use crate::state_name::{StateLifetime, StateName, StateNameDescriptor};
+use crate::subscribe::{DataAccessor, SeenChangeStamp, Subscription};
+use crate::subscribe_group::GroupMemberListener;
+use crate::type_id::TypeId;
+
+/// The interval in which [`watch_prefix`] re-enumerates the registered state names
+const DEFAULT_RESCAN_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Subscribes the given listener to updates of all well-known states whose owner tag starts with the given prefix
+///
+/// The state names are discovered by enumerating the well-known state names registered in the Windows registry (see
+/// [`registered_state_names`](crate::registry::registered_state_names)) and filtering them by their owner tag (see
+/// [`StateNameDescriptor::matches_prefix`]). Since new state names may be registered later, e.g. when a component of
+/// the system is updated, the registered state names are re-enumerated every ten seconds on a background thread and
+/// the listener is subscribed to newly appearing ones. Use [`watch_prefix_with_interval`] to configure this interval.
+///
+/// On every update of any of the states, the listener is called with the name of the updated state and a
+/// [`DataAccessor<'_, OpaqueData>`](DataAccessor), just like with [`subscribe_group`](crate::subscribe_group). The
+/// listener is never called concurrently. Only updates happening after the listener has been subscribed to a state
+/// are reported.
+///
+/// States the listener cannot be subscribed to (e.g. due to missing permissions) are skipped, and subscribing to them
+/// is retried on the next re-enumeration. The same applies to errors while re-enumerating.
+///
+/// This is useful for monitoring and diagnostic tools that want to observe a whole namespace of well-known states,
+/// such as all states owned by the shell (`"SHEL"`) or by the power manager (`"PO"`).
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use wnf::{DataAccessor, OpaqueData, StateName};
+///
+/// let watch = wnf::watch_prefix("SHEL", |state_name: StateName, accessor: DataAccessor<OpaqueData>| {
+///     println!("State {state_name} updated: {} bytes", accessor.get().unwrap().size());
+/// })?;
+///
+/// println!("Watching {} states", watch.state_names().len());
+///
+/// watch.stop()?;
+/// # Ok(()) }
+/// ```
+///
+/// # Errors
+/// Returns an error if the initial enumeration of the registered state names fails or if spawning the background
+/// thread fails
+pub fn watch_prefix<F>(owner_tag: &str, listener: F) -> io::Result<PrefixWatch<F>>
+where
+    F: FnMut(StateName, DataAccessor<'_, OpaqueData>) + Send + 'static,
+{
+    watch_prefix_with_interval(owner_tag, DEFAULT_RESCAN_INTERVAL, listener)
+}
+
+/// Subscribes the given listener to updates of all well-known states whose owner tag starts with the given prefix,
+/// re-enumerating the registered state names in the given interval
+///
+/// This is the same as [`watch_prefix`], except that the registered state names are re-enumerated in the given
+/// interval rather than every ten seconds.
+///
+/// # Panics
+/// Panics if `rescan_interval` is zero
+///
+/// # Errors
+/// See [`watch_prefix`]
+pub fn watch_prefix_with_interval<F>(
+    owner_tag: &str,
+    rescan_interval: Duration,
+    listener: F,
+) -> io::Result<PrefixWatch<F>>
+where
+    F: FnMut(StateName, DataAccessor<'_, OpaqueData>) + Send + 'static,
+{
+    assert!(!rescan_interval.is_zero(), "rescan interval must not be zero");
+
+    let shared = Arc::new(Shared {
+        owner_tag: owner_tag.to_owned(),
+        listener: Arc::new(Mutex::new(listener)),
+        subscriptions: Mutex::new(HashMap::new()),
+        stopped: Mutex::new(false),
+        condvar: Condvar::new(),
+    });
+
+    shared.rescan()?;
+
+    let thread = thread::Builder::new().name("wnf-watch-prefix".into()).spawn({
+        let shared = Arc::clone(&shared);
+        move || rescan_periodically(&shared, rescan_interval)
+    })?;
+
+    Ok(PrefixWatch {
+        shared,
+        thread: Some(thread),
+    })
+}
+
+/// A subscription of a single listener to updates of all well-known states with a given owner tag prefix
+///
+/// This is returned from [`watch_prefix`] and [`watch_prefix_with_interval`].
+///
+/// Note that the background thread is stopped and the listener is automatically unsubscribed from all states when the
+/// [`PrefixWatch<F>`](PrefixWatch) is dropped. In this case, errors while unsubscribing are silently ignored. If you
+/// want to handle them explicitly, use the [`PrefixWatch::stop`] method.
+#[must_use = "a `PrefixWatch` is stopped immediately if it is not used"]
+pub struct PrefixWatch<F> {
+    shared: Arc<Shared<F>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<F> PrefixWatch<F> {
+    /// Returns the owner tag prefix watched by this [`PrefixWatch<F>`](PrefixWatch)
+    pub fn owner_tag(&self) -> &str {
+        &self.shared.owner_tag
+    }
+
+    /// Returns the names of the states the listener is currently subscribed to
+    pub fn state_names(&self) -> Vec<StateName> {
+        self.shared.lock_subscriptions().keys().copied().collect()
+    }
+
+    /// Stops re-enumerating the registered state names and unsubscribes the listener from all states
+    ///
+    /// This happens automatically when the [`PrefixWatch<F>`](PrefixWatch) is dropped, so there is usually no need to
+    /// call this method. Its only purpose is to enable you to handle errors while unsubscribing. This blocks until
+    /// the background thread has finished.
+    ///
+    /// # Errors
+    /// Returns the first error that occurred while unsubscribing from any of the states. Unsubscribing from the other
+    /// states is attempted regardless.
+    pub fn stop(mut self) -> io::Result<()> {
+        self.stop_internal()
+    }
+
+    /// Stops this [`PrefixWatch<F>`](PrefixWatch) without consuming it
+    fn stop_internal(&mut self) -> io::Result<()> {
+        *self.shared.lock_stopped() = true;
+        self.shared.condvar.notify_all();
+
+        if let Some(thread) = self.thread.take() {
+            thread.join().unwrap_or_else(|payload| panic::resume_unwind(payload));
+        }
+
+        let subscriptions = mem::take(&mut *self.shared.lock_subscriptions());
+
+        subscriptions
+            .into_values()
+            .map(Subscription::unsubscribe)
+            .fold(Ok(()), Result::and)
+    }
+}
+
+impl<F> Drop for PrefixWatch<F> {
+    fn drop(&mut self) {
+        let _ = self.stop_internal();
+    }
+}
+
+// We cannot derive this because that would impose an unnecessary trait bound `F: Debug`
+impl<F> Debug for PrefixWatch<F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PrefixWatch")
+            .field("owner_tag", &self.shared.owner_tag)
+            .field("state_names", &self.state_names())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The state shared between a [`PrefixWatch<F>`](PrefixWatch) and its background thread
+struct Shared<F> {
+    owner_tag: String,
+    listener: Arc<Mutex<F>>,
+    subscriptions: Mutex<HashMap<StateName, Subscription<'static, GroupMemberListener<F>>>>,
+    stopped: Mutex<bool>,
+    condvar: Condvar,
+}
+
+impl<F> Shared<F>
+where
+    F: FnMut(StateName, DataAccessor<'_, OpaqueData>) + Send + 'static,
+{
+    /// Enumerates the registered state names and subscribes the listener to those it is not yet subscribed to
+    fn rescan(&self) -> io::Result<()> {
+        let state_names = registry::registered_state_names(StateLifetime::WellKnown)?;
+        let mut subscriptions = self.lock_subscriptions();
+
+        for state_name in state_names {
+            if subscriptions.contains_key(&state_name) || !matches_owner_tag(state_name, &self.owner_tag) {
+                continue;
+            }
+
+            let raw = RawState::<OpaqueData>::from_state_name_and_type_id(state_name, TypeId::none());
+            let listener = GroupMemberListener {
+                state_name,
+                listener: Arc::clone(&self.listener),
+            };
+
+            // Failing to subscribe to a single state is not fatal, subscribing is retried on the next rescan
+            if let Ok(subscription) = raw.subscribe(listener, SeenChangeStamp::Current) {
+                subscriptions.insert(state_name, subscription);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<F> Shared<F> {
+    /// Waits until the given deadline, returning early with `true` if the watch has been stopped
+    fn wait_stopped_until(&self, deadline: Instant) -> bool {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+
+        let (stopped, _) = self
+            .condvar
+            .wait_timeout_while(self.lock_stopped(), timeout, |stopped| !*stopped)
+            .unwrap_or_else(PoisonError::into_inner);
+
+        *stopped
+    }
+
+    /// Locks the flag indicating whether the watch has been stopped
+    fn lock_stopped(&self) -> MutexGuard<'_, bool> {
+        self.stopped.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Locks the subscriptions of the watch
+    fn lock_subscriptions(&self) -> MutexGuard<'_, HashMap<StateName, Subscription<'static, GroupMemberListener<F>>>> {
+        // We can access the subscriptions even when the mutex is poisoned because every entry is valid on its own
+        self.subscriptions.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Re-enumerates the registered state names in the given interval until the watch is stopped
+///
+/// This runs on the background thread of a [`PrefixWatch<F>`](PrefixWatch).
+fn rescan_periodically<F>(shared: &Shared<F>, rescan_interval: Duration)
+where
+    F: FnMut(StateName, DataAccessor<'_, OpaqueData>) + Send + 'static,
+{
+    while !shared.wait_stopped_until(Instant::now() + rescan_interval) {
+        // Errors are not fatal, enumerating is retried on the next rescan
+        let _ = shared.rescan();
+    }
+}
+
+/// Returns whether the owner tag of the given state name starts with the given prefix
+fn matches_owner_tag(state_name: StateName, owner_tag: &str) -> bool {
+    StateNameDescriptor::try_from(state_name).is_ok_and(|descriptor| descriptor.matches_prefix(owner_tag))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use static_assertions::{assert_impl_all, assert_not_impl_any};
+
+    use super::*;
+
+    #[test]
+    fn prefix_watch_is_send_and_sync_if_listener_is_send() {
+        type SendNotSync = Cell<()>;
+        assert_impl_all!(SendNotSync: Send);
+        assert_not_impl_any!(SendNotSync: Sync);
+
+        assert_impl_all!(PrefixWatch<SendNotSync>: Send, Sync);
+    }
+
+    #[test]
+    fn matches_owner_tag_filters_by_prefix() {
+        // `WNF_SHEL_DESKTOP_APPLICATION_STARTED`
+        let state_name = StateName::from_opaque_value(0x0D83_063E_A3BE_5075);
+
+        assert!(matches_owner_tag(state_name, "SH"));
+        assert!(matches_owner_tag(state_name, "SHEL"));
+        assert!(!matches_owner_tag(state_name, "PO"));
+    }
+}
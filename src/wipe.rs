@@ -0,0 +1,135 @@
+//! Wiping intermediate buffers that may contain state data
+//!
+//! When the `zeroize` feature is enabled, the functions in this module overwrite the given memory with zeros in a way
+//! that is not optimized away by the compiler. Otherwise, they are no-ops (except for [`into_boxed_slice`], which
+//! then behaves like [`Vec::into_boxed_slice`]).
+
+use std::mem::MaybeUninit;
+#[cfg(feature = "zeroize")]
+use std::{mem, slice};
+
+#[cfg(feature = "zeroize")]
+use zeroize::Zeroize;
+
+use crate::bytes::AnyBitPattern;
+
+/// Overwrites the given value with zeros
+pub(crate) fn wipe<B>(value: &mut B)
+where
+    B: AnyBitPattern,
+{
+    // SAFETY:
+    // `MaybeUninit<B>` has the same memory layout as `B`, and writing zeros to a `B` leaves it valid because
+    // `B: AnyBitPattern`
+    wipe_uninit(unsafe { &mut *(value as *mut B).cast::<MaybeUninit<B>>() });
+}
+
+/// Overwrites the given possibly uninitialized value with zeros
+pub(crate) fn wipe_uninit<B>(value: &mut MaybeUninit<B>)
+where
+    B: AnyBitPattern,
+{
+    #[cfg(feature = "zeroize")]
+    {
+        // SAFETY:
+        // - `value.as_mut_ptr()` is valid for writes of `mem::size_of::<B>()` bytes because it comes from a live
+        //   mutable reference
+        // - Any byte pattern is a valid `MaybeUninit<u8>`
+        let bytes =
+            unsafe { slice::from_raw_parts_mut(value.as_mut_ptr().cast::<MaybeUninit<u8>>(), mem::size_of::<B>()) };
+        bytes.zeroize();
+    }
+
+    #[cfg(not(feature = "zeroize"))]
+    let _ = value;
+}
+
+/// Overwrites the given slice with zeros
+#[cfg(windows)]
+pub(crate) fn wipe_slice<B>(slice: &mut [B])
+where
+    B: AnyBitPattern,
+{
+    for value in slice {
+        wipe(value);
+    }
+}
+
+/// Overwrites the whole allocation of the given vector with zeros, including its spare capacity
+pub(crate) fn wipe_vec<B>(vec: &mut Vec<B>)
+where
+    B: AnyBitPattern,
+{
+    #[cfg(feature = "zeroize")]
+    {
+        // SAFETY:
+        // - `vec.as_mut_ptr()` is valid for writes of `vec.capacity() * mem::size_of::<B>()` bytes because that is the
+        //   size of the allocation of `vec`
+        // - Any byte pattern is a valid `MaybeUninit<u8>`
+        // - Writing zeros to the initialized elements of `vec` leaves them valid because `B: AnyBitPattern`
+        let bytes = unsafe {
+            slice::from_raw_parts_mut(
+                vec.as_mut_ptr().cast::<MaybeUninit<u8>>(),
+                vec.capacity() * mem::size_of::<B>(),
+            )
+        };
+        bytes.zeroize();
+    }
+
+    #[cfg(not(feature = "zeroize"))]
+    let _ = vec;
+}
+
+/// Converts the given vector into a boxed slice
+///
+/// Unlike [`Vec::into_boxed_slice`], this makes sure that the original allocation is wiped before it is deallocated in
+/// case the vector has spare capacity (which would otherwise cause a reallocation).
+pub(crate) fn into_boxed_slice<B>(mut vec: Vec<B>) -> Box<[B]>
+where
+    B: AnyBitPattern,
+{
+    if cfg!(feature = "zeroize") && vec.len() != vec.capacity() {
+        let data: Box<[B]> = vec.as_slice().into();
+        wipe_vec(&mut vec);
+        data
+    } else {
+        vec.into_boxed_slice()
+    }
+}
+
+#[cfg(all(test, feature = "zeroize"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wipe_overwrites_value() {
+        let mut value = 0x1234_5678_u32;
+
+        wipe(&mut value);
+
+        assert_eq!(value, 0);
+    }
+
+    #[test]
+    fn wipe_vec_overwrites_spare_capacity() {
+        let mut vec = vec![1_u8, 2, 3, 4];
+        vec.truncate(2);
+
+        wipe_vec(&mut vec);
+
+        // SAFETY:
+        // The spare capacity of `vec` has been initialized with zeros by `wipe_vec`
+        unsafe { vec.set_len(4) };
+        assert_eq!(vec, [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn into_boxed_slice_keeps_elements() {
+        let mut vec = Vec::with_capacity(4);
+        vec.extend_from_slice(&[1_u8, 2]);
+
+        let data = into_boxed_slice(vec);
+
+        assert_eq!(*data, [1, 2]);
+    }
+}
@@ -3,8 +3,9 @@ use std::fmt::{self, Debug, Display, Formatter};
 use std::io::ErrorKind;
 use std::sync::Arc;
 use std::thread;
+use std::time::Duration;
 
-use wnf::OwnedState;
+use wnf::{AsState, Backoff, ContentionError, OwnedState, RetryPolicy, SliceIndexError};
 
 #[test]
 fn apply() {
@@ -148,6 +149,32 @@ fn apply_boxed_slice_to_vec_concurrent() {
     assert_eq!(state.get_boxed().unwrap().len(), NUM_THREADS * NUM_ITERATIONS);
 }
 
+#[test]
+fn update_slice_element() {
+    let state = OwnedState::<[u32]>::create_temporary().unwrap();
+    state.set(&[1, 2, 3]).unwrap();
+
+    let result = state.update_slice_element(1, &42).unwrap();
+
+    assert_eq!(*result, [1, 42, 3]);
+    assert_eq!(*state.get_boxed().unwrap(), [1, 42, 3]);
+}
+
+#[test]
+fn update_slice_element_out_of_bounds() {
+    let state = OwnedState::<[u32]>::create_temporary().unwrap();
+    state.set(&[1, 2, 3]).unwrap();
+
+    let err = state.as_state().update_slice_element(3, &42).unwrap_err();
+
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    assert_eq!(
+        err.get_ref().unwrap().downcast_ref::<SliceIndexError>(),
+        Some(&SliceIndexError { index: 3, len: 3 })
+    );
+    assert_eq!(*state.get_boxed().unwrap(), [1, 2, 3]);
+}
+
 #[derive(Debug, Eq, Hash, PartialEq)]
 struct TestError;
 
@@ -158,3 +185,76 @@ impl Display for TestError {
 }
 
 impl Error for TestError {}
+
+#[test]
+fn apply_with_policy_without_contention() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    state.set(&42).unwrap();
+
+    let (result, attempts) = state
+        .apply_with_policy(|value| value + 1, RetryPolicy::default())
+        .unwrap();
+
+    assert_eq!(result, 43);
+    assert_eq!(attempts, 1);
+    assert_eq!(state.get().unwrap(), 43);
+}
+
+#[test]
+fn apply_with_policy_with_contention() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    state.set(&42).unwrap();
+
+    let mut calls = 0;
+    let policy = RetryPolicy {
+        max_retries: 5,
+        backoff: Backoff::Fixed(Duration::from_millis(1)),
+    };
+
+    let (result, attempts) = state
+        .apply_with_policy(
+            |value| {
+                calls += 1;
+                if calls == 1 {
+                    // Simulate a concurrent update
+                    state.set(&100).unwrap();
+                }
+                value + 1
+            },
+            policy,
+        )
+        .unwrap();
+
+    assert_eq!(result, 101);
+    assert_eq!(attempts, 2);
+    assert_eq!(state.get().unwrap(), 101);
+}
+
+#[test]
+fn apply_boxed_with_policy_retries_exhausted() {
+    let state = OwnedState::<[u32]>::create_temporary().unwrap();
+    state.set(&[42]).unwrap();
+
+    let policy = RetryPolicy {
+        max_retries: 2,
+        backoff: Backoff::None,
+    };
+
+    let err = state
+        .apply_boxed_with_policy(
+            |slice| {
+                // Simulate a concurrent update on every attempt
+                state.set(&[0]).unwrap();
+                slice.into_vec()
+            },
+            policy,
+        )
+        .unwrap_err();
+
+    assert_eq!(err.kind(), ErrorKind::Other);
+    assert_eq!(
+        err.get_ref().unwrap().downcast_ref::<ContentionError>(),
+        Some(&ContentionError { attempts: 3 })
+    );
+    assert_eq!(*state.get_boxed().unwrap(), [0]);
+}
@@ -0,0 +1,70 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use wnf::{BroadcastAdapter, OwnedState};
+
+#[tokio::test]
+async fn broadcast_adapter_fans_out_updates() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    state.set(&0).unwrap();
+
+    let adapter = BroadcastAdapter::new(&state).unwrap();
+    let mut receivers = [adapter.receiver(), adapter.receiver(), adapter.receiver()];
+    assert_eq!(adapter.receiver_count(), 3);
+
+    state.set(&1).unwrap();
+
+    for receiver in &mut receivers {
+        let data = tokio::time::timeout(
+            Duration::from_secs(1),
+            receiver.wait_for(|data| data.as_ref().is_some_and(|data| *data.data() == 1)),
+        )
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(data.change_stamp(), 2);
+    }
+
+    adapter.unsubscribe().unwrap();
+}
+
+#[tokio::test]
+async fn broadcast_adapter_boxed() {
+    let state = OwnedState::<[u32]>::create_temporary().unwrap();
+
+    let adapter = BroadcastAdapter::new_boxed(&state).unwrap();
+    let mut receiver = adapter.receiver();
+
+    assert!(receiver.borrow().is_none());
+
+    state.set(&[1, 2, 3]).unwrap();
+
+    let data = tokio::time::timeout(Duration::from_secs(1), receiver.wait_for(|data| data.is_some()))
+        .await
+        .unwrap()
+        .unwrap()
+        .clone()
+        .unwrap();
+
+    assert_eq!(*data.into_data(), [1, 2, 3]);
+}
+
+#[test]
+fn broadcast_adapter_receiver_without_runtime() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+
+    let adapter = BroadcastAdapter::new(&state).unwrap();
+    let receiver = adapter.receiver();
+
+    state.set(&42).unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(1);
+    while receiver.borrow().is_none() {
+        assert!(Instant::now() < deadline, "timed out waiting for update");
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    assert_eq!(receiver.borrow().as_ref().map(|data| *data.data()), Some(42));
+}
@@ -0,0 +1,90 @@
+use std::time::Duration;
+
+use tokio::time;
+use wnf::{ChannelName, OwnedState};
+
+#[tokio::test]
+async fn channel_send_recv() {
+    let (publisher, mut subscriber) = wnf::channel::<u32>(ChannelName::New).unwrap();
+
+    let handle = tokio::spawn(async move {
+        let value = time::timeout(Duration::from_secs(3), subscriber.recv())
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(value, 42);
+    });
+
+    time::sleep(Duration::from_millis(300)).await;
+    publisher.send(&42).unwrap();
+
+    handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn channel_receives_only_latest_value() {
+    let (publisher, mut subscriber) = wnf::channel::<u32>(ChannelName::New).unwrap();
+
+    for value in 1..=10 {
+        publisher.send(&value).unwrap();
+    }
+
+    time::sleep(Duration::from_millis(300)).await;
+
+    let value = time::timeout(Duration::from_secs(1), subscriber.recv())
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(value, 10);
+    assert!(subscriber.try_recv().is_none());
+}
+
+#[tokio::test]
+async fn channel_existing_state() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+
+    let (publisher, mut subscriber) = wnf::channel::<u32>(state.state_name()).unwrap();
+    assert_eq!(publisher.state_name(), state.state_name());
+    assert_eq!(subscriber.state_name(), state.state_name());
+
+    state.set(&42).unwrap();
+
+    let value = time::timeout(Duration::from_secs(3), subscriber.recv())
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(value, 42);
+
+    drop(publisher);
+    drop(subscriber);
+
+    // The channel does not delete an existing state
+    assert!(state.exists().unwrap());
+}
+
+#[tokio::test]
+async fn channel_multiple_subscribers() {
+    let (publisher, mut subscriber) = wnf::channel::<u32>(ChannelName::New).unwrap();
+    let mut other_subscriber = publisher.clone().subscribe().unwrap();
+
+    publisher.send(&42).unwrap();
+
+    for subscriber in [&mut subscriber, &mut other_subscriber] {
+        let value = time::timeout(Duration::from_secs(3), subscriber.recv())
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(value, 42);
+    }
+}
+
+#[test]
+fn channel_try_recv_without_values() {
+    let (_publisher, mut subscriber) = wnf::channel::<u32>(ChannelName::New).unwrap();
+
+    assert!(subscriber.try_recv().is_none());
+}
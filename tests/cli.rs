@@ -0,0 +1,62 @@
+#![cfg(feature = "cli")]
+
+use std::process::{Command, Output};
+
+use wnf::OwnedState;
+
+#[test]
+fn set_and_query() {
+    let state = OwnedState::<[u8]>::create_temporary().unwrap();
+    let state_name = format!("{:x}", state.state_name());
+
+    let output = run(&["set", &state_name, "01ab"]);
+    assert!(output.status.success());
+    assert_eq!(*state.get_boxed().unwrap(), [0x01, 0xAB]);
+
+    let output = run(&["query", &state_name]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "1: 01AB\n");
+}
+
+#[test]
+fn dump() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    state.set(&0x1234_5678).unwrap();
+
+    let output = run(&["dump", &state.state_name().to_string()]);
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Exists:              true"));
+    assert!(stdout.contains("Data:                78563412"));
+}
+
+#[test]
+fn dump_registered() {
+    let output = run(&["dump"]);
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Exists:"));
+}
+
+#[test]
+fn invalid_hex_data() {
+    let state = OwnedState::<[u8]>::create_temporary().unwrap();
+
+    let output = run(&["set", &state.state_name().to_string(), "0x1"]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("invalid hex data"));
+}
+
+#[test]
+fn invalid_usage() {
+    let output = run(&["query"]);
+
+    assert_eq!(output.status.code(), Some(2));
+}
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_wnf-cli")).args(args).output().unwrap()
+}
@@ -0,0 +1,35 @@
+use std::io::ErrorKind;
+
+use wnf::{CompressedState, OwnedState, MAXIMUM_STATE_SIZE};
+
+#[test]
+fn set_and_get() {
+    let state = OwnedState::<[u8]>::create_temporary().unwrap();
+    let compressed_state = CompressedState::<u32>::new(&state);
+
+    compressed_state.set(&42).unwrap();
+
+    assert_eq!(compressed_state.get().unwrap(), 42);
+}
+
+#[test]
+fn set_and_get_boxed_exceeding_maximum_state_size() {
+    let state = OwnedState::<[u8]>::create_temporary().unwrap();
+    let compressed_state = CompressedState::<[u32]>::new(&state);
+    let data: Vec<u32> = (0..MAXIMUM_STATE_SIZE as u32).map(|i| i % 16).collect();
+
+    compressed_state.set(&data).unwrap();
+
+    assert!(state.get_boxed().unwrap().len() < MAXIMUM_STATE_SIZE);
+    assert_eq!(*compressed_state.get_boxed().unwrap(), *data);
+}
+
+#[test]
+fn get_uncompressed_data() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    state.set(&42).unwrap();
+
+    let err = CompressedState::<u32>::new(&state).get().unwrap_err();
+
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+}
@@ -0,0 +1,82 @@
+use std::io::ErrorKind;
+use std::sync::{Arc, Barrier};
+use std::thread;
+use std::time::Duration;
+
+use wnf::{OwnedState, StateCondvar};
+
+#[test]
+fn wait_while_returns_immediately_if_condition_is_not_satisfied() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    state.set(&42).unwrap();
+
+    let condvar = StateCondvar::new(&state).unwrap();
+    let value = condvar.wait_while(|value| *value < 10, Duration::ZERO).unwrap();
+
+    assert_eq!(value, 42);
+}
+
+#[test]
+fn wait_while_waits_until_condition_is_not_satisfied() {
+    let state = Arc::new(OwnedState::<u32>::create_temporary().unwrap());
+    state.set(&0).unwrap();
+
+    let condvar = StateCondvar::new(&*state).unwrap();
+
+    let handle = {
+        let state = Arc::clone(&state);
+        thread::spawn(move || {
+            for value in 1..=10 {
+                state.set(&value).unwrap();
+            }
+        })
+    };
+
+    let value = condvar.wait_while(|value| *value < 10, Duration::from_secs(1)).unwrap();
+    assert_eq!(value, 10);
+
+    handle.join().unwrap();
+    condvar.unsubscribe().unwrap();
+}
+
+#[test]
+fn wait_while_with_multiple_waiters() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    state.set(&0).unwrap();
+
+    let condvar = StateCondvar::new(&state).unwrap();
+    let barrier = Barrier::new(3);
+
+    thread::scope(|scope| {
+        let waiters: Vec<_> = (0..2)
+            .map(|_| {
+                scope.spawn(|| {
+                    barrier.wait();
+                    condvar.wait_while(|value| *value == 0, Duration::from_secs(1))
+                })
+            })
+            .collect();
+
+        barrier.wait();
+        state.set(&1).unwrap();
+
+        for waiter in waiters {
+            assert_eq!(waiter.join().unwrap().unwrap(), 1);
+        }
+    });
+
+    assert_eq!(condvar.latest().unwrap().into_data_change_stamp(), (1, 2.into()));
+}
+
+#[test]
+fn wait_while_times_out() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    state.set(&0).unwrap();
+
+    let condvar = StateCondvar::new(&state).unwrap();
+    let err = condvar
+        .wait_while(|value| *value == 0, Duration::from_millis(10))
+        .unwrap_err();
+
+    assert_eq!(err.kind(), ErrorKind::TimedOut);
+}
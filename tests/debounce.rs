@@ -0,0 +1,63 @@
+use std::thread;
+use std::time::Duration;
+
+use wnf::{DebouncedPublisher, OwnedState};
+
+#[test]
+fn coalesce_updates_within_window() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    state.set(&0).unwrap();
+    let change_stamp = state.change_stamp().unwrap();
+
+    let publisher = DebouncedPublisher::new(&state, Duration::from_millis(100)).unwrap();
+    assert_eq!(publisher.window(), Duration::from_millis(100));
+
+    for value in 1..=10 {
+        publisher.set(&value).unwrap();
+    }
+
+    assert_eq!(state.get().unwrap(), 1);
+
+    thread::sleep(Duration::from_millis(500));
+
+    assert_eq!(state.get().unwrap(), 10);
+    assert_eq!(state.change_stamp().unwrap(), change_stamp + 2);
+
+    publisher.stop().unwrap();
+}
+
+#[test]
+fn flush_publishes_pending_data() {
+    let state = OwnedState::<[u16]>::create_temporary().unwrap();
+
+    let publisher = DebouncedPublisher::new(&state, Duration::from_secs(60)).unwrap();
+    publisher.set(&[1, 2]).unwrap();
+    publisher.set(&[3, 4, 5]).unwrap();
+
+    assert_eq!(*state.get_boxed().unwrap(), [1, 2]);
+
+    publisher.flush().unwrap();
+
+    assert_eq!(*state.get_boxed().unwrap(), [3, 4, 5]);
+}
+
+#[test]
+fn stop_on_drop_publishes_pending_data() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+
+    {
+        let publisher = DebouncedPublisher::new(&state, Duration::from_secs(60)).unwrap();
+        publisher.set(&1).unwrap();
+        publisher.set(&2).unwrap();
+    }
+
+    assert_eq!(state.get().unwrap(), 2);
+}
+
+#[test]
+#[should_panic(expected = "debounce window must not be zero")]
+fn zero_window() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+
+    let _ = DebouncedPublisher::new(&state, Duration::ZERO);
+}
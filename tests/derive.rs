@@ -0,0 +1,65 @@
+use wnf::{OwnedState, WnfStateData};
+
+#[derive(WnfStateData, Clone, Copy, Debug, PartialEq)]
+#[repr(C)]
+struct NamedFields {
+    a: u32,
+    b: u16,
+    c: u16,
+}
+
+#[derive(WnfStateData, Clone, Copy, Debug, PartialEq)]
+#[repr(transparent)]
+struct Transparent(u64);
+
+#[derive(WnfStateData, Clone, Copy, Debug, PartialEq)]
+#[repr(C)]
+struct Nested {
+    inner: NamedFields,
+    values: [u8; 8],
+}
+
+#[derive(WnfStateData, Clone, Copy, Debug, PartialEq)]
+#[repr(C)]
+struct Unit;
+
+#[test]
+fn derive_named_fields() {
+    let state = OwnedState::<NamedFields>::create_temporary().unwrap();
+    let value = NamedFields { a: 1, b: 2, c: 3 };
+
+    state.set(&value).unwrap();
+
+    assert_eq!(state.get().unwrap(), value);
+}
+
+#[test]
+fn derive_transparent() {
+    let state = OwnedState::<Transparent>::create_temporary().unwrap();
+
+    state.set(&Transparent(42)).unwrap();
+
+    assert_eq!(state.get().unwrap(), Transparent(42));
+}
+
+#[test]
+fn derive_nested() {
+    let state = OwnedState::<[Nested]>::create_temporary().unwrap();
+    let values = [Nested {
+        inner: NamedFields { a: 1, b: 2, c: 3 },
+        values: [4; 8],
+    }];
+
+    state.set(&values).unwrap();
+
+    assert_eq!(*state.get_boxed().unwrap(), values);
+}
+
+#[test]
+fn derive_unit() {
+    let state = OwnedState::<Unit>::create_temporary().unwrap();
+
+    state.set(&Unit).unwrap();
+
+    assert_eq!(state.get().unwrap(), Unit);
+}
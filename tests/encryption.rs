@@ -0,0 +1,32 @@
+use wnf::{EncryptedState, OwnedState, ProtectionScope};
+
+#[test]
+fn set_and_get() {
+    let state = OwnedState::<[u8]>::create_temporary().unwrap();
+    let encrypted_state = EncryptedState::<u32>::new(&state);
+
+    encrypted_state.set(&42).unwrap();
+
+    assert_ne!(*state.get_boxed().unwrap(), 42u32.to_ne_bytes());
+    assert_eq!(encrypted_state.get().unwrap(), 42);
+}
+
+#[test]
+fn set_and_get_boxed_with_local_machine_scope() {
+    let state = OwnedState::<[u8]>::create_temporary().unwrap();
+    let encrypted_state = EncryptedState::<[u16]>::with_scope(&state, ProtectionScope::LocalMachine);
+
+    encrypted_state.set(&[1, 2, 3]).unwrap();
+
+    assert_eq!(*EncryptedState::<[u16]>::new(&state).get_boxed().unwrap(), [1, 2, 3]);
+}
+
+#[test]
+fn get_unencrypted_data() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    state.set(&42).unwrap();
+
+    let result = EncryptedState::<u32>::new(&state).get();
+
+    assert!(result.is_err());
+}
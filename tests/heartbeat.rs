@@ -0,0 +1,43 @@
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use wnf::{Heartbeat, HeartbeatPublisher, OwnedState};
+
+#[test]
+fn publish_heartbeats() {
+    let state = OwnedState::<Heartbeat>::create_temporary().unwrap();
+    let before = SystemTime::now() - Duration::from_secs(1);
+
+    let publisher = HeartbeatPublisher::new(&state, Duration::from_millis(10)).unwrap();
+    assert_eq!(publisher.interval(), Duration::from_millis(10));
+    assert_eq!(state.get().unwrap().sequence_number(), 0);
+
+    thread::sleep(Duration::from_millis(200));
+    publisher.stop().unwrap();
+
+    let heartbeat = state.get().unwrap();
+    assert!(heartbeat.sequence_number() > 0);
+    assert!(heartbeat.published_at() >= before);
+
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(state.get().unwrap(), heartbeat);
+}
+
+#[test]
+fn stop_on_drop() {
+    let state = OwnedState::<Heartbeat>::create_temporary().unwrap();
+
+    drop(HeartbeatPublisher::new(&state, Duration::from_millis(10)).unwrap());
+    let heartbeat = state.get().unwrap();
+
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(state.get().unwrap(), heartbeat);
+}
+
+#[test]
+#[should_panic(expected = "heartbeat interval must not be zero")]
+fn zero_interval() {
+    let state = OwnedState::<Heartbeat>::create_temporary().unwrap();
+
+    let _ = HeartbeatPublisher::new(&state, Duration::ZERO);
+}
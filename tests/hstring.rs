@@ -0,0 +1,41 @@
+use windows::core::{BSTR, HSTRING};
+use wnf::{AsState, OwnedState, WideString};
+
+#[test]
+fn set_get_hstring() {
+    let state = OwnedState::<WideString>::create_temporary().unwrap();
+
+    state.set_hstring(&HSTRING::from("Hello")).unwrap();
+
+    assert_eq!(state.get_hstring().unwrap(), "Hello");
+    assert_eq!(state.get().unwrap().as_wide(), &*HSTRING::from("Hello"));
+}
+
+#[test]
+fn set_get_bstr() {
+    let state = OwnedState::<WideString>::create_temporary().unwrap();
+
+    state.as_state().set_bstr(&BSTR::from("Hello")).unwrap();
+
+    assert_eq!(state.as_state().get_bstr().unwrap(), "Hello");
+}
+
+#[test]
+fn get_hstring_terminated_at_nul() {
+    let state = OwnedState::<[u16]>::create_temporary().unwrap();
+    state.set(&[0x0061, 0x0062, 0x0000, 0x0063]).unwrap();
+    let state: OwnedState<WideString> = state.cast();
+
+    assert_eq!(state.get_hstring().unwrap(), "ab");
+}
+
+#[test]
+fn wide_string_into_hstring_and_bstr() {
+    let state = OwnedState::<WideString>::create_temporary().unwrap();
+    state.set_hstring(&HSTRING::from("Hello")).unwrap();
+
+    let data = state.get().unwrap();
+
+    assert_eq!(HSTRING::from(data.clone()), "Hello");
+    assert_eq!(BSTR::from(data), "Hello");
+}
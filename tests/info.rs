@@ -1,5 +1,6 @@
 use wnf::{
-    BorrowedState, DataAccessor, DataScope, OwnedState, SeenChangeStamp, StateLifetime, StateName, StateNameDescriptor,
+    BorrowedState, BoxedSecurityDescriptor, CreatableStateLifetime, DataAccessor, DataScope, OwnedState,
+    SeenChangeStamp, StateCreation, StateLifetime, StateName, StateNameDescriptor,
 };
 
 #[test]
@@ -30,6 +31,33 @@ fn not_exists() {
     assert!(!exists);
 }
 
+#[test]
+fn change_stamp_info() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    state.set(&42).unwrap();
+
+    let info = state.change_stamp_info().unwrap();
+
+    assert!(info.exists());
+    assert_eq!(info.change_stamp(), Some(state.change_stamp().unwrap()));
+}
+
+#[test]
+fn change_stamp_info_deleted() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    state.set(&42).unwrap();
+    let state_name = state.state_name();
+
+    state.delete().unwrap();
+
+    let info = BorrowedState::<u32>::from_state_name(state_name)
+        .change_stamp_info()
+        .unwrap();
+
+    assert!(!info.exists());
+    assert_eq!(info.change_stamp(), None);
+}
+
 #[test]
 fn subscribers_present() {
     let state = OwnedState::<()>::create_temporary().unwrap();
@@ -65,3 +93,133 @@ fn is_quiescent() {
     subscription.unsubscribe().unwrap();
     assert!(state.is_quiescent().unwrap());
 }
+
+#[test]
+fn describe() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    state.set(&42).unwrap();
+
+    let report = state.describe().unwrap();
+
+    assert_eq!(report.state_name, state.state_name());
+    assert_eq!(report.descriptor.unwrap().lifetime, StateLifetime::Temporary);
+    assert!(report.exists);
+    assert_eq!(report.subscribers_present, Some(false));
+    assert_eq!(report.is_quiescent, Some(true));
+    assert_eq!(report.change_stamp, Some(1.into()));
+    assert_eq!(report.data_size, Some(4));
+}
+
+#[test]
+fn describe_not_exists() {
+    let state = BorrowedState::<()>::from_state_name(
+        StateName::try_from(StateNameDescriptor {
+            version: 1,
+            lifetime: StateLifetime::Temporary,
+            data_scope: DataScope::Machine,
+            is_permanent: false,
+            unique_id: 0,
+            owner_tag: 1, // this must be `0` for non-well-known state names, so such a state name cannot exist
+        })
+        .unwrap(),
+    );
+
+    let report = state.describe().unwrap();
+
+    assert!(!report.exists);
+    assert_eq!(report.change_stamp, None);
+    assert_eq!(report.data_size, None);
+}
+
+#[test]
+fn capabilities() {
+    let state_creation = StateCreation::new()
+        .lifetime(CreatableStateLifetime::Temporary)
+        .scope(DataScope::Machine);
+
+    let sd_all: BoxedSecurityDescriptor = "D:(A;;GA;;;WD)".parse().unwrap();
+    let sd_readonly: BoxedSecurityDescriptor = "D:(A;;GR;;;WD)".parse().unwrap();
+    let sd_none: BoxedSecurityDescriptor = "D:(A;;;;;WD)".parse().unwrap();
+
+    let state = state_creation
+        .security_descriptor(sd_all)
+        .create_owned::<u32>()
+        .unwrap();
+    state.set(&42).unwrap();
+
+    let capabilities = state.capabilities().unwrap();
+
+    assert!(capabilities.can_read);
+    assert!(capabilities.can_write);
+    assert!(capabilities.can_subscribe);
+    assert_eq!(state.get().unwrap(), 42);
+    assert_eq!(state.change_stamp().unwrap(), 1);
+
+    let state = state_creation
+        .security_descriptor(sd_readonly)
+        .create_owned::<u32>()
+        .unwrap();
+
+    let capabilities = state.capabilities().unwrap();
+
+    assert!(capabilities.can_read);
+    assert!(!capabilities.can_write);
+    assert!(capabilities.can_subscribe);
+
+    let state = state_creation
+        .security_descriptor(sd_none)
+        .create_owned::<u32>()
+        .unwrap();
+
+    let capabilities = state.capabilities().unwrap();
+
+    assert!(!capabilities.can_read);
+    assert!(!capabilities.can_write);
+    assert!(!capabilities.can_subscribe);
+}
+
+#[test]
+fn capabilities_not_exists() {
+    let state = BorrowedState::<()>::from_state_name(
+        StateName::try_from(StateNameDescriptor {
+            version: 1,
+            lifetime: StateLifetime::Temporary,
+            data_scope: DataScope::Machine,
+            is_permanent: false,
+            unique_id: 0,
+            owner_tag: 1, // this must be `0` for non-well-known state names, so such a state name cannot exist
+        })
+        .unwrap(),
+    );
+
+    assert!(state.capabilities().is_err());
+}
+
+#[test]
+fn snapshot() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    state.set(&0x1234_5678).unwrap();
+
+    let snapshot = state.snapshot().unwrap();
+
+    assert_eq!(snapshot.state_name, state.state_name());
+    assert_eq!(snapshot.descriptor.unwrap().lifetime, StateLifetime::Temporary);
+    assert_eq!(snapshot.change_stamp, 1);
+    assert_eq!(*snapshot.data, 0x1234_5678_u32.to_ne_bytes());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn describe_serialize() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    state.set(&42).unwrap();
+
+    let report = state.describe().unwrap();
+    let json = serde_json::to_value(&report).unwrap();
+
+    assert_eq!(json["state_name"], state.state_name().opaque_value());
+    assert_eq!(json["descriptor"]["lifetime"], "Temporary");
+    assert_eq!(json["exists"], true);
+    assert_eq!(json["change_stamp"], 1);
+    assert_eq!(json["data_size"], 4);
+}
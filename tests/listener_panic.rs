@@ -0,0 +1,39 @@
+use std::time::Duration;
+
+use wnf::{DataAccessor, OwnedState, SeenChangeStamp};
+
+#[test]
+fn listener_panic_during_replay() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+
+    let (panic_tx, panic_rx) = crossbeam_channel::unbounded();
+
+    wnf::set_listener_panic_hook(move |panic_info, state_name| {
+        let _ = panic_tx.send((panic_info.to_string(), state_name));
+    });
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    let subscription = state
+        .subscribe_lock_free(
+            move |accessor: DataAccessor<_>| {
+                if accessor.change_stamp() == 0 {
+                    panic!("listener panicked during replay");
+                }
+
+                tx.send(accessor.get().unwrap()).unwrap();
+            },
+            SeenChangeStamp::Replay,
+        )
+        .unwrap();
+
+    let (message, state_name) = panic_rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert!(message.contains("listener panicked during replay"));
+    assert_eq!(state_name, state.state_name());
+
+    state.set(&42).unwrap();
+
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), 42);
+
+    subscription.unsubscribe().unwrap();
+}
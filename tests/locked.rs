@@ -0,0 +1,133 @@
+use std::io::ErrorKind;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use wnf::{LockTimeoutError, LockedState, OwnedState};
+
+/// Returns the data of a lock state held by some other writer since the given number of milliseconds since the UNIX
+/// epoch
+fn held_since(unix_time_millis: u64) -> Vec<u8> {
+    let mut data = vec![1, 0, 0, 0, 0x78, 0x56, 0x34, 0x12];
+    data.extend_from_slice(&unix_time_millis.to_le_bytes());
+    data
+}
+
+#[test]
+fn apply_releases_lock() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    let lock_state = OwnedState::<[u8]>::create_temporary().unwrap();
+    state.set(&42).unwrap();
+
+    let locked_state = LockedState::new(&state, &lock_state);
+    let new_data = locked_state.apply(|value| value + 1).unwrap();
+
+    assert_eq!(new_data, 43);
+    assert_eq!(state.get().unwrap(), 43);
+    assert_eq!(locked_state.holder().unwrap(), None);
+}
+
+#[test]
+fn apply_boxed() {
+    let state = OwnedState::<[u32]>::create_temporary().unwrap();
+    let lock_state = OwnedState::<[u8]>::create_temporary().unwrap();
+    state.set(&[1, 2]).unwrap();
+
+    let new_data = LockedState::new(&state, &lock_state)
+        .apply_boxed(|slice| {
+            let mut vec = slice.into_vec();
+            vec.push(3);
+            vec
+        })
+        .unwrap();
+
+    assert_eq!(new_data, [1, 2, 3]);
+    assert_eq!(*state.get_boxed().unwrap(), [1, 2, 3]);
+}
+
+#[test]
+fn apply_times_out_while_lock_held() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    let lock_state = OwnedState::<[u8]>::create_temporary().unwrap();
+    state.set(&42).unwrap();
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+    lock_state.set(&held_since(now)).unwrap();
+
+    let locked_state = LockedState::new(&state, &lock_state)
+        .timeout(Duration::from_millis(50))
+        .lease(Duration::from_secs(3600));
+
+    let holder = locked_state.holder().unwrap();
+    assert!(holder.is_some());
+
+    let err = locked_state.apply(|value| value + 1).unwrap_err();
+
+    assert_eq!(err.kind(), ErrorKind::TimedOut);
+    assert_eq!(
+        err.get_ref().unwrap().downcast_ref::<LockTimeoutError>(),
+        Some(&LockTimeoutError {
+            timeout: Duration::from_millis(50),
+            holder,
+        })
+    );
+    assert_eq!(state.get().unwrap(), 42);
+}
+
+#[test]
+fn apply_steals_expired_lock() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    let lock_state = OwnedState::<[u8]>::create_temporary().unwrap();
+    state.set(&42).unwrap();
+    lock_state.set(&held_since(0)).unwrap();
+
+    let locked_state = LockedState::new(&state, &lock_state).timeout(Duration::ZERO);
+    let new_data = locked_state.apply(|value| value + 1).unwrap();
+
+    assert_eq!(new_data, 43);
+    assert_eq!(locked_state.holder().unwrap(), None);
+}
+
+#[test]
+fn apply_invalid_lock_data() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    let lock_state = OwnedState::<u32>::create_temporary().unwrap();
+    lock_state.set(&42).unwrap();
+
+    let err = LockedState::new(&state, &lock_state)
+        .apply(|value| value + 1)
+        .unwrap_err();
+
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+}
+
+#[test]
+fn apply_concurrently() {
+    const THREADS: u32 = 4;
+    const INCREMENTS: u32 = 50;
+
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    let lock_state = OwnedState::<[u8]>::create_temporary().unwrap();
+    state.set(&0).unwrap();
+
+    thread::scope(|scope| {
+        for _ in 0..THREADS {
+            scope.spawn(|| {
+                let locked_state = LockedState::new(&state, &lock_state);
+
+                for _ in 0..INCREMENTS {
+                    let mut calls = 0;
+                    locked_state
+                        .apply(|value| {
+                            calls += 1;
+                            value + 1
+                        })
+                        .unwrap();
+
+                    assert_eq!(calls, 1);
+                }
+            });
+        }
+    });
+
+    assert_eq!(state.get().unwrap(), THREADS * INCREMENTS);
+}
@@ -1,6 +1,8 @@
+use std::io::ErrorKind;
+
 use wnf::{
-    BorrowedState, BoxedSecurityDescriptor, CreatableStateLifetime, DataScope, OwnedState, StateCreation,
-    StateLifetime, StateNameDescriptor, GUID, MAXIMUM_STATE_SIZE,
+    BorrowedState, BoxedSecurityDescriptor, CreatableStateLifetime, CreateError, DataScope, OwnedState, Privilege,
+    StateCreation, StateLifetime, StateNameDescriptor, GUID, MAXIMUM_STATE_SIZE,
 };
 
 #[test]
@@ -95,6 +97,86 @@ fn create_state_with_maximum_state_size_at_limit() {
         .is_err());
 }
 
+#[test]
+fn create_state_with_maximum_state_size_above_limit() {
+    let err = StateCreation::new()
+        .lifetime(CreatableStateLifetime::Temporary)
+        .scope(DataScope::Machine)
+        .maximum_state_size(MAXIMUM_STATE_SIZE + 1)
+        .create_owned::<()>()
+        .unwrap_err();
+
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    assert_eq!(
+        err.get_ref().unwrap().downcast_ref::<CreateError>(),
+        Some(&CreateError::InvalidMaximumSize {
+            maximum_state_size: MAXIMUM_STATE_SIZE + 1
+        })
+    );
+}
+
+#[test]
+fn required_privilege() {
+    let creation = StateCreation::new().scope(DataScope::Machine);
+
+    assert_eq!(
+        creation
+            .lifetime(CreatableStateLifetime::Temporary)
+            .required_privilege(),
+        None
+    );
+    assert_eq!(
+        creation
+            .lifetime(CreatableStateLifetime::Persistent)
+            .required_privilege(),
+        Some(Privilege::CreatePermanent)
+    );
+    assert_eq!(
+        creation
+            .lifetime(CreatableStateLifetime::Permanent { persist_data: true })
+            .required_privilege(),
+        Some(Privilege::CreatePermanent)
+    );
+    assert_eq!(
+        creation
+            .lifetime(CreatableStateLifetime::Temporary)
+            .scope(DataScope::Process)
+            .required_privilege(),
+        Some(Privilege::CreatePermanent)
+    );
+}
+
+#[test]
+fn create_privileged_state_without_required_privilege() {
+    let state = StateCreation::new()
+        .lifetime(CreatableStateLifetime::Temporary)
+        .scope(DataScope::Machine)
+        .create_owned_privileged::<()>()
+        .unwrap();
+
+    assert!(state.exists().unwrap());
+}
+
+#[test]
+fn create_privileged_state_with_required_privilege() {
+    let result = StateCreation::new()
+        .lifetime(CreatableStateLifetime::Persistent)
+        .scope(DataScope::Machine)
+        .create_owned_privileged::<()>();
+
+    // Whether this succeeds depends on the privileges with which the test is run
+    if wnf::can_create_permanent_shared_objects().unwrap() {
+        assert!(result.unwrap().exists().unwrap());
+    } else {
+        let err = result.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        assert_eq!(
+            err.get_ref().unwrap().downcast_ref::<CreateError>(),
+            Some(&CreateError::MissingPrivilege(Privilege::CreatePermanent))
+        );
+    }
+}
+
 #[test]
 fn create_state_with_everyone_generic_all_security_descriptor() {
     let state = StateCreation::new()
@@ -108,6 +190,34 @@ fn create_state_with_everyone_generic_all_security_descriptor() {
     assert!(state.set(&()).is_ok());
 }
 
+#[test]
+fn create_state_with_current_user_generic_all_security_descriptor() {
+    let state = StateCreation::new()
+        .lifetime(CreatableStateLifetime::Temporary)
+        .scope(DataScope::Machine)
+        .security_descriptor(BoxedSecurityDescriptor::create_current_user_generic_all().unwrap())
+        .create_owned()
+        .unwrap();
+
+    assert!(state.get().is_ok());
+    assert!(state.set(&()).is_ok());
+}
+
+#[test]
+fn create_session_scoped_state_for_current_user() {
+    let state = StateCreation::session_scoped_for_current_user(CreatableStateLifetime::Temporary)
+        .unwrap()
+        .create_owned()
+        .unwrap();
+
+    let state_name_descriptor: StateNameDescriptor = state.state_name().try_into().unwrap();
+    assert_eq!(state_name_descriptor.lifetime, StateLifetime::Temporary);
+    assert_eq!(state_name_descriptor.data_scope, DataScope::Session);
+
+    assert!(state.get().is_ok());
+    assert!(state.set(&()).is_ok());
+}
+
 #[test]
 fn create_state_with_security_descriptor_from_string() {
     let state_creation = StateCreation::new()
@@ -212,3 +322,64 @@ fn borrowed_state_delete() {
 
     assert!(!state.exists().unwrap());
 }
+
+#[test]
+fn owned_state_duplicate() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    state.set(&42).unwrap();
+    state.set(&43).unwrap();
+
+    let creation = StateCreation::new()
+        .lifetime(CreatableStateLifetime::Temporary)
+        .scope(DataScope::Session);
+
+    let duplicate = state.duplicate(&creation).unwrap();
+
+    assert_ne!(duplicate.state_name(), state.state_name());
+    let state_name_descriptor: StateNameDescriptor = duplicate.state_name().try_into().unwrap();
+    assert_eq!(state_name_descriptor.data_scope, DataScope::Session);
+
+    let (data, change_stamp) = duplicate.query().unwrap().into_data_change_stamp();
+    assert_eq!(data, 43);
+    assert_eq!(change_stamp, 1);
+}
+
+#[test]
+fn owned_state_duplicate_without_data() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+
+    let creation = StateCreation::new()
+        .lifetime(CreatableStateLifetime::Temporary)
+        .scope(DataScope::Machine);
+
+    let duplicate = state.duplicate(&creation).unwrap();
+
+    assert_eq!(duplicate.change_stamp().unwrap(), 0);
+}
+
+#[test]
+fn owned_state_duplicate_inherits_type_id() {
+    let type_id = GUID::try_from("b75fa6ba-77fd-4790-b825-1715ffefbac8").unwrap();
+
+    let state = StateCreation::new()
+        .lifetime(CreatableStateLifetime::Temporary)
+        .scope(DataScope::Machine)
+        .type_id(type_id)
+        .create_owned::<u32>()
+        .unwrap();
+
+    state.set(&42).unwrap();
+
+    let creation = StateCreation::new()
+        .lifetime(CreatableStateLifetime::Temporary)
+        .scope(DataScope::Machine);
+
+    let duplicate = state.duplicate(&creation).unwrap();
+
+    let borrowed_duplicate = BorrowedState::<u32>::from_state_name_and_type_id(duplicate.state_name(), type_id);
+    assert_eq!(borrowed_duplicate.get().unwrap(), 42);
+
+    let other_type_id = GUID::try_from("ee26d6d2-53f4-4230-9c9e-88556e82c3d3").unwrap();
+    let borrowed_duplicate = BorrowedState::<u32>::from_state_name_and_type_id(duplicate.state_name(), other_type_id);
+    assert!(borrowed_duplicate.set(&43).is_err());
+}
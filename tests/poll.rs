@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+use crossbeam_channel::RecvTimeoutError;
+use wnf::{AsState, DataAccessor, OwnedState, SeenChangeStamp, SubscriptionMode, UpdateKind};
+
+#[test]
+fn subscribe_polling() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    state.set(&0).unwrap();
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    let subscription = state
+        .subscribe_polling(
+            move |accessor: DataAccessor<_>| {
+                tx.send((accessor.query().unwrap(), accessor.update_kind())).unwrap();
+            },
+            SeenChangeStamp::Current,
+            Duration::from_millis(10),
+        )
+        .unwrap();
+
+    state.set(&1).unwrap();
+
+    let (data, update_kind) = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!(data.into_data_change_stamp(), (1, 2.into()));
+    assert_eq!(update_kind, UpdateKind::Sequential);
+
+    assert_eq!(
+        rx.recv_timeout(Duration::from_millis(100)),
+        Err(RecvTimeoutError::Timeout)
+    );
+
+    drop(subscription);
+
+    assert_eq!(
+        rx.recv_timeout(Duration::from_secs(1)),
+        Err(RecvTimeoutError::Disconnected)
+    );
+}
+
+#[test]
+fn subscribe_polling_listener_panic() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    state.set(&0).unwrap();
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    let subscription = state
+        .subscribe_polling(
+            move |accessor: DataAccessor<_>| {
+                tx.send(accessor.get().unwrap()).unwrap();
+                panic!("listener panicked");
+            },
+            SeenChangeStamp::None,
+            Duration::from_millis(10),
+        )
+        .unwrap();
+
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), 0);
+
+    state.set(&1).unwrap();
+
+    // The listener is dropped without being called again
+    assert_eq!(
+        rx.recv_timeout(Duration::from_secs(1)),
+        Err(RecvTimeoutError::Disconnected)
+    );
+
+    drop(subscription);
+}
+
+#[test]
+fn subscribe_with_mode() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+
+    for mode in [
+        SubscriptionMode::Notification,
+        SubscriptionMode::Polling(Duration::from_millis(10)),
+    ] {
+        let (tx, rx) = crossbeam_channel::unbounded();
+
+        let subscription = state
+            .as_state()
+            .subscribe_with_mode(
+                move |accessor: DataAccessor<_>| {
+                    tx.send(accessor.get().unwrap()).unwrap();
+                },
+                SeenChangeStamp::Current,
+                mode,
+            )
+            .unwrap();
+
+        assert_eq!(subscription.is_polling(), mode != SubscriptionMode::Notification);
+
+        state.set(&42).unwrap();
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), 42);
+
+        subscription.unsubscribe().unwrap();
+
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(1)),
+            Err(RecvTimeoutError::Disconnected)
+        );
+    }
+}
@@ -0,0 +1,41 @@
+use std::sync::mpsc;
+use std::time::Duration;
+
+use wnf::prelude::*;
+
+#[test]
+fn prelude_create_query_update() {
+    let state: OwnedState<u32> = StateCreation::new()
+        .lifetime(CreatableStateLifetime::Temporary)
+        .scope(DataScope::Machine)
+        .create_owned()
+        .unwrap();
+
+    state.as_state().set(&42).unwrap();
+
+    let (data, change_stamp) = state.query().unwrap().into_data_change_stamp();
+    assert_eq!(data, 42);
+    assert_eq!(change_stamp, ChangeStamp::from(1));
+
+    let state_name: StateName = state.state_name();
+    assert_eq!(BorrowedState::<u32>::from_state_name(state_name).get().unwrap(), 42);
+}
+
+#[test]
+fn prelude_subscribe() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    let (tx, rx) = mpsc::channel();
+
+    let _subscription: Subscription<'_, _> = state
+        .subscribe(
+            move |accessor: DataAccessor<'_, u32>| {
+                tx.send(accessor.get().unwrap()).unwrap();
+            },
+            SeenChangeStamp::Current,
+        )
+        .unwrap();
+
+    state.set(&42).unwrap();
+
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), 42);
+}
@@ -0,0 +1,237 @@
+use std::io::ErrorKind;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use tokio::time;
+use wnf::{OwnedState, WnfCounter, WnfEventPulse, WnfFlag};
+
+#[test]
+fn flag_set_clear() {
+    let state = OwnedState::<[bool]>::create_temporary().unwrap();
+    let flag = WnfFlag::new(&state);
+
+    assert!(!flag.is_set().unwrap());
+
+    flag.set().unwrap();
+    assert!(flag.is_set().unwrap());
+    assert_eq!(*state.get_boxed().unwrap(), [true]);
+
+    flag.clear().unwrap();
+    assert!(!flag.is_set().unwrap());
+    assert_eq!(*state.get_boxed().unwrap(), [false]);
+}
+
+#[test]
+fn flag_invalid_data() {
+    let state = OwnedState::<[bool]>::create_temporary().unwrap();
+    state.set(&[true, true]).unwrap();
+
+    let result = WnfFlag::new(&state).is_set();
+
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
+}
+
+#[test]
+fn flag_wait_set_blocking() {
+    let state = Arc::new(OwnedState::<[bool]>::create_temporary().unwrap());
+
+    let handle = {
+        let state = Arc::clone(&state);
+
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(300));
+            WnfFlag::new(&state).set().unwrap();
+        })
+    };
+
+    WnfFlag::new(&state).wait_set_blocking(Duration::from_secs(3)).unwrap();
+
+    handle.join().unwrap();
+}
+
+#[test]
+fn flag_wait_set_blocking_already_set() {
+    let state = OwnedState::<[bool]>::create_temporary().unwrap();
+    let flag = WnfFlag::new(&state);
+    flag.set().unwrap();
+
+    flag.wait_set_blocking(Duration::ZERO).unwrap();
+}
+
+#[test]
+fn flag_wait_set_blocking_timeout() {
+    let state = OwnedState::<[bool]>::create_temporary().unwrap();
+    let flag = WnfFlag::new(&state);
+    flag.clear().unwrap();
+
+    let result = flag.wait_set_blocking(Duration::from_millis(100));
+
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::TimedOut);
+}
+
+#[tokio::test]
+async fn flag_wait_set_async() {
+    let state = Arc::new(OwnedState::<[bool]>::create_temporary().unwrap());
+
+    let handle = {
+        let state = Arc::clone(&state);
+
+        tokio::spawn(async move {
+            time::sleep(Duration::from_millis(300)).await;
+            WnfFlag::new(&*state).set().unwrap();
+        })
+    };
+
+    time::timeout(Duration::from_secs(3), WnfFlag::new(&*state).wait_set_async())
+        .await
+        .unwrap()
+        .unwrap();
+
+    handle.await.unwrap();
+}
+
+#[test]
+fn counter_increment() {
+    let state = OwnedState::<[u64]>::create_temporary().unwrap();
+    let counter = WnfCounter::new(&state);
+
+    assert_eq!(counter.get().unwrap(), 0);
+    assert_eq!(counter.increment().unwrap(), 1);
+    assert_eq!(counter.add(41).unwrap(), 42);
+    assert_eq!(counter.get().unwrap(), 42);
+
+    counter.reset().unwrap();
+    assert_eq!(counter.get().unwrap(), 0);
+}
+
+#[test]
+fn counter_concurrent_increments() {
+    let state = Arc::new(OwnedState::<[u64]>::create_temporary().unwrap());
+
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let state = Arc::clone(&state);
+
+            thread::spawn(move || {
+                for _ in 0..100 {
+                    WnfCounter::new(&state).increment().unwrap();
+                }
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    assert_eq!(WnfCounter::new(&state).get().unwrap(), 400);
+}
+
+#[test]
+fn counter_wait_until_at_least_blocking() {
+    let state = Arc::new(OwnedState::<[u64]>::create_temporary().unwrap());
+
+    let handle = {
+        let state = Arc::clone(&state);
+
+        thread::spawn(move || {
+            for _ in 0..10 {
+                thread::sleep(Duration::from_millis(10));
+                WnfCounter::new(&state).increment().unwrap();
+            }
+        })
+    };
+
+    let value = WnfCounter::new(&state)
+        .wait_until_at_least_blocking(5, Duration::from_secs(3))
+        .unwrap();
+
+    assert!(value >= 5);
+
+    handle.join().unwrap();
+}
+
+#[tokio::test]
+async fn counter_wait_until_at_least_async() {
+    let state = Arc::new(OwnedState::<[u64]>::create_temporary().unwrap());
+
+    let handle = {
+        let state = Arc::clone(&state);
+
+        tokio::spawn(async move {
+            for _ in 0..10 {
+                time::sleep(Duration::from_millis(10)).await;
+                WnfCounter::new(&*state).increment().unwrap();
+            }
+        })
+    };
+
+    let value = time::timeout(
+        Duration::from_secs(3),
+        WnfCounter::new(&*state).wait_until_at_least_async(5),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+
+    assert!(value >= 5);
+
+    handle.await.unwrap();
+}
+
+#[test]
+fn event_pulse_increments_change_stamp() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    state.set(&42).unwrap();
+
+    let event = WnfEventPulse::new(&state);
+    let change_stamp = event.change_stamp().unwrap();
+
+    event.pulse().unwrap();
+    event.pulse().unwrap();
+
+    assert_eq!(event.change_stamp().unwrap().distance_from(change_stamp), 2);
+    assert_eq!(*state.cast::<[u8]>().get_boxed().unwrap(), []);
+}
+
+#[test]
+fn event_pulse_wait_blocking() {
+    let state = Arc::new(OwnedState::<()>::create_temporary().unwrap());
+
+    let handle = {
+        let state = Arc::clone(&state);
+
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(300));
+            WnfEventPulse::new(&state).pulse().unwrap();
+        })
+    };
+
+    WnfEventPulse::new(&state)
+        .wait_blocking(Duration::from_secs(3))
+        .unwrap();
+
+    handle.join().unwrap();
+}
+
+#[tokio::test]
+async fn event_pulse_wait_async() {
+    let state = Arc::new(OwnedState::<()>::create_temporary().unwrap());
+
+    let handle = {
+        let state = Arc::clone(&state);
+
+        tokio::spawn(async move {
+            time::sleep(Duration::from_millis(300)).await;
+            WnfEventPulse::new(&*state).pulse().unwrap();
+        })
+    };
+
+    time::timeout(Duration::from_secs(3), WnfEventPulse::new(&*state).wait_async())
+        .await
+        .unwrap()
+        .unwrap();
+
+    handle.await.unwrap();
+}
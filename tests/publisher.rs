@@ -0,0 +1,60 @@
+use std::io::ErrorKind;
+
+use wnf::{OwnedState, PublisherError, PublisherGuard, PublisherId};
+
+#[test]
+fn acquire_and_release() {
+    let lock_state = OwnedState::<[u8]>::create_temporary().unwrap();
+
+    let guard = PublisherGuard::acquire(&lock_state).unwrap();
+    assert_eq!(PublisherGuard::holder(&lock_state).unwrap(), Some(guard.id()));
+
+    guard.release().unwrap();
+    assert_eq!(PublisherGuard::holder(&lock_state).unwrap(), None);
+}
+
+#[test]
+fn acquire_already_held() {
+    let lock_state = OwnedState::<[u8]>::create_temporary().unwrap();
+    let guard = PublisherGuard::acquire(&lock_state).unwrap();
+
+    let err = PublisherGuard::acquire(&lock_state).unwrap_err();
+
+    assert_eq!(err.kind(), ErrorKind::WouldBlock);
+    assert_eq!(
+        err.get_ref().unwrap().downcast_ref::<PublisherError>(),
+        Some(&PublisherError::AlreadyHeld { holder: guard.id() })
+    );
+}
+
+#[test]
+fn acquire_after_drop() {
+    let lock_state = OwnedState::<[u8]>::create_temporary().unwrap();
+    drop(PublisherGuard::acquire(&lock_state).unwrap());
+
+    let guard = PublisherGuard::acquire(&lock_state).unwrap();
+
+    assert_eq!(PublisherGuard::holder(&lock_state).unwrap(), Some(guard.id()));
+}
+
+#[test]
+fn acquire_as_same_id() {
+    let lock_state = OwnedState::<[u8]>::create_temporary().unwrap();
+    let id = PublisherId::new();
+    let guard = PublisherGuard::acquire_as(&lock_state, id).unwrap();
+    std::mem::forget(guard);
+
+    let guard = PublisherGuard::acquire_as(&lock_state, id).unwrap();
+
+    assert_eq!(guard.id(), id);
+}
+
+#[test]
+fn acquire_invalid_lock_data() {
+    let lock_state = OwnedState::<u32>::create_temporary().unwrap();
+    lock_state.set(&42).unwrap();
+
+    let err = PublisherGuard::acquire(&lock_state).unwrap_err();
+
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+}
@@ -1,4 +1,11 @@
-use wnf::{OpaqueData, OwnedState};
+use std::mem::MaybeUninit;
+use std::{io, ptr};
+
+use wnf::{
+    AsState, BorrowedState, BufferGrowth, Consistency, CreatableStateLifetime, DataScope, OpaqueData, OwnedState,
+    QueryOptions, ReadError, ReadInto, ScopeInstance, StateCreation, StateError, UnscopedStateError, WideString,
+    WnfRoutine, GUID,
+};
 
 #[test]
 fn get() {
@@ -46,6 +53,136 @@ fn query_boxed_slice() {
     assert_eq!(change_stamp, 1);
 }
 
+#[test]
+fn query_boxed_slice_with_options() {
+    let state = OwnedState::<[u32]>::create_temporary().unwrap();
+    state.set(&[1, 2, 3]).unwrap();
+
+    let options = QueryOptions::new().initial_capacity(4).growth(BufferGrowth::Exact);
+    let (data, change_stamp) = state
+        .query_boxed_with_options(options)
+        .unwrap()
+        .into_data_change_stamp();
+
+    assert_eq!(*data, [1, 2, 3]);
+    assert_eq!(change_stamp, 1);
+}
+
+#[test]
+fn query_slice_with_len_hint() {
+    let state = OwnedState::<[u32]>::create_temporary().unwrap();
+    state.set(&[1, 2, 3]).unwrap();
+
+    for len in [0, 2, 3, 10] {
+        let (data, change_stamp) = state.query_slice_with_len_hint(len).unwrap().into_data_change_stamp();
+
+        assert_eq!(*data, [1, 2, 3]);
+        assert_eq!(change_stamp, 1);
+    }
+
+    let data = state.as_state().query_slice_with_len_hint(3).unwrap().into_data();
+    assert_eq!(*data, [1, 2, 3]);
+}
+
+#[cfg(feature = "perf_counters")]
+#[test]
+fn query_slice_with_len_hint_queries_once() {
+    let state = OwnedState::<[u32]>::create_temporary().unwrap();
+    state.set(&[1, 2, 3]).unwrap();
+
+    let (result, counters) = wnf::PerfCounters::measure(|| state.query_slice_with_len_hint(3));
+    result.unwrap();
+    assert_eq!(counters.count(WnfRoutine::QueryStateData), 1);
+
+    let (result, counters) = wnf::PerfCounters::measure(|| state.query_boxed());
+    result.unwrap();
+    assert_eq!(counters.count(WnfRoutine::QueryStateData), 2);
+}
+
+#[test]
+fn query_into_vec() {
+    let state = OwnedState::<[u32]>::create_temporary().unwrap();
+    state.set(&[1, 2, 3]).unwrap();
+
+    let mut buffer = vec![4, 5, 6, 7];
+
+    let change_stamp = state.query_into(&mut buffer).unwrap();
+    assert_eq!(buffer, [1, 2, 3]);
+    assert_eq!(change_stamp, 1);
+
+    state.set(&[8; 10]).unwrap();
+
+    let change_stamp = state.as_state().query_into(&mut buffer).unwrap();
+    assert_eq!(buffer, [8; 10]);
+    assert_eq!(change_stamp, 2);
+}
+
+#[test]
+fn query_into_custom_container() {
+    /// A container with a fixed capacity that ignores requests to reserve more
+    struct FixedBuffer {
+        data: [MaybeUninit<u8>; 4],
+        len: usize,
+    }
+
+    impl ReadInto<u8> for FixedBuffer {
+        fn clear(&mut self) {
+            self.len = 0;
+        }
+
+        fn reserve(&mut self, _: usize) {}
+
+        fn spare_capacity_mut(&mut self) -> &mut [MaybeUninit<u8>] {
+            &mut self.data[self.len..]
+        }
+
+        unsafe fn set_len(&mut self, len: usize) {
+            self.len = len;
+        }
+    }
+
+    let state = OwnedState::<[u8]>::create_temporary().unwrap();
+    let mut buffer = FixedBuffer {
+        data: [MaybeUninit::uninit(); 4],
+        len: 0,
+    };
+
+    state.set(&[1, 2, 3]).unwrap();
+    state.query_into(&mut buffer).unwrap();
+    assert_eq!(buffer.len, 3);
+
+    state.set(&[1, 2, 3, 4, 5]).unwrap();
+    let err = state.query_into(&mut buffer).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::OutOfMemory);
+    assert_eq!(buffer.len, 0);
+}
+
+#[test]
+fn get_boxed_slice_exceeding_max_read_size() {
+    let mut state = OwnedState::<[u32]>::create_temporary().unwrap();
+    state.set(&[1, 2, 3]).unwrap();
+    state.set_max_read_size(Some(8));
+
+    let err = state.get_boxed().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(
+        *err.into_inner().unwrap().downcast::<ReadError>().unwrap(),
+        ReadError::ExceedsMaxReadSize {
+            max_read_size: 8,
+            actual: 12
+        }
+    );
+
+    assert!(state.as_state().get_boxed().is_err());
+    assert_eq!(
+        *state.as_state().with_max_read_size(Some(12)).get_boxed().unwrap(),
+        [1, 2, 3]
+    );
+
+    state.set_max_read_size(None);
+    assert_eq!(*state.get_boxed().unwrap(), [1, 2, 3]);
+}
+
 #[test]
 fn change_stamp() {
     let state = OwnedState::<u32>::create_temporary().unwrap();
@@ -68,3 +205,150 @@ fn query_opaque_data() {
     assert_eq!(data.size(), 4);
     assert_eq!(change_stamp, 1);
 }
+
+#[test]
+fn query_consistent() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    state.set(&42).unwrap();
+
+    let (stamped_data, consistency) = state.query_consistent(0).unwrap();
+
+    assert_eq!(stamped_data.into_data_change_stamp(), (42, 1.into()));
+    assert_eq!(consistency, Consistency::Stable);
+}
+
+#[test]
+fn query_boxed_consistent() {
+    let state = OwnedState::<[u32]>::create_temporary().unwrap();
+    state.set(&[1, 2, 3]).unwrap();
+
+    let (stamped_data, consistency) = state.as_state().query_boxed_consistent(3).unwrap();
+
+    assert_eq!(**stamped_data.data(), [1, 2, 3]);
+    assert_eq!(stamped_data.change_stamp(), 1);
+    assert!(consistency.is_stable());
+}
+
+#[test]
+fn get_wide_string() {
+    let state = OwnedState::<[u16]>::create_temporary().unwrap();
+    state.set(&[0x0061, 0x0062, 0x0000]).unwrap();
+    let state: OwnedState<WideString> = state.cast();
+
+    let data = state.get().unwrap();
+
+    assert_eq!(data.as_wide(), [0x0061, 0x0062]);
+    assert_eq!(data.into_os_string(), "ab");
+}
+
+#[test]
+fn query_with_null_explicit_scope() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    let value = 0x12345678;
+    state.set(&value).unwrap();
+
+    // SAFETY:
+    // The explicit scope is a null pointer
+    let (read_value, change_stamp) = unsafe { state.query_with_explicit_scope(ptr::null()) }
+        .unwrap()
+        .into_data_change_stamp();
+
+    assert_eq!(read_value, value);
+    assert_eq!(change_stamp, 1);
+}
+
+#[test]
+fn query_all_instances_session_scope() {
+    let state = StateCreation::new()
+        .lifetime(CreatableStateLifetime::Temporary)
+        .scope(DataScope::Session)
+        .create_owned::<u32>()
+        .unwrap();
+
+    state.set(&0x12345678).unwrap();
+
+    let instances = state.query_all_instances().unwrap();
+
+    assert!(instances
+        .iter()
+        .any(|(instance, data)| matches!(instance, ScopeInstance::Session(..)) && *data.data() == 0x12345678));
+}
+
+#[test]
+fn query_all_instances_unscoped() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+
+    let err = state.query_all_instances().unwrap_err();
+
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    assert_eq!(
+        *err.get_ref().unwrap().downcast_ref::<UnscopedStateError>().unwrap(),
+        UnscopedStateError {
+            data_scope: DataScope::Machine
+        }
+    );
+}
+
+#[test]
+fn query_boxed_slice_with_null_explicit_scope() {
+    let state = OwnedState::<[u32]>::create_temporary().unwrap();
+    let slice = [0x12345678, 0xABCDEF01, 0x23456789];
+    state.set(&slice).unwrap();
+
+    // SAFETY:
+    // The explicit scope is a null pointer
+    let (read_slice, change_stamp) = unsafe { state.query_boxed_with_explicit_scope(ptr::null()) }
+        .unwrap()
+        .into_data_change_stamp();
+
+    assert_eq!(*read_slice, slice);
+    assert_eq!(change_stamp, 1);
+}
+
+#[test]
+fn query_and_set_with_type_id() {
+    let type_id = GUID::try_from("b75fa6ba-77fd-4790-b825-1715ffefbac8").unwrap();
+    let wrong_type_id = GUID::try_from("ee26d6d2-53f4-4230-9c9e-88556e82c3d3").unwrap();
+
+    let state = StateCreation::new()
+        .lifetime(CreatableStateLifetime::Temporary)
+        .scope(DataScope::Machine)
+        .type_id(type_id)
+        .create_owned::<u32>()
+        .unwrap();
+
+    let state_without_type_id = BorrowedState::<u32>::from_state_name(state.state_name());
+
+    state_without_type_id.set_with_type_id(&42, type_id).unwrap();
+    assert_eq!(
+        state_without_type_id.query_with_type_id(type_id).unwrap().into_data(),
+        42
+    );
+    assert_eq!(
+        *state_without_type_id
+            .cast::<[u8]>()
+            .query_boxed_with_type_id(type_id)
+            .unwrap()
+            .into_data(),
+        42u32.to_le_bytes()
+    );
+
+    assert!(state.set_with_type_id(&43, wrong_type_id).is_err());
+    assert!(state.query_with_type_id(wrong_type_id).is_err());
+}
+
+#[test]
+fn get_deleted_state_error_has_state_context() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    let state_name = state.state_name();
+    state.delete().unwrap();
+
+    let err = BorrowedState::<u32>::from_state_name(state_name).get().unwrap_err();
+    let state_err = err.get_ref().unwrap().downcast_ref::<StateError>().unwrap();
+
+    assert_eq!(state_err.state_name(), state_name);
+    assert_eq!(state_err.type_id(), None);
+    assert_eq!(state_err.routine(), WnfRoutine::QueryStateData);
+    assert!(state_err.raw_os_error().is_some());
+    assert!(err.to_string().contains(&state_name.to_string()));
+}
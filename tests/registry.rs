@@ -0,0 +1,72 @@
+use std::io::ErrorKind;
+
+use wnf::{RegFileError, StateLifetime, StateName, GUID};
+
+const WNF_SHEL_DESKTOP_APPLICATION_STARTED: StateName = StateName::from_opaque_value(0x0D83_063E_A3BE_5075);
+
+#[test]
+fn registered_state_names_well_known() {
+    let state_names = wnf::registered_state_names(StateLifetime::WellKnown).unwrap();
+
+    assert!(state_names.contains(&WNF_SHEL_DESKTOP_APPLICATION_STARTED));
+}
+
+#[test]
+fn registered_state_names_temporary() {
+    let state_names = wnf::registered_state_names(StateLifetime::Temporary).unwrap();
+
+    assert!(state_names.is_empty());
+}
+
+#[test]
+fn find_states_by_unused_type_id() {
+    let state_names = wnf::find_states_by_type_id(GUID::new().unwrap()).unwrap();
+
+    assert!(state_names.is_empty());
+}
+
+#[test]
+fn cleanup_orphaned_states_without_match() {
+    let results = wnf::cleanup_orphaned_states(|_| false).unwrap();
+
+    assert!(results.is_empty());
+}
+
+#[test]
+fn export_registry_well_known() {
+    let err = wnf::export_registry([WNF_SHEL_DESKTOP_APPLICATION_STARTED]).unwrap_err();
+
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    assert_eq!(
+        *err.get_ref().unwrap().downcast_ref::<RegFileError>().unwrap(),
+        RegFileError::UnsupportedLifetime {
+            state_name: WNF_SHEL_DESKTOP_APPLICATION_STARTED,
+            lifetime: StateLifetime::WellKnown,
+        }
+    );
+}
+
+#[test]
+fn export_registry_empty() {
+    let reg = wnf::export_registry([]).unwrap();
+
+    assert_eq!(reg, "Windows Registry Editor Version 5.00\r\n");
+}
+
+#[test]
+fn import_registry_empty() {
+    let state_names = wnf::import_registry("Windows Registry Editor Version 5.00\r\n").unwrap();
+
+    assert!(state_names.is_empty());
+}
+
+#[test]
+fn import_registry_missing_header() {
+    let err = wnf::import_registry("REGEDIT4\r\n").unwrap_err();
+
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert_eq!(
+        *err.get_ref().unwrap().downcast_ref::<RegFileError>().unwrap(),
+        RegFileError::MissingHeader
+    );
+}
@@ -0,0 +1,39 @@
+use std::time::Duration;
+
+use wnf::{read_recording, replay, DataAccessor, OwnedState, Recorder, ReplaySpeed, SeenChangeStamp};
+
+#[test]
+fn record_and_replay() {
+    let state_1 = OwnedState::<u32>::create_temporary().unwrap();
+    let state_2 = OwnedState::<u32>::create_temporary().unwrap();
+
+    let recorder = Recorder::new(state_1.state_name(), Vec::new()).unwrap();
+    let subscription_1 = state_1.subscribe(recorder.clone(), SeenChangeStamp::Current).unwrap();
+    let subscription_2 = state_2
+        .subscribe(recorder.for_state(state_2.state_name()), SeenChangeStamp::Current)
+        .unwrap();
+
+    state_1.set(&1).unwrap();
+    state_2.set(&2).unwrap();
+    state_1.set(&3).unwrap();
+
+    // Give the listeners some time to be notified
+    std::thread::sleep(Duration::from_millis(100));
+
+    subscription_1.unsubscribe().unwrap();
+    subscription_2.unsubscribe().unwrap();
+
+    let updates = read_recording(recorder.finish().unwrap().as_slice()).unwrap();
+    assert_eq!(updates.len(), 3);
+
+    let mut replayed = Vec::new();
+    replay(
+        updates
+            .iter()
+            .filter(|update| update.state_name() == state_1.state_name()),
+        ReplaySpeed::Immediate,
+        &mut |accessor: DataAccessor<'_, u32>| replayed.push(accessor.get().unwrap()),
+    );
+
+    assert_eq!(replayed, [1, 3]);
+}
@@ -0,0 +1,96 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use wnf::{AsState, DataAccessor, OwnedState, SeenChangeStamp};
+
+#[test]
+fn scope_listener_borrows_data() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    state.set(&0).unwrap();
+
+    let values = Mutex::new(Vec::new());
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    wnf::scope(|s| {
+        s.subscribe(
+            &state,
+            |accessor: DataAccessor<_>| {
+                values.lock().unwrap().push(accessor.get().unwrap());
+                tx.send(()).unwrap();
+            },
+            SeenChangeStamp::Current,
+        )
+        .unwrap();
+
+        for i in 1..3 {
+            state.set(&i).unwrap();
+            rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        }
+
+        assert!(state.subscribers_present().unwrap());
+    })
+    .unwrap();
+
+    assert!(!state.subscribers_present().unwrap());
+    assert_eq!(values.into_inner().unwrap(), [1, 2]);
+}
+
+#[test]
+fn subscribe_scoped_listener_borrows_data_mutably() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    state.set(&0).unwrap();
+
+    let mut values = Vec::new();
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    {
+        let values = Mutex::new(&mut values);
+
+        wnf::scope(|s| {
+            state
+                .as_state()
+                .subscribe_scoped(
+                    s,
+                    |accessor: DataAccessor<_>| {
+                        values.lock().unwrap().push(accessor.get().unwrap());
+                        tx.send(()).unwrap();
+                    },
+                    SeenChangeStamp::Current,
+                )
+                .unwrap();
+
+            state.set(&1).unwrap();
+            rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        })
+        .unwrap();
+    }
+
+    values.push(2);
+
+    assert_eq!(values, [1, 2]);
+}
+
+#[test]
+fn scope_returns_value() {
+    let value = wnf::scope(|_| 42).unwrap();
+
+    assert_eq!(value, 42);
+}
+
+#[test]
+fn scope_unsubscribes_on_panic() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        wnf::scope(|s| {
+            s.subscribe(&state, |_: DataAccessor<_>| {}, SeenChangeStamp::None)
+                .unwrap();
+
+            panic!("scope panicked");
+        })
+    }));
+
+    assert!(result.is_err());
+    assert!(!state.subscribers_present().unwrap());
+}
@@ -0,0 +1,106 @@
+use std::io::ErrorKind;
+use std::time::Duration;
+
+use crossbeam_channel::RecvTimeoutError;
+use windows::Win32::System::Services::{
+    SERVICE_CONTROL_CONTINUE, SERVICE_CONTROL_INTERROGATE, SERVICE_CONTROL_PAUSE, SERVICE_CONTROL_STOP,
+};
+use wnf::{BorrowedState, DataAccessor, OwnedState, SeenChangeStamp, ServiceStoppedError, ServiceSubscriptions};
+
+#[test]
+fn listeners_are_paused_and_resumed() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    let subscriptions = ServiceSubscriptions::new();
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    subscriptions
+        .subscribe(
+            BorrowedState::<u32>::from_state_name(state.state_name()),
+            move |accessor: DataAccessor<_>| {
+                tx.send(accessor.get().unwrap()).unwrap();
+            },
+            SeenChangeStamp::Current,
+        )
+        .unwrap();
+
+    assert_eq!(subscriptions.len(), 1);
+
+    state.set(&1).unwrap();
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), 1);
+
+    assert!(subscriptions.handle_control(SERVICE_CONTROL_PAUSE).unwrap());
+    state.set(&2).unwrap();
+    assert_eq!(
+        rx.recv_timeout(Duration::from_millis(100)),
+        Err(RecvTimeoutError::Timeout)
+    );
+
+    assert!(subscriptions.handle_control(SERVICE_CONTROL_CONTINUE).unwrap());
+    state.set(&3).unwrap();
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), 3);
+
+    assert!(!subscriptions.handle_control(SERVICE_CONTROL_INTERROGATE).unwrap());
+}
+
+#[test]
+fn subscriptions_are_unsubscribed_on_stop() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    let subscriptions = ServiceSubscriptions::new();
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let added_tx = tx.clone();
+
+    subscriptions
+        .subscribe(
+            BorrowedState::<u32>::from_state_name(state.state_name()),
+            move |accessor: DataAccessor<_>| {
+                tx.send(accessor.get().unwrap()).unwrap();
+            },
+            SeenChangeStamp::Current,
+        )
+        .unwrap();
+
+    let subscription = BorrowedState::<u32>::from_state_name(state.state_name())
+        .subscribe(
+            move |accessor: DataAccessor<_>| {
+                added_tx.send(accessor.get().unwrap()).unwrap();
+            },
+            SeenChangeStamp::Current,
+        )
+        .unwrap();
+
+    subscriptions.add(subscription).unwrap();
+    assert_eq!(subscriptions.len(), 2);
+
+    assert!(subscriptions.handle_control(SERVICE_CONTROL_STOP).unwrap());
+
+    assert!(subscriptions.is_stopped());
+    assert!(subscriptions.is_empty());
+
+    // Unsubscribing dropped the listeners and with them all senders
+    state.set(&42).unwrap();
+    assert_eq!(
+        rx.recv_timeout(Duration::from_secs(1)),
+        Err(RecvTimeoutError::Disconnected)
+    );
+}
+
+#[test]
+fn subscribe_after_stop_fails() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    let subscriptions = ServiceSubscriptions::new();
+
+    subscriptions.stop().unwrap();
+
+    let err = subscriptions
+        .subscribe(
+            BorrowedState::<u32>::from_state_name(state.state_name()),
+            |_: DataAccessor<_>| {},
+            SeenChangeStamp::Current,
+        )
+        .unwrap_err();
+
+    assert_eq!(err.kind(), ErrorKind::Other);
+    assert!(err.into_inner().unwrap().downcast::<ServiceStoppedError>().is_ok());
+}
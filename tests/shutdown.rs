@@ -0,0 +1,44 @@
+use std::time::Duration;
+
+use crossbeam_channel::RecvTimeoutError;
+use wnf::{DataAccessor, OwnedState, SeenChangeStamp};
+
+#[test]
+fn shutdown_drains_listener_calls() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+
+    let (started_tx, started_rx) = crossbeam_channel::unbounded();
+    let (release_tx, release_rx) = crossbeam_channel::unbounded::<()>();
+
+    let _subscription = state
+        .subscribe(
+            move |accessor: DataAccessor<_>| {
+                started_tx.send(accessor.get().unwrap()).unwrap();
+                let _ = release_rx.recv();
+            },
+            SeenChangeStamp::Current,
+        )
+        .unwrap();
+
+    state.set(&1).unwrap();
+    assert_eq!(started_rx.recv_timeout(Duration::from_secs(1)).unwrap(), 1);
+
+    assert!(!wnf::is_shut_down());
+
+    let report = wnf::shutdown(Duration::from_millis(100));
+    assert!(wnf::is_shut_down());
+    assert_eq!(report.pending_listener_calls(), 1);
+    assert!(!report.is_drained());
+    assert!(report.abandoned_subscriptions() >= 1);
+
+    drop(release_tx);
+
+    let report = wnf::shutdown(Duration::from_secs(1));
+    assert!(report.is_drained());
+
+    state.set(&2).unwrap();
+    assert_eq!(
+        started_rx.recv_timeout(Duration::from_millis(100)),
+        Err(RecvTimeoutError::Timeout)
+    );
+}
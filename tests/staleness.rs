@@ -0,0 +1,44 @@
+use std::thread;
+use std::time::Duration;
+
+use wnf::{OwnedState, StalenessMonitor};
+
+#[test]
+fn is_stale() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    let monitor = StalenessMonitor::new(&state, Duration::from_millis(100)).unwrap();
+
+    assert!(monitor.last_update().is_none());
+    assert!(!monitor.is_stale());
+
+    thread::sleep(Duration::from_millis(200));
+    assert!(monitor.is_stale());
+
+    state.set(&42).unwrap();
+    thread::sleep(Duration::from_millis(20));
+
+    let (_, change_stamp) = monitor.last_update().unwrap();
+    assert_eq!(change_stamp, state.change_stamp().unwrap());
+    assert!(!monitor.is_stale());
+}
+
+#[test]
+fn with_callback() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    let monitor = StalenessMonitor::with_callback(&state, Duration::from_millis(50), move |age| {
+        tx.send(age).unwrap();
+    })
+    .unwrap();
+
+    let age = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert!(age > monitor.max_age());
+    assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+
+    state.set(&42).unwrap();
+    assert!(rx.recv_timeout(Duration::from_secs(1)).is_ok());
+
+    drop(monitor);
+    assert!(rx.recv_timeout(Duration::from_secs(1)).is_err());
+}
@@ -1,4 +1,6 @@
-use wnf::{BorrowedState, OwnedState};
+use std::io::ErrorKind;
+
+use wnf::{AsState, BorrowedState, CowState, CreatableStateLifetime, DataScope, DropPolicy, OwnedState, StateCreation};
 
 #[test]
 fn owned_state_drop_deletes_state() {
@@ -31,3 +33,83 @@ fn owned_state_cast_does_not_delete_state() {
     let state = state.cast::<()>();
     assert!(state.exists().unwrap());
 }
+
+#[test]
+fn owned_state_drop_policy_keep_does_not_delete_state() {
+    let mut state = OwnedState::<()>::create_temporary().unwrap();
+    state.set_drop_policy(DropPolicy::Keep);
+
+    let state_name = state.state_name();
+    drop(state);
+
+    let state = BorrowedState::<()>::from_state_name(state_name);
+    assert!(state.exists().unwrap());
+
+    state.delete().unwrap();
+}
+
+#[test]
+fn owned_state_drop_policy_clear_then_delete_deletes_state() {
+    let state = StateCreation::new()
+        .lifetime(CreatableStateLifetime::Temporary)
+        .scope(DataScope::Machine)
+        .drop_policy(DropPolicy::ClearThenDelete)
+        .create_owned::<u32>()
+        .unwrap();
+    state.set(&42).unwrap();
+
+    assert_eq!(state.drop_policy(), DropPolicy::ClearThenDelete);
+
+    let state_name = state.state_name();
+    drop(state);
+
+    let state = BorrowedState::<()>::from_state_name(state_name);
+    assert!(!state.exists().unwrap());
+}
+
+#[test]
+fn owned_state_cast_preserves_drop_policy() {
+    let mut state = OwnedState::<()>::create_temporary().unwrap();
+    state.set_drop_policy(DropPolicy::ClearThenDelete);
+
+    let state = state.cast::<u32>();
+    assert_eq!(state.drop_policy(), DropPolicy::ClearThenDelete);
+}
+
+#[test]
+fn cow_state_owned_drop_deletes_state() {
+    let state: CowState<'_, ()> = OwnedState::create_temporary().unwrap().into();
+    assert!(state.is_owned());
+    assert!(state.as_state().exists().unwrap());
+
+    let state_name = state.state_name();
+    drop(state);
+
+    let state = BorrowedState::<()>::from_state_name(state_name);
+    assert!(!state.exists().unwrap());
+}
+
+#[test]
+fn cow_state_borrowed_drop_does_not_delete_state() {
+    let owned_state = OwnedState::<()>::create_temporary().unwrap();
+
+    let state: CowState<'_, ()> = owned_state.as_state().into();
+    assert!(state.is_borrowed());
+    drop(state);
+
+    assert!(owned_state.exists().unwrap());
+}
+
+#[test]
+fn borrowed_state_well_known() {
+    let state = BorrowedState::<()>::well_known("SHEL", 0x4A, DataScope::System).unwrap();
+
+    assert_eq!(state.state_name(), 0x0D83_063E_A3BE_5075);
+}
+
+#[test]
+fn borrowed_state_well_known_invalid_owner_tag() {
+    let result = BorrowedState::<()>::well_known("SHELL", 0x4A, DataScope::System);
+
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidInput);
+}
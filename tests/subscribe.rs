@@ -1,7 +1,13 @@
+use std::io;
+use std::sync::Arc;
 use std::time::Duration;
 
 use crossbeam_channel::RecvTimeoutError;
-use wnf::{AsState, DataAccessor, OpaqueData, OwnedState, SeenChangeStamp};
+use wnf::{
+    ActiveSubscription, AsState, ChangeStamp, CreatableStateLifetime, DataAccessor, DataScope, DeliveryMode,
+    OpaqueData, OwnedState, ReattachEvent, ReattachPolicy, SeenChangeStamp, StampedData, StateCreation, StateName,
+    SubscribeOwning, Subscription, UpdateKind, GUID,
+};
 
 #[test]
 fn subscribe() {
@@ -197,6 +203,338 @@ fn subscribe_with_last_seen_change_stamp_value() {
     );
 }
 
+#[test]
+fn subscribe_with_last_seen_change_stamp_value_initial() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    state.set(&0).unwrap();
+    state.set(&1).unwrap();
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    let subscription = state
+        .subscribe(
+            move |accessor: DataAccessor<_>| {
+                tx.send((accessor.get().unwrap(), accessor.change_stamp(), accessor.update_kind()))
+                    .unwrap();
+            },
+            SeenChangeStamp::Value(ChangeStamp::initial()),
+        )
+        .unwrap();
+
+    let (data, change_stamp, update_kind) = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!(data, 1);
+    assert_eq!(change_stamp, 2);
+    assert_eq!(update_kind, UpdateKind::Coalesced { missed: 1 });
+
+    subscription.unsubscribe().unwrap();
+}
+
+#[test]
+fn subscribe_with_last_seen_change_stamp_replay_without_data() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    let subscription = state
+        .subscribe(
+            move |accessor: DataAccessor<_>| {
+                tx.send((
+                    accessor.cast::<OpaqueData>().get().unwrap().size(),
+                    accessor.change_stamp(),
+                ))
+                .unwrap();
+            },
+            SeenChangeStamp::Replay,
+        )
+        .unwrap();
+
+    let (size, change_stamp) = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!(size, 0);
+    assert_eq!(change_stamp, 0);
+
+    state.set(&42).unwrap();
+
+    let (size, change_stamp) = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!(size, 4);
+    assert_eq!(change_stamp, 1);
+
+    subscription.unsubscribe().unwrap();
+
+    assert_eq!(
+        rx.recv_timeout(Duration::from_secs(1)),
+        Err(RecvTimeoutError::Disconnected)
+    );
+}
+
+#[test]
+fn subscribe_with_last_seen_change_stamp_replay_with_data() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    state.set(&42).unwrap();
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    let subscription = state
+        .subscribe(
+            move |accessor: DataAccessor<_>| {
+                tx.send(accessor.query().unwrap()).unwrap();
+            },
+            SeenChangeStamp::Replay,
+        )
+        .unwrap();
+
+    let (data, change_stamp) = rx
+        .recv_timeout(Duration::from_secs(1))
+        .unwrap()
+        .into_data_change_stamp();
+
+    assert_eq!(data, 42);
+    assert_eq!(change_stamp, 1);
+
+    subscription.unsubscribe().unwrap();
+
+    assert_eq!(
+        rx.recv_timeout(Duration::from_secs(1)),
+        Err(RecvTimeoutError::Disconnected)
+    );
+}
+
+#[test]
+fn subscribe_type_id() {
+    let type_id = GUID::try_from("b75fa6ba-77fd-4790-b825-1715ffefbac8").unwrap();
+
+    let state = StateCreation::new()
+        .lifetime(CreatableStateLifetime::Temporary)
+        .scope(DataScope::Machine)
+        .type_id(type_id)
+        .create_owned::<u32>()
+        .unwrap();
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    let subscription = state
+        .subscribe(
+            move |accessor: DataAccessor<_>| {
+                tx.send(accessor.type_id()).unwrap();
+            },
+            SeenChangeStamp::Current,
+        )
+        .unwrap();
+
+    state.set(&42).unwrap();
+
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), Some(type_id));
+
+    subscription.unsubscribe().unwrap();
+}
+
+#[test]
+fn subscribe_without_type_id() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    let subscription = state
+        .subscribe(
+            move |accessor: DataAccessor<_>| {
+                tx.send(accessor.type_id()).unwrap();
+            },
+            SeenChangeStamp::Current,
+        )
+        .unwrap();
+
+    state.set(&42).unwrap();
+
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), None);
+
+    subscription.unsubscribe().unwrap();
+}
+
+#[test]
+fn subscribe_pulses() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    let subscription = state
+        .subscribe_pulses(
+            move |change_stamp| tx.send(change_stamp).unwrap(),
+            SeenChangeStamp::Current,
+        )
+        .unwrap();
+
+    state.set(&42).unwrap();
+    state.pulse().unwrap();
+    state.set(&43).unwrap();
+    state.as_state().pulse().unwrap();
+
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), 2);
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), 4);
+
+    subscription.unsubscribe().unwrap();
+
+    assert_eq!(
+        rx.recv_timeout(Duration::from_secs(1)),
+        Err(RecvTimeoutError::Disconnected)
+    );
+}
+
+#[test]
+fn subscribe_stamps() {
+    let state = OwnedState::<[u8]>::create_temporary().unwrap();
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    let subscription = state
+        .subscribe_stamps(
+            move |change_stamp| tx.send(change_stamp).unwrap(),
+            SeenChangeStamp::Current,
+        )
+        .unwrap();
+
+    state.set(&[0; 4096]).unwrap();
+    state.pulse().unwrap();
+    state.as_state().set(&[1, 2, 3]).unwrap();
+
+    for expected in 1..=3 {
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), expected);
+    }
+
+    subscription.unsubscribe().unwrap();
+
+    assert_eq!(
+        rx.recv_timeout(Duration::from_secs(1)),
+        Err(RecvTimeoutError::Disconnected)
+    );
+}
+
+#[test]
+fn subscribe_forever() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    let state = state
+        .subscribe_forever(
+            move |accessor: DataAccessor<_>| {
+                tx.send(accessor.get().unwrap()).unwrap();
+            },
+            SeenChangeStamp::Current,
+        )
+        .unwrap();
+
+    assert!(state.exists().unwrap());
+
+    state.set(&42).unwrap();
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), 42);
+
+    state.delete().unwrap();
+}
+
+#[test]
+fn subscribe_once() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    let subscription = state
+        .subscribe_once(
+            move |accessor: DataAccessor<_>| {
+                tx.send(accessor.get().unwrap()).unwrap();
+                drop(tx);
+            },
+            SeenChangeStamp::Current,
+        )
+        .unwrap();
+
+    assert!(!subscription.is_done());
+
+    state.set(&42).unwrap();
+    state.as_state().set(&43).unwrap();
+
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), 42);
+    assert_eq!(
+        rx.recv_timeout(Duration::from_secs(1)),
+        Err(RecvTimeoutError::Disconnected)
+    );
+    assert!(subscription.is_done());
+}
+
+#[test]
+fn subscribe_once_unsubscribes_after_first_delivery() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    let is_subscribed_to_state = |entry: &ActiveSubscription| entry.state_name() == state.state_name();
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    let subscription = state
+        .subscribe_once(
+            move |accessor: DataAccessor<_>| tx.send(accessor.get().unwrap()).unwrap(),
+            SeenChangeStamp::Current,
+        )
+        .unwrap();
+
+    assert!(wnf::active_subscriptions().iter().any(is_subscribed_to_state));
+
+    state.set(&42).unwrap();
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), 42);
+
+    assert!(!wnf::active_subscriptions().iter().any(is_subscribed_to_state));
+    assert!(subscription.is_done());
+
+    subscription.unsubscribe().unwrap();
+}
+
+#[test]
+fn subscribe_with_snapshot() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    state.set(&0).unwrap();
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    let (snapshot, subscription) = state
+        .subscribe_with_snapshot(move |accessor: DataAccessor<_>| {
+            tx.send(accessor.query().unwrap()).unwrap();
+        })
+        .unwrap();
+
+    assert_eq!(snapshot.into_data_change_stamp(), (0, 1.into()));
+
+    state.set(&1).unwrap();
+
+    let stamped_data = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!(stamped_data.into_data_change_stamp(), (1, 2.into()));
+
+    subscription.unsubscribe().unwrap();
+
+    assert_eq!(
+        rx.recv_timeout(Duration::from_secs(1)),
+        Err(RecvTimeoutError::Disconnected)
+    );
+}
+
+#[test]
+fn subscribe_boxed_with_snapshot() {
+    let state = OwnedState::<[u32]>::create_temporary().unwrap();
+    state.set(&[0, 1]).unwrap();
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    let (snapshot, subscription) = state
+        .subscribe_boxed_with_snapshot(move |accessor: DataAccessor<_>| {
+            tx.send(accessor.query_boxed().unwrap()).unwrap();
+        })
+        .unwrap();
+
+    assert_eq!(snapshot.into_data_change_stamp(), (vec![0, 1].into(), 1.into()));
+
+    state.set(&[2]).unwrap();
+
+    let stamped_data = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!(stamped_data.into_data_change_stamp(), (vec![2].into(), 2.into()));
+
+    subscription.unsubscribe().unwrap();
+}
+
 #[test]
 fn subscribe_opaque_data() {
     let state = OwnedState::<OpaqueData>::create_temporary().unwrap();
@@ -228,3 +566,492 @@ fn subscribe_opaque_data() {
     assert_eq!(data.size(), 2);
     assert_eq!(change_stamp, 2);
 }
+
+#[test]
+fn subscribe_reports_missed_updates() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+
+    for i in 0..3 {
+        state.set(&i).unwrap();
+    }
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    let subscription = state
+        .subscribe(
+            move |accessor: DataAccessor<_>| {
+                tx.send((accessor.change_stamp(), accessor.update_kind())).unwrap();
+            },
+            SeenChangeStamp::Value(1.into()),
+        )
+        .unwrap();
+
+    let (change_stamp, update_kind) = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!(change_stamp, 3);
+    assert_eq!(update_kind, UpdateKind::Coalesced { missed: 1 });
+
+    state.set(&3).unwrap();
+
+    let (change_stamp, update_kind) = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!(change_stamp, 4);
+    assert_eq!(update_kind, UpdateKind::Sequential);
+
+    subscription.unsubscribe().unwrap();
+}
+
+#[test]
+fn subscribe_with_delivery_mode_coalesce_to_latest() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+
+    for i in 0..3 {
+        state.set(&i).unwrap();
+    }
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    let subscription = state
+        .subscribe_with_delivery_mode(
+            move |accessor: DataAccessor<_>| {
+                tx.send((accessor.query().unwrap(), accessor.update_kind())).unwrap();
+            },
+            SeenChangeStamp::Value(1.into()),
+            DeliveryMode::CoalesceToLatest,
+        )
+        .unwrap();
+
+    let (data, update_kind) = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!(data.into_data_change_stamp(), (2, 3.into()));
+    assert_eq!(update_kind, UpdateKind::Coalesced { missed: 1 });
+
+    subscription.unsubscribe().unwrap();
+}
+
+#[test]
+fn subscribe_lock_free() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    state.set(&0).unwrap();
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    let subscription = state
+        .subscribe_lock_free(
+            move |accessor: DataAccessor<_>| {
+                tx.send(accessor.query().unwrap()).unwrap();
+            },
+            SeenChangeStamp::Current,
+        )
+        .unwrap();
+
+    for i in 1..3 {
+        state.set(&i).unwrap();
+
+        let (data, change_stamp) = rx
+            .recv_timeout(Duration::from_secs(1))
+            .unwrap()
+            .into_data_change_stamp();
+
+        assert_eq!(data, i);
+        assert_eq!(change_stamp, i + 1);
+    }
+
+    subscription.unsubscribe().unwrap();
+
+    assert_eq!(
+        rx.recv_timeout(Duration::from_secs(1)),
+        Err(RecvTimeoutError::Disconnected)
+    );
+}
+
+#[test]
+fn subscribe_try_get_slice() {
+    let state = OwnedState::<[u8]>::create_temporary().unwrap();
+    state.set(&[1, 0, 2, 1]).unwrap();
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    let subscription = state
+        .as_state()
+        .cast::<[bool]>()
+        .subscribe(
+            move |accessor: DataAccessor<[bool]>| {
+                tx.send(accessor.try_get_slice().unwrap()).unwrap();
+            },
+            SeenChangeStamp::None,
+        )
+        .unwrap();
+
+    let (valid_prefix, first_invalid_index) = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!(*valid_prefix, [true, false]);
+    assert_eq!(first_invalid_index, Some(2));
+
+    subscription.unsubscribe().unwrap();
+}
+
+#[test]
+fn listener_panic_hook() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    wnf::set_listener_panic_hook(move |panic_info, state_name| {
+        tx.send((panic_info.to_string(), state_name)).unwrap();
+    });
+
+    let subscription = state
+        .subscribe(
+            |_: DataAccessor<_>| panic!("listener panicked"),
+            SeenChangeStamp::Current,
+        )
+        .unwrap();
+
+    state.set(&42).unwrap();
+
+    let (message, state_name) = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert!(message.contains("listener panicked"));
+    assert_eq!(state_name, state.state_name());
+
+    subscription.unsubscribe().unwrap();
+}
+
+#[test]
+fn subscribe_owned() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    let subscription = state
+        .subscribe_owned(
+            move |result| tx.send(result.unwrap()).unwrap(),
+            SeenChangeStamp::Current,
+        )
+        .unwrap();
+
+    state.set(&42).unwrap();
+
+    let (data, change_stamp) = rx
+        .recv_timeout(Duration::from_secs(1))
+        .unwrap()
+        .into_data_change_stamp();
+
+    assert_eq!(data, 42);
+    assert_eq!(change_stamp, 1);
+
+    subscription.unsubscribe().unwrap();
+}
+
+#[test]
+fn subscribe_owned_boxed() {
+    let state = OwnedState::<[u32]>::create_temporary().unwrap();
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    let subscription = state
+        .subscribe_owned_boxed(
+            move |result| tx.send(result.unwrap()).unwrap(),
+            SeenChangeStamp::Current,
+        )
+        .unwrap();
+
+    state.set(&[1, 2, 3]).unwrap();
+
+    let (data, change_stamp) = rx
+        .recv_timeout(Duration::from_secs(1))
+        .unwrap()
+        .into_data_change_stamp();
+
+    assert_eq!(*data, [1, 2, 3]);
+    assert_eq!(change_stamp, 1);
+
+    subscription.unsubscribe().unwrap();
+}
+
+#[test]
+fn gc_failed_unsubscriptions() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+
+    let subscription = state
+        .subscribe(|_: DataAccessor<_>| {}, SeenChangeStamp::Current)
+        .unwrap();
+
+    subscription.unsubscribe().unwrap();
+
+    let stats = wnf::gc_failed_unsubscriptions();
+    assert_eq!(stats.pending(), 0);
+    assert_eq!(stats, wnf::failed_unsubscription_stats());
+}
+
+#[test]
+fn subscribe_group() {
+    let state1 = OwnedState::<u32>::create_temporary().unwrap();
+    let state2 = OwnedState::<u16>::create_temporary().unwrap();
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    let subscription = wnf::subscribe_group(
+        [state1.as_state().cast::<OpaqueData>(), state2.as_state().cast()],
+        move |state_name: StateName, accessor: DataAccessor<OpaqueData>| {
+            tx.send((state_name, accessor.get().unwrap().size())).unwrap();
+        },
+        SeenChangeStamp::None,
+    )
+    .unwrap();
+
+    assert_eq!(subscription.len(), 2);
+
+    state1.set(&1).unwrap();
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)), Ok((state1.state_name(), 4)));
+
+    state2.set(&2).unwrap();
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)), Ok((state2.state_name(), 2)));
+
+    subscription.unsubscribe().unwrap();
+
+    assert_eq!(
+        rx.recv_timeout(Duration::from_secs(1)),
+        Err(RecvTimeoutError::Disconnected)
+    );
+}
+
+#[test]
+fn active_subscriptions() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    let is_subscribed_to_state =
+        |active_subscription: &wnf::ActiveSubscription| active_subscription.state_name() == state.state_name();
+
+    let subscription1 = state.subscribe(|_: DataAccessor<_>| {}, SeenChangeStamp::None).unwrap();
+    let subscription2 = state.subscribe(|_: DataAccessor<_>| {}, SeenChangeStamp::None).unwrap();
+
+    let active_subscriptions: Vec<_> = wnf::active_subscriptions()
+        .into_iter()
+        .filter(is_subscribed_to_state)
+        .collect();
+
+    assert_eq!(active_subscriptions.len(), 2);
+    assert!(active_subscriptions[0].id() < active_subscriptions[1].id());
+    assert!(active_subscriptions[0]
+        .listener_type_name()
+        .contains("active_subscriptions"));
+
+    subscription1.unsubscribe().unwrap();
+    assert_eq!(
+        wnf::active_subscriptions()
+            .iter()
+            .filter(|active_subscription| is_subscribed_to_state(active_subscription))
+            .count(),
+        1
+    );
+
+    drop(subscription2);
+    assert!(!wnf::active_subscriptions().iter().any(is_subscribed_to_state));
+}
+
+#[test]
+fn subscribe_reattaching_detects_deletion() {
+    let state = OwnedState::<u32>::create_temporary().unwrap().leak();
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    let subscription = state
+        .subscribe_reattaching(
+            move |event: ReattachEvent<u32>| {
+                let event = match event {
+                    ReattachEvent::Updated(accessor) => Some(accessor.get().unwrap()),
+                    ReattachEvent::Detached | ReattachEvent::Reattached => None,
+                };
+
+                tx.send(event).unwrap();
+            },
+            SeenChangeStamp::Current,
+            ReattachPolicy::new().check_interval(Duration::from_millis(10)),
+        )
+        .unwrap();
+
+    state.set(&42).unwrap();
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)), Ok(Some(42)));
+
+    state.delete().unwrap();
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)), Ok(None));
+
+    drop(subscription);
+
+    assert_eq!(
+        rx.recv_timeout(Duration::from_secs(1)),
+        Err(RecvTimeoutError::Disconnected)
+    );
+}
+
+#[test]
+fn subscribe_arc() {
+    let state = Arc::new(OwnedState::<u32>::create_temporary().unwrap());
+    let state_name = state.state_name();
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    let subscription = Arc::clone(&state)
+        .subscribe_arc(
+            move |accessor: DataAccessor<_>| {
+                tx.send(accessor.get().unwrap()).unwrap();
+            },
+            SeenChangeStamp::Current,
+        )
+        .unwrap();
+
+    drop(state);
+
+    subscription.state().set(&42).unwrap();
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)), Ok(42));
+
+    let state = subscription.unsubscribe().unwrap();
+    assert_eq!(state.state_name(), state_name);
+
+    assert_eq!(
+        rx.recv_timeout(Duration::from_secs(1)),
+        Err(RecvTimeoutError::Disconnected)
+    );
+}
+
+#[test]
+fn subscribe_owning_boxed() {
+    let state = Box::new(OwnedState::<u32>::create_temporary().unwrap());
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    let subscription = state
+        .subscribe_owning(
+            move |accessor: DataAccessor<_>| {
+                tx.send(accessor.get().unwrap()).unwrap();
+            },
+            SeenChangeStamp::Current,
+        )
+        .unwrap();
+
+    let subscription = std::thread::spawn(move || {
+        subscription.state().set(&42).unwrap();
+        subscription
+    })
+    .join()
+    .unwrap();
+
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)), Ok(42));
+
+    subscription.unsubscribe().unwrap();
+}
+
+#[test]
+fn subscribe_debounced() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    state.set(&0).unwrap();
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    let subscription = state
+        .subscribe_debounced(
+            move |result: io::Result<StampedData<u32>>| tx.send(result.unwrap().into_data()).unwrap(),
+            Duration::from_millis(500),
+        )
+        .unwrap();
+
+    assert_eq!(subscription.window(), Duration::from_millis(500));
+
+    for value in 1..=10 {
+        state.set(&value).unwrap();
+    }
+
+    let mut values = Vec::new();
+
+    while values.last() != Some(&10) {
+        values.push(rx.recv_timeout(Duration::from_secs(5)).unwrap());
+    }
+
+    assert!(values.len() <= 2);
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)), Err(RecvTimeoutError::Timeout));
+}
+
+#[test]
+fn subscribe_debounced_boxed() {
+    let state = OwnedState::<[u32]>::create_temporary().unwrap();
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    let _subscription = state
+        .as_state()
+        .subscribe_debounced_boxed(
+            move |result: io::Result<StampedData<Box<[u32]>>>| tx.send(result.unwrap().into_data()).unwrap(),
+            Duration::from_millis(10),
+        )
+        .unwrap();
+
+    state.set(&[1, 2, 3]).unwrap();
+
+    assert_eq!(*rx.recv_timeout(Duration::from_secs(5)).unwrap(), [1, 2, 3]);
+}
+
+#[test]
+fn subscription_stats() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    state.set(&0).unwrap();
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    let subscription = state
+        .subscribe(
+            move |accessor: DataAccessor<_>| {
+                let _ = accessor.get();
+                tx.send(()).unwrap();
+            },
+            SeenChangeStamp::Current,
+        )
+        .unwrap();
+
+    let stats = subscription.stats();
+    assert_eq!(stats.delivered(), 0);
+    assert_eq!(stats.last_seen_change_stamp(), Some(1.into()));
+    assert_eq!(stats.last_delivery_time(), None);
+
+    state.set(&42).unwrap();
+    rx.recv_timeout(Duration::from_secs(1)).unwrap();
+
+    state.as_state().cast::<[u8]>().set(&[0xFF]).unwrap();
+    rx.recv_timeout(Duration::from_secs(1)).unwrap();
+
+    let stats = subscription.stats();
+    assert_eq!(stats.delivered(), 2);
+    assert_eq!(stats.errors(), 0);
+    assert_eq!(stats.decode_failures(), 1);
+    assert_eq!(stats.last_seen_change_stamp(), Some(3.into()));
+    assert!(stats.last_delivery_time().is_some());
+
+    subscription.unsubscribe().unwrap();
+}
+
+#[test]
+fn subscription_into_and_from_raw_handle() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+
+    type Listener = Box<dyn FnMut(DataAccessor<'_, u32>) + Send>;
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    let listener: Listener = Box::new(move |accessor| {
+        tx.send(accessor.get().unwrap()).unwrap();
+    });
+
+    let subscription = state.subscribe(listener, SeenChangeStamp::Current).unwrap();
+    let handle = subscription.into_raw_handle();
+    assert!(!handle.is_null());
+
+    state.set(&42).unwrap();
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), 42);
+
+    // SAFETY:
+    // `handle` was returned from `into_raw_handle` on a subscription with the same listener type and the subscription
+    // does not outlive `state`
+    let subscription = unsafe { Subscription::<'_, Listener>::from_raw_handle(handle) };
+    subscription.unsubscribe().unwrap();
+
+    // The listener has been dropped, so the channel is disconnected
+    assert_eq!(
+        rx.recv_timeout(Duration::from_secs(1)),
+        Err(RecvTimeoutError::Disconnected)
+    );
+}
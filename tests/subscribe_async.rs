@@ -0,0 +1,71 @@
+use std::time::Duration;
+
+use tokio::runtime::Handle;
+use tokio::time;
+use wnf::{OwnedState, SeenChangeStamp};
+
+#[tokio::test]
+async fn subscribe_async() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+
+    let (tx, rx) = async_channel::unbounded();
+
+    let subscription = state
+        .subscribe_async(
+            Handle::current(),
+            move |result| {
+                let tx = tx.clone();
+                async move {
+                    tx.send(result.unwrap()).await.unwrap();
+                }
+            },
+            SeenChangeStamp::Current,
+        )
+        .unwrap();
+
+    state.set(&42).unwrap();
+
+    let (data, change_stamp) = time::timeout(Duration::from_secs(1), rx.recv())
+        .await
+        .unwrap()
+        .unwrap()
+        .into_data_change_stamp();
+
+    assert_eq!(data, 42);
+    assert_eq!(change_stamp, 1);
+
+    subscription.unsubscribe().unwrap();
+}
+
+#[tokio::test]
+async fn subscribe_boxed_async() {
+    let state = OwnedState::<[u32]>::create_temporary().unwrap();
+
+    let (tx, rx) = async_channel::unbounded();
+
+    let subscription = state
+        .subscribe_boxed_async(
+            Handle::current(),
+            move |result| {
+                let tx = tx.clone();
+                async move {
+                    tx.send(result.unwrap()).await.unwrap();
+                }
+            },
+            SeenChangeStamp::Current,
+        )
+        .unwrap();
+
+    state.set(&[1, 2, 3]).unwrap();
+
+    let (data, change_stamp) = time::timeout(Duration::from_secs(1), rx.recv())
+        .await
+        .unwrap()
+        .unwrap()
+        .into_data_change_stamp();
+
+    assert_eq!(*data, [1, 2, 3]);
+    assert_eq!(change_stamp, 1);
+
+    subscription.unsubscribe().unwrap();
+}
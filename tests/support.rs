@@ -0,0 +1,9 @@
+#[test]
+fn probe_support() {
+    let support_info = wnf::probe_support();
+
+    assert_eq!(support_info.missing_exports(), &[] as &[&str]);
+    assert!(support_info.state_error().is_none());
+    assert!(support_info.supports_states());
+    assert!(support_info.supports_subscriptions());
+}
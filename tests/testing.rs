@@ -0,0 +1,37 @@
+use proptest::test_runner::TestRunner;
+use wnf::testing::{any_data, any_slice_data, check_round_trip, check_round_trip_boxed, temporary_state};
+use wnf::BorrowedState;
+
+#[test]
+fn temporary_state_is_deleted_on_panic() {
+    let state = temporary_state::<u32>();
+    let state_name = state.state_name();
+
+    let result = std::panic::catch_unwind(move || {
+        let _state = state;
+        panic!("test panic");
+    });
+
+    assert!(result.is_err());
+    assert!(!BorrowedState::<u32>::from_state_name(state_name).exists().unwrap());
+}
+
+#[test]
+fn round_trip() {
+    let state = temporary_state::<u64>();
+
+    TestRunner::default()
+        .run(&any_data::<u64>(), |data| check_round_trip(&state, &data))
+        .unwrap();
+}
+
+#[test]
+fn round_trip_boxed() {
+    let state = temporary_state::<[u16]>();
+
+    TestRunner::default()
+        .run(&any_slice_data::<u16>(0..64), |data| {
+            check_round_trip_boxed(&state, &data)
+        })
+        .unwrap();
+}
@@ -1,4 +1,9 @@
-use wnf::{ChangeStamp, OwnedState};
+use std::io::ErrorKind;
+
+use wnf::{
+    AsState, ChangeStamp, CreatableStateLifetime, DataScope, OpaqueData, OwnedState, SizeMismatchError, StateCreation,
+    UpdateOutcome, MAXIMUM_STATE_SIZE,
+};
 
 #[test]
 fn set() {
@@ -47,3 +52,130 @@ fn update() {
     assert_eq!(read_value, 0x22222222);
     assert_eq!(change_stamp, 2);
 }
+
+#[test]
+fn pulse() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    state.set(&42).unwrap();
+
+    state.pulse().unwrap();
+
+    let (data, change_stamp) = state
+        .as_state()
+        .cast::<OpaqueData>()
+        .query()
+        .unwrap()
+        .into_data_change_stamp();
+    assert_eq!(data.size(), 0);
+    assert_eq!(change_stamp, 2);
+    assert_eq!(state.get().unwrap_err().kind(), ErrorKind::InvalidData);
+}
+
+#[test]
+fn set_checked() {
+    let state = OwnedState::<[u32]>::create_temporary().unwrap();
+
+    state.set_checked(&[1, 2], 0).unwrap();
+    assert_eq!(*state.get_boxed().unwrap(), [1, 2]);
+
+    state.as_state().set_checked(&[3, 4, 5], 8).unwrap();
+    assert_eq!(*state.get_boxed().unwrap(), [3, 4, 5]);
+    assert_eq!(state.change_stamp().unwrap(), 2);
+}
+
+#[test]
+fn set_checked_size_mismatch() {
+    let state = OwnedState::<[u32]>::create_temporary().unwrap();
+    state.set(&[1, 2]).unwrap();
+
+    let err = state.set_checked(&[3, 4, 5], 12).unwrap_err();
+
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert_eq!(
+        err.get_ref().unwrap().downcast_ref::<SizeMismatchError>(),
+        Some(&SizeMismatchError {
+            expected: 12,
+            actual: 8
+        })
+    );
+    assert_eq!(*state.get_boxed().unwrap(), [1, 2]);
+    assert_eq!(state.change_stamp().unwrap(), 1);
+}
+
+#[test]
+fn update_all_or_nothing() {
+    let state_a = OwnedState::<u32>::create_temporary().unwrap();
+    let state_b = OwnedState::<u16>::create_temporary().unwrap();
+
+    wnf::update_all_or_nothing(&[
+        (
+            state_a.as_state().cast::<OpaqueData>(),
+            &0x12345678_u32.to_ne_bytes()[..],
+        ),
+        (state_b.as_state().cast(), &0x1234_u16.to_ne_bytes()[..]),
+    ])
+    .unwrap();
+
+    assert_eq!(state_a.get().unwrap(), 0x12345678);
+    assert_eq!(state_b.get().unwrap(), 0x1234);
+}
+
+#[test]
+fn update_all_or_nothing_rolls_back_on_failure() {
+    let state_a = OwnedState::<[u8]>::create_temporary().unwrap();
+    state_a.set(&[1]).unwrap();
+
+    let state_b = StateCreation::new()
+        .lifetime(CreatableStateLifetime::Temporary)
+        .scope(DataScope::Machine)
+        .maximum_state_size(1)
+        .create_owned::<[u8]>()
+        .unwrap();
+
+    let err = wnf::update_all_or_nothing(&[(&state_a, &[2][..]), (&state_b, &[1, 2][..])]).unwrap_err();
+
+    assert_eq!(err.failed_index(), 1);
+    assert!(matches!(err.outcomes()[0], UpdateOutcome::RolledBack));
+    assert!(matches!(err.outcomes()[1], UpdateOutcome::Failed(..)));
+    assert!(err.is_rolled_back());
+
+    assert_eq!(*state_a.get_boxed().unwrap(), [1]);
+    assert_eq!(state_a.change_stamp().unwrap(), 3);
+}
+
+#[test]
+fn set_secret() {
+    let state = OwnedState::<[u8]>::create_temporary().unwrap();
+    let mut secret = *b"secret";
+
+    state.set_secret(&mut secret).unwrap();
+
+    assert_eq!(secret, [0; 6]);
+    assert_eq!(*state.get_boxed().unwrap(), *b"secret");
+}
+
+#[test]
+fn set_with() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+
+    state
+        .set_with(|buffer| {
+            buffer[..4].copy_from_slice(&0x12345678_u32.to_ne_bytes());
+            4
+        })
+        .unwrap();
+
+    let (read_value, change_stamp) = state.query().unwrap().into_data_change_stamp();
+    assert_eq!(read_value, 0x12345678);
+    assert_eq!(change_stamp, 1);
+}
+
+#[test]
+fn set_with_size_too_large() {
+    let state = OwnedState::<[u8]>::create_temporary().unwrap();
+
+    let err = state.as_state().set_with(|_| MAXIMUM_STATE_SIZE + 1).unwrap_err();
+
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    assert_eq!(state.change_stamp().unwrap(), 0);
+}
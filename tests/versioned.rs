@@ -0,0 +1,101 @@
+use std::io::ErrorKind;
+
+use wnf::{OwnedState, ReadError, VersionedState};
+
+#[test]
+fn versioned_state_set_get() {
+    let state = OwnedState::<[u8]>::create_temporary().unwrap();
+    let versioned_state = VersionedState::<u32, 1>::new(&state);
+
+    versioned_state.set(&42).unwrap();
+
+    assert_eq!(versioned_state.get().unwrap(), 42);
+    assert_eq!(versioned_state.stored_version().unwrap(), 1);
+    assert_eq!(*state.get_boxed().unwrap(), [1, 0, 0, 0, 42, 0, 0, 0]);
+}
+
+#[test]
+fn versioned_state_set_get_boxed_slice() {
+    let state = OwnedState::<[u8]>::create_temporary().unwrap();
+    let versioned_state = VersionedState::<[u16], 1>::new(&state);
+
+    versioned_state.set(&[1, 2, 3]).unwrap();
+
+    assert_eq!(*versioned_state.get_boxed().unwrap(), [1, 2, 3]);
+}
+
+#[test]
+fn versioned_state_get_unexpected_version() {
+    let state = OwnedState::<[u8]>::create_temporary().unwrap();
+    VersionedState::<u32, 1>::new(&state).set(&42).unwrap();
+
+    let err = VersionedState::<u32, 2>::new(&state).get().unwrap_err();
+
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert_eq!(
+        err.get_ref().unwrap().downcast_ref::<ReadError>(),
+        Some(&ReadError::UnexpectedVersion { expected: 2, actual: 1 })
+    );
+}
+
+#[test]
+fn versioned_state_get_missing_version() {
+    let state = OwnedState::<[u8]>::create_temporary().unwrap();
+    state.set(&[1, 2]).unwrap();
+
+    let err = VersionedState::<u32, 1>::new(&state).get().unwrap_err();
+
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert_eq!(
+        err.get_ref().unwrap().downcast_ref::<ReadError>(),
+        Some(&ReadError::MissingVersion { actual: 2 })
+    );
+}
+
+#[test]
+fn versioned_state_get_or_migrate() {
+    let state = OwnedState::<[u8]>::create_temporary().unwrap();
+    VersionedState::<u16, 1>::new(&state).set(&42).unwrap();
+
+    let versioned_state = VersionedState::<u32, 2>::new(&state);
+
+    let data = versioned_state
+        .get_or_migrate(|bytes, version| {
+            assert_eq!(version, 1);
+            Some(u16::from_le_bytes(bytes.try_into().unwrap()).into())
+        })
+        .unwrap();
+
+    assert_eq!(data, 42);
+
+    // The migrated data are not written back
+    assert_eq!(versioned_state.stored_version().unwrap(), 1);
+}
+
+#[test]
+fn versioned_state_get_or_migrate_same_version() {
+    let state = OwnedState::<[u8]>::create_temporary().unwrap();
+    let versioned_state = VersionedState::<u32, 1>::new(&state);
+    versioned_state.set(&42).unwrap();
+
+    let data = versioned_state
+        .get_or_migrate(|_, _| panic!("migration should not be called"))
+        .unwrap();
+
+    assert_eq!(data, 42);
+}
+
+#[test]
+fn versioned_state_get_or_migrate_fails() {
+    let state = OwnedState::<[u8]>::create_temporary().unwrap();
+    VersionedState::<u32, 1>::new(&state).set(&42).unwrap();
+
+    let err = VersionedState::<u32, 3>::new(&state)
+        .get_or_migrate(|_, _| None)
+        .unwrap_err();
+
+    assert_eq!(
+        err.get_ref().unwrap().downcast_ref::<ReadError>(),
+        Some(&ReadError::UnexpectedVersion { expected: 3, actual: 1 })
+    );
+}
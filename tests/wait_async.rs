@@ -103,3 +103,63 @@ async fn wait_until_boxed_async() {
 
     handle.await.unwrap();
 }
+
+#[tokio::test]
+async fn wait_until_async_coalesces_updates() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    state.set(&0).unwrap();
+
+    let mut future = state.wait_until_async(|value| *value > 0);
+    assert!(time::timeout(Duration::from_millis(100), &mut future).await.is_err());
+
+    state.set(&1).unwrap();
+    state.set(&2).unwrap();
+    time::sleep(Duration::from_millis(300)).await;
+
+    let value = time::timeout(Duration::from_secs(1), future).await.unwrap().unwrap();
+    assert_eq!(value, 2);
+}
+
+#[tokio::test]
+async fn wait_until_async_check_every_update() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+    state.set(&0).unwrap();
+
+    let mut future = state.wait_until_async(|value| *value > 0).check_every_update();
+    assert!(time::timeout(Duration::from_millis(100), &mut future).await.is_err());
+
+    state.set(&1).unwrap();
+    state.set(&2).unwrap();
+    time::sleep(Duration::from_millis(300)).await;
+
+    let value = time::timeout(Duration::from_secs(1), future).await.unwrap().unwrap();
+    assert_eq!(value, 1);
+}
+
+#[tokio::test]
+async fn wait_until_exists_async_existing() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+
+    time::timeout(Duration::from_secs(1), state.wait_until_exists_async())
+        .await
+        .unwrap()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn wait_until_deleted_async() {
+    let state = OwnedState::<u32>::create_temporary().unwrap().leak();
+
+    let handle = tokio::spawn(async move {
+        time::sleep(Duration::from_millis(300)).await;
+        state.delete().unwrap();
+    });
+
+    time::timeout(Duration::from_secs(3), state.wait_until_deleted_async())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!state.exists().unwrap());
+
+    handle.await.unwrap();
+}
@@ -3,7 +3,7 @@ use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 
-use wnf::OwnedState;
+use wnf::{AsState, OwnedState, WaitNotification};
 
 #[test]
 fn wait_blocking() {
@@ -126,3 +126,104 @@ fn wait_until_boxed_blocking_timeout() {
     assert!(result.is_err());
     assert_eq!(result.unwrap_err().kind(), ErrorKind::TimedOut);
 }
+
+#[test]
+fn wait_until_exists_blocking_existing() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+
+    state.wait_until_exists_blocking(Duration::from_secs(1)).unwrap();
+}
+
+#[test]
+fn wait_until_deleted_blocking() {
+    let state = OwnedState::<u32>::create_temporary().unwrap().leak();
+
+    let handle = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(300));
+        state.delete().unwrap();
+    });
+
+    state.wait_until_deleted_blocking(Duration::from_secs(3)).unwrap();
+    assert!(!state.exists().unwrap());
+
+    handle.join().unwrap();
+}
+
+#[test]
+fn wait_until_deleted_blocking_timeout() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+
+    let result = state.wait_until_deleted_blocking(Duration::from_millis(300));
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::TimedOut);
+}
+
+#[test]
+fn wait_event_blocking() {
+    for notification in [
+        WaitNotification::Event,
+        WaitNotification::Poll(Duration::from_millis(10)),
+    ] {
+        let state = Arc::new(OwnedState::<u32>::create_temporary().unwrap());
+
+        let handle = {
+            let state = Arc::clone(&state);
+            thread::spawn(move || state.wait_event_blocking(Duration::from_secs(3), notification))
+        };
+
+        thread::sleep(Duration::from_millis(300));
+        state.set(&42).unwrap();
+
+        handle.join().unwrap().unwrap();
+    }
+}
+
+#[test]
+fn wait_event_blocking_timeout() {
+    let state = OwnedState::<u32>::create_temporary().unwrap();
+
+    let result = state.wait_event_blocking(Duration::from_millis(50), WaitNotification::Event);
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::TimedOut);
+}
+
+#[test]
+fn wait_until_event_blocking() {
+    for notification in [
+        WaitNotification::Event,
+        WaitNotification::Poll(Duration::from_millis(10)),
+    ] {
+        let state = Arc::new(OwnedState::<u32>::create_temporary().unwrap());
+        state.set(&0).unwrap();
+
+        let handle = {
+            let state = Arc::clone(&state);
+
+            thread::spawn(move || {
+                state.wait_until_event_blocking(|value| *value > 42, Duration::from_secs(3), notification)
+            })
+        };
+
+        thread::sleep(Duration::from_millis(300));
+        state.set(&42).unwrap();
+        thread::sleep(Duration::from_millis(300));
+        state.set(&43).unwrap();
+
+        assert_eq!(handle.join().unwrap().unwrap(), 43);
+    }
+}
+
+#[test]
+fn wait_until_boxed_event_blocking_already_satisfied() {
+    let state = OwnedState::<[u32]>::create_temporary().unwrap();
+    state.set(&[1, 2]).unwrap();
+
+    let slice = state
+        .as_state()
+        .wait_until_boxed_event_blocking(|slice| slice.len() == 2, Duration::ZERO, WaitNotification::Event)
+        .unwrap();
+
+    assert_eq!(*slice, [1, 2]);
+}
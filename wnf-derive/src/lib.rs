@@ -0,0 +1,121 @@
+//! Derive macros for the [`wnf`](https://docs.rs/wnf/latest/wnf) crate
+//!
+//! This crate is not meant to be used directly. Enable the `derive` feature of the `wnf` crate instead, which
+//! re-exports the macros from this crate.
+
+#![deny(missing_docs)]
+#![deny(rustdoc::bare_urls)]
+#![deny(rustdoc::broken_intra_doc_links)]
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields};
+
+/// Derives the `AnyBitPattern` and `NoUninit` traits of the `wnf` crate for a plain struct
+///
+/// This makes it possible to both query and update states with data of the struct type without implementing these
+/// unsafe traits by hand and without depending on the [`bytemuck`](https://docs.rs/bytemuck/1/bytemuck) or
+/// [`zerocopy`](https://docs.rs/zerocopy/0/zerocopy) crates.
+///
+/// The macro can only be applied to a struct that
+/// - has no generic parameters
+/// - is annotated with `#[repr(C)]` or `#[repr(transparent)]`
+/// - implements `Copy`
+/// - has only fields whose types implement both `AnyBitPattern` and `NoUninit`
+/// - contains no padding bytes, i.e. its size is the sum of the sizes of its fields
+///
+/// The first two requirements are checked when the macro is expanded, the other ones are checked by the compiler
+/// through trait bounds and constant assertions. If any of them is violated, compilation fails.
+#[proc_macro_derive(WnfStateData)]
+pub fn derive_wnf_state_data(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    derive_wnf_state_data_impl(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// Expands the `WnfStateData` derive macro for the given input
+fn derive_wnf_state_data_impl(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let ident = &input.ident;
+
+    if !input.generics.params.is_empty() {
+        return Err(Error::new(
+            input.generics.span(),
+            "`WnfStateData` cannot be derived for types with generic parameters",
+        ));
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return Err(Error::new(
+                Span::call_site(),
+                "`WnfStateData` can only be derived for structs",
+            ))
+        }
+    };
+
+    ensure_repr_c_or_transparent(input)?;
+
+    let field_types: Vec<_> = match fields {
+        Fields::Named(fields) => fields.named.iter().map(|field| &field.ty).collect(),
+        Fields::Unnamed(fields) => fields.unnamed.iter().map(|field| &field.ty).collect(),
+        Fields::Unit => Vec::new(),
+    };
+
+    // Implementing the traits is sound because
+    // - the type has a defined layout because it is `#[repr(C)]` or `#[repr(transparent)]`
+    // - the type contains no padding bytes by the size assertion
+    // - any bit pattern is valid for each field type and no field contains uninitialized bytes because the field types
+    //   implement `AnyBitPattern` and `NoUninit`
+    let padding_message = format!("`{ident}` must not contain padding bytes in order to derive `WnfStateData`");
+
+    Ok(quote! {
+        const _: () = {
+            const _: fn() = || {
+                fn assert_any_bit_pattern_and_no_uninit<T: ::wnf::AnyBitPattern + ::wnf::NoUninit>() {}
+
+                #(assert_any_bit_pattern_and_no_uninit::<#field_types>();)*
+            };
+
+            assert!(
+                ::core::mem::size_of::<#ident>() == 0 #(+ ::core::mem::size_of::<#field_types>())*,
+                #padding_message,
+            );
+
+            unsafe impl ::wnf::AnyBitPattern for #ident {}
+
+            unsafe impl ::wnf::NoUninit for #ident {}
+        };
+    })
+}
+
+/// Returns an error if the given input is not annotated with `#[repr(C)]` or `#[repr(transparent)]`
+fn ensure_repr_c_or_transparent(input: &DeriveInput) -> syn::Result<()> {
+    let mut has_repr_c_or_transparent = false;
+
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("repr")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("C") || meta.path.is_ident("transparent") {
+                has_repr_c_or_transparent = true;
+            } else if meta.input.peek(syn::token::Paren) {
+                // Skip the arguments of modifiers such as `align(N)` or `packed(N)`
+                let _ = meta.input.parse::<proc_macro2::Group>()?;
+            }
+
+            Ok(())
+        })?;
+    }
+
+    if has_repr_c_or_transparent {
+        Ok(())
+    } else {
+        Err(Error::new(
+            input.ident.span(),
+            "`WnfStateData` can only be derived for types with `#[repr(C)]` or `#[repr(transparent)]`",
+        ))
+    }
+}